@@ -0,0 +1,73 @@
+//! Compares decoding frames with `Frame::check` followed by `Frame::parse_bytes`
+//! against the single pass `Frame::decode`.
+//!
+//! Run with `cargo bench --bench frame`.
+
+use bytes::{Bytes, BytesMut};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use mini_redis::frame::Limits;
+use mini_redis::Frame;
+use std::io::Cursor;
+
+/// Encoded request frames of increasing size.
+fn inputs() -> Vec<(&'static str, Vec<u8>)> {
+    let mut set_large = b"*3\r\n$3\r\nSET\r\n$3\r\nkey\r\n$16384\r\n".to_vec();
+    set_large.extend_from_slice(&[b'x'; 16384]);
+    set_large.extend_from_slice(b"\r\n");
+
+    let mut mset = b"*201\r\n$4\r\nMSET\r\n".to_vec();
+    for i in 0..100 {
+        let key = format!("key:{}", i);
+        let value = format!("value:{}", i);
+        mset.extend_from_slice(format!("${}\r\n{}\r\n", key.len(), key).as_bytes());
+        mset.extend_from_slice(format!("${}\r\n{}\r\n", value.len(), value).as_bytes());
+    }
+
+    vec![
+        ("get", b"*2\r\n$3\r\nGET\r\n$5\r\nhello\r\n".to_vec()),
+        ("set_16k", set_large),
+        ("mset_100", mset),
+        ("inline", b"SET hello world\r\n".to_vec()),
+    ]
+}
+
+fn check_then_parse(src: &mut BytesMut, limits: &Limits) -> Option<Frame> {
+    let mut buf = Cursor::new(&src[..]);
+    Frame::check_with_limits(&mut buf, limits).ok()?;
+
+    let len = buf.position() as usize;
+    let data: Bytes = src.split_to(len).freeze();
+    Frame::parse_bytes(&data).ok()
+}
+
+fn decode(c: &mut Criterion) {
+    let limits = Limits::default();
+    let mut group = c.benchmark_group("decode");
+
+    for (name, input) in inputs() {
+        group.throughput(Throughput::Bytes(input.len() as u64));
+
+        group.bench_with_input(
+            BenchmarkId::new("check_then_parse", name),
+            &input,
+            |b, input| {
+                b.iter(|| {
+                    let mut src = BytesMut::from(&input[..]);
+                    black_box(check_then_parse(&mut src, &limits).unwrap())
+                })
+            },
+        );
+
+        group.bench_with_input(BenchmarkId::new("single_pass", name), &input, |b, input| {
+            b.iter(|| {
+                let mut src = BytesMut::from(&input[..]);
+                black_box(Frame::decode(&mut src, &limits).unwrap().unwrap())
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, decode);
+criterion_main!(benches);
@@ -0,0 +1,286 @@
+//! 最小化的访问控制列表（ACL）。
+//!
+//! 服务器可以通过 `server::Config::acl` 配置一组具名用户，每个用户只能执行允许的命令、只能访问匹配给定模式的键。
+//! 连接通过 `AUTH` 命令切换用户，通过 `ACL WHOAMI` 和 `ACL LIST` 查看当前用户和所有用户。
+//!
+//! 与 Redis 一样，新连接以名为 `default` 的用户身份开始。默认配置中 `default` 用户没有密码，
+//! 可以执行所有命令、访问所有键。如果配置的 `Acl` 中没有 `default` 用户，连接必须先通过 `AUTH` 认证。
+
+use crate::frame::ErrorKind;
+use crate::{Command, Frame};
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::sync::Arc;
+
+/// 新连接默认使用的用户名。
+pub const DEFAULT_USER: &str = "default";
+
+/// 一个 ACL 用户。
+///
+/// 新建的用户没有密码，不能执行任何命令，也不能访问任何键。通过构建器方法授予权限。
+///
+/// # 示例
+///
+/// ```
+/// use mini_redis::acl::User;
+///
+/// // 只能读写 `app:` 开头的键。
+/// let user = User::new("app")
+///     .password("secret")
+///     .command("get")
+///     .command("set")
+///     .key_pattern("app:*");
+///
+/// assert!(user.can_run("GET"));
+/// assert!(!user.can_run("del"));
+/// assert!(user.can_access(b"app:1"));
+/// assert!(!user.can_access(b"other"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct User {
+    /// 用户名
+    name: String,
+
+    /// 密码。`None` 表示任意密码都可以通过认证。
+    password: Option<String>,
+
+    /// 允许执行的命令，均为小写。`None` 表示允许执行所有命令。
+    commands: Option<BTreeSet<String>>,
+
+    /// 允许访问的键的模式。
+    key_patterns: Vec<String>,
+}
+
+impl User {
+    /// 创建一个没有任何权限的用户。
+    pub fn new(name: impl Into<String>) -> User {
+        User {
+            name: name.into(),
+            password: None,
+            commands: Some(BTreeSet::new()),
+            key_patterns: vec![],
+        }
+    }
+
+    /// 设置用户的密码。没有设置密码的用户可以使用任意密码认证。
+    pub fn password(mut self, password: impl Into<String>) -> User {
+        self.password = Some(password.into());
+        self
+    }
+
+    /// 允许用户执行名为 `name` 的命令，名称不区分大小写。
+    pub fn command(mut self, name: &str) -> User {
+        if let Some(commands) = &mut self.commands {
+            commands.insert(name.to_lowercase());
+        }
+        self
+    }
+
+    /// 允许用户执行所有命令。
+    pub fn all_commands(mut self) -> User {
+        self.commands = None;
+        self
+    }
+
+    /// 允许用户访问匹配 `pattern` 的键。
+    ///
+    /// 模式中 `*` 匹配任意数量的字节，`?` 匹配单个字节，其他字节按原样匹配。
+    pub fn key_pattern(mut self, pattern: impl Into<String>) -> User {
+        self.key_patterns.push(pattern.into());
+        self
+    }
+
+    /// 允许用户访问所有键，等同于 `key_pattern("*")`。
+    pub fn all_keys(self) -> User {
+        self.key_pattern("*")
+    }
+
+    /// 返回用户名。
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// 用户是否可以执行名为 `name` 的命令。
+    pub fn can_run(&self, name: &str) -> bool {
+        match &self.commands {
+            Some(commands) => commands.contains(&name.to_lowercase()),
+            None => true,
+        }
+    }
+
+    /// 用户是否可以访问 `key`。
+    pub fn can_access(&self, key: &[u8]) -> bool {
+        self.key_patterns
+            .iter()
+            .any(|pattern| glob_match(pattern.as_bytes(), key))
+    }
+
+    /// 检查密码是否正确。
+    fn check_password(&self, password: &str) -> bool {
+        match &self.password {
+            Some(expected) => expected == password,
+            None => true,
+        }
+    }
+}
+
+/// 以 `ACL LIST` 的格式描述用户，例如 `user app on ~app:* +get +set`。
+///
+/// 密码不会被显示，没有密码的用户显示为 `nopass`。
+impl fmt::Display for User {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "user {} on", self.name)?;
+
+        if self.password.is_none() {
+            write!(fmt, " nopass")?;
+        }
+
+        for pattern in &self.key_patterns {
+            write!(fmt, " ~{}", pattern)?;
+        }
+
+        match &self.commands {
+            Some(commands) if commands.is_empty() => write!(fmt, " -@all"),
+            Some(commands) => commands
+                .iter()
+                .try_for_each(|command| write!(fmt, " +{}", command)),
+            None => write!(fmt, " +@all"),
+        }
+    }
+}
+
+/// 服务器的用户表。
+///
+/// # 示例
+///
+/// ```
+/// use mini_redis::acl::{Acl, User};
+/// use mini_redis::server::Config;
+///
+/// // 未认证的连接只能执行 `PING`，管理员可以执行所有命令。
+/// let acl = Acl::new()
+///     .user(User::new("default").command("ping"))
+///     .user(User::new("admin").password("secret").all_commands().all_keys());
+///
+/// let config = Config::new().acl(acl);
+/// # drop(config);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Acl {
+    /// 按名称排序的用户
+    users: BTreeMap<String, Arc<User>>,
+}
+
+impl Acl {
+    /// 创建一个空的用户表。
+    ///
+    /// 没有 `default` 用户，因此所有连接都必须先通过 `AUTH` 认证。
+    pub fn new() -> Acl {
+        Acl::default()
+    }
+
+    /// 添加一个用户。同名的用户会被替换。
+    pub fn user(mut self, user: User) -> Acl {
+        self.users.insert(user.name.clone(), Arc::new(user));
+        self
+    }
+
+    /// 不做任何限制的用户表，只包含一个没有密码、拥有所有权限的 `default` 用户。
+    pub(crate) fn unrestricted() -> Acl {
+        Acl::new().user(User::new(DEFAULT_USER).all_commands().all_keys())
+    }
+
+    /// 返回新连接使用的用户。
+    pub(crate) fn default_user(&self) -> Option<Arc<User>> {
+        self.users.get(DEFAULT_USER).cloned()
+    }
+
+    /// `default` 用户是否存在并且没有密码。
+    pub(crate) fn default_user_is_nopass(&self) -> bool {
+        self.users
+            .get(DEFAULT_USER)
+            .is_some_and(|user| user.password.is_none())
+    }
+
+    /// 使用用户名和密码认证，成功时返回对应的用户。
+    pub(crate) fn authenticate(&self, name: &str, password: &str) -> Option<Arc<User>> {
+        self.users
+            .get(name)
+            .filter(|user| user.check_password(password))
+            .cloned()
+    }
+
+    /// 返回所有用户，按名称排序。
+    pub(crate) fn users(&self) -> impl Iterator<Item = &User> {
+        self.users.values().map(|user| &**user)
+    }
+}
+
+/// 检查 `user` 是否可以执行 `cmd`。不允许时返回应当发送给客户端的 `NOPERM` 错误帧。
+///
+/// `EVALBATCH` 中的每个命令也会被检查。
+pub(crate) fn check_command(user: &User, cmd: &Command) -> Option<Frame> {
+    if let Some(name) = denied_command(user, cmd) {
+        return Some(Frame::typed_error(
+            ErrorKind::NoPerm,
+            format_args!("this user has no permissions to run the '{}' command", name),
+        ));
+    }
+
+    if !cmd.keys().into_iter().all(|key| user.can_access(key)) {
+        return Some(Frame::typed_error(
+            ErrorKind::NoPerm,
+            "this user has no permissions to access one of the keys used as arguments",
+        ));
+    }
+
+    None
+}
+
+/// 返回 `cmd` 中第一个不允许 `user` 执行的命令的名称。
+fn denied_command<'a>(user: &User, cmd: &'a Command) -> Option<&'a str> {
+    if !user.can_run(cmd.get_name()) {
+        return Some(cmd.get_name());
+    }
+
+    match cmd {
+        Command::EvalBatch(batch) => batch
+            .commands()
+            .iter()
+            .find_map(|cmd| denied_command(user, cmd)),
+        _ => None,
+    }
+}
+
+/// 判断 `s` 是否匹配 glob 风格的 `pattern`，支持 `*` 和 `?`。
+pub(crate) fn glob_match(pattern: &[u8], s: &[u8]) -> bool {
+    let (mut p, mut i) = (0, 0);
+
+    // 最近一个 `*` 在模式中的位置，以及它当前匹配到的位置，用于回溯。
+    let mut star = None;
+
+    while i < s.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                star = Some((p, i));
+                p += 1;
+            }
+            Some(&c) if c == b'?' || c == s[i] => {
+                p += 1;
+                i += 1;
+            }
+            _ => match star {
+                // 让 `*` 多匹配一个字节，然后重试。
+                Some((star_p, star_i)) => {
+                    star = Some((star_p, star_i + 1));
+                    p = star_p + 1;
+                    i = star_i + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == b'*')
+}
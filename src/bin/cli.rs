@@ -1,10 +1,18 @@
-use mini_redis::{clients::Client, DEFAULT_PORT};
+use mini_redis::{
+    clients::{Client, Message},
+    snapshot, Frame, DEFAULT_PORT,
+};
 
 use bytes::Bytes;
 use clap::{Parser, Subcommand};
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+use std::fmt::Write as _;
+use std::io::{self, Write as _};
 use std::num::ParseIntError;
-use std::str;
-use std::time::Duration;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, BufReader};
 
 #[derive(Parser, Debug)]
 #[command(
@@ -14,14 +22,53 @@ use std::time::Duration;
     about = "Issue Redis commands"
 )]
 struct Cli {
+    /// 要执行的命令。省略时进入交互模式
     #[clap(subcommand)]
-    command: Command,
+    command: Option<Command>,
 
     #[arg(id = "hostname", long, default_value = "127.0.0.1")]
     host: String,
 
     #[arg(long, default_value_t = DEFAULT_PORT)]
     port: u16,
+
+    /// 重复执行命令的次数。大于 1 时，最后打印请求延迟的统计数据
+    #[arg(short = 'r', long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    repeat: u64,
+
+    /// 每次重复执行之间等待的时间（毫秒）
+    #[arg(short = 'i', long, value_parser = duration_from_ms_str)]
+    interval: Option<Duration>,
+
+    /// 以 JSON 格式输出响应，每个响应占一行
+    #[arg(long, conflicts_with = "raw")]
+    json: bool,
+
+    /// 原样输出响应的内容，不加引号和类型前缀
+    #[arg(long)]
+    raw: bool,
+
+    /// 从标准输入逐行读取命令，以流水线方式发送，并按顺序打印响应
+    #[arg(long, conflicts_with_all = ["repeat", "interval"])]
+    pipe: bool,
+
+    /// 检查快照文件是否完整并打印概况，不连接服务器
+    #[arg(long, value_name = "PATH", conflicts_with = "pipe")]
+    check_dump: Option<PathBuf>,
+}
+
+/// `--pipe` 模式下每批以流水线方式发送的命令数量。
+const PIPE_BATCH_SIZE: usize = 1024;
+
+/// 响应的输出格式。
+#[derive(Clone, Copy, Debug)]
+enum Output {
+    /// 与 `redis-cli` 相同的格式，例如 `"value"`、`(integer) 1` 和 `(nil)`。
+    Human,
+    /// 每个响应输出一行 JSON，参见 `Frame::to_json`。
+    Json,
+    /// 原样输出字符串的字节，不加引号，数组的每个元素占一行。
+    Raw,
 }
 
 #[derive(Subcommand, Debug)]
@@ -56,9 +103,19 @@ enum Command {
         message: Bytes,
     },
     /// 订阅一个客户端到特定的频道或频道列表。
+    ///
+    /// 收到 `--count` 条消息、超过 `--timeout` 没有消息或者按下 Ctrl-C 时，取消订阅并退出。
     Subscribe {
         /// 特定的频道或频道列表
         channels: Vec<String>,
+
+        /// 收到指定数量的消息后退出
+        #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+        count: Option<u64>,
+
+        /// 超过指定时间（毫秒）没有收到消息时退出
+        #[arg(long, value_parser = duration_from_ms_str)]
+        timeout: Option<Duration>,
     },
 }
 
@@ -75,72 +132,470 @@ async fn main() -> mini_redis::Result<()> {
     // 解析命令行参数
     let cli = Cli::parse();
 
+    if let Some(path) = &cli.check_dump {
+        return check_dump(path);
+    }
+
     // 获取要连接的远程地址
     let addr = format!("{}:{}", cli.host, cli.port);
 
+    let output = if cli.json {
+        Output::Json
+    } else if cli.raw {
+        Output::Raw
+    } else {
+        Output::Human
+    };
+
     // 建立连接
     let mut client = Client::connect(&addr).await?;
 
-    // 处理请求的命令
-    match cli.command {
-        Command::Ping { msg } => {
-            let value = client.ping(msg).await?;
-            if let Ok(string) = str::from_utf8(&value) {
-                println!("\"{}\"", string);
-            } else {
-                println!("{:?}", value);
+    // 没有指定命令时进入交互模式。
+    let command = match cli.command {
+        Some(_) if cli.pipe => return Err("--pipe cannot be used with a command".into()),
+        Some(command) => command,
+        None if cli.pipe => return pipe(&mut client, output).await,
+        None => return repl(&mut client, &addr, output).await,
+    };
+
+    // 订阅不会结束，不能重复执行。
+    if let Command::Subscribe {
+        channels,
+        count,
+        timeout,
+    } = command
+    {
+        if cli.repeat > 1 {
+            return Err("--repeat cannot be used with subscribe".into());
+        }
+        return subscribe(client, channels, count, timeout, output).await;
+    }
+
+    // 处理请求的命令。只统计请求本身的延迟，不包括打印输出和重复之间的等待。
+    let mut latencies = Vec::with_capacity(cli.repeat as usize);
+
+    for i in 0..cli.repeat {
+        if i > 0 {
+            if let Some(interval) = cli.interval {
+                tokio::time::sleep(interval).await;
             }
         }
-        Command::Get { key } => {
-            if let Some(value) = client.get(&key).await? {
-                if let Ok(string) = str::from_utf8(&value) {
-                    println!("\"{}\"", string);
-                } else {
-                    println!("{:?}", value);
-                }
-            } else {
-                println!("(nil)");
+
+        let start = Instant::now();
+        let reply = execute(&mut client, &command).await?;
+        latencies.push(start.elapsed());
+
+        output.print(&reply)?;
+    }
+
+    if cli.repeat > 1 {
+        print_latency_stats(&mut latencies);
+    }
+
+    Ok(())
+}
+
+/// 订阅 `channels` 并打印收到的消息。
+///
+/// 收到 `count` 条消息、超过 `timeout` 没有消息或者按下 Ctrl-C 时，先向服务器取消订阅所有频道再返回，
+/// 而不是直接断开连接。服务器关闭连接时直接返回。
+async fn subscribe(
+    client: Client,
+    channels: Vec<String>,
+    count: Option<u64>,
+    timeout: Option<Duration>,
+    output: Output,
+) -> mini_redis::Result<()> {
+    if channels.is_empty() {
+        return Err("channel(s) must be provided".into());
+    }
+    let mut subscriber = client.subscribe(channels).await?;
+
+    // 在第一次轮询时注册 Ctrl-C 处理程序，之后 Ctrl-C 不再直接终止进程。
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+
+    let mut received = 0;
+
+    while count.is_none_or(|count| received < count) {
+        // `next_message` 是取消安全的：被 Ctrl-C 或者超时取消时，已经读取的数据保留在连接的缓冲区中。
+        let next_message = async {
+            match timeout {
+                Some(timeout) => tokio::time::timeout(timeout, subscriber.next_message())
+                    .await
+                    .ok(),
+                None => Some(subscriber.next_message().await),
+            }
+        };
+
+        tokio::select! {
+            res = next_message => match res {
+                Some(msg) => match msg? {
+                    Some(msg) => {
+                        output.print_message(&msg)?;
+                        received += 1;
+                    }
+                    // 服务器关闭了连接，无法再取消订阅。
+                    None => return Ok(()),
+                },
+                // 超时没有收到消息
+                None => break,
+            },
+            res = &mut ctrl_c => {
+                res?;
+                break;
             }
         }
+    }
+
+    subscriber.unsubscribe_all().await
+}
+
+/// 执行一次 `command`，返回表示响应的帧。
+async fn execute(client: &mut Client, command: &Command) -> mini_redis::Result<Frame> {
+    let reply = match command {
+        Command::Ping { msg } => Frame::bulk(client.ping(msg.clone()).await?),
+        Command::Get { key } => match client.get(key).await? {
+            Some(value) => Frame::bulk(value),
+            None => Frame::Null,
+        },
         Command::Set {
             key,
             value,
             expires: None,
         } => {
-            client.set(&key, value).await?;
-            println!("OK");
+            client.set(key, value.clone()).await?;
+            Frame::simple("OK")
         }
         Command::Set {
             key,
             value,
             expires: Some(expires),
         } => {
-            client.set_expires(&key, value, expires).await?;
-            println!("OK");
+            client.set_expires(key, value.clone(), *expires).await?;
+            Frame::simple("OK")
         }
         Command::Publish { channel, message } => {
-            client.publish(&channel, message).await?;
-            println!("Publish OK");
+            Frame::Integer(client.publish(channel, message.clone()).await?)
+        }
+        Command::Subscribe { .. } => unreachable!("subscribe is handled by main"),
+    };
+
+    Ok(reply)
+}
+
+impl Output {
+    /// 按照输出格式将响应写入标准输出。
+    fn print(self, frame: &Frame) -> io::Result<()> {
+        let out = match self {
+            Output::Human => format_reply(frame, 0).into_bytes(),
+            Output::Json => format!("{}\n", frame.to_json()).into_bytes(),
+            Output::Raw => {
+                let mut out = vec![];
+                format_raw(frame, &mut out);
+                out
+            }
+        };
+
+        io::stdout().write_all(&out)
+    }
+
+    /// 按照输出格式将订阅收到的消息写入标准输出。
+    fn print_message(self, msg: &Message) -> io::Result<()> {
+        let mut stdout = io::stdout();
+
+        match self {
+            Output::Human => writeln!(
+                stdout,
+                "从频道收到消息：{}; 消息 = {:?}",
+                msg.channel, msg.content
+            ),
+            Output::Json => {
+                let message = serde_json::json!({
+                    "channel": msg.channel,
+                    "message": Frame::bulk(msg.content.clone()).to_json(),
+                });
+                writeln!(stdout, "{}", message)
+            }
+            Output::Raw => {
+                stdout.write_all(&msg.content)?;
+                stdout.write_all(b"\n")
+            }
+        }
+    }
+}
+
+/// 打印请求次数以及延迟的最小值、p50、p99 和最大值。
+///
+/// 统计数据写入标准错误，标准输出中只有响应，以便 `--json` 和 `--raw` 的输出可以直接交给其他程序处理。
+fn print_latency_stats(latencies: &mut [Duration]) {
+    latencies.sort_unstable();
+
+    let ms = |latency: Duration| latency.as_secs_f64() * 1000.0;
+
+    eprintln!(
+        "{} requests: min {:.3} ms, p50 {:.3} ms, p99 {:.3} ms, max {:.3} ms",
+        latencies.len(),
+        ms(latencies[0]),
+        ms(percentile(latencies, 50)),
+        ms(percentile(latencies, 99)),
+        ms(latencies[latencies.len() - 1]),
+    );
+}
+
+/// `--check-dump` 模式：检查快照文件并打印概况。文件损坏时返回错误，进程以非零状态退出。
+fn check_dump(path: &Path) -> mini_redis::Result<()> {
+    match snapshot::check(path) {
+        Ok(summary) => {
+            println!(
+                "{}: OK (format version {}, {} keys, {} bytes)",
+                path.display(),
+                summary.version,
+                summary.keys,
+                summary.size
+            );
+            Ok(())
+        }
+        Err(err) => Err(format!("{}: {}", path.display(), err).into()),
+    }
+}
+
+/// 以最近秩法（nearest-rank）计算已排序的 `sorted` 的第 `p` 百分位数。
+fn percentile(sorted: &[Duration], p: usize) -> Duration {
+    let rank = (sorted.len() * p).div_ceil(100);
+    sorted[rank.max(1) - 1]
+}
+
+/// 交互模式：逐行读取命令，发送给服务器并打印响应，直到输入 `quit`、`exit` 或者 EOF（Ctrl-D）。
+///
+/// 命令行按照与 `redis-cli` 相同的规则拆分为参数，参数可以用双引号（支持 `\n`、`\xHH` 等转义）或者单引号括起来。
+async fn repl(client: &mut Client, addr: &str, output: Output) -> mini_redis::Result<()> {
+    let mut editor = DefaultEditor::new()?;
+    let prompt = format!("{}> ", addr);
+
+    loop {
+        // rustyline 阻塞地读取一行。CLI 使用单线程运行时，等待输入期间没有其他任务需要运行。
+        let line = match editor.readline(&prompt) {
+            Ok(line) => line,
+            // Ctrl-C 放弃当前行。
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+
+        let args = match split_args(&line) {
+            Ok(args) if args.is_empty() => continue,
+            Ok(args) => args,
+            Err(err) => {
+                println!("(error) {}", err);
+                continue;
+            }
+        };
+
+        let _ = editor.add_history_entry(line.as_str());
+
+        if let [command] = &args[..] {
+            if command.eq_ignore_ascii_case(b"quit") || command.eq_ignore_ascii_case(b"exit") {
+                return Ok(());
+            }
         }
-        Command::Subscribe { channels } => {
-            if channels.is_empty() {
-                return Err("channel(s) must be provided".into());
+
+        let reply = client.raw_command(args).await?;
+        output.print(&reply)?;
+    }
+}
+
+/// 流水线模式：从标准输入读取换行分隔的命令，分批以流水线方式发送，并按顺序打印响应。
+///
+/// 每行按照与交互模式相同的规则拆分为参数，空行被忽略。全部发送完成后，
+/// 在标准错误中打印响应和错误响应的数量。
+async fn pipe(client: &mut Client, output: Output) -> mini_redis::Result<()> {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut line_number = 0;
+    let mut replies = 0;
+    let mut errors = 0;
+
+    loop {
+        let mut batch = Vec::with_capacity(PIPE_BATCH_SIZE);
+
+        while batch.len() < PIPE_BATCH_SIZE {
+            let line = match lines.next_line().await? {
+                Some(line) => line,
+                None => break,
+            };
+            line_number += 1;
+
+            let args = split_args(&line).map_err(|err| format!("line {}: {}", line_number, err))?;
+            if !args.is_empty() {
+                batch.push(args);
             }
-            let mut subscriber = client.subscribe(channels).await?;
-
-            // 等待频道上的消息
-            while let Some(msg) = subscriber.next_message().await? {
-                println!(
-                    "从频道收到消息：{}; 消息 = {:?}",
-                    msg.channel, msg.content
-                );
+        }
+
+        // 标准输入已经读完
+        if batch.is_empty() {
+            break;
+        }
+
+        for reply in client.pipeline(batch).await? {
+            if let Frame::Error(_) = reply {
+                errors += 1;
             }
+            replies += 1;
+
+            output.print(&reply)?;
         }
     }
 
+    eprintln!("errors: {}, replies: {}", errors, replies);
+
     Ok(())
 }
 
+/// 将一行输入拆分为参数。
+///
+/// 参数以空白分隔。双引号括起来的参数支持 `\"`、`\\`、`\n`、`\r`、`\t`、`\b`、`\a` 和 `\xHH` 转义，
+/// 单引号括起来的参数只支持 `\'` 转义。闭合的引号之后必须是空白或者行尾。
+fn split_args(line: &str) -> Result<Vec<Bytes>, &'static str> {
+    let mut args = vec![];
+    let mut chars = line.as_bytes().iter().copied().peekable();
+
+    loop {
+        while chars.next_if(u8::is_ascii_whitespace).is_some() {}
+
+        let quote = match chars.peek() {
+            None => return Ok(args),
+            Some(&quote @ (b'"' | b'\'')) => {
+                chars.next();
+                Some(quote)
+            }
+            Some(_) => None,
+        };
+
+        let mut arg = vec![];
+
+        match quote {
+            None => {
+                while let Some(c) = chars.next_if(|c| !c.is_ascii_whitespace()) {
+                    arg.push(c);
+                }
+            }
+            Some(quote) => loop {
+                match chars.next() {
+                    None => return Err("unbalanced quotes"),
+                    Some(c) if c == quote => break,
+                    Some(b'\\') if quote == b'"' => {
+                        let escaped = match chars.next() {
+                            None => return Err("unbalanced quotes"),
+                            Some(b'n') => b'\n',
+                            Some(b'r') => b'\r',
+                            Some(b't') => b'\t',
+                            Some(b'b') => 0x08,
+                            Some(b'a') => 0x07,
+                            Some(b'x') => {
+                                let hex = [chars.next(), chars.next()];
+                                match hex {
+                                    [Some(hi), Some(lo)] => {
+                                        let digits = [hi, lo];
+                                        let digits = std::str::from_utf8(&digits).ok();
+                                        match digits.and_then(|d| u8::from_str_radix(d, 16).ok()) {
+                                            Some(byte) => byte,
+                                            None => return Err("invalid \\x escape"),
+                                        }
+                                    }
+                                    _ => return Err("invalid \\x escape"),
+                                }
+                            }
+                            Some(c) => c,
+                        };
+                        arg.push(escaped);
+                    }
+                    Some(b'\\') if chars.peek() == Some(&b'\'') => {
+                        arg.push(b'\'');
+                        chars.next();
+                    }
+                    Some(c) => arg.push(c),
+                }
+            },
+        }
+
+        if quote.is_some() && chars.peek().is_some_and(|c| !c.is_ascii_whitespace()) {
+            return Err("closing quote must be followed by a space");
+        }
+
+        args.push(Bytes::from(arg));
+    }
+}
+
+/// 以与 `redis-cli` 相同的格式显示响应。嵌套的数组按照 `indent` 缩进。
+fn format_reply(frame: &Frame, indent: usize) -> String {
+    match frame {
+        Frame::Simple(response) => format!("{}\n", response),
+        Frame::Error(msg) => format!("(error) {}\n", msg),
+        Frame::Integer(n) => format!("(integer) {}\n", n),
+        Frame::Bulk(data) => format!("{}\n", quote(data)),
+        Frame::Null => "(nil)\n".to_string(),
+        Frame::Array(items) if items.is_empty() => "(empty array)\n".to_string(),
+        Frame::Array(items) => {
+            let width = items.len().to_string().len();
+            let mut out = String::new();
+
+            for (i, item) in items.iter().enumerate() {
+                let prefix = format!("{:>width$}) ", i + 1, width = width);
+
+                if i > 0 {
+                    out.push_str(&" ".repeat(indent));
+                }
+
+                out.push_str(&prefix);
+                out.push_str(&format_reply(item, indent + prefix.len()));
+            }
+
+            out
+        }
+    }
+}
+
+/// 以 `--raw` 格式显示响应：字符串原样输出，数组的每个元素占一行，空数组不输出任何内容。
+fn format_raw(frame: &Frame, out: &mut Vec<u8>) {
+    match frame {
+        Frame::Simple(response) => out.extend_from_slice(response.as_bytes()),
+        Frame::Error(msg) => out.extend_from_slice(msg.as_bytes()),
+        Frame::Integer(n) => out.extend_from_slice(n.to_string().as_bytes()),
+        Frame::Bulk(data) => out.extend_from_slice(data),
+        Frame::Null => {}
+        Frame::Array(items) => {
+            for item in items {
+                format_raw(item, out);
+            }
+            return;
+        }
+    }
+
+    out.push(b'\n');
+}
+
+/// 将批量字符串显示为带引号的字符串，不可打印的字节被转义。
+fn quote(data: &[u8]) -> String {
+    let mut quoted = String::from("\"");
+
+    for &byte in data {
+        match byte {
+            b'"' => quoted.push_str("\\\""),
+            b'\\' => quoted.push_str("\\\\"),
+            b'\n' => quoted.push_str("\\n"),
+            b'\r' => quoted.push_str("\\r"),
+            b'\t' => quoted.push_str("\\t"),
+            0x20..=0x7e => quoted.push(byte as char),
+            _ => {
+                let _ = write!(quoted, "\\x{:02x}", byte);
+            }
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
+
 fn duration_from_ms_str(src: &str) -> Result<Duration, ParseIntError> {
     let ms = src.parse::<u64>()?;
     Ok(Duration::from_millis(ms))
@@ -0,0 +1,55 @@
+//! mini-redis 故障转移协调者。
+//!
+//! 监控一个主节点，主节点下线时将一个副本提升为新的主节点。参数被传递给 `mini_redis::sentinel`。
+
+use clap::Parser;
+use mini_redis::sentinel;
+use std::time::Duration;
+use tokio::signal;
+
+#[tokio::main]
+pub async fn main() -> mini_redis::Result<()> {
+    tracing_subscriber::fmt::try_init()?;
+
+    let cli = Cli::parse();
+
+    let mut config = sentinel::Config::new(cli.primary)
+        .ping_interval(Duration::from_millis(cli.ping_interval_ms))
+        .down_after(Duration::from_millis(cli.down_after_ms))
+        .channel(cli.channel);
+
+    for replica in cli.replicas {
+        config = config.replica(replica);
+    }
+
+    sentinel::run(config, signal::ctrl_c()).await
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "mini-redis-sentinel",
+    version,
+    author,
+    about = "Monitors a mini-redis primary and fails over to a replica"
+)]
+struct Cli {
+    /// 主节点的地址，例如 `127.0.0.1:6379`
+    #[arg(long, value_name = "HOST:PORT")]
+    primary: String,
+
+    /// 副本的地址。可以指定多次
+    #[arg(long = "replica", value_name = "HOST:PORT")]
+    replicas: Vec<String>,
+
+    /// `PING` 的间隔（毫秒），也是每次请求的超时时间
+    #[arg(long, value_name = "MS", default_value_t = 500)]
+    ping_interval_ms: u64,
+
+    /// 主节点在多长时间（毫秒）内没有响应之后被认为已经下线
+    #[arg(long, value_name = "MS", default_value_t = 5000)]
+    down_after_ms: u64,
+
+    /// 发布故障转移通知的频道
+    #[arg(long, default_value = sentinel::DEFAULT_CHANNEL)]
+    channel: String,
+}
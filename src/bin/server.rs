@@ -5,8 +5,15 @@
 //!
 //! `clap` 库用于解析参数。use mini_redis::{server, DEFAULT_PORT};
 
-use mini_redis::{server, DEFAULT_PORT};
+use mini_redis::cluster::{self, SlotMap};
+use mini_redis::{server, snapshot, DEFAULT_PORT};
 use clap::Parser;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::time::Duration;
 use tokio::net::TcpListener;
 use tokio::signal;
 
@@ -25,17 +32,113 @@ use tracing_subscriber::{
     fmt, layer::SubscriberExt, util::SubscriberInitExt, util::TryInitError, EnvFilter,
 };
 
-#[tokio::main]
-pub async fn main() -> mini_redis::Result<()> {
+pub fn main() -> mini_redis::Result<()> {
+    let cli = Cli::parse();
+
+    // 根据 `--runtime` 构建 Tokio 运行时，而不是使用 `#[tokio::main]`，以便比较两种运行时在负载下的表现。
+    let runtime = match cli.runtime {
+        RuntimeFlavor::Current => {
+            if cli.workers.is_some() {
+                return Err("`--workers` requires `--runtime multi`".into());
+            }
+
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()?
+        }
+        RuntimeFlavor::Multi => {
+            let mut builder = tokio::runtime::Builder::new_multi_thread();
+            if let Some(workers) = cli.workers {
+                builder.worker_threads(workers.get());
+            }
+
+            builder.enable_all().build()?
+        }
+    };
+
+    runtime.block_on(run(cli))
+}
+
+async fn run(cli: Cli) -> mini_redis::Result<()> {
     set_up_logging()?;
 
-    let cli = Cli::parse();
     let port = cli.port.unwrap_or(DEFAULT_PORT);
 
-    // Bind a TCP listener
-    let listener = TcpListener::bind(&format!("127.0.0.1:{}", port)).await?;
+    // 在侦听之前检查快照文件，文件损坏时以非零状态退出。服务器启动时再读取文件并加载数据。
+    // 开启 AOF 时不加载快照。
+    if !cli.appendonly && cli.dbfilename.exists() {
+        snapshot::check(&cli.dbfilename)
+            .map_err(|err| format!("{}: {}", cli.dbfilename.display(), err))?;
+    }
+
+    // Use the sockets passed by systemd, or bind a TCP listener for every address
+    let mut listeners = inherited_listeners()?;
+
+    if listeners.is_empty() {
+        let addrs = bind_addrs(&cli.bind, port);
+
+        for addr in &addrs {
+            let listener =
+                bind(*addr, &addrs).map_err(|err| format!("failed to bind {}: {}", addr, err))?;
+            listeners.push(listener);
+        }
+    } else if !cli.bind.is_empty() || cli.port.is_some() {
+        return Err("`--bind` and `--port` cannot be used with socket activation".into());
+    }
+
+    for listener in &listeners {
+        println!("mini-redis listening on {}", listener.local_addr()?);
+    }
+
+    let mut config = server::Config::new();
+    if let Some(capacity) = cli.channel_capacity {
+        config = config.channel_capacity(capacity.get());
+    }
+
+    if let Some(slots) = cluster_slots(&cli)? {
+        config = config.slot_check(slots);
+    }
+
+    config = config.read_only(cli.read_only);
+
+    if let Some(max) = cli.maxclients {
+        config = config.max_connections(max.get());
+    }
+
+    config = config.reject_excess_connections(cli.reject_excess_clients);
+
+    if let Some(percent) = cli.ttl_jitter {
+        config = config.ttl_jitter(percent);
+    }
+
+    config = config.snapshot_path(cli.dbfilename);
+
+    if cli.appendonly {
+        config = config
+            .append_only(cli.appendfilename)
+            .append_fsync(cli.appendfsync);
+    }
+
+    if let Some(size) = cli.repl_backlog_size {
+        config = config.repl_backlog_size(size.get());
+    }
+
+    if let Some((host, port)) = cli.replicaof {
+        config = config.replica_of(host, port);
+    }
+
+    config = config.latency_monitor_threshold(Duration::from_millis(cli.latency_monitor_threshold));
+    config = config.tcp_keepalive(Duration::from_secs(cli.tcp_keepalive));
 
-    server::run(listener, signal::ctrl_c()).await;
+    if cli.handshake_timeout > 0 {
+        config = config.handshake_timeout(Duration::from_secs(cli.handshake_timeout));
+    }
+
+    // 侦听器已经处于侦听状态，此后到达的连接会在内核中排队，直到服务器开始接受连接。
+    #[cfg(feature = "systemd")]
+    sd_notify::notify(true, &[sd_notify::NotifyState::Ready])?;
+
+    server::run_with_listeners(listeners, config, signal::ctrl_c()).await;
 
     Ok(())
 }
@@ -45,14 +148,237 @@ pub async fn main() -> mini_redis::Result<()> {
 struct Cli {
     #[arg(long)]
     port: Option<u16>,
+
+    /// 侦听的地址，例如 `0.0.0.0`、`::` 或 `[::1]:6380`。没有端口时使用 `--port`。可以指定多次，默认为 `127.0.0.1`
+    #[arg(long, value_name = "ADDR", value_parser = parse_bind_addr)]
+    bind: Vec<BindAddr>,
+
+    /// 每个发布/订阅频道能够缓冲的消息数量
+    #[arg(long)]
+    channel_capacity: Option<NonZeroUsize>,
+
+    /// 以集群模式运行，负责给定的哈希槽范围，例如 `0-8191`。可以指定多次
+    #[arg(long = "cluster-slots", value_name = "RANGE")]
+    cluster_slots: Vec<String>,
+
+    /// 其他节点负责的哈希槽范围，格式为 `RANGE=HOST:PORT`，例如 `8192-16383=127.0.0.1:6380`。可以指定多次
+    #[arg(long = "cluster-node", value_name = "RANGE=ADDR")]
+    cluster_nodes: Vec<String>,
+
+    /// 以只读模式运行，所有写命令都以 `READONLY` 错误响应
+    #[arg(long)]
+    read_only: bool,
+
+    /// 最大并发连接数量，默认为 250。运行时可以通过 `CONFIG SET maxclients` 修改
+    #[arg(long)]
+    maxclients: Option<NonZeroUsize>,
+
+    /// 连接数量达到上限时，以 `-ERR max number of clients reached` 拒绝新连接，而不是让它们等待
+    #[arg(long)]
+    reject_excess_clients: bool,
+
+    /// 将每个键的过期时长随机延长 0 到该百分比之间的时间，避免以相同 TTL 写入的大量键同时过期
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..=100))]
+    ttl_jitter: Option<u8>,
+
+    /// 快照文件的路径。启动时从中加载数据，`BGSAVE` 写入该文件
+    #[arg(long, value_name = "PATH", default_value = "dump.snapshot")]
+    dbfilename: PathBuf,
+
+    /// 开启 AOF：写命令追加到 `--appendfilename` 指定的文件，启动时重新执行其中的命令，不再加载快照
+    #[arg(long)]
+    appendonly: bool,
+
+    /// AOF 的路径
+    #[arg(long, value_name = "PATH", default_value = "appendonly.aof")]
+    appendfilename: PathBuf,
+
+    /// AOF 调用 fsync 的策略：`always`、`everysec` 或 `no`
+    #[arg(long, value_name = "POLICY", default_value = "everysec")]
+    appendfsync: server::AppendFsync,
+
+    /// 复制积压缓冲区的大小（字节），默认为 1MB。断开的副本重新连接时，缺少的写命令仍在缓冲区中才能部分同步
+    #[arg(long, value_name = "BYTES")]
+    repl_backlog_size: Option<NonZeroUsize>,
+
+    /// 以副本的身份启动，从 `HOST:PORT` 上的主节点复制数据
+    #[arg(long, value_name = "HOST:PORT", value_parser = parse_replicaof)]
+    replicaof: Option<(String, u16)>,
+
+    /// 延迟监控的阈值（毫秒）。耗时达到阈值的命令和过期键清除可以通过 `LATENCY HISTORY` 查看，0 表示不记录
+    #[arg(long, value_name = "MS", default_value_t = 0)]
+    latency_monitor_threshold: u64,
+
+    /// 连接空闲多少秒之后开始发送 TCP keepalive 探测，0 表示不开启
+    #[arg(long, value_name = "SECONDS", default_value_t = 300)]
+    tcp_keepalive: u64,
+
+    /// 新连接必须在多少秒内发送第一个完整的命令，否则被关闭。0 表示不限制
+    #[arg(long, value_name = "SECONDS", default_value_t = 0)]
+    handshake_timeout: u64,
+
+    /// Tokio 运行时的类型：`multi` 在多个工作线程上运行连接任务，`current` 在当前线程上运行所有任务
+    #[arg(long, value_enum, default_value_t = RuntimeFlavor::Multi)]
+    runtime: RuntimeFlavor,
+
+    /// 多线程运行时的工作线程数量，默认为 CPU 核心数量
+    #[arg(long, value_name = "N")]
+    workers: Option<NonZeroUsize>,
+}
+
+/// `--runtime` 选择的 Tokio 运行时类型。
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+enum RuntimeFlavor {
+    /// 单线程运行时
+    Current,
+
+    /// 多线程运行时
+    Multi,
+}
+
+/// `--bind` 指定的地址，可以省略端口。
+#[derive(Clone, Copy, Debug)]
+enum BindAddr {
+    Ip(IpAddr),
+    Socket(SocketAddr),
+}
+
+/// 解析 `--bind` 的值。IPv6 地址可以用方括号括起来，带端口时必须用方括号括起来。
+fn parse_bind_addr(src: &str) -> Result<BindAddr, String> {
+    if let Ok(addr) = src.parse() {
+        return Ok(BindAddr::Socket(addr));
+    }
+
+    let ip = src
+        .strip_prefix('[')
+        .and_then(|ip| ip.strip_suffix(']'))
+        .unwrap_or(src);
+
+    ip.parse()
+        .map(BindAddr::Ip)
+        .map_err(|_| format!("invalid bind address `{}`", src))
 }
 
-#[cfg(not(feature = "otel"))]
+/// 解析 `--replicaof` 的 `HOST:PORT`。
+fn parse_replicaof(src: &str) -> Result<(String, u16), String> {
+    let (host, port) = src
+        .rsplit_once(':')
+        .ok_or_else(|| format!("expected `HOST:PORT`, got `{}`", src))?;
+    let port = port
+        .parse()
+        .map_err(|_| format!("invalid port in `{}`", src))?;
+
+    Ok((host.to_string(), port))
+}
+
+/// 为没有端口的地址补上 `port`。没有指定 `--bind` 时只侦听 `127.0.0.1`。
+fn bind_addrs(bind: &[BindAddr], port: u16) -> Vec<SocketAddr> {
+    if bind.is_empty() {
+        return vec![SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port)];
+    }
+
+    bind.iter()
+        .map(|addr| match *addr {
+            BindAddr::Ip(ip) => SocketAddr::new(ip, port),
+            BindAddr::Socket(addr) => addr,
+        })
+        .collect()
+}
+
+/// 创建侦听 `addr` 的 `TcpListener`。
+///
+/// 只侦听一个 IPv6 地址时，套接字同时接受 IPv4 连接（dual-stack），因此 `--bind ::` 可以接受所有连接。
+/// 同时指定了 IPv4 地址时，IPv6 套接字只接受 IPv6 连接，否则 `--bind 0.0.0.0 --bind ::` 会因为端口冲突而失败。
+fn bind(addr: SocketAddr, all: &[SocketAddr]) -> io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+
+    if addr.is_ipv6() {
+        socket.set_only_v6(all.iter().any(SocketAddr::is_ipv4))?;
+    }
+
+    // 与 `TcpListener::bind` 一样设置 `SO_REUSEADDR`，重启服务器时不必等待旧连接的 TIME_WAIT 结束。
+    #[cfg(unix)]
+    socket.set_reuse_address(true)?;
+
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+    socket.set_nonblocking(true)?;
+
+    TcpListener::from_std(socket.into())
+}
+
+/// 以 systemd 的套接字激活（socket activation）方式启动时，返回 systemd 传入的侦听器。
+///
+/// 没有传入套接字时（`LISTEN_PID` 不是当前进程或者没有设置），返回空的列表。
+#[cfg(feature = "systemd")]
+fn inherited_listeners() -> mini_redis::Result<Vec<TcpListener>> {
+    use std::os::unix::io::FromRawFd;
+
+    let mut listeners = vec![];
+
+    for fd in sd_notify::listen_fds()? {
+        // SAFETY: systemd 将 `LISTEN_FDS` 个已经处于侦听状态的套接字从描述符 3 开始传给 `LISTEN_PID` 进程，
+        // `listen_fds` 检查了 `LISTEN_PID` 并清除了环境变量，因此每个描述符只在这里被取得一次所有权。
+        let listener = unsafe { std::net::TcpListener::from_raw_fd(fd) };
+        listener.set_nonblocking(true)?;
+        listeners.push(TcpListener::from_std(listener)?);
+    }
+
+    Ok(listeners)
+}
+
+#[cfg(not(feature = "systemd"))]
+fn inherited_listeners() -> mini_redis::Result<Vec<TcpListener>> {
+    Ok(vec![])
+}
+
+/// 根据命令行参数构建槽分配表。没有指定 `--cluster-slots` 时返回 `None`，服务器不以集群模式运行。
+fn cluster_slots(cli: &Cli) -> mini_redis::Result<Option<SlotMap>> {
+    if cli.cluster_slots.is_empty() {
+        if !cli.cluster_nodes.is_empty() {
+            return Err("`--cluster-node` requires `--cluster-slots`".into());
+        }
+
+        return Ok(None);
+    }
+
+    let mut slots = SlotMap::new();
+
+    for node in &cli.cluster_nodes {
+        let (range, addr) = node
+            .split_once('=')
+            .ok_or_else(|| format!("invalid cluster node `{}`", node))?;
+        slots = slots.node(cluster::parse_slot_range(range)?, addr);
+    }
+
+    for range in &cli.cluster_slots {
+        slots = slots.local(cluster::parse_slot_range(range)?);
+    }
+
+    Ok(Some(slots))
+}
+
+#[cfg(not(any(feature = "otel", feature = "console")))]
 fn set_up_logging() -> mini_redis::Result<()> {
     // See https://docs.rs/tracing for more info
     tracing_subscriber::fmt::try_init()
 }
 
+#[cfg(all(feature = "console", not(feature = "otel")))]
+fn set_up_logging() -> mini_redis::Result<()> {
+    use tracing_subscriber::{
+        fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer,
+    };
+
+    // tokio-console 需要 Tokio 产生的所有任务事件，因此 `RUST_LOG` 只过滤输出到终端的日志。
+    tracing_subscriber::registry()
+        .with(console_subscriber::spawn())
+        .with(fmt::layer().with_filter(EnvFilter::from_default_env()))
+        .try_init()?;
+
+    Ok(())
+}
+
 #[cfg(feature = "otel")]
 fn set_up_logging() -> Result<(), TryInitError> {
     // 将全局传播器设置为 X-Ray 传播器
@@ -2,12 +2,32 @@
 //!
 //! 提供阻塞的连接和执行支持命令的方法。
 
+use crate::stream::{StreamEntry, StreamId};
+
 use bytes::Bytes;
 use std::time::Duration;
 use tokio::net::ToSocketAddrs;
 use tokio::runtime::Runtime;
 
-pub use crate::clients::Message;
+pub use crate::clients::{Message, PubSubEvent};
+
+/// 为 `BlockingClient` 和 `BlockingSubscriber` 生成阻塞的包装方法。
+///
+/// 每个方法都以同名的参数调用异步的 `inner` 客户端上的方法，并在 `rt` 上以阻塞方式执行。
+/// 由于方法名和参数直接转发，异步客户端的方法签名发生变化时，这里会编译失败，两套 API 不会悄悄地产生偏差。
+macro_rules! blocking_methods {
+    ($(
+        $(#[$meta:meta])*
+        fn $name:ident($($arg:ident: $ty:ty),*) -> $ret:ty;
+    )*) => {
+        $(
+            $(#[$meta])*
+            pub fn $name(&mut self, $($arg: $ty),*) -> $ret {
+                self.rt.block_on(self.inner.$name($($arg),*))
+            }
+        )*
+    };
+}
 
 /// 与 Redis 服务器建立的连接。
 ///
@@ -36,6 +56,11 @@ pub struct BlockingSubscriber {
     rt: Runtime,
 }
 
+/// `BlockingSubscriber::try_iter` 返回的迭代器。
+struct TryIter<'a> {
+    subscriber: &'a mut BlockingSubscriber,
+}
+
 /// `Subscriber::into_iter` 返回的迭代器。
 struct SubscriberIterator {
     /// 异步 `Subscriber`。
@@ -74,122 +99,321 @@ impl BlockingClient {
         Ok(BlockingClient { inner, rt })
     }
 
-    /// 获取键的值。
-    ///
-    /// 如果键不存在，则返回特殊值 `None`。
-    ///
-    /// # 示例
-    ///
-    /// 展示基本的用法。
-    ///
-    /// ```no_run
-    /// use mini_redis::clients::BlockingClient;
-    ///
-    /// fn main() {
-    ///     let mut client = BlockingClient::connect("localhost:6379").unwrap();
-    ///
-    ///     let val = client.get("foo").unwrap();
-    ///     println!("Got = {:?}", val);
-    /// }
-    /// ```
-    pub fn get(&mut self, key: &str) -> crate::Result<Option<Bytes>> {
-        self.rt.block_on(self.inner.get(key))
-    }
-    /// 将给定的 `value` 设置为与 `key` 关联。
-    ///
-    /// `value` 与 `key` 关联，直到下一次调用 `set` 或它被删除时覆盖。
-    ///
-    /// 如果键已经有一个值，它将被覆盖。任何与该键相关联的先前的存活时间
-    /// 在成功的 SET 操作时将被丢弃。
-    ///
-    /// # 示例
-    ///
-    /// 展示基本的用法。
-    ///
-    /// ```no_run
-    /// use mini_redis::clients::BlockingClient;
-    ///
-    /// fn main() {
-    ///     let mut client = BlockingClient::connect("localhost:6379").unwrap();
-    ///
-    ///     client.set("foo", "bar".into()).unwrap();
-    ///
-    ///     // 立即获取值是可行的
-    ///     let val = client.get("foo").unwrap().unwrap();
-    ///     assert_eq!(val, "bar");
-    /// }
-    /// ```
-    pub fn set(&mut self, key: &str, value: Bytes) -> crate::Result<()> {
-        self.rt.block_on(self.inner.set(key, value))
+    blocking_methods! {
+        /// 向服务器发送 Ping。
+        ///
+        /// 如果没有提供参数，则返回 PONG，否则返回参数的副本。
+        fn ping(msg: Option<Bytes>) -> crate::Result<Bytes>;
+
+        /// 获取键的值。
+        ///
+        /// 如果键不存在，则返回特殊值 `None`。
+        ///
+        /// # 示例
+        ///
+        /// 展示基本的用法。
+        ///
+        /// ```no_run
+        /// use mini_redis::clients::BlockingClient;
+        ///
+        /// fn main() {
+        ///     let mut client = BlockingClient::connect("localhost:6379").unwrap();
+        ///
+        ///     let val = client.get("foo").unwrap();
+        ///     println!("Got = {:?}", val);
+        /// }
+        /// ```
+        fn get(key: &str) -> crate::Result<Option<Bytes>>;
+
+        /// 将给定的 `value` 设置为与 `key` 关联。
+        ///
+        /// `value` 与 `key` 关联，直到下一次调用 `set` 或它被删除时覆盖。
+        ///
+        /// 如果键已经有一个值，它将被覆盖。任何与该键相关联的先前的存活时间
+        /// 在成功的 SET 操作时将被丢弃。
+        ///
+        /// # 示例
+        ///
+        /// 展示基本的用法。
+        ///
+        /// ```no_run
+        /// use mini_redis::clients::BlockingClient;
+        ///
+        /// fn main() {
+        ///     let mut client = BlockingClient::connect("localhost:6379").unwrap();
+        ///
+        ///     client.set("foo", "bar".into()).unwrap();
+        ///
+        ///     // 立即获取值是可行的
+        ///     let val = client.get("foo").unwrap().unwrap();
+        ///     assert_eq!(val, "bar");
+        /// }
+        /// ```
+        fn set(key: &str, value: Bytes) -> crate::Result<()>;
+
+        /// 将给定的 `value` 设置为与 `key` 关联，该值将在 `expiration` 后过期。
+        ///
+        /// `value` 与 `key` 关联，直到以下之一发生：
+        /// - 它过期。
+        /// - 它被下一次调用 `set` 覆盖。
+        /// - 它被删除。
+        ///
+        /// 如果键已经有一个值，它将被覆盖。任何与该键相关联的先前的存活时间
+        /// 在成功的 SET 操作时将被丢弃。
+        ///
+        /// # 示例
+        ///
+        /// 展示基本用法。这个例子不**保证**总是有效，因为它依赖于基于时间的逻辑，
+        /// 并假设客户端和服务器在时间上保持相对同步。现实世界往往不那么有利。
+        ///
+        /// ```no_run
+        /// use mini_redis::clients::BlockingClient;
+        /// use std::thread;
+        /// use std::time::Duration;
+        ///
+        /// fn main() {
+        ///     let ttl = Duration::from_millis(500);
+        ///     let mut client = BlockingClient::connect("localhost:6379").unwrap();
+        ///
+        ///     client.set_expires("foo", "bar".into(), ttl).unwrap();
+        ///
+        ///     // 立即获取值是可行的
+        ///     let val = client.get("foo").unwrap().unwrap();
+        ///     assert_eq!(val, "bar");
+        ///
+        ///     // 等待 TTL 到期
+        ///     thread::sleep(ttl);
+        ///
+        ///     let val = client.get("foo").unwrap();
+        ///     assert!(val.is_some());
+        /// }
+        /// ```
+        fn set_expires(key: &str, value: Bytes, expiration: Duration) -> crate::Result<()>;
+
+        /// 将 `key` 设为持有指定的 `value`，并保留键原有的过期时间。
+        fn set_keep_ttl(key: &str, value: Bytes) -> crate::Result<()>;
+
+        /// 仅当 `key` 的当前值等于 `expected` 时，将其替换为 `new`。替换成功时返回 `true`。
+        fn compare_and_set(key: &str, expected: Bytes, new: Bytes) -> crate::Result<bool>;
+
+        /// 原子地设置多个键的值。已有的值被替换，原有的过期时间被丢弃。
+        fn mset(pairs: &[(&str, Bytes)]) -> crate::Result<()>;
+
+        /// 删除给定的键，返回实际被删除的键的数量。不存在的键会被忽略。
+        fn del(keys: &[String]) -> crate::Result<u64>;
+
+        /// 返回给定的键中存在的数量。同一个键被指定多次时会被计数多次。
+        fn exists(keys: &[String]) -> crate::Result<u64>;
+
+        /// 返回距离上次读取 `key` 经过的时间，精确到秒。键不存在时返回 `None`。
+        fn object_idletime(key: &str) -> crate::Result<Option<Duration>>;
+
+        /// 返回 `key` 的访问频率计数器。键不存在时返回 `None`。
+        fn object_freq(key: &str) -> crate::Result<Option<u8>>;
+
+        /// 将 `key` 中存储的值序列化为一段二进制数据。键不存在时返回 `None`。
+        fn dump(key: &str) -> crate::Result<Option<Bytes>>;
+
+        /// 以 `dump` 返回的 `data` 创建 `key`，`ttl` 之后过期，`None` 表示不会过期。
+        fn restore(key: &str, ttl: Option<Duration>, data: Bytes, replace: bool) -> crate::Result<()>;
+
+        /// 将 `key` 中存储的整数加一，并返回新值。
+        ///
+        /// 如果键不存在，则在执行操作之前将其设置为 `0`。如果存储的值不是整数，则返回错误。
+        fn incr(key: &str) -> crate::Result<u64>;
+
+        /// 从 `offset` 开始用 `value` 覆盖 `key` 中存储的字符串，并返回修改后字符串的长度。
+        fn setrange(key: &str, offset: u64, value: Bytes) -> crate::Result<u64>;
+
+        /// 返回 `key` 中存储的字符串在 `start` 和 `end`（包含两端）之间的部分。
+        fn getrange(key: &str, start: i64, end: i64) -> crate::Result<Bytes>;
+
+        /// 将 `key` 中存储的字符串的第 `offset` 位设置为 `value`，并返回该位原来的值。
+        fn setbit(key: &str, offset: u64, value: bool) -> crate::Result<u64>;
+
+        /// 返回 `key` 中存储的字符串的第 `offset` 位。
+        fn getbit(key: &str, offset: u64) -> crate::Result<u64>;
+
+        /// 统计 `key` 中存储的字符串中被设置的位的数量，`range` 是可选的字节范围。
+        fn bitcount(key: &str, range: Option<(i64, i64)>) -> crate::Result<u64>;
+
+        /// 将 `elements` 添加到 `key` 中存储的 HyperLogLog。如果估计的基数可能因此改变，则返回 `true`。
+        fn pfadd(key: &str, elements: &[Bytes]) -> crate::Result<bool>;
+
+        /// 返回 `keys` 中存储的 HyperLogLog 的并集的估计基数。
+        fn pfcount(keys: &[String]) -> crate::Result<u64>;
+
+        /// 向 `key` 中存储的流添加一个由 `fields` 组成的条目，返回条目的 ID。`id` 为 `None` 时自动生成。
+        fn xadd(key: &str, id: Option<StreamId>, fields: &[(Bytes, Bytes)]) -> crate::Result<StreamId>;
+
+        /// 返回 `key` 中存储的流在 `start` 和 `end`（包含两端）之间的条目，最多返回 `count` 个。
+        fn xrange(key: &str, start: Option<StreamId>, end: Option<StreamId>, count: Option<u64>) -> crate::Result<Vec<StreamEntry>>;
+
+        /// 读取 `streams` 中每个流里 ID 大于给定 ID 的条目。`block` 不为 `None` 时阻塞等待新的条目。
+        fn xread(streams: &[(String, Option<StreamId>)], count: Option<u64>, block: Option<Duration>) -> crate::Result<Vec<(String, Vec<StreamEntry>)>>;
+
+        /// 将 `values` 依次推入 `key` 中存储的列表的头部，返回推入之后列表的长度。
+        fn lpush(key: &str, values: &[Bytes]) -> crate::Result<u64>;
+
+        /// 将 `values` 依次推入 `key` 中存储的列表的尾部，返回推入之后列表的长度。
+        fn rpush(key: &str, values: &[Bytes]) -> crate::Result<u64>;
+
+        /// 移除并返回 `key` 中存储的列表的第一个元素。列表为空或者键不存在时返回 `None`。
+        fn lpop(key: &str) -> crate::Result<Option<Bytes>>;
+
+        /// 返回 `key` 中存储的列表的长度。
+        fn llen(key: &str) -> crate::Result<u64>;
+
+        /// 设置 `key` 中存储的哈希的字段，返回新添加的字段数量。
+        fn hset(key: &str, pairs: &[(&str, Bytes)]) -> crate::Result<u64>;
+
+        /// 返回 `key` 中存储的哈希的字段的值。键或者字段不存在时返回 `None`。
+        fn hget(key: &str, field: &str) -> crate::Result<Option<Bytes>>;
+
+        /// 将 `key` 中存储的哈希的字段中的整数加上 `increment`，返回新值。
+        fn hincrby(key: &str, field: &str, increment: i64) -> crate::Result<u64>;
+
+        /// 将 `key` 中存储的哈希的字段中的浮点数加上 `increment`，返回新值。
+        fn hincrbyfloat(key: &str, field: &str, increment: f64) -> crate::Result<f64>;
+
+        /// 使 `key` 中存储的哈希的 `fields` 在 `duration` 之后过期，返回每个字段的结果。
+        fn hexpire(key: &str, duration: Duration, fields: &[&str]) -> crate::Result<Vec<Option<u64>>>;
+
+        /// 将 `members` 添加到 `key` 中存储的集合，返回新添加的成员数量。
+        fn sadd(key: &str, members: &[Bytes]) -> crate::Result<u64>;
+
+        /// 返回 `key` 中存储的集合的所有成员，按字节序排列。
+        fn smembers(key: &str) -> crate::Result<Vec<Bytes>>;
+
+        /// 返回 `keys` 中存储的集合的交集。
+        fn sinter(keys: &[String]) -> crate::Result<Vec<Bytes>>;
+
+        /// 返回 `keys` 中存储的集合的并集。
+        fn sunion(keys: &[String]) -> crate::Result<Vec<Bytes>>;
+
+        /// 返回 `keys` 中第一个集合与其他集合的差集。
+        fn sdiff(keys: &[String]) -> crate::Result<Vec<Bytes>>;
+
+        /// 将 `keys` 中存储的集合的交集存储到 `destination` 中，返回结果的成员数量。
+        fn sinterstore(destination: &str, keys: &[String]) -> crate::Result<u64>;
+
+        /// 将 `keys` 中存储的集合的并集存储到 `destination` 中，返回结果的成员数量。
+        fn sunionstore(destination: &str, keys: &[String]) -> crate::Result<u64>;
+
+        /// 将 `keys` 中第一个集合与其他集合的差集存储到 `destination` 中，返回结果的成员数量。
+        fn sdiffstore(destination: &str, keys: &[String]) -> crate::Result<u64>;
+
+        /// 返回所有与 glob 风格的 `pattern` 匹配的键。
+        fn keys(pattern: &str) -> crate::Result<Vec<String>>;
+
+        /// 返回数据库中键的数量。
+        fn dbsize() -> crate::Result<u64>;
+
+        /// 移除并返回 `keys` 中第一个非空列表的第一个元素。所有列表都为空时阻塞等待，`timeout` 为 `None` 表示一直等待。
+        fn blpop(keys: &[String], timeout: Option<Duration>) -> crate::Result<Option<(String, Bytes)>>;
+
+        /// 将任务 `payload` 添加到名为 `queue` 的工作队列的末尾，返回队列中等待的任务数量。
+        fn enqueue(queue: &str, payload: Bytes) -> crate::Result<u64>;
+
+        /// 从名为 `queue` 的工作队列的头部取出一个任务，队列为空时阻塞等待。超时时返回 `None`。
+        fn dequeue(queue: &str, timeout: Option<Duration>) -> crate::Result<Option<Bytes>>;
+
+        /// 阻塞直到之前的写命令被至少 `numreplicas` 个副本确认，或者经过 `timeout`，返回确认了写入的副本数量。
+        ///
+        /// `timeout` 为 `None` 表示一直等待。超时时返回已经确认的副本数量。
+        fn wait(numreplicas: u64, timeout: Option<Duration>) -> crate::Result<u64>;
+
+        /// 以 `username` 用户的身份认证连接。之后的命令都以该用户的权限执行。
+        fn auth(username: &str, password: &str) -> crate::Result<()>;
+
+        /// 返回连接当前的用户名。
+        fn acl_whoami() -> crate::Result<String>;
+
+        /// 返回服务器上所有用户的描述。
+        fn acl_list() -> crate::Result<Vec<String>>;
+
+        /// 返回与 `pattern` 匹配的服务器配置参数及其值。
+        fn config_get(pattern: &str) -> crate::Result<Vec<(String, String)>>;
+
+        /// 在运行时修改服务器配置参数 `parameter` 的值。
+        fn config_set(parameter: &str, value: &str) -> crate::Result<()>;
+
+        /// 返回服务器的信息和统计数据。`section` 为 `None` 时返回所有部分。
+        fn info(section: Option<&str>) -> crate::Result<String>;
+
+        /// 返回延迟监控事件 `event` 的所有采样：记录的时间（Unix 时间戳，秒）和延迟。
+        fn latency_history(event: &str) -> crate::Result<Vec<(u64, Duration)>>;
+
+        /// 删除延迟监控的事件 `events`，`events` 为空时删除所有事件。返回被删除的事件数量。
+        fn latency_reset(events: &[String]) -> crate::Result<u64>;
+
+        /// 请求服务器在后台将数据库的快照写入快照文件，不等待写入完成。
+        fn bgsave() -> crate::Result<()>;
+
+        /// 使服务器成为 `host:port` 上的主节点的副本，不等待同步完成。
+        fn replicaof(host: &str, port: u16) -> crate::Result<()>;
+
+        /// 使服务器停止复制，重新成为主节点。
+        fn replicaof_no_one() -> crate::Result<()>;
+
+        /// 发送由 `args` 组成的任意命令，并原样返回服务器的响应帧。
+        ///
+        /// 服务器以错误响应时返回 `Frame::Error`，而不是 `Err`。
+        fn raw_command(args: impl IntoIterator<Item = Bytes>) -> crate::Result<crate::Frame>;
+
+        /// 以流水线方式发送多个命令，并按顺序返回每个命令的响应帧。
+        fn pipeline(
+            commands: impl IntoIterator<Item = Vec<Bytes>>
+        ) -> crate::Result<Vec<crate::Frame>>;
+
+        /// 发布 `message` 到指定的 `channel`。
+        ///
+        /// 返回当前在频道上监听的订阅者数量。不能保证这些订阅者会接收到消息，因为他们可能随时断开连接。
+        ///
+        /// # 示例
+        ///
+        /// 展示基本用法。
+        ///
+        /// ```no_run
+        /// use mini_redis::clients::BlockingClient;
+        ///
+        /// fn main() {
+        ///     let mut client = BlockingClient::connect("localhost:6379").unwrap();
+        ///
+        ///     let val = client.publish("foo", "bar".into()).unwrap();
+        ///     println!("Got = {:?}", val);
+        /// }
+        /// ```
+        fn publish(channel: &str, message: Bytes) -> crate::Result<u64>;
+
+        /// 将 `messages` 按顺序发送到给定的 `channel`，返回订阅者数量。参见 `Client::mpublish`。
+        fn mpublish(channel: &str, messages: Vec<Bytes>) -> crate::Result<u64>;
+
+        /// 返回至少有一个订阅者的频道。`pattern` 不为 `None` 时只返回与模式匹配的频道。
+        fn pubsub_channels(pattern: Option<&str>) -> crate::Result<Vec<String>>;
+
+        /// 返回 `channels` 中每个频道的订阅者数量。
+        fn pubsub_numsub(channels: &[String]) -> crate::Result<Vec<(String, u64)>>;
+
+        /// 返回连接的 ID。参见 `Client::client_id`。
+        fn client_id() -> crate::Result<u64>;
+
+        /// 开启或关闭发布/订阅消息的元数据，需要在订阅之前调用。参见 `Client::client_pubsub_metadata`。
+        fn client_pubsub_metadata(on: bool) -> crate::Result<()>;
+
+        /// 允许下一个命令访问正在迁入该节点的槽中的键。参见 `Client::asking`。
+        fn asking() -> crate::Result<()>;
     }
 
-    /// 将给定的 `value` 设置为与 `key` 关联，该值将在 `expiration` 后过期。
-    ///
-    /// `value` 与 `key` 关联，直到以下之一发生：
-    /// - 它过期。
-    /// - 它被下一次调用 `set` 覆盖。
-    /// - 它被删除。
-    ///
-    /// 如果键已经有一个值，它将被覆盖。任何与该键相关联的先前的存活时间
-    /// 在成功的 SET 操作时将被丢弃。
-    ///
-    /// # 示例
-    ///
-    /// 展示基本用法。这个例子不**保证**总是有效，因为它依赖于基于时间的逻辑，
-    /// 并假设客户端和服务器在时间上保持相对同步。现实世界往往不那么有利。
-    ///
-    /// ```no_run
-    /// use mini_redis::clients::BlockingClient;
-    /// use std::thread;
-    /// use std::time::Duration;
-    ///
-    /// fn main() {
-    ///     let ttl = Duration::from_millis(500);
-    ///     let mut client = BlockingClient::connect("localhost:6379").unwrap();
-    ///
-    ///     client.set_expires("foo", "bar".into(), ttl).unwrap();
-    ///
-    ///     // 立即获取值是可行的
-    ///     let val = client.get("foo").unwrap().unwrap();
-    ///     assert_eq!(val, "bar");
-    ///
-    ///     // 等待 TTL 到期
-    ///     thread::sleep(ttl);
-    ///
-    ///     let val = client.get("foo").unwrap();
-    ///     assert!(val.is_some());
-    /// }
-    /// ```
-    pub fn set_expires(
-        &mut self,
-        key: &str,
-        value: Bytes,
-        expiration: Duration,
-    ) -> crate::Result<()> {
-        self.rt
-            .block_on(self.inner.set_expires(key, value, expiration))
+    /// 将 `key` 从当前连接的服务器移动到 `target` 连接的服务器。键不存在时返回 `false`。
+    /// 参见 `Client::migrate`。
+    pub fn migrate(&mut self, key: &str, target: &mut BlockingClient) -> crate::Result<bool> {
+        self.rt.block_on(self.inner.migrate(key, &mut target.inner))
     }
 
-    /// 发布 `message` 到指定的 `channel`。
-    ///
-    /// 返回当前在频道上监听的订阅者数量。不能保证这些订阅者会接收到消息，因为他们可能随时断开连接。
-    ///
-    /// # 示例
-    ///
-    /// 展示基本用法。
-    ///
-    /// ```no_run
-    /// use mini_redis::clients::BlockingClient;
-    ///
-    /// fn main() {
-    ///     let mut client = BlockingClient::connect("localhost:6379").unwrap();
-    ///
-    ///     let val = client.publish("foo", "bar".into()).unwrap();
-    ///     println!("Got = {:?}", val);
-    /// }
-    /// ```
-    pub fn publish(&mut self, channel: &str, message: Bytes) -> crate::Result<u64> {
-        self.rt.block_on(self.inner.publish(channel, message))
+    /// 请求服务器关闭连接，并消耗客户端。服务器写入 `OK` 响应之后关闭连接。
+    pub fn quit(self) -> crate::Result<()> {
+        self.rt.block_on(self.inner.quit())
     }
 
     /// 订阅客户端到指定的频道。
@@ -213,11 +437,78 @@ impl BlockingSubscriber {
         self.inner.get_subscribed()
     }
 
-    /// 接收订阅的频道上发布的下一条消息，如有必要，等待。
+    blocking_methods! {
+        /// 接收订阅的频道上发布的下一条消息，如有必要，等待。
+        ///
+        /// `None` 表示订阅已终止。
+        fn next_message() -> crate::Result<Option<Message>>;
+
+        /// 接收连接上的下一个发布/订阅事件，包括订阅和取消订阅的确认，如有必要，等待。
+        ///
+        /// `None` 表示订阅已终止。
+        fn next_event() -> crate::Result<Option<PubSubEvent>>;
+
+        /// 在订阅状态下向服务器发送 Ping。
+        fn ping(msg: Option<Bytes>) -> crate::Result<Bytes>;
+
+        /// 订阅一个新的频道列表
+        fn subscribe(channels: &[String]) -> crate::Result<()>;
+
+        /// 取消订阅一个新的频道列表
+        fn unsubscribe(channels: &[String]) -> crate::Result<()>;
+
+        /// 取消订阅所有已订阅的频道。
+        fn unsubscribe_all() -> crate::Result<()>;
+    }
+
+    /// 取消订阅所有频道，并返回底层的 `BlockingClient`，使连接可以继续执行普通命令。
+    ///
+    /// 尚未读取的消息会被丢弃。
+    pub fn into_client(self) -> crate::Result<BlockingClient> {
+        let inner = self.rt.block_on(self.inner.into_client())?;
+        Ok(BlockingClient { inner, rt: self.rt })
+    }
+
+    /// 接收订阅的频道上发布的下一条消息，最多等待 `timeout`。
+    ///
+    /// 如果在 `timeout` 内没有收到消息，则返回 `Ok(None)`，订阅保持不变，可以继续接收消息。
+    /// 与 `next_message` 不同，订阅已终止时返回错误，以便与超时区分。
+    pub fn next_message_timeout(&mut self, timeout: Duration) -> crate::Result<Option<Message>> {
+        // `next_message` 是取消安全的：超时时，已经读取的部分数据保留在连接的缓冲区中。
+        // 计时器必须在运行时的上下文中创建，因此在 `async` 块中调用 `timeout`。
+        let inner = &mut self.inner;
+        let res = self
+            .rt
+            .block_on(async { tokio::time::timeout(timeout, inner.next_message()).await });
+
+        match res {
+            Ok(Ok(Some(message))) => Ok(Some(message)),
+            Ok(Ok(None)) => Err("subscription terminated".into()),
+            Ok(Err(err)) => Err(err),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// 返回一个迭代器，只提供当前已经可用的消息，不会等待新的消息。
     ///
-    /// `None` 表示订阅已终止。
-    pub fn next_message(&mut self) -> crate::Result<Option<Message>> {
-        self.rt.block_on(self.inner.next_message())
+    /// 当没有更多立即可用的消息（或订阅已终止）时，迭代器结束。之后可以再次调用 `try_iter`。
+    pub fn try_iter(&mut self) -> impl Iterator<Item = crate::Result<Message>> + '_ {
+        TryIter { subscriber: self }
+    }
+
+    /// 接收一条已经可用的消息。如果没有，则立即返回 `Ok(None)`。
+    fn try_next_message(&mut self) -> crate::Result<Option<Message>> {
+        let inner = &mut self.inner;
+
+        self.rt.block_on(async {
+            // `yield_now` 让运行时在不阻塞的情况下处理一次 I/O 事件，
+            // 因此已经到达套接字但尚未读取的消息也会被返回。
+            tokio::select! {
+                biased;
+                res = inner.next_message() => res,
+                _ = tokio::task::yield_now() => Ok(None),
+            }
+        })
     }
 
     /// 将订阅者转换为一个 `Iterator`，提供在已订阅频道上发布的新消息。
@@ -227,15 +518,13 @@ impl BlockingSubscriber {
             rt: self.rt,
         }
     }
+}
 
-    /// 订阅一个新的频道列表
-    pub fn subscribe(&mut self, channels: &[String]) -> crate::Result<()> {
-        self.rt.block_on(self.inner.subscribe(channels))
-    }
+impl Iterator for TryIter<'_> {
+    type Item = crate::Result<Message>;
 
-    /// 取消订阅一个新的频道列表
-    pub fn unsubscribe(&mut self, channels: &[String]) -> crate::Result<()> {
-        self.rt.block_on(self.inner.unsubscribe(channels))
+    fn next(&mut self) -> Option<crate::Result<Message>> {
+        self.subscriber.try_next_message().transpose()
     }
 }
 
@@ -1,34 +1,46 @@
+use crate::clients::request::{Command, Response};
 use crate::clients::Client;
 use crate::Result;
 
 use bytes::Bytes;
+use std::convert::TryFrom;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio::sync::mpsc::{channel, Receiver, Sender};
-use tokio::sync::oneshot;
+use tokio::sync::{oneshot, Notify};
+use tokio::task::JoinHandle;
 
-// 枚举用于从 `BufferedClient` 句柄传递请求的命令
-#[derive(Debug)]
-enum Command {
-    Get(String),
-    Set(String, Bytes),
-}
+/// 请求队列的默认深度。
+const DEFAULT_QUEUE_DEPTH: usize = 32;
 
 // 通过通道发送到连接任务的消息类型。
 //
 // `Command` 是要转发到连接的命令。
 //
 // `oneshot::Sender` 是一种通道类型，用于发送**单个**值。这里用于将从连接接收到的响应发送回原始请求者。
-type Message = (Command, oneshot::Sender<Result<Option<Bytes>>>);
+type Message = (Command, oneshot::Sender<Result<Response>>);
 
 /// 接收通过通道发送的命令并将其转发给客户端。响应通过 `oneshot` 返回给调用者。
-async fn run(mut client: Client, mut rx: Receiver<Message>) {
-    // 重复地从通道中弹出消息。返回值为 `None` 表示所有 `BufferedClient` 句柄已丢弃，通道中将不再有其他消息发送。
-    while let Some((cmd, tx)) = rx.recv().await {
-        // 将命令转发到连接
-        let response = match cmd {
-            Command::Get(key) => client.get(&key).await,
-            Command::Set(key, value) => client.set(&key, value).await.map(|_| None),
+///
+/// 当 `shutdown` 收到通知时，通道被关闭：之后不再接受新的请求，但已经在队列中的请求仍会被处理。
+async fn run(mut client: Client, mut rx: Receiver<Message>, shutdown: Arc<Notify>) {
+    loop {
+        // 重复地从通道中弹出消息。返回值为 `None` 表示所有 `BufferedClient` 句柄已丢弃（或通道已关闭且已排空），
+        // 通道中将不再有其他消息。
+        let (cmd, tx) = tokio::select! {
+            msg = rx.recv() => match msg {
+                Some(msg) => msg,
+                None => break,
+            },
+            _ = shutdown.notified() => {
+                rx.close();
+                continue;
+            }
         };
 
+        // 将命令转发到连接
+        let response = cmd.send(&mut client).await;
+
         // 将响应发送回调用者。
         //
         // 未能发送消息表示 `rx` 半部分在接收消息之前就被丢弃。这是一个正常的运行时事件。
@@ -39,9 +51,78 @@ async fn run(mut client: Client, mut rx: Receiver<Message>) {
 #[derive(Clone)]
 pub struct BufferedClient {
     tx: Sender<Message>,
+
+    // 所有克隆的句柄共享的状态，用于关闭连接任务。
+    shared: Arc<Shared>,
+}
+
+#[derive(Debug)]
+struct Shared {
+    // 通知连接任务停止接受新的请求。
+    shutdown: Arc<Notify>,
+
+    // 连接任务的句柄。由第一个调用 `close` 的句柄取走。
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+/// 用于配置并创建 `BufferedClient` 的构建器。
+///
+/// 通过 `BufferedClient::builder()` 创建。
+#[derive(Debug, Clone)]
+pub struct BufferedClientBuilder {
+    queue_depth: usize,
+}
+
+impl BufferedClientBuilder {
+    /// 设置请求队列的深度，即在连接任务处理之前最多可以缓冲的请求数量。默认为 32。
+    ///
+    /// 队列已满时，发送请求会等待直到队列中有空间。
+    ///
+    /// # Panics
+    ///
+    /// 如果 `queue_depth` 为零，则会 panic。
+    pub fn queue_depth(mut self, queue_depth: usize) -> BufferedClientBuilder {
+        assert!(queue_depth > 0, "queue depth must be greater than zero");
+        self.queue_depth = queue_depth;
+        self
+    }
+
+    /// 生成管理 `client` 的连接任务，并返回 `BufferedClient` 句柄。
+    pub fn build(self, client: Client) -> BufferedClient {
+        let (tx, rx) = channel(self.queue_depth);
+        let shutdown = Arc::new(Notify::new());
+
+        // 生成一个任务来处理连接的请求。
+        let task = crate::task::spawn(
+            "mini-redis::buffered-client",
+            run(client, rx, shutdown.clone()),
+        );
+
+        // 返回 `BufferedClient` 句柄。
+        BufferedClient {
+            tx,
+            shared: Arc::new(Shared {
+                shutdown,
+                task: Mutex::new(Some(task)),
+            }),
+        }
+    }
+}
+
+impl Default for BufferedClientBuilder {
+    fn default() -> BufferedClientBuilder {
+        BufferedClientBuilder {
+            queue_depth: DEFAULT_QUEUE_DEPTH,
+        }
+    }
 }
 
 impl BufferedClient {
+    /// 返回一个用于配置 `BufferedClient` 的构建器。
+    pub fn builder() -> BufferedClientBuilder {
+        BufferedClientBuilder::default()
+    }
+
     /// 创建一个新的客户端请求缓冲区
     ///
     /// `Client` 直接在 TCP 连接上执行 Redis 命令。给定时间内只能有一个请求在处理中，并且操作需要对 `Client` 句柄的可变访问。
@@ -52,53 +133,187 @@ impl BufferedClient {
     /// 当收到响应时，它会被转发给原始请求者。
     ///
     /// 在将新的句柄传递给其他任务之前，可以克隆返回的 `BufferedClient` 句柄。
+    ///
+    /// 使用默认的队列深度。如需配置，请使用 `BufferedClient::builder()`。
     pub fn buffer(client: Client) -> BufferedClient {
-        // 将消息限制设置为硬编码值 32。在真实应用中，缓冲区大小应可配置，但这里无需这样做。
-        let (tx, rx) = channel(32);
+        BufferedClient::builder().build(client)
+    }
 
-        // 生成一个任务来处理连接的请求。
-        tokio::spawn(async move { run(client, rx).await });
+    /// 关闭连接任务。
+    ///
+    /// 调用之后，所有句柄（包括克隆的句柄）发送的新请求都会失败，但已经在队列中的请求仍会被处理并收到响应。
+    /// 该函数等待队列排空、连接任务退出后才返回，因此调用者可以确定性地关闭客户端。
+    ///
+    /// 如果另一个句柄已经调用了 `close`，则立即返回。
+    pub async fn close(self) -> Result<()> {
+        self.shared.shutdown.notify_one();
 
-        // 返回 `BufferedClient` 句柄。
-        BufferedClient { tx }
+        let task = self.shared.task.lock().unwrap().take();
+
+        match task {
+            Some(task) => task.await.map_err(Into::into),
+            None => Ok(()),
+        }
+    }
+
+    /// 向服务器发送 ping。
+    ///
+    /// 与 `Client::ping` 相同，但请求是**缓冲的**，直到相关的连接能够发送请求。
+    pub async fn ping(&mut self, msg: Option<Bytes>) -> Result<Bytes> {
+        self.request(Command::Ping(msg)).await
     }
 
     /// 获取键的值。
     ///
     /// 与 `Client::get` 相同，但请求是**缓冲的**，直到相关的连接能够发送请求。
     pub async fn get(&mut self, key: &str) -> Result<Option<Bytes>> {
-        // 初始化一个新的 `Get` 命令，通过通道发送。
-        let get = Command::Get(key.into());
-
-        // 初始化一个新的 oneshot，用于接收从连接返回的响应。
-        let (tx, rx) = oneshot::channel();
-
-        // 发送请求
-        self.tx.send((get, tx)).await?;
-
-        // 等待响应
-        match rx.await {
-            Ok(res) => res,
-            Err(err) => Err(err.into()),
-        }
+        self.request(Command::Get(key.into())).await
     }
 
     /// 设置 `key` 以保存给定的 `value`。
     ///
     /// 与 `Client::set` 相同，但请求是**缓冲的**，直到相关的连接能够发送请求
     pub async fn set(&mut self, key: &str, value: Bytes) -> Result<()> {
-        // 初始化一个新的 `Set` 命令，通过通道发送。
-        let set = Command::Set(key.into(), value);
+        self.request(Command::Set(key.into(), value, None)).await
+    }
+
+    /// 设置 `key` 以保存给定的 `value`。该值在 `expiration` 之后过期。
+    ///
+    /// 与 `Client::set_expires` 相同，但请求是**缓冲的**，直到相关的连接能够发送请求
+    pub async fn set_expires(
+        &mut self,
+        key: &str,
+        value: Bytes,
+        expiration: Duration,
+    ) -> Result<()> {
+        self.request(Command::Set(key.into(), value, Some(expiration)))
+            .await
+    }
+
+    /// 设置 `key` 以保存给定的 `value`，并保留键原有的过期时间。
+    ///
+    /// 与 `Client::set_keep_ttl` 相同，但请求是**缓冲的**，直到相关的连接能够发送请求
+    pub async fn set_keep_ttl(&mut self, key: &str, value: Bytes) -> Result<()> {
+        self.request(Command::SetKeepTtl(key.into(), value)).await
+    }
+
+    /// 仅当 `key` 的当前值等于 `expected` 时，将其替换为 `new`。替换成功时返回 `true`。
+    ///
+    /// 与 `Client::compare_and_set` 相同，但请求是**缓冲的**，直到相关的连接能够发送请求
+    pub async fn compare_and_set(
+        &mut self,
+        key: &str,
+        expected: Bytes,
+        new: Bytes,
+    ) -> Result<bool> {
+        self.request(Command::CompareAndSet(key.into(), expected, new))
+            .await
+    }
+
+    /// 将 `message` 发送到给定的 `channel`，返回订阅者的数量。
+    ///
+    /// 与 `Client::publish` 相同，但请求是**缓冲的**，直到相关的连接能够发送请求
+    pub async fn publish(&mut self, channel: &str, message: Bytes) -> Result<u64> {
+        self.request(Command::Publish(channel.into(), message))
+            .await
+    }
+
+    /// 删除给定的键，返回实际被删除的键的数量。
+    ///
+    /// 与 `Client::del` 相同，但请求是**缓冲的**，直到相关的连接能够发送请求
+    pub async fn del(&mut self, keys: &[String]) -> Result<u64> {
+        self.request(Command::Del(keys.to_vec())).await
+    }
+
+    /// 返回给定的键中存在的数量。
+    ///
+    /// 与 `Client::exists` 相同，但请求是**缓冲的**，直到相关的连接能够发送请求
+    pub async fn exists(&mut self, keys: &[String]) -> Result<u64> {
+        self.request(Command::Exists(keys.to_vec())).await
+    }
+
+    /// 将 `key` 中存储的整数加一，并返回新值。
+    ///
+    /// 与 `Client::incr` 相同，但请求是**缓冲的**，直到相关的连接能够发送请求
+    pub async fn incr(&mut self, key: &str) -> Result<u64> {
+        self.request(Command::Incr(key.into())).await
+    }
+
+    /// 从 `offset` 开始用 `value` 覆盖 `key` 中存储的字符串，并返回修改后字符串的长度。
+    ///
+    /// 与 `Client::setrange` 相同，但请求是**缓冲的**，直到相关的连接能够发送请求
+    pub async fn setrange(&mut self, key: &str, offset: u64, value: Bytes) -> Result<u64> {
+        self.request(Command::SetRange(key.into(), offset, value))
+            .await
+    }
+
+    /// 返回 `key` 中存储的字符串在 `start` 和 `end`（包含两端）之间的部分。
+    ///
+    /// 与 `Client::getrange` 相同，但请求是**缓冲的**，直到相关的连接能够发送请求
+    pub async fn getrange(&mut self, key: &str, start: i64, end: i64) -> Result<Bytes> {
+        self.request(Command::GetRange(key.into(), start, end))
+            .await
+    }
+
+    /// 将 `key` 中存储的字符串的第 `offset` 位设置为 `value`，并返回该位原来的值。
+    ///
+    /// 与 `Client::setbit` 相同，但请求是**缓冲的**，直到相关的连接能够发送请求
+    pub async fn setbit(&mut self, key: &str, offset: u64, value: bool) -> Result<u64> {
+        self.request(Command::SetBit(key.into(), offset, value))
+            .await
+    }
+
+    /// 返回 `key` 中存储的字符串的第 `offset` 位。
+    ///
+    /// 与 `Client::getbit` 相同，但请求是**缓冲的**，直到相关的连接能够发送请求
+    pub async fn getbit(&mut self, key: &str, offset: u64) -> Result<u64> {
+        self.request(Command::GetBit(key.into(), offset)).await
+    }
+
+    /// 统计 `key` 中存储的字符串中被设置的位的数量，`range` 是可选的字节范围。
+    ///
+    /// 与 `Client::bitcount` 相同，但请求是**缓冲的**，直到相关的连接能够发送请求
+    pub async fn bitcount(&mut self, key: &str, range: Option<(i64, i64)>) -> Result<u64> {
+        self.request(Command::BitCount(key.into(), range)).await
+    }
+
+    /// 将 `elements` 添加到 `key` 中存储的 HyperLogLog。如果估计的基数可能因此改变，则返回 `true`。
+    ///
+    /// 与 `Client::pfadd` 相同，但请求是**缓冲的**，直到相关的连接能够发送请求
+    pub async fn pfadd(&mut self, key: &str, elements: &[Bytes]) -> Result<bool> {
+        self.request(Command::PfAdd(key.into(), elements.to_vec()))
+            .await
+    }
+
+    /// 返回 `keys` 中存储的 HyperLogLog 的并集的估计基数。
+    ///
+    /// 与 `Client::pfcount` 相同，但请求是**缓冲的**，直到相关的连接能够发送请求
+    pub async fn pfcount(&mut self, keys: &[String]) -> Result<u64> {
+        self.request(Command::PfCount(keys.to_vec())).await
+    }
+
+    /// 阻塞直到之前的写命令被至少 `numreplicas` 个副本确认，或者经过 `timeout`，返回确认了写入的副本数量。
+    ///
+    /// 与 `Client::wait` 相同，但请求是**缓冲的**，直到相关的连接能够发送请求。
+    /// 等待期间，共享同一连接的其他句柄的请求也会被阻塞。
+    pub async fn wait(&mut self, numreplicas: u64, timeout: Option<Duration>) -> Result<u64> {
+        self.request(Command::Wait(numreplicas, timeout)).await
+    }
 
+    /// 将命令发送到连接任务并等待响应，然后将响应转换为调用者期望的类型。
+    async fn request<T>(&mut self, cmd: Command) -> Result<T>
+    where
+        T: TryFrom<Response, Error = crate::Error>,
+    {
         // 初始化一个新的 oneshot，用于接收从连接返回的响应。
         let (tx, rx) = oneshot::channel();
 
         // 发送请求
-        self.tx.send((set, tx)).await?;
+        self.tx.send((cmd, tx)).await?;
 
         // 等待响应
         match rx.await {
-            Ok(res) => res.map(|_| ()),
+            Ok(res) => T::try_from(res?),
             Err(err) => Err(err.into()),
         }
     }
@@ -0,0 +1,121 @@
+use crate::clients::Client;
+use crate::Result;
+
+use bytes::Bytes;
+use std::collections::HashMap;
+use tokio::net::ToSocketAddrs;
+use tracing::debug;
+
+/// 带有本地缓存的客户端，基于服务器辅助的客户端缓存（`CLIENT TRACKING`）。
+///
+/// `get` 的结果（包括键不存在）被缓存在本地的 `HashMap` 中，重复读取同一个键时不再访问服务器。
+/// 键被任意客户端修改、删除或者过期时，服务器推送失效通知，缓存的值在下一次调用时被丢弃。
+///
+/// 失效通知是异步到达的：在另一个客户端写入之后的很短时间内，`get` 仍然可能返回旧的值。
+/// 通过这个客户端自己的 `set` 和 `del` 写入的键会立即从缓存中移除。
+///
+/// # 示例
+///
+/// ```no_run
+/// use mini_redis::clients::CachingClient;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let mut client = CachingClient::connect("localhost:6379").await.unwrap();
+///
+///     // 第一次读取访问服务器，之后的读取直接使用缓存，直到键被修改。
+///     let first = client.get("config").await.unwrap();
+///     let second = client.get("config").await.unwrap();
+///     assert_eq!(first, second);
+/// }
+/// ```
+pub struct CachingClient {
+    /// 开启了 `CLIENT TRACKING` 的连接
+    client: Client,
+
+    /// 缓存的值。`None` 表示键不存在。
+    cache: HashMap<String, Option<Bytes>>,
+}
+
+impl CachingClient {
+    /// 与位于 `addr` 的 Redis 服务器建立连接，并开启键跟踪。
+    pub async fn connect<T: ToSocketAddrs>(addr: T) -> Result<CachingClient> {
+        CachingClient::new(Client::connect(addr).await?).await
+    }
+
+    /// 在已经建立的连接上开启键跟踪，将其包装为 `CachingClient`。
+    pub async fn new(mut client: Client) -> Result<CachingClient> {
+        client.client_tracking(true).await?;
+
+        Ok(CachingClient {
+            client,
+            cache: HashMap::new(),
+        })
+    }
+
+    /// 获取键的值。缓存中有未失效的值时直接返回，否则从服务器读取并缓存。
+    pub async fn get(&mut self, key: &str) -> Result<Option<Bytes>> {
+        self.invalidate()?;
+
+        if let Some(value) = self.cache.get(key) {
+            debug!(key, "cache hit");
+            return Ok(value.clone());
+        }
+
+        let value = self.client.get(key).await?;
+
+        // 在响应之前到达的失效通知对应于读取之前的写入，先处理它们，再缓存读取到的值。
+        self.invalidate()?;
+        self.cache.insert(key.to_string(), value.clone());
+
+        Ok(value)
+    }
+
+    /// 设置 `key` 以保存给定的 `value`，并从缓存中移除该键。
+    pub async fn set(&mut self, key: &str, value: Bytes) -> Result<()> {
+        self.cache.remove(key);
+        self.client.set(key, value).await
+    }
+
+    /// 删除 `keys`，并从缓存中移除这些键。返回被删除的键的数量。
+    pub async fn del(&mut self, keys: &[String]) -> Result<u64> {
+        for key in keys {
+            self.cache.remove(key);
+        }
+
+        self.client.del(keys).await
+    }
+
+    /// 返回缓存中键的数量。
+    pub fn cached(&self) -> usize {
+        self.cache.len()
+    }
+
+    /// 丢弃所有缓存的值。
+    pub fn clear(&mut self) {
+        self.cache.clear();
+    }
+
+    /// 关闭键跟踪，返回底层的 `Client`。
+    pub async fn into_client(mut self) -> Result<Client> {
+        self.client.client_tracking(false).await?;
+        Ok(self.client)
+    }
+
+    /// 处理已经收到的失效通知，从缓存中移除失效的键。
+    fn invalidate(&mut self) -> Result<()> {
+        for invalidation in self.client.take_invalidations()? {
+            match invalidation {
+                Some(keys) => {
+                    for key in keys {
+                        debug!(%key, "invalidated");
+                        self.cache.remove(&key);
+                    }
+                }
+                None => self.cache.clear(),
+            }
+        }
+
+        Ok(())
+    }
+}
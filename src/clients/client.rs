@@ -2,20 +2,35 @@
 //!
 //! 提供异步连接和发出支持的命令的方法。
 
-use crate::cmd::{Get, Ping, Publish, Set, Subscribe, Unsubscribe};
-use crate::{Connection, Frame};
+use crate::clients::{Middleware, ServerError};
+use crate::cmd::{
+    Acl, Asking, Auth, BLPop, BgSave, BitCount, Cas, ClientCommand, ConfigCommand, DbSize, Del,
+    Dump, Exists, Get, GetBit, GetRange, HExpire, HGet, HIncrBy, HIncrByFloat, HSet, Incr, Info,
+    Keys, LLen, LPop, LPush, Latency, MSet, Object, PfAdd, PfCount, Ping, PubSubCommand, Publish,
+    Quit, RPush, ReplicaOf, Restore, SAdd, SMembers, Set, SetBit, SetOp, SetRange, Subscribe,
+    Unsubscribe, Wait, XAdd, XRange, XRead,
+};
+use crate::db::SetOperation;
+use crate::stream::{StreamEntry, StreamId};
+use crate::{Connection, Frame, SocketOptions};
 
 use async_stream::try_stream;
 use bytes::Bytes;
+use std::collections::VecDeque;
+use std::fmt;
 use std::io::{Error, ErrorKind};
-use std::time::Duration;
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::net::{TcpStream, ToSocketAddrs};
 use tokio_stream::Stream;
-use tracing::{debug, instrument};
+use tracing::{debug, instrument, warn};
 
 /// 与 Redis 服务器建立的连接。
 ///
-/// 基于单个 `TcpStream`，`Client` 提供基本的网络客户端功能（不包含池化、重试等）。可以使用 [`connect`](fn@connect) 函数建立连接。
+/// 基于单个 `TcpStream`，`Client` 提供基本的网络客户端功能（不包含池化、重试等）。可以使用 [`connect`](fn@connect) 函数建立连接，
+/// 需要更多选项时使用 `Client::builder()`。
+/// 通过 `connect_with_replicas` 建立连接时，只读命令被轮流发送给副本。
 ///
 /// 可以通过 `Client` 的各种方法发出请求。
 pub struct Client {
@@ -24,8 +39,66 @@ pub struct Client {
     /// 当 `Listener` 接收到一个入站连接时，`TcpStream` 被传递给 `Connection::new`，它会初始化相关联的缓冲区。
     /// `Connection` 允许处理器在“帧”级别运行，并在 `Connection` 中将字节级别的协议解析细节封装起来。
     connection: Connection,
+
+    /// 通过 `with_middleware` 注册的中间件，按注册顺序调用。
+    middleware: Vec<Arc<dyn Middleware>>,
+
+    /// 最近一次发送的请求及其发送时间。仅在注册了中间件时记录，用于调用 `Middleware::after_receive`。
+    last_request: Option<(Frame, Instant)>,
+
+    /// 开启 `CLIENT TRACKING` 之后收到、尚未被取走的失效通知。`None` 表示没有开启跟踪。
+    invalidations: Option<Vec<Invalidation>>,
+
+    /// 到副本的连接，通过 `connect_with_replicas` 建立。只读命令轮流发送给这些副本。
+    replicas: Vec<Connection>,
+
+    /// 下一个只读命令发送给 `replicas` 中的哪一个副本。
+    next_replica: usize,
+
+    /// 正在等待响应的请求被发送给了哪一个副本。`None` 表示请求被发送给了主节点。
+    pending_replica: Option<usize>,
+
+    /// 是否暂时将只读命令也发送给主节点，参见 `read_from_primary`。
+    primary_reads: bool,
+}
+
+/// 用于配置并建立 `Client` 连接的构建器。
+///
+/// 通过 `Client::builder()` 创建。之后新增的连接选项都会加在这里，不会改变 `Client::connect` 的签名。
+///
+/// # 示例
+///
+/// ```no_run
+/// use mini_redis::clients::Client;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let client = Client::builder().connect("localhost:6379").await.unwrap();
+/// # drop(client);
+/// }
+/// ```
+#[derive(Clone, Default)]
+pub struct ClientBuilder {
+    /// 建立的连接上注册的中间件，按注册顺序调用。
+    middleware: Vec<Arc<dyn Middleware>>,
+
+    /// 连接每个地址的超时时间。`None` 表示不限制，由操作系统决定何时放弃。
+    connect_timeout: Option<Duration>,
+
+    /// 应用于建立的每个连接的 TCP 选项。
+    socket_options: SocketOptions,
+}
+
+/// `Client::read_from_primary` 返回的客户端，通过它发送的所有命令都发送给主节点。
+///
+/// 被丢弃时，只读命令恢复为发送给副本。
+pub struct PrimaryReads<'a> {
+    client: &'a mut Client,
 }
 
+/// 开启 `CLIENT TRACKING` 之后服务器推送的失效通知：失效的键，`None` 表示所有键都失效了。
+pub(crate) type Invalidation = Option<Vec<String>>;
+
 /// 处于发布/订阅模式的客户端。
 ///
 /// 一旦客户端订阅了一个频道，它们就只能执行与发布/订阅相关的命令。
@@ -36,6 +109,9 @@ pub struct Subscriber {
 
     /// `Subscriber` 当前订阅的频道集合。
     subscribed_channels: Vec<String>,
+
+    /// 在等待其他响应（例如 `PING` 的响应）时收到的事件，将在下一次调用 `next_event` 时返回。
+    pending_events: VecDeque<PubSubEvent>,
 }
 
 /// 在已订阅的频道上接收到的消息。
@@ -43,13 +119,188 @@ pub struct Subscriber {
 pub struct Message {
     pub channel: String,
     pub content: Bytes,
+
+    /// 发布者的元数据。只有在订阅之前通过 [`Client::client_pubsub_metadata`] 开启时才有。
+    pub metadata: Option<MessageMetadata>,
+}
+
+/// 服务器附带在消息上的发布者元数据，参见 [`Client::client_pubsub_metadata`]。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MessageMetadata {
+    /// 发布消息的连接的 ID，与该连接上 [`Client::client_id`] 的返回值相同。
+    /// 不是由客户端连接发布的消息（例如嵌入服务器的应用程序调用 `Db::publish`）为 `None`。
+    pub publisher: Option<u64>,
+
+    /// 消息被发布的时间，精确到毫秒。
+    pub published_at: SystemTime,
+}
+
+/// 处于发布/订阅模式的连接上接收到的事件。
+///
+/// 除了消息本身，服务器还会推送订阅和取消订阅的确认。
+/// `PubSubEvent` 将这些帧解析为类型化的值，应用程序可以通过
+/// [`Subscriber::next_event`] 观察订阅的完整生命周期，而不仅仅是消息。
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum PubSubEvent {
+    /// 已订阅 `channel`。`count` 是该连接当前订阅的频道数量。
+    Subscribed { channel: String, count: u64 },
+
+    /// 已取消订阅 `channel`。`count` 是该连接剩余订阅的频道数量。
+    Unsubscribed { channel: String, count: u64 },
+
+    /// 在已订阅的频道上接收到的消息。
+    Message { channel: String, content: Bytes },
+
+    /// 在已订阅的频道上接收到的、附带发布者元数据的消息，参见 [`Client::client_pubsub_metadata`]。
+    MessageWithMetadata {
+        channel: String,
+        content: Bytes,
+        metadata: MessageMetadata,
+    },
+
+    /// 通过模式订阅接收到的消息。
+    PMessage {
+        pattern: String,
+        channel: String,
+        content: Bytes,
+    },
+
+    /// 订阅状态下 `PING` 的响应，包含 `PING` 携带的消息（没有消息时为空）。
+    Pong(Bytes),
+
+    /// 由于消费过慢，服务器丢弃了 `channel` 上的 `skipped` 条消息。
+    Lagged { channel: String, skipped: u64 },
+}
+
+impl ClientBuilder {
+    /// 注册一个中间件，与 `Client::with_middleware` 相同。
+    pub fn middleware<M: Middleware + 'static>(mut self, middleware: M) -> ClientBuilder {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    /// 设置连接每个地址的超时时间。默认不限制。
+    ///
+    /// `addr` 解析出多个地址时（例如同时有 IPv6 和 IPv4 地址），超时的地址被跳过，继续尝试下一个地址，
+    /// 而不是在第一个无法路由的地址上等待操作系统放弃。
+    pub fn connect_timeout(mut self, timeout: Duration) -> ClientBuilder {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// 设置是否在连接上设置 `TCP_NODELAY`，默认开启，请求不会被操作系统延迟发送。
+    pub fn nodelay(mut self, nodelay: bool) -> ClientBuilder {
+        self.socket_options.nodelay = nodelay;
+        self
+    }
+
+    /// 开启 TCP keepalive，连接空闲 `time`（精确到秒）之后开始发送探测。默认不开启。
+    ///
+    /// 长时间空闲的连接（例如等待消息的订阅者）可以借此发现已经消失的服务器，也可以防止中间的网络设备
+    /// 因为空闲而丢弃连接。
+    pub fn tcp_keepalive(mut self, time: Duration) -> ClientBuilder {
+        self.socket_options.keepalive = Some(time);
+        self
+    }
+
+    /// 与位于 `addr` 的 Redis 服务器建立连接。参见 `Client::connect`。
+    pub async fn connect<T: ToSocketAddrs>(self, addr: T) -> crate::Result<Client> {
+        // 在任一步发生错误都会返回错误，该错误会被传递给 `mini_redis` connect 的调用者。
+        let socket = self.connect_socket(addr).await?;
+
+        // 初始化连接状态。这会分配读/写缓冲区以执行 Redis 协议帧解析。
+        let connection = Connection::new(socket);
+
+        Ok(Client {
+            connection,
+            middleware: self.middleware,
+            last_request: None,
+            invalidations: None,
+            replicas: Vec::new(),
+            next_replica: 0,
+            pending_replica: None,
+            primary_reads: false,
+        })
+    }
+
+    /// 与位于 `primary` 的主节点以及位于 `replicas` 的副本建立连接。参见 `Client::connect_with_replicas`。
+    pub async fn connect_with_replicas<T: ToSocketAddrs>(
+        self,
+        primary: T,
+        replicas: impl IntoIterator<Item = T>,
+    ) -> crate::Result<Client> {
+        let builder = self.clone();
+        let mut client = self.connect(primary).await?;
+
+        for addr in replicas {
+            let socket = builder.connect_socket(addr).await?;
+            client.replicas.push(Connection::new(socket));
+        }
+
+        Ok(client)
+    }
+
+    /// 解析 `addr` 并按顺序尝试连接解析出的每个地址，返回第一个成功建立的连接。
+    ///
+    /// 所有地址都失败时返回最后一个地址的错误。
+    async fn connect_socket<T: ToSocketAddrs>(&self, addr: T) -> crate::Result<TcpStream> {
+        // 执行任何异步 DNS 查找。
+        let addrs = tokio::net::lookup_host(addr).await?;
+        let mut last_err = None;
+
+        for addr in addrs {
+            let connect = TcpStream::connect(addr);
+
+            let result = match self.connect_timeout {
+                Some(timeout) => match tokio::time::timeout(timeout, connect).await {
+                    Ok(result) => result,
+                    Err(_) => Err(Error::new(ErrorKind::TimedOut, "connect timed out")),
+                },
+                None => connect.await,
+            };
+
+            match result {
+                Ok(socket) => {
+                    self.socket_options.apply(&socket)?;
+                    return Ok(socket);
+                }
+                Err(err) => {
+                    debug!(%addr, cause = %err, "failed to connect");
+                    last_err = Some(err);
+                }
+            }
+        }
+
+        Err(match last_err {
+            Some(err) => err.into(),
+            None => "could not resolve to any address".into(),
+        })
+    }
+}
+
+impl fmt::Debug for ClientBuilder {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("ClientBuilder")
+            .field("middleware", &self.middleware.len())
+            .field("connect_timeout", &self.connect_timeout)
+            .field("socket_options", &self.socket_options)
+            .finish()
+    }
 }
 
 impl Client {
+    /// 返回一个用于配置连接选项的构建器。
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::default()
+    }
+
     /// 与位于 `addr` 的 Redis 服务器建立连接。
     ///
     /// `addr` 可以是任何类型，只要它能够异步转换为 `SocketAddr`。这包括 `SocketAddr` 和字符串。
     /// `ToSocketAddrs` 特性是 Tokio 版本，而不是 `std` 版本。
+    /// 使用默认选项，等同于 `Client::builder().connect(addr)`。
     ///
     /// # 示例
     ///
@@ -67,15 +318,59 @@ impl Client {
     /// ```
     ///
     pub async fn connect<T: ToSocketAddrs>(addr: T) -> crate::Result<Client> {
-        // `addr` 参数直接传递给 `TcpStream::connect`。这会执行任何异步 DNS 查找
-        // 并尝试建立 TCP 连接。在任一步发生错误都会返回错误，
-        // 该错误会被传递给 `mini_redis` connect 的调用者。
-        let socket = TcpStream::connect(addr).await?;
+        Client::builder().connect(addr).await
+    }
 
-        // 初始化连接状态。这会分配读/写缓冲区以执行 Redis 协议帧解析。
-        let connection = Connection::new(socket);
+    /// 与位于 `primary` 的主节点以及位于 `replicas` 的副本建立连接。
+    ///
+    /// 只读命令（`COMMAND` 中带有 `readonly` 标志的命令，例如 `GET`、`EXISTS`、`XRANGE`）轮流发送给各个副本，
+    /// 其他命令发送给主节点。副本上的数据可能落后于主节点，刚刚写入的值不一定能从副本读到；
+    /// 需要读到自己的写入时，通过 `read_from_primary` 从主节点读取。
+    ///
+    /// 开启了 `CLIENT TRACKING` 时，所有命令都发送给主节点，失效通知只由主节点推送。
+    /// 连接到任意一个节点失败时返回错误。`replicas` 为空时等同于 `connect`。
+    ///
+    /// # 示例
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect_with_replicas("localhost:6379", vec!["localhost:6380"])
+    ///         .await
+    ///         .unwrap();
+    ///
+    ///     client.set("foo", "bar".into()).await.unwrap();
+    ///
+    ///     // 从主节点读取，保证读到刚刚写入的值。
+    ///     let value = client.read_from_primary().get("foo").await.unwrap();
+    ///     assert_eq!(value.as_deref(), Some(&b"bar"[..]));
+    /// }
+    /// ```
+    pub async fn connect_with_replicas<T: ToSocketAddrs>(
+        primary: T,
+        replicas: impl IntoIterator<Item = T>,
+    ) -> crate::Result<Client> {
+        Client::builder()
+            .connect_with_replicas(primary, replicas)
+            .await
+    }
+
+    /// 返回一个将所有命令都发送给主节点的客户端，用于需要读到自己刚刚写入的值的读取。
+    ///
+    /// 没有副本时等同于直接使用 `self`。
+    pub fn read_from_primary(&mut self) -> PrimaryReads<'_> {
+        self.primary_reads = true;
+        PrimaryReads { client: self }
+    }
 
-        Ok(Client { connection })
+    /// 注册一个中间件，它会拦截此客户端之后发送的每个请求和收到的每个响应。
+    ///
+    /// 可以注册多个中间件，它们按注册顺序被调用。
+    pub fn with_middleware<M: Middleware + 'static>(mut self, middleware: M) -> Client {
+        self.middleware.push(Arc::new(middleware));
+        self
     }
 
     /// 向服务器发送 Ping。
@@ -98,14 +393,13 @@ impl Client {
     ///     assert_eq!(b"PONG", &pong[..]);
     /// }
     /// ```
-    #[instrument(skip(self))]
+    #[instrument(skip(self), fields(command = "ping"))]
     pub async fn ping(&mut self, msg: Option<Bytes>) -> crate::Result<Bytes> {
         let frame = Ping::new(msg).into_frame();
-        debug!(request = ?frame);
-        self.connection.write_frame(&frame).await?;
+        self.write_request(frame).await?;
 
         match self.read_response().await? {
-            Frame::Simple(value) => Ok(value.into()),
+            Frame::Simple(value) => Ok(value.into_bytes()),
             Frame::Bulk(value) => Ok(value),
             frame => Err(frame.to_error()),
         }
@@ -130,21 +424,19 @@ impl Client {
     ///     println!("Got = {:?}", val);
     /// }
     /// ```
-    #[instrument(skip(self))]
+    #[instrument(skip(self), fields(command = "get"))]
     pub async fn get(&mut self, key: &str) -> crate::Result<Option<Bytes>> {
         // 为 `key` 创建一个 `Get` 命令并将其转换为帧。
         let frame = Get::new(key).into_frame();
 
-        debug!(request = ?frame);
-
         // 将帧写入套接字。这会将完整帧写入套接字，必要时会等待。
-        self.connection.write_frame(&frame).await?;
+        self.write_request(frame).await?;
 
         // 等待服务器的响应
         //
         // 接受 `Simple` 和 `Bulk` 帧。`Null` 表示键不存在，返回 `None`。
         match self.read_response().await? {
-            Frame::Simple(value) => Ok(Some(value.into())),
+            Frame::Simple(value) => Ok(Some(value.into_bytes())),
             Frame::Bulk(value) => Ok(Some(value)),
             Frame::Null => Ok(None),
             frame => Err(frame.to_error()),
@@ -175,7 +467,7 @@ impl Client {
     ///     assert_eq!(val, "bar");
     /// }
     /// ```
-    #[instrument(skip(self))]
+    #[instrument(skip(self, value), fields(command = "set"))]
     pub async fn set(&mut self, key: &str, value: Bytes) -> crate::Result<()> {
         // 创建一个 `Set` 命令并将其传递给 `set_cmd`。一个单独的方法用于设置带有过期时间的值。
         // 两个函数的共同部分由 `set_cmd` 实现。
@@ -218,7 +510,7 @@ impl Client {
     ///     assert!(val.is_some());
     /// }
     /// ```
-    #[instrument(skip(self))]
+    #[instrument(skip(self, value), fields(command = "set"))]
     pub async fn set_expires(
         &mut self,
         key: &str,
@@ -230,15 +522,39 @@ impl Client {
         self.set_cmd(Set::new(key, value, Some(expiration))).await
     }
 
-    /// 核心的 `SET` 逻辑，既被 `set` 使用，也被 `set_expires` 使用。
+    /// 将 `key` 设为持有指定的 `value`，并保留键原有的过期时间。
+    ///
+    /// 与 `set` 不同，如果键已经有过期时间，覆盖后的值仍在原来的时刻过期。键不存在时，新值没有过期时间。
+    ///
+    /// # 示例
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    /// use std::time::Duration;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     client.set_expires("session", "a".into(), Duration::from_secs(60)).await.unwrap();
+    ///
+    ///     // 更新值，但会话仍在 60 秒后过期
+    ///     client.set_keep_ttl("session", "b".into()).await.unwrap();
+    /// }
+    /// ```
+    #[instrument(skip(self, value), fields(command = "set"))]
+    pub async fn set_keep_ttl(&mut self, key: &str, value: Bytes) -> crate::Result<()> {
+        self.set_cmd(Set::new(key, value, None).with_keep_ttl())
+            .await
+    }
+
+    /// 核心的 `SET` 逻辑，被 `set`、`set_expires` 和 `set_keep_ttl` 使用。
     async fn set_cmd(&mut self, cmd: Set) -> crate::Result<()> {
         // 将 `Set` 命令转换为帧
         let frame = cmd.into_frame();
 
-        debug!(request = ?frame);
-
         // 将帧写入套接字。这会将完整帧写入套接字，必要时会等待。
-        self.connection.write_frame(&frame).await?;
+        self.write_request(frame).await?;
 
         // 等待服务器的响应。成功时，服务器仅以 `OK` 响应。任何其他响应表示错误。
         match self.read_response().await? {
@@ -247,14 +563,12 @@ impl Client {
         }
     }
 
-    /// 将 `message` 发送到给定的 `channel`。
+    /// 仅当 `key` 的当前值等于 `expected` 时，将其替换为 `new`。替换成功时返回 `true`。
     ///
-    /// 返回当前监听频道的订阅者数量。无法保证这些订阅者会收到消息，因为他们可能随时断开连接。
+    /// 比较和替换在服务器上原子地完成，可以用来实现乐观并发控制。键不存在时不会匹配。
     ///
     /// # 示例
     ///
-    /// 演示基本用法。
-    ///
     /// ```no_run
     /// use mini_redis::clients::Client;
     ///
@@ -262,169 +576,1999 @@ impl Client {
     /// async fn main() {
     ///     let mut client = Client::connect("localhost:6379").await.unwrap();
     ///
-    ///     let val = client.publish("foo", "bar".into()).await.unwrap();
-    ///     println!("Got = {:?}", val);
+    ///     client.set("lock", "free".into()).await.unwrap();
+    ///
+    ///     if client.compare_and_set("lock", "free".into(), "taken".into()).await.unwrap() {
+    ///         println!("acquired the lock");
+    ///     }
     /// }
     /// ```
-    #[instrument(skip(self))]
-    pub async fn publish(&mut self, channel: &str, message: Bytes) -> crate::Result<u64> {
-        // 将 `Publish` 命令转换为帧
-        let frame = Publish::new(channel, message).into_frame();
-
-        debug!(request = ?frame);
+    #[instrument(skip(self, expected, new), fields(command = "cas"))]
+    pub async fn compare_and_set(
+        &mut self,
+        key: &str,
+        expected: Bytes,
+        new: Bytes,
+    ) -> crate::Result<bool> {
+        let frame = Cas::new(key, expected, new).into_frame();
 
-        // 将帧写入套接字
-        self.connection.write_frame(&frame).await?;
+        self.write_request(frame).await?;
 
-        // 读取响应
         match self.read_response().await? {
-            Frame::Integer(response) => Ok(response),
+            Frame::Integer(swapped) => Ok(swapped != 0),
             frame => Err(frame.to_error()),
         }
     }
 
-    /// 订阅客户端到指定的频道。
+    /// 原子地设置多个键的值。已有的值被替换，原有的过期时间被丢弃。
     ///
-    /// 一旦客户端发出订阅命令，它不再能发出任何非发布/订阅命令。该函数消耗 `self` 并返回一个 `Subscriber`。
+    /// # 示例
     ///
-    /// `Subscriber` 用于接收消息以及管理客户端订阅的频道列表。
-    #[instrument(skip(self))]
-    pub async fn subscribe(mut self, channels: Vec<String>) -> crate::Result<Subscriber> {
-        // 向服务器发出订阅命令并等待确认。
-        // 客户端随后将转换为“订阅者”状态，从那时起只能发出发布/订阅命令。
-        self.subscribe_cmd(&channels).await?;
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     client
+    ///         .mset(&[("foo", "1".into()), ("bar", "2".into())])
+    ///         .await
+    ///         .unwrap();
+    /// }
+    /// ```
+    #[instrument(skip(self, pairs), fields(command = "mset"))]
+    pub async fn mset(&mut self, pairs: &[(&str, Bytes)]) -> crate::Result<()> {
+        let frame = MSet::new(pairs).into_frame();
 
-        // 返回 `Subscriber` 类型
-        Ok(Subscriber {
-            client: self,
-            subscribed_channels: channels,
-        })
-    }
+        self.write_request(frame).await?;
 
-    /// 核心的 `SUBSCRIBE` 逻辑，由各种订阅函数使用
-    async fn subscribe_cmd(&mut self, channels: &[String]) -> crate::Result<()> {
-        // 将 `Subscribe` 命令转换为帧
-        let frame = Subscribe::new(channels.to_vec()).into_frame();
+        match self.read_response().await? {
+            Frame::Simple(response) if response == "OK" => Ok(()),
+            frame => Err(frame.to_error()),
+        }
+    }
 
-        debug!(request = ?frame);
+    /// 删除给定的键，返回实际被删除的键的数量。不存在的键会被忽略。
+    ///
+    /// # 示例
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     let removed = client.del(&["foo".into(), "bar".into()]).await.unwrap();
+    ///     println!("removed {} keys", removed);
+    /// }
+    /// ```
+    #[instrument(skip(self), fields(command = "del"))]
+    pub async fn del(&mut self, keys: &[String]) -> crate::Result<u64> {
+        let frame = Del::new(keys).into_frame();
 
-        // 将帧写入套接字
-        self.connection.write_frame(&frame).await?;
+        self.write_request(frame).await?;
 
-        // 对于每个被订阅的频道，服务器会响应一个确认订阅该频道的消息。
-        for channel in channels {
-            // 读取响应
-            let response = self.read_response().await?;
-
-            // 验证它是订阅确认。
-            match response {
-                Frame::Array(ref frame) => match frame.as_slice() {
-                    // 服务器以如下形式的数组帧响应：
-                    //
-                    // ```
-                    // [ "subscribe", channel, num-subscribed ]
-                    // ```
-                    //
-                    // 其中 channel 是频道的名称，
-                    // num-subscribed 是客户端当前订阅的频道数量。
-                    [subscribe, schannel, ..]
-                        if *subscribe == "subscribe" && *schannel == channel => {}
-                    _ => return Err(response.to_error()),
-                },
-                frame => return Err(frame.to_error()),
-            };
+        match self.read_response().await? {
+            Frame::Integer(removed) => Ok(removed),
+            frame => Err(frame.to_error()),
         }
-
-        Ok(())
     }
 
-    /// 从套接字读取响应帧。
-    ///
-    /// 如果接收到 `Error` 帧，则将其转换为 `Err`。
-    async fn read_response(&mut self) -> crate::Result<Frame> {
-        let response = self.connection.read_frame().await?;
-
-        debug!(?response);
+    /// 返回给定的键中存在的数量。同一个键被指定多次时会被计数多次。
+    #[instrument(skip(self), fields(command = "exists"))]
+    pub async fn exists(&mut self, keys: &[String]) -> crate::Result<u64> {
+        let frame = Exists::new(keys).into_frame();
 
-        match response {
-            // 将错误帧转换为 `Err`
-            Some(Frame::Error(msg)) => Err(msg.into()),
-            Some(frame) => Ok(frame),
-            None => {
-                // 接收到 `None` 表示服务器已关闭连接而未发送帧。这是意外的，被表示为“连接被对端重置”错误。
-                let err = Error::new(ErrorKind::ConnectionReset, "connection reset by server");
+        self.write_request(frame).await?;
 
-                Err(err.into())
-            }
+        match self.read_response().await? {
+            Frame::Integer(found) => Ok(found),
+            frame => Err(frame.to_error()),
         }
     }
-}
 
-impl Subscriber {
-    /// 返回当前订阅的频道集合。
-    pub fn get_subscribed(&self) -> &[String] {
-        &self.subscribed_channels
+    /// 返回距离上次读取 `key` 经过的时间，精确到秒。键不存在时返回 `None`。
+    ///
+    /// `GET` 等读取键的命令会重置这个时间，`OBJECT` 本身不会。
+    ///
+    /// # 示例
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     if let Some(idle) = client.object_idletime("foo").await.unwrap() {
+    ///         println!("foo was last read {:?} ago", idle);
+    ///     }
+    /// }
+    /// ```
+    #[instrument(skip(self), fields(command = "object"))]
+    pub async fn object_idletime(&mut self, key: &str) -> crate::Result<Option<Duration>> {
+        let frame = Object::IdleTime(key.to_string()).into_frame();
+
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(seconds) => Ok(Some(Duration::from_secs(seconds))),
+            Frame::Null => Ok(None),
+            frame => Err(frame.to_error()),
+        }
     }
 
-    /// 接收在订阅频道上发布的下一条消息，必要时等待。
+    /// 返回 `key` 的访问频率计数器。键不存在时返回 `None`。
     ///
-    /// `None` 表示订阅已被终止。
-    pub async fn next_message(&mut self) -> crate::Result<Option<Message>> {
-        match self.client.connection.read_frame().await? {
-            Some(mframe) => {
-                debug!(?mframe);
+    /// 计数器从 5 开始，每次读取加一，每经过一分钟没有读取就减一，最大为 255。
+    #[instrument(skip(self), fields(command = "object"))]
+    pub async fn object_freq(&mut self, key: &str) -> crate::Result<Option<u8>> {
+        let frame = Object::Freq(key.to_string()).into_frame();
 
-                match mframe {
-                    Frame::Array(ref frame) => match frame.as_slice() {
-                        [message, channel, content] if *message == "message" => Ok(Some(Message {
-                            channel: channel.to_string(),
-                            content: Bytes::from(content.to_string()),
-                        })),
-                        _ => Err(mframe.to_error()),
-                    },
-                    frame => Err(frame.to_error()),
-                }
-            }
-            None => Ok(None),
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(frequency) => Ok(Some(frequency.min(u8::MAX as u64) as u8)),
+            Frame::Null => Ok(None),
+            frame => Err(frame.to_error()),
         }
     }
 
-    /// 将订阅者转换为一个 `Stream`，生成在订阅频道上发布的新消息。
+    /// 将 `key` 中存储的值序列化为一段二进制数据。键不存在时返回 `None`。
     ///
-    /// `Subscriber` 本身并不实现流，因为使用安全代码实现这一点并不简单。使用 async/await 需要手动实现 `unsafe` 的流代码。
-    /// 因此，提供了一个转换函数，并结合 `async-stream` crate 来实现返回的流。
-    pub fn into_stream(mut self) -> impl Stream<Item = crate::Result<Message>> {
-        // 使用 `async-stream` crate 中的 `try_stream` 宏。在 Rust 中生成器尚不稳定。
-        // 该 crate 使用宏来模拟基于 async/await 的生成器。存在一些限制，因此请阅读相关文档。
-        try_stream! {
-            while let Some(message) = self.next_message().await? {
-                yield message;
+    /// 数据可以通过 `restore` 在同一个或者另一个服务器上重新创建该键，不包含键的过期时间。
+    ///
+    /// # 示例
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     if let Some(data) = client.dump("foo").await.unwrap() {
+    ///         client.restore("foo-copy", None, data, false).await.unwrap();
+    ///     }
+    /// }
+    /// ```
+    #[instrument(skip(self), fields(command = "dump"))]
+    pub async fn dump(&mut self, key: &str) -> crate::Result<Option<Bytes>> {
+        let frame = Dump::new(key).into_frame();
+
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Bulk(data) => Ok(Some(data)),
+            Frame::Null => Ok(None),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 以 `dump` 返回的 `data` 创建 `key`，`ttl` 之后过期，`None` 表示不会过期。
+    ///
+    /// 键已经存在时返回 `BUSYKEY` 错误，`replace` 为 `true` 时替换原有的键。
+    /// 数据损坏或者由更新版本的服务器生成时返回错误。
+    #[instrument(skip(self, data), fields(command = "restore"))]
+    pub async fn restore(
+        &mut self,
+        key: &str,
+        ttl: Option<Duration>,
+        data: Bytes,
+        replace: bool,
+    ) -> crate::Result<()> {
+        let frame = Restore::new(key, ttl, data, replace).into_frame();
+
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(response) if response == "OK" => Ok(()),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 将 `key` 从当前连接的服务器移动到 `target` 连接的服务器。键不存在时返回 `false`。
+    ///
+    /// 通过 `DUMP` 序列化键的值，在 `target` 上以 `RESTORE ... REPLACE` 创建（替换同名的键），
+    /// 成功之后删除源服务器上的键。`RESTORE` 失败时源服务器上的键保持不变。
+    ///
+    /// 值在 `DUMP` 执行的时刻被完整地序列化，但整个迁移不是原子的：`DUMP` 和删除之间对该键的写入会丢失。
+    /// mini-redis 没有读取过期时间的命令，迁移后的键没有过期时间。
+    ///
+    /// # 示例
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut source = Client::connect("localhost:6379").await.unwrap();
+    ///     let mut target = Client::connect("localhost:6380").await.unwrap();
+    ///
+    ///     if source.migrate("foo", &mut target).await.unwrap() {
+    ///         println!("moved foo");
+    ///     }
+    /// }
+    /// ```
+    #[instrument(skip(self, target))]
+    pub async fn migrate(&mut self, key: &str, target: &mut Client) -> crate::Result<bool> {
+        let data = match self.dump(key).await? {
+            Some(data) => data,
+            None => return Ok(false),
+        };
+
+        target.restore(key, None, data, true).await?;
+        self.del(&[key.to_string()]).await?;
+
+        Ok(true)
+    }
+
+    /// 将 `key` 中存储的整数加一，并返回新值。
+    ///
+    /// 如果键不存在，则在执行操作之前将其设置为 `0`。如果存储的值不是整数，则返回错误。
+    ///
+    /// # 示例
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     let hits = client.incr("hits").await.unwrap();
+    ///     println!("hits = {}", hits);
+    /// }
+    /// ```
+    #[instrument(skip(self), fields(command = "incr"))]
+    pub async fn incr(&mut self, key: &str) -> crate::Result<u64> {
+        let frame = Incr::new(key).into_frame();
+
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(value) => Ok(value),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 从 `offset` 开始用 `value` 覆盖 `key` 中存储的字符串，并返回修改后字符串的长度。
+    ///
+    /// 如果字符串的长度不足 `offset`，则用零字节填充。键不存在时视为空字符串。
+    ///
+    /// # 示例
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     client.set("greeting", "Hello World".into()).await.unwrap();
+    ///     let len = client.setrange("greeting", 6, "Redis".into()).await.unwrap();
+    ///     assert_eq!(11, len);
+    /// }
+    /// ```
+    #[instrument(skip(self, value), fields(command = "setrange"))]
+    pub async fn setrange(&mut self, key: &str, offset: u64, value: Bytes) -> crate::Result<u64> {
+        let frame = SetRange::new(key, offset, value).into_frame();
+
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(len) => Ok(len),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 返回 `key` 中存储的字符串在 `start` 和 `end`（包含两端）之间的部分。
+    ///
+    /// 负的偏移量从字符串末尾开始计算，`-1` 表示最后一个字节。键不存在时返回空字符串。
+    ///
+    /// # 示例
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     client.set("greeting", "Hello World".into()).await.unwrap();
+    ///     let world = client.getrange("greeting", -5, -1).await.unwrap();
+    ///     assert_eq!(&b"World"[..], &world[..]);
+    /// }
+    /// ```
+    #[instrument(skip(self), fields(command = "getrange"))]
+    pub async fn getrange(&mut self, key: &str, start: i64, end: i64) -> crate::Result<Bytes> {
+        let frame = GetRange::new(key, start, end).into_frame();
+
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(value) => Ok(value.into_bytes()),
+            Frame::Bulk(value) => Ok(value),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 将 `key` 中存储的字符串的第 `offset` 位设置为 `value`，并返回该位原来的值。
+    ///
+    /// 偏移量 `0` 是第一个字节的最高位。如果字符串的长度不足，则用零字节填充。键不存在时视为空字符串。
+    ///
+    /// # 示例
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     let prev = client.setbit("flags", 7, true).await.unwrap();
+    ///     assert_eq!(0, prev);
+    /// }
+    /// ```
+    #[instrument(skip(self), fields(command = "setbit"))]
+    pub async fn setbit(&mut self, key: &str, offset: u64, value: bool) -> crate::Result<u64> {
+        let frame = SetBit::new(key, offset, value).into_frame();
+
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(prev) => Ok(prev),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 返回 `key` 中存储的字符串的第 `offset` 位。
+    ///
+    /// 超出字符串长度的位，以及不存在的键，都视为 `0`。
+    ///
+    /// # 示例
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     client.setbit("flags", 7, true).await.unwrap();
+    ///     assert_eq!(1, client.getbit("flags", 7).await.unwrap());
+    /// }
+    /// ```
+    #[instrument(skip(self), fields(command = "getbit"))]
+    pub async fn getbit(&mut self, key: &str, offset: u64) -> crate::Result<u64> {
+        let frame = GetBit::new(key, offset).into_frame();
+
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(bit) => Ok(bit),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 统计 `key` 中存储的字符串中被设置的位的数量。
+    ///
+    /// `range` 是可选的字节范围 `(start, end)`（包含两端），语义与 `getrange` 相同。
+    ///
+    /// # 示例
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     client.set("key", "foobar".into()).await.unwrap();
+    ///     assert_eq!(26, client.bitcount("key", None).await.unwrap());
+    ///     assert_eq!(6, client.bitcount("key", Some((1, 1))).await.unwrap());
+    /// }
+    /// ```
+    #[instrument(skip(self), fields(command = "bitcount"))]
+    pub async fn bitcount(&mut self, key: &str, range: Option<(i64, i64)>) -> crate::Result<u64> {
+        let frame = BitCount::new(key, range).into_frame();
+
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(count) => Ok(count),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 将 `elements` 添加到 `key` 中存储的 HyperLogLog。
+    ///
+    /// 如果键被创建，或者估计的基数可能因此改变，则返回 `true`。
+    ///
+    /// # 示例
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     client.pfadd("visitors", &["alice".into(), "bob".into()]).await.unwrap();
+    ///     let count = client.pfcount(&["visitors".into()]).await.unwrap();
+    ///     println!("about {} visitors", count);
+    /// }
+    /// ```
+    #[instrument(skip(self, elements), fields(command = "pfadd"))]
+    pub async fn pfadd(&mut self, key: &str, elements: &[Bytes]) -> crate::Result<bool> {
+        let frame = PfAdd::new(key, elements).into_frame();
+
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(changed) => Ok(changed != 0),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 返回 `keys` 中存储的 HyperLogLog 的并集的估计基数。不存在的键视为空集合。
+    #[instrument(skip(self), fields(command = "pfcount"))]
+    pub async fn pfcount(&mut self, keys: &[String]) -> crate::Result<u64> {
+        let frame = PfCount::new(keys).into_frame();
+
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(count) => Ok(count),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 向 `key` 中存储的流添加一个由 `fields` 组成的条目，返回条目的 ID。
+    ///
+    /// `id` 为 `None` 时由服务器根据当前时间生成 ID，否则指定的 ID 必须大于流中最后一个条目的 ID。
+    ///
+    /// # 示例
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     let fields = [("event".into(), "login".into())];
+    ///     let id = client.xadd("events", None, &fields).await.unwrap();
+    ///     println!("added {}", id);
+    /// }
+    /// ```
+    #[instrument(skip(self, fields), fields(command = "xadd"))]
+    pub async fn xadd(
+        &mut self,
+        key: &str,
+        id: Option<StreamId>,
+        fields: &[(Bytes, Bytes)],
+    ) -> crate::Result<StreamId> {
+        let frame = XAdd::new(key, id, fields).into_frame();
+
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Bulk(id) => Ok(std::str::from_utf8(&id)?.parse()?),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 返回 `key` 中存储的流在 `start` 和 `end`（包含两端）之间的条目，最多返回 `count` 个。
+    ///
+    /// `start` 为 `None` 表示从第一个条目开始，`end` 为 `None` 表示直到最后一个条目。
+    ///
+    /// # 示例
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     for entry in client.xrange("events", None, None, None).await.unwrap() {
+    ///         println!("{} {:?}", entry.id, entry.fields);
+    ///     }
+    /// }
+    /// ```
+    #[instrument(skip(self), fields(command = "xrange"))]
+    pub async fn xrange(
+        &mut self,
+        key: &str,
+        start: Option<StreamId>,
+        end: Option<StreamId>,
+        count: Option<u64>,
+    ) -> crate::Result<Vec<StreamEntry>> {
+        let start = start.unwrap_or(StreamId::MIN);
+        let end = end.unwrap_or(StreamId::MAX);
+        let frame = XRange::new(key, start, end, count).into_frame();
+
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Array(entries) => entries.into_iter().map(StreamEntry::from_frame).collect(),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 读取 `streams` 中每个流里 ID 大于给定 ID 的条目，每个流最多返回 `count` 个。
+    ///
+    /// ID 为 `None` 表示只读取调用之后添加的条目（即 Redis 的 `$`）。
+    /// `block` 不为 `None` 时，如果没有可读的条目，服务器会等待新的条目，直到经过 `block`；
+    /// `Duration::ZERO` 表示一直等待。
+    ///
+    /// 返回有新条目的流及其条目。没有读取到任何条目时返回空列表。
+    ///
+    /// # 示例
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    /// use std::time::Duration;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     let streams = [("events".to_string(), None)];
+    ///     let block = Some(Duration::from_secs(5));
+    ///
+    ///     for (key, entries) in client.xread(&streams, None, block).await.unwrap() {
+    ///         println!("{}: {} new entries", key, entries.len());
+    ///     }
+    /// }
+    /// ```
+    #[instrument(skip(self), fields(command = "xread"))]
+    pub async fn xread(
+        &mut self,
+        streams: &[(String, Option<StreamId>)],
+        count: Option<u64>,
+        block: Option<Duration>,
+    ) -> crate::Result<Vec<(String, Vec<StreamEntry>)>> {
+        let frame = XRead::new(streams, count, block).into_frame();
+
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Null => Ok(vec![]),
+            Frame::Array(streams) => streams
+                .into_iter()
+                .map(|stream| {
+                    let mut parts = stream.try_into_vec()?.into_iter();
+
+                    match (parts.next(), parts.next(), parts.next()) {
+                        (Some(Frame::Bulk(key)), Some(Frame::Array(entries)), None) => {
+                            let key = String::from_utf8(key.to_vec())?;
+                            let entries = entries
+                                .into_iter()
+                                .map(StreamEntry::from_frame)
+                                .collect::<crate::Result<_>>()?;
+
+                            Ok((key, entries))
+                        }
+                        _ => Err("protocol error; invalid XREAD response".into()),
+                    }
+                })
+                .collect(),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 将 `values` 依次推入 `key` 中存储的列表的头部，返回推入之后列表的长度。
+    ///
+    /// # 示例
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     client.lpush("list", &["a".into(), "b".into()]).await.unwrap();
+    ///     assert_eq!(Some("b".into()), client.lpop("list").await.unwrap());
+    /// }
+    /// ```
+    #[instrument(skip(self, values), fields(command = "lpush"))]
+    pub async fn lpush(&mut self, key: &str, values: &[Bytes]) -> crate::Result<u64> {
+        let frame = LPush::new(key, values).into_frame();
+
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(len) => Ok(len),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 将 `values` 依次推入 `key` 中存储的列表的尾部，返回推入之后列表的长度。
+    #[instrument(skip(self, values), fields(command = "rpush"))]
+    pub async fn rpush(&mut self, key: &str, values: &[Bytes]) -> crate::Result<u64> {
+        let frame = RPush::new(key, values).into_frame();
+
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(len) => Ok(len),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 移除并返回 `key` 中存储的列表的第一个元素。列表为空或者键不存在时返回 `None`。
+    #[instrument(skip(self), fields(command = "lpop"))]
+    pub async fn lpop(&mut self, key: &str) -> crate::Result<Option<Bytes>> {
+        let frame = LPop::new(key).into_frame();
+
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Bulk(value) => Ok(Some(value)),
+            Frame::Null => Ok(None),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 返回所有与 glob 风格的 `pattern` 匹配的键，模式支持 `*` 和 `?`。
+    ///
+    /// 服务器需要遍历整个键空间，只适合调试和小型数据集。
+    ///
+    /// # 示例
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     for key in client.keys("user:*").await.unwrap() {
+    ///         println!("{}", key);
+    ///     }
+    /// }
+    /// ```
+    #[instrument(skip(self), fields(command = "keys"))]
+    pub async fn keys(&mut self, pattern: &str) -> crate::Result<Vec<String>> {
+        let frame = Keys::new(pattern).into_frame();
+
+        self.write_request(frame).await?;
+
+        self.read_response()
+            .await?
+            .try_into_vec()?
+            .into_iter()
+            .map(|key| match key {
+                Frame::Bulk(key) => Ok(String::from_utf8(key.to_vec())?),
+                frame => Err(frame.to_error()),
+            })
+            .collect()
+    }
+
+    /// 返回数据库中键的数量。
+    #[instrument(skip(self), fields(command = "dbsize"))]
+    pub async fn dbsize(&mut self) -> crate::Result<u64> {
+        let frame = DbSize::new().into_frame();
+
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(len) => Ok(len),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 返回 `key` 中存储的列表的长度。键不存在时返回 `0`。
+    #[instrument(skip(self), fields(command = "llen"))]
+    pub async fn llen(&mut self, key: &str) -> crate::Result<u64> {
+        let frame = LLen::new(key).into_frame();
+
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(len) => Ok(len),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 设置 `key` 中存储的哈希的字段，返回新添加的字段数量。被覆盖的字段的过期时间被移除。
+    ///
+    /// # 示例
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    /// use std::time::Duration;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     client.hset("session:1", &[("user", "alice".into())]).await.unwrap();
+    ///     client.hincrby("session:1", "hits", 1).await.unwrap();
+    ///
+    ///     // 只有 `hits` 字段在一分钟之后过期，`user` 字段保留。
+    ///     client.hexpire("session:1", Duration::from_secs(60), &["hits"]).await.unwrap();
+    /// }
+    /// ```
+    #[instrument(skip(self), fields(command = "hset"))]
+    pub async fn hset(&mut self, key: &str, pairs: &[(&str, Bytes)]) -> crate::Result<u64> {
+        let frame = HSet::new(key, pairs).into_frame();
+
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(added) => Ok(added),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 返回 `key` 中存储的哈希的字段的值。键或者字段不存在时返回 `None`。
+    #[instrument(skip(self), fields(command = "hget"))]
+    pub async fn hget(&mut self, key: &str, field: &str) -> crate::Result<Option<Bytes>> {
+        let frame = HGet::new(key, field).into_frame();
+
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Bulk(value) => Ok(Some(value)),
+            Frame::Null => Ok(None),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 将 `key` 中存储的哈希的字段中的整数加上 `increment`，返回新值。字段不存在时视为 `0`。
+    #[instrument(skip(self), fields(command = "hincrby"))]
+    pub async fn hincrby(&mut self, key: &str, field: &str, increment: i64) -> crate::Result<u64> {
+        let frame = HIncrBy::new(key, field, increment).into_frame();
+
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(value) => Ok(value),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 将 `key` 中存储的哈希的字段中的浮点数加上 `increment`，返回新值。字段不存在时视为 `0`。
+    #[instrument(skip(self), fields(command = "hincrbyfloat"))]
+    pub async fn hincrbyfloat(
+        &mut self,
+        key: &str,
+        field: &str,
+        increment: f64,
+    ) -> crate::Result<f64> {
+        let frame = HIncrByFloat::new(key, field, increment).into_frame();
+
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Bulk(value) => Ok(std::str::from_utf8(&value)?.parse()?),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 使 `key` 中存储的哈希的 `fields` 在 `duration` 之后过期，`duration` 以秒为单位发送。
+    ///
+    /// 返回每个字段的结果：`Some(1)` 表示设置了过期时间，`Some(2)` 表示 `duration` 为 0、字段被立即删除，
+    /// `None` 表示键或者字段不存在。
+    #[instrument(skip(self), fields(command = "hexpire"))]
+    pub async fn hexpire(
+        &mut self,
+        key: &str,
+        duration: Duration,
+        fields: &[&str],
+    ) -> crate::Result<Vec<Option<u64>>> {
+        let frame = HExpire::new(key, duration, fields).into_frame();
+
+        self.write_request(frame).await?;
+
+        self.read_response()
+            .await?
+            .try_into_vec()?
+            .into_iter()
+            .map(|result| match result {
+                Frame::Integer(result) => Ok(Some(result)),
+                Frame::Null => Ok(None),
+                frame => Err(frame.to_error()),
+            })
+            .collect()
+    }
+
+    /// 将 `members` 添加到 `key` 中存储的集合，返回新添加的成员数量。已经存在的成员不计算在内。
+    ///
+    /// # 示例
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     client.sadd("tags:1", &["rust".into(), "redis".into()]).await.unwrap();
+    ///     client.sadd("tags:2", &["rust".into(), "tokio".into()]).await.unwrap();
+    ///
+    ///     let common = client.sinter(&["tags:1".into(), "tags:2".into()]).await.unwrap();
+    ///     assert_eq!(vec![bytes::Bytes::from("rust")], common);
+    /// }
+    /// ```
+    #[instrument(skip(self), fields(command = "sadd"))]
+    pub async fn sadd(&mut self, key: &str, members: &[Bytes]) -> crate::Result<u64> {
+        let frame = SAdd::new(key, members).into_frame();
+
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(added) => Ok(added),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 返回 `key` 中存储的集合的所有成员，按字节序排列。键不存在时返回空列表。
+    #[instrument(skip(self), fields(command = "smembers"))]
+    pub async fn smembers(&mut self, key: &str) -> crate::Result<Vec<Bytes>> {
+        let frame = SMembers::new(key).into_frame();
+
+        self.write_request(frame).await?;
+
+        self.read_members().await
+    }
+
+    /// 返回 `keys` 中存储的集合的交集。不存在的键视为空集合。
+    #[instrument(skip(self), fields(command = "sinter"))]
+    pub async fn sinter(&mut self, keys: &[String]) -> crate::Result<Vec<Bytes>> {
+        self.set_operation(SetOperation::Inter, keys).await
+    }
+
+    /// 返回 `keys` 中存储的集合的并集。不存在的键视为空集合。
+    #[instrument(skip(self), fields(command = "sunion"))]
+    pub async fn sunion(&mut self, keys: &[String]) -> crate::Result<Vec<Bytes>> {
+        self.set_operation(SetOperation::Union, keys).await
+    }
+
+    /// 返回 `keys` 中第一个集合与其他集合的差集。不存在的键视为空集合。
+    #[instrument(skip(self), fields(command = "sdiff"))]
+    pub async fn sdiff(&mut self, keys: &[String]) -> crate::Result<Vec<Bytes>> {
+        self.set_operation(SetOperation::Diff, keys).await
+    }
+
+    /// 将 `keys` 中存储的集合的交集存储到 `destination` 中，返回结果的成员数量。
+    ///
+    /// `destination` 原有的值被替换，结果为空时删除 `destination`。
+    #[instrument(skip(self), fields(command = "sinterstore"))]
+    pub async fn sinterstore(&mut self, destination: &str, keys: &[String]) -> crate::Result<u64> {
+        self.set_operation_store(SetOperation::Inter, destination, keys)
+            .await
+    }
+
+    /// 将 `keys` 中存储的集合的并集存储到 `destination` 中，返回结果的成员数量。参见 `sinterstore`。
+    #[instrument(skip(self), fields(command = "sunionstore"))]
+    pub async fn sunionstore(&mut self, destination: &str, keys: &[String]) -> crate::Result<u64> {
+        self.set_operation_store(SetOperation::Union, destination, keys)
+            .await
+    }
+
+    /// 将 `keys` 中第一个集合与其他集合的差集存储到 `destination` 中，返回结果的成员数量。
+    /// 参见 `sinterstore`。
+    #[instrument(skip(self), fields(command = "sdiffstore"))]
+    pub async fn sdiffstore(&mut self, destination: &str, keys: &[String]) -> crate::Result<u64> {
+        self.set_operation_store(SetOperation::Diff, destination, keys)
+            .await
+    }
+
+    /// 移除并返回 `keys` 中第一个非空列表的第一个元素，以及该列表的键。
+    ///
+    /// 所有列表都为空时，服务器等待直到有元素被推入，或者经过 `timeout`。`timeout` 为 `None`
+    /// 表示一直等待。超时时返回 `None`。服务器只支持毫秒精度的超时时间。
+    ///
+    /// # 示例
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    /// use std::time::Duration;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     let keys = ["high".to_string(), "low".to_string()];
+    ///
+    ///     if let Some((key, value)) = client.blpop(&keys, Some(Duration::from_secs(5))).await.unwrap() {
+    ///         println!("{}: {:?}", key, value);
+    ///     }
+    /// }
+    /// ```
+    #[instrument(skip(self), fields(command = "blpop"))]
+    pub async fn blpop(
+        &mut self,
+        keys: &[String],
+        timeout: Option<Duration>,
+    ) -> crate::Result<Option<(String, Bytes)>> {
+        let frame = BLPop::new(keys, timeout).into_frame();
+
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Null => Ok(None),
+            Frame::Array(parts) => {
+                let mut parts = parts.into_iter();
+
+                match (parts.next(), parts.next(), parts.next()) {
+                    (Some(Frame::Bulk(key)), Some(Frame::Bulk(value)), None) => {
+                        Ok(Some((String::from_utf8(key.to_vec())?, value)))
+                    }
+                    _ => Err("protocol error; invalid BLPOP response".into()),
+                }
+            }
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 将任务 `payload` 添加到名为 `queue` 的工作队列的末尾，返回添加之后队列中等待的任务数量。
+    ///
+    /// 工作队列是一个普通的列表：`enqueue` 使用 `RPUSH` 添加任务，`dequeue` 使用 `BLPOP` 取出任务，
+    /// 因此任务按照添加的顺序被处理。多个工作者可以同时从同一个队列中取出任务，每个任务只会被其中一个取出。
+    ///
+    /// 注意任务在被取出的同时就从队列中删除了。如果工作者在处理任务的过程中崩溃，任务会丢失；
+    /// 需要“至少一次”语义的应用程序应当自行记录正在处理的任务。
+    ///
+    /// # 示例
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    /// use std::time::Duration;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut producer = Client::connect("localhost:6379").await.unwrap();
+    ///     let mut worker = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     producer.enqueue("jobs", "resize image 42".into()).await.unwrap();
+    ///
+    ///     while let Some(job) = worker.dequeue("jobs", Some(Duration::from_secs(1))).await.unwrap() {
+    ///         println!("processing {:?}", job);
+    ///     }
+    /// }
+    /// ```
+    #[instrument(skip(self, payload))]
+    pub async fn enqueue(&mut self, queue: &str, payload: Bytes) -> crate::Result<u64> {
+        self.rpush(queue, &[payload]).await
+    }
+
+    /// 从名为 `queue` 的工作队列的头部取出一个任务。
+    ///
+    /// 队列为空时等待直到有任务被添加，或者经过 `timeout`（`None` 表示一直等待）。超时时返回 `None`。
+    /// 参见 [`enqueue`](Client::enqueue)。
+    #[instrument(skip(self))]
+    pub async fn dequeue(
+        &mut self,
+        queue: &str,
+        timeout: Option<Duration>,
+    ) -> crate::Result<Option<Bytes>> {
+        let popped = self.blpop(&[queue.to_string()], timeout).await?;
+        Ok(popped.map(|(_, payload)| payload))
+    }
+
+    /// 返回连接的 ID。
+    ///
+    /// 连接的 ID 在服务器上是唯一的。开启了 [`client_pubsub_metadata`](Client::client_pubsub_metadata)
+    /// 的订阅者收到的消息以这个 ID 标识发布者。
+    #[instrument(skip(self), fields(command = "client"))]
+    pub async fn client_id(&mut self) -> crate::Result<u64> {
+        let frame = ClientCommand::Id.into_frame();
+
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(id) => Ok(id),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 开启或关闭发布/订阅消息的元数据。
+    ///
+    /// 开启后，这个连接之后订阅时收到的每条消息都附带发布者的连接 ID 和发布时间，
+    /// [`Message::metadata`] 和 [`PubSubEvent::MessageWithMetadata`] 中包含这些信息，可以用来追溯消息的来源。
+    /// 需要在 [`subscribe`](Client::subscribe) 之前调用。
+    ///
+    /// # 示例
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///     client.client_pubsub_metadata(true).await.unwrap();
+    ///
+    ///     let mut subscriber = client.subscribe(vec!["foo".into()]).await.unwrap();
+    ///
+    ///     if let Some(msg) = subscriber.next_message().await.unwrap() {
+    ///         println!("{:?} from {:?}", msg.content, msg.metadata);
+    ///     }
+    /// }
+    /// ```
+    #[instrument(skip(self), fields(command = "client"))]
+    pub async fn client_pubsub_metadata(&mut self, on: bool) -> crate::Result<()> {
+        let frame = ClientCommand::PubSubMetadata(on).into_frame();
+
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(response) if response == "OK" => Ok(()),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 开启或关闭服务器辅助的客户端缓存。
+    ///
+    /// 开启后，服务器在这个连接读取过的键被修改时推送失效通知。`Client` 在读取响应时收集这些通知，
+    /// 不会把它们当作响应返回。通常不需要直接调用此方法，[`CachingClient`](crate::clients::CachingClient)
+    /// 基于它实现了本地缓存。
+    #[instrument(skip(self), fields(command = "client"))]
+    pub async fn client_tracking(&mut self, on: bool) -> crate::Result<()> {
+        let frame = ClientCommand::Tracking(on).into_frame();
+
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(response) if response == "OK" => {
+                // 关闭之前收到的通知已经没有意义了。
+                self.invalidations = if on { Some(vec![]) } else { None };
+                Ok(())
+            }
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 取走开启 `CLIENT TRACKING` 之后收到的失效通知，包括已经到达套接字、尚未读取的通知。
+    ///
+    /// 只能在没有等待中的响应时调用：此时服务器发送的任何帧都应当是失效通知。
+    pub(crate) fn take_invalidations(&mut self) -> crate::Result<Vec<Invalidation>> {
+        if self.invalidations.is_none() {
+            return Ok(vec![]);
+        }
+
+        while let Some(frame) = self.connection.try_read_frame()? {
+            debug!(push = ?frame);
+
+            match parse_invalidation(frame) {
+                Ok(invalidation) => self
+                    .invalidations
+                    .get_or_insert_with(Vec::new)
+                    .push(invalidation),
+                Err(frame) => return Err(frame.to_error()),
+            }
+        }
+
+        Ok(self
+            .invalidations
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default())
+    }
+
+    /// 将 `message` 发送到给定的 `channel`。
+    ///
+    /// 返回当前监听频道的订阅者数量。无法保证这些订阅者会收到消息，因为他们可能随时断开连接。
+    ///
+    /// # 示例
+    ///
+    /// 演示基本用法。
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     let val = client.publish("foo", "bar".into()).await.unwrap();
+    ///     println!("Got = {:?}", val);
+    /// }
+    /// ```
+    #[instrument(skip(self, message), fields(command = "publish"))]
+    pub async fn publish(&mut self, channel: &str, message: Bytes) -> crate::Result<u64> {
+        // 将 `Publish` 命令转换为帧
+        let frame = Publish::new(channel, message).into_frame();
+
+        // 将帧写入套接字
+        self.write_request(frame).await?;
+
+        // 读取响应
+        match self.read_response().await? {
+            Frame::Integer(response) => Ok(response),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 将 `messages` 按顺序发送到给定的 `channel`。
+    ///
+    /// 所有消息通过一个 `MPUBLISH` 请求发送，其他发布者的消息不会插入其中。返回当前监听频道的订阅者数量。
+    ///
+    /// # 示例
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     let val = client
+    ///         .mpublish("foo", vec!["bar".into(), "baz".into()])
+    ///         .await
+    ///         .unwrap();
+    ///     println!("Got = {:?}", val);
+    /// }
+    /// ```
+    #[instrument(skip(self, messages), fields(command = "mpublish"))]
+    pub async fn mpublish(&mut self, channel: &str, messages: Vec<Bytes>) -> crate::Result<u64> {
+        let frame = Publish::new_multi(channel, messages).into_frame();
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(response) => Ok(response),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 返回至少有一个订阅者的频道。`pattern` 不为 `None` 时只返回与 glob 风格的模式匹配的频道。
+    ///
+    /// # 示例
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     for channel in client.pubsub_channels(Some("news.*")).await.unwrap() {
+    ///         println!("{}", channel);
+    ///     }
+    /// }
+    /// ```
+    #[instrument(skip(self), fields(command = "pubsub"))]
+    pub async fn pubsub_channels(&mut self, pattern: Option<&str>) -> crate::Result<Vec<String>> {
+        let frame = PubSubCommand::Channels(pattern.map(str::to_string)).into_frame();
+        self.write_request(frame).await?;
+
+        self.read_response()
+            .await?
+            .try_into_vec()?
+            .into_iter()
+            .map(|channel| match channel {
+                Frame::Bulk(channel) => Ok(String::from_utf8(channel.to_vec())?),
+                frame => Err(frame.to_error()),
+            })
+            .collect()
+    }
+
+    /// 返回 `channels` 中每个频道的订阅者数量，顺序与 `channels` 相同。
+    #[instrument(skip(self), fields(command = "pubsub"))]
+    pub async fn pubsub_numsub(
+        &mut self,
+        channels: &[String],
+    ) -> crate::Result<Vec<(String, u64)>> {
+        let frame = PubSubCommand::NumSub(channels.to_vec()).into_frame();
+        self.write_request(frame).await?;
+
+        let mut values = match self.read_response().await? {
+            Frame::Array(values) => values.into_iter(),
+            frame => return Err(frame.to_error()),
+        };
+
+        let mut counts = vec![];
+
+        while let (Some(channel), Some(count)) = (values.next(), values.next()) {
+            match (channel, count) {
+                (Frame::Bulk(channel), Frame::Integer(count)) => {
+                    counts.push((String::from_utf8(channel.to_vec())?, count))
+                }
+                (frame, _) => return Err(frame.to_error()),
+            }
+        }
+
+        Ok(counts)
+    }
+
+    /// 阻塞直到之前的写命令被至少 `numreplicas` 个副本确认，或者经过 `timeout`，返回确认了写入的副本数量。
+    ///
+    /// `timeout` 为 `None` 表示一直等待。副本每处理一批写命令就向主节点确认一次，超时时返回已经确认的副本数量。
+    #[instrument(skip(self), fields(command = "wait"))]
+    pub async fn wait(
+        &mut self,
+        numreplicas: u64,
+        timeout: Option<Duration>,
+    ) -> crate::Result<u64> {
+        let frame = Wait::new(numreplicas, timeout).into_frame();
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(acked) => Ok(acked),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 以 `username` 用户的身份认证连接。之后的命令都以该用户的权限执行。
+    ///
+    /// # 示例
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     client.auth("admin", "secret").await.unwrap();
+    ///     assert_eq!("admin", client.acl_whoami().await.unwrap());
+    /// }
+    /// ```
+    #[instrument(skip(self, password), fields(command = "auth"))]
+    pub async fn auth(&mut self, username: &str, password: &str) -> crate::Result<()> {
+        let frame = Auth::new(username, password).into_frame();
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(response) if response == "OK" => Ok(()),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 返回连接当前的用户名。
+    #[instrument(skip(self), fields(command = "acl"))]
+    pub async fn acl_whoami(&mut self) -> crate::Result<String> {
+        let frame = Acl::WhoAmI.into_frame();
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Bulk(name) => Ok(String::from_utf8(name.to_vec())?),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 返回服务器上所有用户的描述，例如 `user app on ~app:* +get +set`。
+    #[instrument(skip(self), fields(command = "acl"))]
+    pub async fn acl_list(&mut self) -> crate::Result<Vec<String>> {
+        let frame = Acl::List.into_frame();
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Array(users) => users
+                .into_iter()
+                .map(|user| match user {
+                    Frame::Bulk(user) => Ok(String::from_utf8(user.to_vec())?),
+                    frame => Err(frame.to_error()),
+                })
+                .collect(),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 返回与 `pattern` 匹配的服务器配置参数及其值，`pattern` 为参数名称或者 `*`。
+    ///
+    /// # 示例
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     for (parameter, value) in client.config_get("*").await.unwrap() {
+    ///         println!("{} = {}", parameter, value);
+    ///     }
+    /// }
+    /// ```
+    #[instrument(skip(self), fields(command = "config"))]
+    pub async fn config_get(&mut self, pattern: &str) -> crate::Result<Vec<(String, String)>> {
+        let frame = ConfigCommand::Get(pattern.to_string()).into_frame();
+        self.write_request(frame).await?;
+
+        let mut values = match self.read_response().await? {
+            Frame::Array(values) => values.into_iter(),
+            frame => return Err(frame.to_error()),
+        };
+
+        let mut parameters = vec![];
+
+        while let (Some(parameter), Some(value)) = (values.next(), values.next()) {
+            match (parameter, value) {
+                (Frame::Bulk(parameter), Frame::Bulk(value)) => parameters.push((
+                    String::from_utf8(parameter.to_vec())?,
+                    String::from_utf8(value.to_vec())?,
+                )),
+                (frame, _) => return Err(frame.to_error()),
+            }
+        }
+
+        Ok(parameters)
+    }
+
+    /// 在运行时修改服务器配置参数 `parameter` 的值。
+    ///
+    /// # 示例
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     client.config_set("maxclients", "1000").await.unwrap();
+    /// }
+    /// ```
+    #[instrument(skip(self), fields(command = "config"))]
+    pub async fn config_set(&mut self, parameter: &str, value: &str) -> crate::Result<()> {
+        let frame = ConfigCommand::Set(parameter.to_string(), value.to_string()).into_frame();
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(response) if response == "OK" => Ok(()),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 返回服务器的信息和统计数据。`section` 为 `None` 时返回所有部分。
+    ///
+    /// # 示例
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     println!("{}", client.info(Some("clients")).await.unwrap());
+    /// }
+    /// ```
+    #[instrument(skip(self), fields(command = "info"))]
+    pub async fn info(&mut self, section: Option<&str>) -> crate::Result<String> {
+        let frame = Info::new(section.map(str::to_string)).into_frame();
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Bulk(info) => Ok(String::from_utf8(info.to_vec())?),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 返回延迟监控事件 `event`（例如 `command`、`expire-cycle`）的所有采样，按时间顺序排列。
+    ///
+    /// 每个采样是记录的时间（Unix 时间戳，秒）和延迟，延迟精确到毫秒。服务器没有设置
+    /// `latency_monitor_threshold` 或者事件从未达到阈值时返回空列表。
+    ///
+    /// # 示例
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     for (time, latency) in client.latency_history("command").await.unwrap() {
+    ///         println!("{}: {:?}", time, latency);
+    ///     }
+    /// }
+    /// ```
+    #[instrument(skip(self), fields(command = "latency"))]
+    pub async fn latency_history(&mut self, event: &str) -> crate::Result<Vec<(u64, Duration)>> {
+        let frame = Latency::History(event.to_string()).into_frame();
+        self.write_request(frame).await?;
+
+        let samples = match self.read_response().await? {
+            Frame::Array(samples) => samples,
+            frame => return Err(frame.to_error()),
+        };
+
+        samples
+            .into_iter()
+            .map(|sample| match sample {
+                Frame::Array(sample) => match &sample[..] {
+                    [Frame::Integer(time), Frame::Integer(latency_ms)] => {
+                        Ok((*time, Duration::from_millis(*latency_ms)))
+                    }
+                    _ => Err(Frame::Array(sample).to_error()),
+                },
+                frame => Err(frame.to_error()),
+            })
+            .collect()
+    }
+
+    /// 删除延迟监控的事件 `events`，`events` 为空时删除所有事件。返回被删除的事件数量。
+    #[instrument(skip(self), fields(command = "latency"))]
+    pub async fn latency_reset(&mut self, events: &[String]) -> crate::Result<u64> {
+        let frame = Latency::Reset(events.to_vec()).into_frame();
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(count) => Ok(count),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 请求服务器在后台将数据库的快照写入快照文件。
+    ///
+    /// 快照开始之后立即返回，不等待写入完成。已经有正在进行的快照时返回错误。
+    /// 快照的进度和结果可以通过 `info(Some("persistence"))` 查看。
+    #[instrument(skip(self), fields(command = "bgsave"))]
+    pub async fn bgsave(&mut self) -> crate::Result<()> {
+        let frame = BgSave::new().into_frame();
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(_) => Ok(()),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 使服务器成为 `host:port` 上的主节点的副本。
+    ///
+    /// 服务器在后台连接到主节点并同步数据，该方法不等待同步完成。同步的进度可以通过
+    /// `info(Some("replication"))` 查看。成为副本之后，服务器拒绝写命令。
+    ///
+    /// # 示例
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6380").await.unwrap();
+    ///
+    ///     client.replicaof("localhost", 6379).await.unwrap();
+    /// }
+    /// ```
+    #[instrument(skip(self), fields(command = "replicaof"))]
+    pub async fn replicaof(&mut self, host: &str, port: u16) -> crate::Result<()> {
+        let frame = ReplicaOf::new(Some((host.to_string(), port))).into_frame();
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(_) => Ok(()),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 使服务器停止复制，重新成为主节点。已经复制的数据被保留。
+    #[instrument(skip(self), fields(command = "replicaof"))]
+    pub async fn replicaof_no_one(&mut self) -> crate::Result<()> {
+        let frame = ReplicaOf::new(None).into_frame();
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(_) => Ok(()),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 发送由 `args` 组成的任意命令，并原样返回服务器的响应帧。
+    ///
+    /// 服务器以错误响应时返回 `Frame::Error`，而不是 `Err`；只有连接出错时才返回 `Err`。
+    /// 适用于类型化的 API 尚未包装的命令，`mini-redis-cli` 的交互模式也通过它执行命令。
+    ///
+    /// # 示例
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    /// use bytes::Bytes;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     let args = ["incr", "counter"].map(Bytes::from);
+    ///     let reply = client.raw_command(args).await.unwrap();
+    ///     println!("{}", reply);
+    /// }
+    /// ```
+    #[instrument(skip(self, args))]
+    pub async fn raw_command(
+        &mut self,
+        args: impl IntoIterator<Item = Bytes>,
+    ) -> crate::Result<Frame> {
+        let mut frame = Frame::array();
+        for arg in args {
+            frame.push_bulk(arg);
+        }
+
+        self.write_request(frame).await?;
+        self.read_reply().await
+    }
+
+    /// 以流水线方式发送多个命令，并按顺序返回每个命令的响应帧。
+    ///
+    /// 所有命令先被写入连接，之后再依次读取响应，整批命令只需要一次往返，适合批量导入数据。
+    /// 等待响应期间，尚未发送完的命令会继续写入套接字，因此即使命令很多，也不会因为双方的缓冲区写满而死锁。
+    ///
+    /// 与 `raw_command` 一样，服务器的错误响应以 `Frame::Error` 返回，只有连接出错时才返回 `Err`。
+    ///
+    /// # 示例
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    /// use bytes::Bytes;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     let commands = (0..100).map(|i| {
+    ///         vec![Bytes::from("set"), Bytes::from(format!("key:{}", i)), Bytes::from("value")]
+    ///     });
+    ///     let replies = client.pipeline(commands).await.unwrap();
+    ///     assert_eq!(100, replies.len());
+    /// }
+    /// ```
+    #[instrument(skip(self, commands))]
+    pub async fn pipeline(
+        &mut self,
+        commands: impl IntoIterator<Item = Vec<Bytes>>,
+    ) -> crate::Result<Vec<Frame>> {
+        // 注册了中间件时，记录每个请求及其发送时间，以便与对应的响应一起交给 `after_receive`。
+        let mut sent = VecDeque::new();
+        let mut count = 0;
+
+        // 流水线中的命令全部发送给主节点，响应按顺序从同一个连接读取。
+        self.pending_replica = None;
+
+        for args in commands {
+            let mut frame = Frame::array();
+            for arg in args {
+                frame.push_bulk(arg);
+            }
+
+            for middleware in &self.middleware {
+                middleware.before_send(&mut frame);
+            }
+
+            debug!(request = ?frame);
+
+            self.connection.queue_frame(&frame)?;
+            count += 1;
+
+            if !self.middleware.is_empty() {
+                sent.push_back((frame, Instant::now()));
+            }
+        }
+
+        let mut replies = Vec::with_capacity(count);
+
+        for _ in 0..count {
+            self.last_request = sent.pop_front();
+            replies.push(self.read_reply().await?);
+        }
+
+        Ok(replies)
+    }
+
+    /// 发送 `ASKING`，允许下一个命令访问正在迁入该节点的槽中的键。
+    ///
+    /// 收到 `ASK slot host:port` 重定向后，在目标节点上先调用此方法，再重新发送原来的命令。
+    /// `ClusterClient` 会自动处理 `ASK` 重定向。
+    ///
+    /// # 示例
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6380").await.unwrap();
+    ///
+    ///     client.asking().await.unwrap();
+    ///     let value = client.get("foo").await.unwrap();
+    ///     println!("Got = {:?}", value);
+    /// }
+    /// ```
+    #[instrument(skip(self), fields(command = "asking"))]
+    pub async fn asking(&mut self) -> crate::Result<()> {
+        let frame = Asking::new().into_frame();
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(response) if response == "OK" => Ok(()),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 请求服务器关闭连接，并消耗客户端。
+    ///
+    /// 服务器写入 `OK` 响应之后关闭连接，因此之前发送的命令都已经执行完毕。与副本的连接（参见
+    /// `connect_with_replicas`）直接关闭。
+    ///
+    /// # 示例
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     client.set("foo", "bar".into()).await.unwrap();
+    ///     client.quit().await.unwrap();
+    /// }
+    /// ```
+    #[instrument(skip(self), fields(command = "quit"))]
+    pub async fn quit(mut self) -> crate::Result<()> {
+        let frame = Quit::new().into_frame();
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(response) if response == "OK" => Ok(()),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 订阅客户端到指定的频道。
+    ///
+    /// 一旦客户端发出订阅命令，它不再能发出任何非发布/订阅命令。该函数消耗 `self` 并返回一个 `Subscriber`。
+    ///
+    /// `Subscriber` 用于接收消息以及管理客户端订阅的频道列表。
+    #[instrument(skip(self), fields(command = "subscribe"))]
+    pub async fn subscribe(mut self, channels: Vec<String>) -> crate::Result<Subscriber> {
+        // 向服务器发出订阅命令并等待确认。
+        // 客户端随后将转换为“订阅者”状态，从那时起只能发出发布/订阅命令。
+        let mut pending_events = VecDeque::new();
+        self.subscribe_cmd(&channels, &mut pending_events).await?;
+
+        // 返回 `Subscriber` 类型
+        let mut subscriber = Subscriber {
+            client: self,
+            subscribed_channels: vec![],
+            pending_events,
+        };
+        subscriber.add_subscribed(&channels);
+
+        Ok(subscriber)
+    }
+
+    /// 核心的 `SUBSCRIBE` 逻辑，由各种订阅函数使用
+    ///
+    /// 已经订阅的频道上的消息可能先于确认到达，它们被缓存到 `pending` 中。
+    async fn subscribe_cmd(
+        &mut self,
+        channels: &[String],
+        pending: &mut VecDeque<PubSubEvent>,
+    ) -> crate::Result<()> {
+        // 将 `Subscribe` 命令转换为帧
+        let frame = Subscribe::new(channels.to_vec()).into_frame();
+
+        // 将帧写入套接字
+        self.write_request(frame).await?;
+
+        // 对于每个被订阅的频道，服务器会响应一个确认订阅该频道的消息。
+        let mut channels = channels.iter();
+        let mut next = channels.next();
+
+        while let Some(channel) = next {
+            // 读取响应并验证它是订阅确认。
+            match PubSubEvent::from_frame(self.read_response().await?)? {
+                PubSubEvent::Subscribed {
+                    channel: schannel, ..
+                } if schannel == *channel => next = channels.next(),
+                event @ PubSubEvent::Message { .. }
+                | event @ PubSubEvent::MessageWithMetadata { .. }
+                | event @ PubSubEvent::PMessage { .. }
+                | event @ PubSubEvent::Lagged { .. } => pending.push_back(event),
+                event => return Err(event.to_error()),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// `SINTER`、`SUNION` 和 `SDIFF` 的共同逻辑
+    async fn set_operation(
+        &mut self,
+        op: SetOperation,
+        keys: &[String],
+    ) -> crate::Result<Vec<Bytes>> {
+        let frame = SetOp::new(op, None, keys).into_frame();
+
+        self.write_request(frame).await?;
+
+        self.read_members().await
+    }
+
+    /// `SINTERSTORE`、`SUNIONSTORE` 和 `SDIFFSTORE` 的共同逻辑
+    async fn set_operation_store(
+        &mut self,
+        op: SetOperation,
+        destination: &str,
+        keys: &[String],
+    ) -> crate::Result<u64> {
+        let frame = SetOp::new(op, Some(destination), keys).into_frame();
+
+        self.write_request(frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(len) => Ok(len),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 读取由批量字符串组成的数组响应，例如集合的成员。
+    async fn read_members(&mut self) -> crate::Result<Vec<Bytes>> {
+        self.read_response()
+            .await?
+            .try_into_vec()?
+            .into_iter()
+            .map(|member| match member {
+                Frame::Bulk(member) => Ok(member),
+                frame => Err(frame.to_error()),
+            })
+            .collect()
+    }
+
+    /// 将请求帧写入套接字。写入之前，帧会经过所有已注册的中间件。
+    async fn write_request(&mut self, mut frame: Frame) -> crate::Result<()> {
+        for middleware in &self.middleware {
+            middleware.before_send(&mut frame);
+        }
+
+        debug!(request = ?frame);
+
+        self.pending_replica = self.route(&frame);
+        let connection = match self.pending_replica {
+            Some(replica) => &mut self.replicas[replica],
+            None => &mut self.connection,
+        };
+
+        // 将帧写入套接字。这会将完整帧写入套接字，必要时会等待。
+        connection.write_frame(&frame).await?;
+
+        if !self.middleware.is_empty() {
+            self.last_request = Some((frame, Instant::now()));
+        }
+
+        Ok(())
+    }
+
+    /// 从套接字读取响应帧。
+    ///
+    /// 如果接收到 `Error` 帧，则将其转换为包含 `ServerError` 的 `Err`。在转换之前，帧会经过所有已注册的中间件。
+    async fn read_response(&mut self) -> crate::Result<Frame> {
+        match self.read_reply().await? {
+            // 将错误帧转换为 `Err`
+            Frame::Error(msg) => Err(ServerError::new(msg).into()),
+            frame => Ok(frame),
+        }
+    }
+
+    /// 决定请求 `frame` 发送给哪一个副本，`None` 表示发送给主节点。
+    fn route(&mut self, frame: &Frame) -> Option<usize> {
+        if self.replicas.is_empty()
+            || self.primary_reads
+            || self.invalidations.is_some()
+            || !is_read_only(frame)
+        {
+            return None;
+        }
+
+        let replica = self.next_replica % self.replicas.len();
+        self.next_replica = self.next_replica.wrapping_add(1);
+        Some(replica)
+    }
+
+    /// 从套接字读取响应帧，`Error` 帧原样返回。帧会经过所有已注册的中间件。
+    async fn read_reply(&mut self) -> crate::Result<Frame> {
+        // 请求被发送给了副本时，从同一个副本读取响应。副本不会推送失效通知。
+        if let Some(replica) = self.pending_replica.take() {
+            let response = self.replicas[replica].read_frame().await?;
+            return self.finish_reply(response);
+        }
+
+        let mut response = self.connection.read_frame().await?;
+
+        // 开启了 `CLIENT TRACKING` 时，响应之前可能有服务器推送的失效通知，将它们收集起来。
+        while let Some(invalidations) = &mut self.invalidations {
+            match response.take().map(parse_invalidation) {
+                Some(Ok(invalidation)) => {
+                    debug!(?invalidation);
+                    invalidations.push(invalidation);
+                    response = self.connection.read_frame().await?;
+                }
+                Some(Err(frame)) => {
+                    response = Some(frame);
+                    break;
+                }
+                None => break,
+            }
+        }
+
+        self.finish_reply(response)
+    }
+
+    /// 将响应交给已注册的中间件。`None` 表示服务器关闭了连接。
+    fn finish_reply(&mut self, mut response: Option<Frame>) -> crate::Result<Frame> {
+        if let (Some(frame), Some((request, sent_at))) = (&mut response, &self.last_request) {
+            let elapsed = sent_at.elapsed();
+
+            for middleware in &self.middleware {
+                middleware.after_receive(request, frame, elapsed);
+            }
+        }
+
+        debug!(?response);
+
+        match response {
+            Some(frame) => Ok(frame),
+            None => {
+                // 接收到 `None` 表示服务器已关闭连接而未发送帧。这是意外的，被表示为“连接被对端重置”错误。
+                let err = Error::new(ErrorKind::ConnectionReset, "connection reset by server");
+
+                Err(err.into())
+            }
+        }
+    }
+}
+
+impl Deref for PrimaryReads<'_> {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        self.client
+    }
+}
+
+impl DerefMut for PrimaryReads<'_> {
+    fn deref_mut(&mut self) -> &mut Client {
+        self.client
+    }
+}
+
+impl Drop for PrimaryReads<'_> {
+    fn drop(&mut self) {
+        self.client.primary_reads = false;
+    }
+}
+
+impl Subscriber {
+    /// 返回当前订阅的频道集合。
+    pub fn get_subscribed(&self) -> &[String] {
+        &self.subscribed_channels
+    }
+
+    /// 接收在订阅频道上发布的下一条消息，必要时等待。
+    ///
+    /// 服务器报告的消息丢失（`PubSubEvent::Lagged`）会被记录日志后跳过；
+    /// 需要感知消息丢失的应用程序应使用 `next_event`。
+    ///
+    /// `None` 表示订阅已被终止。
+    pub async fn next_message(&mut self) -> crate::Result<Option<Message>> {
+        loop {
+            match self.next_event().await? {
+                Some(PubSubEvent::Message { channel, content })
+                | Some(PubSubEvent::PMessage {
+                    channel, content, ..
+                }) => {
+                    return Ok(Some(Message {
+                        channel,
+                        content,
+                        metadata: None,
+                    }))
+                }
+                Some(PubSubEvent::MessageWithMetadata {
+                    channel,
+                    content,
+                    metadata,
+                }) => {
+                    return Ok(Some(Message {
+                        channel,
+                        content,
+                        metadata: Some(metadata),
+                    }))
+                }
+                Some(PubSubEvent::Lagged { channel, skipped }) => {
+                    warn!(%channel, skipped, "subscriber lagged");
+                }
+                Some(event) => return Err(event.to_error()),
+                None => return Ok(None),
+            }
+        }
+    }
+
+    /// 接收连接上的下一个发布/订阅事件，必要时等待。
+    ///
+    /// 与 `next_message` 不同，该方法还会返回订阅和取消订阅的确认，
+    /// 使应用程序可以观察订阅的生命周期变化。
+    ///
+    /// `None` 表示订阅已被终止。
+    pub async fn next_event(&mut self) -> crate::Result<Option<PubSubEvent>> {
+        if let Some(event) = self.pending_events.pop_front() {
+            return Ok(Some(event));
+        }
+
+        match self.client.connection.read_frame().await? {
+            Some(mframe) => {
+                debug!(?mframe);
+
+                PubSubEvent::from_frame(mframe).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// 在订阅状态下向服务器发送 Ping。
+    ///
+    /// 如果没有提供参数，则返回 PONG，否则返回参数的副本。
+    ///
+    /// 在等待响应期间到达的消息不会丢失，它们会被缓存并由后续的 `next_message` 或 `next_event` 调用返回。
+    #[instrument(skip(self), fields(command = "ping"))]
+    pub async fn ping(&mut self, msg: Option<Bytes>) -> crate::Result<Bytes> {
+        let frame = Ping::new(msg.clone()).into_frame();
+        self.client.write_request(frame).await?;
+
+        loop {
+            match PubSubEvent::from_frame(self.client.read_response().await?)? {
+                PubSubEvent::Pong(pong) => {
+                    return Ok(match msg {
+                        Some(_) => pong,
+                        None => Bytes::from_static(b"PONG"),
+                    })
+                }
+                event => self.pending_events.push_back(event),
+            }
+        }
+    }
+
+    /// 将订阅者转换为一个 `Stream`，生成在订阅频道上发布的新消息。
+    ///
+    /// `Subscriber` 本身并不实现流，因为使用安全代码实现这一点并不简单。使用 async/await 需要手动实现 `unsafe` 的流代码。
+    /// 因此，提供了一个转换函数，并结合 `async-stream` crate 来实现返回的流。
+    pub fn into_stream(mut self) -> impl Stream<Item = crate::Result<Message>> {
+        // 使用 `async-stream` crate 中的 `try_stream` 宏。在 Rust 中生成器尚不稳定。
+        // 该 crate 使用宏来模拟基于 async/await 的生成器。存在一些限制，因此请阅读相关文档。
+        try_stream! {
+            while let Some(message) = self.next_message().await? {
+                yield message;
             }
         }
     }
 
     /// 订阅新的频道列表
-    #[instrument(skip(self))]
+    #[instrument(skip(self), fields(command = "subscribe"))]
     pub async fn subscribe(&mut self, channels: &[String]) -> crate::Result<()> {
         // 发出订阅命令
-        self.client.subscribe_cmd(channels).await?;
+        self.client
+            .subscribe_cmd(channels, &mut self.pending_events)
+            .await?;
 
         // 更新已订阅的频道集合。
-        self.subscribed_channels
-            .extend(channels.iter().map(Clone::clone));
+        self.add_subscribed(channels);
 
         Ok(())
     }
 
+    /// 将 `channels` 中尚未订阅的频道加入已订阅的频道集合。服务器不会重复订阅同一个频道，这里也不重复记录。
+    fn add_subscribed(&mut self, channels: &[String]) {
+        for channel in channels {
+            if !self.subscribed_channels.contains(channel) {
+                self.subscribed_channels.push(channel.clone());
+            }
+        }
+    }
+
     /// 取消订阅指定的频道列表
-    #[instrument(skip(self))]
+    #[instrument(skip(self), fields(command = "unsubscribe"))]
     pub async fn unsubscribe(&mut self, channels: &[String]) -> crate::Result<()> {
         let frame = Unsubscribe::new(channels).into_frame();
 
-        debug!(request = ?frame);
-
         // 将帧写入套接字
-        self.client.connection.write_frame(&frame).await?;
+        self.client.write_request(frame).await?;
 
         // 如果输入的频道列表为空，服务器会确认取消订阅所有已订阅的频道，
         // 因此我们断言接收到的取消订阅列表与客户端订阅的列表相匹配
@@ -434,34 +2578,173 @@ impl Subscriber {
             channels.len()
         };
 
-        // 读取响应
-        for _ in 0..num {
-            let response = self.client.read_response().await?;
-
-            match response {
-                Frame::Array(ref frame) => match frame.as_slice() {
-                    [unsubscribe, channel, ..] if *unsubscribe == "unsubscribe" => {
-                        let len = self.subscribed_channels.len();
+        // 读取响应。在取消订阅生效之前发布的消息可能先于确认到达，
+        // 它们被缓存并由后续的 `next_message` 或 `next_event` 调用返回。
+        let mut received = 0;
+        while received < num {
+            match PubSubEvent::from_frame(self.client.read_response().await?)? {
+                PubSubEvent::Unsubscribed { channel, .. } => {
+                    let len = self.subscribed_channels.len();
 
-                        if len == 0 {
-                            // 必须至少有一个频道
-                            return Err(response.to_error());
-                        }
+                    if len == 0 {
+                        // 必须至少有一个频道
+                        return Err(format!("unexpected unsubscribe from `{}`", channel).into());
+                    }
 
-                        // 已取消订阅的频道现在应该存在于订阅列表中
-                        self.subscribed_channels.retain(|c| *channel != &c[..]);
+                    // 已取消订阅的频道现在应该存在于订阅列表中
+                    self.subscribed_channels.retain(|c| *c != channel);
 
-                        // 只应从订阅频道列表中删除一个频道。
-                        if self.subscribed_channels.len() != len - 1 {
-                            return Err(response.to_error());
-                        }
+                    // 只应从订阅频道列表中删除一个频道。
+                    if self.subscribed_channels.len() != len - 1 {
+                        return Err(format!("unexpected unsubscribe from `{}`", channel).into());
                     }
-                    _ => return Err(response.to_error()),
-                },
-                frame => return Err(frame.to_error()),
-            };
+
+                    received += 1;
+                }
+                event @ PubSubEvent::Message { .. }
+                | event @ PubSubEvent::MessageWithMetadata { .. }
+                | event @ PubSubEvent::PMessage { .. }
+                | event @ PubSubEvent::Lagged { .. } => self.pending_events.push_back(event),
+                event => return Err(event.to_error()),
+            }
         }
 
         Ok(())
     }
+
+    /// 取消订阅所有已订阅的频道。
+    ///
+    /// 取消订阅之后，服务器上的连接离开订阅状态。
+    #[instrument(skip(self), fields(command = "unsubscribe"))]
+    pub async fn unsubscribe_all(&mut self) -> crate::Result<()> {
+        self.unsubscribe(&[]).await
+    }
+
+    /// 取消订阅所有频道，并返回底层的 `Client`，使连接可以继续执行普通命令。
+    ///
+    /// 尚未通过 `next_message` 或 `next_event` 读取的消息会被丢弃。
+    #[instrument(skip(self), fields(command = "unsubscribe"))]
+    pub async fn into_client(mut self) -> crate::Result<Client> {
+        self.unsubscribe_all().await?;
+
+        if !self.pending_events.is_empty() {
+            debug!(
+                discarded = self.pending_events.len(),
+                "discarding unread pub/sub events"
+            );
+        }
+
+        Ok(self.client)
+    }
+}
+
+impl PubSubEvent {
+    /// 将服务器推送的数组帧解析为 `PubSubEvent`。
+    ///
+    /// 服务器以如下形式的数组帧响应：
+    ///
+    /// ```text
+    /// [ "subscribe", channel, num-subscribed ]
+    /// [ "unsubscribe", channel, num-subscribed ]
+    /// [ "message", channel, content ]
+    /// [ "message", channel, content, publisher, timestamp ]
+    /// [ "pmessage", pattern, channel, content ]
+    /// [ "pong", message ]
+    /// ```
+    fn from_frame(frame: Frame) -> crate::Result<PubSubEvent> {
+        let event = match frame {
+            Frame::Array(ref parts) => match parts.as_slice() {
+                [kind, channel, Frame::Integer(count)] if *kind == "subscribe" => {
+                    PubSubEvent::Subscribed {
+                        channel: channel.to_string(),
+                        count: *count,
+                    }
+                }
+                [kind, channel, Frame::Integer(count)] if *kind == "unsubscribe" => {
+                    PubSubEvent::Unsubscribed {
+                        channel: channel.to_string(),
+                        count: *count,
+                    }
+                }
+                // 消息内容可以是任意字节，直接取出 bulk 帧中的数据，不经过字符串转换。
+                [kind, channel, content] if *kind == "message" => PubSubEvent::Message {
+                    channel: channel.to_string(),
+                    content: content.as_bytes().ok_or_else(|| frame.to_error())?,
+                },
+                [kind, channel, content, publisher, Frame::Integer(timestamp)]
+                    if *kind == "message" =>
+                {
+                    let publisher = match publisher {
+                        Frame::Integer(id) => Some(*id),
+                        Frame::Null => None,
+                        _ => return Err(frame.to_error()),
+                    };
+
+                    PubSubEvent::MessageWithMetadata {
+                        channel: channel.to_string(),
+                        content: content.as_bytes().ok_or_else(|| frame.to_error())?,
+                        metadata: MessageMetadata {
+                            publisher,
+                            published_at: UNIX_EPOCH + Duration::from_millis(*timestamp),
+                        },
+                    }
+                }
+                [kind, channel, Frame::Integer(skipped)] if *kind == "lagged" => {
+                    PubSubEvent::Lagged {
+                        channel: channel.to_string(),
+                        skipped: *skipped,
+                    }
+                }
+                [kind, msg] if *kind == "pong" => {
+                    PubSubEvent::Pong(msg.as_bytes().ok_or_else(|| frame.to_error())?)
+                }
+                [kind, pattern, channel, content] if *kind == "pmessage" => PubSubEvent::PMessage {
+                    pattern: pattern.to_string(),
+                    channel: channel.to_string(),
+                    content: content.as_bytes().ok_or_else(|| frame.to_error())?,
+                },
+                _ => return Err(frame.to_error()),
+            },
+            frame => return Err(frame.to_error()),
+        };
+
+        Ok(event)
+    }
+
+    /// 将事件转换为“意外事件”错误
+    fn to_error(&self) -> crate::Error {
+        format!("unexpected pub/sub event: {:?}", self).into()
+    }
+}
+
+/// 解析服务器推送的失效通知。`frame` 不是失效通知时原样返回。
+fn parse_invalidation(frame: Frame) -> Result<Invalidation, Frame> {
+    let invalidation = match &frame {
+        Frame::Array(parts) => match parts.as_slice() {
+            [kind, Frame::Null] if *kind == "invalidate" => Some(None),
+            [kind, Frame::Array(keys)] if *kind == "invalidate" => {
+                Some(Some(keys.iter().map(Frame::to_string).collect()))
+            }
+            _ => None,
+        },
+        _ => None,
+    };
+
+    invalidation.ok_or(frame)
+}
+
+/// 请求是否是只读命令，可以发送给副本。
+fn is_read_only(frame: &Frame) -> bool {
+    let name = match frame {
+        Frame::Array(parts) => match parts.first() {
+            Some(Frame::Bulk(name)) => name,
+            _ => return false,
+        },
+        _ => return false,
+    };
+
+    std::str::from_utf8(name)
+        .ok()
+        .and_then(crate::cmd::lookup)
+        .is_some_and(|info| info.flags.contains(&"readonly"))
 }
@@ -0,0 +1,325 @@
+use crate::clients::request::{Command, Response};
+use crate::clients::{Client, ServerError};
+use crate::cluster::{key_slot, SLOT_COUNT};
+use crate::frame::ErrorKind;
+use crate::Result;
+
+use bytes::Bytes;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::time::Duration;
+use tracing::debug;
+
+/// 一个命令最多跟随的 `MOVED` 和 `ASK` 重定向次数。
+const MAX_REDIRECTS: usize = 5;
+
+/// 按哈希槽把命令路由到多个 mini-redis 节点的客户端。
+///
+/// 创建时给出一个静态的节点列表，16384 个哈希槽按顺序平均分配给这些节点，作为初始的槽映射。
+/// 每个命令根据其键所属的槽（见 `cluster::key_slot`）发送给对应的节点，到每个节点的连接在第一次使用时建立。
+///
+/// 如果节点以 `MOVED slot host:port` 错误响应（见 `server::Config::slot_check`），
+/// 客户端会更新槽映射，并将命令重新发送到新的节点。因此，初始映射不必与服务器的实际配置一致。
+///
+/// 槽正在迁移时，节点以 `ASK slot host:port` 错误响应已经迁出的键。客户端向目标节点发送 `ASKING`
+/// 之后在那里重试该命令，但不更新槽映射：迁移完成之前，槽的其他键仍然由原来的节点负责。
+///
+/// # 示例
+///
+/// ```no_run
+/// use mini_redis::clients::ClusterClient;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let mut client = ClusterClient::new(vec!["127.0.0.1:6379", "127.0.0.1:6380"]);
+///
+///     client.set("foo", "bar".into()).await.unwrap();
+///     let value = client.get("foo").await.unwrap();
+///     println!("Got = {:?}", value);
+/// }
+/// ```
+pub struct ClusterClient {
+    /// 已知的节点地址。从 `MOVED` 和 `ASK` 重定向中得知的新节点会被追加到这里。
+    nodes: Vec<String>,
+
+    /// 每个哈希槽所属的节点，存储为 `nodes` 中的下标。
+    slots: Vec<usize>,
+
+    /// 到各个节点的连接，以节点地址为键。
+    connections: HashMap<String, Client>,
+}
+
+impl ClusterClient {
+    /// 使用给定的节点列表创建一个新的 `ClusterClient`。
+    ///
+    /// 此时不会建立任何连接。
+    ///
+    /// # Panics
+    ///
+    /// 如果 `nodes` 为空，则会 panic。
+    pub fn new<T: ToString>(nodes: impl IntoIterator<Item = T>) -> ClusterClient {
+        let nodes: Vec<String> = nodes.into_iter().map(|node| node.to_string()).collect();
+        assert!(!nodes.is_empty(), "at least one node is required");
+
+        // 按顺序将槽平均分配给各个节点。
+        let slots = (0..SLOT_COUNT as usize)
+            .map(|slot| slot * nodes.len() / SLOT_COUNT as usize)
+            .collect();
+
+        ClusterClient {
+            nodes,
+            slots,
+            connections: HashMap::new(),
+        }
+    }
+
+    /// 返回当前认为负责 `key` 的节点地址。
+    pub fn node_for(&self, key: &str) -> &str {
+        &self.nodes[self.slots[key_slot(key.as_bytes()) as usize]]
+    }
+
+    /// 获取键的值。
+    ///
+    /// 与 `Client::get` 相同，但请求被发送到负责 `key` 的节点。
+    pub async fn get(&mut self, key: &str) -> Result<Option<Bytes>> {
+        self.request(key_slot(key.as_bytes()), Command::Get(key.into()))
+            .await
+    }
+
+    /// 设置 `key` 以保存给定的 `value`。
+    ///
+    /// 与 `Client::set` 相同，但请求被发送到负责 `key` 的节点。
+    pub async fn set(&mut self, key: &str, value: Bytes) -> Result<()> {
+        self.request(
+            key_slot(key.as_bytes()),
+            Command::Set(key.into(), value, None),
+        )
+        .await
+    }
+
+    /// 设置 `key` 以保存给定的 `value`。该值在 `expiration` 之后过期。
+    ///
+    /// 与 `Client::set_expires` 相同，但请求被发送到负责 `key` 的节点。
+    pub async fn set_expires(
+        &mut self,
+        key: &str,
+        value: Bytes,
+        expiration: Duration,
+    ) -> Result<()> {
+        let cmd = Command::Set(key.into(), value, Some(expiration));
+        self.request(key_slot(key.as_bytes()), cmd).await
+    }
+
+    /// 设置 `key` 以保存给定的 `value`，并保留键原有的过期时间。
+    ///
+    /// 与 `Client::set_keep_ttl` 相同，但请求被发送到负责 `key` 的节点。
+    pub async fn set_keep_ttl(&mut self, key: &str, value: Bytes) -> Result<()> {
+        let cmd = Command::SetKeepTtl(key.into(), value);
+        self.request(key_slot(key.as_bytes()), cmd).await
+    }
+
+    /// 仅当 `key` 的当前值等于 `expected` 时，将其替换为 `new`。替换成功时返回 `true`。
+    ///
+    /// 与 `Client::compare_and_set` 相同，但请求被发送到负责 `key` 的节点。
+    pub async fn compare_and_set(
+        &mut self,
+        key: &str,
+        expected: Bytes,
+        new: Bytes,
+    ) -> Result<bool> {
+        let cmd = Command::CompareAndSet(key.into(), expected, new);
+        self.request(key_slot(key.as_bytes()), cmd).await
+    }
+
+    /// 将 `key` 中存储的整数加一，并返回新值。
+    ///
+    /// 与 `Client::incr` 相同，但请求被发送到负责 `key` 的节点。
+    pub async fn incr(&mut self, key: &str) -> Result<u64> {
+        self.request(key_slot(key.as_bytes()), Command::Incr(key.into()))
+            .await
+    }
+
+    /// 从 `offset` 开始用 `value` 覆盖 `key` 中存储的字符串，并返回修改后字符串的长度。
+    ///
+    /// 与 `Client::setrange` 相同，但请求被发送到负责 `key` 的节点。
+    pub async fn setrange(&mut self, key: &str, offset: u64, value: Bytes) -> Result<u64> {
+        let cmd = Command::SetRange(key.into(), offset, value);
+        self.request(key_slot(key.as_bytes()), cmd).await
+    }
+
+    /// 返回 `key` 中存储的字符串在 `start` 和 `end`（包含两端）之间的部分。
+    ///
+    /// 与 `Client::getrange` 相同，但请求被发送到负责 `key` 的节点。
+    pub async fn getrange(&mut self, key: &str, start: i64, end: i64) -> Result<Bytes> {
+        let cmd = Command::GetRange(key.into(), start, end);
+        self.request(key_slot(key.as_bytes()), cmd).await
+    }
+
+    /// 将 `key` 中存储的字符串的第 `offset` 位设置为 `value`，并返回该位原来的值。
+    ///
+    /// 与 `Client::setbit` 相同，但请求被发送到负责 `key` 的节点。
+    pub async fn setbit(&mut self, key: &str, offset: u64, value: bool) -> Result<u64> {
+        let cmd = Command::SetBit(key.into(), offset, value);
+        self.request(key_slot(key.as_bytes()), cmd).await
+    }
+
+    /// 返回 `key` 中存储的字符串的第 `offset` 位。
+    ///
+    /// 与 `Client::getbit` 相同，但请求被发送到负责 `key` 的节点。
+    pub async fn getbit(&mut self, key: &str, offset: u64) -> Result<u64> {
+        let cmd = Command::GetBit(key.into(), offset);
+        self.request(key_slot(key.as_bytes()), cmd).await
+    }
+
+    /// 统计 `key` 中存储的字符串中被设置的位的数量，`range` 是可选的字节范围。
+    ///
+    /// 与 `Client::bitcount` 相同，但请求被发送到负责 `key` 的节点。
+    pub async fn bitcount(&mut self, key: &str, range: Option<(i64, i64)>) -> Result<u64> {
+        let cmd = Command::BitCount(key.into(), range);
+        self.request(key_slot(key.as_bytes()), cmd).await
+    }
+
+    /// 将 `elements` 添加到 `key` 中存储的 HyperLogLog。如果估计的基数可能因此改变，则返回 `true`。
+    ///
+    /// 与 `Client::pfadd` 相同，但请求被发送到负责 `key` 的节点。
+    pub async fn pfadd(&mut self, key: &str, elements: &[Bytes]) -> Result<bool> {
+        let cmd = Command::PfAdd(key.into(), elements.to_vec());
+        self.request(key_slot(key.as_bytes()), cmd).await
+    }
+
+    /// 返回 `keys` 中存储的 HyperLogLog 的并集的估计基数。
+    ///
+    /// 并集需要在同一个节点上计算，因此所有的键必须位于同一个哈希槽中（可以使用哈希标签），
+    /// 否则服务器返回 `CROSSSLOT` 错误。
+    pub async fn pfcount(&mut self, keys: &[String]) -> Result<u64> {
+        let slot = keys.first().map_or(0, |key| key_slot(key.as_bytes()));
+        self.request(slot, Command::PfCount(keys.to_vec())).await
+    }
+
+    /// 删除给定的键，返回实际被删除的键的数量。
+    ///
+    /// 键按哈希槽分组，每组单独发送到负责的节点，结果相加。因此，不同槽中的键不会被原子地删除。
+    pub async fn del(&mut self, keys: &[String]) -> Result<u64> {
+        let mut removed = 0;
+
+        for (slot, keys) in group_by_slot(keys) {
+            removed += self.request::<u64>(slot, Command::Del(keys)).await?;
+        }
+
+        Ok(removed)
+    }
+
+    /// 返回给定的键中存在的数量。
+    ///
+    /// 键按哈希槽分组，每组单独发送到负责的节点，结果相加。
+    pub async fn exists(&mut self, keys: &[String]) -> Result<u64> {
+        let mut found = 0;
+
+        for (slot, keys) in group_by_slot(keys) {
+            found += self.request::<u64>(slot, Command::Exists(keys)).await?;
+        }
+
+        Ok(found)
+    }
+
+    /// 将命令发送到负责 `slot` 的节点，并跟随 `MOVED` 和 `ASK` 重定向。
+    async fn request<T>(&mut self, slot: u16, cmd: Command) -> Result<T>
+    where
+        T: TryFrom<Response, Error = crate::Error>,
+    {
+        // `ASK` 重定向只对下一次尝试有效，之后仍然使用槽映射中的节点。
+        let mut ask = None;
+
+        for _ in 0..MAX_REDIRECTS {
+            let (node, asking) = match ask.take() {
+                Some(addr) => (self.node_index(addr), true),
+                None => (self.slots[slot as usize], false),
+            };
+            let client = self.connection(node).await?;
+
+            if asking {
+                client.asking().await?;
+            }
+
+            match cmd.clone().send(client).await {
+                Ok(response) => return T::try_from(response),
+                Err(err) => match parse_redirect(&err) {
+                    Some((ErrorKind::Moved, slot, addr)) => {
+                        debug!(slot, %addr, "redirected");
+                        self.assign(slot, addr);
+                    }
+                    Some((_, slot, addr)) => {
+                        debug!(slot, %addr, "asked");
+                        ask = Some(addr);
+                    }
+                    None => return Err(err),
+                },
+            }
+        }
+
+        Err(format!("too many redirects for slot {}", slot).into())
+    }
+
+    /// 返回到 `nodes[node]` 的连接，必要时建立连接。
+    async fn connection(&mut self, node: usize) -> Result<&mut Client> {
+        let addr = &self.nodes[node];
+
+        if !self.connections.contains_key(addr) {
+            let client = Client::connect(addr.as_str()).await?;
+            self.connections.insert(addr.clone(), client);
+        }
+
+        Ok(self.connections.get_mut(addr).unwrap())
+    }
+
+    /// 记录 `slot` 由 `addr` 负责。
+    fn assign(&mut self, slot: u16, addr: String) {
+        self.slots[slot as usize] = self.node_index(addr);
+    }
+
+    /// 返回 `addr` 在 `nodes` 中的下标，必要时将其追加到 `nodes`。
+    fn node_index(&mut self, addr: String) -> usize {
+        match self.nodes.iter().position(|node| *node == addr) {
+            Some(node) => node,
+            None => {
+                self.nodes.push(addr);
+                self.nodes.len() - 1
+            }
+        }
+    }
+}
+
+/// 将键按哈希槽分组。
+fn group_by_slot(keys: &[String]) -> BTreeMap<u16, Vec<String>> {
+    let mut groups = BTreeMap::new();
+
+    for key in keys {
+        groups
+            .entry(key_slot(key.as_bytes()))
+            .or_insert_with(Vec::new)
+            .push(key.clone());
+    }
+
+    groups
+}
+
+/// 解析 `MOVED slot host:port` 和 `ASK slot host:port` 错误，返回错误类型、槽和节点地址。其他错误返回 `None`。
+fn parse_redirect(err: &crate::Error) -> Option<(ErrorKind, u16, String)> {
+    let err = err.downcast_ref::<ServerError>()?;
+
+    let kind = match err.kind() {
+        Some(kind @ (ErrorKind::Moved | ErrorKind::Ask)) => kind,
+        _ => return None,
+    };
+
+    let mut parts = err.message().split(' ');
+
+    let slot = parts.next()?.parse().ok()?;
+    let addr = parts.next()?;
+
+    if slot >= SLOT_COUNT || parts.next().is_some() {
+        return None;
+    }
+
+    Some((kind, slot, addr.to_string()))
+}
@@ -0,0 +1,81 @@
+use crate::frame::ErrorKind;
+
+use std::fmt;
+
+/// 服务器以错误帧响应请求时，客户端返回的错误。
+///
+/// 客户端的方法返回装箱的 `crate::Error`，可以通过 `downcast_ref` 取得 `ServerError`，
+/// 根据错误类型区分处理，例如重试 `BUSY` 错误，而对 `WRONGTYPE` 错误直接放弃：
+///
+/// ```no_run
+/// use mini_redis::clients::{Client, ServerError};
+/// use mini_redis::frame::ErrorKind;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let mut client = Client::connect("localhost:6379").await.unwrap();
+///
+///     if let Err(err) = client.get("list").await {
+///         match err.downcast_ref::<ServerError>() {
+///             Some(err) if err.kind() == Some(ErrorKind::WrongType) => println!("not a string"),
+///             Some(err) if err.is_retryable() => println!("try again later"),
+///             _ => println!("failed: {}", err),
+///         }
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerError {
+    /// 错误类型，错误码无法识别时为 `None`
+    kind: Option<ErrorKind>,
+
+    /// 服务器发送的完整错误信息，包括开头的错误码
+    msg: String,
+}
+
+impl ServerError {
+    /// 根据错误帧的内容创建一个 `ServerError`。
+    pub(crate) fn new(msg: String) -> ServerError {
+        ServerError {
+            kind: ErrorKind::of(&msg),
+            msg,
+        }
+    }
+
+    /// 返回错误类型。服务器发送了 `ErrorKind` 中没有的错误码时返回 `None`，此时可以通过 `code` 获取错误码。
+    pub fn kind(&self) -> Option<ErrorKind> {
+        self.kind
+    }
+
+    /// 返回错误信息开头的错误码，例如 `WRONGTYPE`。
+    pub fn code(&self) -> &str {
+        self.msg.split(' ').next().unwrap_or(&self.msg)
+    }
+
+    /// 返回错误码之后的错误信息。
+    pub fn message(&self) -> &str {
+        match self.msg.split_once(' ') {
+            Some((_, message)) => message,
+            None => "",
+        }
+    }
+
+    /// 稍后重新发送同一个请求是否可能成功。
+    ///
+    /// 服务器繁忙（`BUSY`）或者集群暂时没有节点负责键所在的槽位（`CLUSTERDOWN`）时返回 `true`。
+    /// 其他错误由请求本身或者连接的状态导致，重试会得到同样的错误。
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self.kind,
+            Some(ErrorKind::Busy) | Some(ErrorKind::ClusterDown)
+        )
+    }
+}
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str(&self.msg)
+    }
+}
+
+impl std::error::Error for ServerError {}
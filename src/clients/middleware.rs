@@ -0,0 +1,52 @@
+use crate::Frame;
+
+use std::time::Duration;
+
+/// `Client` 的请求/响应拦截器。
+///
+/// 通过 `Client::with_middleware` 注册。每个请求帧在写入连接之前都会依次经过所有中间件的 `before_send`，
+/// 每个响应帧在被客户端解析之前都会依次经过 `after_receive`。中间件可以用来记录日志、测量延迟，
+/// 或者修改帧（例如为所有键加上前缀）。
+///
+/// 两个方法都有默认的空实现，只需实现关心的方法。
+///
+/// # 示例
+///
+/// ```no_run
+/// use mini_redis::clients::{Client, Middleware};
+/// use mini_redis::Frame;
+/// use std::time::Duration;
+///
+/// struct Latency;
+///
+/// impl Middleware for Latency {
+///     fn after_receive(&self, request: &Frame, _response: &mut Frame, elapsed: Duration) {
+///         println!("{} took {:?}", request, elapsed);
+///     }
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let mut client = Client::connect("localhost:6379")
+///         .await
+///         .unwrap()
+///         .with_middleware(Latency);
+///
+///     client.ping(None).await.unwrap();
+/// }
+/// ```
+pub trait Middleware: Send + Sync {
+    /// 在请求帧写入连接之前调用。
+    fn before_send(&self, request: &mut Frame) {
+        let _ = request;
+    }
+
+    /// 在收到响应帧之后、客户端解析它之前调用。
+    ///
+    /// `request` 是最近一次发送的请求（经过 `before_send` 修改之后的帧），`elapsed` 是从发送该请求到收到
+    /// 此响应经过的时间。一个请求可能有多个响应，例如 `SUBSCRIBE` 的每个频道都有一个确认。
+    /// 发布/订阅模式下服务器主动推送的消息不经过此方法。
+    fn after_receive(&self, request: &Frame, response: &mut Frame, elapsed: Duration) {
+        let _ = (request, response, elapsed);
+    }
+}
@@ -1,8 +1,27 @@
 mod client;
-pub use client::{Client, Message, Subscriber};
+pub use client::{
+    Client, ClientBuilder, Message, MessageMetadata, PrimaryReads, PubSubEvent, Subscriber,
+};
+
+mod error;
+pub use error::ServerError;
+
+mod request;
+
+mod middleware;
+pub use middleware::Middleware;
 
 mod blocking_client;
 pub use blocking_client::BlockingClient;
 
+mod cluster_client;
+pub use cluster_client::ClusterClient;
+
+mod caching_client;
+pub use caching_client::CachingClient;
+
 mod buffered_client;
-pub use buffered_client::BufferedClient;
+pub use buffered_client::{BufferedClient, BufferedClientBuilder};
+
+mod shared_subscriber;
+pub use shared_subscriber::{SharedSubscriber, SharedSubscriberBuilder, SharedSubscription};
@@ -0,0 +1,162 @@
+//! `BufferedClient` 和 `ClusterClient` 转发给 `Client` 的请求。
+
+use crate::clients::Client;
+use crate::Result;
+
+use bytes::Bytes;
+use std::convert::TryFrom;
+use std::time::Duration;
+
+/// 转发给 `Client` 的命令。
+#[derive(Debug, Clone)]
+pub(crate) enum Command {
+    Ping(Option<Bytes>),
+    Get(String),
+    Set(String, Bytes, Option<Duration>),
+    SetKeepTtl(String, Bytes),
+    CompareAndSet(String, Bytes, Bytes),
+    Publish(String, Bytes),
+    Del(Vec<String>),
+    Exists(Vec<String>),
+    Incr(String),
+    SetRange(String, u64, Bytes),
+    GetRange(String, i64, i64),
+    SetBit(String, u64, bool),
+    GetBit(String, u64),
+    BitCount(String, Option<(i64, i64)>),
+    PfAdd(String, Vec<Bytes>),
+    PfCount(Vec<String>),
+    Wait(u64, Option<Duration>),
+}
+
+/// `Client` 执行命令后返回的响应。
+///
+/// 每个命令的结果都被转换为以下几种形式之一，再由调用者通过 `TryFrom` 转换为具体的返回类型。
+/// 这样，添加新命令时只需要扩展 `Command`，而不需要新的响应类型。
+#[derive(Debug)]
+pub(crate) enum Response {
+    Ok,
+    Integer(u64),
+    Value(Option<Bytes>),
+}
+
+impl Command {
+    /// 在 `client` 上执行命令。
+    pub(crate) async fn send(self, client: &mut Client) -> Result<Response> {
+        match self {
+            Command::Ping(msg) => client
+                .ping(msg)
+                .await
+                .map(|pong| Response::Value(Some(pong))),
+            Command::Get(key) => client.get(&key).await.map(Response::Value),
+            Command::Set(key, value, None) => client.set(&key, value).await.map(|_| Response::Ok),
+            Command::Set(key, value, Some(expiration)) => client
+                .set_expires(&key, value, expiration)
+                .await
+                .map(|_| Response::Ok),
+            Command::SetKeepTtl(key, value) => {
+                client.set_keep_ttl(&key, value).await.map(|_| Response::Ok)
+            }
+            Command::CompareAndSet(key, expected, new) => client
+                .compare_and_set(&key, expected, new)
+                .await
+                .map(|swapped| Response::Integer(swapped as u64)),
+            Command::Publish(channel, message) => client
+                .publish(&channel, message)
+                .await
+                .map(Response::Integer),
+            Command::Del(keys) => client.del(&keys).await.map(Response::Integer),
+            Command::Exists(keys) => client.exists(&keys).await.map(Response::Integer),
+            Command::Incr(key) => client.incr(&key).await.map(Response::Integer),
+            Command::SetRange(key, offset, value) => client
+                .setrange(&key, offset, value)
+                .await
+                .map(Response::Integer),
+            Command::GetRange(key, start, end) => client
+                .getrange(&key, start, end)
+                .await
+                .map(|value| Response::Value(Some(value))),
+            Command::SetBit(key, offset, value) => client
+                .setbit(&key, offset, value)
+                .await
+                .map(Response::Integer),
+            Command::GetBit(key, offset) => {
+                client.getbit(&key, offset).await.map(Response::Integer)
+            }
+            Command::BitCount(key, range) => {
+                client.bitcount(&key, range).await.map(Response::Integer)
+            }
+            Command::PfAdd(key, elements) => client
+                .pfadd(&key, &elements)
+                .await
+                .map(|changed| Response::Integer(changed as u64)),
+            Command::PfCount(keys) => client.pfcount(&keys).await.map(Response::Integer),
+            Command::Wait(numreplicas, timeout) => client
+                .wait(numreplicas, timeout)
+                .await
+                .map(Response::Integer),
+        }
+    }
+}
+
+impl Response {
+    /// 将响应转换为“意外响应”错误
+    fn to_error(&self) -> crate::Error {
+        format!("unexpected response: {:?}", self).into()
+    }
+}
+
+impl TryFrom<Response> for () {
+    type Error = crate::Error;
+
+    fn try_from(response: Response) -> Result<()> {
+        match response {
+            Response::Ok => Ok(()),
+            response => Err(response.to_error()),
+        }
+    }
+}
+
+impl TryFrom<Response> for u64 {
+    type Error = crate::Error;
+
+    fn try_from(response: Response) -> Result<u64> {
+        match response {
+            Response::Integer(value) => Ok(value),
+            response => Err(response.to_error()),
+        }
+    }
+}
+
+impl TryFrom<Response> for bool {
+    type Error = crate::Error;
+
+    fn try_from(response: Response) -> Result<bool> {
+        match response {
+            Response::Integer(value) => Ok(value != 0),
+            response => Err(response.to_error()),
+        }
+    }
+}
+
+impl TryFrom<Response> for Option<Bytes> {
+    type Error = crate::Error;
+
+    fn try_from(response: Response) -> Result<Option<Bytes>> {
+        match response {
+            Response::Value(value) => Ok(value),
+            response => Err(response.to_error()),
+        }
+    }
+}
+
+impl TryFrom<Response> for Bytes {
+    type Error = crate::Error;
+
+    fn try_from(response: Response) -> Result<Bytes> {
+        match response {
+            Response::Value(Some(value)) => Ok(value),
+            response => Err(response.to_error()),
+        }
+    }
+}
@@ -0,0 +1,343 @@
+use crate::clients::{Client, Message, Subscriber};
+use crate::Result;
+
+use std::collections::HashMap;
+use std::fmt;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::sync::oneshot;
+use tracing::{debug, warn};
+
+/// 每个本地订阅的消息队列的默认深度。
+const DEFAULT_QUEUE_DEPTH: usize = 64;
+
+/// 通过通道发送到连接任务的请求。
+enum Request {
+    /// 为一个本地消费者订阅 `channel`。连接任务为它分配 ID 和消息队列，通过 `done` 返回。
+    Subscribe {
+        channel: String,
+        done: oneshot::Sender<Result<(u64, mpsc::Receiver<Message>)>>,
+    },
+
+    /// ID 为 `id` 的本地消费者不再需要 `channel` 上的消息。
+    Unsubscribe { channel: String, id: u64 },
+}
+
+/// 订阅了某个频道的本地消费者。
+struct Consumer {
+    id: u64,
+    tx: mpsc::Sender<Message>,
+}
+
+/// 连接任务持有的连接。第一次订阅之前是普通的 `Client`，之后一直是 `Subscriber`。
+enum Conn {
+    Idle(Client),
+    Subscribed(Subscriber),
+
+    /// 订阅的过程中出现错误，连接已经不可用。
+    Closed,
+}
+
+impl Conn {
+    /// 在服务器上订阅 `channel`。
+    async fn subscribe(&mut self, channel: &str) -> Result<()> {
+        match std::mem::replace(self, Conn::Closed) {
+            Conn::Idle(client) => {
+                *self = Conn::Subscribed(client.subscribe(vec![channel.to_string()]).await?);
+            }
+            Conn::Subscribed(mut subscriber) => {
+                subscriber.subscribe(&[channel.to_string()]).await?;
+                *self = Conn::Subscribed(subscriber);
+            }
+            Conn::Closed => return Err("shared subscriber connection closed".into()),
+        }
+
+        Ok(())
+    }
+
+    /// 在服务器上取消订阅 `channel`。
+    async fn unsubscribe(&mut self, channel: &str) -> Result<()> {
+        match self {
+            Conn::Subscribed(subscriber) => subscriber.unsubscribe(&[channel.to_string()]).await,
+            Conn::Idle(_) => Ok(()),
+            Conn::Closed => Err("shared subscriber connection closed".into()),
+        }
+    }
+
+    /// 等待下一条消息。还没有订阅任何频道时永远不会完成。
+    async fn next_message(&mut self) -> Result<Option<Message>> {
+        match self {
+            Conn::Subscribed(subscriber) => subscriber.next_message().await,
+            _ => std::future::pending().await,
+        }
+    }
+}
+
+/// 连接任务在等待时可能发生的事件。
+enum Event {
+    Request(Option<Request>),
+    Message(Result<Option<Message>>),
+}
+
+/// 管理共享的连接：按照请求在服务器上订阅和取消订阅频道，并将收到的消息分发给本地的消费者。
+///
+/// 每个频道的订阅按本地消费者计数：第一个消费者订阅时才向服务器发送 `SUBSCRIBE`，最后一个消费者离开时才发送
+/// `UNSUBSCRIBE`。连接出现错误时任务退出，所有消费者的消息队列随之关闭。
+async fn run(client: Client, mut rx: mpsc::UnboundedReceiver<Request>, queue_depth: usize) {
+    let mut conn = Conn::Idle(client);
+    let mut consumers: HashMap<String, Vec<Consumer>> = HashMap::new();
+    let mut next_id = 0;
+
+    loop {
+        let event = tokio::select! {
+            request = rx.recv() => Event::Request(request),
+            message = conn.next_message() => Event::Message(message),
+        };
+
+        let res = match event {
+            // 所有的 `SharedSubscriber` 和 `SharedSubscription` 句柄都已丢弃。
+            Event::Request(None) => break,
+            Event::Request(Some(Request::Subscribe { channel, done })) => {
+                let entry = consumers.entry(channel.clone()).or_default();
+
+                let res = if entry.is_empty() {
+                    conn.subscribe(&channel).await
+                } else {
+                    Ok(())
+                };
+
+                let (tx, messages) = mpsc::channel(queue_depth);
+                let reply = match &res {
+                    Ok(()) => {
+                        let id = next_id;
+                        next_id += 1;
+                        entry.push(Consumer { id, tx });
+                        Ok((id, messages))
+                    }
+                    Err(err) => {
+                        consumers.remove(&channel);
+                        Err(err.to_string().into())
+                    }
+                };
+
+                // 请求者可能已经放弃等待，这是正常的运行时事件。
+                let _ = done.send(reply);
+                res
+            }
+            Event::Request(Some(Request::Unsubscribe { channel, id })) => {
+                match consumers.get_mut(&channel) {
+                    Some(entry) => {
+                        entry.retain(|consumer| consumer.id != id);
+
+                        if entry.is_empty() {
+                            consumers.remove(&channel);
+                            conn.unsubscribe(&channel).await
+                        } else {
+                            Ok(())
+                        }
+                    }
+                    None => Ok(()),
+                }
+            }
+            Event::Message(Ok(Some(message))) => {
+                if let Some(entry) = consumers.get(&message.channel) {
+                    for consumer in entry {
+                        // 不等待消费过慢的消费者，否则所有消费者都会被它阻塞。
+                        // 与服务器丢弃落后的订阅者的消息一样，队列已满时这条消息对该消费者丢失。
+                        // 队列已关闭的消费者随后会发送 `Unsubscribe` 请求。
+                        let res = consumer.tx.try_send(message.clone());
+
+                        if let Err(TrySendError::Full(_)) = res {
+                            let channel = &message.channel;
+                            warn!(%channel, id = consumer.id, "shared subscription lagged");
+                        }
+                    }
+                }
+
+                Ok(())
+            }
+            // 服务器关闭了连接。
+            Event::Message(Ok(None)) => break,
+            Event::Message(Err(err)) => Err(err),
+        };
+
+        if let Err(err) = res {
+            warn!(cause = %err, "shared subscriber connection failed");
+            break;
+        }
+    }
+
+    debug!("shared subscriber task exiting");
+}
+
+/// 多个本地消费者共享一个订阅连接。
+///
+/// `Subscriber` 独占一个连接，每个需要接收消息的任务都订阅一次就需要一个连接。`SharedSubscriber` 生成一个专用的
+/// 任务管理一个连接，进程内的任意多个任务通过 [`subscribe`](SharedSubscriber::subscribe) 订阅频道，
+/// 各自从 [`SharedSubscription`] 接收消息。同一个频道无论有多少个本地消费者，服务器上都只有一个订阅。
+///
+/// 在将句柄传递给其他任务之前，可以克隆 `SharedSubscriber`。所有句柄和订阅都被丢弃之后，连接任务退出并关闭连接。
+///
+/// 每个订阅的消息队列有固定的深度（默认为 64，可以通过 `SharedSubscriber::builder()` 配置）。
+/// 消费过慢、队列已满的订阅会丢失消息，不会阻塞其他订阅。
+///
+/// # 示例
+///
+/// ```no_run
+/// use mini_redis::clients::{Client, SharedSubscriber};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let client = Client::connect("localhost:6379").await.unwrap();
+///     let shared = SharedSubscriber::new(client);
+///
+///     let mut first = shared.subscribe("news").await.unwrap();
+///     let mut second = shared.subscribe("news").await.unwrap();
+///
+///     tokio::spawn(async move {
+///         while let Some(msg) = first.next_message().await {
+///             println!("first got {:?}", msg.content);
+///         }
+///     });
+///
+///     while let Some(msg) = second.next_message().await {
+///         println!("second got {:?}", msg.content);
+///     }
+/// }
+/// ```
+#[derive(Clone)]
+pub struct SharedSubscriber {
+    tx: mpsc::UnboundedSender<Request>,
+}
+
+/// 通过 `SharedSubscriber` 对一个频道的订阅。
+///
+/// 丢弃 `SharedSubscription` 即取消订阅；频道的最后一个本地订阅被丢弃时，共享的连接在服务器上取消订阅该频道。
+pub struct SharedSubscription {
+    channel: String,
+
+    /// 连接任务分配的消费者 ID，取消订阅时用来找到这个订阅。
+    id: u64,
+
+    messages: mpsc::Receiver<Message>,
+
+    /// 用于在丢弃时通知连接任务。
+    tx: mpsc::UnboundedSender<Request>,
+}
+
+/// 用于配置并创建 `SharedSubscriber` 的构建器。
+///
+/// 通过 `SharedSubscriber::builder()` 创建。
+#[derive(Debug, Clone)]
+pub struct SharedSubscriberBuilder {
+    queue_depth: usize,
+}
+
+impl SharedSubscriberBuilder {
+    /// 设置每个订阅的消息队列的深度。默认为 64。
+    ///
+    /// # Panics
+    ///
+    /// 如果 `queue_depth` 为零，则会 panic。
+    pub fn queue_depth(mut self, queue_depth: usize) -> SharedSubscriberBuilder {
+        assert!(queue_depth > 0, "queue depth must be greater than zero");
+        self.queue_depth = queue_depth;
+        self
+    }
+
+    /// 生成管理 `client` 的连接任务，并返回 `SharedSubscriber` 句柄。
+    pub fn build(self, client: Client) -> SharedSubscriber {
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        crate::task::spawn(
+            "mini-redis::shared-subscriber",
+            run(client, rx, self.queue_depth),
+        );
+
+        SharedSubscriber { tx }
+    }
+}
+
+impl Default for SharedSubscriberBuilder {
+    fn default() -> SharedSubscriberBuilder {
+        SharedSubscriberBuilder {
+            queue_depth: DEFAULT_QUEUE_DEPTH,
+        }
+    }
+}
+
+impl SharedSubscriber {
+    /// 返回一个用于配置 `SharedSubscriber` 的构建器。
+    pub fn builder() -> SharedSubscriberBuilder {
+        SharedSubscriberBuilder::default()
+    }
+
+    /// 生成一个管理 `client` 的连接任务，使用默认的队列深度。`client` 之后只用于订阅。
+    pub fn new(client: Client) -> SharedSubscriber {
+        SharedSubscriber::builder().build(client)
+    }
+
+    /// 订阅 `channel`，返回接收该频道上消息的 `SharedSubscription`。
+    ///
+    /// 该频道已经有其他本地订阅时不会访问服务器，新的订阅只接收之后到达的消息。
+    /// 共享的连接已经关闭时返回错误。
+    pub async fn subscribe(&self, channel: &str) -> Result<SharedSubscription> {
+        let (done, rx) = oneshot::channel();
+
+        self.tx
+            .send(Request::Subscribe {
+                channel: channel.to_string(),
+                done,
+            })
+            .map_err(|_| "shared subscriber connection closed")?;
+
+        let (id, messages) = rx
+            .await
+            .map_err(|_| "shared subscriber connection closed")??;
+
+        Ok(SharedSubscription {
+            channel: channel.to_string(),
+            id,
+            messages,
+            tx: self.tx.clone(),
+        })
+    }
+}
+
+impl SharedSubscription {
+    /// 返回订阅的频道。
+    pub fn channel(&self) -> &str {
+        &self.channel
+    }
+
+    /// 接收频道上的下一条消息，必要时等待。
+    ///
+    /// `None` 表示共享的连接已经关闭。
+    pub async fn next_message(&mut self) -> Option<Message> {
+        self.messages.recv().await
+    }
+}
+
+impl Drop for SharedSubscription {
+    fn drop(&mut self) {
+        // 连接任务已经退出时发送失败，此时也不需要取消订阅。
+        let _ = self.tx.send(Request::Unsubscribe {
+            channel: std::mem::take(&mut self.channel),
+            id: self.id,
+        });
+    }
+}
+
+impl fmt::Debug for SharedSubscriber {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("SharedSubscriber").finish_non_exhaustive()
+    }
+}
+
+impl fmt::Debug for SharedSubscription {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("SharedSubscription")
+            .field("channel", &self.channel)
+            .field("id", &self.id)
+            .finish()
+    }
+}
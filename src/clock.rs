@@ -0,0 +1,91 @@
+//! `Db` 使用的时间源。
+//!
+//! 键的过期时间、`DEBUG OBJECT` 报告的剩余生存时间等都基于 `Clock::now`。默认的 `SystemClock`
+//! 使用 `tokio::time::Instant::now()`，因此在 `tokio::time::pause` 之后同样可以通过 `tokio::time::advance`
+//! 控制。不方便暂停整个运行时的测试和模拟可以通过 `server::Config::clock` 使用 `ManualClock`，
+//! 只控制数据库看到的时间。
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use tokio::time::{Duration, Instant};
+
+/// 时间源。
+///
+/// 对于 `Fn() -> Instant` 类型的闭包，该 trait 已经自动实现。
+pub trait Clock: Send + Sync {
+    /// 返回当前时刻。返回值必须单调不减。
+    fn now(&self) -> Instant;
+}
+
+impl<F> Clock for F
+where
+    F: Fn() -> Instant + Send + Sync,
+{
+    fn now(&self) -> Instant {
+        self()
+    }
+}
+
+impl fmt::Debug for dyn Clock {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("Clock")
+    }
+}
+
+/// 使用 `tokio::time::Instant::now()` 的时间源，这是默认的时间源。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// 只在调用 `advance` 时前进的时间源。克隆的 `ManualClock` 共享同一个时刻。
+///
+/// 键在时间前进之后立即被视为过期，但后台任务仍然按照实际时间清除过期的键，
+/// 因此过期的键可能在一段时间内仍然占用内存。
+///
+/// # 示例
+///
+/// ```
+/// use mini_redis::clock::{Clock, ManualClock};
+/// use std::time::Duration;
+///
+/// let clock = ManualClock::new();
+/// let start = clock.now();
+///
+/// clock.advance(Duration::from_secs(10));
+/// assert_eq!(Duration::from_secs(10), clock.now() - start);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    now: Arc<Mutex<Instant>>,
+}
+
+impl ManualClock {
+    /// 创建一个从当前时刻开始的时间源。
+    pub fn new() -> ManualClock {
+        ManualClock {
+            now: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    /// 使时间前进 `duration`。
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> ManualClock {
+        ManualClock::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap()
+    }
+}
@@ -0,0 +1,305 @@
+//! 集群风格的哈希槽。
+//!
+//! 与 Redis Cluster 一样，键空间被划分为 16384 个哈希槽，键通过 CRC16 映射到槽。
+//! 服务器可以通过 `server::Config::slot_check` 注册一个 `SlotCheck`（通常是一个 `SlotMap`），对不属于本节点的键回复
+//! `MOVED slot host:port`；`clients::ClusterClient` 据此把命令路由到正确的节点。
+//!
+//! 迁移槽的过程中，源节点对已经不在本节点的键回复 `ASK slot host:port`，客户端先向目标节点发送 `ASKING`，
+//! 再在目标节点上重试该命令，见 `SlotOwner::Migrating` 和 `SlotOwner::Importing`。
+
+use crate::frame::ErrorKind;
+use crate::{Command, Db, Frame};
+
+use std::fmt;
+use std::ops::RangeInclusive;
+
+/// 哈希槽的数量。
+pub const SLOT_COUNT: u16 = 16384;
+
+/// 返回 `key` 所属的哈希槽。
+///
+/// 如果键包含一个非空的哈希标签（第一个 `{` 与其后第一个 `}` 之间的内容），则只对哈希标签计算哈希，
+/// 这样应用程序可以让多个键落在同一个槽中。
+///
+/// # 示例
+///
+/// ```
+/// use mini_redis::cluster::key_slot;
+///
+/// assert_eq!(12182, key_slot(b"foo"));
+/// assert_eq!(key_slot(b"{user1000}.following"), key_slot(b"{user1000}.followers"));
+/// ```
+pub fn key_slot(key: &[u8]) -> u16 {
+    let key = match hash_tag(key) {
+        Some(tag) => tag,
+        None => key,
+    };
+
+    crc16(key) % SLOT_COUNT
+}
+
+/// 返回键的哈希标签。没有哈希标签或哈希标签为空时返回 `None`。
+fn hash_tag(key: &[u8]) -> Option<&[u8]> {
+    let open = key.iter().position(|&b| b == b'{')?;
+    let len = key[open + 1..].iter().position(|&b| b == b'}')?;
+
+    if len == 0 {
+        return None;
+    }
+
+    Some(&key[open + 1..open + 1 + len])
+}
+
+/// CRC16/XMODEM，与 Redis Cluster 使用的算法相同。
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0;
+
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+
+    crc
+}
+
+/// 负责一个哈希槽的节点。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SlotOwner {
+    /// 由本节点负责。
+    Local,
+
+    /// 由给定地址（`host:port`）的节点负责。
+    Node(String),
+
+    /// 由本节点负责，但正在迁移到给定地址的节点。
+    ///
+    /// 键都在本节点时命令照常执行；否则以 `ASK` 将命令重定向到目标节点。
+    Migrating(String),
+
+    /// 由给定地址的节点负责，但正在迁移到本节点。
+    ///
+    /// 只有紧跟在 `ASKING` 之后的命令在本节点执行，其他命令仍以 `MOVED` 重定向到给定地址的节点。
+    Importing(String),
+
+    /// 没有节点负责该槽。
+    Unassigned,
+}
+
+/// 服务器端的槽检查钩子，决定一个槽由哪个节点负责。
+///
+/// 对于 `Fn(u16) -> SlotOwner` 类型的闭包，该 trait 已经自动实现。
+pub trait SlotCheck: Send + Sync {
+    /// 返回负责 `slot` 的节点。
+    fn owner(&self, slot: u16) -> SlotOwner;
+}
+
+impl<F> SlotCheck for F
+where
+    F: Fn(u16) -> SlotOwner + Send + Sync,
+{
+    fn owner(&self, slot: u16) -> SlotOwner {
+        self(slot)
+    }
+}
+
+/// 静态的槽分配表，实现了 `SlotCheck`。
+///
+/// 新建的表中所有槽都未分配。通过 `local` 指定本节点负责的槽，通过 `node` 指定其他节点负责的槽。
+/// 后面的分配会覆盖前面的分配。
+///
+/// # 示例
+///
+/// ```
+/// use mini_redis::cluster::{SlotCheck, SlotMap, SlotOwner};
+///
+/// let map = SlotMap::new()
+///     .local(0..=8191)
+///     .node(8192..=16383, "127.0.0.1:6380");
+///
+/// assert_eq!(SlotOwner::Local, map.owner(100));
+/// assert_eq!(SlotOwner::Node("127.0.0.1:6380".into()), map.owner(10000));
+/// ```
+#[derive(Debug, Clone)]
+pub struct SlotMap {
+    /// 每个槽的负责者。
+    slots: Vec<Entry>,
+
+    /// 其他节点的地址，由 `Entry::Node` 引用。
+    nodes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Entry {
+    Unassigned,
+    Local,
+    Node(u16),
+}
+
+impl SlotMap {
+    /// 创建一个所有槽都未分配的表。
+    pub fn new() -> SlotMap {
+        SlotMap {
+            slots: vec![Entry::Unassigned; SLOT_COUNT as usize],
+            nodes: Vec::new(),
+        }
+    }
+
+    /// 将 `slots` 分配给本节点。
+    ///
+    /// # Panics
+    ///
+    /// 如果范围超出 `0..SLOT_COUNT`，则会 panic。
+    pub fn local(mut self, slots: RangeInclusive<u16>) -> SlotMap {
+        self.assign(slots, Entry::Local);
+        self
+    }
+
+    /// 将 `slots` 分配给地址为 `addr`（`host:port`）的节点。
+    ///
+    /// # Panics
+    ///
+    /// 如果范围超出 `0..SLOT_COUNT`，则会 panic。
+    pub fn node(mut self, slots: RangeInclusive<u16>, addr: impl Into<String>) -> SlotMap {
+        let addr = addr.into();
+
+        let node = match self.nodes.iter().position(|node| *node == addr) {
+            Some(node) => node,
+            None => {
+                self.nodes.push(addr);
+                self.nodes.len() - 1
+            }
+        };
+
+        self.assign(slots, Entry::Node(node as u16));
+        self
+    }
+
+    fn assign(&mut self, slots: RangeInclusive<u16>, entry: Entry) {
+        assert!(
+            *slots.end() < SLOT_COUNT,
+            "slot {} is out of range",
+            slots.end()
+        );
+
+        for slot in slots {
+            self.slots[slot as usize] = entry;
+        }
+    }
+}
+
+impl Default for SlotMap {
+    fn default() -> SlotMap {
+        SlotMap::new()
+    }
+}
+
+impl SlotCheck for SlotMap {
+    fn owner(&self, slot: u16) -> SlotOwner {
+        match self.slots[slot as usize] {
+            Entry::Unassigned => SlotOwner::Unassigned,
+            Entry::Local => SlotOwner::Local,
+            Entry::Node(node) => SlotOwner::Node(self.nodes[node as usize].clone()),
+        }
+    }
+}
+
+/// 解析 `start-end` 形式的槽范围（包含两端），或者单个槽 `slot`。
+///
+/// # 示例
+///
+/// ```
+/// use mini_redis::cluster::parse_slot_range;
+///
+/// assert_eq!(0..=8191, parse_slot_range("0-8191").unwrap());
+/// assert_eq!(42..=42, parse_slot_range("42").unwrap());
+/// assert!(parse_slot_range("0-16384").is_err());
+/// ```
+pub fn parse_slot_range(src: &str) -> crate::Result<RangeInclusive<u16>> {
+    let parse = |slot: &str| -> crate::Result<u16> {
+        match slot.trim().parse::<u16>() {
+            Ok(slot) if slot < SLOT_COUNT => Ok(slot),
+            _ => Err(format!("invalid slot `{}`", slot).into()),
+        }
+    };
+
+    let (start, end) = match src.split_once('-') {
+        Some((start, end)) => (parse(start)?, parse(end)?),
+        None => {
+            let slot = parse(src)?;
+            (slot, slot)
+        }
+    };
+
+    if start > end {
+        return Err(format!("invalid slot range `{}`", src).into());
+    }
+
+    Ok(start..=end)
+}
+
+impl fmt::Debug for dyn SlotCheck {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("SlotCheck")
+    }
+}
+
+/// 检查命令访问的键是否都由本节点负责。
+///
+/// 返回 `None` 表示命令可以在本节点执行；否则返回要发送给客户端的错误帧：
+/// 键不属于同一个槽时返回 `CROSSSLOT`，槽由其他节点负责时返回 `MOVED`，槽未分配时返回 `CLUSTERDOWN`。
+///
+/// 槽正在迁出时，命令访问的键有任何一个不在 `db` 中，就返回 `ASK`。`asking` 表示连接在这个命令之前发送了
+/// `ASKING`，此时正在迁入本节点的槽中的键可以在本节点访问。
+pub(crate) fn check_command(
+    cmd: &Command,
+    check: &dyn SlotCheck,
+    asking: bool,
+    db: &Db,
+) -> Option<Frame> {
+    let mut slots = cmd.keys().into_iter().map(key_slot);
+
+    let slot = slots.next()?;
+
+    if slots.any(|other| other != slot) {
+        return Some(Frame::typed_error(
+            ErrorKind::CrossSlot,
+            "Keys in request don't hash to the same slot",
+        ));
+    }
+
+    match check.owner(slot) {
+        SlotOwner::Local => None,
+        SlotOwner::Importing(_) if asking => None,
+        SlotOwner::Node(addr) | SlotOwner::Importing(addr) => Some(Frame::typed_error(
+            ErrorKind::Moved,
+            format_args!("{} {}", slot, addr),
+        )),
+        SlotOwner::Migrating(addr) => {
+            let keys = cmd.keys();
+            let present = db.batch(|batch| {
+                keys.iter()
+                    .all(|key| std::str::from_utf8(key).is_ok_and(|key| batch.exists(key)))
+            });
+
+            if present {
+                None
+            } else {
+                Some(Frame::typed_error(
+                    ErrorKind::Ask,
+                    format_args!("{} {}", slot, addr),
+                ))
+            }
+        }
+        SlotOwner::Unassigned => Some(Frame::typed_error(
+            ErrorKind::ClusterDown,
+            "Hash slot not served",
+        )),
+    }
+}
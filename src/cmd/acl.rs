@@ -0,0 +1,83 @@
+use crate::acl::{self, User, DEFAULT_USER};
+use crate::{Connection, Frame, Parse};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// 查看访问控制列表。
+///
+/// 当前支持 `ACL WHOAMI` 和 `ACL LIST` 两个子命令。
+#[derive(Debug)]
+pub enum Acl {
+    /// 返回当前连接的用户名。
+    WhoAmI,
+
+    /// 返回所有用户的描述，每个用户一行。
+    List,
+}
+
+impl Acl {
+    /// 从接收到的帧中解析一个 `Acl` 实例。
+    ///
+    /// `ACL` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// ```text
+    /// ACL WHOAMI
+    /// ACL LIST
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Acl> {
+        match &parse.next_string()?.to_uppercase()[..] {
+            "WHOAMI" => Ok(Acl::WhoAmI),
+            "LIST" => Ok(Acl::List),
+            subcommand => Err(format!("unknown ACL subcommand '{}'", subcommand).into()),
+        }
+    }
+
+    /// 应用 `Acl` 命令。
+    ///
+    /// 当前用户属于连接的状态，因此该命令由连接处理程序直接调用。
+    #[instrument(skip(self, acl, user, dst))]
+    pub(crate) async fn apply(
+        self,
+        acl: &acl::Acl,
+        user: Option<&User>,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        let response = match self {
+            Acl::WhoAmI => {
+                let name = user.map_or(DEFAULT_USER, User::name);
+                Frame::Bulk(Bytes::copy_from_slice(name.as_bytes()))
+            }
+            Acl::List => {
+                let mut response = Frame::array();
+                for user in acl.users() {
+                    response.push_bulk(Bytes::from(user.to_string()));
+                }
+                response
+            }
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `Acl` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        let subcommand: &'static [u8] = match self {
+            Acl::WhoAmI => b"whoami",
+            Acl::List => b"list",
+        };
+
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("acl".as_bytes()));
+        frame.push_bulk(Bytes::from_static(subcommand));
+        frame
+    }
+}
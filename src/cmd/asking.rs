@@ -0,0 +1,55 @@
+use crate::{Connection, Frame, Parse};
+use bytes::Bytes;
+use bytestring::ByteString;
+
+use tracing::{debug, instrument};
+
+/// 允许连接的下一个命令访问正在迁入本节点的槽中的键。
+///
+/// 客户端收到 `ASK slot host:port` 重定向后，先向目标节点发送 `ASKING`，再发送原来的命令。
+/// 该标志只对紧随其后的一个命令有效，由连接处理程序负责设置和清除。
+#[derive(Debug)]
+pub struct Asking;
+
+impl Asking {
+    /// 创建一个新的 `Asking` 命令。
+    pub(crate) fn new() -> Asking {
+        Asking
+    }
+
+    /// 从接收到的帧中解析一个 `Asking` 实例。
+    ///
+    /// `ASKING` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// ```text
+    /// ASKING
+    /// ```
+    pub(crate) fn parse_frames(_parse: &mut Parse) -> crate::Result<Asking> {
+        Ok(Asking)
+    }
+
+    /// 应用 `Asking` 命令。
+    ///
+    /// 连接的标志由连接处理程序负责设置，这里只需要写入响应。
+    #[instrument(skip(self, dst))]
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = Frame::Simple(ByteString::from_static("OK"));
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `Asking` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("asking".as_bytes()));
+        frame
+    }
+}
@@ -0,0 +1,106 @@
+use crate::acl::{Acl, User, DEFAULT_USER};
+use crate::frame::ErrorKind;
+use crate::{Connection, Frame, Parse, ParseError};
+
+use bytes::Bytes;
+use bytestring::ByteString;
+use std::sync::Arc;
+use tracing::{debug, instrument};
+
+/// 以指定用户的身份认证连接。
+///
+/// 认证成功后，连接之后的命令都以该用户的权限执行。省略用户名时使用 `default` 用户。
+#[derive(Debug)]
+pub struct Auth {
+    /// 用户名，`None` 表示 `default` 用户
+    username: Option<String>,
+
+    /// 密码
+    password: String,
+}
+
+impl Auth {
+    /// 创建一个新的 `Auth` 命令，以 `username` 用户的身份认证。
+    pub(crate) fn new(username: impl ToString, password: impl ToString) -> Auth {
+        Auth {
+            username: Some(username.to_string()),
+            password: password.to_string(),
+        }
+    }
+
+    /// 从接收到的帧中解析一个 `Auth` 实例。
+    ///
+    /// `AUTH` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// ```text
+    /// AUTH [username] password
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Auth> {
+        let first = parse.next_string()?;
+
+        match parse.next_string() {
+            Ok(password) => Ok(Auth {
+                username: Some(first),
+                password,
+            }),
+            Err(ParseError::EndOfStream) => Ok(Auth {
+                username: None,
+                password: first,
+            }),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// 应用 `Auth` 命令，认证成功时将 `user` 替换为认证的用户。
+    ///
+    /// 当前用户属于连接的状态，因此该命令由连接处理程序直接调用。
+    #[instrument(skip(self, acl, user, dst))]
+    pub(crate) async fn apply(
+        self,
+        acl: &Acl,
+        user: &mut Option<Arc<User>>,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        let name = self.username.as_deref().unwrap_or(DEFAULT_USER);
+
+        let response = match acl.authenticate(name, &self.password) {
+            // 与 Redis 一样，`default` 用户没有密码时，只提供密码的 `AUTH` 很可能是配置错误。
+            Some(_) if self.username.is_none() && acl.default_user_is_nopass() => {
+                Frame::typed_error(
+                    ErrorKind::Err,
+                    "AUTH <password> called without any password configured for the default user. \
+                 Are you sure your configuration is correct?",
+                )
+            }
+            Some(authenticated) => {
+                *user = Some(authenticated);
+                Frame::Simple(ByteString::from_static("OK"))
+            }
+            None => Frame::typed_error(
+                ErrorKind::WrongPass,
+                "invalid username-password pair or user is disabled.",
+            ),
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `Auth` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("auth".as_bytes()));
+        if let Some(username) = self.username {
+            frame.push_bulk(Bytes::from(username.into_bytes()));
+        }
+        frame.push_bulk(Bytes::from(self.password.into_bytes()));
+        frame
+    }
+}
@@ -0,0 +1,60 @@
+use crate::cmd::Parse;
+use crate::{Connection, Db, Frame};
+
+use bytes::Bytes;
+use bytestring::ByteString;
+use tracing::{debug, instrument};
+
+/// 在后台将数据库的快照写入快照文件。
+///
+/// 服务器只在复制所有键时短暂地持有锁，编码和写入文件在后台线程上进行，期间其他连接照常读写数据库。
+/// 命令在快照开始之后立即响应，快照的进度和结果通过 `INFO persistence` 查看。
+/// 已经有正在进行的快照时返回错误。
+#[derive(Debug)]
+pub struct BgSave;
+
+impl BgSave {
+    /// 创建一个新的 `BgSave` 命令。
+    pub(crate) fn new() -> BgSave {
+        BgSave
+    }
+
+    /// 从接收到的帧中解析一个 `BgSave` 实例。
+    ///
+    /// `BGSAVE` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// ```text
+    /// BGSAVE
+    /// ```
+    pub(crate) fn parse_frames(_parse: &mut Parse) -> crate::Result<BgSave> {
+        Ok(BgSave)
+    }
+
+    /// 将 `BgSave` 命令应用到指定的 `Db` 实例。
+    ///
+    /// 响应写入到 `dst`。服务器调用此函数以执行接收到的命令。
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = match db.bgsave() {
+            Ok(()) => Frame::Simple(ByteString::from_static("Background saving started")),
+            Err(msg) => Frame::error(msg),
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `BgSave` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("bgsave".as_bytes()));
+        frame
+    }
+}
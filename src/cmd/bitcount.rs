@@ -0,0 +1,99 @@
+use crate::cmd::{Parse, ParseError};
+use crate::db::Batch;
+use crate::{Connection, Db, Frame};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// 统计键中存储的字符串中被设置的位的数量。
+///
+/// 可以指定一个字节范围 `start` 和 `end`（包含两端），只统计这部分字节。范围的语义与 `GETRANGE` 相同，
+/// 负的偏移量从字符串末尾开始计算。键不存在时返回 `0`。
+#[derive(Debug)]
+pub struct BitCount {
+    /// 要统计的键
+    key: String,
+
+    /// 可选的字节范围
+    range: Option<(i64, i64)>,
+}
+
+impl BitCount {
+    /// 创建一个新的 `BitCount` 命令，统计 `key` 在 `range` 范围内被设置的位。
+    pub(crate) fn new(key: impl ToString, range: Option<(i64, i64)>) -> BitCount {
+        BitCount {
+            key: key.to_string(),
+            range,
+        }
+    }
+
+    /// 获取键
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// 从接收到的帧中解析一个 `BitCount` 实例。
+    ///
+    /// `BITCOUNT` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// 期望一个包含两个或四个条目的数组帧。
+    ///
+    /// ```text
+    /// BITCOUNT key [start end]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<BitCount> {
+        use ParseError::EndOfStream;
+
+        let key = parse.next_string()?;
+
+        // 范围是可选的，但 `start` 和 `end` 必须同时指定。
+        let range = match parse.next_signed_int() {
+            Ok(start) => Some((start, parse.next_signed_int()?)),
+            Err(EndOfStream) => None,
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(BitCount { key, range })
+    }
+
+    /// 将 `BitCount` 命令应用到指定的 `Db` 实例。
+    ///
+    /// 响应写入到 `dst`。服务器调用此函数以执行接收到的命令。
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = db.batch(|batch| self.execute(batch));
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 在批处理中执行 `BitCount` 命令，返回响应帧而不是写入连接。
+    pub(crate) fn execute(self, batch: &mut Batch<'_>) -> Frame {
+        match batch.bitcount(&self.key, self.range) {
+            Ok(count) => Frame::Integer(count),
+            Err(msg) => Frame::error(msg),
+        }
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `BitCount` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("bitcount".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+
+        // 整数帧是无符号的，因此偏移量以字符串的形式发送。
+        if let Some((start, end)) = self.range {
+            frame.push_bulk(Bytes::from(start.to_string()));
+            frame.push_bulk(Bytes::from(end.to_string()));
+        }
+
+        frame
+    }
+}
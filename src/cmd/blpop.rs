@@ -0,0 +1,175 @@
+use crate::cmd::{Parse, ParseError};
+use crate::db::Batch;
+use crate::{Connection, Db, Frame, Shutdown};
+
+use bytes::Bytes;
+use std::time::Duration;
+use tokio::time::{self, Instant};
+use tracing::{debug, instrument};
+
+/// 移除并返回第一个非空列表的第一个元素，所有列表都为空时阻塞等待。
+///
+/// 按照给定的顺序检查列表，响应 `[key, element]`。如果所有列表都为空，则等待直到有元素被推入、
+/// 超时（`0` 表示一直等待）或者服务器关闭。超时时响应 `Null`。
+///
+/// 多个客户端等待同一个列表时，每个元素只会被其中一个客户端弹出。
+#[derive(Debug)]
+pub struct BLPop {
+    /// 要检查的列表的键，按顺序检查
+    keys: Vec<String>,
+
+    /// 阻塞等待的时长，`None` 表示一直等待
+    timeout: Option<Duration>,
+}
+
+impl BLPop {
+    /// 创建一个新的 `BLPop` 命令，从 `keys` 中弹出元素。
+    pub(crate) fn new(keys: &[String], timeout: Option<Duration>) -> BLPop {
+        BLPop {
+            keys: keys.to_vec(),
+            timeout,
+        }
+    }
+
+    /// 获取要检查的列表的键
+    pub fn keys(&self) -> &[String] {
+        &self.keys
+    }
+
+    /// 从接收到的帧中解析一个 `BLPop` 实例。
+    ///
+    /// `BLPOP` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// 期望一个至少包含三个条目的数组帧。超时时间以秒为单位，可以是小数。
+    ///
+    /// ```text
+    /// BLPOP key [key ...] timeout
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<BLPop> {
+        use ParseError::EndOfStream;
+
+        let mut keys = vec![parse.next_string()?];
+
+        loop {
+            match parse.next_string() {
+                Ok(arg) => keys.push(arg),
+                Err(EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        // 最后一个参数是超时时间。
+        let timeout = match keys.pop() {
+            Some(timeout) if !keys.is_empty() => timeout,
+            _ => return Err("ERR wrong number of arguments for 'blpop' command".into()),
+        };
+
+        let timeout = timeout
+            .parse::<f64>()
+            .ok()
+            .filter(|timeout| timeout.is_finite())
+            .ok_or("ERR timeout is not a float or out of range")?;
+
+        if timeout < 0.0 {
+            return Err("ERR timeout is negative".into());
+        }
+
+        let timeout = Some(Duration::from_secs_f64(timeout)).filter(|timeout| !timeout.is_zero());
+
+        Ok(BLPop { keys, timeout })
+    }
+
+    /// 将 `BLPop` 命令应用到指定的 `Db` 实例。
+    ///
+    /// 响应写入到 `dst`。服务器调用此函数以执行接收到的命令。
+    ///
+    /// 阻塞时在 `Db` 中登记要弹出的键，只有元素被推入这些列表时才重新检查。
+    #[instrument(skip(self, db, dst, shutdown))]
+    pub(crate) async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+
+        // 与 `XREAD` 相同，在检查列表之前登记，避免错过检查之后、等待之前推入的元素。
+        let waiter = db.watch_keys(self.keys.iter().map(String::as_str));
+
+        let response = loop {
+            let (response, write) = db.batch_logged(|batch| pop(batch, &self.keys));
+
+            if let Some(response) = response {
+                // `always` 策略下等待弹出写入磁盘之后再响应。
+                break match write.wait().await {
+                    Ok(()) => response,
+                    Err(msg) => Frame::error(msg),
+                };
+            }
+
+            tokio::select! {
+                _ = waiter.wait() => {}
+                _ = sleep_until(deadline) => break Frame::Null,
+                _ = shutdown.recv() => break Frame::Null,
+            }
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `BLPop` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("blpop".as_bytes()));
+
+        for key in self.keys {
+            frame.push_bulk(Bytes::from(key.into_bytes()));
+        }
+
+        let timeout = self.timeout.map_or(0.0, |timeout| timeout.as_secs_f64());
+        frame.push_bulk(Bytes::from(timeout.to_string()));
+
+        frame
+    }
+}
+
+/// 从第一个非空的列表中弹出一个元素。所有列表都为空时返回 `None`。
+///
+/// 弹出被记录为 `LPOP key`，加载 AOF 和副本执行时弹出同一个元素。
+fn pop(batch: &mut Batch<'_>, keys: &[String]) -> Option<Frame> {
+    for key in keys {
+        match batch.lpop(key) {
+            Ok(Some(value)) => {
+                let mut effect = Frame::array();
+                effect.push_bulk(Bytes::from_static(b"lpop"));
+                effect.push_bulk(Bytes::from(key.clone().into_bytes()));
+                batch.propagate(effect);
+
+                return Some(Frame::Array(vec![
+                    Frame::Bulk(Bytes::from(key.clone().into_bytes())),
+                    Frame::Bulk(value),
+                ]));
+            }
+            Ok(None) => {}
+            Err(msg) => return Some(Frame::error(msg)),
+        }
+    }
+
+    None
+}
+
+/// 休眠直到 `deadline`。`deadline` 为 `None` 时永远不会完成。
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
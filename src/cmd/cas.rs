@@ -0,0 +1,95 @@
+use crate::cmd::Parse;
+use crate::db::Batch;
+use crate::{Connection, Db, Frame};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// 仅当键中存储的值等于 `expected` 时，将其替换为 `new`。
+///
+/// 比较和替换在同一次加锁下完成，因此多个客户端可以用它实现无锁的协调，例如乐观并发控制：
+/// 读取值、计算新值，再用 `CAS` 写回，失败时重试。键不存在时不会匹配。
+/// 替换成功时响应 `1`，否则响应 `0`。键的过期时间保持不变。
+#[derive(Debug)]
+pub struct Cas {
+    /// 要修改的键
+    key: String,
+
+    /// 期望的当前值
+    expected: Bytes,
+
+    /// 要写入的新值
+    new: Bytes,
+}
+
+impl Cas {
+    /// 创建一个新的 `Cas` 命令，当 `key` 的值等于 `expected` 时将其替换为 `new`。
+    pub(crate) fn new(key: impl ToString, expected: Bytes, new: Bytes) -> Cas {
+        Cas {
+            key: key.to_string(),
+            expected,
+            new,
+        }
+    }
+
+    /// 获取键
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// 从接收到的帧中解析一个 `Cas` 实例。
+    ///
+    /// `CAS` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// 期望一个包含四个条目的数组帧。
+    ///
+    /// ```text
+    /// CAS key expected new
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Cas> {
+        let key = parse.next_string()?;
+        let expected = parse.next_bytes()?;
+        let new = parse.next_bytes()?;
+
+        Ok(Cas { key, expected, new })
+    }
+
+    /// 将 `Cas` 命令应用到指定的 `Db` 实例。
+    ///
+    /// 响应写入到 `dst`。服务器调用此函数以执行接收到的命令。
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = match db.compare_and_set(&self.key, &self.expected, self.new) {
+            Ok(swapped) => Frame::Integer(swapped as u64),
+            Err(msg) => Frame::error(msg),
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 在批处理中执行 `Cas` 命令，返回响应帧而不是写入连接。
+    pub(crate) fn execute(self, batch: &mut Batch<'_>) -> Frame {
+        match batch.compare_and_set(&self.key, &self.expected, self.new) {
+            Ok(swapped) => Frame::Integer(swapped as u64),
+            Err(msg) => Frame::error(msg),
+        }
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `Cas` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("cas".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame.push_bulk(self.expected);
+        frame.push_bulk(self.new);
+        frame
+    }
+}
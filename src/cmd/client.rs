@@ -0,0 +1,184 @@
+use crate::{Connection, Db, Frame, Parse};
+
+use bytes::Bytes;
+use bytestring::ByteString;
+use std::collections::HashSet;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{debug, instrument};
+
+/// 管理当前连接。
+///
+/// 支持以下子命令：
+///
+/// - `CLIENT ID` 返回连接的 ID。
+/// - `CLIENT TRACKING ON|OFF` 开启或关闭服务器辅助的客户端缓存。
+/// - `CLIENT PUBSUB-METADATA ON|OFF` 开启或关闭消息的元数据。开启之后，该连接订阅时收到的消息帧在内容之后附带
+///   发布者的连接 ID 和发布时间（Unix 毫秒时间戳）：`["message", channel, content, publisher, timestamp]`，
+///   订阅者可以据此追溯消息的来源。需要在 `SUBSCRIBE` 之前开启，`RESET` 将其关闭。
+///
+/// 开启后，服务器记住连接通过只读命令读取过的键。这些键之后被任意连接修改、删除或者过期时，
+/// 服务器在同一个连接上推送一个 `["invalidate", [key]]` 数组帧，客户端据此丢弃本地缓存的值。
+/// 服务器无法确定哪些键被修改时（例如失效通知积压过多），推送 `["invalidate", nil]`，
+/// 表示客户端应当丢弃所有缓存。每个键在被读取之后只会失效一次，再次读取后重新开始跟踪。
+#[derive(Debug)]
+pub enum ClientCommand {
+    /// 返回连接的 ID。
+    Id,
+
+    /// 开启（`true`）或关闭（`false`）键跟踪。
+    Tracking(bool),
+
+    /// 开启（`true`）或关闭（`false`）发布/订阅消息的元数据。
+    PubSubMetadata(bool),
+}
+
+/// 开启了 `CLIENT TRACKING` 的连接的跟踪状态。
+///
+/// 连接处理程序持有 `Tracking`，在等待下一个命令的同时接收被修改的键，并为被跟踪的键生成失效通知。
+#[derive(Debug, Default)]
+pub(crate) struct Tracking {
+    /// 被修改的键。`None` 表示没有开启跟踪。
+    invalidations: Option<broadcast::Receiver<String>>,
+
+    /// 连接读取过、尚未失效的键
+    keys: HashSet<String>,
+}
+
+impl ClientCommand {
+    /// 从接收到的帧中解析一个 `ClientCommand` 实例。
+    ///
+    /// `CLIENT` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// ```text
+    /// CLIENT ID
+    /// CLIENT TRACKING <ON | OFF>
+    /// CLIENT PUBSUB-METADATA <ON | OFF>
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<ClientCommand> {
+        match &parse.next_string()?.to_uppercase()[..] {
+            "ID" => Ok(ClientCommand::Id),
+            "TRACKING" => Ok(ClientCommand::Tracking(parse_switch(parse)?)),
+            "PUBSUB-METADATA" => Ok(ClientCommand::PubSubMetadata(parse_switch(parse)?)),
+            subcommand => Err(format!("unknown CLIENT subcommand '{}'", subcommand).into()),
+        }
+    }
+
+    /// 应用 `ClientCommand` 命令。
+    ///
+    /// 跟踪状态和 `pubsub_metadata` 属于连接的状态，因此该命令由连接处理程序直接调用。
+    #[instrument(skip(self, tracking, pubsub_metadata, db, dst))]
+    pub(crate) async fn apply(
+        self,
+        tracking: &mut Tracking,
+        pubsub_metadata: &mut bool,
+        db: &Db,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        let response = match self {
+            ClientCommand::Id => Frame::Integer(db.client_id().unwrap_or(0)),
+            ClientCommand::Tracking(on) => {
+                if on {
+                    tracking.enable(db);
+                } else {
+                    tracking.disable();
+                }
+                Frame::Simple(ByteString::from_static("OK"))
+            }
+            ClientCommand::PubSubMetadata(on) => {
+                *pubsub_metadata = on;
+                Frame::Simple(ByteString::from_static("OK"))
+            }
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `ClientCommand` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("client".as_bytes()));
+
+        match self {
+            ClientCommand::Id => frame.push_bulk(Bytes::from("id".as_bytes())),
+            ClientCommand::Tracking(on) => {
+                frame.push_bulk(Bytes::from("tracking".as_bytes()));
+                frame.push_bulk(Bytes::from(if on { "on" } else { "off" }));
+            }
+            ClientCommand::PubSubMetadata(on) => {
+                frame.push_bulk(Bytes::from("pubsub-metadata".as_bytes()));
+                frame.push_bulk(Bytes::from(if on { "on" } else { "off" }));
+            }
+        }
+
+        frame
+    }
+}
+
+/// 解析子命令的 `ON` 或 `OFF` 参数。
+fn parse_switch(parse: &mut Parse) -> crate::Result<bool> {
+    match &parse.next_string()?.to_uppercase()[..] {
+        "ON" => Ok(true),
+        "OFF" => Ok(false),
+        _ => Err("ERR syntax error".into()),
+    }
+}
+
+impl Tracking {
+    /// 开启跟踪。已经开启时不做任何事情。
+    pub(crate) fn enable(&mut self, db: &Db) {
+        if self.invalidations.is_none() {
+            self.invalidations = Some(db.invalidations());
+        }
+    }
+
+    /// 关闭跟踪，并忘记所有被跟踪的键。
+    pub(crate) fn disable(&mut self) {
+        self.invalidations = None;
+        self.keys.clear();
+    }
+
+    /// 是否开启了跟踪。
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.invalidations.is_some()
+    }
+
+    /// 跟踪连接读取的键。没有开启跟踪时不做任何事情。
+    pub(crate) fn track(&mut self, keys: Vec<String>) {
+        if self.is_enabled() {
+            self.keys.extend(keys);
+        }
+    }
+
+    /// 等待下一个被跟踪的键被修改，返回要推送给客户端的失效通知。没有开启跟踪时永远不会完成。
+    pub(crate) async fn next_invalidation(&mut self) -> Frame {
+        let invalidations = match &mut self.invalidations {
+            Some(invalidations) => invalidations,
+            None => return std::future::pending().await,
+        };
+
+        let keys = loop {
+            match invalidations.recv().await {
+                Ok(key) if self.keys.remove(&key) => {
+                    break Frame::Array(vec![Frame::Bulk(Bytes::from(key.into_bytes()))]);
+                }
+                Ok(_) => {}
+                // 错过了一些被修改的键，无法确定哪些被跟踪的键失效了，只能让客户端丢弃所有缓存。
+                Err(RecvError::Lagged(_)) => {
+                    self.keys.clear();
+                    break Frame::Null;
+                }
+                Err(RecvError::Closed) => return std::future::pending().await,
+            }
+        };
+
+        Frame::Array(vec![Frame::Bulk(Bytes::from_static(b"invalidate")), keys])
+    }
+}
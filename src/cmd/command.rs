@@ -0,0 +1,122 @@
+use crate::cmd::{self, CommandInfo};
+use crate::{Connection, Frame, Parse, ParseError};
+
+use bytes::Bytes;
+use bytestring::ByteString;
+use std::convert::TryFrom;
+use tracing::{debug, instrument};
+
+/// 查询服务器支持的命令，使通用的 Redis 客户端可以探测服务器的能力。
+///
+/// 当前支持 `COMMAND` 和 `COMMAND DOCS [command ...]`，数据来自 `cmd::COMMANDS` 注册表。
+#[derive(Debug)]
+pub enum Commands {
+    /// 返回所有命令的名称、参数数量、标志和键的位置。
+    All,
+
+    /// 返回给定命令的文档，没有给定命令时返回所有命令的文档。未知的命令被忽略。
+    Docs(Vec<String>),
+}
+
+impl Commands {
+    /// 从接收到的帧中解析一个 `Commands` 实例。
+    ///
+    /// `COMMAND` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// ```text
+    /// COMMAND
+    /// COMMAND DOCS [command [command ...]]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Commands> {
+        use ParseError::EndOfStream;
+
+        let subcommand = match parse.next_string() {
+            Ok(subcommand) => subcommand.to_uppercase(),
+            Err(EndOfStream) => return Ok(Commands::All),
+            Err(err) => return Err(err.into()),
+        };
+
+        match &subcommand[..] {
+            "DOCS" => {
+                let mut names = vec![];
+
+                loop {
+                    match parse.next_string() {
+                        Ok(name) => names.push(name),
+                        Err(EndOfStream) => break,
+                        Err(err) => return Err(err.into()),
+                    }
+                }
+
+                Ok(Commands::Docs(names))
+            }
+            subcommand => Err(format!("unknown COMMAND subcommand '{}'", subcommand).into()),
+        }
+    }
+
+    /// 应用 `Commands` 命令。
+    #[instrument(skip(self, dst))]
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = match self {
+            Commands::All => Frame::Array(cmd::COMMANDS.iter().map(info_frame).collect()),
+            Commands::Docs(names) => {
+                let infos: Vec<_> = if names.is_empty() {
+                    cmd::COMMANDS.iter().collect()
+                } else {
+                    names.iter().filter_map(|name| cmd::lookup(name)).collect()
+                };
+
+                let mut response = vec![];
+                for info in infos {
+                    response.push(Frame::Bulk(Bytes::from_static(info.name.as_bytes())));
+                    response.push(docs_frame(info));
+                }
+                Frame::Array(response)
+            }
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
+
+/// `COMMAND` 中描述一个命令的帧：`[name, arity, [flag ...], first_key, last_key, step]`。
+fn info_frame(info: &CommandInfo) -> Frame {
+    let flags = info
+        .flags
+        .iter()
+        .map(|flag| Frame::Simple(ByteString::from_static(flag)))
+        .collect();
+
+    Frame::Array(vec![
+        Frame::Bulk(Bytes::from_static(info.name.as_bytes())),
+        int_frame(info.arity),
+        Frame::Array(flags),
+        int_frame(info.first_key),
+        int_frame(info.last_key),
+        int_frame(info.step),
+    ])
+}
+
+/// `COMMAND DOCS` 中描述一个命令的帧：`["summary", summary, "group", group]`。
+fn docs_frame(info: &CommandInfo) -> Frame {
+    let mut frame = Frame::array();
+    frame.push_bulk(Bytes::from_static(b"summary"));
+    frame.push_bulk(Bytes::from_static(info.summary.as_bytes()));
+    frame.push_bulk(Bytes::from_static(b"group"));
+    frame.push_bulk(Bytes::from_static(info.group.as_bytes()));
+    frame
+}
+
+/// 整数帧是无符号的，与 `Parse::next_signed_int` 的约定一致，负数以 `Simple` 帧的形式返回。
+fn int_frame(n: i64) -> Frame {
+    match u64::try_from(n) {
+        Ok(n) => Frame::Integer(n),
+        Err(_) => Frame::Simple(ByteString::from(n.to_string())),
+    }
+}
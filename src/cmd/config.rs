@@ -0,0 +1,119 @@
+use crate::frame::ErrorKind;
+use crate::server::ConnectionLimit;
+use crate::{Connection, Frame, Parse};
+
+use bytes::Bytes;
+use bytestring::ByteString;
+use tracing::{debug, instrument};
+
+/// 查看或者在运行时修改服务器的配置。
+///
+/// 当前只支持 `maxclients` 一个参数，即服务器允许的最大并发连接数量。调低上限不会断开已有的连接，
+/// 只会使新连接等待（或者被拒绝）直到连接数量降到上限以下。
+#[derive(Debug)]
+pub enum ConfigCommand {
+    /// 返回与模式匹配的参数及其值。模式为参数名称或者 `*`。
+    Get(String),
+
+    /// 设置参数的值。
+    Set(String, String),
+}
+
+/// 支持的参数。
+const MAXCLIENTS: &str = "maxclients";
+
+impl ConfigCommand {
+    /// 从接收到的帧中解析一个 `ConfigCommand` 实例。
+    ///
+    /// `CONFIG` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// ```text
+    /// CONFIG GET parameter
+    /// CONFIG SET parameter value
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<ConfigCommand> {
+        match &parse.next_string()?.to_uppercase()[..] {
+            "GET" => Ok(ConfigCommand::Get(parse.next_string()?)),
+            "SET" => Ok(ConfigCommand::Set(
+                parse.next_string()?,
+                parse.next_string()?,
+            )),
+            subcommand => Err(format!("unknown CONFIG subcommand '{}'", subcommand).into()),
+        }
+    }
+
+    /// 应用 `ConfigCommand` 命令。
+    ///
+    /// 配置属于服务器而不是数据库，因此该命令由连接处理程序直接调用。
+    #[instrument(skip(self, limit, dst))]
+    pub(crate) async fn apply(
+        self,
+        limit: &ConnectionLimit,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        let response = match self {
+            ConfigCommand::Get(pattern) => {
+                let mut response = Frame::array();
+
+                if pattern == "*" || pattern.eq_ignore_ascii_case(MAXCLIENTS) {
+                    response.push_bulk(Bytes::from_static(MAXCLIENTS.as_bytes()));
+                    response.push_bulk(Bytes::from(limit.max().to_string()));
+                }
+
+                response
+            }
+            ConfigCommand::Set(parameter, value) if parameter.eq_ignore_ascii_case(MAXCLIENTS) => {
+                match value.parse::<usize>() {
+                    Ok(max) if max > 0 => {
+                        limit.set_max(max);
+                        Frame::Simple(ByteString::from_static("OK"))
+                    }
+                    _ => Frame::typed_error(
+                        ErrorKind::Err,
+                        format_args!(
+                            "Invalid argument '{}' for CONFIG SET '{}'",
+                            value, MAXCLIENTS
+                        ),
+                    ),
+                }
+            }
+            ConfigCommand::Set(parameter, _) => Frame::typed_error(
+                ErrorKind::Err,
+                format_args!(
+                    "Unknown option or number of arguments for CONFIG SET - '{}'",
+                    parameter
+                ),
+            ),
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `ConfigCommand` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("config".as_bytes()));
+
+        match self {
+            ConfigCommand::Get(pattern) => {
+                frame.push_bulk(Bytes::from("get".as_bytes()));
+                frame.push_bulk(Bytes::from(pattern.into_bytes()));
+            }
+            ConfigCommand::Set(parameter, value) => {
+                frame.push_bulk(Bytes::from("set".as_bytes()));
+                frame.push_bulk(Bytes::from(parameter.into_bytes()));
+                frame.push_bulk(Bytes::from(value.into_bytes()));
+            }
+        }
+
+        frame
+    }
+}
@@ -0,0 +1,113 @@
+use crate::{Connection, Frame, Parse, ParseError};
+
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use tracing::{debug, instrument};
+
+/// 自定义命令的处理程序，通过 `server::Config::register_command` 注册。
+///
+/// 处理程序接收命令名称之后的所有参数，返回发送给客户端的响应帧。
+/// 对于 `Fn(Vec<Bytes>) -> Frame` 类型的闭包，该 trait 已经自动实现。
+pub trait CommandHandler: Send + Sync {
+    /// 执行命令，返回响应帧。
+    fn call(&self, args: Vec<Bytes>) -> Frame;
+}
+
+impl<F> CommandHandler for F
+where
+    F: Fn(Vec<Bytes>) -> Frame + Send + Sync,
+{
+    fn call(&self, args: Vec<Bytes>) -> Frame {
+        self(args)
+    }
+}
+
+impl fmt::Debug for dyn CommandHandler {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("CommandHandler")
+    }
+}
+
+/// 一个自定义命令的调用。
+///
+/// 自定义命令不访问键，也不能在 `EVALBATCH` 或脚本中执行。
+#[derive(Debug)]
+pub struct Custom {
+    /// 命令名称，均为小写
+    name: String,
+
+    /// 命令名称之后的参数
+    args: Vec<Bytes>,
+
+    /// 注册的处理程序
+    handler: Arc<dyn CommandHandler>,
+}
+
+/// 服务器注册的自定义命令，按小写的名称索引。
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Registry {
+    handlers: HashMap<String, Arc<dyn CommandHandler>>,
+}
+
+impl Custom {
+    /// 返回命令名称
+    pub(crate) fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// 返回命令的参数
+    pub fn args(&self) -> &[Bytes] {
+        &self.args
+    }
+
+    /// 从接收到的帧中解析出一个 `Custom` 实例。
+    ///
+    /// 命令名称已经被消费，剩余的所有条目都作为参数。
+    pub(crate) fn parse_frames(
+        name: String,
+        handler: &Arc<dyn CommandHandler>,
+        parse: &mut Parse,
+    ) -> crate::Result<Custom> {
+        let mut args = vec![];
+
+        loop {
+            match parse.next_bytes() {
+                Ok(arg) => args.push(arg),
+                Err(ParseError::EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(Custom {
+            name,
+            args,
+            handler: handler.clone(),
+        })
+    }
+
+    /// 调用处理程序，并将响应写入 `dst`。
+    #[instrument(skip(self, dst))]
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.handler.call(self.args);
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
+
+impl Registry {
+    /// 注册 `name` 命令的处理程序。同名的处理程序会被替换。
+    pub(crate) fn insert(&mut self, name: &str, handler: Arc<dyn CommandHandler>) {
+        self.handlers.insert(name.to_lowercase(), handler);
+    }
+
+    /// 查找 `name` 命令的处理程序，`name` 必须是小写的。
+    pub(crate) fn get(&self, name: &str) -> Option<&Arc<dyn CommandHandler>> {
+        self.handlers.get(name)
+    }
+}
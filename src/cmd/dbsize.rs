@@ -0,0 +1,52 @@
+use crate::cmd::Parse;
+use crate::{Connection, Db, Frame};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// 返回数据库中键的数量。
+#[derive(Debug)]
+pub struct DbSize;
+
+impl DbSize {
+    /// 创建一个新的 `DbSize` 命令。
+    pub(crate) fn new() -> DbSize {
+        DbSize
+    }
+
+    /// 从接收到的帧中解析一个 `DbSize` 实例。
+    ///
+    /// `DBSIZE` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// ```text
+    /// DBSIZE
+    /// ```
+    pub(crate) fn parse_frames(_parse: &mut Parse) -> crate::Result<DbSize> {
+        Ok(DbSize)
+    }
+
+    /// 将 `DbSize` 命令应用到指定的 `Db` 实例。
+    ///
+    /// 响应写入到 `dst`。服务器调用此函数以执行接收到的命令。
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = Frame::Integer(db.len() as u64);
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `DbSize` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("dbsize".as_bytes()));
+        frame
+    }
+}
@@ -0,0 +1,96 @@
+use crate::{Connection, Db, Frame, Parse, Shutdown};
+
+use bytestring::ByteString;
+use std::time::Duration;
+use tracing::{debug, instrument};
+
+/// 用于实验和排查问题的调试命令，需要启用 `debug` 功能。
+///
+/// 当前支持 `DEBUG SLEEP seconds` 和 `DEBUG OBJECT key` 两个子命令。
+#[derive(Debug)]
+pub enum Debug {
+    /// 等待给定的时间后响应 `OK`，用于观察延迟。等待期间不持有任何锁，其他连接不受影响。
+    Sleep(Duration),
+
+    /// 返回键的元数据：类型、大小和剩余的生存时间。
+    Object(String),
+}
+
+impl Debug {
+    /// 获取 `DEBUG OBJECT` 访问的键
+    pub fn key(&self) -> Option<&str> {
+        match self {
+            Debug::Object(key) => Some(key),
+            Debug::Sleep(_) => None,
+        }
+    }
+
+    /// 从接收到的帧中解析一个 `Debug` 实例。
+    ///
+    /// `DEBUG` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// ```text
+    /// DEBUG SLEEP seconds
+    /// DEBUG OBJECT key
+    /// ```
+    ///
+    /// 与 Redis 一样，`seconds` 可以是小数，例如 `0.5`。
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Debug> {
+        match &parse.next_string()?.to_uppercase()[..] {
+            "SLEEP" => {
+                let seconds = parse
+                    .next_string()?
+                    .parse::<f64>()
+                    .ok()
+                    .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+                    .ok_or("ERR value is not a valid float")?;
+
+                Ok(Debug::Sleep(seconds))
+            }
+            "OBJECT" => Ok(Debug::Object(parse.next_string()?)),
+            subcommand => Err(format!("unknown DEBUG subcommand '{}'", subcommand).into()),
+        }
+    }
+
+    /// 应用 `Debug` 命令。
+    ///
+    /// `DEBUG SLEEP` 等待期间收到关闭信号时，不写入响应直接返回。
+    #[instrument(skip(self, db, dst, shutdown))]
+    pub(crate) async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match self {
+            Debug::Sleep(duration) => {
+                tokio::select! {
+                    _ = tokio::time::sleep(duration) => {}
+                    _ = shutdown.recv() => return Ok(()),
+                }
+
+                Frame::Simple(ByteString::from_static("OK"))
+            }
+            Debug::Object(key) => match db.object_info(&key) {
+                Some(info) => {
+                    // 没有过期时间时与 `PTTL` 一样显示 `-1`。
+                    let pttl = info.ttl.map_or(-1, |ttl| ttl.as_millis() as i64);
+
+                    Frame::Simple(ByteString::from(format!(
+                        "type:{} size:{} pttl:{}",
+                        info.kind, info.size, pttl
+                    )))
+                }
+                None => Frame::error("no such key"),
+            },
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,98 @@
+use crate::cmd::{Parse, ParseError};
+use crate::db::Batch;
+use crate::{Connection, Db, Frame};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// 删除一个或多个键。
+///
+/// 不存在的键会被忽略。返回实际被删除的键的数量。
+#[derive(Debug)]
+pub struct Del {
+    /// 要删除的键
+    keys: Vec<String>,
+}
+
+impl Del {
+    /// 创建一个新的 `Del` 命令以删除 `keys`。
+    pub(crate) fn new(keys: &[String]) -> Del {
+        Del {
+            keys: keys.to_vec(),
+        }
+    }
+
+    /// 获取要删除的键
+    pub fn keys(&self) -> &[String] {
+        &self.keys
+    }
+
+    /// 从接收到的帧中解析一个 `Del` 实例。
+    ///
+    /// `DEL` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// 期望一个至少包含两个条目的数组帧。
+    ///
+    /// ```text
+    /// DEL key [key ...]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Del> {
+        Ok(Del {
+            keys: parse_keys(parse)?,
+        })
+    }
+
+    /// 将 `Del` 命令应用到指定的 `Db` 实例。
+    ///
+    /// 响应写入到 `dst`。服务器调用此函数以执行接收到的命令。
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        // 所有键在同一次加锁下删除。
+        let response = db.batch(|batch| self.execute(batch));
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 在批处理中执行 `Del` 命令，返回响应帧而不是写入连接。
+    pub(crate) fn execute(self, batch: &mut Batch<'_>) -> Frame {
+        let removed = self.keys.iter().filter(|key| batch.del(key)).count();
+        Frame::Integer(removed as u64)
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `Del` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("del".as_bytes()));
+
+        for key in self.keys {
+            frame.push_bulk(Bytes::from(key.into_bytes()));
+        }
+
+        frame
+    }
+}
+
+/// 解析 `key [key ...]` 形式的参数，至少需要一个键。`DEL` 和 `EXISTS` 共用此函数。
+pub(crate) fn parse_keys(parse: &mut Parse) -> crate::Result<Vec<String>> {
+    use ParseError::EndOfStream;
+
+    let mut keys = vec![parse.next_string()?];
+
+    loop {
+        match parse.next_string() {
+            Ok(key) => keys.push(key),
+            Err(EndOfStream) => break,
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    Ok(keys)
+}
@@ -0,0 +1,75 @@
+use crate::db::Batch;
+use crate::{Connection, Db, Frame, Parse};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// 将键中存储的值序列化为一段二进制数据，之后可以通过 `RESTORE` 在同一个或者另一个服务器上重新创建该键。
+///
+/// 数据包含格式版本和校验和，不包含键名和过期时间。键不存在时返回 nil。
+#[derive(Debug)]
+pub struct Dump {
+    /// 要序列化的键
+    key: String,
+}
+
+impl Dump {
+    /// 创建一个新的 `Dump` 命令，序列化 `key` 中存储的值。
+    pub fn new(key: impl ToString) -> Dump {
+        Dump {
+            key: key.to_string(),
+        }
+    }
+
+    /// 获取键
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// 从接收到的帧中解析一个 `Dump` 实例。
+    ///
+    /// `DUMP` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// ```text
+    /// DUMP key
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Dump> {
+        let key = parse.next_string()?;
+
+        Ok(Dump { key })
+    }
+
+    /// 将 `Dump` 命令应用到指定的 `Db` 实例。
+    ///
+    /// 响应写入到 `dst`。服务器调用此函数以执行接收到的命令。
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = db.batch(|batch| self.execute(batch));
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 在批处理中执行 `Dump` 命令，返回响应帧而不是写入连接。
+    pub(crate) fn execute(self, batch: &mut Batch<'_>) -> Frame {
+        match batch.dump(&self.key) {
+            Some(data) => Frame::Bulk(data),
+            None => Frame::Null,
+        }
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `Dump` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("dump".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame
+    }
+}
@@ -0,0 +1,439 @@
+use crate::cmd::{Parse, ParseError};
+use crate::db::{AofWrite, Batch};
+use crate::frame::ErrorKind;
+use crate::{Command, Connection, Db, Frame};
+
+use bytes::Bytes;
+use mlua::{HookTriggers, Lua, LuaOptions, StdLib, Value, Variadic};
+use std::cell::Cell;
+use std::rc::Rc;
+use tracing::{debug, instrument};
+
+/// 每执行多少条 Lua 指令检查一次脚本是否超出指令上限。
+const INSTRUCTION_CHECK_INTERVAL: u32 = 10_000;
+
+/// 脚本通过 `redis.call` 执行每个命令之前进行的检查，由连接处理程序根据连接的用户、只读模式和集群的槽提供。
+/// 返回 `Some` 时不执行命令，`redis.call` 以返回的错误帧报错。
+pub(crate) type CallCheck<'a> = &'a (dyn Fn(&Command) -> Option<Frame> + Sync);
+
+/// 使用嵌入的 Lua 解释器执行脚本。
+///
+/// 脚本通过全局的 `KEYS` 和 `ARGV` 表访问参数，并通过 `redis.call` 在服务器上执行命令。
+/// 整个脚本在 `Db` 的一次加锁下执行，因此与 Redis 一样，脚本的执行是原子的。
+/// 执行的指令数量超出上限（见 `server::Config::script_instruction_limit`）的脚本会被中止，
+/// 并返回 `BUSY` 错误，此时脚本已经执行的写入不会被撤销。
+///
+/// 出于安全考虑，解释器只加载 `table`、`string` 和 `math` 标准库，
+/// `redis.call` 也只能执行 `EVALBATCH` 所允许的命令。
+///
+/// 仅在启用 `scripting` 特性时可用。
+#[derive(Debug)]
+pub struct Eval {
+    /// 脚本源码
+    script: Bytes,
+
+    /// 脚本访问的键，在脚本中以 `KEYS` 表的形式提供。
+    keys: Vec<Bytes>,
+
+    /// 附加参数，在脚本中以 `ARGV` 表的形式提供。
+    args: Vec<Bytes>,
+}
+
+/// 执行之前通过 `SCRIPT LOAD` 缓存的脚本。
+///
+/// 除了使用脚本的 SHA1 摘要代替脚本源码外，与 `EVAL` 相同。
+#[derive(Debug)]
+pub struct EvalSha {
+    /// 脚本的 SHA1 十六进制摘要
+    sha: String,
+
+    /// 脚本访问的键
+    keys: Vec<Bytes>,
+
+    /// 附加参数
+    args: Vec<Bytes>,
+}
+
+/// 管理脚本缓存。
+///
+/// 当前支持 `SCRIPT LOAD script` 和 `SCRIPT EXISTS sha [sha ...]` 两个子命令。
+#[derive(Debug)]
+pub enum Script {
+    /// 缓存脚本并返回其 SHA1 摘要。
+    Load(Bytes),
+
+    /// 检查各个摘要对应的脚本是否已被缓存。
+    Exists(Vec<String>),
+}
+
+impl Eval {
+    /// 获取脚本访问的键
+    pub fn keys(&self) -> &[Bytes] {
+        &self.keys
+    }
+
+    /// 从接收到的帧中解析一个 `Eval` 实例。
+    ///
+    /// `EVAL` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// ```text
+    /// EVAL script numkeys [key [key ...]] [arg [arg ...]]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Eval> {
+        let script = parse.next_bytes()?;
+        let (keys, args) = parse_keys_and_args(parse)?;
+
+        Ok(Eval { script, keys, args })
+    }
+
+    /// 将 `Eval` 命令应用到指定的 `Db` 实例。
+    ///
+    /// 脚本本身也会被缓存，之后可以通过 `EVALSHA` 执行。脚本通过 `redis.call` 执行的每个命令都先经过 `check`。
+    #[instrument(skip(self, db, dst, check))]
+    pub(crate) async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        check: CallCheck<'_>,
+    ) -> crate::Result<()> {
+        db.load_script(self.script.clone());
+
+        let response = run_script(db, &self.script, self.keys, self.args, check).await;
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
+
+impl EvalSha {
+    /// 获取脚本访问的键
+    pub fn keys(&self) -> &[Bytes] {
+        &self.keys
+    }
+
+    /// 从接收到的帧中解析一个 `EvalSha` 实例。
+    ///
+    /// `EVALSHA` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// ```text
+    /// EVALSHA sha1 numkeys [key [key ...]] [arg [arg ...]]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<EvalSha> {
+        let sha = parse.next_string()?;
+        let (keys, args) = parse_keys_and_args(parse)?;
+
+        Ok(EvalSha { sha, keys, args })
+    }
+
+    /// 将 `EvalSha` 命令应用到指定的 `Db` 实例。
+    ///
+    /// 如果脚本没有被缓存，则以 `NOSCRIPT` 错误响应。与 `Eval` 相同，`redis.call` 执行的命令都先经过 `check`。
+    #[instrument(skip(self, db, dst, check))]
+    pub(crate) async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        check: CallCheck<'_>,
+    ) -> crate::Result<()> {
+        let response = match db.get_script(&self.sha) {
+            Some(script) => run_script(db, &script, self.keys, self.args, check).await,
+            None => Frame::typed_error(ErrorKind::NoScript, "No matching script. Please use EVAL."),
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
+
+impl Script {
+    /// 从接收到的帧中解析一个 `Script` 实例。
+    ///
+    /// `SCRIPT` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// ```text
+    /// SCRIPT LOAD script
+    /// SCRIPT EXISTS sha1 [sha1 ...]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Script> {
+        use ParseError::EndOfStream;
+
+        match &parse.next_string()?.to_uppercase()[..] {
+            "LOAD" => Ok(Script::Load(parse.next_bytes()?)),
+            "EXISTS" => {
+                let mut shas = vec![parse.next_string()?];
+
+                loop {
+                    match parse.next_string() {
+                        Ok(sha) => shas.push(sha),
+                        Err(EndOfStream) => break,
+                        Err(err) => return Err(err.into()),
+                    }
+                }
+
+                Ok(Script::Exists(shas))
+            }
+            subcommand => Err(format!("unknown SCRIPT subcommand '{}'", subcommand).into()),
+        }
+    }
+
+    /// 将 `Script` 命令应用到指定的 `Db` 实例。
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = match self {
+            Script::Load(script) => Frame::Bulk(Bytes::from(db.load_script(script))),
+            Script::Exists(shas) => {
+                let mut response = Frame::array();
+                for sha in shas {
+                    response.push_int(db.get_script(&sha).is_some() as u64);
+                }
+                response
+            }
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
+
+/// 解析 `EVAL` 和 `EVALSHA` 共有的 `numkeys key ... arg ...` 部分。
+fn parse_keys_and_args(parse: &mut Parse) -> crate::Result<(Vec<Bytes>, Vec<Bytes>)> {
+    use ParseError::EndOfStream;
+
+    let numkeys = parse.next_int()?;
+
+    let mut keys = vec![];
+    for _ in 0..numkeys {
+        keys.push(parse.next_bytes()?);
+    }
+
+    let mut args = vec![];
+    loop {
+        match parse.next_bytes() {
+            Ok(arg) => args.push(arg),
+            Err(EndOfStream) => break,
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    Ok((keys, args))
+}
+
+/// 在 `Db` 的一次加锁下执行脚本，并将脚本的返回值转换为响应帧。
+///
+/// 脚本错误以 `-ERR` 帧的形式返回给客户端，不会关闭连接。`always` 策略下等待脚本的效果写入磁盘之后才返回。
+async fn run_script(
+    db: &Db,
+    script: &[u8],
+    keys: Vec<Bytes>,
+    args: Vec<Bytes>,
+    check: CallCheck<'_>,
+) -> Frame {
+    let (response, write) = eval_script(db, script, keys, args, check);
+
+    match write.wait().await {
+        Ok(()) => response,
+        Err(msg) => Frame::error(msg),
+    }
+}
+
+/// `run_script` 的同步部分。`Lua` 不能跨越 `.await`，因此在返回之前被丢弃。
+///
+/// 脚本通过 `redis.call` 执行的写命令的效果在同一次加锁下追加到 AOF 和复制积压缓冲区，参见 `Db::batch_logged`。
+/// 脚本中途出错或者被中止时，已经执行的写命令同样被记录。
+fn eval_script(
+    db: &Db,
+    script: &[u8],
+    keys: Vec<Bytes>,
+    args: Vec<Bytes>,
+    check: CallCheck<'_>,
+) -> (Frame, AofWrite) {
+    let lua = match Lua::new_with(
+        StdLib::TABLE | StdLib::STRING | StdLib::MATH,
+        LuaOptions::default(),
+    ) {
+        Ok(lua) => lua,
+        Err(err) => return (Frame::typed_error(ErrorKind::Err, err), AofWrite::done()),
+    };
+
+    // 脚本执行期间一直持有锁，因此用钩子统计执行的指令数量，超出上限即中止脚本。
+    let limit = db.script_instruction_limit();
+    let aborted = Rc::new(Cell::new(false));
+    if let Some(limit) = limit {
+        let executed = Cell::new(0u64);
+        let aborted = aborted.clone();
+        lua.set_hook(
+            HookTriggers::new().every_nth_instruction(INSTRUCTION_CHECK_INTERVAL),
+            move |_, _| {
+                executed.set(executed.get() + u64::from(INSTRUCTION_CHECK_INTERVAL));
+                if executed.get() > limit {
+                    aborted.set(true);
+                    return Err(mlua::Error::runtime("script instruction limit exceeded"));
+                }
+                Ok(())
+            },
+        );
+    }
+
+    let (result, write) = db.batch_logged(|batch| {
+        lua.scope(|scope| {
+            let globals = lua.globals();
+
+            let keys = keys.iter().map(|key| lua.create_string(key));
+            let args = args.iter().map(|arg| lua.create_string(arg));
+            globals.set(
+                "KEYS",
+                lua.create_sequence_from(keys.collect::<mlua::Result<Vec<_>>>()?)?,
+            )?;
+            globals.set(
+                "ARGV",
+                lua.create_sequence_from(args.collect::<mlua::Result<Vec<_>>>()?)?,
+            )?;
+
+            // `redis.call` 需要可变地借用 `batch`，因此使用作用域函数：
+            // 脚本执行完毕后，该函数即失效。
+            let call = scope.create_function_mut(|lua, args: Variadic<Value>| {
+                redis_call(lua, batch, check, args)
+            })?;
+
+            let redis = lua.create_table()?;
+            redis.set("call", call)?;
+            globals.set("redis", redis)?;
+
+            let value = lua.load(script).eval::<Value>()?;
+            lua_to_frame(value)
+        })
+    });
+
+    if aborted.get() {
+        let response = Frame::typed_error(
+            ErrorKind::Busy,
+            format_args!(
+                "script killed after executing more than {} instructions",
+                limit.unwrap_or_default()
+            ),
+        );
+        return (response, write);
+    }
+
+    let response = result.unwrap_or_else(|err| {
+        Frame::typed_error(
+            ErrorKind::Err,
+            format_args!("Error running script: {}", err),
+        )
+    });
+
+    (response, write)
+}
+
+/// `redis.call` 的实现：将参数组装为命令帧，经过 `check` 之后在批处理中执行，并将响应转换为 Lua 值。
+fn redis_call<'lua>(
+    lua: &'lua Lua,
+    batch: &mut Batch<'_>,
+    check: CallCheck<'_>,
+    args: Variadic<Value<'lua>>,
+) -> mlua::Result<Value<'lua>> {
+    let mut frame = Frame::array();
+
+    for arg in args.iter() {
+        match lua.coerce_string(arg.clone())? {
+            Some(arg) => frame.push_bulk(Bytes::copy_from_slice(arg.as_bytes())),
+            None => {
+                return Err(mlua::Error::runtime(
+                    "Lua redis() command arguments must be strings or integers",
+                ))
+            }
+        }
+    }
+
+    let command =
+        Command::from_frame(frame.clone()).map_err(|err| mlua::Error::runtime(err.to_string()))?;
+
+    // 与客户端直接发送的命令一样，检查用户的权限、只读模式以及键所在的槽。
+    if let Some(denied) = check(&command) {
+        return frame_to_lua(lua, denied);
+    }
+
+    // 写命令的效果与客户端直接发送时一样被记录，例如 `SET ... EX` 被记录为 `SET ... PXAT`。
+    let response = command
+        .execute_propagated(batch, frame)
+        .map_err(|err| mlua::Error::runtime(err.to_string()))?;
+
+    frame_to_lua(lua, response)
+}
+
+/// 按照 Redis 的约定将响应帧转换为 Lua 值。
+fn frame_to_lua(lua: &Lua, frame: Frame) -> mlua::Result<Value<'_>> {
+    let value = match frame {
+        Frame::Simple(s) => {
+            let table = lua.create_table()?;
+            table.set("ok", &*s)?;
+            Value::Table(table)
+        }
+        Frame::Error(msg) => return Err(mlua::Error::runtime(msg)),
+        Frame::Integer(n) => Value::Integer(n as i64),
+        Frame::Bulk(data) => Value::String(lua.create_string(&data)?),
+        Frame::Null => Value::Boolean(false),
+        Frame::Array(parts) => {
+            let values = parts
+                .into_iter()
+                .map(|part| frame_to_lua(lua, part))
+                .collect::<mlua::Result<Vec<_>>>()?;
+            Value::Table(lua.create_sequence_from(values)?)
+        }
+    };
+
+    Ok(value)
+}
+
+/// 按照 Redis 的约定将脚本的返回值转换为响应帧。
+fn lua_to_frame(value: Value<'_>) -> mlua::Result<Frame> {
+    let frame = match value {
+        Value::Nil | Value::Boolean(false) => Frame::Null,
+        Value::Boolean(true) => Frame::Integer(1),
+        Value::Integer(n) if n >= 0 => Frame::Integer(n as u64),
+        Value::Number(n) if n >= 0.0 => Frame::Integer(n as u64),
+        Value::Integer(_) | Value::Number(_) => {
+            return Err(mlua::Error::runtime(
+                "negative integers are not supported by mini-redis",
+            ))
+        }
+        Value::String(s) => Frame::Bulk(Bytes::copy_from_slice(s.as_bytes())),
+        Value::Table(table) => {
+            if let Some(ok) = table.get::<_, Option<String>>("ok")? {
+                Frame::Simple(ok.into())
+            } else if let Some(err) = table.get::<_, Option<String>>("err")? {
+                Frame::error(err)
+            } else {
+                let mut parts = vec![];
+
+                for value in table.sequence_values::<Value>() {
+                    parts.push(lua_to_frame(value?)?);
+                }
+
+                Frame::Array(parts)
+            }
+        }
+        _ => {
+            return Err(mlua::Error::runtime(
+                "unsupported Lua type returned from script",
+            ))
+        }
+    };
+
+    Ok(frame)
+}
@@ -0,0 +1,143 @@
+use crate::db::Batch;
+use crate::{Command, Connection, Db, Frame, Parse};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// 在一次加锁下原子地执行一组命令，并以数组的形式返回每个命令的结果。
+///
+/// 这是 `EVAL` 的一个务实的替代品：mini-redis 没有嵌入 Lua 解释器，
+/// 但很多脚本的用途只是“原子地执行几个命令”，`EVALBATCH` 正好覆盖这种场景。
+/// 批处理中的命令在同一个临界区内执行，其他连接无法观察到中间状态。
+///
+/// 批处理中只允许不会阻塞、也不会改变连接状态的命令：`GET`、`SET`、`DEL`、`EXISTS`、`INCR`、
+/// `GETRANGE`、`SETRANGE`、`GETBIT`、`SETBIT`、`BITCOUNT`、`PFADD`、`PFCOUNT`、`XADD`、`XRANGE`、`CAS`、`PUBLISH` 和 `PING`。
+#[derive(Debug)]
+pub struct EvalBatch {
+    /// 要执行的命令，按顺序排列。
+    commands: Vec<Command>,
+
+    /// 每个命令的帧，执行时作为命令的效果记录，参见 `Command::execute_propagated`。
+    frames: Vec<Frame>,
+}
+
+impl EvalBatch {
+    /// 获取要执行的命令
+    pub fn commands(&self) -> &[Command] {
+        &self.commands
+    }
+
+    /// 从接收到的帧中解析一个 `EvalBatch` 实例。
+    ///
+    /// `Parse` 参数提供了一个类似光标的 API，用于从 `Frame` 中读取字段。
+    /// 此时，整个帧已经从套接字接收到。
+    ///
+    /// `EVALBATCH` 字符串已经被解析消耗。
+    ///
+    /// # 返回
+    ///
+    /// 成功时返回 `EvalBatch` 值。如果帧格式错误，或批处理中包含不支持的命令，则返回 `Err`。
+    ///
+    /// # 格式
+    ///
+    /// 命令被序列化为一个扁平的列表：首先是命令的数量，
+    /// 然后每个命令以其参数个数开头，紧跟命令名称和参数。
+    ///
+    /// ```text
+    /// EVALBATCH numcommands argc command [arg ...] [argc command [arg ...] ...]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<EvalBatch> {
+        let num_commands = parse.next_int()?;
+        let mut commands = vec![];
+        let mut frames = vec![];
+
+        for _ in 0..num_commands {
+            let argc = parse.next_int()?;
+
+            if argc == 0 {
+                return Err(
+                    "protocol error; EVALBATCH command must have at least one argument".into(),
+                );
+            }
+
+            // 将该命令的参数重新组装为一个数组帧，以复用 `Command::from_frame`。
+            let mut frame = Frame::array();
+            for _ in 0..argc {
+                frame.push_bulk(parse.next_bytes()?);
+            }
+
+            let command = Command::from_frame(frame.clone())?;
+
+            if !command.is_atomic() {
+                return Err(format!(
+                    "protocol error; command '{}' is not allowed in EVALBATCH",
+                    command.get_name()
+                )
+                .into());
+            }
+
+            commands.push(command);
+            frames.push(frame);
+        }
+
+        Ok(EvalBatch { commands, frames })
+    }
+
+    /// 将 `EvalBatch` 命令应用到指定的 `Db` 实例。
+    ///
+    /// 所有命令在 `Db::batch` 的同一次加锁下执行，结果按顺序收集到一个数组帧中写入 `dst`。
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = db.batch(|batch| self.execute(batch));
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 在批处理中执行 `EvalBatch` 命令，返回响应帧而不是写入连接。
+    ///
+    /// 每个写命令的效果通过 `Batch::propagate` 记录，参见 `Command::execute_propagated`。
+    pub(crate) fn execute(self, batch: &mut Batch<'_>) -> Frame {
+        let results = self
+            .commands
+            .into_iter()
+            .zip(self.frames)
+            .map(|(command, frame)| {
+                // `parse_frames` 已经拒绝了无法在批处理中执行的命令。
+                command
+                    .execute_propagated(batch, frame)
+                    .unwrap_or_else(|err| Frame::error(err.to_string()))
+            })
+            .collect();
+
+        Frame::Array(results)
+    }
+
+    /// 将一组命令的帧包装为一个 `EVALBATCH` 命令的帧，`Db::batch_logged` 以此原子地记录一个批处理中的多个效果。
+    ///
+    /// 命令的参数中的整数帧被转换为批量字符串，与 `parse_frames` 读取的格式一致。
+    pub(crate) fn wrap(commands: Vec<Frame>) -> Frame {
+        let mut frame = vec![
+            Frame::Bulk(Bytes::from_static(b"evalbatch")),
+            Frame::Integer(commands.len() as u64),
+        ];
+
+        for command in commands {
+            let args = match command {
+                Frame::Array(args) => args,
+                command => vec![command],
+            };
+
+            frame.push(Frame::Integer(args.len() as u64));
+            frame.extend(args.into_iter().map(|arg| match arg {
+                Frame::Integer(value) => Frame::Bulk(Bytes::from(value.to_string())),
+                arg => arg,
+            }));
+        }
+
+        Frame::Array(frame)
+    }
+}
@@ -0,0 +1,81 @@
+use crate::cmd::del::parse_keys;
+use crate::cmd::Parse;
+use crate::db::Batch;
+use crate::{Connection, Db, Frame};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// 返回给定的键中存在的数量。
+///
+/// 与 Redis 相同，同一个键被指定多次时会被计数多次。
+#[derive(Debug)]
+pub struct Exists {
+    /// 要检查的键
+    keys: Vec<String>,
+}
+
+impl Exists {
+    /// 创建一个新的 `Exists` 命令以检查 `keys`。
+    pub(crate) fn new(keys: &[String]) -> Exists {
+        Exists {
+            keys: keys.to_vec(),
+        }
+    }
+
+    /// 获取要检查的键
+    pub fn keys(&self) -> &[String] {
+        &self.keys
+    }
+
+    /// 从接收到的帧中解析一个 `Exists` 实例。
+    ///
+    /// `EXISTS` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// 期望一个至少包含两个条目的数组帧。
+    ///
+    /// ```text
+    /// EXISTS key [key ...]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Exists> {
+        Ok(Exists {
+            keys: parse_keys(parse)?,
+        })
+    }
+
+    /// 将 `Exists` 命令应用到指定的 `Db` 实例。
+    ///
+    /// 响应写入到 `dst`。服务器调用此函数以执行接收到的命令。
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = db.batch(|batch| self.execute(batch));
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 在批处理中执行 `Exists` 命令，返回响应帧而不是写入连接。
+    pub(crate) fn execute(self, batch: &mut Batch<'_>) -> Frame {
+        let found = self.keys.iter().filter(|key| batch.exists(key)).count();
+        Frame::Integer(found as u64)
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `Exists` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("exists".as_bytes()));
+
+        for key in self.keys {
+            frame.push_bulk(Bytes::from(key.into_bytes()));
+        }
+
+        frame
+    }
+}
@@ -1,6 +1,8 @@
+use crate::db::Batch;
 use crate::{Connection, Db, Frame, Parse};
 
 use bytes::Bytes;
+use bytestring::ByteString;
 use tracing::{debug, instrument};
 
 /// 获取键的值。
@@ -8,15 +10,15 @@ use tracing::{debug, instrument};
 /// 如果键不存在，则返回特殊值 nil。如果存储在键处的值不是字符串，则返回错误，因为 GET 只处理字符串值。
 #[derive(Debug)]
 pub struct Get {
-    /// 要获取的键名
-    key: String,
+    /// 要获取的键名。它直接引用接收到的帧中的数据，解析时不会分配。
+    key: ByteString,
 }
 
 impl Get {
     /// 创建一个新的 `Get` 命令以获取 `key`。
     pub fn new(key: impl ToString) -> Get {
         Get {
-            key: key.to_string(),
+            key: key.to_string().into(),
         }
     }
 
@@ -46,7 +48,7 @@ impl Get {
     pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Get> {
         // `GET` 字符串已经被解析消耗。下一个值是要获取的键名。
         // 如果下一个值不是字符串或输入已完全消耗，则返回错误。
-        let key = parse.next_string()?;
+        let key = parse.next_str()?;
 
         Ok(Get { key })
     }
@@ -57,12 +59,13 @@ impl Get {
     #[instrument(skip(self, db, dst))]
     pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
         // 从共享数据库状态获取值
-        let response = if let Some(value) = db.get(&self.key) {
+        let response = match db.get(&self.key) {
             // 如果存在值，以 "bulk" 格式写入客户端。
-            Frame::Bulk(value)
-        } else {
+            Ok(Some(value)) => Frame::Bulk(value),
             // 如果没有值，写入 `Null`。
-            Frame::Null
+            Ok(None) => Frame::Null,
+            // 键中存储的不是字符串。
+            Err(msg) => Frame::error(msg),
         };
 
         debug!(?response);
@@ -73,13 +76,22 @@ impl Get {
         Ok(())
     }
 
+    /// 在 `EVALBATCH` 的批处理中执行 `Get` 命令，返回响应帧而不是写入连接。
+    pub(crate) fn execute(self, batch: &mut Batch<'_>) -> Frame {
+        match batch.get(&self.key) {
+            Ok(Some(value)) => Frame::Bulk(value),
+            Ok(None) => Frame::Null,
+            Err(msg) => Frame::error(msg),
+        }
+    }
+
     /// 将命令转换为等效的 `Frame`。
     ///
     /// 客户端在编码一个 `Get` 命令以发送到服务器时调用此函数。
     pub(crate) fn into_frame(self) -> Frame {
         let mut frame = Frame::array();
         frame.push_bulk(Bytes::from("get".as_bytes()));
-        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame.push_bulk(self.key.into_bytes());
         frame
     }
 }
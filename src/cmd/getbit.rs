@@ -0,0 +1,84 @@
+use crate::cmd::Parse;
+use crate::db::Batch;
+use crate::{Connection, Db, Frame};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// 返回键中存储的字符串在指定偏移量处的位。
+///
+/// 超出字符串长度的位，以及不存在的键，都视为 `0`。
+#[derive(Debug)]
+pub struct GetBit {
+    /// 要读取的键
+    key: String,
+
+    /// 位偏移量
+    offset: u64,
+}
+
+impl GetBit {
+    /// 创建一个新的 `GetBit` 命令，读取 `key` 中第 `offset` 位。
+    pub(crate) fn new(key: impl ToString, offset: u64) -> GetBit {
+        GetBit {
+            key: key.to_string(),
+            offset,
+        }
+    }
+
+    /// 获取键
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// 从接收到的帧中解析一个 `GetBit` 实例。
+    ///
+    /// `GETBIT` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// 期望一个包含三个条目的数组帧。
+    ///
+    /// ```text
+    /// GETBIT key offset
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<GetBit> {
+        let key = parse.next_string()?;
+        let offset = parse.next_int()?;
+
+        Ok(GetBit { key, offset })
+    }
+
+    /// 将 `GetBit` 命令应用到指定的 `Db` 实例。
+    ///
+    /// 响应写入到 `dst`。服务器调用此函数以执行接收到的命令。
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = db.batch(|batch| self.execute(batch));
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 在批处理中执行 `GetBit` 命令，返回响应帧而不是写入连接。
+    pub(crate) fn execute(self, batch: &mut Batch<'_>) -> Frame {
+        match batch.getbit(&self.key, self.offset) {
+            Ok(bit) => Frame::Integer(bit as u64),
+            Err(msg) => Frame::error(msg),
+        }
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `GetBit` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("getbit".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame.push_int(self.offset);
+        frame
+    }
+}
@@ -0,0 +1,92 @@
+use crate::cmd::Parse;
+use crate::db::Batch;
+use crate::{Connection, Db, Frame};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// 返回键中存储的字符串在 `start` 和 `end`（包含两端）之间的部分。
+///
+/// 负的偏移量从字符串末尾开始计算，`-1` 表示最后一个字节。超出字符串的范围被截断。
+/// 键不存在时返回空字符串。
+#[derive(Debug)]
+pub struct GetRange {
+    /// 要读取的键
+    key: String,
+
+    /// 起始偏移量
+    start: i64,
+
+    /// 结束偏移量（包含）
+    end: i64,
+}
+
+impl GetRange {
+    /// 创建一个新的 `GetRange` 命令，读取 `key` 在 `start` 和 `end` 之间的部分。
+    pub(crate) fn new(key: impl ToString, start: i64, end: i64) -> GetRange {
+        GetRange {
+            key: key.to_string(),
+            start,
+            end,
+        }
+    }
+
+    /// 获取键
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// 从接收到的帧中解析一个 `GetRange` 实例。
+    ///
+    /// `GETRANGE` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// 期望一个包含四个条目的数组帧。
+    ///
+    /// ```text
+    /// GETRANGE key start end
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<GetRange> {
+        let key = parse.next_string()?;
+        let start = parse.next_signed_int()?;
+        let end = parse.next_signed_int()?;
+
+        Ok(GetRange { key, start, end })
+    }
+
+    /// 将 `GetRange` 命令应用到指定的 `Db` 实例。
+    ///
+    /// 响应写入到 `dst`。服务器调用此函数以执行接收到的命令。
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = db.batch(|batch| self.execute(batch));
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 在批处理中执行 `GetRange` 命令，返回响应帧而不是写入连接。
+    pub(crate) fn execute(self, batch: &mut Batch<'_>) -> Frame {
+        match batch.getrange(&self.key, self.start, self.end) {
+            Ok(value) => Frame::Bulk(value),
+            Err(msg) => Frame::error(msg),
+        }
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `GetRange` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        // 整数帧是无符号的，因此偏移量以字符串的形式发送。
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("getrange".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame.push_bulk(Bytes::from(self.start.to_string()));
+        frame.push_bulk(Bytes::from(self.end.to_string()));
+        frame
+    }
+}
@@ -0,0 +1,214 @@
+use crate::cmd::{Parse, ParseError};
+use crate::db::Batch;
+use crate::{Connection, Db, Frame};
+
+use bytes::Bytes;
+use std::time::Duration;
+use tracing::{debug, instrument};
+
+/// 为键中存储的哈希的字段设置过期时间。
+///
+/// 字段过期时被后台任务从哈希中移除，最后一个字段被移除时键被删除。字段的过期时间与键的过期时间相互独立，
+/// `HSET` 覆盖字段时移除字段的过期时间，`HINCRBY` 和 `HINCRBYFLOAT` 则保留它。
+///
+/// 响应是一个数组，依次对应每个字段：`1` 表示设置了过期时间，`2` 表示过期时间为 0、字段被立即删除。
+/// Redis 对不存在的字段返回 `-2`，mini-redis 的整数帧不支持负数，因此返回 nil。
+/// 尚不支持 `NX`、`XX`、`GT` 和 `LT` 选项。
+///
+/// `HPEXPIREAT key unix-time-milliseconds FIELDS numfields field [field ...]` 也被解析为 `HExpire`，
+/// 字段在指定的 Unix 时间戳（毫秒）过期，已经过去的时间戳立即删除字段。AOF 和复制以此记录 `HEXPIRE`。
+#[derive(Debug)]
+pub struct HExpire {
+    /// 哈希的键
+    key: String,
+
+    /// 字段在多长时间之后过期
+    duration: Duration,
+
+    /// 字段过期的 Unix 时间戳（毫秒），由 `HPEXPIREAT` 指定。此时忽略 `duration`
+    expire_at: Option<u64>,
+
+    /// 要设置过期时间的字段
+    fields: Vec<Bytes>,
+
+    /// 命令名称：`hexpire` 或者 `hpexpireat`
+    name: &'static str,
+}
+
+impl HExpire {
+    /// 创建一个新的 `HExpire` 命令，使 `key` 中的 `fields` 在 `duration` 之后过期。
+    ///
+    /// 过期时间以秒为单位发送，不足一秒的部分被舍去。
+    pub(crate) fn new(key: impl ToString, duration: Duration, fields: &[&str]) -> HExpire {
+        HExpire {
+            key: key.to_string(),
+            duration,
+            expire_at: None,
+            fields: fields
+                .iter()
+                .map(|field| Bytes::copy_from_slice(field.as_bytes()))
+                .collect(),
+            name: "hexpire",
+        }
+    }
+
+    /// 获取键
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// 返回命令名称，`HPEXPIREAT` 保留自己的名称，以便权限检查和 `COMMAND` 使用正确的条目。
+    pub(crate) fn get_name(&self) -> &'static str {
+        self.name
+    }
+
+    /// 从接收到的帧中解析一个 `HExpire` 实例。
+    ///
+    /// `HEXPIRE` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// `numfields` 必须等于之后的字段数量。
+    ///
+    /// ```text
+    /// HEXPIRE key seconds FIELDS numfields field [field ...]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<HExpire> {
+        let key = parse.next_string()?;
+        let duration = Duration::from_secs(parse.next_int()?);
+
+        Ok(HExpire {
+            key,
+            duration,
+            expire_at: None,
+            fields: parse_fields(parse)?,
+            name: "hexpire",
+        })
+    }
+
+    /// 从接收到的帧中解析 `HPEXPIREAT` 命令。
+    ///
+    /// `HPEXPIREAT` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// ```text
+    /// HPEXPIREAT key unix-time-milliseconds FIELDS numfields field [field ...]
+    /// ```
+    pub(crate) fn parse_hpexpireat_frames(parse: &mut Parse) -> crate::Result<HExpire> {
+        let key = parse.next_string()?;
+        let unix_ms = parse.next_int()?;
+
+        Ok(HExpire {
+            key,
+            duration: Duration::ZERO,
+            expire_at: Some(unix_ms),
+            fields: parse_fields(parse)?,
+            name: "hpexpireat",
+        })
+    }
+
+    /// 将 `HExpire` 命令应用到指定的 `Db` 实例。
+    ///
+    /// 响应写入到 `dst`。服务器调用此函数以执行接收到的命令。
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = db.batch(|batch| self.execute(batch));
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 在批处理中执行 `HExpire` 命令，返回响应帧而不是写入连接。
+    pub(crate) fn execute(self, batch: &mut Batch<'_>) -> Frame {
+        let duration = match self.expire_at {
+            Some(unix_ms) => batch.duration_until(unix_ms),
+            None => self.duration,
+        };
+
+        match batch.hexpire(&self.key, duration, self.fields) {
+            Ok(results) => Frame::Array(
+                results
+                    .into_iter()
+                    .map(|result| result.map_or(Frame::Null, Frame::Integer))
+                    .collect(),
+            ),
+            Err(msg) => Frame::error(msg),
+        }
+    }
+
+    /// 与 `execute` 相同，并记录 `HPEXPIREAT` 作为命令的效果，加载 AOF 和副本执行时字段在与这里相同的时刻过期。
+    pub(crate) fn execute_propagated(self, batch: &mut Batch<'_>) -> Frame {
+        let effect = HExpire {
+            key: self.key.clone(),
+            duration: Duration::ZERO,
+            expire_at: Some(
+                self.expire_at
+                    .unwrap_or_else(|| batch.unix_time_after(self.duration)),
+            ),
+            fields: self.fields.clone(),
+            name: "hpexpireat",
+        };
+
+        let response = self.execute(batch);
+
+        if !matches!(response, Frame::Error(_)) {
+            batch.propagate(effect.into_frame());
+        }
+
+        response
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `HExpire` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from(self.name.as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        match self.expire_at {
+            Some(unix_ms) => frame.push_int(unix_ms),
+            None => frame.push_int(self.duration.as_secs()),
+        }
+        frame.push_bulk(Bytes::from("fields".as_bytes()));
+        frame.push_int(self.fields.len() as u64);
+
+        for field in self.fields {
+            frame.push_bulk(field);
+        }
+
+        frame
+    }
+}
+
+/// 解析 `HEXPIRE` 和 `HPEXPIREAT` 共有的 `FIELDS numfields field [field ...]` 部分。
+fn parse_fields(parse: &mut Parse) -> crate::Result<Vec<Bytes>> {
+    if parse.next_string()?.to_uppercase() != "FIELDS" {
+        return Err("ERR Mandatory argument FIELDS is missing or not at the right position".into());
+    }
+
+    let numfields = parse.next_int()?;
+
+    if numfields == 0 {
+        return Err("ERR Parameter `numFields` should be greater than 0".into());
+    }
+
+    let mut fields = vec![];
+
+    loop {
+        match parse.next_bytes() {
+            Ok(field) => fields.push(field),
+            Err(ParseError::EndOfStream) => break,
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    if fields.len() as u64 != numfields {
+        return Err("ERR The `numfields` parameter must match the number of arguments".into());
+    }
+
+    Ok(fields)
+}
@@ -0,0 +1,85 @@
+use crate::cmd::Parse;
+use crate::db::Batch;
+use crate::{Connection, Db, Frame};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// 获取键中存储的哈希的字段的值。
+///
+/// 键或者字段不存在（包括字段已经过期）时返回特殊值 nil。如果键中存储的不是哈希，则返回错误。
+#[derive(Debug)]
+pub struct HGet {
+    /// 哈希的键
+    key: String,
+
+    /// 要获取的字段
+    field: Bytes,
+}
+
+impl HGet {
+    /// 创建一个新的 `HGet` 命令，获取 `key` 中的 `field`。
+    pub(crate) fn new(key: impl ToString, field: &str) -> HGet {
+        HGet {
+            key: key.to_string(),
+            field: Bytes::copy_from_slice(field.as_bytes()),
+        }
+    }
+
+    /// 获取键
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// 从接收到的帧中解析一个 `HGet` 实例。
+    ///
+    /// `HGET` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// 期望一个包含三个条目的数组帧。
+    ///
+    /// ```text
+    /// HGET key field
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<HGet> {
+        let key = parse.next_string()?;
+        let field = parse.next_bytes()?;
+
+        Ok(HGet { key, field })
+    }
+
+    /// 将 `HGet` 命令应用到指定的 `Db` 实例。
+    ///
+    /// 响应写入到 `dst`。服务器调用此函数以执行接收到的命令。
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = db.batch(|batch| self.execute(batch));
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 在批处理中执行 `HGet` 命令，返回响应帧而不是写入连接。
+    pub(crate) fn execute(self, batch: &mut Batch<'_>) -> Frame {
+        match batch.hget(&self.key, &self.field) {
+            Ok(Some(value)) => Frame::Bulk(value),
+            Ok(None) => Frame::Null,
+            Err(msg) => Frame::error(msg),
+        }
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `HGet` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("hget".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame.push_bulk(self.field);
+        frame
+    }
+}
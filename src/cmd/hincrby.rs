@@ -0,0 +1,195 @@
+use crate::cmd::Parse;
+use crate::db::Batch;
+use crate::{Connection, Db, Frame};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// 将键中存储的哈希的字段中的整数加上增量，返回新值。
+///
+/// 键或者字段不存在时视为 `0`。增量可以是负数，但是与 `INCR` 一样，结果不能是负数。
+/// 字段的过期时间保持不变。
+#[derive(Debug)]
+pub struct HIncrBy {
+    /// 哈希的键
+    key: String,
+
+    /// 要修改的字段
+    field: Bytes,
+
+    /// 增量
+    increment: i64,
+}
+
+/// 将键中存储的哈希的字段中的浮点数加上增量，返回新值。
+///
+/// 键或者字段不存在时视为 `0`。结果不能是 NaN 或者无穷大。字段的过期时间保持不变。
+#[derive(Debug)]
+pub struct HIncrByFloat {
+    /// 哈希的键
+    key: String,
+
+    /// 要修改的字段
+    field: Bytes,
+
+    /// 增量
+    increment: f64,
+}
+
+impl HIncrBy {
+    /// 创建一个新的 `HIncrBy` 命令，将 `key` 中的 `field` 加上 `increment`。
+    pub(crate) fn new(key: impl ToString, field: &str, increment: i64) -> HIncrBy {
+        HIncrBy {
+            key: key.to_string(),
+            field: Bytes::copy_from_slice(field.as_bytes()),
+            increment,
+        }
+    }
+
+    /// 获取键
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// 从接收到的帧中解析一个 `HIncrBy` 实例。
+    ///
+    /// `HINCRBY` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// 期望一个包含四个条目的数组帧。
+    ///
+    /// ```text
+    /// HINCRBY key field increment
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<HIncrBy> {
+        let key = parse.next_string()?;
+        let field = parse.next_bytes()?;
+        let increment = parse.next_signed_int()?;
+
+        Ok(HIncrBy {
+            key,
+            field,
+            increment,
+        })
+    }
+
+    /// 将 `HIncrBy` 命令应用到指定的 `Db` 实例。
+    ///
+    /// 响应写入到 `dst`。服务器调用此函数以执行接收到的命令。
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = db.batch(|batch| self.execute(batch));
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 在批处理中执行 `HIncrBy` 命令，返回响应帧而不是写入连接。
+    pub(crate) fn execute(self, batch: &mut Batch<'_>) -> Frame {
+        match batch.hincrby(&self.key, self.field, self.increment) {
+            Ok(value) => Frame::Integer(value),
+            Err(msg) => Frame::error(msg),
+        }
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `HIncrBy` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        incr_frame("hincrby", self.key, self.field, self.increment.to_string())
+    }
+}
+
+impl HIncrByFloat {
+    /// 创建一个新的 `HIncrByFloat` 命令，将 `key` 中的 `field` 加上 `increment`。
+    pub(crate) fn new(key: impl ToString, field: &str, increment: f64) -> HIncrByFloat {
+        HIncrByFloat {
+            key: key.to_string(),
+            field: Bytes::copy_from_slice(field.as_bytes()),
+            increment,
+        }
+    }
+
+    /// 获取键
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// 从接收到的帧中解析一个 `HIncrByFloat` 实例。
+    ///
+    /// `HINCRBYFLOAT` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// 期望一个包含四个条目的数组帧。
+    ///
+    /// ```text
+    /// HINCRBYFLOAT key field increment
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<HIncrByFloat> {
+        let key = parse.next_string()?;
+        let field = parse.next_bytes()?;
+        let increment = parse
+            .next_string()?
+            .parse::<f64>()
+            .ok()
+            .filter(|increment| increment.is_finite())
+            .ok_or("ERR value is not a valid float")?;
+
+        Ok(HIncrByFloat {
+            key,
+            field,
+            increment,
+        })
+    }
+
+    /// 将 `HIncrByFloat` 命令应用到指定的 `Db` 实例。
+    ///
+    /// 响应写入到 `dst`。服务器调用此函数以执行接收到的命令。
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = db.batch(|batch| self.execute(batch));
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 在批处理中执行 `HIncrByFloat` 命令，返回响应帧而不是写入连接。
+    ///
+    /// 与 Redis 一样，新值以批量字符串的形式返回。
+    pub(crate) fn execute(self, batch: &mut Batch<'_>) -> Frame {
+        match batch.hincrbyfloat(&self.key, self.field, self.increment) {
+            Ok(value) => Frame::Bulk(value),
+            Err(msg) => Frame::error(msg),
+        }
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `HIncrByFloat` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        incr_frame(
+            "hincrbyfloat",
+            self.key,
+            self.field,
+            self.increment.to_string(),
+        )
+    }
+}
+
+/// 编码 `HINCRBY` 或 `HINCRBYFLOAT` 命令。
+fn incr_frame(name: &'static str, key: String, field: Bytes, increment: String) -> Frame {
+    let mut frame = Frame::array();
+    frame.push_bulk(Bytes::from(name.as_bytes()));
+    frame.push_bulk(Bytes::from(key.into_bytes()));
+    frame.push_bulk(field);
+    frame.push_bulk(Bytes::from(increment.into_bytes()));
+    frame
+}
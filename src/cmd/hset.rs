@@ -0,0 +1,100 @@
+use crate::cmd::{Parse, ParseError};
+use crate::db::Batch;
+use crate::{Connection, Db, Frame};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// 设置键中存储的哈希的一个或多个字段，返回新添加的字段数量。
+///
+/// 键不存在时创建一个新的哈希。已有的字段被覆盖，并且与 Redis 一样，它们的过期时间被移除。
+#[derive(Debug)]
+pub struct HSet {
+    /// 哈希的键
+    key: String,
+
+    /// 要设置的字段和值
+    pairs: Vec<(Bytes, Bytes)>,
+}
+
+impl HSet {
+    /// 创建一个新的 `HSet` 命令，设置 `key` 中 `pairs` 的每个字段。
+    pub(crate) fn new(key: impl ToString, pairs: &[(&str, Bytes)]) -> HSet {
+        HSet {
+            key: key.to_string(),
+            pairs: pairs
+                .iter()
+                .map(|(field, value)| (Bytes::copy_from_slice(field.as_bytes()), value.clone()))
+                .collect(),
+        }
+    }
+
+    /// 获取键
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// 从接收到的帧中解析一个 `HSet` 实例。
+    ///
+    /// `HSET` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// 期望一个至少包含四个条目、并且条目数量为偶数的数组帧。
+    ///
+    /// ```text
+    /// HSET key field value [field value ...]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<HSet> {
+        let key = parse.next_string()?;
+        let mut pairs = vec![(parse.next_bytes()?, parse.next_bytes()?)];
+
+        loop {
+            match parse.next_bytes() {
+                Ok(field) => pairs.push((field, parse.next_bytes()?)),
+                Err(ParseError::EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(HSet { key, pairs })
+    }
+
+    /// 将 `HSet` 命令应用到指定的 `Db` 实例。
+    ///
+    /// 响应写入到 `dst`。服务器调用此函数以执行接收到的命令。
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = db.batch(|batch| self.execute(batch));
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 在批处理中执行 `HSet` 命令，返回响应帧而不是写入连接。
+    pub(crate) fn execute(self, batch: &mut Batch<'_>) -> Frame {
+        match batch.hset(&self.key, self.pairs) {
+            Ok(added) => Frame::Integer(added),
+            Err(msg) => Frame::error(msg),
+        }
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `HSet` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("hset".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+
+        for (field, value) in self.pairs {
+            frame.push_bulk(field);
+            frame.push_bulk(value);
+        }
+
+        frame
+    }
+}
@@ -0,0 +1,82 @@
+use crate::cmd::Parse;
+use crate::db::Batch;
+use crate::{Connection, Db, Frame};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// 将键中存储的数字加一。
+///
+/// 如果键不存在，则在执行操作之前将其设置为 `0`。如果键中存储的值不能表示为整数，则返回错误。
+/// 键的过期时间保持不变。
+///
+/// 由于 mini-redis 的整数帧是无符号的，结果为负数时同样返回错误，且不会修改存储的值。
+#[derive(Debug)]
+pub struct Incr {
+    /// 要递增的键
+    key: String,
+}
+
+impl Incr {
+    /// 创建一个新的 `Incr` 命令以递增 `key`。
+    pub(crate) fn new(key: impl ToString) -> Incr {
+        Incr {
+            key: key.to_string(),
+        }
+    }
+
+    /// 获取要递增的键
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// 从接收到的帧中解析一个 `Incr` 实例。
+    ///
+    /// `INCR` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// 期望一个包含两个条目的数组帧。
+    ///
+    /// ```text
+    /// INCR key
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Incr> {
+        let key = parse.next_string()?;
+
+        Ok(Incr { key })
+    }
+
+    /// 将 `Incr` 命令应用到指定的 `Db` 实例。
+    ///
+    /// 响应写入到 `dst`。服务器调用此函数以执行接收到的命令。
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        // 读取、递增和写回在同一次加锁下完成，因此并发的 `INCR` 不会丢失更新。
+        let response = db.batch(|batch| self.execute(batch));
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 在批处理中执行 `Incr` 命令，返回响应帧而不是写入连接。
+    pub(crate) fn execute(self, batch: &mut Batch<'_>) -> Frame {
+        match batch.incr(&self.key) {
+            Ok(value) => Frame::Integer(value),
+            Err(msg) => Frame::error(msg),
+        }
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `Incr` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("incr".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame
+    }
+}
@@ -0,0 +1,273 @@
+use crate::db::TTL_BUCKET_NAMES;
+use crate::server::ConnectionLimit;
+use crate::{BufferPool, Connection, Db, Frame, Parse, ParseError};
+
+use bytes::Bytes;
+use std::fmt::Write;
+use tracing::{debug, instrument};
+
+/// 返回服务器的信息和统计数据。
+///
+/// 响应是一个批量字符串，由若干部分组成，每个部分以 `# 名称` 开头，之后每行一个 `字段:值`。
+/// 当前包含 `clients`（连接数量）、`persistence`（快照和 AOF 的状态）、`stats`（过期键的清除和同步的次数）、
+/// `keyspace`（键的数量、过期的方式和 TTL 的分布）、`replication`（角色、复制偏移量和积压缓冲区）、
+/// `latency`（延迟监控的阈值和各事件的概况）和 `buffers`（缓冲池的统计信息）七个部分。
+///
+/// `keyspace` 部分中，`expired_keys_active` 和 `expired_keys_lazy` 分别是被后台任务清除的过期键和
+/// 写入时发现已经过期、被立即移除的键的数量；`ttl_lt_1s` 到 `ttl_ge_1d` 是设置过期时间时指定的 TTL 的分布，
+/// 每次设置计数一次，不随键的过期或删除减少。
+///
+/// `latency` 部分中每个记录了采样的事件占一行，字段名为事件名称，值为
+/// `time=最近一次记录的时间,latest=最近一次的延迟,max=最大的延迟`，延迟以毫秒为单位。
+#[derive(Debug, Default)]
+pub struct Info {
+    /// 只返回该部分。`None` 表示返回所有部分。
+    section: Option<String>,
+}
+
+impl Info {
+    /// 创建一个新的 `Info` 命令，`section` 为 `None` 时返回所有部分。
+    pub fn new(section: Option<String>) -> Info {
+        Info { section }
+    }
+
+    /// 从接收到的帧中解析一个 `Info` 实例。
+    ///
+    /// `INFO` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// ```text
+    /// INFO [section]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Info> {
+        match parse.next_string() {
+            Ok(section) => Ok(Info::new(Some(section))),
+            Err(ParseError::EndOfStream) => Ok(Info::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// 应用 `Info` 命令。
+    ///
+    /// 统计数据属于服务器而不是数据库，因此该命令由连接处理程序直接调用。
+    #[instrument(skip(self, db, limit, pool, dst))]
+    pub(crate) async fn apply(
+        self,
+        db: &Db,
+        limit: &ConnectionLimit,
+        pool: &BufferPool,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        let stats = pool.stats();
+        let expiration = db.expiration_stats();
+        let keyspace = db.keyspace_stats();
+        let snapshot = db.snapshot_stats();
+        let aof = db.aof_stats();
+        let replication = db.replication_stats();
+        let link = db.replica_link_status();
+        let latency = db.latency_events();
+
+        let sections = [
+            (
+                "Clients",
+                vec![
+                    ("connected_clients", limit.active().to_string()),
+                    ("maxclients", limit.max().to_string()),
+                ],
+            ),
+            (
+                "Persistence",
+                vec![
+                    (
+                        "rdb_bgsave_in_progress",
+                        u8::from(snapshot.in_progress).to_string(),
+                    ),
+                    ("rdb_bgsave_keys_total", snapshot.keys_total.to_string()),
+                    ("rdb_bgsave_keys_saved", snapshot.keys_saved.to_string()),
+                    ("rdb_saves", snapshot.saves.to_string()),
+                    ("rdb_last_save_time", snapshot.last_save_time.to_string()),
+                    (
+                        "rdb_last_bgsave_status",
+                        if snapshot.last_status_ok { "ok" } else { "err" }.to_string(),
+                    ),
+                    (
+                        "rdb_last_bgsave_time_ms",
+                        snapshot.last_duration_ms.to_string(),
+                    ),
+                    ("aof_enabled", u8::from(aof.is_some()).to_string()),
+                ]
+                .into_iter()
+                .chain(aof.into_iter().flat_map(|aof| {
+                    vec![
+                        ("aof_fsync", aof.policy.to_string()),
+                        ("aof_current_size", aof.size.to_string()),
+                        ("aof_pending_bytes", aof.pending_bytes.to_string()),
+                        ("aof_fsyncs", aof.fsyncs.to_string()),
+                        (
+                            "aof_last_fsync_latency_us",
+                            aof.last_fsync_latency_us.to_string(),
+                        ),
+                        (
+                            "aof_last_write_status",
+                            if aof.last_write_ok { "ok" } else { "err" }.to_string(),
+                        ),
+                    ]
+                }))
+                .collect(),
+            ),
+            (
+                "Stats",
+                vec![
+                    ("expired_keys", expiration.expired_keys.to_string()),
+                    ("expire_cycles", expiration.cycles.to_string()),
+                    (
+                        "expire_cycle_max_latency_us",
+                        expiration.max_cycle_latency_us.to_string(),
+                    ),
+                    ("ttl_jitter_percent", db.ttl_jitter().to_string()),
+                    ("pubsub_channels", db.channel_count().to_string()),
+                    ("sync_full", replication.sync_full.to_string()),
+                    ("sync_partial_ok", replication.sync_partial_ok.to_string()),
+                    ("sync_partial_err", replication.sync_partial_err.to_string()),
+                ],
+            ),
+            (
+                "Keyspace",
+                vec![
+                    (
+                        "db0",
+                        format!("keys={},expires={}", keyspace.keys, keyspace.expires),
+                    ),
+                    (
+                        "expired_keys_active",
+                        expiration.active_expired_keys.to_string(),
+                    ),
+                    (
+                        "expired_keys_lazy",
+                        expiration.lazy_expired_keys.to_string(),
+                    ),
+                ]
+                .into_iter()
+                .chain(
+                    TTL_BUCKET_NAMES
+                        .iter()
+                        .zip(expiration.ttl_histogram)
+                        .map(|(&name, count)| (name, count.to_string())),
+                )
+                .collect(),
+            ),
+            (
+                "Replication",
+                vec![(
+                    "role",
+                    if link.is_some() { "slave" } else { "master" }.to_string(),
+                )]
+                .into_iter()
+                .chain(link.into_iter().flat_map(|link| {
+                    vec![
+                        ("master_host", link.host),
+                        ("master_port", link.port.to_string()),
+                        (
+                            "master_link_status",
+                            if link.up { "up" } else { "down" }.to_string(),
+                        ),
+                        ("slave_repl_offset", link.offset.to_string()),
+                    ]
+                }))
+                .chain(vec![
+                    (
+                        "connected_slaves",
+                        replication.connected_replicas.to_string(),
+                    ),
+                    ("master_replid", replication.replid),
+                    ("master_repl_offset", replication.offset.to_string()),
+                    (
+                        "repl_backlog_active",
+                        u8::from(replication.backlog_active).to_string(),
+                    ),
+                    ("repl_backlog_size", replication.backlog_size.to_string()),
+                    (
+                        "repl_backlog_first_byte_offset",
+                        replication.backlog_first_offset.to_string(),
+                    ),
+                    ("repl_backlog_histlen", replication.backlog_len.to_string()),
+                ])
+                .collect(),
+            ),
+            (
+                "Latency",
+                vec![(
+                    "latency_monitor_threshold",
+                    db.latency_threshold().as_millis().to_string(),
+                )]
+                .into_iter()
+                .chain(latency.into_iter().map(|event| {
+                    (
+                        event.name,
+                        format!(
+                            "time={},latest={},max={}",
+                            event.latest.time, event.latest.latency_ms, event.max_ms
+                        ),
+                    )
+                }))
+                .collect(),
+            ),
+            (
+                "Buffers",
+                vec![
+                    ("buffer_pool_allocated", stats.allocated.to_string()),
+                    ("buffer_pool_reused", stats.reused.to_string()),
+                    ("buffer_pool_idle", stats.idle.to_string()),
+                ],
+            ),
+        ];
+
+        let mut info = String::new();
+
+        for (name, fields) in &sections {
+            let included = match &self.section {
+                None => true,
+                Some(section) => {
+                    ["all", "default", "everything"].contains(&&section.to_lowercase()[..])
+                        || section.eq_ignore_ascii_case(name)
+                }
+            };
+
+            if !included {
+                continue;
+            }
+
+            if !info.is_empty() {
+                info.push_str("\r\n");
+            }
+
+            let _ = write!(info, "# {}\r\n", name);
+            for (field, value) in fields {
+                let _ = write!(info, "{}:{}\r\n", field, value);
+            }
+        }
+
+        let response = Frame::Bulk(Bytes::from(info));
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `Info` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("info".as_bytes()));
+
+        if let Some(section) = self.section {
+            frame.push_bulk(Bytes::from(section.into_bytes()));
+        }
+
+        frame
+    }
+}
@@ -0,0 +1,76 @@
+use crate::acl::glob_match;
+use crate::cmd::Parse;
+use crate::{Connection, Db, Frame};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// 返回所有与 glob 风格的模式匹配的键，模式支持 `*` 和 `?`。
+///
+/// 与 Redis 一样，该命令需要遍历整个键空间，只适合调试和小型数据集。
+#[derive(Debug)]
+pub struct Keys {
+    /// 键需要匹配的模式
+    pattern: String,
+}
+
+impl Keys {
+    /// 创建一个新的 `Keys` 命令，返回与 `pattern` 匹配的键。
+    pub(crate) fn new(pattern: impl ToString) -> Keys {
+        Keys {
+            pattern: pattern.to_string(),
+        }
+    }
+
+    /// 获取模式
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// 从接收到的帧中解析一个 `Keys` 实例。
+    ///
+    /// `KEYS` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// 期望一个包含两个条目的数组帧。
+    ///
+    /// ```text
+    /// KEYS pattern
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Keys> {
+        let pattern = parse.next_string()?;
+
+        Ok(Keys { pattern })
+    }
+
+    /// 将 `Keys` 命令应用到指定的 `Db` 实例。
+    ///
+    /// 键的列表通过 `Db::keys` 在一次加锁下复制，模式匹配在锁外进行。
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let mut response = Frame::array();
+
+        for key in db.keys() {
+            if glob_match(self.pattern.as_bytes(), key.as_bytes()) {
+                response.push_bulk(Bytes::from(key));
+            }
+        }
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `Keys` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("keys".as_bytes()));
+        frame.push_bulk(Bytes::from(self.pattern.into_bytes()));
+        frame
+    }
+}
@@ -0,0 +1,100 @@
+use crate::{Connection, Db, Frame, Parse, ParseError};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// 查看或者重置延迟监控记录的事件。
+///
+/// 耗时达到 `server::Config::latency_monitor_threshold` 的命令和过期键清除被分别记录到 `command` 和
+/// `expire-cycle` 事件中，每个事件保留最近 160 个采样。各事件最近一次和最大的延迟可以通过 `INFO latency` 查看。
+#[derive(Debug)]
+pub enum Latency {
+    /// 返回事件的所有采样，每个采样是 `[Unix 时间戳（秒）, 延迟（毫秒）]`，按时间顺序排列。
+    History(String),
+
+    /// 删除指定的事件，没有指定时删除所有事件。返回被删除的事件数量。
+    Reset(Vec<String>),
+}
+
+impl Latency {
+    /// 从接收到的帧中解析一个 `Latency` 实例。
+    ///
+    /// `LATENCY` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// ```text
+    /// LATENCY HISTORY event
+    /// LATENCY RESET [event [event ...]]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Latency> {
+        match &parse.next_string()?.to_uppercase()[..] {
+            "HISTORY" => Ok(Latency::History(parse.next_string()?)),
+            "RESET" => {
+                let mut events = vec![];
+
+                loop {
+                    match parse.next_string() {
+                        Ok(event) => events.push(event),
+                        Err(ParseError::EndOfStream) => break,
+                        Err(err) => return Err(err.into()),
+                    }
+                }
+
+                Ok(Latency::Reset(events))
+            }
+            subcommand => Err(format!("unknown LATENCY subcommand '{}'", subcommand).into()),
+        }
+    }
+
+    /// 将 `Latency` 命令应用到指定的 `Db` 实例。
+    ///
+    /// 响应写入到 `dst`。
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = match self {
+            Latency::History(event) => Frame::Array(
+                db.latency_history(&event)
+                    .into_iter()
+                    .map(|sample| {
+                        Frame::Array(vec![
+                            Frame::Integer(sample.time),
+                            Frame::Integer(sample.latency_ms),
+                        ])
+                    })
+                    .collect(),
+            ),
+            Latency::Reset(events) => Frame::Integer(db.reset_latency(&events) as u64),
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `Latency` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("latency".as_bytes()));
+
+        match self {
+            Latency::History(event) => {
+                frame.push_bulk(Bytes::from("history".as_bytes()));
+                frame.push_bulk(Bytes::from(event.into_bytes()));
+            }
+            Latency::Reset(events) => {
+                frame.push_bulk(Bytes::from("reset".as_bytes()));
+
+                for event in events {
+                    frame.push_bulk(Bytes::from(event.into_bytes()));
+                }
+            }
+        }
+
+        frame
+    }
+}
@@ -0,0 +1,76 @@
+use crate::cmd::Parse;
+use crate::db::Batch;
+use crate::{Connection, Db, Frame};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// 返回键中存储的列表的长度。键不存在时返回 `0`。
+#[derive(Debug)]
+pub struct LLen {
+    /// 列表的键
+    key: String,
+}
+
+impl LLen {
+    /// 创建一个新的 `LLen` 命令，获取 `key` 的长度。
+    pub(crate) fn new(key: impl ToString) -> LLen {
+        LLen {
+            key: key.to_string(),
+        }
+    }
+
+    /// 获取键
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// 从接收到的帧中解析一个 `LLen` 实例。
+    ///
+    /// `LLEN` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// 期望一个包含两个条目的数组帧。
+    ///
+    /// ```text
+    /// LLEN key
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<LLen> {
+        let key = parse.next_string()?;
+
+        Ok(LLen { key })
+    }
+
+    /// 将 `LLen` 命令应用到指定的 `Db` 实例。
+    ///
+    /// 响应写入到 `dst`。服务器调用此函数以执行接收到的命令。
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = db.batch(|batch| self.execute(batch));
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 在批处理中执行 `LLen` 命令，返回响应帧而不是写入连接。
+    pub(crate) fn execute(self, batch: &mut Batch<'_>) -> Frame {
+        match batch.llen(&self.key) {
+            Ok(len) => Frame::Integer(len),
+            Err(msg) => Frame::error(msg),
+        }
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `LLen` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("llen".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame
+    }
+}
@@ -1,21 +1,183 @@
+#[cfg(feature = "scripting")]
+mod eval;
+#[cfg(feature = "scripting")]
+pub use eval::{Eval, EvalSha, Script};
+
+#[cfg(feature = "debug")]
+mod debug;
+#[cfg(feature = "debug")]
+pub use debug::Debug;
+
+mod acl;
+pub use acl::Acl;
+
+mod asking;
+pub use asking::Asking;
+
+mod auth;
+pub use auth::Auth;
+
+mod bgsave;
+pub use bgsave::BgSave;
+
+mod bitcount;
+pub use bitcount::BitCount;
+
+mod blpop;
+pub use blpop::BLPop;
+
+mod cas;
+pub use cas::Cas;
+
+mod client;
+pub use client::ClientCommand;
+pub(crate) use client::Tracking;
+
+mod command;
+pub use command::Commands;
+
+mod config;
+pub use config::ConfigCommand;
+
+mod custom;
+pub(crate) use custom::Registry;
+pub use custom::{CommandHandler, Custom};
+
+mod dbsize;
+pub use dbsize::DbSize;
+
+mod del;
+pub use del::Del;
+
+mod dump;
+pub use dump::Dump;
+
+mod eval_batch;
+pub use eval_batch::EvalBatch;
+
+mod exists;
+pub use exists::Exists;
+
 mod get;
 pub use get::Get;
 
+mod getbit;
+pub use getbit::GetBit;
+
+mod getrange;
+pub use getrange::GetRange;
+
+mod hexpire;
+pub use hexpire::HExpire;
+
+mod hget;
+pub use hget::HGet;
+
+mod hincrby;
+pub use hincrby::{HIncrBy, HIncrByFloat};
+
+mod hset;
+pub use hset::HSet;
+
+mod incr;
+pub use incr::Incr;
+
+mod info;
+pub use info::Info;
+
+mod keys;
+pub use keys::Keys;
+
+mod llen;
+pub use llen::LLen;
+
+mod lpop;
+pub use lpop::LPop;
+
+mod latency;
+pub use latency::Latency;
+
+mod mset;
+pub use mset::MSet;
+
+mod multi;
+pub use multi::{Discard, Exec, Multi};
+
+mod object;
+pub use object::Object;
+
+mod pfadd;
+pub use pfadd::PfAdd;
+
+mod pfcount;
+pub use pfcount::PfCount;
+
+mod psync;
+pub(crate) use psync::replconf_ack;
+pub use psync::PSync;
+
+mod pubsub;
+pub use pubsub::PubSubCommand;
+
 mod publish;
 pub use publish::Publish;
 
+mod push;
+pub use push::{LPush, RPush};
+
+mod replicaof;
+pub use replicaof::ReplicaOf;
+
+mod sadd;
+pub use sadd::SAdd;
+
 mod set;
 pub use set::Set;
 
+mod setbit;
+pub use setbit::SetBit;
+
+mod setop;
+pub use setop::SetOp;
+
+mod setrange;
+pub use setrange::SetRange;
+
+mod smembers;
+pub use smembers::SMembers;
+
 mod subscribe;
+pub(crate) use subscribe::Subscriptions;
 pub use subscribe::{Subscribe, Unsubscribe};
 
 mod ping;
 pub use ping::Ping;
 
+mod quit;
+pub use quit::Quit;
+
+mod reset;
+pub use reset::Reset;
+
+mod restore;
+pub use restore::Restore;
+
 mod unknown;
 pub use unknown::Unknown;
 
+mod wait;
+pub use wait::Wait;
+
+mod xadd;
+pub use xadd::XAdd;
+
+mod xrange;
+pub use xrange::XRange;
+
+mod xread;
+pub use xread::XRead;
+
+use crate::db::{Batch, SetOperation};
 use crate::{Connection, Db, Frame, Parse, ParseError, Shutdown};
 
 /// 支持的 Redis 命令的枚举。
@@ -23,12 +185,73 @@ use crate::{Connection, Db, Frame, Parse, ParseError, Shutdown};
 /// 对 `Command` 调用的方法会委托到具体的命令实现。
 #[derive(Debug)]
 pub enum Command {
+    #[cfg(feature = "scripting")]
+    Eval(Eval),
+    #[cfg(feature = "scripting")]
+    EvalSha(EvalSha),
+    #[cfg(feature = "scripting")]
+    Script(Script),
+    #[cfg(feature = "debug")]
+    Debug(Debug),
+    Acl(Acl),
+    Asking(Asking),
+    Auth(Auth),
+    BgSave(BgSave),
+    BitCount(BitCount),
+    BLPop(BLPop),
+    Cas(Cas),
+    Client(ClientCommand),
+    Commands(Commands),
+    Config(ConfigCommand),
+    DbSize(DbSize),
+    Del(Del),
+    Discard(Discard),
+    Dump(Dump),
+    EvalBatch(EvalBatch),
+    Exec(Exec),
+    Exists(Exists),
     Get(Get),
+    GetBit(GetBit),
+    GetRange(GetRange),
+    HExpire(HExpire),
+    HGet(HGet),
+    HIncrBy(HIncrBy),
+    HIncrByFloat(HIncrByFloat),
+    HSet(HSet),
+    Incr(Incr),
+    Info(Info),
+    Keys(Keys),
+    Latency(Latency),
+    LLen(LLen),
+    LPop(LPop),
+    LPush(LPush),
+    MSet(MSet),
+    Multi(Multi),
+    Object(Object),
+    PfAdd(PfAdd),
+    PfCount(PfCount),
+    PSync(PSync),
     Publish(Publish),
+    PubSub(PubSubCommand),
+    Quit(Quit),
+    ReplicaOf(ReplicaOf),
+    Restore(Restore),
+    RPush(RPush),
+    SAdd(SAdd),
     Set(Set),
+    SetBit(SetBit),
+    SetOp(SetOp),
+    SetRange(SetRange),
+    SMembers(SMembers),
     Subscribe(Subscribe),
     Unsubscribe(Unsubscribe),
     Ping(Ping),
+    Reset(Reset),
+    Wait(Wait),
+    XAdd(XAdd),
+    XRange(XRange),
+    XRead(XRead),
+    Custom(Custom),
     Unknown(Unknown),
 }
 
@@ -41,6 +264,11 @@ impl Command {
     ///
     /// 成功时返回命令值，否则返回 `Err`。
     pub fn from_frame(frame: Frame) -> crate::Result<Command> {
+        Command::from_frame_with(frame, &Registry::default())
+    }
+
+    /// 与 `from_frame` 相同，但是不认识的命令名称会在 `custom` 中查找，找到时解析为 `Command::Custom`。
+    pub(crate) fn from_frame_with(frame: Frame, custom: &Registry) -> crate::Result<Command> {
         // 为帧值加上 `Parse` 装饰。`Parse` 提供了一个类似“光标”的 API，使得解析命令更简单。
         //
         // 帧值必须是数组变体。任何其他帧变体都会导致返回错误。
@@ -49,15 +277,25 @@ impl Command {
         // 所有的 redis 命令都以命令名称作为字符串开头。名称被读取并转换为小写以进行大小写敏感的匹配。
         let command_name = parse.next_string()?.to_lowercase();
 
-        // 匹配命令名称，将其余的解析任务委派给具体的命令。
-        let command = match &command_name[..] {
-            "get" => Command::Get(Get::parse_frames(&mut parse)?),
-            "publish" => Command::Publish(Publish::parse_frames(&mut parse)?),
-            "set" => Command::Set(Set::parse_frames(&mut parse)?),
-            "subscribe" => Command::Subscribe(Subscribe::parse_frames(&mut parse)?),
-            "unsubscribe" => Command::Unsubscribe(Unsubscribe::parse_frames(&mut parse)?),
-            "ping" => Command::Ping(Ping::parse_frames(&mut parse)?),
-            _ => {
+        // 在命令表中查找命令名称，将其余的解析任务委派给具体的命令。内置命令优先于自定义命令。
+        let command = match (lookup(&command_name), custom.get(&command_name)) {
+            (Some(info), _) => {
+                // 参数数量与命令表中的 `arity` 不符时，与 Redis 一样报告参数数量错误，而不是由具体命令的解析函数
+                // 报告一个笼统的协议错误。解析函数在参数不足时返回的 `EndOfStream` 也报告为同样的错误。
+                if !info.accepts(parse.remaining() + 1) {
+                    return Err(info.wrong_arity());
+                }
+
+                match (info.parse)(&mut parse) {
+                    Ok(command) => command,
+                    Err(err) if is_end_of_stream(&err) => return Err(info.wrong_arity()),
+                    Err(err) => return Err(err),
+                }
+            }
+            (None, Some(handler)) => {
+                Command::Custom(Custom::parse_frames(command_name, handler, &mut parse)?)
+            }
+            (None, None) => {
                 // 命令不被识别，返回一个 Unknown 命令。
                 //
                 // 这里调用 `return` 以跳过下面的 `finish()` 调用。
@@ -66,8 +304,11 @@ impl Command {
             }
         };
 
-        // 检查 `Parse` 值中是否还有未消费的字段。如果有字段未消费，表明帧格式不符合预期，将返回错误。
-        parse.finish()?;
+        // 检查 `Parse` 值中是否还有未消费的字段。如果有字段未消费，表明命令带有无法识别的参数，与 Redis 一样
+        // 以语法错误响应。
+        if parse.finish().is_err() {
+            return Err("syntax error".into());
+        }
 
         // 命令已成功解析
         Ok(command)
@@ -85,27 +326,1209 @@ impl Command {
         use Command::*;
 
         match self {
+            #[cfg(feature = "scripting")]
+            Script(cmd) => cmd.apply(db, dst).await,
+            #[cfg(feature = "debug")]
+            Debug(cmd) => cmd.apply(db, dst, shutdown).await,
+            BgSave(cmd) => cmd.apply(db, dst).await,
+            BitCount(cmd) => cmd.apply(db, dst).await,
+            BLPop(cmd) => cmd.apply(db, dst, shutdown).await,
+            Cas(cmd) => cmd.apply(db, dst).await,
+            Commands(cmd) => cmd.apply(dst).await,
+            DbSize(cmd) => cmd.apply(db, dst).await,
+            Del(cmd) => cmd.apply(db, dst).await,
+            Dump(cmd) => cmd.apply(db, dst).await,
+            EvalBatch(cmd) => cmd.apply(db, dst).await,
+            Exists(cmd) => cmd.apply(db, dst).await,
             Get(cmd) => cmd.apply(db, dst).await,
+            GetBit(cmd) => cmd.apply(db, dst).await,
+            GetRange(cmd) => cmd.apply(db, dst).await,
+            HExpire(cmd) => cmd.apply(db, dst).await,
+            HGet(cmd) => cmd.apply(db, dst).await,
+            HIncrBy(cmd) => cmd.apply(db, dst).await,
+            HIncrByFloat(cmd) => cmd.apply(db, dst).await,
+            HSet(cmd) => cmd.apply(db, dst).await,
+            Incr(cmd) => cmd.apply(db, dst).await,
+            Keys(cmd) => cmd.apply(db, dst).await,
+            Latency(cmd) => cmd.apply(db, dst).await,
+            LLen(cmd) => cmd.apply(db, dst).await,
+            LPop(cmd) => cmd.apply(db, dst).await,
+            LPush(cmd) => cmd.apply(db, dst).await,
+            MSet(cmd) => cmd.apply(db, dst).await,
+            Object(cmd) => cmd.apply(db, dst).await,
+            PfAdd(cmd) => cmd.apply(db, dst).await,
+            PfCount(cmd) => cmd.apply(db, dst).await,
             Publish(cmd) => cmd.apply(db, dst).await,
+            PubSub(cmd) => cmd.apply(db, dst).await,
+            Restore(cmd) => cmd.apply(db, dst).await,
+            RPush(cmd) => cmd.apply(db, dst).await,
+            SAdd(cmd) => cmd.apply(db, dst).await,
             Set(cmd) => cmd.apply(db, dst).await,
-            Subscribe(cmd) => cmd.apply(db, dst, shutdown).await,
+            SetBit(cmd) => cmd.apply(db, dst).await,
+            SetOp(cmd) => cmd.apply(db, dst).await,
+            SetRange(cmd) => cmd.apply(db, dst).await,
+            SMembers(cmd) => cmd.apply(db, dst).await,
             Ping(cmd) => cmd.apply(dst).await,
+            Wait(cmd) => cmd.apply(db, dst, shutdown).await,
+            XAdd(cmd) => cmd.apply(db, dst).await,
+            XRange(cmd) => cmd.apply(db, dst).await,
+            XRead(cmd) => cmd.apply(db, dst, shutdown).await,
+            Custom(cmd) => cmd.apply(dst).await,
             Unknown(cmd) => cmd.apply(dst).await,
-            // `Unsubscribe` 不能被应用。它只能在 `Subscribe` 命令的上下文中接收。
-            Unsubscribe(_) => Err("`Unsubscribe` is unsupported in this context".into()),
+            // 脚本需要按照连接的权限检查 `redis.call` 执行的命令，同样由连接处理程序直接应用。
+            #[cfg(feature = "scripting")]
+            Eval(_) | EvalSha(_) => {
+                Err(format!("`{}` is unsupported in this context", self.get_name()).into())
+            }
+            // 这些命令读取或修改连接的状态（用户、键跟踪、事务、订阅）或者服务器的配置，由连接处理程序直接应用。
+            Acl(_) | Asking(_) | Auth(_) | Client(_) | Config(_) | Discard(_) | Exec(_)
+            | Info(_) | Multi(_) | PSync(_) | Quit(_) | ReplicaOf(_) | Reset(_) | Subscribe(_)
+            | Unsubscribe(_) => {
+                Err(format!("`{}` is unsupported in this context", self.get_name()).into())
+            }
         }
     }
 
-    /// 返回命令名称
-    pub(crate) fn get_name(&self) -> &str {
+    /// 在 `Db::batch` 持有的锁内执行命令，返回响应帧而不是写入连接。
+    ///
+    /// 只有不会阻塞、也不会改变连接状态的命令可以这样执行，其他命令返回 `Err`。
+    /// `EVALBATCH`、`EXEC` 和脚本中的 `redis.call` 都通过此方法执行命令。
+    pub(crate) fn execute(self, batch: &mut Batch<'_>) -> crate::Result<Frame> {
+        use Command::*;
+
         match self {
+            BitCount(cmd) => Ok(cmd.execute(batch)),
+            Cas(cmd) => Ok(cmd.execute(batch)),
+            Del(cmd) => Ok(cmd.execute(batch)),
+            Dump(cmd) => Ok(cmd.execute(batch)),
+            Exists(cmd) => Ok(cmd.execute(batch)),
+            Get(cmd) => Ok(cmd.execute(batch)),
+            GetBit(cmd) => Ok(cmd.execute(batch)),
+            GetRange(cmd) => Ok(cmd.execute(batch)),
+            HExpire(cmd) => Ok(cmd.execute(batch)),
+            HGet(cmd) => Ok(cmd.execute(batch)),
+            HIncrBy(cmd) => Ok(cmd.execute(batch)),
+            HIncrByFloat(cmd) => Ok(cmd.execute(batch)),
+            HSet(cmd) => Ok(cmd.execute(batch)),
+            Incr(cmd) => Ok(cmd.execute(batch)),
+            LLen(cmd) => Ok(cmd.execute(batch)),
+            LPop(cmd) => Ok(cmd.execute(batch)),
+            LPush(cmd) => Ok(cmd.execute(batch)),
+            MSet(cmd) => Ok(cmd.execute(batch)),
+            Object(cmd) => Ok(cmd.execute(batch)),
+            PfAdd(cmd) => Ok(cmd.execute(batch)),
+            PfCount(cmd) => Ok(cmd.execute(batch)),
+            Publish(cmd) => Ok(cmd.execute(batch)),
+            Restore(cmd) => Ok(cmd.execute(batch)),
+            RPush(cmd) => Ok(cmd.execute(batch)),
+            SAdd(cmd) => Ok(cmd.execute(batch)),
+            Set(cmd) => Ok(cmd.execute(batch)),
+            SetBit(cmd) => Ok(cmd.execute(batch)),
+            SetOp(cmd) => Ok(cmd.execute(batch)),
+            SetRange(cmd) => Ok(cmd.execute(batch)),
+            SMembers(cmd) => Ok(cmd.execute(batch)),
+            Ping(cmd) => Ok(cmd.execute()),
+            XAdd(cmd) => Ok(cmd.execute(batch)),
+            XRange(cmd) => Ok(cmd.execute(batch)),
+            cmd => {
+                Err(format!("command '{}' cannot be executed atomically", cmd.get_name()).into())
+            }
+        }
+    }
+
+    /// 与 `execute` 相同，并通过 `Batch::propagate` 记录命令的效果，用于 AOF 和复制。`frame` 是命令的帧。
+    ///
+    /// 效果是重新执行之后得到相同结果的命令：相对的过期时间被改写为绝对的 Unix 时间戳
+    /// （`SET ... PXAT`、`RESTORE ... ABSTTL` 和 `HPEXPIREAT`），`XADD *` 被改写为生成的 ID，
+    /// 其他写命令按原样记录。以错误响应的命令没有修改数据库，不被记录。`EVALBATCH` 记录其中每个命令的效果。
+    pub(crate) fn execute_propagated(
+        self,
+        batch: &mut Batch<'_>,
+        frame: Frame,
+    ) -> crate::Result<Frame> {
+        use Command::*;
+
+        if let EvalBatch(cmd) = self {
+            return Ok(cmd.execute(batch));
+        }
+
+        if !batch.is_propagating() || !self.is_write() {
+            return self.execute(batch);
+        }
+
+        match self {
+            HExpire(cmd) => Ok(cmd.execute_propagated(batch)),
+            Restore(cmd) => Ok(cmd.execute_propagated(batch)),
+            Set(cmd) => Ok(cmd.execute_propagated(batch)),
+            XAdd(cmd) => Ok(cmd.execute_propagated(batch)),
+            cmd => {
+                let response = cmd.execute(batch)?;
+
+                if !matches!(response, Frame::Error(_)) {
+                    batch.propagate(frame);
+                }
+
+                Ok(response)
+            }
+        }
+    }
+
+    /// 命令能否通过 `execute` 在 `Db::batch` 持有的锁内执行。
+    ///
+    /// `EVALBATCH` 和 `MULTI` 事务只接受这些命令。
+    pub(crate) fn is_atomic(&self) -> bool {
+        use Command::*;
+
+        matches!(
+            self,
+            BitCount(_)
+                | Cas(_)
+                | Del(_)
+                | Dump(_)
+                | Exists(_)
+                | Get(_)
+                | GetBit(_)
+                | GetRange(_)
+                | HExpire(_)
+                | HGet(_)
+                | HIncrBy(_)
+                | HIncrByFloat(_)
+                | HSet(_)
+                | Incr(_)
+                | LLen(_)
+                | LPop(_)
+                | LPush(_)
+                | MSet(_)
+                | Object(_)
+                | PfAdd(_)
+                | PfCount(_)
+                | Publish(_)
+                | Restore(_)
+                | RPush(_)
+                | SAdd(_)
+                | Set(_)
+                | SetBit(_)
+                | SetOp(_)
+                | SetRange(_)
+                | SMembers(_)
+                | Ping(_)
+                | XAdd(_)
+                | XRange(_)
+        )
+    }
+
+    /// 命令是否可能修改数据库，用于只读模式。
+    ///
+    /// 根据命令表中的 `write` 标志判断，`EVALBATCH` 检查其中的每个命令。
+    /// 脚本可能通过 `redis.call` 写入，因此总是被视为写命令。
+    pub(crate) fn is_write(&self) -> bool {
+        match self {
+            #[cfg(feature = "scripting")]
+            Command::Eval(_) | Command::EvalSha(_) => true,
+            Command::EvalBatch(batch) => batch.commands().iter().any(Command::is_write),
+            cmd => lookup(cmd.get_name()).is_some_and(|info| info.flags.contains(&"write")),
+        }
+    }
+
+    /// 命令是否会等待其他连接或者副本，例如 `BLPOP`、`WAIT` 和 `PSYNC`。
+    ///
+    /// 这些命令的执行时间取决于等待的事件，不计入延迟监控。
+    pub(crate) fn may_wait(&self) -> bool {
+        matches!(self, Command::PSync(_) | Command::Wait(_))
+            || lookup(self.get_name()).is_some_and(|info| info.flags.contains(&"blocking"))
+    }
+
+    /// 命令是否通过 `execute_propagated` 执行并将效果写入 AOF 和复制积压缓冲区，即原子的写命令和
+    /// 包含写命令的 `EVALBATCH`。加载 AOF 和副本只接受这些命令。
+    ///
+    /// 脚本和阻塞命令（`EVAL`、`BLPOP` 等）自行记录它们的效果：`BLPOP` 记录为 `LPOP`，
+    /// 脚本记录 `redis.call` 执行的写命令。
+    pub(crate) fn is_propagated(&self) -> bool {
+        self.is_write() && (self.is_atomic() || matches!(self, Command::EvalBatch(_)))
+    }
+
+    /// 返回命令访问的键，用于集群模式下的槽检查和键跟踪。不访问键的命令返回空列表。
+    pub fn keys(&self) -> Vec<&[u8]> {
+        use Command::*;
+
+        match self {
+            #[cfg(feature = "scripting")]
+            Eval(cmd) => cmd.keys().iter().map(|key| &key[..]).collect(),
+            #[cfg(feature = "scripting")]
+            EvalSha(cmd) => cmd.keys().iter().map(|key| &key[..]).collect(),
+            #[cfg(feature = "debug")]
+            Debug(cmd) => cmd.key().map(str::as_bytes).into_iter().collect(),
+            BitCount(cmd) => vec![cmd.key().as_bytes()],
+            BLPop(cmd) => cmd.keys().iter().map(|key| key.as_bytes()).collect(),
+            Cas(cmd) => vec![cmd.key().as_bytes()],
+            Del(cmd) => cmd.keys().iter().map(|key| key.as_bytes()).collect(),
+            Dump(cmd) => vec![cmd.key().as_bytes()],
+            EvalBatch(cmd) => cmd.commands().iter().flat_map(Command::keys).collect(),
+            Exists(cmd) => cmd.keys().iter().map(|key| key.as_bytes()).collect(),
+            Get(cmd) => vec![cmd.key().as_bytes()],
+            GetBit(cmd) => vec![cmd.key().as_bytes()],
+            GetRange(cmd) => vec![cmd.key().as_bytes()],
+            HExpire(cmd) => vec![cmd.key().as_bytes()],
+            HGet(cmd) => vec![cmd.key().as_bytes()],
+            HIncrBy(cmd) => vec![cmd.key().as_bytes()],
+            HIncrByFloat(cmd) => vec![cmd.key().as_bytes()],
+            HSet(cmd) => vec![cmd.key().as_bytes()],
+            Incr(cmd) => vec![cmd.key().as_bytes()],
+            LLen(cmd) => vec![cmd.key().as_bytes()],
+            LPop(cmd) => vec![cmd.key().as_bytes()],
+            LPush(cmd) => vec![cmd.key().as_bytes()],
+            MSet(cmd) => cmd.pairs().iter().map(|(key, _)| key.as_bytes()).collect(),
+            Object(cmd) => vec![cmd.key().as_bytes()],
+            PfAdd(cmd) => vec![cmd.key().as_bytes()],
+            PfCount(cmd) => cmd.keys().iter().map(|key| key.as_bytes()).collect(),
+            Restore(cmd) => vec![cmd.key().as_bytes()],
+            RPush(cmd) => vec![cmd.key().as_bytes()],
+            SAdd(cmd) => vec![cmd.key().as_bytes()],
+            Set(cmd) => vec![cmd.key().as_bytes()],
+            SetBit(cmd) => vec![cmd.key().as_bytes()],
+            SetOp(cmd) => cmd
+                .destination()
+                .into_iter()
+                .chain(cmd.keys().iter().map(String::as_str))
+                .map(str::as_bytes)
+                .collect(),
+            SetRange(cmd) => vec![cmd.key().as_bytes()],
+            SMembers(cmd) => vec![cmd.key().as_bytes()],
+            XAdd(cmd) => vec![cmd.key().as_bytes()],
+            XRange(cmd) => vec![cmd.key().as_bytes()],
+            XRead(cmd) => cmd.keys().map(str::as_bytes).collect(),
+            _ => vec![],
+        }
+    }
+
+    /// 返回命令名称，均为小写。`SINTER` 等共用一个变体的命令返回各自的名称。
+    pub fn get_name(&self) -> &str {
+        match self {
+            #[cfg(feature = "scripting")]
+            Command::Eval(_) => "eval",
+            #[cfg(feature = "scripting")]
+            Command::EvalSha(_) => "evalsha",
+            #[cfg(feature = "scripting")]
+            Command::Script(_) => "script",
+            #[cfg(feature = "debug")]
+            Command::Debug(_) => "debug",
+            Command::Acl(_) => "acl",
+            Command::Asking(_) => "asking",
+            Command::Auth(_) => "auth",
+            Command::BgSave(_) => "bgsave",
+            Command::BitCount(_) => "bitcount",
+            Command::BLPop(_) => "blpop",
+            Command::Cas(_) => "cas",
+            Command::Client(_) => "client",
+            Command::Commands(_) => "command",
+            Command::Config(_) => "config",
+            Command::DbSize(_) => "dbsize",
+            Command::Del(_) => "del",
+            Command::Discard(_) => "discard",
+            Command::Dump(_) => "dump",
+            Command::EvalBatch(_) => "evalbatch",
+            Command::Exec(_) => "exec",
+            Command::Exists(_) => "exists",
             Command::Get(_) => "get",
-            Command::Publish(_) => "pub",
-            Command::Set(_) => "set",
+            Command::GetBit(_) => "getbit",
+            Command::GetRange(_) => "getrange",
+            Command::HExpire(cmd) => cmd.get_name(),
+            Command::HGet(_) => "hget",
+            Command::HIncrBy(_) => "hincrby",
+            Command::HIncrByFloat(_) => "hincrbyfloat",
+            Command::HSet(_) => "hset",
+            Command::Incr(_) => "incr",
+            Command::Info(_) => "info",
+            Command::Keys(_) => "keys",
+            Command::Latency(_) => "latency",
+            Command::LLen(_) => "llen",
+            Command::LPop(_) => "lpop",
+            Command::LPush(_) => "lpush",
+            Command::MSet(_) => "mset",
+            Command::Multi(_) => "multi",
+            Command::Object(_) => "object",
+            Command::PfAdd(_) => "pfadd",
+            Command::PfCount(_) => "pfcount",
+            Command::PSync(_) => "psync",
+            Command::PubSub(_) => "pubsub",
+            Command::Quit(_) => "quit",
+            Command::ReplicaOf(_) => "replicaof",
+            Command::Restore(_) => "restore",
+            Command::RPush(_) => "rpush",
+            Command::SAdd(_) => "sadd",
+            Command::Set(cmd) => cmd.get_name(),
+            Command::Publish(cmd) => cmd.get_name(),
+            Command::SetBit(_) => "setbit",
+            Command::SetOp(cmd) => cmd.get_name(),
+            Command::SetRange(_) => "setrange",
+            Command::SMembers(_) => "smembers",
             Command::Subscribe(_) => "subscribe",
             Command::Unsubscribe(_) => "unsubscribe",
             Command::Ping(_) => "ping",
+            Command::Reset(_) => "reset",
+            Command::Wait(_) => "wait",
+            Command::XAdd(_) => "xadd",
+            Command::XRange(_) => "xrange",
+            Command::XRead(_) => "xread",
+            Command::Custom(cmd) => cmd.get_name(),
             Command::Unknown(cmd) => cmd.get_name(),
         }
     }
 }
+
+/// 命令表中的一个条目：命令的解析函数，以及用于 `COMMAND` 和 `COMMAND DOCS` 的元数据。
+///
+/// 元数据字段的含义与 Redis 的 `COMMAND` 响应相同。
+#[derive(Debug)]
+pub(crate) struct CommandInfo {
+    /// 命令名称，均为小写
+    pub(crate) name: &'static str,
+
+    /// 解析命令名称之后的参数
+    pub(crate) parse: fn(&mut Parse) -> crate::Result<Command>,
+
+    /// 参数的数量，包括命令名称本身。负数 `-N` 表示至少 `N` 个参数。
+    pub(crate) arity: i64,
+
+    /// 命令的标志，例如 `write`、`readonly`、`fast`
+    pub(crate) flags: &'static [&'static str],
+
+    /// 第一个键的位置。`0` 表示命令不访问键，或者键的位置不固定。
+    pub(crate) first_key: i64,
+
+    /// 最后一个键的位置。负数从最后一个参数开始计算，`-1` 表示最后一个参数。
+    pub(crate) last_key: i64,
+
+    /// 相邻两个键之间的距离
+    pub(crate) step: i64,
+
+    /// 命令所属的分组，例如 `string`、`pubsub`
+    pub(crate) group: &'static str,
+
+    /// 简短的描述
+    pub(crate) summary: &'static str,
+}
+
+/// 所有内置命令的命令表，按名称排序。
+///
+/// `Command::from_frame` 根据这里的条目解析命令，`COMMAND` 返回这里的元数据。添加新命令时，需要在这里添加对应的条目。
+pub(crate) static COMMANDS: &[CommandInfo] = &[
+    CommandInfo {
+        name: "acl",
+        parse: |parse| Ok(Command::Acl(Acl::parse_frames(parse)?)),
+        arity: -2,
+        flags: &["noscript", "loading", "stale"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        group: "server",
+        summary: "Returns the authenticated username or the list of ACL users.",
+    },
+    CommandInfo {
+        name: "asking",
+        parse: |parse| Ok(Command::Asking(Asking::parse_frames(parse)?)),
+        arity: 1,
+        flags: &["fast"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        group: "cluster",
+        summary: "Signals that a cluster client is following an -ASK redirect.",
+    },
+    CommandInfo {
+        name: "auth",
+        parse: |parse| Ok(Command::Auth(Auth::parse_frames(parse)?)),
+        arity: -2,
+        flags: &["noscript", "loading", "stale", "fast", "no_auth"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        group: "connection",
+        summary: "Authenticates the connection.",
+    },
+    CommandInfo {
+        name: "bgsave",
+        parse: |parse| Ok(Command::BgSave(BgSave::parse_frames(parse)?)),
+        arity: 1,
+        flags: &["admin", "noscript", "no_async_loading"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        group: "server",
+        summary: "Asynchronously saves the database to disk.",
+    },
+    CommandInfo {
+        name: "bitcount",
+        parse: |parse| Ok(Command::BitCount(BitCount::parse_frames(parse)?)),
+        arity: -2,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "bitmap",
+        summary: "Counts the number of set bits in a string.",
+    },
+    CommandInfo {
+        name: "blpop",
+        parse: |parse| Ok(Command::BLPop(BLPop::parse_frames(parse)?)),
+        arity: -3,
+        flags: &["write", "blocking"],
+        first_key: 1,
+        last_key: -2,
+        step: 1,
+        group: "list",
+        summary: "Removes and returns the first element in a list. Blocks until an element is available otherwise.",
+    },
+    CommandInfo {
+        name: "cas",
+        parse: |parse| Ok(Command::Cas(Cas::parse_frames(parse)?)),
+        arity: 4,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "string",
+        summary: "Replaces the value of a key only if it equals the expected value.",
+    },
+    CommandInfo {
+        name: "client",
+        parse: |parse| Ok(Command::Client(ClientCommand::parse_frames(parse)?)),
+        arity: -2,
+        flags: &["noscript", "loading", "stale"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        group: "connection",
+        summary: "A container for client connection commands.",
+    },
+    CommandInfo {
+        name: "command",
+        parse: |parse| Ok(Command::Commands(Commands::parse_frames(parse)?)),
+        arity: -1,
+        flags: &["loading", "stale"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        group: "server",
+        summary: "Returns detailed information about all commands.",
+    },
+    CommandInfo {
+        name: "config",
+        parse: |parse| Ok(Command::Config(ConfigCommand::parse_frames(parse)?)),
+        arity: -3,
+        flags: &["admin", "noscript", "loading", "stale"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        group: "server",
+        summary: "Gets or sets server configuration parameters.",
+    },
+    CommandInfo {
+        name: "dbsize",
+        parse: |parse| Ok(Command::DbSize(DbSize::parse_frames(parse)?)),
+        arity: 1,
+        flags: &["readonly", "fast"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        group: "server",
+        summary: "Returns the number of keys in the database.",
+    },
+    #[cfg(feature = "debug")]
+    CommandInfo {
+        name: "debug",
+        parse: |parse| Ok(Command::Debug(Debug::parse_frames(parse)?)),
+        arity: -2,
+        flags: &["admin", "noscript", "loading", "stale"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        group: "server",
+        summary: "A container for debugging commands.",
+    },
+    CommandInfo {
+        name: "del",
+        parse: |parse| Ok(Command::Del(Del::parse_frames(parse)?)),
+        arity: -2,
+        flags: &["write"],
+        first_key: 1,
+        last_key: -1,
+        step: 1,
+        group: "generic",
+        summary: "Deletes one or more keys.",
+    },
+    CommandInfo {
+        name: "discard",
+        parse: |parse| Ok(Command::Discard(Discard::parse_frames(parse)?)),
+        arity: 1,
+        flags: &["noscript", "loading", "stale", "fast"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        group: "transactions",
+        summary: "Discards a transaction.",
+    },
+    CommandInfo {
+        name: "dump",
+        parse: |parse| Ok(Command::Dump(Dump::parse_frames(parse)?)),
+        arity: 2,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "generic",
+        summary: "Returns a serialized representation of the value stored at a key.",
+    },
+    #[cfg(feature = "scripting")]
+    CommandInfo {
+        name: "eval",
+        parse: |parse| Ok(Command::Eval(Eval::parse_frames(parse)?)),
+        arity: -3,
+        flags: &["noscript", "movablekeys"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        group: "scripting",
+        summary: "Executes a server-side Lua script.",
+    },
+    CommandInfo {
+        name: "evalbatch",
+        parse: |parse| Ok(Command::EvalBatch(EvalBatch::parse_frames(parse)?)),
+        arity: -2,
+        flags: &["write", "noscript", "movablekeys"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        group: "scripting",
+        summary: "Executes a batch of commands atomically.",
+    },
+    #[cfg(feature = "scripting")]
+    CommandInfo {
+        name: "evalsha",
+        parse: |parse| Ok(Command::EvalSha(EvalSha::parse_frames(parse)?)),
+        arity: -3,
+        flags: &["noscript", "movablekeys"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        group: "scripting",
+        summary: "Executes a server-side Lua script by SHA1 digest.",
+    },
+    CommandInfo {
+        name: "exec",
+        parse: |parse| Ok(Command::Exec(Exec::parse_frames(parse)?)),
+        arity: 1,
+        flags: &["noscript", "loading", "stale", "skip_slowlog"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        group: "transactions",
+        summary: "Executes all commands in a transaction.",
+    },
+    CommandInfo {
+        name: "exists",
+        parse: |parse| Ok(Command::Exists(Exists::parse_frames(parse)?)),
+        arity: -2,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: -1,
+        step: 1,
+        group: "generic",
+        summary: "Determines whether one or more keys exist.",
+    },
+    CommandInfo {
+        name: "get",
+        parse: |parse| Ok(Command::Get(Get::parse_frames(parse)?)),
+        arity: 2,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "string",
+        summary: "Returns the string value of a key.",
+    },
+    CommandInfo {
+        name: "getbit",
+        parse: |parse| Ok(Command::GetBit(GetBit::parse_frames(parse)?)),
+        arity: 3,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "bitmap",
+        summary: "Returns a bit value by offset.",
+    },
+    CommandInfo {
+        name: "getrange",
+        parse: |parse| Ok(Command::GetRange(GetRange::parse_frames(parse)?)),
+        arity: 4,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "string",
+        summary: "Returns a substring of the string stored at a key.",
+    },
+    CommandInfo {
+        name: "hexpire",
+        parse: |parse| Ok(Command::HExpire(HExpire::parse_frames(parse)?)),
+        arity: -6,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "hash",
+        summary: "Set expiry for hash field using relative time to expire (seconds).",
+    },
+    CommandInfo {
+        name: "hget",
+        parse: |parse| Ok(Command::HGet(HGet::parse_frames(parse)?)),
+        arity: 3,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "hash",
+        summary: "Returns the value of a field in a hash.",
+    },
+    CommandInfo {
+        name: "hincrby",
+        parse: |parse| Ok(Command::HIncrBy(HIncrBy::parse_frames(parse)?)),
+        arity: 4,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "hash",
+        summary: "Increments the integer value of a field in a hash by a number. Uses 0 as initial value if the field doesn't exist.",
+    },
+    CommandInfo {
+        name: "hincrbyfloat",
+        parse: |parse| Ok(Command::HIncrByFloat(HIncrByFloat::parse_frames(parse)?)),
+        arity: 4,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "hash",
+        summary: "Increments the floating point value of a field by a number. Uses 0 as initial value if the field doesn't exist.",
+    },
+    CommandInfo {
+        name: "hpexpireat",
+        parse: |parse| Ok(Command::HExpire(HExpire::parse_hpexpireat_frames(parse)?)),
+        arity: -6,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "hash",
+        summary: "Set expiry for hash field using an absolute Unix timestamp (milliseconds).",
+    },
+    CommandInfo {
+        name: "hset",
+        parse: |parse| Ok(Command::HSet(HSet::parse_frames(parse)?)),
+        arity: -4,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "hash",
+        summary: "Creates or modifies the value of a field in a hash.",
+    },
+    CommandInfo {
+        name: "incr",
+        parse: |parse| Ok(Command::Incr(Incr::parse_frames(parse)?)),
+        arity: 2,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "string",
+        summary: "Increments the integer value of a key by one.",
+    },
+    CommandInfo {
+        name: "info",
+        parse: |parse| Ok(Command::Info(Info::parse_frames(parse)?)),
+        arity: -1,
+        flags: &["loading", "stale"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        group: "server",
+        summary: "Returns information and statistics about the server.",
+    },
+    CommandInfo {
+        name: "keys",
+        parse: |parse| Ok(Command::Keys(Keys::parse_frames(parse)?)),
+        arity: 2,
+        flags: &["readonly"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        group: "generic",
+        summary: "Returns all key names that match a pattern.",
+    },
+    CommandInfo {
+        name: "latency",
+        parse: |parse| Ok(Command::Latency(Latency::parse_frames(parse)?)),
+        arity: -2,
+        flags: &["admin", "noscript", "loading", "stale"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        group: "server",
+        summary: "Returns or resets the latency samples recorded for an event.",
+    },
+    CommandInfo {
+        name: "llen",
+        parse: |parse| Ok(Command::LLen(LLen::parse_frames(parse)?)),
+        arity: 2,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "list",
+        summary: "Returns the length of a list.",
+    },
+    CommandInfo {
+        name: "lpop",
+        parse: |parse| Ok(Command::LPop(LPop::parse_frames(parse)?)),
+        arity: 2,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "list",
+        summary: "Returns the first element in a list after removing it.",
+    },
+    CommandInfo {
+        name: "lpush",
+        parse: |parse| Ok(Command::LPush(LPush::parse_frames(parse)?)),
+        arity: -3,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "list",
+        summary: "Prepends one or more elements to a list.",
+    },
+    CommandInfo {
+        name: "mpublish",
+        parse: |parse| Ok(Command::Publish(Publish::parse_mpublish_frames(parse)?)),
+        arity: -3,
+        flags: &["pubsub", "loading", "stale", "fast"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        group: "pubsub",
+        summary: "Posts one or more messages to a channel in order.",
+    },
+    CommandInfo {
+        name: "mset",
+        parse: |parse| Ok(Command::MSet(MSet::parse_frames(parse)?)),
+        arity: -3,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: -1,
+        step: 2,
+        group: "string",
+        summary: "Atomically creates or modifies the string values of one or more keys.",
+    },
+    CommandInfo {
+        name: "multi",
+        parse: |parse| Ok(Command::Multi(Multi::parse_frames(parse)?)),
+        arity: 1,
+        flags: &["noscript", "loading", "stale", "fast"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        group: "transactions",
+        summary: "Starts a transaction.",
+    },
+    CommandInfo {
+        name: "object",
+        parse: |parse| Ok(Command::Object(Object::parse_frames(parse)?)),
+        arity: 3,
+        flags: &["readonly"],
+        first_key: 2,
+        last_key: 2,
+        step: 1,
+        group: "generic",
+        summary: "Returns the idle time or the access frequency of a key.",
+    },
+    CommandInfo {
+        name: "pfadd",
+        parse: |parse| Ok(Command::PfAdd(PfAdd::parse_frames(parse)?)),
+        arity: -2,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "hyperloglog",
+        summary: "Adds elements to a HyperLogLog key.",
+    },
+    CommandInfo {
+        name: "pfcount",
+        parse: |parse| Ok(Command::PfCount(PfCount::parse_frames(parse)?)),
+        arity: -2,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: -1,
+        step: 1,
+        group: "hyperloglog",
+        summary: "Returns the approximated cardinality of the set(s) observed by the HyperLogLog key(s).",
+    },
+    CommandInfo {
+        name: "ping",
+        parse: |parse| Ok(Command::Ping(Ping::parse_frames(parse)?)),
+        arity: -1,
+        flags: &["fast", "stale"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        group: "connection",
+        summary: "Returns the server's liveliness response.",
+    },
+    CommandInfo {
+        name: "psetex",
+        parse: |parse| Ok(Command::Set(Set::parse_setex_frames(parse, true)?)),
+        arity: 4,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "string",
+        summary: "Sets both string value and expiration time in milliseconds of a key.",
+    },
+    CommandInfo {
+        name: "psync",
+        parse: |parse| Ok(Command::PSync(PSync::parse_frames(parse)?)),
+        arity: 3,
+        flags: &["admin", "noscript", "no_async_loading", "no_multi"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        group: "server",
+        summary: "An internal command used in replication.",
+    },
+    CommandInfo {
+        name: "publish",
+        parse: |parse| Ok(Command::Publish(Publish::parse_frames(parse)?)),
+        arity: 3,
+        flags: &["pubsub", "loading", "stale", "fast"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        group: "pubsub",
+        summary: "Posts a message to a channel.",
+    },
+    CommandInfo {
+        name: "pubsub",
+        parse: |parse| Ok(Command::PubSub(PubSubCommand::parse_frames(parse)?)),
+        arity: -2,
+        flags: &["pubsub", "loading", "stale"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        group: "pubsub",
+        summary: "A container for Pub/Sub commands.",
+    },
+    CommandInfo {
+        name: "quit",
+        parse: |parse| Ok(Command::Quit(Quit::parse_frames(parse)?)),
+        arity: -1,
+        flags: &["noscript", "loading", "stale", "fast", "no_auth"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        group: "connection",
+        summary: "Closes the connection.",
+    },
+    CommandInfo {
+        name: "replicaof",
+        parse: |parse| Ok(Command::ReplicaOf(ReplicaOf::parse_frames(parse)?)),
+        arity: 3,
+        flags: &["admin", "noscript", "stale", "no_async_loading"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        group: "server",
+        summary: "Configures a server as replica of another, or promotes it to a primary.",
+    },
+    CommandInfo {
+        name: "reset",
+        parse: |parse| Ok(Command::Reset(Reset::parse_frames(parse)?)),
+        arity: 1,
+        flags: &["noscript", "loading", "stale", "fast"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        group: "connection",
+        summary: "Resets the connection.",
+    },
+    CommandInfo {
+        name: "restore",
+        parse: |parse| Ok(Command::Restore(Restore::parse_frames(parse)?)),
+        arity: -4,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "generic",
+        summary: "Creates a key from the serialized representation of a value.",
+    },
+    CommandInfo {
+        name: "rpush",
+        parse: |parse| Ok(Command::RPush(RPush::parse_frames(parse)?)),
+        arity: -3,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "list",
+        summary: "Appends one or more elements to a list.",
+    },
+    CommandInfo {
+        name: "sadd",
+        parse: |parse| Ok(Command::SAdd(SAdd::parse_frames(parse)?)),
+        arity: -3,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "set",
+        summary: "Adds one or more members to a set. Creates the key if it doesn't exist.",
+    },
+    #[cfg(feature = "scripting")]
+    CommandInfo {
+        name: "script",
+        parse: |parse| Ok(Command::Script(Script::parse_frames(parse)?)),
+        arity: -2,
+        flags: &["noscript"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        group: "scripting",
+        summary: "A container for Lua scripts management commands.",
+    },
+    CommandInfo {
+        name: "sdiff",
+        parse: |parse| Ok(Command::SetOp(SetOp::parse_frames(SetOperation::Diff, false, parse)?)),
+        arity: -2,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: -1,
+        step: 1,
+        group: "set",
+        summary: "Returns the difference of multiple sets.",
+    },
+    CommandInfo {
+        name: "sdiffstore",
+        parse: |parse| Ok(Command::SetOp(SetOp::parse_frames(SetOperation::Diff, true, parse)?)),
+        arity: -3,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: -1,
+        step: 1,
+        group: "set",
+        summary: "Stores the difference of multiple sets in a key.",
+    },
+    CommandInfo {
+        name: "set",
+        parse: |parse| Ok(Command::Set(Set::parse_frames(parse)?)),
+        arity: -3,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "string",
+        summary: "Sets the string value of a key, ignoring its type.",
+    },
+    CommandInfo {
+        name: "setbit",
+        parse: |parse| Ok(Command::SetBit(SetBit::parse_frames(parse)?)),
+        arity: 4,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "bitmap",
+        summary: "Sets or clears the bit at offset of the string value.",
+    },
+    CommandInfo {
+        name: "setex",
+        parse: |parse| Ok(Command::Set(Set::parse_setex_frames(parse, false)?)),
+        arity: 4,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "string",
+        summary: "Sets the string value and expiration time of a key.",
+    },
+    CommandInfo {
+        name: "setrange",
+        parse: |parse| Ok(Command::SetRange(SetRange::parse_frames(parse)?)),
+        arity: 4,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "string",
+        summary: "Overwrites a part of a string value with another by an offset.",
+    },
+    CommandInfo {
+        name: "sinter",
+        parse: |parse| Ok(Command::SetOp(SetOp::parse_frames(SetOperation::Inter, false, parse)?)),
+        arity: -2,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: -1,
+        step: 1,
+        group: "set",
+        summary: "Returns the intersect of multiple sets.",
+    },
+    CommandInfo {
+        name: "sinterstore",
+        parse: |parse| Ok(Command::SetOp(SetOp::parse_frames(SetOperation::Inter, true, parse)?)),
+        arity: -3,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: -1,
+        step: 1,
+        group: "set",
+        summary: "Stores the intersect of multiple sets in a key.",
+    },
+    CommandInfo {
+        name: "smembers",
+        parse: |parse| Ok(Command::SMembers(SMembers::parse_frames(parse)?)),
+        arity: 2,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "set",
+        summary: "Returns all members of a set.",
+    },
+    CommandInfo {
+        name: "subscribe",
+        parse: |parse| Ok(Command::Subscribe(Subscribe::parse_frames(parse)?)),
+        arity: -2,
+        flags: &["pubsub", "noscript", "loading", "stale"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        group: "pubsub",
+        summary: "Listens for messages published to channels.",
+    },
+    CommandInfo {
+        name: "sunion",
+        parse: |parse| Ok(Command::SetOp(SetOp::parse_frames(SetOperation::Union, false, parse)?)),
+        arity: -2,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: -1,
+        step: 1,
+        group: "set",
+        summary: "Returns the union of multiple sets.",
+    },
+    CommandInfo {
+        name: "sunionstore",
+        parse: |parse| Ok(Command::SetOp(SetOp::parse_frames(SetOperation::Union, true, parse)?)),
+        arity: -3,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: -1,
+        step: 1,
+        group: "set",
+        summary: "Stores the union of multiple sets in a key.",
+    },
+    CommandInfo {
+        name: "unsubscribe",
+        parse: |parse| Ok(Command::Unsubscribe(Unsubscribe::parse_frames(parse)?)),
+        arity: -1,
+        flags: &["pubsub", "noscript", "loading", "stale"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        group: "pubsub",
+        summary: "Stops listening to messages posted to channels.",
+    },
+    CommandInfo {
+        name: "wait",
+        parse: |parse| Ok(Command::Wait(Wait::parse_frames(parse)?)),
+        arity: 3,
+        flags: &["noscript"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        group: "generic",
+        summary: "Blocks until the asynchronous replication of all preceding write commands is acknowledged.",
+    },
+    CommandInfo {
+        name: "xadd",
+        parse: |parse| Ok(Command::XAdd(XAdd::parse_frames(parse)?)),
+        arity: -5,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "stream",
+        summary: "Appends a new message to a stream.",
+    },
+    CommandInfo {
+        name: "xrange",
+        parse: |parse| Ok(Command::XRange(XRange::parse_frames(parse)?)),
+        arity: -4,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        group: "stream",
+        summary: "Returns the messages from a stream within a range of IDs.",
+    },
+    CommandInfo {
+        name: "xread",
+        parse: |parse| Ok(Command::XRead(XRead::parse_frames(parse)?)),
+        arity: -4,
+        flags: &["readonly", "blocking", "movablekeys"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        group: "stream",
+        summary: "Returns messages from multiple streams with IDs greater than the ones requested.",
+    },
+];
+
+impl CommandInfo {
+    /// 命令是否接受 `argc` 个参数（包括命令名称本身）。
+    fn accepts(&self, argc: usize) -> bool {
+        let argc = argc as i64;
+
+        if self.arity >= 0 {
+            argc == self.arity
+        } else {
+            argc >= -self.arity
+        }
+    }
+
+    /// 参数数量错误时返回的错误。
+    fn wrong_arity(&self) -> crate::Error {
+        format!("wrong number of arguments for '{}' command", self.name).into()
+    }
+}
+
+/// 解析函数返回的错误是否是因为参数不足。
+fn is_end_of_stream(err: &crate::Error) -> bool {
+    matches!(
+        err.downcast_ref::<ParseError>(),
+        Some(ParseError::EndOfStream)
+    )
+}
+
+/// 按名称查找命令的元数据，名称不区分大小写。
+pub(crate) fn lookup(name: &str) -> Option<&'static CommandInfo> {
+    COMMANDS
+        .iter()
+        .find(|info| info.name.eq_ignore_ascii_case(name))
+}
@@ -0,0 +1,100 @@
+use crate::cmd::{Parse, ParseError};
+use crate::db::Batch;
+use crate::{Connection, Db, Frame};
+
+use bytes::Bytes;
+use bytestring::ByteString;
+use tracing::{debug, instrument};
+
+/// 设置多个键的值。
+///
+/// 已有的值被替换，原有的过期时间被丢弃。所有键原子地写入，其他客户端不会看到只写入了一部分的键。
+#[derive(Debug)]
+pub struct MSet {
+    /// 要设置的键和值
+    pairs: Vec<(String, Bytes)>,
+}
+
+impl MSet {
+    /// 创建一个新的 `MSet` 命令，设置 `pairs` 中的每个键。
+    pub(crate) fn new(pairs: &[(&str, Bytes)]) -> MSet {
+        MSet {
+            pairs: pairs
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.clone()))
+                .collect(),
+        }
+    }
+
+    /// 获取要设置的键和值
+    pub fn pairs(&self) -> &[(String, Bytes)] {
+        &self.pairs
+    }
+
+    /// 从接收到的帧中解析一个 `MSet` 实例。
+    ///
+    /// `MSET` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// 期望一个至少包含三个条目、并且条目数量为奇数的数组帧。
+    ///
+    /// ```text
+    /// MSET key value [key value ...]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<MSet> {
+        let mut pairs = vec![(parse.next_string()?, parse.next_bytes()?)];
+
+        loop {
+            match parse.next_string() {
+                Ok(key) => pairs.push((key, parse.next_bytes()?)),
+                Err(ParseError::EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(MSet { pairs })
+    }
+
+    /// 将 `MSet` 命令应用到指定的 `Db` 实例。
+    ///
+    /// 所有键通过 `Db::set_many` 在同一次加锁下写入。
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        db.set_many(
+            self.pairs
+                .into_iter()
+                .map(|(key, value)| (key, value, None)),
+        );
+
+        let response = Frame::Simple(ByteString::from_static("OK"));
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 在批处理中执行 `MSet` 命令，返回响应帧而不是写入连接。
+    pub(crate) fn execute(self, batch: &mut Batch<'_>) -> Frame {
+        for (key, value) in self.pairs {
+            batch.set(key, value, None, false);
+        }
+
+        Frame::Simple(ByteString::from_static("OK"))
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `MSet` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("mset".as_bytes()));
+
+        for (key, value) in self.pairs {
+            frame.push_bulk(Bytes::from(key.into_bytes()));
+            frame.push_bulk(value);
+        }
+
+        frame
+    }
+}
@@ -0,0 +1,122 @@
+use crate::{Command, Connection, Db, Frame, Parse};
+
+use bytestring::ByteString;
+use tracing::{debug, instrument};
+
+/// 开始一个事务。
+///
+/// 之后的命令不会立即执行，而是被排队，直到 `EXEC` 原子地执行它们，或者 `DISCARD` 放弃它们。
+/// 与 `EVALBATCH` 一样，事务中只允许不会阻塞、也不会改变连接状态的命令。
+#[derive(Debug)]
+pub struct Multi;
+
+/// 原子地执行事务中排队的所有命令。
+#[derive(Debug)]
+pub struct Exec;
+
+/// 放弃事务中排队的所有命令。
+#[derive(Debug)]
+pub struct Discard;
+
+impl Multi {
+    /// 从接收到的帧中解析一个 `Multi` 实例。
+    ///
+    /// `MULTI` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// ```text
+    /// MULTI
+    /// ```
+    pub(crate) fn parse_frames(_parse: &mut Parse) -> crate::Result<Multi> {
+        Ok(Multi)
+    }
+
+    /// 应用 `Multi` 命令。连接进入事务状态由连接处理程序负责，这里只需要写入响应。
+    #[instrument(skip(self, dst))]
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = Frame::Simple(ByteString::from_static("OK"));
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
+
+impl Exec {
+    /// 从接收到的帧中解析一个 `Exec` 实例。
+    ///
+    /// `EXEC` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// ```text
+    /// EXEC
+    /// ```
+    pub(crate) fn parse_frames(_parse: &mut Parse) -> crate::Result<Exec> {
+        Ok(Exec)
+    }
+
+    /// 在 `Db::batch` 的同一次加锁下执行 `queued` 中的命令，结果按顺序收集到一个数组帧中写入 `dst`。
+    ///
+    /// `queued` 中的每个命令与它的帧一起排队，写命令的效果在同一次加锁下追加到 AOF 和复制积压缓冲区。
+    #[instrument(skip(self, queued, db, dst))]
+    pub(crate) async fn apply(
+        self,
+        queued: Vec<(Command, Frame)>,
+        db: &Db,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        let (results, write) = db.batch_logged(|batch| {
+            queued
+                .into_iter()
+                .map(|(command, frame)| {
+                    // 排队时已经拒绝了无法在批处理中执行的命令。
+                    command
+                        .execute_propagated(batch, frame)
+                        .unwrap_or_else(|err| Frame::error(err.to_string()))
+                })
+                .collect()
+        });
+
+        let response = match write.wait().await {
+            Ok(()) => Frame::Array(results),
+            Err(msg) => Frame::error(msg),
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
+
+impl Discard {
+    /// 从接收到的帧中解析一个 `Discard` 实例。
+    ///
+    /// `DISCARD` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// ```text
+    /// DISCARD
+    /// ```
+    pub(crate) fn parse_frames(_parse: &mut Parse) -> crate::Result<Discard> {
+        Ok(Discard)
+    }
+
+    /// 应用 `Discard` 命令。排队的命令由连接处理程序丢弃，这里只需要写入响应。
+    #[instrument(skip(self, dst))]
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = Frame::Simple(ByteString::from_static("OK"));
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,91 @@
+use crate::db::Batch;
+use crate::{Connection, Db, Frame, Parse};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// 查看键的访问元数据。
+///
+/// 读取键的命令（例如 `GET`）会更新键的最后访问时间和访问频率，`OBJECT` 本身不会。
+/// 键不存在时响应 nil。
+#[derive(Debug)]
+pub enum Object {
+    /// 返回距离上次读取键经过的秒数。
+    IdleTime(String),
+
+    /// 返回键的访问频率计数器，取值范围为 0 到 255。
+    ///
+    /// 计数器从 5 开始，每次读取加一，每经过一分钟没有读取就减一。与 Redis 不同，
+    /// 不需要先将 `maxmemory-policy` 设置为 LFU 策略。
+    Freq(String),
+}
+
+impl Object {
+    /// 获取要查看的键
+    pub fn key(&self) -> &str {
+        match self {
+            Object::IdleTime(key) | Object::Freq(key) => key,
+        }
+    }
+
+    /// 从接收到的帧中解析一个 `Object` 实例。
+    ///
+    /// `OBJECT` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// ```text
+    /// OBJECT IDLETIME key
+    /// OBJECT FREQ key
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Object> {
+        match &parse.next_string()?.to_uppercase()[..] {
+            "IDLETIME" => Ok(Object::IdleTime(parse.next_string()?)),
+            "FREQ" => Ok(Object::Freq(parse.next_string()?)),
+            subcommand => Err(format!("unknown OBJECT subcommand '{}'", subcommand).into()),
+        }
+    }
+
+    /// 将 `Object` 命令应用到指定的 `Db` 实例。
+    ///
+    /// 响应写入到 `dst`。
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = db.batch(|batch| self.execute(batch));
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 在批处理中执行 `Object` 命令，返回响应帧而不是写入连接。
+    pub(crate) fn execute(self, batch: &mut Batch<'_>) -> Frame {
+        let value = match &self {
+            Object::IdleTime(key) => batch.idle_time(key).map(|idle| idle.as_secs()),
+            Object::Freq(key) => batch.frequency(key).map(u64::from),
+        };
+
+        match value {
+            Some(value) => Frame::Integer(value),
+            None => Frame::Null,
+        }
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `Object` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        let (subcommand, key) = match self {
+            Object::IdleTime(key) => ("idletime", key),
+            Object::Freq(key) => ("freq", key),
+        };
+
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("object".as_bytes()));
+        frame.push_bulk(Bytes::from(subcommand.as_bytes()));
+        frame.push_bulk(Bytes::from(key.into_bytes()));
+        frame
+    }
+}
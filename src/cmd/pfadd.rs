@@ -0,0 +1,99 @@
+use crate::cmd::{Parse, ParseError};
+use crate::db::Batch;
+use crate::{Connection, Db, Frame};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// 将元素添加到键中存储的 HyperLogLog。
+///
+/// HyperLogLog 是一种近似计数的数据结构，用固定大小的内存估计集合中不同元素的数量。
+/// 键不存在时创建一个空的 HyperLogLog。如果键被创建，或者估计的基数可能因此改变，返回 `1`，否则返回 `0`。
+#[derive(Debug)]
+pub struct PfAdd {
+    /// 要修改的键
+    key: String,
+
+    /// 要添加的元素
+    elements: Vec<Bytes>,
+}
+
+impl PfAdd {
+    /// 创建一个新的 `PfAdd` 命令，将 `elements` 添加到 `key`。
+    pub(crate) fn new(key: impl ToString, elements: &[Bytes]) -> PfAdd {
+        PfAdd {
+            key: key.to_string(),
+            elements: elements.to_vec(),
+        }
+    }
+
+    /// 获取键
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// 从接收到的帧中解析一个 `PfAdd` 实例。
+    ///
+    /// `PFADD` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// 期望一个至少包含两个条目的数组帧。
+    ///
+    /// ```text
+    /// PFADD key [element ...]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<PfAdd> {
+        use ParseError::EndOfStream;
+
+        let key = parse.next_string()?;
+        let mut elements = vec![];
+
+        loop {
+            match parse.next_bytes() {
+                Ok(element) => elements.push(element),
+                Err(EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(PfAdd { key, elements })
+    }
+
+    /// 将 `PfAdd` 命令应用到指定的 `Db` 实例。
+    ///
+    /// 响应写入到 `dst`。服务器调用此函数以执行接收到的命令。
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = db.batch(|batch| self.execute(batch));
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 在批处理中执行 `PfAdd` 命令，返回响应帧而不是写入连接。
+    pub(crate) fn execute(self, batch: &mut Batch<'_>) -> Frame {
+        match batch.pfadd(&self.key, &self.elements) {
+            Ok(changed) => Frame::Integer(changed as u64),
+            Err(msg) => Frame::error(msg),
+        }
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `PfAdd` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("pfadd".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+
+        for element in self.elements {
+            frame.push_bulk(element);
+        }
+
+        frame
+    }
+}
@@ -0,0 +1,83 @@
+use crate::cmd::del::parse_keys;
+use crate::cmd::Parse;
+use crate::db::Batch;
+use crate::{Connection, Db, Frame};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// 返回给定的键中存储的 HyperLogLog 的并集的估计基数。
+///
+/// 不存在的键视为空集合。如果某个键中存储的不是 HyperLogLog，则返回错误。
+#[derive(Debug)]
+pub struct PfCount {
+    /// 要统计的键
+    keys: Vec<String>,
+}
+
+impl PfCount {
+    /// 创建一个新的 `PfCount` 命令以统计 `keys`。
+    pub(crate) fn new(keys: &[String]) -> PfCount {
+        PfCount {
+            keys: keys.to_vec(),
+        }
+    }
+
+    /// 获取要统计的键
+    pub fn keys(&self) -> &[String] {
+        &self.keys
+    }
+
+    /// 从接收到的帧中解析一个 `PfCount` 实例。
+    ///
+    /// `PFCOUNT` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// 期望一个至少包含两个条目的数组帧。
+    ///
+    /// ```text
+    /// PFCOUNT key [key ...]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<PfCount> {
+        Ok(PfCount {
+            keys: parse_keys(parse)?,
+        })
+    }
+
+    /// 将 `PfCount` 命令应用到指定的 `Db` 实例。
+    ///
+    /// 响应写入到 `dst`。服务器调用此函数以执行接收到的命令。
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = db.batch(|batch| self.execute(batch));
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 在批处理中执行 `PfCount` 命令，返回响应帧而不是写入连接。
+    pub(crate) fn execute(self, batch: &mut Batch<'_>) -> Frame {
+        match batch.pfcount(&self.keys) {
+            Ok(count) => Frame::Integer(count),
+            Err(msg) => Frame::error(msg),
+        }
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `PfCount` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("pfcount".as_bytes()));
+
+        for key in self.keys {
+            frame.push_bulk(Bytes::from(key.into_bytes()));
+        }
+
+        frame
+    }
+}
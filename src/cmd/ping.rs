@@ -1,5 +1,6 @@
 use crate::{Connection, Frame, Parse, ParseError};
 use bytes::Bytes;
+use bytestring::ByteString;
 use tracing::{debug, instrument};
 
 /// 如果没有提供参数，则返回 PONG，否则返回参数的一个副本作为批量回应。
@@ -48,10 +49,7 @@ impl Ping {
     /// 响应写入到 `dst`。服务器调用此函数以执行接收到的命令。
     #[instrument(skip(self, dst))]
     pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
-        let response = match self.msg {
-            None => Frame::Simple("PONG".to_string()),
-            Some(msg) => Frame::Bulk(msg),
-        };
+        let response = self.execute();
 
         debug!(?response);
 
@@ -61,6 +59,31 @@ impl Ping {
         Ok(())
     }
 
+    /// 在订阅状态下应用 `Ping` 命令。
+    ///
+    /// 与 Redis 一样，订阅状态下的响应是一个数组帧 `["pong", message]`，
+    /// 没有提供消息时 `message` 为空字符串。
+    #[instrument(skip(self, dst))]
+    pub(crate) fn apply_subscribed(self, dst: &mut Connection) -> crate::Result<()> {
+        let mut response = Frame::array();
+        response.push_bulk(Bytes::from_static(b"pong"));
+        response.push_bulk(self.msg.unwrap_or_default());
+
+        debug!(?response);
+
+        dst.queue_frame(&response)?;
+
+        Ok(())
+    }
+
+    /// 在 `EVALBATCH` 的批处理中执行 `Ping` 命令，返回响应帧而不是写入连接。
+    pub(crate) fn execute(self) -> Frame {
+        match self.msg {
+            None => Frame::Simple(ByteString::from_static("PONG")),
+            Some(msg) => Frame::Bulk(msg),
+        }
+    }
+
     /// 将命令转换为等效的 `Frame`。
     ///
     /// 客户端在编码一个 `Ping` 命令以发送到服务器时调用此函数。
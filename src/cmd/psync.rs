@@ -0,0 +1,202 @@
+use crate::db::{BacklogRead, PsyncStart};
+use crate::{Connection, Db, Frame, Parse, Shutdown};
+
+use bytes::Bytes;
+use bytestring::ByteString;
+use std::convert::TryFrom;
+use tracing::{debug, info, instrument};
+
+/// 副本请求从主节点接收写命令。
+///
+/// `replid` 和 `offset` 是副本上一次处理到的位置。主节点的复制 ID 相同并且该偏移量仍在积压缓冲区中时，
+/// 主节点以 `+CONTINUE replid` 响应，只发送缺少的写命令；否则以 `+FULLRESYNC replid offset` 响应，
+/// 接着发送快照格式的完整数据，再从 `offset` 开始发送写命令。第一次同步时副本发送 `PSYNC ? -1`。
+///
+/// 之后主节点在连接上发送写命令的效果，副本以 `REPLCONF ACK offset` 确认处理到的偏移量，直到任意一方关闭连接。
+/// 该命令由连接处理程序直接调用。
+#[derive(Debug)]
+pub struct PSync {
+    /// 副本上一次处理到的位置。`None` 表示请求完整同步。
+    position: Option<(String, u64)>,
+}
+
+impl PSync {
+    /// 创建一个新的 `PSync` 命令，`position` 为 `None` 时请求完整同步。
+    pub(crate) fn new(position: Option<(String, u64)>) -> PSync {
+        PSync { position }
+    }
+
+    /// 从接收到的帧中解析一个 `PSync` 实例。
+    ///
+    /// `PSYNC` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// ```text
+    /// PSYNC replid offset
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<PSync> {
+        let replid = parse.next_string()?;
+        let offset = parse.next_signed_int()?;
+
+        // `? -1` 以及任何负的偏移量都表示请求完整同步。
+        let position = match u64::try_from(offset) {
+            Ok(offset) if replid != "?" => Some((replid, offset)),
+            _ => None,
+        };
+
+        Ok(PSync::new(position))
+    }
+
+    /// 应用 `PSync` 命令，之后持续向副本发送写命令。
+    ///
+    /// 副本关闭连接或者服务器关闭时返回 `Ok`。副本处理得太慢、需要的数据已经从积压缓冲区中被丢弃时返回 `Err`，
+    /// 连接被关闭，副本重新连接之后进行完整同步。
+    #[instrument(skip(self, db, dst, shutdown))]
+    pub(crate) async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let position = self.position.as_ref();
+        let start = db.psync(
+            position.map(|(replid, _)| &replid[..]),
+            position.map(|(_, offset)| *offset),
+        );
+
+        let mut offset = match start {
+            PsyncStart::Continue { replid, offset } => {
+                info!(offset, "replica continues from the replication backlog");
+
+                let response = Frame::Simple(ByteString::from(format!("CONTINUE {}", replid)));
+                debug!(?response);
+                dst.write_frame(&response).await?;
+
+                offset
+            }
+            PsyncStart::Full {
+                replid,
+                offset,
+                snapshot,
+            } => {
+                info!(offset, bytes = snapshot.len(), "full resynchronization");
+
+                let response = Frame::Simple(ByteString::from(format!(
+                    "FULLRESYNC {} {}",
+                    replid, offset
+                )));
+                debug!(?response);
+                dst.write_frame(&response).await?;
+                dst.write_frame(&Frame::Bulk(Bytes::from(snapshot))).await?;
+
+                offset
+            }
+        };
+
+        db.replica_connected(true);
+        let res = stream(db, dst, shutdown, &mut offset).await;
+        db.replica_connected(false);
+
+        res
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 副本在连接到主节点时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("psync".as_bytes()));
+
+        match self.position {
+            Some((replid, offset)) => {
+                frame.push_bulk(Bytes::from(replid.into_bytes()));
+                frame.push_bulk(Bytes::from(offset.to_string().into_bytes()));
+            }
+            None => {
+                frame.push_bulk(Bytes::from("?".as_bytes()));
+                frame.push_bulk(Bytes::from("-1".as_bytes()));
+            }
+        }
+
+        frame
+    }
+}
+
+/// 返回副本确认处理到复制偏移量 `offset` 的帧：`REPLCONF ACK offset`。
+pub(crate) fn replconf_ack(offset: u64) -> Frame {
+    let mut frame = Frame::array();
+    frame.push_bulk(Bytes::from("replconf".as_bytes()));
+    frame.push_bulk(Bytes::from("ack".as_bytes()));
+    frame.push_bulk(Bytes::from(offset.to_string().into_bytes()));
+    frame
+}
+
+/// 解析副本发送的 `REPLCONF ACK offset`，返回确认的偏移量。副本不发送其他命令，收到其他帧时返回 `Err`。
+fn parse_replconf_ack(frame: Frame) -> crate::Result<u64> {
+    let mut parse = Parse::new(frame)?;
+
+    if !parse.next_string()?.eq_ignore_ascii_case("replconf")
+        || !parse.next_string()?.eq_ignore_ascii_case("ack")
+    {
+        return Err("protocol error; replica may only send REPLCONF ACK".into());
+    }
+
+    let offset = parse.next_int()?;
+    parse.finish()?;
+
+    Ok(offset)
+}
+
+/// 记录副本在连接上已经发送的所有确认，不等待。
+fn read_acks(db: &Db, dst: &mut Connection) -> crate::Result<()> {
+    while let Some(frame) = dst.try_read_frame()? {
+        db.record_replica_ack(parse_replconf_ack(frame)?);
+    }
+
+    Ok(())
+}
+
+/// 从 `offset` 开始，将积压缓冲区中的写命令原样发送给副本，并记录副本确认的偏移量。
+async fn stream(
+    db: &Db,
+    dst: &mut Connection,
+    shutdown: &mut Shutdown,
+    offset: &mut u64,
+) -> crate::Result<()> {
+    loop {
+        // 在读取积压缓冲区之前开始等待，不会错过读取之后追加的写命令。
+        let appended = db.backlog_appended();
+        tokio::pin!(appended);
+        appended.as_mut().enable();
+
+        match db.read_backlog(*offset) {
+            BacklogRead::Data(data, next) if !data.is_empty() => {
+                dst.write_raw(&data).await?;
+                *offset = next;
+
+                // 持续有写命令时不会进入下面的等待，在这里读取副本的确认。
+                read_acks(db, dst)?;
+                continue;
+            }
+            BacklogRead::Data(..) => {}
+            BacklogRead::Unavailable => {
+                return Err(format!(
+                    "replica fell behind, offset {} is no longer in the replication backlog",
+                    offset
+                )
+                .into());
+            }
+        }
+
+        tokio::select! {
+            _ = &mut appended => {}
+            // 副本只发送确认，读取同时用于发现副本关闭了连接。
+            res = dst.read_frame() => match res? {
+                Some(frame) => db.record_replica_ack(parse_replconf_ack(frame)?),
+                None => return Ok(()),
+            },
+            _ = shutdown.recv() => return Ok(()),
+        }
+    }
+}
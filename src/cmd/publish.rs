@@ -1,4 +1,5 @@
-use crate::{Connection, Db, Frame, Parse};
+use crate::db::Batch;
+use crate::{Connection, Db, Frame, Parse, ParseError};
 
 use bytes::Bytes;
 
@@ -8,13 +9,19 @@ use bytes::Bytes;
 /// 消费者可以订阅频道以接收消息。
 ///
 /// 频道名称与键值命名空间没有关系。在名为 "foo" 的频道上发布与设置 "foo" 键没有关系。
+///
+/// `MPUBLISH` 在一个命令中向同一个频道按顺序发布多条消息。消息在一次加锁中全部发送，订阅者收到的消息与逐条
+/// `PUBLISH` 相同，但发布者每批消息只需要一次请求和一次响应。
 #[derive(Debug)]
 pub struct Publish {
     /// 消息应发布到的频道名称。
     channel: String,
 
-    /// 要发布的消息。
-    message: Bytes,
+    /// 要发布的消息，按顺序发送。`PUBLISH` 只有一条消息。
+    messages: Vec<Bytes>,
+
+    /// 命令名称：`publish` 或者 `mpublish`
+    name: &'static str,
 }
 
 impl Publish {
@@ -22,10 +29,25 @@ impl Publish {
     pub(crate) fn new(channel: impl ToString, message: Bytes) -> Publish {
         Publish {
             channel: channel.to_string(),
-            message,
+            messages: vec![message],
+            name: "publish",
+        }
+    }
+
+    /// 创建一个新的 `MPUBLISH` 命令，在 `channel` 上按顺序发送 `messages`。
+    pub(crate) fn new_multi(channel: impl ToString, messages: Vec<Bytes>) -> Publish {
+        Publish {
+            channel: channel.to_string(),
+            messages,
+            name: "mpublish",
         }
     }
 
+    /// 返回命令名称，`MPUBLISH` 保留自己的名称，以便 `COMMAND` 使用正确的条目。
+    pub(crate) fn get_name(&self) -> &'static str {
+        self.name
+    }
+
     /// 从接收到的帧中解析一个 `Publish` 实例。
     ///
     /// `Parse` 参数提供了一个类似光标的 API，用于从 `Frame` 中读取字段。
@@ -53,7 +75,31 @@ impl Publish {
         // `message` 是任意字节。
         let message = parse.next_bytes()?;
 
-        Ok(Publish { channel, message })
+        Ok(Publish::new(channel, message))
+    }
+
+    /// 从接收到的帧中解析一个 `MPUBLISH` 命令。
+    ///
+    /// `MPUBLISH` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// ```text
+    /// MPUBLISH channel message [message ...]
+    /// ```
+    pub(crate) fn parse_mpublish_frames(parse: &mut Parse) -> crate::Result<Publish> {
+        let channel = parse.next_string()?;
+        let mut messages = vec![parse.next_bytes()?];
+
+        loop {
+            match parse.next_bytes() {
+                Ok(message) => messages.push(message),
+                Err(ParseError::EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(Publish::new_multi(channel, messages))
     }
 
     /// 将 `Publish` 命令应用到指定的 `Db` 实例。
@@ -66,7 +112,7 @@ impl Publish {
         // 返回当前在频道上收听的订阅者数量。
         // 这并不意味着有 `num_subscriber` 个频道将接收到该消息。
         // 订阅者可能在接收到消息之前掉线。因此，`num_subscribers` 应仅用作“提示”。
-        let num_subscribers = db.publish(&self.channel, self.message);
+        let num_subscribers = db.publish_all(&self.channel, self.messages);
 
         // 订阅者数量作为发布请求的响应返回。
         let response = Frame::Integer(num_subscribers as u64);
@@ -76,14 +122,24 @@ impl Publish {
 
         Ok(())
     }
+
+    /// 在 `EVALBATCH` 的批处理中执行 `Publish` 命令，返回响应帧而不是写入连接。
+    pub(crate) fn execute(self, batch: &mut Batch<'_>) -> Frame {
+        let num_subscribers = batch.publish_all(&self.channel, self.messages);
+        Frame::Integer(num_subscribers as u64)
+    }
+
     /// 将命令转换为等效的 `Frame`。
     ///
     /// 客户端在编码一个 `Publish` 命令以发送到服务器时调用此函数。
     pub(crate) fn into_frame(self) -> Frame {
         let mut frame = Frame::array();
-        frame.push_bulk(Bytes::from("publish".as_bytes()));
+        frame.push_bulk(Bytes::from(self.name.as_bytes()));
         frame.push_bulk(Bytes::from(self.channel.into_bytes()));
-        frame.push_bulk(self.message);
+
+        for message in self.messages {
+            frame.push_bulk(message);
+        }
 
         frame
     }
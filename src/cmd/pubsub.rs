@@ -0,0 +1,115 @@
+use crate::acl::glob_match;
+use crate::{Connection, Db, Frame, Parse, ParseError};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// 查看发布/订阅系统的状态。
+///
+/// 所有订阅者都离开的频道会被移除，因此只报告至少有一个订阅者的频道。
+#[derive(Debug)]
+pub enum PubSubCommand {
+    /// 返回有订阅者的频道。指定模式时只返回与 glob 风格的模式匹配的频道。
+    Channels(Option<String>),
+
+    /// 返回每个指定频道的订阅者数量。
+    NumSub(Vec<String>),
+}
+
+impl PubSubCommand {
+    /// 从接收到的帧中解析一个 `PubSubCommand` 实例。
+    ///
+    /// `PUBSUB` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// ```text
+    /// PUBSUB CHANNELS [pattern]
+    /// PUBSUB NUMSUB [channel [channel ...]]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<PubSubCommand> {
+        match &parse.next_string()?.to_uppercase()[..] {
+            "CHANNELS" => match parse.next_string() {
+                Ok(pattern) => Ok(PubSubCommand::Channels(Some(pattern))),
+                Err(ParseError::EndOfStream) => Ok(PubSubCommand::Channels(None)),
+                Err(err) => Err(err.into()),
+            },
+            "NUMSUB" => {
+                let mut channels = vec![];
+
+                loop {
+                    match parse.next_string() {
+                        Ok(channel) => channels.push(channel),
+                        Err(ParseError::EndOfStream) => break,
+                        Err(err) => return Err(err.into()),
+                    }
+                }
+
+                Ok(PubSubCommand::NumSub(channels))
+            }
+            subcommand => Err(format!("unknown PUBSUB subcommand '{}'", subcommand).into()),
+        }
+    }
+
+    /// 将 `PubSubCommand` 命令应用到指定的 `Db` 实例。
+    ///
+    /// 响应写入到 `dst`。
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let mut response = Frame::array();
+
+        match self {
+            PubSubCommand::Channels(pattern) => {
+                for channel in db.channels() {
+                    let matched = pattern
+                        .as_ref()
+                        .is_none_or(|pattern| glob_match(pattern.as_bytes(), channel.as_bytes()));
+
+                    if matched {
+                        response.push_bulk(Bytes::from(channel));
+                    }
+                }
+            }
+            PubSubCommand::NumSub(channels) => {
+                for channel in channels {
+                    let count = db.subscriber_count(&channel);
+                    response.push_bulk(Bytes::from(channel));
+                    response.push_int(count as u64);
+                }
+            }
+        }
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `PubSubCommand` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("pubsub".as_bytes()));
+
+        match self {
+            PubSubCommand::Channels(pattern) => {
+                frame.push_bulk(Bytes::from("channels".as_bytes()));
+
+                if let Some(pattern) = pattern {
+                    frame.push_bulk(Bytes::from(pattern.into_bytes()));
+                }
+            }
+            PubSubCommand::NumSub(channels) => {
+                frame.push_bulk(Bytes::from("numsub".as_bytes()));
+
+                for channel in channels {
+                    frame.push_bulk(Bytes::from(channel.into_bytes()));
+                }
+            }
+        }
+
+        frame
+    }
+}
@@ -0,0 +1,182 @@
+use crate::cmd::{Parse, ParseError};
+use crate::db::Batch;
+use crate::{Connection, Db, Frame};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// 将一个或多个元素依次推入键中存储的列表的头部，返回推入之后列表的长度。
+///
+/// 键不存在时创建一个新的列表。由于元素是依次推入的，`LPUSH key a b c` 之后列表为 `c b a`。
+/// 推入元素会唤醒正在 `BLPOP` 中等待的客户端。
+#[derive(Debug)]
+pub struct LPush {
+    /// 列表的键
+    key: String,
+
+    /// 要推入的元素
+    values: Vec<Bytes>,
+}
+
+/// 将一个或多个元素依次推入键中存储的列表的尾部，返回推入之后列表的长度。
+///
+/// 与 `LPOP` 或 `BLPOP` 配合使用时，列表是一个先进先出的队列。
+#[derive(Debug)]
+pub struct RPush {
+    /// 列表的键
+    key: String,
+
+    /// 要推入的元素
+    values: Vec<Bytes>,
+}
+
+impl LPush {
+    /// 创建一个新的 `LPush` 命令，将 `values` 推入 `key`。
+    pub(crate) fn new(key: impl ToString, values: &[Bytes]) -> LPush {
+        LPush {
+            key: key.to_string(),
+            values: values.to_vec(),
+        }
+    }
+
+    /// 获取键
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// 从接收到的帧中解析一个 `LPush` 实例。
+    ///
+    /// `LPUSH` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// 期望一个至少包含三个条目的数组帧。
+    ///
+    /// ```text
+    /// LPUSH key element [element ...]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<LPush> {
+        let (key, values) = parse_push(parse)?;
+        Ok(LPush { key, values })
+    }
+
+    /// 将 `LPush` 命令应用到指定的 `Db` 实例。
+    ///
+    /// 响应写入到 `dst`。服务器调用此函数以执行接收到的命令。
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = db.batch(|batch| self.execute(batch));
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 在批处理中执行 `LPush` 命令，返回响应帧而不是写入连接。
+    pub(crate) fn execute(self, batch: &mut Batch<'_>) -> Frame {
+        match batch.lpush(&self.key, self.values) {
+            Ok(len) => Frame::Integer(len),
+            Err(msg) => Frame::error(msg),
+        }
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `LPush` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        push_frame("lpush", self.key, self.values)
+    }
+}
+
+impl RPush {
+    /// 创建一个新的 `RPush` 命令，将 `values` 推入 `key`。
+    pub(crate) fn new(key: impl ToString, values: &[Bytes]) -> RPush {
+        RPush {
+            key: key.to_string(),
+            values: values.to_vec(),
+        }
+    }
+
+    /// 获取键
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// 从接收到的帧中解析一个 `RPush` 实例。
+    ///
+    /// `RPUSH` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// 期望一个至少包含三个条目的数组帧。
+    ///
+    /// ```text
+    /// RPUSH key element [element ...]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<RPush> {
+        let (key, values) = parse_push(parse)?;
+        Ok(RPush { key, values })
+    }
+
+    /// 将 `RPush` 命令应用到指定的 `Db` 实例。
+    ///
+    /// 响应写入到 `dst`。服务器调用此函数以执行接收到的命令。
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = db.batch(|batch| self.execute(batch));
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 在批处理中执行 `RPush` 命令，返回响应帧而不是写入连接。
+    pub(crate) fn execute(self, batch: &mut Batch<'_>) -> Frame {
+        match batch.rpush(&self.key, self.values) {
+            Ok(len) => Frame::Integer(len),
+            Err(msg) => Frame::error(msg),
+        }
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `RPush` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        push_frame("rpush", self.key, self.values)
+    }
+}
+
+/// 解析 `LPUSH`、`RPUSH` 和 `SADD` 共同的参数：一个键和至少一个元素。
+pub(crate) fn parse_push(parse: &mut Parse) -> crate::Result<(String, Vec<Bytes>)> {
+    use ParseError::EndOfStream;
+
+    let key = parse.next_string()?;
+    let mut values = vec![parse.next_bytes()?];
+
+    loop {
+        match parse.next_bytes() {
+            Ok(value) => values.push(value),
+            Err(EndOfStream) => break,
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    Ok((key, values))
+}
+
+/// 编码 `LPUSH`、`RPUSH` 或 `SADD` 命令。
+pub(crate) fn push_frame(name: &'static str, key: String, values: Vec<Bytes>) -> Frame {
+    let mut frame = Frame::array();
+    frame.push_bulk(Bytes::from(name.as_bytes()));
+    frame.push_bulk(Bytes::from(key.into_bytes()));
+
+    for value in values {
+        frame.push_bulk(value);
+    }
+
+    frame
+}
@@ -0,0 +1,55 @@
+use crate::{Connection, Frame, Parse};
+use bytes::Bytes;
+use bytestring::ByteString;
+
+use tracing::{debug, instrument};
+
+/// 请求服务器关闭连接。
+///
+/// 服务器以 `OK` 响应，确保响应以及之前积压的数据都已经写入之后关闭连接。
+/// 与 `RESET` 一样，在任何状态下都可以执行，也不需要认证。
+#[derive(Debug)]
+pub struct Quit;
+
+impl Quit {
+    /// 创建一个新的 `Quit` 命令。
+    pub(crate) fn new() -> Quit {
+        Quit
+    }
+
+    /// 从接收到的帧中解析一个 `Quit` 实例。
+    ///
+    /// `QUIT` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// ```text
+    /// QUIT
+    /// ```
+    pub(crate) fn parse_frames(_parse: &mut Parse) -> crate::Result<Quit> {
+        Ok(Quit)
+    }
+
+    /// 应用 `Quit` 命令。
+    ///
+    /// 这里只写入响应，连接处理程序在此之后退出循环并关闭连接。
+    #[instrument(skip(self, dst))]
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = Frame::Simple(ByteString::from_static("OK"));
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `Quit` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("quit".as_bytes()));
+        frame
+    }
+}
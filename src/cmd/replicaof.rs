@@ -0,0 +1,85 @@
+use crate::replication::Link;
+use crate::{Connection, Db, Frame, Parse};
+
+use bytes::Bytes;
+use bytestring::ByteString;
+use tracing::{debug, instrument};
+
+/// 使服务器成为另一个服务器的副本，或者停止复制、重新成为主节点。
+///
+/// 成为副本之后，服务器在后台连接到主节点，以主节点的数据替换自己的数据，之后执行主节点发送的写命令。
+/// 副本拒绝客户端发送的写命令。`REPLICAOF NO ONE` 停止复制，已经复制的数据被保留。
+#[derive(Debug)]
+pub struct ReplicaOf {
+    /// 主节点的主机名和端口。`None` 表示停止复制。
+    primary: Option<(String, u16)>,
+}
+
+impl ReplicaOf {
+    /// 创建一个新的 `ReplicaOf` 命令，`primary` 为 `None` 时停止复制。
+    pub fn new(primary: Option<(String, u16)>) -> ReplicaOf {
+        ReplicaOf { primary }
+    }
+
+    /// 从接收到的帧中解析一个 `ReplicaOf` 实例。
+    ///
+    /// `REPLICAOF` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// ```text
+    /// REPLICAOF host port
+    /// REPLICAOF NO ONE
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<ReplicaOf> {
+        let host = parse.next_string()?;
+        let port = parse.next_string()?;
+
+        if host.eq_ignore_ascii_case("no") && port.eq_ignore_ascii_case("one") {
+            return Ok(ReplicaOf::new(None));
+        }
+
+        match port.parse::<u16>() {
+            Ok(port) => Ok(ReplicaOf::new(Some((host, port)))),
+            Err(_) => Err("Invalid master port".into()),
+        }
+    }
+
+    /// 应用 `ReplicaOf` 命令。
+    ///
+    /// 复制任务属于服务器而不是单个连接，因此该命令由连接处理程序直接调用。
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let link = self
+            .primary
+            .map(|(host, port)| Link::start(db.clone(), host, port));
+        db.set_replica_link(link);
+
+        let response = Frame::Simple(ByteString::from_static("OK"));
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `ReplicaOf` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("replicaof".as_bytes()));
+
+        match self.primary {
+            Some((host, port)) => {
+                frame.push_bulk(Bytes::from(host.into_bytes()));
+                frame.push_bulk(Bytes::from(port.to_string().into_bytes()));
+            }
+            None => {
+                frame.push_bulk(Bytes::from("no".as_bytes()));
+                frame.push_bulk(Bytes::from("one".as_bytes()));
+            }
+        }
+
+        frame
+    }
+}
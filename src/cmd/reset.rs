@@ -0,0 +1,40 @@
+use crate::{Connection, Frame, Parse};
+use bytestring::ByteString;
+
+use tracing::{debug, instrument};
+
+/// 将连接重置为初始状态。
+///
+/// 放弃 `MULTI` 开始的事务，取消所有订阅，并将连接的用户恢复为 `default` 用户。
+/// 在任何状态下都可以执行，响应总是 `RESET`。
+#[derive(Debug)]
+pub struct Reset;
+
+impl Reset {
+    /// 从接收到的帧中解析一个 `Reset` 实例。
+    ///
+    /// `RESET` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// ```text
+    /// RESET
+    /// ```
+    pub(crate) fn parse_frames(_parse: &mut Parse) -> crate::Result<Reset> {
+        Ok(Reset)
+    }
+
+    /// 应用 `Reset` 命令。
+    ///
+    /// 连接的状态由连接处理程序负责清理，这里只需要写入响应。
+    #[instrument(skip(self, dst))]
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = Frame::Simple(ByteString::from_static("RESET"));
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,166 @@
+use crate::db::Batch;
+use crate::{Connection, Db, Frame, Parse, ParseError};
+
+use bytes::Bytes;
+use bytestring::ByteString;
+use std::time::Duration;
+use tracing::{debug, instrument};
+
+/// 以 `DUMP` 生成的数据创建键。
+///
+/// `ttl` 是以毫秒为单位的生存时间，`0` 表示键不会过期；指定了 `ABSTTL` 时 `ttl` 是键过期的 Unix 时间戳（毫秒）。
+/// 键已经存在时返回 `BUSYKEY` 错误，指定了 `REPLACE` 时替换原有的键。数据的格式版本不受支持或者校验和不符时返回错误，不修改数据库。
+#[derive(Debug)]
+pub struct Restore {
+    /// 要创建的键
+    key: String,
+
+    /// 键的生存时间，`None` 表示不会过期
+    ttl: Option<Duration>,
+
+    /// 键过期的 Unix 时间戳（毫秒），由 `ABSTTL` 指定。此时忽略 `ttl`
+    expire_at: Option<u64>,
+
+    /// `DUMP` 生成的数据
+    data: Bytes,
+
+    /// 是否替换已经存在的键
+    replace: bool,
+}
+
+impl Restore {
+    /// 创建一个新的 `Restore` 命令，以 `data` 创建 `key`，`ttl` 之后过期。
+    pub(crate) fn new(
+        key: impl ToString,
+        ttl: Option<Duration>,
+        data: Bytes,
+        replace: bool,
+    ) -> Restore {
+        Restore {
+            key: key.to_string(),
+            ttl,
+            expire_at: None,
+            data,
+            replace,
+        }
+    }
+
+    /// 获取键
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// 从接收到的帧中解析一个 `Restore` 实例。
+    ///
+    /// `RESTORE` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// ```text
+    /// RESTORE key ttl serialized-value [REPLACE] [ABSTTL]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Restore> {
+        let key = parse.next_string()?;
+        let ttl = parse.next_int()?;
+        let data = parse.next_bytes()?;
+
+        let mut replace = false;
+        let mut abs_ttl = false;
+
+        loop {
+            match parse.next_string() {
+                Ok(s) if s.to_uppercase() == "REPLACE" => replace = true,
+                Ok(s) if s.to_uppercase() == "ABSTTL" => abs_ttl = true,
+                Ok(_) => return Err("ERR syntax error".into()),
+                Err(ParseError::EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        let (ttl, expire_at) = match ttl {
+            0 => (None, None),
+            unix_ms if abs_ttl => (None, Some(unix_ms)),
+            ms => (Some(Duration::from_millis(ms)), None),
+        };
+
+        Ok(Restore {
+            key,
+            ttl,
+            expire_at,
+            data,
+            replace,
+        })
+    }
+
+    /// 将 `Restore` 命令应用到指定的 `Db` 实例。
+    ///
+    /// 响应写入到 `dst`。服务器调用此函数以执行接收到的命令。
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = db.batch(|batch| self.execute(batch));
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 在批处理中执行 `Restore` 命令，返回响应帧而不是写入连接。
+    pub(crate) fn execute(self, batch: &mut Batch<'_>) -> Frame {
+        let ttl = match self.expire_at {
+            Some(unix_ms) => Some(batch.duration_until(unix_ms)),
+            None => self.ttl,
+        };
+
+        match batch.restore(self.key, ttl, &self.data, self.replace) {
+            Ok(()) => Frame::Simple(ByteString::from_static("OK")),
+            Err(msg) => Frame::error(msg),
+        }
+    }
+
+    /// 与 `execute` 相同，并记录 `RESTORE key unix-time-milliseconds serialized-value ABSTTL` 作为命令的效果，
+    /// 加载 AOF 和副本执行时键在与这里相同的时刻过期。
+    pub(crate) fn execute_propagated(self, batch: &mut Batch<'_>) -> Frame {
+        let key = self.key.clone();
+        let data = self.data.clone();
+        let replace = self.replace;
+        let response = self.execute(batch);
+
+        if !matches!(response, Frame::Error(_)) {
+            let mut frame = Frame::array();
+            frame.push_bulk(Bytes::from_static(b"restore"));
+            frame.push_bulk(Bytes::from(key.clone().into_bytes()));
+            frame.push_int(batch.expire_time(&key).unwrap_or(0));
+            frame.push_bulk(data);
+            frame.push_bulk(Bytes::from_static(b"absttl"));
+            if replace {
+                frame.push_bulk(Bytes::from_static(b"replace"));
+            }
+            batch.propagate(frame);
+        }
+
+        response
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `Restore` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("restore".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        match self.expire_at {
+            Some(unix_ms) => frame.push_int(unix_ms),
+            None => frame.push_int(self.ttl.map_or(0, |ttl| ttl.as_millis().max(1) as u64)),
+        }
+        frame.push_bulk(self.data);
+        if self.replace {
+            frame.push_bulk(Bytes::from("replace".as_bytes()));
+        }
+        if self.expire_at.is_some() {
+            frame.push_bulk(Bytes::from("absttl".as_bytes()));
+        }
+        frame
+    }
+}
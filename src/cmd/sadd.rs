@@ -0,0 +1,79 @@
+use crate::cmd::push::{parse_push, push_frame};
+use crate::cmd::Parse;
+use crate::db::Batch;
+use crate::{Connection, Db, Frame};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// 将一个或多个成员添加到键中存储的集合，返回新添加的成员数量。
+///
+/// 键不存在时创建一个新的集合。已经存在的成员被忽略，不计算在返回值内。
+#[derive(Debug)]
+pub struct SAdd {
+    /// 集合的键
+    key: String,
+
+    /// 要添加的成员
+    members: Vec<Bytes>,
+}
+
+impl SAdd {
+    /// 创建一个新的 `SAdd` 命令，将 `members` 添加到 `key`。
+    pub(crate) fn new(key: impl ToString, members: &[Bytes]) -> SAdd {
+        SAdd {
+            key: key.to_string(),
+            members: members.to_vec(),
+        }
+    }
+
+    /// 获取键
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// 从接收到的帧中解析一个 `SAdd` 实例。
+    ///
+    /// `SADD` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// 期望一个至少包含三个条目的数组帧。
+    ///
+    /// ```text
+    /// SADD key member [member ...]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<SAdd> {
+        let (key, members) = parse_push(parse)?;
+        Ok(SAdd { key, members })
+    }
+
+    /// 将 `SAdd` 命令应用到指定的 `Db` 实例。
+    ///
+    /// 响应写入到 `dst`。服务器调用此函数以执行接收到的命令。
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = db.batch(|batch| self.execute(batch));
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 在批处理中执行 `SAdd` 命令，返回响应帧而不是写入连接。
+    pub(crate) fn execute(self, batch: &mut Batch<'_>) -> Frame {
+        match batch.sadd(&self.key, self.members) {
+            Ok(added) => Frame::Integer(added),
+            Err(msg) => Frame::error(msg),
+        }
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `SAdd` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        push_frame("sadd", self.key, self.members)
+    }
+}
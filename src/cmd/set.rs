@@ -1,21 +1,28 @@
 use crate::cmd::{Parse, ParseError};
+use crate::db::Batch;
 use crate::{Connection, Db, Frame};
 
 use bytes::Bytes;
+use bytestring::ByteString;
 use std::time::Duration;
 use tracing::{debug, instrument};
 
 /// 设置 `key` 以保存字符串 `value`。
 ///
 /// 如果 `key` 已经持有一个值，则不管其类型如何，它都会被覆盖。
-/// 任何与该键关联的先前生存时间在成功的 SET 操作时都会被丢弃。
+/// 除非指定了 KEEPTTL，任何与该键关联的先前生存时间在成功的 SET 操作时都会被丢弃。
 ///
 /// # 选项
 ///
-/// 当前支持以下选项：
+/// 当前支持以下选项（最多指定一个）：
 ///
 /// * EX `seconds` -- 设置过期时间，以秒为单位。
 /// * PX `milliseconds` -- 设置过期时间，以毫秒为单位。
+/// * PXAT `unix-time-milliseconds` -- 在指定的 Unix 时间戳（毫秒）过期。AOF 和复制以此记录 `SET` 的过期时间。
+/// * KEEPTTL -- 保留键原有的过期时间。
+///
+/// 兼容旧版本的 `SETEX key seconds value` 和 `PSETEX key milliseconds value` 也被解析为 `Set`，
+/// 分别等同于 `SET key value EX seconds` 和 `SET key value PX milliseconds`。
 #[derive(Debug)]
 pub struct Set {
     /// 查找键
@@ -26,6 +33,15 @@ pub struct Set {
 
     /// 键何时过期
     expire: Option<Duration>,
+
+    /// 键过期的 Unix 时间戳（毫秒），由 `PXAT` 指定
+    expire_at: Option<u64>,
+
+    /// 是否保留键原有的过期时间
+    keep_ttl: bool,
+
+    /// 命令名称：`set`，或者兼容命令 `setex`、`psetex`
+    name: &'static str,
 }
 
 impl Set {
@@ -37,9 +53,19 @@ impl Set {
             key: key.to_string(),
             value,
             expire,
+            expire_at: None,
+            keep_ttl: false,
+            name: "set",
         }
     }
 
+    /// 保留键原有的过期时间，而不是将其丢弃。同时清除 `expire`。
+    pub fn with_keep_ttl(mut self) -> Set {
+        self.expire = None;
+        self.keep_ttl = true;
+        self
+    }
+
     /// 获取键
     pub fn key(&self) -> &str {
         &self.key
@@ -55,6 +81,16 @@ impl Set {
         self.expire
     }
 
+    /// 是否保留键原有的过期时间
+    pub fn keep_ttl(&self) -> bool {
+        self.keep_ttl
+    }
+
+    /// 返回命令名称，`SETEX` 和 `PSETEX` 保留各自的名称，以便权限检查和 `COMMAND` 使用正确的条目。
+    pub(crate) fn get_name(&self) -> &'static str {
+        self.name
+    }
+
     /// 从接收到的帧中解析一个 `Set` 实例。
     ///
     /// `Parse` 参数提供了一个类似光标的 API，用于从 `Frame` 中读取字段。
@@ -71,7 +107,7 @@ impl Set {
     /// 期望一个至少包含三个条目的数组帧。
     ///
     /// ```text
-    /// SET key value [EX seconds|PX milliseconds]
+    /// SET key value [EX seconds|PX milliseconds|PXAT unix-time-milliseconds|KEEPTTL]
     /// ```
     pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Set> {
         use ParseError::EndOfStream;
@@ -84,6 +120,8 @@ impl Set {
 
         // 过期时间是可选的。如果没有其他内容跟随，则为 `None`。
         let mut expire = None;
+        let mut expire_at = None;
+        let mut keep_ttl = false;
 
         // 尝试解析另一个字符串。
         match parse.next_string() {
@@ -97,17 +135,72 @@ impl Set {
                 let ms = parse.next_int()?;
                 expire = Some(Duration::from_millis(ms));
             }
-            // 目前，mini-redis 不支持任何其他的 SET 选项。此处的错误将导致连接被终止。
-            // 其他连接将继续正常运行。
-            Ok(_) => return Err("目前 `SET` 仅支持过期选项".into()),
+            Ok(s) if s.to_uppercase() == "PXAT" => {
+                // 以 Unix 时间戳（毫秒）指定的过期时刻。与 Redis 一样，时间戳必须是正整数。
+                let unix_ms = parse.next_int()?;
+                if unix_ms == 0 {
+                    return Err("invalid expire time in 'set' command".into());
+                }
+                expire_at = Some(unix_ms);
+            }
+            Ok(s) if s.to_uppercase() == "KEEPTTL" => {
+                // 保留键原有的过期时间。
+                keep_ttl = true;
+            }
+            // 目前，mini-redis 不支持任何其他的 SET 选项。与 Redis 一样以语法错误响应，连接保持打开。
+            Ok(_) => return Err("ERR syntax error".into()),
             // `EndOfStream` 错误表示没有更多数据可解析。在这种情况下，这是一个正常的运行情况，
             // 表示没有指定 `SET` 选项。
             Err(EndOfStream) => {}
-            // 所有其他错误都会冒泡，导致连接被终止。
+            // 所有其他错误都会冒泡，服务器以错误响应。
             Err(err) => return Err(err.into()),
         }
 
-        Ok(Set { key, value, expire })
+        Ok(Set {
+            key,
+            value,
+            expire,
+            expire_at,
+            keep_ttl,
+            name: "set",
+        })
+    }
+
+    /// 从接收到的帧中解析 `SETEX` 或者 `PSETEX` 命令。`millis` 为 `true` 时过期时间以毫秒为单位。
+    ///
+    /// 命令名称已经被解析消耗。与 Redis 一样，过期时间必须是正整数。
+    ///
+    /// # 格式
+    ///
+    /// ```text
+    /// SETEX key seconds value
+    /// PSETEX key milliseconds value
+    /// ```
+    pub(crate) fn parse_setex_frames(parse: &mut Parse, millis: bool) -> crate::Result<Set> {
+        let name = if millis { "psetex" } else { "setex" };
+
+        let key = parse.next_string()?;
+        let ttl = parse.next_signed_int()?;
+        let value = parse.next_bytes()?;
+
+        if ttl <= 0 {
+            return Err(format!("invalid expire time in '{}' command", name).into());
+        }
+
+        let expire = if millis {
+            Duration::from_millis(ttl as u64)
+        } else {
+            Duration::from_secs(ttl as u64)
+        };
+
+        Ok(Set {
+            key,
+            value,
+            expire: Some(expire),
+            expire_at: None,
+            keep_ttl: false,
+            name,
+        })
     }
 
     /// 将 `Set` 命令应用到指定的 `Db` 实例。
@@ -116,16 +209,50 @@ impl Set {
     #[instrument(skip(self, db, dst))]
     pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
         // 在共享的数据库状态中设置值。
-        db.set(self.key, self.value, self.expire);
+        match self.expire_at {
+            Some(unix_ms) => db.batch(|batch| batch.set_at(self.key, self.value, unix_ms)),
+            None => db.set(self.key, self.value, self.expire, self.keep_ttl),
+        }
 
         // 创建一个成功响应并将其写入 `dst`。
-        let response = Frame::Simple("OK".to_string());
+        let response = Frame::Simple(ByteString::from_static("OK"));
         debug!(?response);
         dst.write_frame(&response).await?;
 
         Ok(())
     }
 
+    /// 在 `EVALBATCH` 的批处理中执行 `Set` 命令，返回响应帧而不是写入连接。
+    pub(crate) fn execute(self, batch: &mut Batch<'_>) -> Frame {
+        match self.expire_at {
+            Some(unix_ms) => batch.set_at(self.key, self.value, unix_ms),
+            None => batch.set(self.key, self.value, self.expire, self.keep_ttl),
+        }
+        Frame::Simple(ByteString::from_static("OK"))
+    }
+
+    /// 与 `execute` 相同，并记录 `SET key value [PXAT unix-time-milliseconds]` 作为命令的效果。
+    ///
+    /// 过期时间（包括被随机延长的部分和 `KEEPTTL` 保留的过期时间）被记录为绝对的时间戳，
+    /// 加载 AOF 和副本执行时键在与这里相同的时刻过期，而不是从执行的时刻重新计算。
+    pub(crate) fn execute_propagated(self, batch: &mut Batch<'_>) -> Frame {
+        let key = self.key.clone();
+        let value = self.value.clone();
+        let response = self.execute(batch);
+
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from_static(b"set"));
+        frame.push_bulk(Bytes::from(key.clone().into_bytes()));
+        frame.push_bulk(value);
+        if let Some(unix_ms) = batch.expire_time(&key) {
+            frame.push_bulk(Bytes::from_static(b"pxat"));
+            frame.push_int(unix_ms);
+        }
+        batch.propagate(frame);
+
+        response
+    }
+
     /// 将命令转换为等效的 `Frame`。
     ///
     /// 客户端在编码一个 `Set` 命令以发送到服务器时调用此函数。
@@ -143,6 +270,13 @@ impl Set {
             frame.push_bulk(Bytes::from("px".as_bytes()));
             frame.push_int(ms.as_millis() as u64);
         }
+        if let Some(unix_ms) = self.expire_at {
+            frame.push_bulk(Bytes::from("pxat".as_bytes()));
+            frame.push_int(unix_ms);
+        }
+        if self.keep_ttl {
+            frame.push_bulk(Bytes::from("keepttl".as_bytes()));
+        }
         frame
     }
 }
@@ -0,0 +1,97 @@
+use crate::cmd::Parse;
+use crate::db::Batch;
+use crate::{Connection, Db, Frame};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// 设置键中存储的字符串在指定偏移量处的位，并返回该位原来的值。
+///
+/// 位按字节从高到低编号：偏移量 `0` 是第一个字节的最高位。如果字符串的长度不足，则用零字节填充。
+/// 键不存在时视为空字符串。键的过期时间保持不变。
+#[derive(Debug)]
+pub struct SetBit {
+    /// 要修改的键
+    key: String,
+
+    /// 位偏移量
+    offset: u64,
+
+    /// 要设置的值，只能是 `0` 或 `1`
+    value: u64,
+}
+
+impl SetBit {
+    /// 创建一个新的 `SetBit` 命令，将 `key` 中第 `offset` 位设置为 `value`。
+    pub(crate) fn new(key: impl ToString, offset: u64, value: bool) -> SetBit {
+        SetBit {
+            key: key.to_string(),
+            offset,
+            value: value as u64,
+        }
+    }
+
+    /// 获取键
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// 从接收到的帧中解析一个 `SetBit` 实例。
+    ///
+    /// `SETBIT` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// 期望一个包含四个条目的数组帧。
+    ///
+    /// ```text
+    /// SETBIT key offset value
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<SetBit> {
+        let key = parse.next_string()?;
+        let offset = parse.next_int()?;
+        let value = parse.next_int()?;
+
+        Ok(SetBit { key, offset, value })
+    }
+
+    /// 将 `SetBit` 命令应用到指定的 `Db` 实例。
+    ///
+    /// 响应写入到 `dst`。服务器调用此函数以执行接收到的命令。
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = db.batch(|batch| self.execute(batch));
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 在批处理中执行 `SetBit` 命令，返回响应帧而不是写入连接。
+    pub(crate) fn execute(self, batch: &mut Batch<'_>) -> Frame {
+        let bit = match self.value {
+            0 => false,
+            1 => true,
+            _ => return Frame::error("bit is not an integer or out of range"),
+        };
+
+        match batch.setbit(&self.key, self.offset, bit) {
+            Ok(prev) => Frame::Integer(prev as u64),
+            Err(msg) => Frame::error(msg),
+        }
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `SetBit` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("setbit".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame.push_int(self.offset);
+        frame.push_int(self.value);
+        frame
+    }
+}
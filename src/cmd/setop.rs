@@ -0,0 +1,134 @@
+use crate::cmd::del::parse_keys;
+use crate::cmd::Parse;
+use crate::db::{Batch, SetOperation};
+use crate::{Connection, Db, Frame};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// 集合运算：`SINTER`、`SUNION`、`SDIFF`，以及将结果存储到另一个键的 `SINTERSTORE`、`SUNIONSTORE`、`SDIFFSTORE`。
+///
+/// 运算在 `Db` 的一次加锁下完成，其他连接不会在读取各个集合之间修改它们。不存在的键视为空集合，
+/// 任何一个键中存储的不是集合时返回 `WRONGTYPE` 错误。
+///
+/// 不存储结果时返回结果集合的成员（按字节序），存储结果时返回结果集合的成员数量。
+#[derive(Debug)]
+pub struct SetOp {
+    /// 要执行的运算
+    op: SetOperation,
+
+    /// 存储结果的键。`None` 表示直接返回结果。
+    destination: Option<String>,
+
+    /// 参与运算的集合的键
+    keys: Vec<String>,
+}
+
+impl SetOp {
+    /// 创建一个新的 `SetOp` 命令，对 `keys` 执行 `op` 运算。
+    pub(crate) fn new(op: SetOperation, destination: Option<&str>, keys: &[String]) -> SetOp {
+        SetOp {
+            op,
+            destination: destination.map(str::to_string),
+            keys: keys.to_vec(),
+        }
+    }
+
+    /// 获取存储结果的键
+    pub fn destination(&self) -> Option<&str> {
+        self.destination.as_deref()
+    }
+
+    /// 获取参与运算的集合的键
+    pub fn keys(&self) -> &[String] {
+        &self.keys
+    }
+
+    /// 返回命令名称，例如 `sinter` 或 `sdiffstore`
+    pub(crate) fn get_name(&self) -> &'static str {
+        match (self.op, self.destination.is_some()) {
+            (SetOperation::Inter, false) => "sinter",
+            (SetOperation::Inter, true) => "sinterstore",
+            (SetOperation::Union, false) => "sunion",
+            (SetOperation::Union, true) => "sunionstore",
+            (SetOperation::Diff, false) => "sdiff",
+            (SetOperation::Diff, true) => "sdiffstore",
+        }
+    }
+
+    /// 从接收到的帧中解析一个 `SetOp` 实例。`store` 表示命令是否为 `STORE` 变体。
+    ///
+    /// 命令名称已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// ```text
+    /// SINTER key [key ...]
+    /// SINTERSTORE destination key [key ...]
+    /// ```
+    ///
+    /// `SUNION`、`SDIFF` 及其 `STORE` 变体的格式相同。
+    pub(crate) fn parse_frames(
+        op: SetOperation,
+        store: bool,
+        parse: &mut Parse,
+    ) -> crate::Result<SetOp> {
+        let destination = if store {
+            Some(parse.next_string()?)
+        } else {
+            None
+        };
+
+        Ok(SetOp {
+            op,
+            destination,
+            keys: parse_keys(parse)?,
+        })
+    }
+
+    /// 将 `SetOp` 命令应用到指定的 `Db` 实例。
+    ///
+    /// 响应写入到 `dst`。服务器调用此函数以执行接收到的命令。
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = db.batch(|batch| self.execute(batch));
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 在批处理中执行 `SetOp` 命令，返回响应帧而不是写入连接。
+    pub(crate) fn execute(self, batch: &mut Batch<'_>) -> Frame {
+        let result = match &self.destination {
+            Some(destination) => batch
+                .set_operation_store(self.op, destination, &self.keys)
+                .map(Frame::Integer),
+            None => batch
+                .set_operation(self.op, &self.keys)
+                .map(|members| Frame::Array(members.into_iter().map(Frame::Bulk).collect())),
+        };
+
+        result.unwrap_or_else(Frame::error)
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `SetOp` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from(self.get_name().as_bytes()));
+
+        if let Some(destination) = self.destination {
+            frame.push_bulk(Bytes::from(destination.into_bytes()));
+        }
+
+        for key in self.keys {
+            frame.push_bulk(Bytes::from(key.into_bytes()));
+        }
+
+        frame
+    }
+}
@@ -0,0 +1,95 @@
+use crate::cmd::Parse;
+use crate::db::Batch;
+use crate::{Connection, Db, Frame};
+
+use bytes::Bytes;
+use std::convert::TryFrom;
+use tracing::{debug, instrument};
+
+/// 从指定的偏移量开始，用 `value` 覆盖键中存储的字符串的一部分。
+///
+/// 如果字符串的长度不足 `offset`，则用零字节填充。键不存在时视为空字符串。
+/// 返回修改后字符串的长度。键的过期时间保持不变。
+#[derive(Debug)]
+pub struct SetRange {
+    /// 要修改的键
+    key: String,
+
+    /// 开始写入的字节偏移量
+    offset: u64,
+
+    /// 要写入的数据
+    value: Bytes,
+}
+
+impl SetRange {
+    /// 创建一个新的 `SetRange` 命令，从 `offset` 开始将 `value` 写入 `key`。
+    pub(crate) fn new(key: impl ToString, offset: u64, value: Bytes) -> SetRange {
+        SetRange {
+            key: key.to_string(),
+            offset,
+            value,
+        }
+    }
+
+    /// 获取键
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// 从接收到的帧中解析一个 `SetRange` 实例。
+    ///
+    /// `SETRANGE` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// 期望一个包含四个条目的数组帧。
+    ///
+    /// ```text
+    /// SETRANGE key offset value
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<SetRange> {
+        let key = parse.next_string()?;
+        let offset = parse.next_int()?;
+        let value = parse.next_bytes()?;
+
+        Ok(SetRange { key, offset, value })
+    }
+
+    /// 将 `SetRange` 命令应用到指定的 `Db` 实例。
+    ///
+    /// 响应写入到 `dst`。服务器调用此函数以执行接收到的命令。
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = db.batch(|batch| self.execute(batch));
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 在批处理中执行 `SetRange` 命令，返回响应帧而不是写入连接。
+    pub(crate) fn execute(self, batch: &mut Batch<'_>) -> Frame {
+        // 超出 `usize` 的偏移量必然超过字符串的最大长度。
+        let offset = usize::try_from(self.offset).unwrap_or(usize::MAX);
+
+        match batch.setrange(&self.key, offset, &self.value) {
+            Ok(len) => Frame::Integer(len),
+            Err(msg) => Frame::error(msg),
+        }
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `SetRange` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("setrange".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame.push_int(self.offset);
+        frame.push_bulk(self.value);
+        frame
+    }
+}
@@ -0,0 +1,78 @@
+use crate::cmd::Parse;
+use crate::db::Batch;
+use crate::{Connection, Db, Frame};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// 返回键中存储的集合的所有成员。键不存在时返回空数组。
+///
+/// 成员按字节序返回。
+#[derive(Debug)]
+pub struct SMembers {
+    /// 集合的键
+    key: String,
+}
+
+impl SMembers {
+    /// 创建一个新的 `SMembers` 命令，获取 `key` 的成员。
+    pub(crate) fn new(key: impl ToString) -> SMembers {
+        SMembers {
+            key: key.to_string(),
+        }
+    }
+
+    /// 获取键
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// 从接收到的帧中解析一个 `SMembers` 实例。
+    ///
+    /// `SMEMBERS` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// 期望一个包含两个条目的数组帧。
+    ///
+    /// ```text
+    /// SMEMBERS key
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<SMembers> {
+        let key = parse.next_string()?;
+
+        Ok(SMembers { key })
+    }
+
+    /// 将 `SMembers` 命令应用到指定的 `Db` 实例。
+    ///
+    /// 响应写入到 `dst`。服务器调用此函数以执行接收到的命令。
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = db.batch(|batch| self.execute(batch));
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 在批处理中执行 `SMembers` 命令，返回响应帧而不是写入连接。
+    pub(crate) fn execute(self, batch: &mut Batch<'_>) -> Frame {
+        match batch.smembers(&self.key) {
+            Ok(members) => Frame::Array(members.into_iter().map(Frame::Bulk).collect()),
+            Err(msg) => Frame::error(msg),
+        }
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `SMembers` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("smembers".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame
+    }
+}
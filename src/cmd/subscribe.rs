@@ -1,16 +1,19 @@
-use crate::cmd::{Parse, ParseError, Unknown};
-use crate::{Command, Connection, Db, Frame, Shutdown};
+use crate::cmd::{Parse, ParseError};
+use crate::db::Published;
+use crate::{Connection, Db, Frame};
 
 use bytes::Bytes;
+use std::fmt;
 use std::pin::Pin;
-use tokio::select;
+use std::time::UNIX_EPOCH;
 use tokio::sync::broadcast;
 use tokio_stream::{Stream, StreamExt, StreamMap};
+use tracing::warn;
 
 /// 订阅客户端至一个或多个频道。
 ///
 /// 一旦客户端进入订阅状态, 除了额外的 SUBSCRIBE、PSUBSCRIBE、UNSUBSCRIBE、
-/// PUNSUBSCRIBE、PING 和 QUIT 命令外，不应发布其他命令。
+/// PUNSUBSCRIBE、PING、RESET 和 QUIT 命令外，不应发布其他命令。
 #[derive(Debug)]
 pub struct Subscribe {
     channels: Vec<String>,
@@ -24,9 +27,26 @@ pub struct Unsubscribe {
     channels: Vec<String>,
 }
 
-/// 消息流。该流从 `broadcast::Receiver` 接收消息。我们使用 `stream!` 来创建一个
+/// 消息流。该流从 `broadcast::Receiver` 接收消息，并产生要写入客户端的帧。我们使用 `stream!` 来创建一个
 /// 消费消息的 `Stream`。因为 `stream!` 的值不能命名，我们使用 trait 对象对流进行装箱。
-type Messages = Pin<Box<dyn Stream<Item = Bytes> + Send>>;
+type Messages = Pin<Box<dyn Stream<Item = Frame> + Send>>;
+
+/// 一个连接的活动订阅。
+///
+/// 每个单独的频道订阅是使用 `sync::broadcast` 频道处理的。然后，消息被扩展到当前订阅这些频道的所有客户端。
+///
+/// 一个单独的客户端可以订阅多个频道，并可以动态地添加和移除其订阅集中的频道。
+/// 为了处理这一点，使用 `StreamMap` 来跟踪活动订阅。`StreamMap` 将接收到的来自各个广播频道的消息合并。
+/// 连接处理程序在订阅状态下持有 `Subscriptions`，在等待下一个命令的同时转发消息。
+pub(crate) struct Subscriptions {
+    streams: StreamMap<String, Messages>,
+
+    /// 订阅的频道所在的数据库。取消订阅之后通过它移除不再有订阅者的频道。
+    db: Db,
+
+    /// 是否在转发的消息中附带发布者的连接 ID 和发布时间，由 `CLIENT PUBSUB-METADATA` 开启。
+    metadata: bool,
+}
 
 impl Subscribe {
     /// 创建一个新的 `Subscribe` 命令以监听指定的频道。
@@ -71,7 +91,7 @@ impl Subscribe {
                 Ok(s) => channels.push(s),
                 // `EndOfStream` 错误表示没有更多数据可解析。
                 Err(EndOfStream) => break,
-                // 所有其他错误都被向上传递，导致连接终止。
+                // 所有其他错误都被向上传递，服务器以错误响应。
                 Err(err) => return Err(err.into()),
             }
         }
@@ -79,64 +99,6 @@ impl Subscribe {
         Ok(Subscribe { channels })
     }
 
-    /// 将 `Subscribe` 命令应用于指定的 `Db` 实例。
-    ///
-    /// 这个函数是入口点，包含了要订阅的初始频道列表。
-    /// 客户端可能会接收到额外的 `subscribe` 和 `unsubscribe` 命令，
-    /// 并据此更新订阅列表。
-    ///
-    /// [这里]: https://redis.io/topics/pubsub
-    pub(crate) async fn apply(
-        mut self,
-        db: &Db,
-        dst: &mut Connection,
-        shutdown: &mut Shutdown,
-    ) -> crate::Result<()> {
-        // 每个单独的频道订阅是使用 `sync::broadcast` 频道处理的。
-        // 然后，消息被扩展到当前订阅这些频道的所有客户端。
-        //
-        // 一个单独的客户端可以订阅多个频道，并可以动态地添加和移除其订阅集中的频道。
-        // 为了处理这一点，使用 `StreamMap` 来跟踪活动订阅。
-        // `StreamMap` 将接收到的来自各个广播频道的消息合并。
-        let mut subscriptions = StreamMap::new();
-
-        loop {
-            // `self.channels` 用于跟踪要额外订阅的频道。在执行 `apply` 的过程中
-            // 收到新的 `SUBSCRIBE` 命令时，新频道被推入这个 vec。
-            for channel_name in self.channels.drain(..) {
-                subscribe_to_channel(channel_name, &mut subscriptions, db, dst).await?;
-            }
-
-            // 等待以下事件之一发生：
-            //
-            // - 从已订阅频道之一接收到信息。
-            // - 从客户端接收到订阅或取消订阅命令。
-            // - 服务器关闭信号。
-            select! {
-                // 从已订阅的频道接收消息
-                Some((channel_name, msg)) = subscriptions.next() => {
-                    dst.write_frame(&make_message_frame(channel_name, msg)).await?;
-                }
-                res = dst.read_frame() => {
-                    let frame = match res? {
-                        Some(frame) => frame,
-                        // 这种情况发生在远程客户端已断开连接时。
-                        None => return Ok(())
-                    };
-
-                    handle_command(
-                        frame,
-                        &mut self.channels,
-                        &mut subscriptions,
-                        dst,
-                    ).await?;
-                }
-                _ = shutdown.recv() => {
-                    return Ok(());
-                }
-            };
-        }
-    }
     /// 将命令转换为等效的 `Frame`。
     ///
     /// 当客户端编码一个要发送到服务器的 `Subscribe` 命令时调用此方法。
@@ -150,76 +112,145 @@ impl Subscribe {
     }
 }
 
-async fn subscribe_to_channel(
-    channel_name: String,
-    subscriptions: &mut StreamMap<String, Messages>,
-    db: &Db,
-    dst: &mut Connection,
-) -> crate::Result<()> {
-    let mut rx = db.subscribe(channel_name.clone());
+impl Subscriptions {
+    /// 创建一个空的订阅集，订阅 `db` 中的频道。`metadata` 表示转发的消息是否附带发布者的元数据，
+    /// 参见 `make_message_frame`。
+    pub(crate) fn new(db: &Db, metadata: bool) -> Subscriptions {
+        Subscriptions {
+            streams: StreamMap::new(),
+            db: db.clone(),
+            metadata,
+        }
+    }
 
-    // 订阅频道。
-    let rx = Box::pin(async_stream::stream! {
-        loop {
-            match rx.recv().await {
-                Ok(msg) => yield msg,
-                // 如果我们在消费消息时落后了，只需继续。
-                Err(broadcast::error::RecvError::Lagged(_)) => {}
-                Err(_) => break,
+    /// 是否没有订阅任何频道。
+    pub(crate) fn is_empty(&self) -> bool {
+        self.streams.is_empty()
+    }
+
+    /// 订阅 `Subscribe` 命令中的频道，并为每个频道写入一个确认帧。
+    ///
+    /// 与转发的消息一样，确认帧通过 `Connection::queue_frame` 写入，不等待慢速的对等方，
+    /// 连接处理程序因此在写入停滞时仍然能够响应关闭信号和对等方断开连接。积压的数据超过连接的输出上限时返回错误。
+    pub(crate) fn subscribe(&mut self, cmd: Subscribe, dst: &mut Connection) -> crate::Result<()> {
+        for channel_name in cmd.channels {
+            self.subscribe_to_channel(channel_name, dst)?;
+        }
+
+        Ok(())
+    }
+
+    /// 取消 `Unsubscribe` 命令中的频道的订阅，并为每个频道写入一个确认帧。
+    ///
+    /// 没有指定频道时取消所有频道的订阅。与 Redis 一样，如果此时没有订阅任何频道，
+    /// 响应 `["unsubscribe", nil, 0]`。确认帧不等待写入完成，参见 `subscribe`。
+    pub(crate) fn unsubscribe(
+        &mut self,
+        mut cmd: Unsubscribe,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        // 如果没有指定频道，这将请求取消订阅**所有**频道。
+        // 要实现这一点，将 `cmd.channels` vec 填充为当前已订阅的频道列表。
+        if cmd.channels.is_empty() {
+            cmd.channels = self
+                .streams
+                .keys()
+                .map(|channel_name| channel_name.to_string())
+                .collect();
+
+            if cmd.channels.is_empty() {
+                let response = Frame::Array(vec![
+                    Frame::Bulk(Bytes::from_static(b"unsubscribe")),
+                    Frame::Null,
+                    Frame::Integer(0),
+                ]);
+
+                dst.queue_frame(&response)?;
+                return Ok(());
             }
         }
-    });
 
-    // 在此客户端的订阅集中跟踪订阅。
-    subscriptions.insert(channel_name.clone(), rx);
+        for channel_name in cmd.channels {
+            // 丢弃消息流同时丢弃了其中的接收器，之后频道可能已经没有订阅者。
+            if self.streams.remove(&channel_name).is_some() {
+                self.db.release_channel(&channel_name);
+            }
 
-    // 响应成功订阅
-    let response = make_subscribe_frame(channel_name, subscriptions.len());
-    dst.write_frame(&response).await?;
+            let response = make_unsubscribe_frame(channel_name, self.streams.len());
+            dst.queue_frame(&response)?;
+        }
 
-    Ok(())
-}
+        Ok(())
+    }
 
-/// 处理在 `Subscribe::apply` 中接收到的命令。在此上下文中只允许订阅和取消订阅命令。
-///
-/// 任何新的订阅都会被追加到 `subscribe_to` 中，而不是修改 `subscriptions`。
-async fn handle_command(
-    frame: Frame,
-    subscribe_to: &mut Vec<String>,
-    subscriptions: &mut StreamMap<String, Messages>,
-    dst: &mut Connection,
-) -> crate::Result<()> {
-    // 从客户端接收到一个命令。
-    //
-    // 在此上下文中只允许 `SUBSCRIBE` 和 `UNSUBSCRIBE` 命令。
-    match Command::from_frame(frame)? {
-        Command::Subscribe(subscribe) => {
-            // `apply` 方法会订阅我们添加到此向量中的频道。
-            subscribe_to.extend(subscribe.channels.into_iter());
+    /// 等待已订阅的频道上的下一条消息。没有订阅任何频道时返回 `None`。
+    pub(crate) async fn next_message(&mut self) -> Option<Frame> {
+        self.streams.next().await.map(|(_, frame)| frame)
+    }
+
+    /// 订阅一个频道并写入确认帧。
+    ///
+    /// 与 Redis 一样，重复订阅已经订阅的频道只写入确认帧，订阅数量不变。不会为它再创建一个接收器，
+    /// 否则原有的接收器被替换，其中尚未转发的消息会丢失。
+    fn subscribe_to_channel(
+        &mut self,
+        channel_name: String,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        if self.streams.contains_key(&channel_name) {
+            let response = make_subscribe_frame(channel_name, self.streams.len());
+            dst.queue_frame(&response)?;
+            return Ok(());
         }
-        Command::Unsubscribe(mut unsubscribe) => {
-            // 如果没有指定频道，这将请求取消订阅**所有**频道。
-            // 要实现这一点，将 `unsubscribe.channels` vec 填充为当前已订阅的频道列表。
-            if unsubscribe.channels.is_empty() {
-                unsubscribe.channels = subscriptions
-                    .keys()
-                    .map(|channel_name| channel_name.to_string())
-                    .collect();
+
+        let mut rx = self.db.subscribe(channel_name.clone());
+
+        // 订阅频道。
+        let name = channel_name.clone();
+        let metadata = self.metadata;
+        let rx = Box::pin(async_stream::stream! {
+            loop {
+                match rx.recv().await {
+                    Ok(msg) => yield make_message_frame(name.clone(), msg, metadata),
+                    // 如果我们在消费消息时落后了，旧消息已被丢弃。
+                    // 告知客户端丢失了多少条消息，然后继续。
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!(channel = %name, skipped, "subscriber lagged");
+                        yield make_lagged_frame(name.clone(), skipped);
+                    }
+                    Err(_) => break,
+                }
             }
+        });
 
-            for channel_name in unsubscribe.channels {
-                subscriptions.remove(&channel_name);
+        // 在此客户端的订阅集中跟踪订阅。
+        self.streams.insert(channel_name.clone(), rx);
 
-                let response = make_unsubscribe_frame(channel_name, subscriptions.len());
-                dst.write_frame(&response).await?;
-            }
-        }
-        command => {
-            let cmd = Unknown::new(command.get_name());
-            cmd.apply(dst).await?;
+        // 响应成功订阅
+        let response = make_subscribe_frame(channel_name, self.streams.len());
+        dst.queue_frame(&response)?;
+
+        Ok(())
+    }
+}
+
+impl Drop for Subscriptions {
+    fn drop(&mut self) {
+        // 连接断开或者执行 `RESET` 时，先丢弃所有的接收器，再移除不再有订阅者的频道。
+        let streams = std::mem::take(&mut self.streams);
+        let channels: Vec<String> = streams.keys().cloned().collect();
+        drop(streams);
+
+        for channel in channels {
+            self.db.release_channel(&channel);
         }
     }
-    Ok(())
+}
+
+impl fmt::Debug for Subscriptions {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_set().entries(self.streams.keys()).finish()
+    }
 }
 
 /// 创建对订阅请求的响应。
@@ -244,11 +275,38 @@ fn make_unsubscribe_frame(channel_name: String, num_subs: usize) -> Frame {
 }
 
 /// 创建一个消息，用于通知客户端有关频道上的新消息，该频道是客户端订阅的频道。
-fn make_message_frame(channel_name: String, msg: Bytes) -> Frame {
+///
+/// `metadata` 为 `true` 时，在消息内容之后附带发布者的连接 ID 和发布时间（Unix 毫秒时间戳）：
+/// `["message", channel, content, publisher, timestamp]`。不是由客户端连接发布的消息，发布者为 nil。
+fn make_message_frame(channel_name: String, msg: Published, metadata: bool) -> Frame {
+    let mut parts = vec![
+        Frame::Bulk(Bytes::from_static(b"message")),
+        Frame::Bulk(Bytes::from(channel_name)),
+        Frame::Bulk(msg.payload),
+    ];
+
+    if metadata {
+        let timestamp = msg
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+
+        parts.push(msg.publisher.map_or(Frame::Null, Frame::Integer));
+        parts.push(Frame::Integer(timestamp));
+    }
+
+    Frame::Array(parts)
+}
+
+/// 创建一个诊断消息，用于通知客户端由于消费过慢，频道上有 `skipped` 条消息被丢弃。
+///
+/// 格式与普通消息类似：`["lagged", channel, skipped]`。
+fn make_lagged_frame(channel_name: String, skipped: u64) -> Frame {
     let mut response = Frame::array();
-    response.push_bulk(Bytes::from_static(b"message"));
+    response.push_bulk(Bytes::from_static(b"lagged"));
     response.push_bulk(Bytes::from(channel_name));
-    response.push_bulk(msg);
+    response.push_int(skipped);
     response
 }
 
@@ -292,7 +350,7 @@ impl Unsubscribe {
                 Ok(s) => channels.push(s),
                 // `EndOfStream` 错误表示没有进一步的数据可以解析。
                 Err(EndOfStream) => break,
-                // 所有其他错误都被向上传递，导致连接终止。
+                // 所有其他错误都被向上传递，服务器以错误响应。
                 Err(err) => return Err(err),
             }
         }
@@ -1,3 +1,4 @@
+use crate::frame::ErrorKind;
 use crate::{Connection, Frame};
 
 use tracing::{debug, instrument};
@@ -26,11 +27,19 @@ impl Unknown {
     /// 这通常意味着该命令尚未被 `mini-redis` 实现。
     #[instrument(skip(self, dst))]
     pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
-        let response = Frame::Error(format!("ERR unknown command '{}'", self.command_name));
+        let response = self.to_error();
 
         debug!(?response);
 
         dst.write_frame(&response).await?;
         Ok(())
     }
+
+    /// 返回发送给客户端的错误帧。
+    pub(crate) fn to_error(&self) -> Frame {
+        Frame::typed_error(
+            ErrorKind::Err,
+            format_args!("unknown command '{}'", self.command_name),
+        )
+    }
 }
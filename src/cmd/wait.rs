@@ -0,0 +1,118 @@
+use crate::cmd::Parse;
+use crate::{Connection, Db, Frame, Shutdown};
+
+use bytes::Bytes;
+use std::time::Duration;
+use tokio::time;
+use tracing::{debug, instrument};
+
+/// 阻塞当前客户端，直到之前的写命令被至少 `numreplicas` 个副本确认，或者超时。
+///
+/// 返回确认了写入的副本数量。`timeout` 以毫秒为单位，`0` 表示一直等待。
+///
+/// 副本以 `REPLCONF ACK offset` 确认处理到的复制偏移量。`WAIT` 等待确认的偏移量达到执行 `WAIT` 时主节点的
+/// 复制偏移量的副本数量达到 `numreplicas`，因此覆盖了这个连接之前的所有写命令（也可能包括其他连接的写命令）。
+#[derive(Debug)]
+pub struct Wait {
+    /// 需要确认写入的副本数量
+    numreplicas: u64,
+
+    /// 最长等待时间。`None` 表示一直等待。
+    timeout: Option<Duration>,
+}
+
+impl Wait {
+    /// 创建一个新的 `Wait` 命令。`timeout` 为 `None` 表示一直等待。
+    pub(crate) fn new(numreplicas: u64, timeout: Option<Duration>) -> Wait {
+        Wait {
+            numreplicas,
+            timeout,
+        }
+    }
+
+    /// 从接收到的帧中解析一个 `Wait` 实例。
+    ///
+    /// `WAIT` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// 期望一个包含三个条目的数组帧。
+    ///
+    /// ```text
+    /// WAIT numreplicas timeout
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Wait> {
+        let numreplicas = parse.next_int()?;
+
+        let timeout = match parse.next_int()? {
+            0 => None,
+            ms => Some(Duration::from_millis(ms)),
+        };
+
+        Ok(Wait {
+            numreplicas,
+            timeout,
+        })
+    }
+
+    /// 将 `Wait` 命令应用到服务器。
+    ///
+    /// 超时或者等待期间收到服务器关闭信号时，立即以当前的确认数量响应。
+    #[instrument(skip(self, db, dst, shutdown))]
+    pub(crate) async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let offset = db.replication_offset();
+
+        let timeout = async {
+            match self.timeout {
+                Some(timeout) => time::sleep(timeout).await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::pin!(timeout);
+
+        let acked = loop {
+            // 在统计之前开始等待，不会错过统计之后到达的确认。
+            let notified = db.replica_acked();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            let acked = db.acked_replicas(offset);
+
+            if acked >= self.numreplicas {
+                break acked;
+            }
+
+            tokio::select! {
+                _ = &mut notified => {}
+                _ = &mut timeout => break db.acked_replicas(offset),
+                _ = shutdown.recv() => break db.acked_replicas(offset),
+            }
+        };
+
+        let response = Frame::Integer(acked);
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `Wait` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        let timeout = self.timeout.map_or(0, |timeout| timeout.as_millis() as u64);
+
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("wait".as_bytes()));
+        frame.push_int(self.numreplicas);
+        frame.push_int(timeout);
+        frame
+    }
+}
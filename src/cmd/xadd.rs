@@ -0,0 +1,136 @@
+use crate::cmd::{Parse, ParseError};
+use crate::db::Batch;
+use crate::stream::StreamId;
+use crate::{Connection, Db, Frame};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// 向键中存储的流添加一个条目，返回条目的 ID。
+///
+/// 键不存在时创建一个新的流。ID 为 `*` 时根据当前时间自动生成，否则指定的 ID
+/// 必须大于流中最后一个条目的 ID。添加条目会唤醒正在 `XREAD BLOCK` 中等待的客户端。
+#[derive(Debug)]
+pub struct XAdd {
+    /// 流的键
+    key: String,
+
+    /// 条目的 ID，`None` 表示自动生成
+    id: Option<StreamId>,
+
+    /// 条目的字段/值对
+    fields: Vec<(Bytes, Bytes)>,
+}
+
+impl XAdd {
+    /// 创建一个新的 `XAdd` 命令，将由 `fields` 组成的条目添加到 `key`。
+    pub(crate) fn new(key: impl ToString, id: Option<StreamId>, fields: &[(Bytes, Bytes)]) -> XAdd {
+        XAdd {
+            key: key.to_string(),
+            id,
+            fields: fields.to_vec(),
+        }
+    }
+
+    /// 获取键
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// 从接收到的帧中解析一个 `XAdd` 实例。
+    ///
+    /// `XADD` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// 期望一个至少包含五个条目的数组帧。
+    ///
+    /// ```text
+    /// XADD key <* | id> field value [field value ...]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<XAdd> {
+        use ParseError::EndOfStream;
+
+        let key = parse.next_string()?;
+
+        let id = match &parse.next_string()?[..] {
+            "*" => None,
+            id => Some(id.parse()?),
+        };
+
+        let mut fields = vec![(parse.next_bytes()?, parse.next_bytes()?)];
+
+        loop {
+            let field = match parse.next_bytes() {
+                Ok(field) => field,
+                Err(EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            };
+
+            // 字段和值必须成对出现。
+            fields.push((field, parse.next_bytes()?));
+        }
+
+        Ok(XAdd { key, id, fields })
+    }
+
+    /// 将 `XAdd` 命令应用到指定的 `Db` 实例。
+    ///
+    /// 响应写入到 `dst`。服务器调用此函数以执行接收到的命令。
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = db.batch(|batch| self.execute(batch));
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 在批处理中执行 `XAdd` 命令，返回响应帧而不是写入连接。
+    pub(crate) fn execute(self, batch: &mut Batch<'_>) -> Frame {
+        match batch.xadd(&self.key, self.id, self.fields) {
+            Ok(id) => Frame::Bulk(Bytes::from(id.to_string())),
+            Err(msg) => Frame::error(msg),
+        }
+    }
+
+    /// 与 `execute` 相同，并记录指定了生成的 ID 的 `XADD` 作为命令的效果，
+    /// 加载 AOF 和副本执行时条目的 ID 与这里相同，而不是根据执行时的时钟重新生成。
+    pub(crate) fn execute_propagated(self, batch: &mut Batch<'_>) -> Frame {
+        match batch.xadd(&self.key, self.id, self.fields.clone()) {
+            Ok(id) => {
+                let effect = XAdd {
+                    id: Some(id),
+                    ..self
+                };
+                batch.propagate(effect.into_frame());
+                Frame::Bulk(Bytes::from(id.to_string()))
+            }
+            Err(msg) => Frame::error(msg),
+        }
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `XAdd` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        let id = match self.id {
+            Some(id) => id.to_string(),
+            None => "*".to_string(),
+        };
+
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("xadd".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame.push_bulk(Bytes::from(id));
+
+        for (field, value) in self.fields {
+            frame.push_bulk(field);
+            frame.push_bulk(value);
+        }
+
+        frame
+    }
+}
@@ -0,0 +1,129 @@
+use crate::cmd::{Parse, ParseError};
+use crate::db::Batch;
+use crate::stream::StreamId;
+use crate::{Connection, Db, Frame};
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+/// 返回键中存储的流在两个 ID 之间（包含两端）的条目。
+///
+/// `-` 和 `+` 分别表示最小和最大的 ID。省略序列号的起始 ID 表示该毫秒内的第一个条目，
+/// 结束 ID 表示最后一个条目。`COUNT` 限制返回的条目数量。键不存在时返回空数组。
+#[derive(Debug)]
+pub struct XRange {
+    /// 流的键
+    key: String,
+
+    /// 起始 ID（包含）
+    start: StreamId,
+
+    /// 结束 ID（包含）
+    end: StreamId,
+
+    /// 最多返回的条目数量
+    count: Option<u64>,
+}
+
+impl XRange {
+    /// 创建一个新的 `XRange` 命令，读取 `key` 中 ID 在 `start` 和 `end` 之间的条目。
+    pub(crate) fn new(
+        key: impl ToString,
+        start: StreamId,
+        end: StreamId,
+        count: Option<u64>,
+    ) -> XRange {
+        XRange {
+            key: key.to_string(),
+            start,
+            end,
+            count,
+        }
+    }
+
+    /// 获取键
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// 从接收到的帧中解析一个 `XRange` 实例。
+    ///
+    /// `XRANGE` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// 期望一个包含四个或六个条目的数组帧。
+    ///
+    /// ```text
+    /// XRANGE key start end [COUNT count]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<XRange> {
+        use ParseError::EndOfStream;
+
+        let key = parse.next_string()?;
+
+        let start = match &parse.next_string()?[..] {
+            "-" => StreamId::MIN,
+            id => StreamId::parse_with_default_seq(id, 0)?,
+        };
+
+        let end = match &parse.next_string()?[..] {
+            "+" => StreamId::MAX,
+            id => StreamId::parse_with_default_seq(id, u64::MAX)?,
+        };
+
+        let count = match parse.next_string() {
+            Ok(s) if s.to_uppercase() == "COUNT" => Some(parse.next_int()?),
+            Ok(_) => return Err("ERR syntax error".into()),
+            Err(EndOfStream) => None,
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(XRange {
+            key,
+            start,
+            end,
+            count,
+        })
+    }
+
+    /// 将 `XRange` 命令应用到指定的 `Db` 实例。
+    ///
+    /// 响应写入到 `dst`。服务器调用此函数以执行接收到的命令。
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = db.batch(|batch| self.execute(batch));
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 在批处理中执行 `XRange` 命令，返回响应帧而不是写入连接。
+    pub(crate) fn execute(self, batch: &mut Batch<'_>) -> Frame {
+        match batch.xrange(&self.key, self.start, self.end, self.count) {
+            Ok(entries) => Frame::Array(entries.into_iter().map(|e| e.into_frame()).collect()),
+            Err(msg) => Frame::error(msg),
+        }
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `XRange` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("xrange".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame.push_bulk(Bytes::from(self.start.to_string()));
+        frame.push_bulk(Bytes::from(self.end.to_string()));
+
+        if let Some(count) = self.count {
+            frame.push_bulk(Bytes::from("count".as_bytes()));
+            frame.push_int(count);
+        }
+
+        frame
+    }
+}
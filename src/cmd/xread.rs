@@ -0,0 +1,248 @@
+use crate::cmd::{Parse, ParseError};
+use crate::db::Batch;
+use crate::stream::StreamId;
+use crate::{Connection, Db, Frame, Shutdown};
+
+use bytes::Bytes;
+use std::time::Duration;
+use tokio::time::{self, Instant};
+use tracing::{debug, instrument};
+
+/// 读取一个或多个流中 ID 大于给定 ID 的条目。
+///
+/// ID 为 `$` 时表示流中当前最后一个条目的 ID，即只读取命令执行之后添加的条目。
+/// 指定 `BLOCK` 时，如果没有可读的条目，则等待直到有条目被添加、超时（`0` 表示一直等待）
+/// 或者服务器关闭。没有读取到任何条目时响应 `Null`。
+#[derive(Debug)]
+pub struct XRead {
+    /// 要读取的流及其起始 ID，`None` 表示 `$`
+    streams: Vec<(String, Option<StreamId>)>,
+
+    /// 每个流最多返回的条目数量
+    count: Option<u64>,
+
+    /// 阻塞等待的时长
+    block: Option<Duration>,
+}
+
+impl XRead {
+    /// 创建一个新的 `XRead` 命令，读取 `streams` 中的条目。
+    pub(crate) fn new(
+        streams: &[(String, Option<StreamId>)],
+        count: Option<u64>,
+        block: Option<Duration>,
+    ) -> XRead {
+        XRead {
+            streams: streams.to_vec(),
+            count,
+            block,
+        }
+    }
+
+    /// 获取要读取的流的键
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.streams.iter().map(|(key, _)| &key[..])
+    }
+
+    /// 从接收到的帧中解析一个 `XRead` 实例。
+    ///
+    /// `XREAD` 字符串已经被解析消耗。
+    ///
+    /// # 格式
+    ///
+    /// 期望一个至少包含四个条目的数组帧。键和 ID 的数量必须相同。
+    ///
+    /// ```text
+    /// XREAD [COUNT count] [BLOCK milliseconds] STREAMS key [key ...] id [id ...]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<XRead> {
+        use ParseError::EndOfStream;
+
+        let mut count = None;
+        let mut block = None;
+
+        loop {
+            match &parse.next_string()?.to_uppercase()[..] {
+                "COUNT" => count = Some(parse.next_int()?),
+                "BLOCK" => block = Some(Duration::from_millis(parse.next_int()?)),
+                "STREAMS" => break,
+                option => return Err(format!("unsupported XREAD option '{}'", option).into()),
+            }
+        }
+
+        let mut args = vec![];
+
+        loop {
+            match parse.next_string() {
+                Ok(arg) => args.push(arg),
+                Err(EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        if args.is_empty() || args.len() % 2 != 0 {
+            return Err("ERR Unbalanced 'xread' list of streams: for each stream key an ID or '$' must be specified.".into());
+        }
+
+        let ids = args.split_off(args.len() / 2);
+
+        let streams = args
+            .into_iter()
+            .zip(ids)
+            .map(|(key, id)| match &id[..] {
+                "$" => Ok((key, None)),
+                id => Ok((key, Some(id.parse()?))),
+            })
+            .collect::<crate::Result<_>>()?;
+
+        Ok(XRead {
+            streams,
+            count,
+            block,
+        })
+    }
+
+    /// 将 `XRead` 命令应用到指定的 `Db` 实例。
+    ///
+    /// 响应写入到 `dst`。服务器调用此函数以执行接收到的命令。
+    ///
+    /// 阻塞时在 `Db` 中登记要读取的流，只有条目被添加到这些流时才重新检查。
+    #[instrument(skip(self, db, dst, shutdown))]
+    pub(crate) async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let XRead {
+            streams,
+            count,
+            block,
+        } = self;
+
+        // `$` 只在命令开始时解析一次，之后的检查都基于同样的 ID。
+        let streams = db.batch(|batch| {
+            streams
+                .into_iter()
+                .map(|(key, id)| match id {
+                    Some(id) => Ok((key, id)),
+                    None => batch.stream_last_id(&key).map(|id| (key, id)),
+                })
+                .collect::<Result<Vec<_>, _>>()
+        });
+
+        let response = match streams {
+            Ok(streams) => read_blocking(db, &streams, count, block, shutdown).await,
+            Err(msg) => Frame::error(msg),
+        };
+
+        debug!(?response);
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 将命令转换为等效的 `Frame`。
+    ///
+    /// 客户端在编码一个 `XRead` 命令以发送到服务器时调用此函数。
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("xread".as_bytes()));
+
+        if let Some(count) = self.count {
+            frame.push_bulk(Bytes::from("count".as_bytes()));
+            frame.push_int(count);
+        }
+
+        if let Some(block) = self.block {
+            frame.push_bulk(Bytes::from("block".as_bytes()));
+            frame.push_int(block.as_millis() as u64);
+        }
+
+        frame.push_bulk(Bytes::from("streams".as_bytes()));
+
+        let mut ids = Vec::with_capacity(self.streams.len());
+
+        for (key, id) in self.streams {
+            frame.push_bulk(Bytes::from(key.into_bytes()));
+            ids.push(id.map_or_else(|| "$".to_string(), |id| id.to_string()));
+        }
+
+        for id in ids {
+            frame.push_bulk(Bytes::from(id));
+        }
+
+        frame
+    }
+}
+
+/// 读取 `streams`，没有可读的条目且指定了 `block` 时等待新的条目。
+async fn read_blocking(
+    db: &Db,
+    streams: &[(String, StreamId)],
+    count: Option<u64>,
+    block: Option<Duration>,
+    shutdown: &mut Shutdown,
+) -> Frame {
+    // `BLOCK 0` 表示一直等待。
+    let deadline = block
+        .filter(|block| !block.is_zero())
+        .map(|block| Instant::now() + block);
+
+    // 在检查流之前登记，这样在检查之后、等待之前添加的条目也会唤醒此任务。只有读取的流被写入时才会唤醒。
+    let waiter = db.watch_keys(streams.iter().map(|(key, _)| key.as_str()));
+
+    loop {
+        let response = db.batch(|batch| read(batch, streams, count));
+
+        if !matches!(response, Frame::Null) || block.is_none() {
+            return response;
+        }
+
+        tokio::select! {
+            _ = waiter.wait() => {}
+            _ = sleep_until(deadline) => return Frame::Null,
+            _ = shutdown.recv() => return Frame::Null,
+        }
+    }
+}
+
+/// 读取每个流中 ID 大于给定 ID 的条目。没有读取到任何条目时返回 `Null`。
+fn read(batch: &mut Batch<'_>, streams: &[(String, StreamId)], count: Option<u64>) -> Frame {
+    let mut response = vec![];
+
+    for (key, id) in streams {
+        let entries = match batch.xread(key, *id, count) {
+            Ok(entries) => entries,
+            Err(msg) => return Frame::error(msg),
+        };
+
+        // 与 Redis 相同，没有新条目的流不出现在响应中。
+        if !entries.is_empty() {
+            let entries = entries
+                .into_iter()
+                .map(|entry| entry.into_frame())
+                .collect();
+
+            response.push(Frame::Array(vec![
+                Frame::Bulk(Bytes::from(key.clone().into_bytes())),
+                Frame::Array(entries),
+            ]));
+        }
+    }
+
+    if response.is_empty() {
+        Frame::Null
+    } else {
+        Frame::Array(response)
+    }
+}
+
+/// 休眠直到 `deadline`。`deadline` 为 `None` 时永远不会完成。
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
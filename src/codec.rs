@@ -0,0 +1,93 @@
+//! 实现 `tokio_util::codec` 的 RESP 编解码器。
+//!
+//! `Connection` 在内部使用 `RespCodec` 解析帧，并在此基础上提供自适应的读缓冲区和输出缓冲区上限。
+//! 不需要这些功能的应用程序可以直接将 `RespCodec` 与 `Framed`、`FramedRead` 和 `FramedWrite` 组合，
+//! 在任意 `AsyncRead`/`AsyncWrite` 传输上读写帧。
+
+use crate::frame::{Frame, Limits};
+
+use bytes::BytesMut;
+use std::io;
+use tokio_util::codec::{Decoder, Encoder};
+
+/// Redis 协议（RESP）的编解码器。
+///
+/// 解码时接受 RESP 编码的帧和 inline 命令，与 `Frame::check` 相同。解码出的帧中的字符串和批量数据
+/// 都是读缓冲区的切片，不会复制数据。
+///
+/// # 示例
+///
+/// ```no_run
+/// use mini_redis::codec::RespCodec;
+/// use mini_redis::Frame;
+/// use tokio::net::TcpStream;
+/// use tokio_stream::StreamExt;
+/// use tokio_util::codec::FramedRead;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let socket = TcpStream::connect("127.0.0.1:6379").await.unwrap();
+///     let mut frames = FramedRead::new(socket, RespCodec::new());
+///
+///     while let Some(frame) = frames.next().await {
+///         let frame: Frame = frame.unwrap();
+///         println!("{}", frame);
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RespCodec {
+    /// 解码的帧的大小限制。
+    limits: Limits,
+}
+
+impl RespCodec {
+    /// 使用默认的 `Limits` 创建编解码器。
+    pub fn new() -> RespCodec {
+        RespCodec::default()
+    }
+
+    /// 使用 `limits` 限制解码的帧的大小。超过限制的帧会导致 `decode` 返回协议错误。
+    pub fn with_limits(limits: Limits) -> RespCodec {
+        RespCodec { limits }
+    }
+
+    /// 返回解码的帧的大小限制。
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// 设置解码的帧的大小限制。
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+}
+
+impl Decoder for RespCodec {
+    type Item = Frame;
+    type Error = crate::Error;
+
+    /// 尝试从 `src` 中解码一个帧。如果 `src` 包含完整的帧，则返回帧并从 `src` 中移除对应的数据；
+    /// 如果数据不足，则返回 `Ok(None)`；如果数据不是有效的帧，则返回 `frame::Error`。
+    fn decode(&mut self, src: &mut BytesMut) -> crate::Result<Option<Frame>> {
+        // 验证和解析在一次扫描中完成，详见 `Frame::decode`。
+        Ok(Frame::decode(src, &self.limits)?)
+    }
+}
+
+impl Encoder<Frame> for RespCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, frame: Frame, dst: &mut BytesMut) -> io::Result<()> {
+        self.encode(&frame, dst)
+    }
+}
+
+impl Encoder<&Frame> for RespCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, frame: &Frame, dst: &mut BytesMut) -> io::Result<()> {
+        frame.encode(dst);
+        Ok(())
+    }
+}
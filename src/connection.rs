@@ -1,9 +1,14 @@
-use crate::frame::{self, Frame};
+use crate::codec::RespCodec;
+use crate::frame::{self, Frame, Limits};
 
 use bytes::{Buf, BytesMut};
-use std::io::{self, Cursor};
-use tokio::io::{AsyncReadExt, AsyncWriteExt, BufWriter};
+use std::io;
+use std::mem;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
+use tokio_util::codec::Decoder;
 
 /// 从远程对等方发送和接收 `Frame` 值。
 ///
@@ -14,28 +19,282 @@ use tokio::net::TcpStream;
 /// 一旦发生这种情况，`Connection` 将创建帧并将其返回给调用者。
 ///
 /// 在发送帧时，帧首先被编码到写缓冲区中。写缓冲区的内容然后被写入套接字。
+/// 通过 `queue_frame` 发送的帧不等待写入完成，尚未写入套接字的数据保留在写缓冲区中，
+/// 在之后的写入或者 `read_frame` 等待数据期间继续发送。
 #[derive(Debug)]
 pub struct Connection {
-    // `TcpStream`。它装饰有 `BufWriter`，提供写级别的缓冲。
-    // Tokio 提供的 `BufWriter` 实现足以满足我们的需求。
-    stream: BufWriter<TcpStream>,
+    // `TcpStream`。写入时的缓冲由 `write_buf` 提供。
+    stream: TcpStream,
+
+    // 用于编码待写入的帧的缓冲区。每个帧在写入套接字之前都被完整地编码到这里。
+    // 缓冲区中的数据就是尚未写入套接字的数据。
+    write_buf: BytesMut,
+
+    // 尚未写入套接字的数据的上限。`None` 表示不限制。
+    output_limit: Option<usize>,
 
     // 用于读取帧的缓冲区。
     buffer: BytesMut,
+
+    // 读缓冲区中尚未组成完整帧的数据的上限。`None` 表示不限制。
+    read_buffer_limit: Option<usize>,
+
+    // 每次从套接字读取时，读缓冲区至少保留的空闲容量。它会根据接收到的帧的大小自适应地调整。
+    read_capacity: usize,
+
+    // `read_capacity` 的初始值，也是自适应收缩的下限。
+    min_read_capacity: usize,
+
+    // 连续大于 `read_capacity` 的帧的数量。
+    large_frames: usize,
+
+    // 连续远小于 `read_capacity` 的帧的数量。
+    small_frames: usize,
+
+    // 读缓冲区调整大小的统计信息。
+    stats: BufferStats,
+
+    // 解码接收的帧，并限制帧的大小。
+    codec: RespCodec,
+
+    // 通过 `write_frame` 或者 `queue_frame` 写入的错误帧的数量。
+    error_replies: u64,
+
+    // 连接关闭时归还读写缓冲区的缓冲池。
+    pool: Option<BufferPool>,
+}
+
+/// `Connection` 读缓冲区调整大小的统计信息。
+///
+/// 可以用来观察自适应缓冲区在具体工作负载下的行为，例如用于基准测试。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BufferStats {
+    /// 读缓冲区增长的次数。
+    pub grows: u64,
+
+    /// 读缓冲区收缩的次数。
+    pub shrinks: u64,
+
+    /// 当前每次读取时保留的空闲容量。
+    pub read_capacity: usize,
+
+    /// 处理完一个超大的帧后，读缓冲区被重新分配以释放内存的次数。
+    pub resets: u64,
+}
+
+/// 在连接之间复用的读写缓冲区。
+///
+/// 连接频繁地建立和关闭时，每个连接都要分配一对新的缓冲区。通过 `Connection::new_with_pool`
+/// 创建的连接从缓冲池中取出缓冲区，关闭时将它们归还，供之后的连接复用。克隆 `BufferPool`
+/// 得到的是同一个缓冲池的句柄。
+///
+/// 容量在使用过程中增长得过大的缓冲区不会被归还，以免缓冲池长期占用峰值时的内存。
+#[derive(Debug, Clone)]
+pub struct BufferPool {
+    shared: Arc<PoolShared>,
 }
 
+#[derive(Debug)]
+struct PoolShared {
+    // 空闲的缓冲区
+    buffers: Mutex<Vec<BytesMut>>,
+
+    // 最多保留的空闲缓冲区数量
+    max_idle: usize,
+
+    // 因为缓冲池为空而新分配的缓冲区数量
+    allocated: AtomicU64,
+
+    // 从缓冲池中复用的缓冲区数量
+    reused: AtomicU64,
+}
+
+/// `BufferPool` 的统计信息。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BufferPoolStats {
+    /// 因为缓冲池为空而新分配的缓冲区数量。
+    pub allocated: u64,
+
+    /// 从缓冲池中复用的缓冲区数量。
+    pub reused: u64,
+
+    /// 当前空闲的缓冲区数量。
+    pub idle: usize,
+}
+
+impl BufferPool {
+    /// 创建一个最多保留 `max_idle` 个空闲缓冲区的缓冲池。
+    pub fn new(max_idle: usize) -> BufferPool {
+        BufferPool {
+            shared: Arc::new(PoolShared {
+                buffers: Mutex::new(Vec::new()),
+                max_idle,
+                allocated: AtomicU64::new(0),
+                reused: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// 返回缓冲池的统计信息。
+    pub fn stats(&self) -> BufferPoolStats {
+        BufferPoolStats {
+            allocated: self.shared.allocated.load(Ordering::Relaxed),
+            reused: self.shared.reused.load(Ordering::Relaxed),
+            idle: self.shared.buffers.lock().unwrap().len(),
+        }
+    }
+
+    /// 取出一个空的缓冲区。缓冲池为空时分配一个新的缓冲区。
+    fn get(&self) -> BytesMut {
+        match self.shared.buffers.lock().unwrap().pop() {
+            Some(buffer) => {
+                self.shared.reused.fetch_add(1, Ordering::Relaxed);
+                buffer
+            }
+            None => {
+                self.shared.allocated.fetch_add(1, Ordering::Relaxed);
+                BytesMut::with_capacity(POOLED_CAPACITY)
+            }
+        }
+    }
+
+    /// 归还一个缓冲区。容量不合适或者缓冲池已满时，缓冲区被直接释放。
+    fn put(&self, mut buffer: BytesMut) {
+        buffer.clear();
+
+        if !(POOLED_CAPACITY..=MAX_READ_CAPACITY).contains(&buffer.capacity()) {
+            return;
+        }
+
+        let mut buffers = self.shared.buffers.lock().unwrap();
+
+        if buffers.len() < self.shared.max_idle {
+            buffers.push(buffer);
+        }
+    }
+}
+
+/// 读缓冲区的初始容量。对于 mini redis 的用例来说，4KB 足够了。
+const INITIAL_READ_CAPACITY: usize = 4 * 1024;
+
+/// 读缓冲区自适应增长的上限。读缓冲区的实际容量超过该值时（例如接收了一个超大的帧），
+/// 会在处理完该帧后被重新分配。
+const MAX_READ_CAPACITY: usize = 256 * 1024;
+
+/// 连续多少个帧超过当前容量后，读缓冲区的容量翻倍。
+const GROW_AFTER: usize = 2;
+
+/// 连续多少个帧小于当前容量的四分之一后，读缓冲区的容量减半。
+const SHRINK_AFTER: usize = 64;
+
+/// 写缓冲区的初始容量。
+const INITIAL_WRITE_CAPACITY: usize = 4 * 1024;
+
+/// 缓冲池中的缓冲区的最小容量，同时用作读缓冲区和写缓冲区。
+const POOLED_CAPACITY: usize = if INITIAL_READ_CAPACITY > INITIAL_WRITE_CAPACITY {
+    INITIAL_READ_CAPACITY
+} else {
+    INITIAL_WRITE_CAPACITY
+};
+
+/// 写入一个帧之后，写缓冲区最多保留的容量。超过该容量时，缓冲区会被一个新的小缓冲区替换。
+const MAX_RETAINED_WRITE_CAPACITY: usize = 64 * 1024;
+
 impl Connection {
     /// 创建一个新的 `Connection`，由 `socket` 支持。读写缓冲区被初始化。
+    ///
+    /// 默认使用4KB的读缓冲区。对于大多数请求来说，这足够了。
     pub fn new(socket: TcpStream) -> Connection {
+        Connection::new_with_capacity(socket, INITIAL_READ_CAPACITY)
+    }
+
+    /// 创建一个新的 `Connection`，读缓冲区的初始容量为 `capacity` 字节。
+    ///
+    /// 当连接上的帧经常超过该容量时，缓冲区会自适应地增长（见 `adapt_read_capacity`），
+    /// 而不是依赖 `read_buf` 每次只扩充少量字节的隐式增长；之后只处理小帧时，会收缩回 `capacity`。
+    pub fn new_with_capacity(socket: TcpStream, capacity: usize) -> Connection {
+        let buffer = BytesMut::with_capacity(capacity);
+        let write_buf = BytesMut::with_capacity(INITIAL_WRITE_CAPACITY);
+
+        Connection::from_parts(socket, capacity, buffer, write_buf, None)
+    }
+
+    /// 创建一个新的 `Connection`，读写缓冲区从 `pool` 中取出，连接被丢弃时归还。
+    ///
+    /// 读缓冲区的初始容量与 `Connection::new` 相同。
+    pub fn new_with_pool(socket: TcpStream, pool: &BufferPool) -> Connection {
+        let buffer = pool.get();
+        let write_buf = pool.get();
+
+        Connection::from_parts(
+            socket,
+            INITIAL_READ_CAPACITY,
+            buffer,
+            write_buf,
+            Some(pool.clone()),
+        )
+    }
+
+    fn from_parts(
+        socket: TcpStream,
+        capacity: usize,
+        buffer: BytesMut,
+        write_buf: BytesMut,
+        pool: Option<BufferPool>,
+    ) -> Connection {
         Connection {
-            stream: BufWriter::new(socket),
-            // 默认使用4KB的读缓冲区。对于 mini redis 的用例来说，这足够了。
-            // 然而，实际应用程序将希望根据其具体用例调整此值。
-            // 更大的读缓冲区可能表现更好。
-            buffer: BytesMut::with_capacity(4 * 1024),
+            stream: socket,
+            write_buf,
+            output_limit: None,
+            buffer,
+            read_buffer_limit: None,
+            read_capacity: capacity,
+            min_read_capacity: capacity,
+            large_frames: 0,
+            small_frames: 0,
+            stats: BufferStats {
+                read_capacity: capacity,
+                ..BufferStats::default()
+            },
+            codec: RespCodec::new(),
+            error_replies: 0,
+            pool,
         }
     }
 
+    /// 设置接收的帧的大小限制。超过限制的帧会导致 `read_frame` 返回协议错误。
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.codec.set_limits(limits);
+    }
+
+    /// 设置尚未写入套接字的数据的上限（字节），默认不限制。
+    ///
+    /// 对等方读取得太慢时，通过 `queue_frame` 发送的数据会在写缓冲区中积压。加入一个帧会使积压的数据超过上限时，
+    /// `queue_frame` 返回错误，连接应当被关闭，类似 Redis 的 `client-output-buffer-limit`。
+    /// `write_frame` 等待帧写入完成，积压的数据不会超过一个帧，因此不受此限制，很大的响应仍然可以发送。
+    pub fn set_output_limit(&mut self, limit: Option<usize>) {
+        self.output_limit = limit;
+    }
+
+    /// 设置读缓冲区中尚未组成完整帧的数据的上限（字节），默认不限制。
+    ///
+    /// `set_limits` 分别限制单个批量字符串的长度和数组的元素数量，一个帧的总大小仍然可能远远超过它们。
+    /// 对等方发送的帧在缓冲了这么多数据之后仍不完整时，`read_frame` 返回协议错误，连接应当被关闭，
+    /// 而不是让读缓冲区无限制地增长，类似 Redis 的 `client-query-buffer-limit`。
+    pub fn set_read_buffer_limit(&mut self, limit: Option<usize>) {
+        self.read_buffer_limit = limit;
+    }
+
+    /// 返回尚未写入套接字的字节数。
+    pub fn pending_bytes(&self) -> usize {
+        self.write_buf.len()
+    }
+
+    /// 返回读缓冲区调整大小的统计信息。
+    pub fn buffer_stats(&self) -> BufferStats {
+        self.stats
+    }
+
     /// 从底层流中读取一个 `Frame` 值。
     ///
     /// 该函数等待直到检索到足够的数据以解析一个帧。
@@ -52,10 +311,17 @@ impl Connection {
                 return Ok(Some(frame));
             }
 
+            self.check_read_buffer_limit()?;
+
             // 没有足够的缓冲数据来读取帧。尝试从 socket 中读取更多数据。
             //
+            // 在读取之前确保缓冲区至少有 `read_capacity` 的空闲容量，使得一次系统调用可以读取尽可能多的数据。
+            if self.buffer.capacity() - self.buffer.len() < self.read_capacity {
+                self.buffer.reserve(self.read_capacity);
+            }
+
             // 成功时，返回字节数。`0` 表示“流结束”。
-            if 0 == self.stream.read_buf(&mut self.buffer).await? {
+            if 0 == self.read_or_flush().await? {
                 // 远程关闭了连接。若是正常关闭，读缓冲区中不应有数据。
                 // 如果有，这表明对等方在发送帧时关闭了套接字。
                 if self.buffer.is_empty() {
@@ -67,135 +333,252 @@ impl Connection {
         }
     }
 
+    /// 不等待地读取一个 `Frame` 值。
+    ///
+    /// 只使用读缓冲区中已有的数据和套接字中已经到达的数据。没有完整的帧时返回 `Ok(None)`，
+    /// 已读取的部分数据保留到下一次读取。与 `read_frame` 不同，`None` 并不表示连接已关闭。
+    pub fn try_read_frame(&mut self) -> crate::Result<Option<Frame>> {
+        loop {
+            if let Some(frame) = self.parse_frame()? {
+                return Ok(Some(frame));
+            }
+
+            self.check_read_buffer_limit()?;
+
+            if self.buffer.capacity() - self.buffer.len() < self.read_capacity {
+                self.buffer.reserve(self.read_capacity);
+            }
+
+            match self.stream.try_read_buf(&mut self.buffer) {
+                // 连接已关闭，交给下一次 `read_frame` 报告。
+                Ok(0) => return Ok(None),
+                Ok(_) => {}
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(None),
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    /// 从套接字读取数据到读缓冲区，返回读取的字节数。
+    ///
+    /// 等待数据期间，继续将写缓冲区中积压的数据写入套接字。
+    async fn read_or_flush(&mut self) -> io::Result<usize> {
+        loop {
+            if self.write_buf.is_empty() {
+                return self.stream.read_buf(&mut self.buffer).await;
+            }
+
+            tokio::select! {
+                res = self.stream.readable() => {
+                    res?;
+
+                    match self.stream.try_read_buf(&mut self.buffer) {
+                        Ok(n) => return Ok(n),
+                        Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
+                        Err(err) => return Err(err),
+                    }
+                }
+                res = self.stream.writable() => {
+                    res?;
+                    self.try_flush()?;
+                }
+            }
+        }
+    }
+
     /// 尝试从缓冲区中解析一个帧。如果缓冲区包含足够的数据，则返回帧并从缓冲区中移除数据。
     /// 如果缓冲区中的数据不足，则返回 `Ok(None)`。如果缓冲的数据不是有效的帧，则返回 `Err`。
     fn parse_frame(&mut self) -> crate::Result<Option<Frame>> {
-        use frame::Error::Incomplete;
-
-        // 光标用于跟踪缓冲区中的“当前位置”。光标还实现了 `bytes` crate 中的 `Buf` 接口，
-        // 该接口提供了处理字节的许多有用工具。
-        let mut buf = Cursor::new(&self.buffer[..]);
-
-        // 第一步是检查缓冲的数据是否足够解析一个完整的帧。
-        // 这一步通常比完整解析帧要快得多，并允许我们在知道完整帧已收到之前，跳过分配用于保存帧数据的数据结构。
-        match Frame::check(&mut buf) {
-            Ok(_) => {
-                // `check` 函数会将光标推进到帧的末尾。
-                // 由于在调用 `Frame::check` 之前光标的位置设置为零，我们通过检查光标位置来获得帧的长度。
-                let len = buf.position() as usize;
-
-                // 在传递光标给 `Frame::parse` 之前将位置重置为零。
-                buf.set_position(0);
-
-                // 从缓冲区解析帧。这会分配必要的结构来表示帧并返回帧值。
-                //
-                // 如果编码的帧表示无效，则返回错误。
-                // 这应该终止**当前**连接，但不应影响任何其他连接的客户端。
-                let frame = Frame::parse(&mut buf)?;
-
-                // 从读取缓冲区中丢弃已解析的数据。
-                //
-                // 当在读取缓冲区上调用 `advance` 时，所有的数据直到 `len` 都会被丢弃。
-                // 这部分细节如何实现留给 `BytesMut`。
-                // 这通常通过移动内部光标来完成，但也可能通过重新分配和复制数据来完成。
-                self.buffer.advance(len);
-
-                // 返回解析的帧给调用者。
-                Ok(Some(frame))
+        let buffered = self.buffer.len();
+
+        // 解码由 `RespCodec` 完成。如果编码的帧表示无效，则返回错误。
+        // 这应该终止**当前**连接，但不应影响任何其他连接的客户端。
+        let frame = self.codec.decode(&mut self.buffer)?;
+
+        if frame.is_some() {
+            // 根据刚刚解析的帧的大小调整读缓冲区的容量。
+            self.adapt_read_capacity(buffered - self.buffer.len());
+        }
+
+        Ok(frame)
+    }
+
+    /// 根据刚刚解析的帧的长度 `len` 自适应地调整读缓冲区的容量。
+    ///
+    /// 当帧经常超过当前容量时，容量翻倍（最多到 `MAX_READ_CAPACITY`），
+    /// 以减少读取一个大帧所需的系统调用次数。当连接长时间只处理小帧时，容量减半（最少到初始容量），
+    /// 并在缓冲区为空时释放多余的内存，避免长连接一直保留峰值时的内存。
+    ///
+    /// 接收一个超大的帧会使缓冲区的实际容量远远超过 `read_capacity`。这种情况下不必等待后续的小帧，
+    /// 处理完该帧后立即重新分配缓冲区。
+    fn adapt_read_capacity(&mut self, len: usize) {
+        let max_read_capacity = MAX_READ_CAPACITY.max(self.min_read_capacity);
+
+        if self.buffer.capacity() > max_read_capacity && self.buffer.len() <= self.read_capacity {
+            // 缓冲区中剩余的数据（如果有）很少，复制它们的开销可以忽略。
+            let mut buffer = BytesMut::with_capacity(self.read_capacity);
+            buffer.extend_from_slice(&self.buffer);
+            self.buffer = buffer;
+            self.stats.resets += 1;
+        }
+
+        if len > self.read_capacity {
+            self.small_frames = 0;
+            self.large_frames += 1;
+
+            if self.large_frames >= GROW_AFTER && self.read_capacity < max_read_capacity {
+                self.large_frames = 0;
+                self.read_capacity = (self.read_capacity * 2).min(max_read_capacity);
+                self.stats.grows += 1;
             }
-            // 读取缓冲区中没有足够的数据来解析单个帧。
-            // 我们必须等待从套接字接收到更多的数据。
-            // 从套接字中读取将在此 `match` 语句后的语句中进行。
-            //
-            // 我们不希望从这里返回 `Err`，因为这个“错误”是一个预期的运行时条件。
-            Err(Incomplete) => Ok(None),
-            // 解析帧时遇到错误。
-            // 连接现在处于无效状态。从这里返回 `Err` 将导致连接被关闭。
-            Err(e) => Err(e.into()),
+        } else if len < self.read_capacity / 4 {
+            self.large_frames = 0;
+            self.small_frames += 1;
+
+            if self.small_frames >= SHRINK_AFTER && self.read_capacity > self.min_read_capacity {
+                self.small_frames = 0;
+                self.read_capacity = (self.read_capacity / 2).max(self.min_read_capacity);
+                self.stats.shrinks += 1;
+
+                // `BytesMut` 不会自动释放多余的容量。当缓冲区中没有未处理的数据时，
+                // 用一个较小的新缓冲区替换它。
+                if self.buffer.is_empty() {
+                    self.buffer = BytesMut::with_capacity(self.read_capacity);
+                }
+            }
+        } else {
+            self.large_frames = 0;
+            self.small_frames = 0;
         }
+
+        self.stats.read_capacity = self.read_capacity;
     }
 
     /// 将单个 `Frame` 值写入底层流。
     ///
-    /// 使用 `AsyncWrite` 提供的各种 `write_*` 函数将 `Frame` 值写入套接字。
-    /// 不建议直接在 `TcpStream` 上调用这些函数，因为这会导致大量的系统调用。
-    /// 但是，在*缓冲*写流上调用这些函数是可以的。数据将被写入缓冲区。
-    /// 一旦缓冲区满了，它将被刷新到底层套接字。
+    /// 整个帧首先被编码到写缓冲区中，然后通过一次 `write_all` 写入套接字。
+    /// 与逐个字段地写入相比，这避免了大量的小写入，通常只需要一次系统调用。
+    ///
+    /// 写缓冲区中积压的数据会先被写入，因此帧的顺序保持不变。
+    ///
+    /// # 取消安全
+    ///
+    /// 此方法是取消安全的：如果返回的 future 在写入完成之前被丢弃（例如在 `select!` 中），
+    /// 帧已经完整地编码到写缓冲区中，尚未写入的部分保留在写缓冲区，由之后的写入或者 `read_frame` 继续发送。
     pub async fn write_frame(&mut self, frame: &Frame) -> io::Result<()> {
-        // 将数组通过编码每个条目进行编码。其他所有的帧类型都被视为文字。
-        // 目前，mini-Redis 不能编码递归的帧结构。详情参见下文。
-        match frame {
-            Frame::Array(val) => {
-                // 编码帧类型前缀。对于数组，它是 `*`。
-                self.stream.write_u8(b'*').await?;
-
-                // 编码数组的长度。
-                self.write_decimal(val.len() as u64).await?;
-
-                // 迭代并编码数组中的每个条目。
-                for entry in &**val {
-                    self.write_value(entry).await?;
-                }
-            }
-            // 帧类型是一个文字。直接编码该值。
-            _ => self.write_value(frame).await?,
+        if let Frame::Error(_) = frame {
+            self.error_replies += 1;
         }
 
-        // 确保编码后的帧被写入套接字。上述调用是对缓冲流和写入的。
-        // 调用 `flush` 将缓冲区的剩余内容写入套接字。
-        self.stream.flush().await
+        frame.encode(&mut self.write_buf);
+        self.flush_write_buf().await
     }
 
-    /// 将一个帧字面量写入流中。
-    async fn write_value(&mut self, frame: &Frame) -> io::Result<()> {
-        match frame {
-            Frame::Simple(val) => {
-                self.stream.write_u8(b'+').await?;
-                self.stream.write_all(val.as_bytes()).await?;
-                self.stream.write_all(b"\r\n").await?;
-            }
-            Frame::Error(val) => {
-                self.stream.write_u8(b'-').await?;
-                self.stream.write_all(val.as_bytes()).await?;
-                self.stream.write_all(b"\r\n").await?;
-            }
-            Frame::Integer(val) => {
-                self.stream.write_u8(b':').await?;
-                self.write_decimal(*val).await?;
-            }
-            Frame::Null => {
-                self.stream.write_all(b"$-1\r\n").await?;
-            }
-            Frame::Bulk(val) => {
-                let len = val.len();
+    /// 将已经编码的数据原样写入套接字。主节点据此向副本发送积压缓冲区中的写命令，不需要逐个解码再编码。
+    pub(crate) async fn write_raw(&mut self, data: &[u8]) -> io::Result<()> {
+        self.write_buf.extend_from_slice(data);
+        self.flush_write_buf().await
+    }
 
-                self.stream.write_u8(b'$').await?;
-                self.write_decimal(len as u64).await?;
-                self.stream.write_all(val).await?;
-                self.stream.write_all(b"\r\n").await?;
+    /// 将写缓冲区中的数据全部写入套接字。
+    ///
+    /// 与 `write_all` 不同，每次写入之后立即从写缓冲区中移除已写入的数据，
+    /// 因此 future 被丢弃时，尚未写入的数据仍然保留在写缓冲区中，不会丢失，也不会重复发送。
+    async fn flush_write_buf(&mut self) -> io::Result<()> {
+        while !self.write_buf.is_empty() {
+            match self.stream.write(&self.write_buf).await? {
+                0 => return Err(io::ErrorKind::WriteZero.into()),
+                n => self.write_buf.advance(n),
             }
-            // 从值中编码一个 `Array` 不能使用递归策略。
-            // 通常，异步函数不支持递归。
-            // Mini-redis 尚不需要编码嵌套的数组，因此目前跳过。
-            Frame::Array(_val) => unreachable!(),
         }
 
+        self.reclaim_write_buf();
+
         Ok(())
     }
 
-    /// 将一个十进制帧写入流中。
-    async fn write_decimal(&mut self, val: u64) -> io::Result<()> {
-        use std::io::Write;
+    /// 返回通过 `write_frame` 或者 `queue_frame` 写入的错误帧的数量。服务器据此判断命令是否以错误响应。
+    pub(crate) fn error_replies(&self) -> u64 {
+        self.error_replies
+    }
+
+    /// 将 `frame` 加入写缓冲区，并在不等待的情况下尽可能多地写入套接字。
+    ///
+    /// 未能立即写入的数据保留在写缓冲区中，由之后的写入或者 `read_frame` 继续发送。
+    /// 适用于发布/订阅消息这类不应等待慢速对等方的场景。积压的数据超过 `set_output_limit`
+    /// 设置的上限时返回错误。
+    pub fn queue_frame(&mut self, frame: &Frame) -> io::Result<()> {
+        if let Frame::Error(_) = frame {
+            self.error_replies += 1;
+        }
+
+        frame.encode(&mut self.write_buf);
+        self.check_output_limit()?;
+        self.try_flush()
+    }
 
-        // Convert the value to a string
-        let mut buf = [0u8; 20];
-        let mut buf = Cursor::new(&mut buf[..]);
-        write!(&mut buf, "{}", val)?;
+    /// 在不等待的情况下，尽可能多地将写缓冲区中的数据写入套接字。
+    fn try_flush(&mut self) -> io::Result<()> {
+        while !self.write_buf.is_empty() {
+            match self.stream.try_write(&self.write_buf) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => self.write_buf.advance(n),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(err) => return Err(err),
+            }
+        }
 
-        let pos = buf.position() as usize;
-        self.stream.write_all(&buf.get_ref()[..pos]).await?;
-        self.stream.write_all(b"\r\n").await?;
+        self.reclaim_write_buf();
 
         Ok(())
     }
+
+    /// 检查读缓冲区中不完整的帧是否已经达到上限。返回的 `frame::Error` 使服务器在关闭连接之前发送协议错误。
+    fn check_read_buffer_limit(&self) -> crate::Result<()> {
+        match self.read_buffer_limit {
+            Some(limit) if self.buffer.len() >= limit => Err(frame::Error::from(format!(
+                "protocol error; incomplete frame exceeds the read buffer limit of {} bytes",
+                limit
+            ))
+            .into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// 检查积压的数据是否超过上限。
+    fn check_output_limit(&self) -> io::Result<()> {
+        match self.output_limit {
+            Some(limit) if self.write_buf.len() > limit => Err(io::Error::other(format!(
+                "client output buffer limit exceeded: {} bytes pending, limit is {} bytes",
+                self.write_buf.len(),
+                limit
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// 写缓冲区为空时，不要一直保留编码很大的帧时达到的峰值内存。
+    ///
+    /// `advance` 会减小 `BytesMut` 的可用容量，因此写缓冲区被清空后，通过 `reserve` 重新利用已写入部分的空间，
+    /// 这不会重新分配内存。
+    fn reclaim_write_buf(&mut self) {
+        if !self.write_buf.is_empty() {
+            return;
+        }
+
+        if self.write_buf.capacity() > MAX_RETAINED_WRITE_CAPACITY {
+            self.write_buf = BytesMut::with_capacity(INITIAL_WRITE_CAPACITY);
+        } else {
+            self.write_buf.reserve(INITIAL_WRITE_CAPACITY);
+        }
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        if let Some(pool) = &self.pool {
+            pool.put(mem::take(&mut self.buffer));
+            pool.put(mem::take(&mut self.write_buf));
+        }
+    }
 }
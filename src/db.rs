@@ -1,11 +1,89 @@
-use tokio::sync::{broadcast, Notify};
-use tokio::time::{self, Duration, Instant};
+use crate::clock::Clock;
+use crate::cmd::EvalBatch;
+use crate::hyperloglog::{self, HyperLogLog};
+use crate::stream::{Stream, StreamEntry, StreamId};
+use crate::{sync, Frame};
 
-use bytes::Bytes;
-use std::collections::{BTreeSet, HashMap};
-use std::sync::{Arc, Mutex};
+use tokio::sync::futures::Notified;
+use tokio::sync::{broadcast, watch, Notify};
+use tokio::time::{Duration, Instant};
+
+use bytes::{Bytes, BytesMut};
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::ops::{Deref, DerefMut, RangeInclusive};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
 use tracing::debug;
 
+mod aof;
+use aof::Aof;
+pub use aof::AppendFsync;
+pub(crate) use aof::{AofStats, AofWrite};
+
+mod backlog;
+use backlog::Backlog;
+pub(crate) use backlog::{BacklogRead, DEFAULT_BACKLOG_SIZE};
+
+mod blocked;
+use blocked::BlockedKeys;
+pub(crate) use blocked::KeyWaiter;
+
+mod channels;
+use channels::Channels;
+pub(crate) use channels::Published;
+
+mod observer;
+pub use observer::DbObserver;
+use observer::{KeyEvent, Observer};
+
+mod latency;
+pub(crate) use latency::{LatencyEvent, LatencySample, COMMAND_EVENT};
+use latency::{LatencyMonitor, EXPIRE_CYCLE_EVENT};
+
+pub mod snapshot;
+use snapshot::{Snapshot, SnapshotError, Snapshots, Timestamps};
+pub(crate) use snapshot::{SnapshotStats, DEFAULT_SNAPSHOT_PATH};
+
+/// 字符串值的最大长度（512MB），与 Redis 的默认限制相同。
+const MAX_STRING_LEN: usize = 512 * 1024 * 1024;
+
+/// 后台任务每次持有锁时最多清除的过期键数量。
+///
+/// 大量键同时过期时，后台任务每清除这么多键就释放锁并让出执行权，然后继续清除，
+/// 其他连接的命令不会因为一次清除而长时间等待锁。
+const PURGE_BATCH_SIZE: usize = 256;
+
+/// TTL 直方图各个桶的上限（不包含）。最后一个桶记录不短于一天的 TTL。
+const TTL_BUCKET_LIMITS: [Duration; 4] = [
+    Duration::from_secs(1),
+    Duration::from_secs(60),
+    Duration::from_secs(60 * 60),
+    Duration::from_secs(24 * 60 * 60),
+];
+
+/// TTL 直方图各个桶在 `INFO keyspace` 中的字段名，与 `ExpirationStats::ttl_histogram` 一一对应。
+pub(crate) const TTL_BUCKET_NAMES: [&str; TTL_BUCKET_LIMITS.len() + 1] = [
+    "ttl_lt_1s",
+    "ttl_lt_1m",
+    "ttl_lt_1h",
+    "ttl_lt_1d",
+    "ttl_ge_1d",
+];
+
+/// 脚本默认最多执行的 Lua 指令数量，大约相当于零点几秒的执行时间。参见 `Db::set_script_instruction_limit`。
+#[cfg(feature = "scripting")]
+pub(crate) const DEFAULT_SCRIPT_INSTRUCTION_LIMIT: u64 = 100_000_000;
+
+/// 脚本缓存最多保存的脚本数量，与 Redis 对 `EVAL` 缓存的脚本的限制相同。超出时最早缓存的脚本被移除。
+#[cfg(feature = "scripting")]
+const MAX_CACHED_SCRIPTS: usize = 500;
+
+/// 对键执行与其值的类型不符的操作时返回的错误。
+const WRONGTYPE: &str = "WRONGTYPE Operation against a key holding the wrong kind of value";
+
 /// `Db` 实例的包装器。此结构存在的目的是在该结构被丢弃时，通过通知后台清理任务关闭 `Db`，从而允许有序清理。
 #[derive(Debug)]
 pub(crate) struct DbDropGuard {
@@ -19,11 +97,17 @@ pub(crate) struct DbDropGuard {
 /// 一个 `Db` 实例是共享状态的句柄。克隆 `Db` 是浅拷贝，只会增加一个原子引用计数。
 /// 当创建 `Db` 值时，会生成一个后台任务。此任务用于在请求的持续时间过去后过期值。
 /// 该任务运行直到所有 `Db` 实例被丢弃，届时任务终止。
+///
+/// 嵌入服务器的应用程序可以通过 `server::Handle::db` 获取服务器使用的 `Db`，用 `get`、`set` 和 `publish`
+/// 读写与网络客户端相同的数据，并通过 `keys`、`len` 和 `for_each` 遍历键空间，这些方法都不会在调用者的代码运行期间持有锁。
 #[derive(Debug, Clone)]
-pub(crate) struct Db {
+pub struct Db {
     /// 用于共享状态的句柄。后台任务也将具有一个
     /// `Arc<Shared>`.
-    shared: Arc<Shared>,
+    shared: sync::Arc<Shared>,
+
+    /// 持有该句柄的连接的 ID，通过该句柄发布的消息记录这个 ID。不属于任何连接的句柄为 `None`。
+    client_id: Option<u64>,
 }
 
 #[derive(Debug)]
@@ -34,10 +118,187 @@ struct Shared {
     /// Tokio 互斥锁主要用于需要跨 `.await` 让步点持有的锁。所有其他情况通常最好使用 std 互斥锁。
     /// 如果临界区不包含任何异步操作但很长（CPU 密集型或执行阻塞操作），
     /// 则整个操作包括等待互斥锁都被视为“阻塞”操作，应使用 `tokio::task::spawn_blocking`。
-    state: Mutex<State>,
+    state: sync::Mutex<State>,
 
     /// 通知处理条目过期的后台任务。后台任务等待此通知，然后检查过期的值或关闭信号。
-    background_task: Notify,
+    background_task: sync::Notify,
+
+    /// 发布/订阅频道。与 `state` 分开加锁，发布/订阅的流量不会与键值读写争用同一个锁。
+    channels: Channels,
+
+    /// 阻塞的 `BLPOP` 和 `XREAD` 按键登记的通知。元素被推入列表或者条目被添加到流时，只唤醒等待该键的命令。
+    blocked: BlockedKeys,
+
+    /// 过期键的统计数据。`State` 持有同一个实例，在持有锁时更新。
+    expiration_stats: Arc<ExpirationCounters>,
+
+    /// 延迟监控记录的事件。
+    latency: LatencyMonitor,
+
+    /// 快照文件的路径和 `BGSAVE` 的状态。写入快照的线程持有它的引用，而不是整个 `Shared`。
+    snapshots: Arc<Snapshots>,
+
+    /// AOF 写入任务的句柄。只在服务器启动时设置一次，未开启 AOF 时为空。
+    aof: OnceLock<Aof>,
+
+    /// 每当有写命令被追加到复制积压缓冲区时通知所有等待者。向副本发送写命令的连接等待此通知。
+    backlog_appended: Notify,
+
+    /// 复制的统计数据。
+    replication_stats: ReplicationCounters,
+
+    /// 每个副本以 `REPLCONF ACK` 确认处理到的复制偏移量，以向副本发送写命令的连接的 ID 为键。
+    replica_acks: std::sync::Mutex<HashMap<u64, u64>>,
+
+    /// 每当有副本确认偏移量时通知所有等待者。
+    replica_acked: Notify,
+
+    /// 作为副本时与主节点的连接，`None` 表示本节点是主节点。
+    replica_link: std::sync::Mutex<Option<crate::replication::Link>>,
+
+    /// 本节点是否是副本，与 `replica_link` 一起更新。连接处理程序据此拒绝写命令，不需要获取锁。
+    is_replica: AtomicBool,
+
+    /// 下一个连接的 ID。与 Redis 一样从 1 开始递增，同一个服务器上的 ID 不会重复。
+    next_client_id: AtomicU64,
+
+    /// 键空间变化的观察者，与 `State::observer` 相同。释放 `state` 的锁之后通过它交付记录的事件。
+    observer: Option<Arc<Observer>>,
+}
+
+/// `State` 的锁守护体，通过 `Shared::lock` 获取。
+///
+/// 被丢弃时先释放锁，再将临界区中记录的事件交付给 `DbObserver`。
+struct StateGuard<'a> {
+    /// 总是 `Some`，只在 `drop` 中被取走。
+    state: Option<sync::MutexGuard<'a, State>>,
+
+    observer: Option<&'a Observer>,
+}
+
+/// 复制的统计数据，参见 `Db::replication_stats`。
+#[derive(Debug, Default)]
+struct ReplicationCounters {
+    connected_replicas: AtomicUsize,
+    sync_full: AtomicU64,
+    sync_partial_ok: AtomicU64,
+    sync_partial_err: AtomicU64,
+}
+
+/// 复制的状态，由 `INFO replication` 报告。
+#[derive(Debug, Clone)]
+pub(crate) struct ReplicationStats {
+    /// 复制 ID
+    pub(crate) replid: String,
+
+    /// 复制偏移量
+    pub(crate) offset: u64,
+
+    /// 积压缓冲区是否已经开始记录
+    pub(crate) backlog_active: bool,
+
+    /// 积压缓冲区最多缓冲的字节数
+    pub(crate) backlog_size: usize,
+
+    /// 积压缓冲区中第一个字节的偏移量
+    pub(crate) backlog_first_offset: u64,
+
+    /// 积压缓冲区缓冲的字节数
+    pub(crate) backlog_len: u64,
+
+    /// 正在接收写命令的副本的数量
+    pub(crate) connected_replicas: usize,
+
+    /// 完整同步的次数
+    pub(crate) sync_full: u64,
+
+    /// 接受的部分同步请求的次数
+    pub(crate) sync_partial_ok: u64,
+
+    /// 被拒绝、改为完整同步的部分同步请求的次数
+    pub(crate) sync_partial_err: u64,
+}
+
+/// `Db::psync` 的结果：副本从哪里开始接收写命令。
+#[derive(Debug)]
+pub(crate) enum PsyncStart {
+    /// 副本请求的偏移量仍在积压缓冲区中，从该偏移量继续。
+    Continue { replid: String, offset: u64 },
+
+    /// 副本先加载快照格式的数据 `snapshot`，再从 `offset` 开始接收写命令。
+    Full {
+        replid: String,
+        offset: u64,
+        snapshot: Vec<u8>,
+    },
+}
+
+/// 过期键的统计数据，参见 `Db::expiration_stats`。
+#[derive(Debug, Default)]
+struct ExpirationCounters {
+    expired_keys: AtomicU64,
+    cycles: AtomicU64,
+    max_cycle_latency_us: AtomicU64,
+    active_expired_keys: AtomicU64,
+    lazy_expired_keys: AtomicU64,
+    ttl_histogram: [AtomicU64; TTL_BUCKET_NAMES.len()],
+}
+
+/// 过期键的统计数据，由 `INFO` 报告。
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ExpirationStats {
+    /// 后台任务清除的过期键的总数，包括过期的哈希字段
+    pub(crate) expired_keys: u64,
+
+    /// 清除的次数。每次最多清除 `PURGE_BATCH_SIZE` 个键。
+    pub(crate) cycles: u64,
+
+    /// 一次清除持有锁的最长时间（微秒）
+    pub(crate) max_cycle_latency_us: u64,
+
+    /// 后台任务清除的过期键的数量，不包括哈希字段
+    pub(crate) active_expired_keys: u64,
+
+    /// 写入之前发现已经过期、被立即移除的键的数量
+    pub(crate) lazy_expired_keys: u64,
+
+    /// 设置过期时间时指定的 TTL 的分布，各个桶的名称参见 `TTL_BUCKET_NAMES`
+    pub(crate) ttl_histogram: [u64; TTL_BUCKET_NAMES.len()],
+}
+
+/// 键空间的概况，由 `INFO keyspace` 报告。
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct KeyspaceStats {
+    /// 未过期的键的数量
+    pub(crate) keys: u64,
+
+    /// 其中设置了过期时间的键的数量
+    pub(crate) expires: u64,
+}
+
+impl ExpirationCounters {
+    /// 将为键设置的 `ttl` 计入直方图。
+    fn record_ttl(&self, ttl: Duration) {
+        let bucket = TTL_BUCKET_LIMITS
+            .iter()
+            .position(|&limit| ttl < limit)
+            .unwrap_or(TTL_BUCKET_LIMITS.len());
+
+        self.ttl_histogram[bucket].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// 一次清除之后后台任务应该做什么。
+#[derive(Debug, PartialEq)]
+enum Purge {
+    /// 还有已过期的键没有清除，让出执行权之后立即继续。
+    More,
+
+    /// 下一个键在这段时间之后过期。
+    Wait(Duration),
+
+    /// 没有键会过期，或者数据库正在关闭。等待通知。
+    Idle,
 }
 
 #[derive(Debug)]
@@ -45,9 +306,6 @@ struct State {
     /// 键值数据。我们不打算做任何复杂的事情，所以 `std::collections::HashMap` 就足够了。
     entries: HashMap<String, Entry>,
 
-    /// 发布/订阅键空间。Redis 使用一个**独立**的键空间来分别处理键值和发布/订阅。`mini-redis` 通过使用一个独立的 `HashMap` 来处理这个问题。
-    pub_sub: HashMap<String, broadcast::Sender<Bytes>>,
-
     /// 跟踪键的 TTL（生存时间）。
     ///
     /// 使用 `BTreeSet` 来按照过期时间排序维护过期时间。这使得后台任务可以迭代此映射以找到下一个到期的值。
@@ -55,6 +313,47 @@ struct State {
     /// 尽管极不可能，但有可能在同一时刻创建多个过期。因此，`Instant` 对于键来说是不够的。使用唯一键（`String`）来打破这种僵局。
     expirations: BTreeSet<(Instant, String)>,
 
+    /// 跟踪通过 `HEXPIRE` 设置了过期时间的哈希字段，与 `expirations` 一样按过期时间排序。
+    ///
+    /// 后台任务同时清除过期的键和过期的字段：每次处理两者中更早过期的一个。
+    field_expirations: BTreeSet<(Instant, String, Bytes)>,
+
+    /// 通过 `SCRIPT LOAD` 或 `EVAL` 缓存的脚本，以脚本的 SHA1 十六进制摘要为键。
+    #[cfg(feature = "scripting")]
+    scripts: HashMap<String, Bytes>,
+
+    /// `scripts` 中的摘要，按缓存的顺序排列。缓存的脚本超过 `MAX_CACHED_SCRIPTS` 个时从最早的开始移除。
+    #[cfg(feature = "scripting")]
+    script_order: VecDeque<String>,
+
+    /// 脚本最多执行的 Lua 指令数量，`None` 表示不限制。
+    #[cfg(feature = "scripting")]
+    script_instruction_limit: Option<u64>,
+
+    /// 每当键被修改、删除或者过期时发送该键，开启了 `CLIENT TRACKING` 的连接据此发送失效通知。
+    invalidations: broadcast::Sender<String>,
+
+    /// 通过 `Db::watch_key` 观察的键，以及向观察者发送键的当前值的 `watch::Sender`。
+    watchers: HashMap<String, watch::Sender<Option<Bytes>>>,
+
+    /// 键空间变化的观察者。修改键时在这里记录事件，释放锁之后交付。
+    observer: Option<Arc<Observer>>,
+
+    /// 过期键的统计数据，与 `Shared::expiration_stats` 相同。
+    expiration_stats: Arc<ExpirationCounters>,
+
+    /// 过期时间使用的时间源。
+    clock: Arc<dyn Clock>,
+
+    /// 设置过期时间时随机延长 TTL 的最大百分比，参见 `Db::set_ttl_jitter`。
+    ttl_jitter: u8,
+
+    /// 计算 TTL 随机延长量的哈希种子，每个 `Db` 实例各不相同。
+    jitter_seed: RandomState,
+
+    /// 复制积压缓冲区。写命令在执行的同一次加锁下被追加，副本看到的顺序因此与执行的顺序一致。
+    backlog: Backlog,
+
     /// 当 Db 实例关闭时为 true。当所有 `Db` 值被丢弃时, 会发生这种情况。将其设置为 `true` 通知后台任务退出。
     shutdown: bool,
 }
@@ -63,17 +362,169 @@ struct State {
 #[derive(Debug)]
 struct Entry {
     /// 存储的数据
-    data: Bytes,
+    data: Value,
 
     /// 条目过期并应从数据库中移除的时刻。
     expires_at: Option<Instant>,
 
+    /// 最后一次通过 `Db::get` 等读取操作访问条目的时刻，用于 `OBJECT IDLETIME`。
+    /// 条目创建时设置为创建的时刻。
+    accessed_at: Instant,
+
+    /// 访问频率计数器，用于 `OBJECT FREQ`。参见 `Entry::touch`。
+    frequency: u8,
+}
+
+/// 哈希中的一个字段
+#[derive(Debug, Clone)]
+struct Field {
+    /// 字段的值
+    value: Bytes,
+
+    /// 字段过期并应从哈希中移除的时刻。与键的过期时间相互独立。
+    expires_at: Option<Instant>,
+}
+
+impl Entry {
+    /// 新条目的访问频率，与 Redis 的 `LFU_INIT_VAL` 相同，避免新写入的键立即被视为最少使用的键。
+    const INITIAL_FREQUENCY: u8 = 5;
+
+    /// 访问频率计数器每经过这段时间没有访问就减一，与 Redis 默认的 `lfu-decay-time` 相同。
+    const FREQUENCY_DECAY: Duration = Duration::from_secs(60);
+
+    /// 创建一个在 `now` 时刻写入的条目。
+    fn new(data: Value, expires_at: Option<Instant>, now: Instant) -> Entry {
+        Entry {
+            data,
+            expires_at,
+            accessed_at: now,
+            frequency: Entry::INITIAL_FREQUENCY,
+        }
+    }
+
+    /// 记录一次在 `now` 时刻的访问。
+    ///
+    /// 访问频率先按照距离上次访问的时间衰减，再加一，最大为 255。与 Redis 不同，计数器线性增长，
+    /// 而不是以对数概率增长，因此频繁访问的键大约在 250 次访问之后就达到上限。
+    fn touch(&mut self, now: Instant) {
+        self.frequency = self.frequency(now).saturating_add(1);
+        self.accessed_at = now;
+    }
+
+    /// 距离上次访问经过的时间。
+    fn idle_time(&self, now: Instant) -> Duration {
+        now.saturating_duration_since(self.accessed_at)
+    }
+
+    /// 在 `now` 时刻的访问频率，包括自上次访问以来的衰减，但不记录一次新的访问。
+    fn frequency(&self, now: Instant) -> u8 {
+        let decay = self.idle_time(now).as_secs() / Entry::FREQUENCY_DECAY.as_secs();
+        (self.frequency as u64).saturating_sub(decay) as u8
+    }
+
+    /// 条目在 `now` 时刻是否仍然可见。
+    ///
+    /// 除了键本身的过期时间，所有字段都已过期的哈希同样视为已经过期，
+    /// 即使后台任务尚未清除这些字段。
+    fn is_live(&self, now: Instant) -> bool {
+        if self.expires_at.is_some_and(|when| when <= now) {
+            return false;
+        }
+
+        match &self.data {
+            Value::Hash(hash) => hash
+                .values()
+                .any(|field| field.expires_at.is_none_or(|when| when > now)),
+            _ => true,
+        }
+    }
+}
+
+/// 键中存储的值。
+#[derive(Debug, Clone)]
+enum Value {
+    /// 字符串值。HyperLogLog 也被编码为字符串存储。
+    String(Bytes),
+
+    /// 由 `XADD` 创建的流。
+    Stream(Stream),
+
+    /// 由 `LPUSH` 或 `RPUSH` 创建的列表。列表变为空时键被删除，因此存储的列表总是非空的。
+    List(VecDeque<Bytes>),
+
+    /// 由 `SADD` 或者 `SINTERSTORE` 之类的命令创建的集合。与列表一样，存储的集合总是非空的。
+    ///
+    /// 使用 `BTreeSet` 保存成员，`SMEMBERS` 等命令按字节序返回成员，结果是确定的。
+    Set(BTreeSet<Bytes>),
+
+    /// 由 `HSET` 或 `HINCRBY` 之类的命令创建的哈希。每个字段可以有自己的过期时间，
+    /// 最后一个字段被删除或者过期时键被删除，因此存储的哈希总是非空的。
+    Hash(HashMap<Bytes, Field>),
+}
+
+/// `SINTER`、`SUNION` 和 `SDIFF` 对集合执行的运算。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SetOperation {
+    /// 交集：所有集合中都存在的成员
+    Inter,
+
+    /// 并集：至少一个集合中存在的成员
+    Union,
+
+    /// 差集：第一个集合中存在、而其他集合中都不存在的成员
+    Diff,
+}
+
+/// `DEBUG OBJECT` 返回的条目元数据。
+#[cfg(feature = "debug")]
+#[derive(Debug)]
+pub(crate) struct ObjectInfo {
+    /// 值的类型，`string`、`stream`、`list`、`set` 或 `hash`
+    pub(crate) kind: &'static str,
+
+    /// 字符串的字节数，或者流、列表、集合、哈希中元素的数量
+    pub(crate) size: usize,
+
+    /// 剩余的生存时间。`None` 表示键没有过期时间。
+    pub(crate) ttl: Option<Duration>,
+}
+
+/// 在 `Db::batch` 持有的锁内对共享状态的视图。
+///
+/// 通过 `Batch` 执行的所有操作都在同一个临界区内完成。
+pub(crate) struct Batch<'a> {
+    state: &'a mut State,
+
+    /// 发布/订阅频道，`PUBLISH` 在持有 `state` 的锁时获取它的锁。
+    channels: &'a Channels,
+
+    /// 执行批处理的连接的 ID，参见 `Db::client_id`。
+    client_id: Option<u64>,
+
+    /// 批处理过程中是否有 `set` 需要唤醒后台任务。
+    notify: bool,
+
+    /// 批处理过程中被推入元素的列表和被添加条目的流，需要唤醒在这些键上阻塞的 `BLPOP` 和 `XREAD`。
+    ready_keys: Vec<String>,
+
+    /// 批处理中写命令的效果，追加到 AOF 和复制积压缓冲区。`None` 表示不需要记录，参见 `Db::batch_logged`。
+    effects: Option<Vec<Frame>>,
 }
 
 impl DbDropGuard {
     /// 创建一个新的 `DbDropGuard`，包装一个 `Db` 实例。当该实例被丢弃时，`Db` 的清理任务将被关闭。
-    pub(crate) fn new() -> DbDropGuard {
-        DbDropGuard { db: Db::new() }
+    ///
+    /// `channel_capacity` 是每个发布/订阅频道能够缓冲的消息数量。
+    ///
+    /// 过期时间使用 `clock` 计算，键空间的变化通知 `observer`，参见 `Db::new_with_observer`。
+    pub(crate) fn new_with_observer(
+        channel_capacity: usize,
+        clock: Arc<dyn Clock>,
+        observer: Option<Arc<dyn DbObserver>>,
+    ) -> DbDropGuard {
+        DbDropGuard {
+            db: Db::new_with_observer(channel_capacity, clock, observer),
+        }
     }
 
     /// 获取共享数据库。在内部，这是一个 `Arc`，因此克隆只会增加引用计数。
@@ -86,223 +537,2385 @@ impl Drop for DbDropGuard {
     fn drop(&mut self) {
         // 通知 'Db' 实例关闭清理过期键的任务
         self.db.shutdown_purge_task();
+
+        // 关闭与主节点的连接。复制任务持有 `Db` 的句柄，不会因为 `Db` 被丢弃而退出。
+        self.db.set_replica_link(None);
     }
 }
 
 impl Db {
     /// 创建一个新的、空的 `Db` 实例。分配共享状态并启动一个后台任务来管理key的过期。
-    pub(crate) fn new() -> Db {
-        let shared = Arc::new(Shared {
-            state: Mutex::new(State {
+    ///
+    /// 新建的发布/订阅频道能够缓冲 `channel_capacity` 条消息。
+    ///
+    /// `clock` 是计算过期时间的时间源，服务器默认使用 `SystemClock`，测试和模拟可以借此确定性地控制键的过期。
+    /// 读写键时总是根据 `clock` 判断键是否过期；后台任务也根据 `clock` 计算距离下一个键过期的时间，
+    /// 但按照实际时间休眠，因此 `clock` 被手动推进后，过期的键会在后台任务下一次醒来时才被清除。
+    ///
+    /// 设置了 `observer` 时，键被写入、修改、删除或者过期之后，观察者在释放锁之后得到通知，参见 `DbObserver`。
+    pub(crate) fn new_with_observer(
+        channel_capacity: usize,
+        clock: Arc<dyn Clock>,
+        observer: Option<Arc<dyn DbObserver>>,
+    ) -> Db {
+        let observer = observer.map(|observer| Arc::new(Observer::new(observer)));
+
+        let expiration_stats = Arc::new(ExpirationCounters::default());
+
+        let shared = sync::Arc::new(Shared {
+            state: sync::Mutex::new(State {
                 entries: HashMap::new(),
-                pub_sub: HashMap::new(),
                 expirations: BTreeSet::new(),
+                field_expirations: BTreeSet::new(),
+                #[cfg(feature = "scripting")]
+                scripts: HashMap::new(),
+                #[cfg(feature = "scripting")]
+                script_order: VecDeque::new(),
+                #[cfg(feature = "scripting")]
+                script_instruction_limit: Some(DEFAULT_SCRIPT_INSTRUCTION_LIMIT),
+                invalidations: broadcast::channel(channel_capacity).0,
+                watchers: HashMap::new(),
+                observer: observer.clone(),
+                expiration_stats: expiration_stats.clone(),
+                clock,
+                ttl_jitter: 0,
+                jitter_seed: RandomState::new(),
+                backlog: Backlog::new(DEFAULT_BACKLOG_SIZE),
                 shutdown: false,
             }),
-            background_task: Notify::new(),
+            background_task: sync::Notify::new(),
+            channels: Channels::new(channel_capacity),
+            blocked: BlockedKeys::default(),
+            expiration_stats,
+            latency: LatencyMonitor::default(),
+            snapshots: Arc::new(Snapshots::default()),
+            aof: OnceLock::new(),
+            backlog_appended: Notify::new(),
+            replication_stats: ReplicationCounters::default(),
+            replica_acks: std::sync::Mutex::new(HashMap::new()),
+            replica_acked: Notify::new(),
+            replica_link: std::sync::Mutex::new(None),
+            is_replica: AtomicBool::new(false),
+            next_client_id: AtomicU64::new(1),
+            observer,
         });
 
         // Start the background task.
-        tokio::spawn(purge_expired_tasks(shared.clone()));
+        #[cfg(not(mini_redis_loom))]
+        crate::task::spawn("mini-redis::purge", purge_expired_tasks(shared.clone()));
+
+        // loom 无法运行 Tokio 任务，改为在 loom 线程中执行同步版本的后台任务。
+        #[cfg(mini_redis_loom)]
+        {
+            let shared = shared.clone();
+            loom::thread::spawn(move || purge_expired_blocking(&shared));
+        }
+
+        Db {
+            shared,
+            client_id: None,
+        }
+    }
+
+    /// 为一个新的连接分配 ID，返回属于该连接的句柄。
+    ///
+    /// 句柄与 `self` 共享同一个数据库。通过它发布的消息记录连接的 ID，开启了 `CLIENT PUBSUB-METADATA`
+    /// 的订阅者据此知道消息来自哪个连接。
+    pub(crate) fn for_new_client(&self) -> Db {
+        Db {
+            shared: self.shared.clone(),
+            client_id: Some(self.shared.next_client_id.fetch_add(1, Ordering::Relaxed)),
+        }
+    }
 
-        Db { shared }
+    /// 持有该句柄的连接的 ID，`CLIENT ID` 返回它。参见 `Db::for_new_client`。
+    pub(crate) fn client_id(&self) -> Option<u64> {
+        self.client_id
     }
 
     /// 获取与key相关联的值。
     ///
     /// 如果没有与key相关联的value，则返回 `None`。
     /// 这可能是因为从未给key分配过value，或先前分配的value已过期。
-    pub(crate) fn get(&self, key: &str) -> Option<Bytes> {
+    /// 如果键中存储的不是字符串，则返回错误。
+    pub fn get(&self, key: &str) -> Result<Option<Bytes>, &'static str> {
         // 获取锁，获取条目并克隆值。
         //
         // 因为数据是使用 `Bytes` 存储的，所以此处的克隆是浅克隆。
         // 数据不会被复制。
-        let state = self.shared.state.lock().unwrap();
-        state.entries.get(key).map(|entry| entry.data.clone())
+        let mut state = self.shared.lock();
+        let value = state.get(key)?;
+        state.touch(key);
+        Ok(value)
     }
 
     /// 设置与键相关联的值，并可选择指定一个过期时长。
     ///
-    /// 如果已存在与该键相关联的值，则将其移除。
-    pub(crate) fn set(&self, key: String, value: Bytes, expire: Option<Duration>) {
-        let mut state = self.shared.state.lock().unwrap();
+    /// 如果已存在与该键相关联的值，则将其移除。`keep_ttl` 为 `true` 时保留键原有的过期时间，
+    /// 此时 `expire` 被忽略；否则原有的过期时间被丢弃。
+    pub fn set(&self, key: String, value: Bytes, expire: Option<Duration>, keep_ttl: bool) {
+        let mut state = self.shared.lock();
 
-        // 如果这个 `set` 成为**下一个**过期的键，则需要通知后台任务，以便它可以更新其状态。
-        //
         // 是否需要通知后台任务是在执行 `set` 操作期间计算的。
-        let mut notify = false;
+        let notify = state.set(key, value, expire, keep_ttl);
 
-        let expires_at = expire.map(|duration| {
-            // `Instant` at which the key expires.
-            let when = Instant::now() + duration;
+        // 在通知后台任务之前释放互斥锁。这有助于减少争用，
+        // 避免后台任务被唤醒时由于此函数仍持有互斥锁而无法获取。
+        drop(state);
 
-            // 仅当新插入的过期时间是下一个要驱逐的键时，才通知工作任务。
-            // 在这种情况下，需要唤醒工作任务以更新其状态。
-            notify = state
-                .next_expiration()
-                .map(|expiration| expiration > when)
-                .unwrap_or(true);
+        if notify {
+            // 最后，仅在后台任务需要更新其状态以反映新的过期时间时才通知它。
+            self.shared.background_task.notify_one();
+        }
+    }
 
-            when
-        });
+    /// 设置多个键的值，每个键可以指定一个过期时长，已有的值和过期时间被替换。
+    ///
+    /// 所有键在同一次加锁下写入，之后最多通知后台任务一次，批量导入大量键（例如恢复快照或者 `MSET`）
+    /// 时比逐个调用 `set` 快得多。其他连接不会看到只写入了一部分的键。
+    pub fn set_many(&self, entries: impl IntoIterator<Item = (String, Bytes, Option<Duration>)>) {
+        let mut state = self.shared.lock();
 
-        // 将条目插入到 `HashMap` 中。
-        let prev = state.entries.insert(
-            key.clone(),
-            Entry {
-                data: value,
-                expires_at,
-            },
-        );
+        let mut notify = false;
+        for (key, value, expire) in entries {
+            notify |= state.set(key, value, expire, false);
+        }
 
-        // 如果先前已经存在与该键关联的值**并且**有一个过期时间，
-        // 则必须从 `expirations` 映射中移除关联的条目。这可以避免数据泄漏。
-        if let Some(prev) = prev {
-            if let Some(when) = prev.expires_at {
-                // clear expiration
-                state.expirations.remove(&(when, key.clone()));
-            }
+        drop(state);
+
+        if notify {
+            self.shared.background_task.notify_one();
         }
+    }
 
-        // 跟踪过期时间。如果在移除之前插入，当当前 `(when, key)` 等于之前的 `(when, key)` 时会导致错误。
-        // 先移除再插入可以避免这种情况。
-        if let Some(when) = expires_at {
-            state.expirations.insert((when, key));
+    /// 设置 TTL 随机延长的最大百分比，`0` 表示不延长。
+    ///
+    /// 之后通过 `set` 设置的过期时长会被随机延长 `0` 到 `percent`% 之间的时间。大量键以相同的 TTL 写入时，
+    /// 它们的过期时间因此分散开，不会在同一时刻过期而使后台任务长时间地清除。键不会早于请求的时间过期。
+    ///
+    /// # Panic
+    ///
+    /// 当 `percent` 大于 100 时会触发panic。
+    pub(crate) fn set_ttl_jitter(&self, percent: u8) {
+        assert!(percent <= 100, "TTL jitter must be at most 100 percent");
+        self.shared.lock().ttl_jitter = percent;
+    }
+
+    /// 设置 `BGSAVE` 写入的快照文件的路径。
+    pub(crate) fn set_snapshot_path(&self, path: PathBuf) {
+        self.shared.snapshots.set_path(path);
+    }
+
+    /// 在后台生成快照并写入快照文件。
+    ///
+    /// 只在复制所有条目时持有锁，编码和写入文件在 `spawn_blocking` 的线程上进行，不阻塞其他连接。
+    /// 已经有正在进行的快照时返回错误。快照的进度和结果通过 `snapshot_stats` 查看。
+    pub(crate) fn bgsave(&self) -> Result<(), &'static str> {
+        let snapshots = self.shared.snapshots.clone();
+
+        if !snapshots.begin() {
+            return Err("ERR Background save already in progress");
         }
 
-        // 在通知后台任务之前释放互斥锁。这有助于减少争用，
-        // 避免后台任务被唤醒时由于此函数仍持有互斥锁而无法获取。
+        let snapshot = {
+            let state = self.shared.lock();
+            Snapshot::copy(&state)
+        };
+
+        tokio::task::spawn_blocking(move || snapshots.save(snapshot));
+
+        Ok(())
+    }
+
+    /// 从 `path` 处的快照文件加载数据，返回加载的键的数量。文件不存在时不加载任何数据。
+    ///
+    /// 写入快照之后已经过期的键被跳过，已有的同名键被替换。文件损坏、被截断或者由更新版本写入时返回错误，
+    /// 此时数据库保持不变。
+    pub(crate) fn load_snapshot(&self, path: &Path) -> Result<u64, SnapshotError> {
+        let mut state = self.shared.lock();
+        let loaded = snapshot::load(&mut state, path)?;
+        let notify = state.next_expiration().is_some();
         drop(state);
 
         if notify {
-            // 最后，仅在后台任务需要更新其状态以反映新的过期时间时才通知它。
             self.shared.background_task.notify_one();
         }
+
+        Ok(loaded)
     }
 
-    /// 返回请求的频道的 `Receiver`。
+    /// 重新执行 `path` 处的 AOF 中的命令，然后开启 AOF，之后的写命令追加到该文件。返回重新执行的命令数量。
     ///
-    /// 返回的 `Receiver` 用于接收由 `PUBLISH` 命令广播的值。
-    pub(crate) fn subscribe(&self, key: String) -> broadcast::Receiver<Bytes> {
-        use std::collections::hash_map::Entry;
-
-        // Acquire the mutex
-        let mut state = self.shared.state.lock().unwrap();
-
-        // 如果请求的频道没有条目，则创建一个新的广播频道并将其与键关联。
-        // 如果已经存在，则返回一个关联的接收器。
-        match state.pub_sub.entry(key) {
-            Entry::Occupied(e) => e.get().subscribe(),
-            Entry::Vacant(e) => {
-                // 目前没有广播频道，因此创建一个。
-                //
-                // 创建的频道容量为 `1024` 条消息。消息会存储在频道中，直到**所有**订阅者都已查看。
-                // 这意味着缓慢的订阅者可能导致消息被无限期保留。
-                //
-                // 当频道容量达到上限时，发布操作会导致旧消息被丢弃。
-                // 这可以防止缓慢的消费者阻塞整个系统。
-                let (tx, rx) = broadcast::channel(1024);
-                e.insert(tx);
-                rx
-            }
+    /// 只能调用一次，由服务器在接受连接之前调用。
+    pub(crate) fn open_aof(&self, path: &Path, policy: AppendFsync) -> crate::Result<u64> {
+        let loaded = aof::load(self, path)?;
+
+        if self.shared.aof.set(Aof::open(path, policy)?).is_err() {
+            return Err("AOF is already enabled".into());
         }
-    }
 
-    /// 将消息发布到频道。返回正在监听该频道的订阅者数量。
-    pub(crate) fn publish(&self, key: &str, value: Bytes) -> usize {
-        let state = self.shared.state.lock().unwrap();
+        Ok(loaded)
+    }
 
-        state
-            .pub_sub
-            .get(key)
-            // 在广播频道成功发送消息时，返回订阅者的数量。
-            // 如果发生错误，表示没有接收器，此时应返回 `0`。
-            .map(|tx| tx.send(value).unwrap_or(0))
-            // 如果频道键没有条目，则表示没有订阅者。在这种情况下，返回 `0`。
-            .unwrap_or(0)
+    /// 等待追加到 AOF 的所有命令写入磁盘。服务器关闭时调用。
+    pub(crate) async fn sync_aof(&self) {
+        if let Some(aof) = self.shared.aof.get() {
+            aof.sync().await;
+        }
     }
 
-    /// 发出信号以关闭清理后台任务。这是由 `DbShutdown` 的 `Drop` 实现调用的。
-    fn shutdown_purge_task(&self) {
-        // 必须发出信号以关闭后台任务。这是通过将 `State::shutdown` 设为 `true` 并发出信号给任务来完成的。
-        let mut state = self.shared.state.lock().unwrap();
-        state.shutdown = true;
+    /// 返回 AOF 的状态，由 `INFO` 报告。未开启 AOF 时返回 `None`。
+    pub(crate) fn aof_stats(&self) -> Option<AofStats> {
+        self.shared.aof.get().map(Aof::stats)
+    }
 
-        // 在通知后台任务之前释放锁。这有助于减少锁争用，确保后台任务唤醒时不会因无法获取互斥锁而阻塞。
-        drop(state);
-        self.shared.background_task.notify_one();
+    /// 写入 AOF 失败、尚未恢复时返回拒绝写命令的错误信息。未开启 AOF 或者最后一次写入成功时返回 `None`。
+    pub(crate) fn aof_write_error(&self) -> Option<&'static str> {
+        self.shared.aof.get().and_then(Aof::write_error)
     }
-}
 
-impl Shared {
-    /// 清除所有已过期的键，并返回下一个键将过期的 `Instant`。后台任务将休眠至该时刻。
-    fn purge_expired_keys(&self) -> Option<Instant> {
-        let mut state = self.state.lock().unwrap();
+    /// 返回快照的状态，由 `INFO` 报告。
+    pub(crate) fn snapshot_stats(&self) -> SnapshotStats {
+        self.shared.snapshots.stats()
+    }
 
-        if state.shutdown {
-            // 数据库正在关闭。所有共享状态的句柄已被释放。后台任务应退出。
-            return None;
-        }
+    /// 返回 TTL 随机延长的最大百分比，由 `INFO` 报告。
+    pub(crate) fn ttl_jitter(&self) -> u8 {
+        self.shared.lock().ttl_jitter
+    }
 
-        // 这是为了让借用检查器满意。简而言之，`lock()` 返回一个 `MutexGuard`，而不是 `&mut State`。
-        // 借用检查器无法“透过”互斥锁守护体判断同时可变访问 `state.expirations` 和 `state.entries` 是安全的，
-        // 因此我们在循环外部获取 `State` 的“真正”可变引用。
-        let state = &mut *state;
+    /// 仅当键中存储的值等于 `expected` 时，将其替换为 `new`，并返回是否替换。
+    ///
+    /// 比较和替换在同一次加锁下完成，其他连接不会在两者之间修改键。键不存在时不会匹配。
+    /// 替换时保留键原有的过期时间。如果键中存储的不是字符串，则返回错误。
+    pub(crate) fn compare_and_set(
+        &self,
+        key: &str,
+        expected: &[u8],
+        new: Bytes,
+    ) -> Result<bool, &'static str> {
+        let mut state = self.shared.lock();
+        state.compare_and_set(key, expected, new)
+    }
 
-        // 查找所有计划在当前时间之前过期的键。
-        let now = Instant::now();
+    /// 返回键的元数据，用于 `DEBUG OBJECT`。键不存在时返回 `None`。
+    #[cfg(feature = "debug")]
+    pub(crate) fn object_info(&self, key: &str) -> Option<ObjectInfo> {
+        let state = self.shared.lock();
+        let entry = state.entry(key)?;
 
-        while let Some(&(when, ref key)) = state.expirations.iter().next() {
-            if when > now {
-                // 清除完成，`when` 是下一个键过期的时刻。工作线程将等待至此时刻。
-                return Some(when);
-            }
+        let (kind, size) = match &entry.data {
+            Value::String(data) => ("string", data.len()),
+            Value::Stream(stream) => ("stream", stream.len()),
+            Value::List(list) => ("list", list.len()),
+            Value::Set(set) => ("set", set.len()),
+            Value::Hash(hash) => ("hash", hash.len()),
+        };
 
-            // 键已过期，移除它
-            state.entries.remove(key);
-            state.expirations.remove(&(when, key.clone()));
-        }
+        let ttl = entry
+            .expires_at
+            .map(|when| when.saturating_duration_since(state.clock.now()));
 
-        None
+        Some(ObjectInfo { kind, size, ttl })
     }
 
-    /// 如果数据库正在关闭则返回 `true`
+    /// 返回所有未过期的键。
     ///
-    /// 当所有 `Db` 值都已被释放时，`shutdown` 标志被设置，这表明共享状态不再可访问。
-    fn is_shutdown(&self) -> bool {
-        self.state.lock().unwrap().shutdown
+    /// 键的列表在一次加锁下复制，是某一时刻的快照。返回之后，其他连接可能已经修改或删除了这些键。
+    pub fn keys(&self) -> Vec<String> {
+        let state = self.shared.lock();
+        state.live_entries().map(|(key, _)| key.clone()).collect()
     }
-}
 
-impl State {
-    fn next_expiration(&self) -> Option<Instant> {
-        self.expirations
-            .iter()
-            .next()
-            .map(|expiration| expiration.0)
+    /// 返回未过期的键的数量。
+    pub fn len(&self) -> usize {
+        let state = self.shared.lock();
+        state.live_entries().count()
     }
-}
 
-/// 后台任务执行的例程。
-///
+    /// 没有未过期的键时返回 `true`。
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 对每个存储字符串的未过期键调用 `f`，参数为键、值和剩余的生存时间（`None` 表示没有过期时间）。
+    ///
+    /// 所有条目在一次加锁下复制（值是 `Bytes`，复制只增加引用计数），之后在不持有锁的情况下调用 `f`。
+    /// 因此 `f` 看到的是某一时刻一致的快照，可以执行耗时的操作，也可以访问同一个 `Db` 而不会死锁。
+    /// 存储流或列表的键被跳过。
+    pub fn for_each(&self, mut f: impl FnMut(&str, &Bytes, Option<Duration>)) {
+        let snapshot: Vec<_> = {
+            let state = self.shared.lock();
+            let now = state.clock.now();
+
+            state
+                .live_entries()
+                .filter_map(|(key, entry)| match &entry.data {
+                    Value::String(data) => Some((
+                        key.clone(),
+                        data.clone(),
+                        entry.expires_at.map(|when| when - now),
+                    )),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        for (key, value, ttl) in &snapshot {
+            f(key, value, *ttl);
+        }
+    }
+
+    /// 返回过期键的统计数据。
+    pub(crate) fn expiration_stats(&self) -> ExpirationStats {
+        let counters = &self.shared.expiration_stats;
+
+        ExpirationStats {
+            expired_keys: counters.expired_keys.load(Ordering::Relaxed),
+            cycles: counters.cycles.load(Ordering::Relaxed),
+            max_cycle_latency_us: counters.max_cycle_latency_us.load(Ordering::Relaxed),
+            active_expired_keys: counters.active_expired_keys.load(Ordering::Relaxed),
+            lazy_expired_keys: counters.lazy_expired_keys.load(Ordering::Relaxed),
+            ttl_histogram: counters
+                .ttl_histogram
+                .each_ref()
+                .map(|count| count.load(Ordering::Relaxed)),
+        }
+    }
+
+    /// 返回未过期的键的数量和其中设置了过期时间的键的数量。
+    pub(crate) fn keyspace_stats(&self) -> KeyspaceStats {
+        let state = self.shared.lock();
+        let mut stats = KeyspaceStats {
+            keys: 0,
+            expires: 0,
+        };
+
+        for (_, entry) in state.live_entries() {
+            stats.keys += 1;
+            stats.expires += u64::from(entry.expires_at.is_some());
+        }
+
+        stats
+    }
+
+    /// 返回延迟监控记录事件的阈值，`Duration::ZERO` 表示不记录。
+    pub(crate) fn latency_threshold(&self) -> Duration {
+        self.shared.latency.threshold()
+    }
+
+    /// 设置延迟监控记录事件的阈值，精确到毫秒。`Duration::ZERO` 表示不记录。
+    pub(crate) fn set_latency_threshold(&self, threshold: Duration) {
+        self.shared.latency.set_threshold(threshold);
+    }
+
+    /// 延迟达到阈值时记录到延迟监控的事件 `event` 中。
+    pub(crate) fn record_latency(&self, event: &'static str, latency: Duration) {
+        self.shared.latency.record(event, latency);
+    }
+
+    /// 返回延迟监控事件 `event` 的所有采样，按时间顺序排列。
+    pub(crate) fn latency_history(&self, event: &str) -> Vec<LatencySample> {
+        self.shared.latency.history(event)
+    }
+
+    /// 返回延迟监控记录的所有事件的概况，按名称排序。
+    pub(crate) fn latency_events(&self) -> Vec<LatencyEvent> {
+        self.shared.latency.events()
+    }
+
+    /// 删除延迟监控的事件 `events`，为空时删除所有事件。返回被删除的事件数量。
+    pub(crate) fn reset_latency(&self, events: &[String]) -> usize {
+        self.shared.latency.reset(events)
+    }
+
+    /// 在**一次**加锁下执行 `f`。
+    ///
+    /// `f` 接收一个 `Batch`，通过它执行的所有读写操作都在同一个临界区内完成，
+    /// 其他连接不会观察到中间状态。这是 `EVALBATCH` 命令原子性的基础。
+    ///
+    /// 与其他方法一样，`f` 内部不能执行任何异步操作，并且应当尽量短小。
+    pub(crate) fn batch<R>(&self, f: impl FnOnce(&mut Batch<'_>) -> R) -> R {
+        let mut state = self.shared.lock();
+
+        let mut batch = Batch {
+            state: &mut state,
+            channels: &self.shared.channels,
+            client_id: self.client_id,
+            notify: false,
+            ready_keys: Vec::new(),
+            effects: None,
+        };
+
+        let ret = f(&mut batch);
+        let notify = batch.notify;
+        let ready_keys = batch.ready_keys;
+
+        // 与 `set` 相同，在通知后台任务之前释放互斥锁。
+        drop(state);
+
+        if notify {
+            self.shared.background_task.notify_one();
+        }
+
+        for key in &ready_keys {
+            self.shared.blocked.wake(key);
+        }
+
+        ret
+    }
+
+    /// 与 `batch` 相同，并在同一次加锁下将 `f` 通过 `Batch::propagate` 记录的写命令的效果追加到 AOF 和
+    /// 复制积压缓冲区，两者中命令的顺序因此与执行的顺序一致。多个效果被包装为一个 `EVALBATCH`，
+    /// 加载 AOF 的节点和副本同样原子地执行它们。
+    ///
+    /// 未开启 AOF 并且积压缓冲区尚未开始记录时等同于 `batch`，`Batch::is_propagating` 返回 `false`。
+    /// 积压缓冲区是否开始记录在锁内检查，因此在第一个副本完整同步之后执行的写命令不会被遗漏。
+    /// 调用者在响应之前等待返回的 `AofWrite`，`always` 策略下命令写入磁盘之后才响应。
+    pub(crate) fn batch_logged<R>(&self, f: impl FnOnce(&mut Batch<'_>) -> R) -> (R, AofWrite) {
+        let aof = self.shared.aof.get();
+        let mut appended = false;
+
+        let ret = self.batch(|batch| {
+            if aof.is_none() && !batch.state.backlog.is_active() {
+                return (f(batch), AofWrite::done());
+            }
+
+            batch.effects = Some(Vec::new());
+            let ret = f(batch);
+
+            let mut effects = batch.effects.take().unwrap_or_default();
+            let frame = match effects.len() {
+                0 => return (ret, AofWrite::done()),
+                1 => effects.pop().unwrap(),
+                _ => EvalBatch::wrap(effects),
+            };
+
+            let mut buf = BytesMut::new();
+            frame.encode(&mut buf);
+            let data = buf.freeze();
+
+            let write = match aof {
+                Some(aof) => aof.send(data.clone()),
+                None => AofWrite::done(),
+            };
+            appended = batch.state.backlog.is_active();
+            batch.state.backlog.append(data);
+
+            (ret, write)
+        });
+
+        // 唤醒向副本发送写命令的连接。
+        if appended {
+            self.shared.backlog_appended.notify_waiters();
+        }
+
+        ret
+    }
+
+    /// 处理副本的 `PSYNC replid offset` 请求，决定副本从哪里开始接收写命令。
+    ///
+    /// `replid` 与本节点的复制 ID 相同并且 `offset` 仍在积压缓冲区中时，副本从 `offset` 继续；
+    /// 否则在同一次加锁下复制所有条目并记下当前的偏移量，副本加载快照之后从该偏移量开始接收写命令。
+    /// 积压缓冲区在第一次完整同步时开始记录。
+    pub(crate) fn psync(&self, replid: Option<&str>, offset: Option<u64>) -> PsyncStart {
+        let mut state = self.shared.lock();
+        let stats = &self.shared.replication_stats;
+
+        if let Some((replid, offset)) = replid.zip(offset) {
+            if replid == state.backlog.replid()
+                && matches!(state.backlog.read(offset), BacklogRead::Data(..))
+            {
+                stats.sync_partial_ok.fetch_add(1, Ordering::Relaxed);
+
+                return PsyncStart::Continue {
+                    replid: replid.to_string(),
+                    offset,
+                };
+            }
+
+            stats.sync_partial_err.fetch_add(1, Ordering::Relaxed);
+        }
+
+        state.backlog.activate();
+        stats.sync_full.fetch_add(1, Ordering::Relaxed);
+
+        let replid = state.backlog.replid().to_string();
+        let offset = state.backlog.offset();
+        let snapshot = Snapshot::copy(&state);
+        drop(state);
+
+        // 在锁外编码，条目很多时不阻塞其他连接。
+        PsyncStart::Full {
+            replid,
+            offset,
+            snapshot: snapshot.to_bytes(),
+        }
+    }
+
+    /// 读取积压缓冲区中从 `offset` 开始的写命令。
+    pub(crate) fn read_backlog(&self, offset: u64) -> BacklogRead {
+        self.shared.lock().backlog.read(offset)
+    }
+
+    /// 返回一个在下一次有写命令被追加到积压缓冲区时完成的 future。
+    ///
+    /// 为了不错过在检查积压缓冲区和开始等待之间追加的写命令，调用者应当先创建并 `enable` 这个 future，
+    /// 再检查积压缓冲区，最后等待。
+    pub(crate) fn backlog_appended(&self) -> Notified<'_> {
+        self.shared.backlog_appended.notified()
+    }
+
+    /// 设置积压缓冲区最多缓冲的字节数，超出的旧数据立即被丢弃。
+    pub(crate) fn set_backlog_size(&self, size: usize) {
+        let mut state = self.shared.lock();
+        state.backlog.set_capacity(size);
+    }
+
+    /// 记录一个副本开始或者停止接收写命令。副本停止时，它确认的偏移量被移除。
+    pub(crate) fn replica_connected(&self, connected: bool) {
+        let replicas = &self.shared.replication_stats.connected_replicas;
+
+        if connected {
+            replicas.fetch_add(1, Ordering::Relaxed);
+        } else {
+            replicas.fetch_sub(1, Ordering::Relaxed);
+
+            if let Some(id) = self.client_id {
+                self.shared.replica_acks.lock().unwrap().remove(&id);
+            }
+        }
+    }
+
+    /// 记录当前连接上的副本确认处理到了复制偏移量 `offset`，并唤醒等待确认的连接。
+    pub(crate) fn record_replica_ack(&self, offset: u64) {
+        if let Some(id) = self.client_id {
+            self.shared.replica_acks.lock().unwrap().insert(id, offset);
+            self.shared.replica_acked.notify_waiters();
+        }
+    }
+
+    /// 返回当前的复制偏移量，即追加到积压缓冲区的字节总数。
+    pub(crate) fn replication_offset(&self) -> u64 {
+        self.shared.lock().backlog.offset()
+    }
+
+    /// 返回确认处理到复制偏移量 `offset` 的副本数量。
+    pub(crate) fn acked_replicas(&self, offset: u64) -> u64 {
+        let acks = self.shared.replica_acks.lock().unwrap();
+        acks.values().filter(|&&acked| acked >= offset).count() as u64
+    }
+
+    /// 返回一个在下一次有副本确认偏移量时完成的 future。
+    ///
+    /// 与 `backlog_appended` 相同，调用者应当先创建并 `enable` 这个 future，再调用 `acked_replicas`，最后等待。
+    pub(crate) fn replica_acked(&self) -> Notified<'_> {
+        self.shared.replica_acked.notified()
+    }
+
+    /// 返回复制的状态，由 `INFO` 报告。
+    pub(crate) fn replication_stats(&self) -> ReplicationStats {
+        let state = self.shared.lock();
+        let backlog = &state.backlog;
+        let stats = &self.shared.replication_stats;
+
+        ReplicationStats {
+            replid: backlog.replid().to_string(),
+            offset: backlog.offset(),
+            backlog_active: backlog.is_active(),
+            backlog_size: backlog.capacity(),
+            backlog_first_offset: backlog.first_offset(),
+            backlog_len: backlog.len(),
+            connected_replicas: stats.connected_replicas.load(Ordering::Relaxed),
+            sync_full: stats.sync_full.load(Ordering::Relaxed),
+            sync_partial_ok: stats.sync_partial_ok.load(Ordering::Relaxed),
+            sync_partial_err: stats.sync_partial_err.load(Ordering::Relaxed),
+        }
+    }
+
+    /// 以主节点发送的快照 `data` 替换所有数据，返回加载的键的数量。副本完整同步时调用。
+    pub(crate) fn replace_with_snapshot(&self, data: &[u8]) -> Result<u64, SnapshotError> {
+        let mut state = self.shared.lock();
+
+        let keys: Vec<String> = state.entries.keys().cloned().collect();
+        for key in keys {
+            state.del(&key);
+        }
+
+        // 数据被整个替换，本节点的副本不能再从原来的积压缓冲区继续，换用新的复制 ID 使它们重新完整同步。
+        let capacity = state.backlog.capacity();
+        state.backlog = Backlog::new(capacity);
+
+        let loaded = snapshot::load_data(&mut state, data)?;
+        let notify = state.next_expiration().is_some();
+        drop(state);
+
+        if notify {
+            self.shared.background_task.notify_one();
+        }
+
+        Ok(loaded)
+    }
+
+    /// 设置与主节点的连接。`None` 表示成为主节点，原有的连接被关闭。
+    pub(crate) fn set_replica_link(&self, link: Option<crate::replication::Link>) {
+        let mut current = self.shared.replica_link.lock().unwrap();
+        self.shared
+            .is_replica
+            .store(link.is_some(), Ordering::Release);
+        *current = link;
+    }
+
+    /// 本节点是否是副本。副本以 `READONLY` 错误拒绝客户端的写命令。
+    pub(crate) fn is_replica(&self) -> bool {
+        self.shared.is_replica.load(Ordering::Acquire)
+    }
+
+    /// 作为副本时返回与主节点的连接的状态。
+    pub(crate) fn replica_link_status(&self) -> Option<crate::replication::LinkStatus> {
+        let link = self.shared.replica_link.lock().unwrap();
+        link.as_ref().map(crate::replication::Link::status)
+    }
+
+    /// 在 `keys` 下登记一个等待者。之后有元素被推入这些列表、或者有条目被添加到这些流时，`KeyWaiter::wait` 返回。
+    ///
+    /// 为了不错过在检查键和开始等待之间的写入，调用者应当先登记，再检查键，最后等待。
+    /// 写入其他键不会唤醒该等待者。
+    pub(crate) fn watch_keys<'a>(
+        &'a self,
+        keys: impl IntoIterator<Item = &'a str>,
+    ) -> KeyWaiter<'a> {
+        self.shared.blocked.watch(keys)
+    }
+
+    /// 返回一个接收被修改的键的 `Receiver`。
+    ///
+    /// 每当键被写入、删除或者过期时，键的名称会被发送给所有的接收者。用于实现 `CLIENT TRACKING`。
+    pub(crate) fn invalidations(&self) -> broadcast::Receiver<String> {
+        let state = self.shared.lock();
+        state.invalidations.subscribe()
+    }
+
+    /// 观察键的值，返回一个始终持有键的当前值的 `watch::Receiver`。
+    ///
+    /// 键被 `set`、`del`、任意写命令修改或者过期之后，新的值被推送给接收者，`changed()` 随之完成，
+    /// 嵌入服务器的应用程序不需要轮询。键不存在或者存储的不是字符串时，值为 `None`。
+    /// 只有值真正改变时才会通知：写入相同的值不会唤醒接收者。
+    ///
+    /// 过期的键在后台任务清除它或者下一次被访问时才被通知，通知可能晚于过期时间。
+    /// 同一个键的所有接收者共享一个发送器；所有接收者都被丢弃之后，发送器在键下一次改变时被移除。
+    ///
+    /// # 示例
+    ///
+    /// ```
+    /// use mini_redis::server;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let handle = server::spawn("127.0.0.1:0", server::Config::new()).await.unwrap();
+    ///     let db = handle.db();
+    ///
+    ///     let mut watcher = db.watch_key("greeting");
+    ///     assert_eq!(None, *watcher.borrow());
+    ///
+    ///     db.set("greeting".into(), "hello".into(), None, false);
+    ///     watcher.changed().await.unwrap();
+    ///     assert_eq!(Some("hello".into()), *watcher.borrow_and_update());
+    ///
+    ///     handle.shutdown().await;
+    /// }
+    /// ```
+    pub fn watch_key(&self, key: &str) -> watch::Receiver<Option<Bytes>> {
+        let mut state = self.shared.lock();
+        let value = state.watched_value(key);
+
+        match state.watchers.get(key) {
+            Some(tx) => {
+                // 过期但尚未被清除的键还没有通知过，先更新为当前值。
+                tx.send_if_modified(|current| update_watched(current, value));
+                tx.subscribe()
+            }
+            None => {
+                let (tx, rx) = watch::channel(value);
+                state.watchers.insert(key.to_string(), tx);
+                rx
+            }
+        }
+    }
+
+    /// 返回请求的频道的 `Receiver`。
+    ///
+    /// 返回的 `Receiver` 用于接收由 `PUBLISH` 命令广播的值。
+    pub(crate) fn subscribe(&self, key: String) -> broadcast::Receiver<Published> {
+        self.shared.channels.subscribe(key)
+    }
+
+    /// 在频道的订阅者离开之后调用。频道已经没有订阅者时，移除它的发送器。
+    ///
+    /// 订阅者在取消订阅或者断开连接时调用此方法，所有订阅者都离开的频道因此不会一直留在频道表中。
+    pub(crate) fn release_channel(&self, key: &str) {
+        self.shared.channels.release(key);
+    }
+
+    /// 返回至少有一个订阅者的频道，用于 `PUBSUB CHANNELS`。
+    ///
+    /// 同时移除所有已经没有订阅者的频道。
+    pub(crate) fn channels(&self) -> Vec<String> {
+        self.shared.channels.names()
+    }
+
+    /// 返回至少有一个订阅者的频道的数量，由 `INFO` 报告。参见 `Db::channels`。
+    pub(crate) fn channel_count(&self) -> usize {
+        self.shared.channels.len()
+    }
+
+    /// 返回频道的订阅者数量，用于 `PUBSUB NUMSUB`。
+    pub(crate) fn subscriber_count(&self, key: &str) -> usize {
+        self.shared.channels.subscriber_count(key)
+    }
+
+    /// 将消息发布到频道。返回正在监听该频道的订阅者数量。
+    ///
+    /// 频道没有订阅者时直接返回 `0`，不会发送消息。所有订阅者都已离开的频道在此时被移除。
+    /// 发布只获取频道表的锁，不会与键值的读写争用。
+    pub fn publish(&self, key: &str, value: Bytes) -> usize {
+        self.shared
+            .channels
+            .publish(key, Some(value), self.client_id)
+    }
+
+    /// 在一次加锁中将 `values` 按顺序发布到频道，其他发布者的消息不会插入其中。返回正在监听该频道的订阅者数量。
+    pub(crate) fn publish_all(&self, key: &str, values: Vec<Bytes>) -> usize {
+        self.shared.channels.publish(key, values, self.client_id)
+    }
+
+    /// 缓存脚本，返回其 SHA1 十六进制摘要，之后可以通过 `EVALSHA` 引用该脚本。
+    ///
+    /// 缓存最多保存 `MAX_CACHED_SCRIPTS` 个脚本，超出时最早缓存的脚本被移除，之后的 `EVALSHA` 以 `NOSCRIPT` 响应，
+    /// 客户端需要重新通过 `EVAL` 或者 `SCRIPT LOAD` 发送脚本。
+    #[cfg(feature = "scripting")]
+    pub(crate) fn load_script(&self, script: Bytes) -> String {
+        let sha = sha1_smol::Sha1::from(&script[..]).digest().to_string();
+
+        let mut state = self.shared.lock();
+
+        if state.scripts.insert(sha.clone(), script).is_none() {
+            state.script_order.push_back(sha.clone());
+
+            if state.script_order.len() > MAX_CACHED_SCRIPTS {
+                if let Some(oldest) = state.script_order.pop_front() {
+                    state.scripts.remove(&oldest);
+                }
+            }
+        }
+
+        sha
+    }
+
+    /// 返回摘要为 `sha` 的已缓存脚本。
+    #[cfg(feature = "scripting")]
+    pub(crate) fn get_script(&self, sha: &str) -> Option<Bytes> {
+        let state = self.shared.lock();
+        state.scripts.get(&sha.to_lowercase()).cloned()
+    }
+
+    /// 设置脚本最多执行的 Lua 指令数量，`None` 表示不限制。
+    ///
+    /// 脚本在持有锁时执行，超出上限的脚本被中止，其他连接和清除过期键的后台任务不会因为陷入死循环的脚本而停止。
+    #[cfg(feature = "scripting")]
+    pub(crate) fn set_script_instruction_limit(&self, limit: Option<u64>) {
+        self.shared.lock().script_instruction_limit = limit;
+    }
+
+    /// 返回脚本最多执行的 Lua 指令数量，`None` 表示不限制。
+    #[cfg(feature = "scripting")]
+    pub(crate) fn script_instruction_limit(&self) -> Option<u64> {
+        self.shared.lock().script_instruction_limit
+    }
+
+    /// 发出信号以关闭清理后台任务。这是由 `DbShutdown` 的 `Drop` 实现调用的。
+    fn shutdown_purge_task(&self) {
+        // 必须发出信号以关闭后台任务。这是通过将 `State::shutdown` 设为 `true` 并发出信号给任务来完成的。
+        let mut state = self.shared.lock();
+        state.shutdown = true;
+
+        // 在通知后台任务之前释放锁。这有助于减少锁争用，确保后台任务唤醒时不会因无法获取互斥锁而阻塞。
+        drop(state);
+        self.shared.background_task.notify_one();
+    }
+}
+
+impl Batch<'_> {
+    /// 获取与key相关联的值。参见 `Db::get`。
+    pub(crate) fn get(&mut self, key: &str) -> Result<Option<Bytes>, &'static str> {
+        let value = self.state.get(key)?;
+        self.state.touch(key);
+        Ok(value)
+    }
+
+    /// 设置与键相关联的值。参见 `Db::set`。
+    pub(crate) fn set(
+        &mut self,
+        key: String,
+        value: Bytes,
+        expire: Option<Duration>,
+        keep_ttl: bool,
+    ) {
+        self.notify |= self.state.set(key, value, expire, keep_ttl);
+    }
+
+    /// 设置键的值，在 Unix 时间戳 `unix_ms`（毫秒）过期。与 `set` 不同，过期时间不会被随机延长，
+    /// 加载 AOF 和副本执行 `SET key value PXAT` 时得到与主节点相同的过期时刻。
+    pub(crate) fn set_at(&mut self, key: String, value: Bytes, unix_ms: u64) {
+        let when = self.state.clock.now() + self.duration_until(unix_ms);
+        self.notify |= self.state.insert_string(key, value, Some(when));
+    }
+
+    /// 批处理是否需要通过 `propagate` 记录写命令的效果，参见 `Db::batch_logged`。
+    pub(crate) fn is_propagating(&self) -> bool {
+        self.effects.is_some()
+    }
+
+    /// 记录一个写命令的效果：重新执行 `frame` 得到与这次执行相同的结果。不需要记录时忽略。
+    pub(crate) fn propagate(&mut self, frame: Frame) {
+        if let Some(effects) = &mut self.effects {
+            effects.push(frame);
+        }
+    }
+
+    /// 返回键的过期时刻的 Unix 时间戳（毫秒）。键不存在或者不会过期时返回 `None`。
+    ///
+    /// 已经过期但尚未被清除的键同样返回它的过期时刻，例如刚刚以已经过去的 `PXAT` 设置的键。
+    pub(crate) fn expire_time(&self, key: &str) -> Option<u64> {
+        let when = self.state.entries.get(key)?.expires_at?;
+        Some(Timestamps::now(self.state.clock.now()).to_unix_ms(Some(when)))
+    }
+
+    /// 返回从现在起经过 `duration` 之后的 Unix 时间戳（毫秒）。
+    pub(crate) fn unix_time_after(&self, duration: Duration) -> u64 {
+        let now = self.state.clock.now();
+        Timestamps::now(now).to_unix_ms(Some(now + duration))
+    }
+
+    /// 返回距离 Unix 时间戳 `unix_ms`（毫秒）的时长，已经过去的时间戳返回 0。
+    pub(crate) fn duration_until(&self, unix_ms: u64) -> Duration {
+        let now = self.state.clock.now();
+        Timestamps::now(now)
+            .to_instant(unix_ms)
+            .map_or(Duration::ZERO, |when| when - now)
+    }
+
+    /// 将多条消息按顺序发布到频道。参见 `Db::publish_all`。
+    pub(crate) fn publish_all(&mut self, key: &str, values: Vec<Bytes>) -> usize {
+        self.channels.publish(key, values, self.client_id)
+    }
+
+    /// 仅当键中存储的值等于 `expected` 时，将其替换为 `new`。参见 `Db::compare_and_set`。
+    pub(crate) fn compare_and_set(
+        &mut self,
+        key: &str,
+        expected: &[u8],
+        new: Bytes,
+    ) -> Result<bool, &'static str> {
+        self.state.compare_and_set(key, expected, new)
+    }
+
+    /// 删除键，如果键存在则返回 `true`。
+    pub(crate) fn del(&mut self, key: &str) -> bool {
+        self.state.del(key)
+    }
+
+    /// 如果键存在则返回 `true`。
+    pub(crate) fn exists(&self, key: &str) -> bool {
+        self.state.entry(key).is_some()
+    }
+
+    /// 将键中存储的整数加一并返回新值。
+    ///
+    /// 键不存在时视为 `0`。如果存储的值不是整数，或结果无法表示为 `u64`，
+    /// 则返回 Redis 风格的错误信息，且不修改存储的值。
+    pub(crate) fn incr(&mut self, key: &str) -> Result<u64, &'static str> {
+        self.state.incr(key)
+    }
+
+    /// 从 `offset` 开始用 `value` 覆盖键中存储的字符串，并返回修改后字符串的长度。
+    ///
+    /// 键不存在时视为空字符串。字符串的长度不足 `offset` 时用零字节填充。
+    /// 键的过期时间保持不变。
+    pub(crate) fn setrange(
+        &mut self,
+        key: &str,
+        offset: usize,
+        value: &[u8],
+    ) -> Result<u64, &'static str> {
+        self.state.setrange(key, offset, value)
+    }
+
+    /// 返回键中存储的字符串在 `start` 和 `end`（包含两端）之间的部分。
+    ///
+    /// 负的偏移量从字符串末尾开始计算，`-1` 表示最后一个字节。超出字符串的范围被截断，
+    /// 键不存在时返回空字符串。
+    pub(crate) fn getrange(
+        &mut self,
+        key: &str,
+        start: i64,
+        end: i64,
+    ) -> Result<Bytes, &'static str> {
+        let value = self.state.getrange(key, start, end)?;
+        self.state.touch(key);
+        Ok(value)
+    }
+
+    /// 设置字符串中第 `offset` 位（每个字节的最高位在前）为 `bit`，并返回该位原来的值。
+    ///
+    /// 键不存在时视为空字符串，字符串长度不足时用零字节填充。键的过期时间保持不变。
+    pub(crate) fn setbit(&mut self, key: &str, offset: u64, bit: bool) -> Result<u8, &'static str> {
+        self.state.setbit(key, offset, bit)
+    }
+
+    /// 返回字符串中第 `offset` 位的值。超出字符串长度的位，以及不存在的键，都视为 `0`。
+    pub(crate) fn getbit(&self, key: &str, offset: u64) -> Result<u8, &'static str> {
+        self.state.getbit(key, offset)
+    }
+
+    /// 统计字符串中被设置的位的数量。
+    ///
+    /// `range` 是可选的字节范围，语义与 `getrange` 相同。
+    pub(crate) fn bitcount(
+        &self,
+        key: &str,
+        range: Option<(i64, i64)>,
+    ) -> Result<u64, &'static str> {
+        self.state.bitcount(key, range)
+    }
+
+    /// 将 `elements` 添加到 `key` 中存储的 HyperLogLog。
+    ///
+    /// 键不存在时创建一个空的 HyperLogLog。如果键被创建，或者估计的基数可能因此改变，则返回 `true`。
+    /// 如果键中存储的不是 HyperLogLog，则返回错误。键的过期时间保持不变。
+    pub(crate) fn pfadd(&mut self, key: &str, elements: &[Bytes]) -> Result<bool, &'static str> {
+        self.state.pfadd(key, elements)
+    }
+
+    /// 返回 `keys` 中存储的 HyperLogLog 的并集的估计基数。不存在的键视为空集合。
+    pub(crate) fn pfcount(&self, keys: &[String]) -> Result<u64, &'static str> {
+        self.state.pfcount(keys)
+    }
+
+    /// 向 `key` 中存储的流添加一个条目，返回条目的 ID。
+    ///
+    /// 键不存在时创建一个新的流。`id` 为 `None` 时自动生成 ID，否则指定的 ID 必须大于流中最后一个条目的 ID。
+    pub(crate) fn xadd(
+        &mut self,
+        key: &str,
+        id: Option<StreamId>,
+        fields: Vec<(Bytes, Bytes)>,
+    ) -> Result<StreamId, &'static str> {
+        let id = self.state.xadd(key, id, fields)?;
+        self.ready_keys.push(key.to_string());
+        Ok(id)
+    }
+
+    /// 返回 `key` 中存储的流在 `start` 和 `end`（包含两端）之间的条目，最多返回 `count` 个。
+    ///
+    /// 键不存在时返回空列表。
+    pub(crate) fn xrange(
+        &self,
+        key: &str,
+        start: StreamId,
+        end: StreamId,
+        count: Option<u64>,
+    ) -> Result<Vec<StreamEntry>, &'static str> {
+        Ok(self
+            .state
+            .stream(key)?
+            .map_or_else(Vec::new, |stream| stream.range(start, end, count)))
+    }
+
+    /// 返回 `key` 中存储的流中 ID 大于 `after` 的条目，最多返回 `count` 个。
+    pub(crate) fn xread(
+        &self,
+        key: &str,
+        after: StreamId,
+        count: Option<u64>,
+    ) -> Result<Vec<StreamEntry>, &'static str> {
+        Ok(self
+            .state
+            .stream(key)?
+            .map_or_else(Vec::new, |stream| stream.read_after(after, count)))
+    }
+
+    /// 返回 `key` 中存储的流中最后一个条目的 ID。键不存在时返回 `0-0`。
+    pub(crate) fn stream_last_id(&self, key: &str) -> Result<StreamId, &'static str> {
+        Ok(self
+            .state
+            .stream(key)?
+            .map_or(StreamId::MIN, Stream::last_id))
+    }
+
+    /// 将 `values` 依次推入 `key` 中存储的列表的头部，返回推入之后列表的长度。
+    ///
+    /// 键不存在时创建一个新的列表。推入元素会唤醒正在 `BLPOP` 中等待的客户端。
+    pub(crate) fn lpush(&mut self, key: &str, values: Vec<Bytes>) -> Result<u64, &'static str> {
+        let len = self.state.push(key, values, true)?;
+        self.ready_keys.push(key.to_string());
+        Ok(len)
+    }
+
+    /// 将 `values` 依次推入 `key` 中存储的列表的尾部，返回推入之后列表的长度。参见 `lpush`。
+    pub(crate) fn rpush(&mut self, key: &str, values: Vec<Bytes>) -> Result<u64, &'static str> {
+        let len = self.state.push(key, values, false)?;
+        self.ready_keys.push(key.to_string());
+        Ok(len)
+    }
+
+    /// 移除并返回 `key` 中存储的列表的第一个元素。键不存在时返回 `None`。
+    pub(crate) fn lpop(&mut self, key: &str) -> Result<Option<Bytes>, &'static str> {
+        self.state.lpop(key)
+    }
+
+    /// 返回 `key` 中存储的列表的长度。键不存在时返回 `0`。
+    pub(crate) fn llen(&self, key: &str) -> Result<u64, &'static str> {
+        Ok(self.state.list(key)?.map_or(0, |list| list.len() as u64))
+    }
+
+    /// 将 `members` 添加到 `key` 中存储的集合，返回新添加的成员数量，已经存在的成员不计算在内。
+    ///
+    /// 键不存在时创建一个新的集合。
+    pub(crate) fn sadd(&mut self, key: &str, members: Vec<Bytes>) -> Result<u64, &'static str> {
+        self.state.sadd(key, members)
+    }
+
+    /// 返回 `key` 中存储的集合的所有成员。键不存在时返回空列表。
+    pub(crate) fn smembers(&mut self, key: &str) -> Result<Vec<Bytes>, &'static str> {
+        let members = self
+            .state
+            .members(key)?
+            .map_or_else(Vec::new, |set| set.iter().cloned().collect());
+        self.state.touch(key);
+        Ok(members)
+    }
+
+    /// 对 `keys` 中存储的集合执行 `op` 运算并返回结果。不存在的键视为空集合。
+    pub(crate) fn set_operation(
+        &self,
+        op: SetOperation,
+        keys: &[String],
+    ) -> Result<Vec<Bytes>, &'static str> {
+        Ok(self.state.set_operation(op, keys)?.into_iter().collect())
+    }
+
+    /// 与 `set_operation` 相同，但是将结果存储到 `destination` 中并返回结果的成员数量。
+    ///
+    /// `destination` 原有的值（无论类型）和过期时间被替换。结果为空集合时删除 `destination`。
+    pub(crate) fn set_operation_store(
+        &mut self,
+        op: SetOperation,
+        destination: &str,
+        keys: &[String],
+    ) -> Result<u64, &'static str> {
+        let set = self.state.set_operation(op, keys)?;
+        Ok(self.state.store_set(destination, set))
+    }
+
+    /// 设置 `key` 中存储的哈希的字段，返回新添加的字段数量。键不存在时创建一个新的哈希。
+    ///
+    /// 与 Redis 一样，被覆盖的字段的过期时间会被移除。
+    pub(crate) fn hset(
+        &mut self,
+        key: &str,
+        pairs: Vec<(Bytes, Bytes)>,
+    ) -> Result<u64, &'static str> {
+        self.state.hset(key, pairs)
+    }
+
+    /// 返回 `key` 中存储的哈希的字段的值。键或者字段不存在时返回 `None`。
+    pub(crate) fn hget(&mut self, key: &str, field: &[u8]) -> Result<Option<Bytes>, &'static str> {
+        let value = self.state.hget(key, field)?;
+        self.state.touch(key);
+        Ok(value)
+    }
+
+    /// 将哈希字段中存储的整数加上 `increment`，返回新值。字段不存在时视为 `0`，字段的过期时间保持不变。
+    pub(crate) fn hincrby(
+        &mut self,
+        key: &str,
+        field: Bytes,
+        increment: i64,
+    ) -> Result<u64, &'static str> {
+        let mut result = 0;
+
+        self.state.update_field(key, field, |current| {
+            let current = match current {
+                Some(data) => std::str::from_utf8(data)
+                    .ok()
+                    .and_then(|s| s.parse::<i64>().ok())
+                    .ok_or("ERR hash value is not an integer")?,
+                None => 0,
+            };
+
+            let value = current
+                .checked_add(increment)
+                .ok_or("ERR increment or decrement would overflow")?;
+
+            if value < 0 {
+                return Err("ERR negative integers are not supported by mini-redis");
+            }
+
+            result = value as u64;
+            Ok(Bytes::from(value.to_string()))
+        })?;
+
+        Ok(result)
+    }
+
+    /// 将哈希字段中存储的浮点数加上 `increment`，返回新值的字符串表示。参见 `hincrby`。
+    pub(crate) fn hincrbyfloat(
+        &mut self,
+        key: &str,
+        field: Bytes,
+        increment: f64,
+    ) -> Result<Bytes, &'static str> {
+        let mut result = Bytes::new();
+
+        self.state.update_field(key, field, |current| {
+            let current = match current {
+                Some(data) => std::str::from_utf8(data)
+                    .ok()
+                    .and_then(|s| s.parse::<f64>().ok())
+                    .filter(|value| value.is_finite())
+                    .ok_or("ERR hash value is not a float")?,
+                None => 0.0,
+            };
+
+            let value = current + increment;
+
+            if !value.is_finite() {
+                return Err("ERR increment would produce NaN or Infinity");
+            }
+
+            result = Bytes::from(value.to_string());
+            Ok(result.clone())
+        })?;
+
+        Ok(result)
+    }
+
+    /// 为哈希的每个字段设置经过 `duration` 之后过期，返回每个字段的结果：
+    /// `Some(1)` 表示设置了过期时间，`Some(2)` 表示 `duration` 为 0、字段被立即删除，`None` 表示键或者字段不存在。
+    ///
+    /// 字段过期时从哈希中移除，最后一个字段过期时键被删除。
+    pub(crate) fn hexpire(
+        &mut self,
+        key: &str,
+        duration: Duration,
+        fields: Vec<Bytes>,
+    ) -> Result<Vec<Option<u64>>, &'static str> {
+        let (results, notify) = self.state.hexpire(key, duration, fields)?;
+        self.notify |= notify;
+        Ok(results)
+    }
+
+    /// 返回距离上次读取键经过的时间，用于 `OBJECT IDLETIME`。键不存在时返回 `None`。
+    pub(crate) fn idle_time(&self, key: &str) -> Option<Duration> {
+        let now = self.state.clock.now();
+        self.state.entry(key).map(|entry| entry.idle_time(now))
+    }
+
+    /// 返回键的访问频率计数器，用于 `OBJECT FREQ`。键不存在时返回 `None`。
+    pub(crate) fn frequency(&self, key: &str) -> Option<u8> {
+        let now = self.state.clock.now();
+        self.state.entry(key).map(|entry| entry.frequency(now))
+    }
+
+    /// 将键中存储的值序列化为 `DUMP` 的格式，不包含键的过期时间。键不存在时返回 `None`。
+    ///
+    /// 序列化在批处理的锁内完成，得到的是键在这一时刻的完整的值。格式参见 `snapshot` 模块。
+    pub(crate) fn dump(&self, key: &str) -> Option<Bytes> {
+        let now = self.state.clock.now();
+        self.state
+            .entry(key)
+            .map(|entry| snapshot::dump(&entry.data, now))
+    }
+
+    /// 以 `DUMP` 生成的 `data` 创建键，`ttl` 之后过期，`None` 表示不会过期。
+    ///
+    /// 键已经存在且 `replace` 为 `false` 时返回 `BUSYKEY` 错误；`data` 的版本不受支持或者校验和不符时返回错误。
+    /// 所有哈希字段都已过期时不创建键。
+    pub(crate) fn restore(
+        &mut self,
+        key: String,
+        ttl: Option<Duration>,
+        data: &[u8],
+        replace: bool,
+    ) -> Result<(), &'static str> {
+        if !replace && self.state.entry(&key).is_some() {
+            return Err("BUSYKEY Target key name already exists.");
+        }
+
+        let value = snapshot::restore(data, self.state.clock.now())
+            .map_err(|_| "ERR DUMP payload version or checksum are wrong")?;
+
+        if matches!(&value, Value::Hash(hash) if hash.is_empty()) {
+            return Ok(());
+        }
+
+        if matches!(&value, Value::List(_) | Value::Stream(_)) {
+            self.ready_keys.push(key.clone());
+        }
+
+        self.notify |= self.state.restore(key, value, ttl);
+        Ok(())
+    }
+}
+
+impl Shared {
+    /// 获取 `State` 的锁。
+    fn lock(&self) -> StateGuard<'_> {
+        StateGuard {
+            state: Some(self.state.lock().unwrap()),
+            observer: self.observer.as_deref(),
+        }
+    }
+
+    /// 清除最多 `limit` 个已过期的键，并返回后台任务接下来应该做什么。
+    ///
+    /// 每次调用的耗时（包括等待锁的时间）和清除的键数量被记录在统计数据中，
+    /// 并以 `histogram.purge_latency_us` 事件输出。
+    fn purge_expired_keys(&self, limit: usize) -> Purge {
+        let start = std::time::Instant::now();
+        let mut purged = 0;
+
+        let next = self.purge_locked(limit, &mut purged);
+
+        if purged > 0 {
+            let latency_us = start.elapsed().as_micros() as u64;
+            let counters = &self.expiration_stats;
+
+            counters
+                .expired_keys
+                .fetch_add(purged as u64, Ordering::Relaxed);
+            counters.cycles.fetch_add(1, Ordering::Relaxed);
+            counters
+                .max_cycle_latency_us
+                .fetch_max(latency_us, Ordering::Relaxed);
+
+            self.latency
+                .record(EXPIRE_CYCLE_EVENT, Duration::from_micros(latency_us));
+
+            debug!(histogram.purge_latency_us = latency_us, purged);
+        }
+
+        next
+    }
+
+    /// 在持有锁的情况下清除最多 `limit` 个已过期的键，清除的数量累加到 `purged`。
+    fn purge_locked(&self, limit: usize, purged: &mut usize) -> Purge {
+        let mut state = self.lock();
+
+        if state.shutdown {
+            // 数据库正在关闭。所有共享状态的句柄已被释放。后台任务应退出。
+            return Purge::Idle;
+        }
+
+        // 这是为了让借用检查器满意。简而言之，`lock()` 返回一个 `MutexGuard`，而不是 `&mut State`。
+        // 借用检查器无法“透过”互斥锁守护体判断同时可变访问 `state.expirations` 和 `state.entries` 是安全的，
+        // 因此我们在循环外部获取 `State` 的“真正”可变引用。
+        let state = &mut *state;
+
+        // 查找所有计划在当前时间之前过期的键。
+        let now = state.clock.now();
+
+        while let Some(when) = state.next_expiration() {
+            if when > now {
+                // 清除完成，`when` 是下一个键过期的时刻。工作线程将等待至此时刻。
+                return Purge::Wait(when - now);
+            }
+
+            if *purged == limit {
+                // 本次清除的键已经达到上限。释放锁，让其他连接有机会执行命令。
+                return Purge::More;
+            }
+
+            match state.expirations.iter().next() {
+                Some((key_when, key)) if *key_when == when => {
+                    // 键已过期，移除它
+                    let key = key.clone();
+                    state.expirations.remove(&(when, key.clone()));
+
+                    if let Some(prev) = state.entries.remove(&key) {
+                        state.forget_fields(&key, &prev.data);
+                        state.record(|| KeyEvent::Expire(key.clone()));
+                        self.expiration_stats
+                            .active_expired_keys
+                            .fetch_add(1, Ordering::Relaxed);
+                    }
+
+                    state.invalidate(&key);
+                }
+                _ => {
+                    // 哈希字段已过期，从哈希中移除它。过期的字段也计入 `purged`。
+                    let (when, key, field) = match state.field_expirations.iter().next() {
+                        Some(expiration) => expiration.clone(),
+                        None => break,
+                    };
+
+                    state
+                        .field_expirations
+                        .remove(&(when, key.clone(), field.clone()));
+                    state.remove_field(&key, &field);
+                }
+            }
+
+            *purged += 1;
+        }
+
+        Purge::Idle
+    }
+
+    /// 如果数据库正在关闭则返回 `true`
+    ///
+    /// 当所有 `Db` 值都已被释放时，`shutdown` 标志被设置，这表明共享状态不再可访问。
+    fn is_shutdown(&self) -> bool {
+        self.lock().shutdown
+    }
+}
+
+impl Deref for StateGuard<'_> {
+    type Target = State;
+
+    fn deref(&self) -> &State {
+        self.state.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for StateGuard<'_> {
+    fn deref_mut(&mut self) -> &mut State {
+        self.state.as_mut().unwrap()
+    }
+}
+
+impl Drop for StateGuard<'_> {
+    fn drop(&mut self) {
+        // 先释放锁，观察者的代码因此不会在持有锁时执行。
+        drop(self.state.take());
+
+        if let Some(observer) = self.observer {
+            observer.dispatch();
+        }
+    }
+}
+
+impl State {
+    /// 获取与key相关联的值。参见 `Db::get`。
+    fn get(&self, key: &str) -> Result<Option<Bytes>, &'static str> {
+        Ok(self.string(key)?.cloned())
+    }
+
+    /// 设置与键相关联的值。参见 `Db::set`。
+    ///
+    /// 当新的过期时间成为**下一个**过期的键时返回 `true`，此时调用者需要在释放锁之后通知后台任务。
+    fn set(&mut self, key: String, value: Bytes, expire: Option<Duration>, keep_ttl: bool) -> bool {
+        // 保留原有的过期时间时，`expirations` 不需要更新，也不需要通知后台任务。
+        if keep_ttl {
+            self.replace_data(&key, value);
+            return false;
+        }
+
+        let expires_at = expire.map(|duration| {
+            self.expiration_stats.record_ttl(duration);
+
+            // `Instant` at which the key expires.
+            self.clock.now() + duration + self.jitter(&key, duration)
+        });
+
+        self.insert_string(key, value, expires_at)
+    }
+
+    /// 以字符串 `value` 创建或替换键，在 `expires_at` 过期。参见 `set` 和 `Batch::set_at`。
+    fn insert_string(&mut self, key: String, value: Bytes, expires_at: Option<Instant>) -> bool {
+        // 仅当新插入的过期时间是下一个要驱逐的键时，才通知工作任务。
+        // 在这种情况下，需要唤醒工作任务以更新其状态。
+        let notify = expires_at.is_some_and(|when| {
+            self.next_expiration()
+                .map(|expiration| expiration > when)
+                .unwrap_or(true)
+        });
+
+        // 将条目插入到 `HashMap` 中。
+        let prev = self.entries.insert(
+            key.clone(),
+            Entry::new(Value::String(value.clone()), expires_at, self.clock.now()),
+        );
+
+        // 如果先前已经存在与该键关联的值**并且**有一个过期时间，
+        // 则必须从 `expirations` 映射中移除关联的条目。这可以避免数据泄漏。
+        if let Some(prev) = prev {
+            if let Some(when) = prev.expires_at {
+                // clear expiration
+                self.expirations.remove(&(when, key.clone()));
+            }
+
+            self.forget_fields(&key, &prev.data);
+        }
+
+        self.record(|| KeyEvent::Set {
+            key: key.clone(),
+            value,
+            ttl: self.ttl(&key),
+        });
+        self.invalidate(&key);
+
+        // 跟踪过期时间。如果在移除之前插入，当当前 `(when, key)` 等于之前的 `(when, key)` 时会导致错误。
+        // 先移除再插入可以避免这种情况。
+        if let Some(when) = expires_at {
+            self.expirations.insert((when, key));
+        }
+
+        notify
+    }
+
+    /// 比较并替换键中存储的值。参见 `Db::compare_and_set`。
+    fn compare_and_set(
+        &mut self,
+        key: &str,
+        expected: &[u8],
+        new: Bytes,
+    ) -> Result<bool, &'static str> {
+        match self.string(key)? {
+            Some(current) if current[..] == *expected => {
+                self.replace_data(key, new);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// 删除键及其过期时间。参见 `Batch::del`。
+    fn del(&mut self, key: &str) -> bool {
+        // 已经过期的键视为不存在。
+        self.remove_expired(key);
+
+        match self.entries.remove(key) {
+            Some(prev) => {
+                if let Some(when) = prev.expires_at {
+                    self.expirations.remove(&(when, key.to_string()));
+                }
+
+                self.forget_fields(key, &prev.data);
+
+                self.record(|| KeyEvent::Delete(key.to_string()));
+                self.invalidate(key);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 以 `value` 创建或替换键，`expire` 之后过期。参见 `Batch::restore`。
+    ///
+    /// 如果需要唤醒后台任务（新的过期时间早于之前所有的过期时间），则返回 `true`。
+    fn restore(&mut self, key: String, value: Value, expire: Option<Duration>) -> bool {
+        let now = self.clock.now();
+        let expires_at = expire.map(|duration| {
+            self.expiration_stats.record_ttl(duration);
+            now + duration
+        });
+
+        // 先移除原有的条目：新值中的哈希字段可能与原有的同名，不能在插入之后再清除原有字段的过期时间。
+        self.remove_expired(&key);
+        if let Some(prev) = self.entries.remove(&key) {
+            if let Some(when) = prev.expires_at {
+                self.expirations.remove(&(when, key.clone()));
+            }
+
+            self.forget_fields(&key, &prev.data);
+        }
+
+        let mut earliest = expires_at;
+
+        if let Value::Hash(hash) = &value {
+            for (name, field) in hash {
+                if let Some(when) = field.expires_at {
+                    self.field_expirations
+                        .insert((when, key.clone(), name.clone()));
+                    earliest = Some(earliest.map_or(when, |earliest| earliest.min(when)));
+                }
+            }
+        }
+
+        let notify = earliest.is_some_and(|when| {
+            self.next_expiration()
+                .is_none_or(|expiration| expiration > when)
+        });
+
+        if let Some(when) = expires_at {
+            self.expirations.insert((when, key.clone()));
+        }
+
+        self.entries
+            .insert(key.clone(), Entry::new(value, expires_at, now));
+        self.record_write(&key);
+        self.invalidate(&key);
+
+        notify
+    }
+
+    /// 覆盖键中存储的字符串的一部分。参见 `Batch::setrange`。
+    fn setrange(&mut self, key: &str, offset: usize, value: &[u8]) -> Result<u64, &'static str> {
+        let current = self.string(key)?.map(|data| &data[..]);
+        let len = current.map_or(0, <[u8]>::len);
+
+        // 与 Redis 一样，写入空字符串不会修改（或创建）键。
+        if value.is_empty() {
+            return Ok(len as u64);
+        }
+
+        let end = offset
+            .checked_add(value.len())
+            .filter(|&end| end <= MAX_STRING_LEN)
+            .ok_or("ERR string exceeds maximum allowed size (proto-max-bulk-len)")?;
+
+        // `Bytes` 是不可变的，因此将当前值复制到 `BytesMut` 中修改，必要时用零字节填充到 `offset`。
+        let mut data = BytesMut::with_capacity(len.max(end));
+        data.extend_from_slice(current.unwrap_or_default());
+        if data.len() < end {
+            data.resize(end, 0);
+        }
+        data[offset..end].copy_from_slice(value);
+
+        let new_len = data.len() as u64;
+        let data = data.freeze();
+
+        self.replace_data(key, data);
+
+        Ok(new_len)
+    }
+
+    /// 返回键中存储的字符串的一部分。参见 `Batch::getrange`。
+    fn getrange(&self, key: &str, start: i64, end: i64) -> Result<Bytes, &'static str> {
+        let data = match self.string(key)? {
+            Some(data) => data,
+            None => return Ok(Bytes::new()),
+        };
+
+        // 返回的 `Bytes` 与存储的值共享内存，不会复制数据。
+        match byte_range(data.len(), start, end) {
+            Some(range) => Ok(data.slice(range)),
+            None => Ok(Bytes::new()),
+        }
+    }
+
+    /// 设置字符串中指定位置的位，并返回该位原来的值。参见 `Batch::setbit`。
+    fn setbit(&mut self, key: &str, offset: u64, bit: bool) -> Result<u8, &'static str> {
+        if offset >= (MAX_STRING_LEN as u64) * 8 {
+            return Err("ERR bit offset is not an integer or out of range");
+        }
+
+        let byte = (offset / 8) as usize;
+        let mask = 0x80 >> (offset % 8);
+
+        let current = self.string(key)?.map_or(&[][..], |data| &data[..]);
+
+        // 与 `setrange` 相同，复制到 `BytesMut` 中修改，必要时用零字节填充。
+        let mut data = BytesMut::with_capacity(current.len().max(byte + 1));
+        data.extend_from_slice(current);
+        if data.len() <= byte {
+            data.resize(byte + 1, 0);
+        }
+
+        let prev = (data[byte] & mask != 0) as u8;
+
+        if bit {
+            data[byte] |= mask;
+        } else {
+            data[byte] &= !mask;
+        }
+
+        self.replace_data(key, data.freeze());
+
+        Ok(prev)
+    }
+
+    /// 返回字符串中指定位置的位。参见 `Batch::getbit`。
+    fn getbit(&self, key: &str, offset: u64) -> Result<u8, &'static str> {
+        let data = match self.string(key)? {
+            Some(data) => data,
+            None => return Ok(0),
+        };
+
+        let byte = offset / 8;
+
+        if byte >= data.len() as u64 {
+            return Ok(0);
+        }
+
+        Ok((data[byte as usize] & (0x80 >> (offset % 8)) != 0) as u8)
+    }
+
+    /// 统计字符串中被设置的位的数量。参见 `Batch::bitcount`。
+    fn bitcount(&self, key: &str, range: Option<(i64, i64)>) -> Result<u64, &'static str> {
+        let data = match self.string(key)? {
+            Some(data) => &data[..],
+            None => return Ok(0),
+        };
+
+        let data = match range {
+            Some((start, end)) => match byte_range(data.len(), start, end) {
+                Some(range) => &data[range],
+                None => return Ok(0),
+            },
+            None => data,
+        };
+
+        Ok(popcount(data))
+    }
+
+    /// 将元素添加到 HyperLogLog。参见 `Batch::pfadd`。
+    fn pfadd(&mut self, key: &str, elements: &[Bytes]) -> Result<bool, &'static str> {
+        let (mut hll, mut changed) = match self.hyperloglog(key)? {
+            Some(hll) => (hll, false),
+            None => (HyperLogLog::new(), true),
+        };
+
+        for element in elements {
+            changed |= hll.add(element);
+        }
+
+        if changed {
+            self.replace_data(key, hll.encode());
+        }
+
+        Ok(changed)
+    }
+
+    /// 估计 HyperLogLog 的并集的基数。参见 `Batch::pfcount`。
+    fn pfcount(&self, keys: &[String]) -> Result<u64, &'static str> {
+        let mut union = HyperLogLog::new();
+
+        for key in keys {
+            if let Some(hll) = self.hyperloglog(key)? {
+                union.merge(&hll);
+            }
+        }
+
+        Ok(union.count())
+    }
+
+    /// 解码键中存储的 HyperLogLog。键不存在时返回 `None`。
+    fn hyperloglog(&self, key: &str) -> Result<Option<HyperLogLog>, &'static str> {
+        match self.string(key)? {
+            Some(data) => HyperLogLog::decode(data)
+                .map(Some)
+                .ok_or(hyperloglog::WRONGTYPE),
+            None => Ok(None),
+        }
+    }
+
+    /// 向流中添加一个条目。参见 `Batch::xadd`。
+    fn xadd(
+        &mut self,
+        key: &str,
+        id: Option<StreamId>,
+        fields: Vec<(Bytes, Bytes)>,
+    ) -> Result<StreamId, &'static str> {
+        self.remove_expired(key);
+
+        let now = self.clock.now();
+
+        let entry = self
+            .entries
+            .entry(key.to_string())
+            .or_insert_with(|| Entry::new(Value::Stream(Stream::default()), None, now));
+
+        let id = match &mut entry.data {
+            Value::Stream(stream) => stream.add(id, fields)?,
+            _ => return Err(WRONGTYPE),
+        };
+
+        self.record(|| KeyEvent::Modify(key.to_string()));
+        self.invalidate(key);
+        Ok(id)
+    }
+
+    /// 向列表的头部（`front` 为 `true`）或尾部推入元素。参见 `Batch::lpush` 和 `Batch::rpush`。
+    fn push(&mut self, key: &str, values: Vec<Bytes>, front: bool) -> Result<u64, &'static str> {
+        self.remove_expired(key);
+
+        let now = self.clock.now();
+
+        let entry = self
+            .entries
+            .entry(key.to_string())
+            .or_insert_with(|| Entry::new(Value::List(VecDeque::new()), None, now));
+
+        let list = match &mut entry.data {
+            Value::List(list) => list,
+            _ => return Err(WRONGTYPE),
+        };
+
+        for value in values {
+            if front {
+                list.push_front(value);
+            } else {
+                list.push_back(value);
+            }
+        }
+
+        let len = list.len() as u64;
+        self.record(|| KeyEvent::Modify(key.to_string()));
+        self.invalidate(key);
+        Ok(len)
+    }
+
+    /// 移除并返回列表的第一个元素，列表变为空时删除键。参见 `Batch::lpop`。
+    fn lpop(&mut self, key: &str) -> Result<Option<Bytes>, &'static str> {
+        self.remove_expired(key);
+
+        let list = match self.entries.get_mut(key).map(|entry| &mut entry.data) {
+            Some(Value::List(list)) => list,
+            Some(_) => return Err(WRONGTYPE),
+            None => return Ok(None),
+        };
+
+        let value = list.pop_front();
+
+        if list.is_empty() {
+            self.del(key);
+        } else {
+            self.record(|| KeyEvent::Modify(key.to_string()));
+            self.invalidate(key);
+        }
+
+        Ok(value)
+    }
+
+    /// 将成员添加到集合。参见 `Batch::sadd`。
+    fn sadd(&mut self, key: &str, members: Vec<Bytes>) -> Result<u64, &'static str> {
+        self.remove_expired(key);
+
+        let now = self.clock.now();
+
+        let entry = self
+            .entries
+            .entry(key.to_string())
+            .or_insert_with(|| Entry::new(Value::Set(BTreeSet::new()), None, now));
+
+        let set = match &mut entry.data {
+            Value::Set(set) => set,
+            _ => return Err(WRONGTYPE),
+        };
+
+        let len = set.len();
+        set.extend(members);
+        let added = (set.len() - len) as u64;
+
+        if added > 0 {
+            self.record(|| KeyEvent::Modify(key.to_string()));
+            self.invalidate(key);
+        }
+
+        Ok(added)
+    }
+
+    /// 计算集合运算的结果。参见 `Batch::set_operation`。
+    ///
+    /// 与 Redis 一样，任何一个键中存储的不是集合时都返回错误，即使结果已经可以确定为空。
+    fn set_operation(
+        &self,
+        op: SetOperation,
+        keys: &[String],
+    ) -> Result<BTreeSet<Bytes>, &'static str> {
+        let sets = keys
+            .iter()
+            .map(|key| self.members(key))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let empty = BTreeSet::new();
+        let mut sets = sets.into_iter().map(|set| set.unwrap_or(&empty));
+
+        let mut result = match sets.next() {
+            Some(first) => first.clone(),
+            None => return Ok(BTreeSet::new()),
+        };
+
+        for set in sets {
+            match op {
+                SetOperation::Inter => result.retain(|member| set.contains(member)),
+                SetOperation::Union => result.extend(set.iter().cloned()),
+                SetOperation::Diff => result.retain(|member| !set.contains(member)),
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// 将集合存储到 `key` 中，替换原有的值并移除过期时间，返回集合的成员数量。
+    /// 参见 `Batch::set_operation_store`。
+    fn store_set(&mut self, key: &str, set: BTreeSet<Bytes>) -> u64 {
+        let len = set.len() as u64;
+
+        // 结果为空集合时只删除键，与列表一样，不存储空的集合。
+        self.del(key);
+
+        if !set.is_empty() {
+            let now = self.clock.now();
+            self.entries
+                .insert(key.to_string(), Entry::new(Value::Set(set), None, now));
+
+            self.record(|| KeyEvent::Modify(key.to_string()));
+            self.invalidate(key);
+        }
+
+        len
+    }
+
+    /// 设置哈希字段的值。参见 `Batch::hset`。
+    fn hset(&mut self, key: &str, pairs: Vec<(Bytes, Bytes)>) -> Result<u64, &'static str> {
+        self.remove_expired(key);
+
+        let now = self.clock.now();
+
+        let entry = self
+            .entries
+            .entry(key.to_string())
+            .or_insert_with(|| Entry::new(Value::Hash(HashMap::new()), None, now));
+
+        let hash = match &mut entry.data {
+            Value::Hash(hash) => hash,
+            _ => return Err(WRONGTYPE),
+        };
+
+        let now = self.clock.now();
+        let mut added = 0;
+        let mut forgotten = vec![];
+
+        for (field, value) in pairs {
+            let new = Field {
+                value,
+                expires_at: None,
+            };
+
+            match hash.insert(field.clone(), new) {
+                // 与 Redis 一样，覆盖字段的值会移除字段的过期时间。已经过期的字段视为新添加的字段。
+                Some(Field {
+                    expires_at: Some(when),
+                    ..
+                }) => {
+                    if when <= now {
+                        added += 1;
+                    }
+
+                    forgotten.push((when, key.to_string(), field));
+                }
+                Some(_) => {}
+                None => added += 1,
+            }
+        }
+
+        for expiration in forgotten {
+            self.field_expirations.remove(&expiration);
+        }
+
+        self.record(|| KeyEvent::Modify(key.to_string()));
+        self.invalidate(key);
+        Ok(added)
+    }
+
+    /// 返回哈希字段的值。参见 `Batch::hget`。
+    fn hget(&self, key: &str, field: &[u8]) -> Result<Option<Bytes>, &'static str> {
+        let now = self.clock.now();
+
+        Ok(self
+            .hash(key)?
+            .and_then(|hash| hash.get(field))
+            .filter(|field| field.expires_at.is_none_or(|when| when > now))
+            .map(|field| field.value.clone()))
+    }
+
+    /// 根据哈希字段当前的值（字段不存在时为 `None`）计算新的值并写入，保留字段的过期时间。
+    /// `HINCRBY` 和 `HINCRBYFLOAT` 通过此方法修改字段。
+    ///
+    /// `update` 返回错误时不会修改（或创建）键。
+    fn update_field(
+        &mut self,
+        key: &str,
+        field: Bytes,
+        update: impl FnOnce(Option<&Bytes>) -> Result<Bytes, &'static str>,
+    ) -> Result<(), &'static str> {
+        self.remove_expired(key);
+        self.remove_expired_field(key, &field);
+
+        let current = self.hash(key)?.and_then(|hash| hash.get(&field));
+        let value = update(current.map(|field| &field.value))?;
+
+        let now = self.clock.now();
+
+        let entry = self
+            .entries
+            .entry(key.to_string())
+            .or_insert_with(|| Entry::new(Value::Hash(HashMap::new()), None, now));
+
+        if let Value::Hash(hash) = &mut entry.data {
+            hash.entry(field)
+                .and_modify(|field| field.value = value.clone())
+                .or_insert(Field {
+                    value,
+                    expires_at: None,
+                });
+        }
+
+        self.record(|| KeyEvent::Modify(key.to_string()));
+        self.invalidate(key);
+        Ok(())
+    }
+
+    /// 设置哈希字段的过期时间。参见 `Batch::hexpire`。
+    ///
+    /// 返回每个字段的结果，以及新的过期时间是否成为**下一个**过期的时刻（此时需要通知后台任务）。
+    fn hexpire(
+        &mut self,
+        key: &str,
+        duration: Duration,
+        fields: Vec<Bytes>,
+    ) -> Result<(Vec<Option<u64>>, bool), &'static str> {
+        self.remove_expired(key);
+
+        if self.hash(key)?.is_none() {
+            return Ok((vec![None; fields.len()], false));
+        }
+
+        let when = self.clock.now() + duration;
+        let notify = self
+            .next_expiration()
+            .map(|expiration| expiration > when)
+            .unwrap_or(true);
+
+        let mut results = Vec::with_capacity(fields.len());
+
+        for field in fields {
+            self.remove_expired_field(key, &field);
+
+            let prev = match self.entries.get_mut(key).map(|entry| &mut entry.data) {
+                Some(Value::Hash(hash)) => match hash.get_mut(&field) {
+                    Some(entry) if duration.is_zero() => entry.expires_at,
+                    Some(entry) => entry.expires_at.replace(when),
+                    None => {
+                        results.push(None);
+                        continue;
+                    }
+                },
+                // 哈希的最后一个字段在之前的迭代中被删除，键已经不存在。
+                _ => {
+                    results.push(None);
+                    continue;
+                }
+            };
+
+            if let Some(prev) = prev {
+                self.field_expirations
+                    .remove(&(prev, key.to_string(), field.clone()));
+            }
+
+            if duration.is_zero() {
+                // 与 Redis 一样，过期时间为 0 时立即删除字段。
+                self.remove_field(key, &field);
+                results.push(Some(2));
+            } else {
+                self.field_expirations
+                    .insert((when, key.to_string(), field));
+                results.push(Some(1));
+            }
+        }
+
+        // 没有任何字段存在时哈希没有变化，不报告给观察者。
+        if results.iter().any(Option::is_some) {
+            self.record(|| KeyEvent::Modify(key.to_string()));
+        }
+
+        self.invalidate(key);
+        Ok((results, notify && !duration.is_zero()))
+    }
+
+    /// 从哈希中移除字段，哈希变为空时删除键。字段在 `field_expirations` 中的条目由调用者负责移除。
+    fn remove_field(&mut self, key: &str, field: &[u8]) {
+        let now_empty = match self.entries.get_mut(key).map(|entry| &mut entry.data) {
+            Some(Value::Hash(hash)) => {
+                hash.remove(field);
+                hash.is_empty()
+            }
+            _ => return,
+        };
+
+        if now_empty {
+            self.del(key);
+        } else {
+            self.record(|| KeyEvent::Modify(key.to_string()));
+            self.invalidate(key);
+        }
+    }
+
+    /// 如果哈希字段已经过期但后台任务尚未清除它，立即删除它。参见 `remove_expired`。
+    fn remove_expired_field(&mut self, key: &str, field: &Bytes) {
+        let when = match self.hash(key) {
+            Ok(Some(hash)) => match hash.get(field).and_then(|field| field.expires_at) {
+                Some(when) if when <= self.clock.now() => when,
+                _ => return,
+            },
+            _ => return,
+        };
+
+        self.field_expirations
+            .remove(&(when, key.to_string(), field.clone()));
+        self.remove_field(key, field);
+    }
+
+    /// 哈希被删除或者替换时，移除其字段在 `field_expirations` 中的条目，避免残留无效的条目。
+    fn forget_fields(&mut self, key: &str, data: &Value) {
+        if let Value::Hash(hash) = data {
+            for (field, entry) in hash {
+                if let Some(when) = entry.expires_at {
+                    self.field_expirations
+                        .remove(&(when, key.to_string(), field.clone()));
+                }
+            }
+        }
+    }
+
+    /// 返回键中存储的字符串。键不存在时返回 `None`，键中存储的不是字符串时返回错误。
+    fn string(&self, key: &str) -> Result<Option<&Bytes>, &'static str> {
+        match self.entry(key).map(|entry| &entry.data) {
+            Some(Value::String(data)) => Ok(Some(data)),
+            Some(_) => Err(WRONGTYPE),
+            None => Ok(None),
+        }
+    }
+
+    /// 返回键中存储的流。键不存在时返回 `None`，键中存储的不是流时返回错误。
+    fn stream(&self, key: &str) -> Result<Option<&Stream>, &'static str> {
+        match self.entry(key).map(|entry| &entry.data) {
+            Some(Value::Stream(stream)) => Ok(Some(stream)),
+            Some(_) => Err(WRONGTYPE),
+            None => Ok(None),
+        }
+    }
+
+    /// 返回键中存储的列表。键不存在时返回 `None`，键中存储的不是列表时返回错误。
+    fn list(&self, key: &str) -> Result<Option<&VecDeque<Bytes>>, &'static str> {
+        match self.entry(key).map(|entry| &entry.data) {
+            Some(Value::List(list)) => Ok(Some(list)),
+            Some(_) => Err(WRONGTYPE),
+            None => Ok(None),
+        }
+    }
+
+    /// 返回键中存储的集合。键不存在时返回 `None`，键中存储的不是集合时返回错误。
+    fn members(&self, key: &str) -> Result<Option<&BTreeSet<Bytes>>, &'static str> {
+        match self.entry(key).map(|entry| &entry.data) {
+            Some(Value::Set(set)) => Ok(Some(set)),
+            Some(_) => Err(WRONGTYPE),
+            None => Ok(None),
+        }
+    }
+
+    /// 返回键中存储的哈希。键不存在时返回 `None`，键中存储的不是哈希时返回错误。
+    fn hash(&self, key: &str) -> Result<Option<&HashMap<Bytes, Field>>, &'static str> {
+        match self.entry(key).map(|entry| &entry.data) {
+            Some(Value::Hash(hash)) => Ok(Some(hash)),
+            Some(_) => Err(WRONGTYPE),
+            None => Ok(None),
+        }
+    }
+
+    /// 替换键中存储的字符串，保留键原有的过期时间。键不存在时创建一个没有过期时间的键。
+    fn replace_data(&mut self, key: &str, value: Bytes) {
+        let data = Value::String(value.clone());
+
+        // 已经过期的键视为不存在，新的值不保留过去的过期时间。
+        self.remove_expired(key);
+
+        match self.entries.get_mut(key) {
+            Some(entry) => {
+                let prev = std::mem::replace(&mut entry.data, data);
+                self.forget_fields(key, &prev);
+            }
+            None => {
+                let now = self.clock.now();
+                self.entries
+                    .insert(key.to_string(), Entry::new(data, None, now));
+            }
+        }
+
+        self.record(|| KeyEvent::Set {
+            key: key.to_string(),
+            value,
+            ttl: self.ttl(key),
+        });
+        self.invalidate(key);
+    }
+
+    /// 返回键剩余的生存时间。键不存在或者不会过期时返回 `None`。
+    fn ttl(&self, key: &str) -> Option<Duration> {
+        let now = self.clock.now();
+
+        self.entry(key)?
+            .expires_at
+            .map(|when| when.saturating_duration_since(now))
+    }
+
+    /// 记录一次键空间变化，释放锁之后交给 `DbObserver`。没有安装观察者时不做任何事情，也不会调用 `event`。
+    fn record(&self, event: impl FnOnce() -> KeyEvent) {
+        if let Some(observer) = &self.observer {
+            observer.record(event());
+        }
+    }
+
+    /// 将键被写入报告给 `DbObserver`：键中存储的是字符串时记录为 `Set`，其他类型记录为 `Modify`。
+    fn record_write(&self, key: &str) {
+        self.record(|| match self.entries.get(key).map(|entry| &entry.data) {
+            Some(Value::String(value)) => KeyEvent::Set {
+                key: key.to_string(),
+                value: value.clone(),
+                ttl: self.ttl(key),
+            },
+            _ => KeyEvent::Modify(key.to_string()),
+        });
+    }
+
+    /// 记录一次对键的读取，更新其最后访问时间和访问频率。键不存在或者已经过期时不做任何事情。
+    ///
+    /// 写入操作不调用此方法：新写入的条目以写入的时刻作为最后访问时间，修改已有的条目不影响这两项元数据。
+    fn touch(&mut self, key: &str) {
+        let now = self.clock.now();
+
+        if let Some(entry) = self.entries.get_mut(key) {
+            if entry.is_live(now) {
+                entry.touch(now);
+            }
+        }
+    }
+
+    /// 返回所有未过期的条目。已经过期但后台任务尚未清除的条目被跳过。
+    fn live_entries(&self) -> impl Iterator<Item = (&String, &Entry)> {
+        let now = self.clock.now();
+
+        self.entries
+            .iter()
+            .filter(move |(_, entry)| entry.is_live(now))
+    }
+
+    /// 返回键的条目。键已经过期、但后台任务尚未清除它时，同样返回 `None`。
+    fn entry(&self, key: &str) -> Option<&Entry> {
+        let now = self.clock.now();
+
+        self.entries.get(key).filter(|entry| entry.is_live(now))
+    }
+
+    /// 如果键已经过期但后台任务尚未清除它，立即删除它。
+    ///
+    /// 修改键的操作在执行之前调用此方法，以免修改后的值继承已经过去的过期时间。
+    fn remove_expired(&mut self, key: &str) {
+        let now = self.clock.now();
+
+        match self.entries.get(key) {
+            Some(entry) if !entry.is_live(now) => {}
+            _ => return,
+        }
+
+        if let Some(prev) = self.entries.remove(key) {
+            self.forget_fields(key, &prev.data);
+
+            if let Some(when) = prev.expires_at {
+                self.expirations.remove(&(when, key.to_string()));
+            }
+
+            self.expiration_stats
+                .lazy_expired_keys
+                .fetch_add(1, Ordering::Relaxed);
+            self.record(|| KeyEvent::Expire(key.to_string()));
+        }
+
+        self.invalidate(key);
+    }
+
+    /// 通知开启了 `CLIENT TRACKING` 的连接和 `key` 的观察者 `key` 已被修改。没有这样的连接和观察者时不做任何事情。
+    ///
+    /// 在修改完成之后调用，观察者因此收到修改之后的值。
+    fn invalidate(&mut self, key: &str) {
+        if self.invalidations.receiver_count() > 0 {
+            let _ = self.invalidations.send(key.to_string());
+        }
+
+        if let Some(tx) = self.watchers.get(key) {
+            // 所有接收者都已丢弃时移除发送器，避免观察过的键无限增长。
+            if tx.receiver_count() == 0 {
+                self.watchers.remove(key);
+                return;
+            }
+
+            let value = self.watched_value(key);
+            tx.send_if_modified(|current| update_watched(current, value));
+        }
+    }
+
+    /// 返回 `Db::watch_key` 的观察者看到的键的值：键中存储的字符串，键不存在或者存储的不是字符串时为 `None`。
+    fn watched_value(&self, key: &str) -> Option<Bytes> {
+        self.string(key).ok().flatten().cloned()
+    }
+
+    /// 将键中存储的整数加一。参见 `Batch::incr`。
+    fn incr(&mut self, key: &str) -> Result<u64, &'static str> {
+        const NOT_AN_INTEGER: &str = "ERR value is not an integer or out of range";
+
+        let current = match self.string(key)? {
+            Some(data) => std::str::from_utf8(data)
+                .ok()
+                .and_then(|s| s.parse::<i64>().ok())
+                .ok_or(NOT_AN_INTEGER)?,
+            None => 0,
+        };
+
+        let value = current
+            .checked_add(1)
+            .ok_or("ERR increment or decrement would overflow")?;
+
+        if value < 0 {
+            return Err("ERR negative integers are not supported by mini-redis");
+        }
+
+        let data = Bytes::from(value.to_string());
+
+        self.replace_data(key, data);
+
+        Ok(value as u64)
+    }
+
+    /// 返回过期时长 `duration` 随机延长的时间，参见 `Db::set_ttl_jitter`。
+    fn jitter(&self, key: &str, duration: Duration) -> Duration {
+        if self.ttl_jitter == 0 {
+            return Duration::ZERO;
+        }
+
+        // 不需要密码学意义上的随机数，对键和当前时间求哈希就足以把同一批键分散开。
+        let mut hasher = self.jitter_seed.build_hasher();
+        key.hash(&mut hasher);
+        self.clock.now().hash(&mut hasher);
+        let fraction = hasher.finish() as f64 / u64::MAX as f64;
+
+        duration.mul_f64(fraction * f64::from(self.ttl_jitter) / 100.0)
+    }
+
+    /// 返回下一个键或者哈希字段过期的时刻。
+    fn next_expiration(&self) -> Option<Instant> {
+        let key = self
+            .expirations
+            .iter()
+            .next()
+            .map(|expiration| expiration.0);
+        let field = self
+            .field_expirations
+            .iter()
+            .next()
+            .map(|expiration| expiration.0);
+
+        key.into_iter().chain(field).min()
+    }
+}
+
+/// 将观察者持有的值更新为 `value`，返回值是否改变。参见 `Db::watch_key`。
+fn update_watched(current: &mut Option<Bytes>, value: Option<Bytes>) -> bool {
+    if *current == value {
+        return false;
+    }
+
+    *current = value;
+    true
+}
+
+/// 将 Redis 风格的字节范围（包含两端，负数从末尾开始计算）转换为长度为 `len` 的字符串中的下标范围。
+///
+/// 范围被截断到字符串之内。截断后范围为空时返回 `None`。
+fn byte_range(len: usize, start: i64, end: i64) -> Option<RangeInclusive<usize>> {
+    let len = len as i64;
+
+    let start = if start < 0 {
+        (len + start).max(0)
+    } else {
+        start
+    };
+    let end = if end < 0 { len + end } else { end.min(len - 1) };
+
+    if start > end || len == 0 {
+        return None;
+    }
+
+    Some(start as usize..=end as usize)
+}
+
+/// 统计 `data` 中被设置的位的数量。
+///
+/// 每次处理 8 个字节，使用 `u64::count_ones`（在大多数平台上编译为一条 `popcnt` 指令），剩余的字节逐个统计。
+fn popcount(data: &[u8]) -> u64 {
+    let mut chunks = data.chunks_exact(8);
+
+    let mut count: u64 = chunks
+        .by_ref()
+        .map(|chunk| {
+            let mut word = [0; 8];
+            word.copy_from_slice(chunk);
+            u64::from_ne_bytes(word).count_ones() as u64
+        })
+        .sum();
+
+    count += chunks
+        .remainder()
+        .iter()
+        .map(|byte| byte.count_ones() as u64)
+        .sum::<u64>();
+
+    count
+}
+
+/// 后台任务执行的例程。
+///
 /// 等待通知。在收到通知时，从共享状态句柄中清除任何已过期的键。如果设置了 `shutdown`，则终止任务。
-async fn purge_expired_tasks(shared: Arc<Shared>) {
+#[cfg(not(mini_redis_loom))]
+async fn purge_expired_tasks(shared: sync::Arc<Shared>) {
     // 如果关闭标志被设置，则任务应退出。
     while !shared.is_shutdown() {
-        // 清除所有已过期的键。该函数返回下一个键过期的时刻。
-        // 工作线程应该等待直到该时刻过去，然后再次清除。
-        if let Some(when) = shared.purge_expired_keys() {
-            // 等待直到下一个键过期或直到收到后台任务的通知。
-            // 如果任务收到通知，则必须重新加载其状态，因为新的键被设置为提前过期。这是通过循环完成的。
-            tokio::select! {
-                _ = time::sleep_until(when) => {}
-                _ = shared.background_task.notified() => {}
+        // 清除已过期的键。该函数返回距离下一个键过期的时间。
+        // 工作线程应该等待这段时间，然后再次清除。
+        match shared.purge_expired_keys(PURGE_BATCH_SIZE) {
+            // 一次清除的键达到了上限。让出执行权，使同一个工作线程上的其他任务有机会运行，然后继续清除。
+            Purge::More => tokio::task::yield_now().await,
+            Purge::Wait(delay) => {
+                // 等待直到下一个键过期或直到收到后台任务的通知。
+                // 如果任务收到通知，则必须重新加载其状态，因为新的键被设置为提前过期。这是通过循环完成的。
+                tokio::select! {
+                    _ = tokio::time::sleep(delay) => {}
+                    _ = shared.background_task.notified() => {}
+                }
             }
-        } else {
             // 没有键在未来过期。等待任务通知。
-            shared.background_task.notified().await;
+            Purge::Idle => shared.background_task.notified().await,
         }
     }
 
     debug!("Purge background task shut down")
-}
\ No newline at end of file
+}
+
+/// `cfg(mini_redis_loom)` 构建中后台任务执行的例程。
+///
+/// loom 中没有计时器，因此只在收到通知时清除过期的键。测试通过推进 `ManualClock` 并写入新的键来驱动清除。
+#[cfg(mini_redis_loom)]
+fn purge_expired_blocking(shared: &Shared) {
+    while !shared.is_shutdown() {
+        while shared.purge_expired_keys(PURGE_BATCH_SIZE) == Purge::More {}
+        shared.background_task.wait();
+    }
+
+    debug!("Purge background task shut down")
+}
+
+#[cfg(all(mini_redis_loom, test))]
+mod loom_tests;
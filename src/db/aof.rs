@@ -0,0 +1,484 @@
+//! 追加写入文件（AOF）。
+//!
+//! 开启 AOF 时，连接处理程序通过 `Db::batch_logged` 执行写命令，在同一次加锁下将命令的帧按照 RESP 格式
+//! 交给一个专门的写入任务，因此文件中命令的顺序与执行的顺序一致。写入任务把同时到达的命令合并为一次写入，
+//! 再按照 `AppendFsync` 策略调用 `fsync`。服务器启动时按顺序重新执行文件中的命令。
+//!
+//! 文件中记录的是写命令的效果，参见 `Command::execute_propagated`：`BLPOP` 被记录为 `LPOP`，脚本被记录为
+//! `redis.call` 执行的写命令，一次执行产生的多个效果被包装为一个 `EVALBATCH`。`SET key value EX 10` 等相对的过期时间
+//! 被记录为绝对的 Unix 时间戳，加载时键在原来的时刻过期，而不是从加载的时刻重新计算。
+
+use super::Db;
+use crate::frame::Limits;
+use crate::{Command, Frame};
+
+use bytes::{Bytes, BytesMut};
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::{self, Duration, Instant, MissedTickBehavior};
+use tracing::{error, info, warn};
+
+/// 写入任务一次最多合并的请求数量。
+const MAX_BATCH: usize = 1024;
+
+/// `everysec` 策略调用 `fsync` 的间隔，写入失败之后也以此间隔重试。
+const FSYNC_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 写入 AOF 失败时发送给客户端的错误信息。
+const MISCONF: &str = "MISCONF Errors writing to the AOF file";
+
+/// 何时调用 `fsync` 将 AOF 写入磁盘，与 Redis 的 `appendfsync` 选项相同。
+///
+/// 无论哪种策略，写入或者 `fsync` 失败之后，写命令都以 `MISCONF` 错误被拒绝，直到写入任务重试成功为止。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum AppendFsync {
+    /// 每次写入之后调用 `fsync`，写命令在写入磁盘之后才响应。最安全也最慢。
+    Always,
+
+    /// 每秒最多调用一次 `fsync`，宕机时最多丢失约一秒的写命令。
+    #[default]
+    EverySec,
+
+    /// 从不主动调用 `fsync`，由操作系统决定何时写入磁盘。
+    No,
+}
+
+impl fmt::Display for AppendFsync {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AppendFsync::Always => "always",
+            AppendFsync::EverySec => "everysec",
+            AppendFsync::No => "no",
+        }
+        .fmt(fmt)
+    }
+}
+
+impl FromStr for AppendFsync {
+    type Err = crate::Error;
+
+    fn from_str(src: &str) -> crate::Result<AppendFsync> {
+        match &src.to_lowercase()[..] {
+            "always" => Ok(AppendFsync::Always),
+            "everysec" => Ok(AppendFsync::EverySec),
+            "no" => Ok(AppendFsync::No),
+            _ => Err(format!(
+                "invalid appendfsync policy `{}`, expected always, everysec or no",
+                src
+            )
+            .into()),
+        }
+    }
+}
+
+/// 写入任务的句柄，保存在 `Shared` 中。
+#[derive(Debug)]
+pub(super) struct Aof {
+    /// 向写入任务发送请求
+    tx: mpsc::UnboundedSender<Request>,
+
+    /// `fsync` 策略
+    policy: AppendFsync,
+
+    /// 与写入任务共享的统计数据
+    counters: Arc<AofCounters>,
+}
+
+/// 发送给写入任务的请求。
+#[derive(Debug)]
+enum Request {
+    /// 追加 `data`。`always` 策略下写入并 `fsync` 之后通知 `synced`。
+    Append {
+        data: Bytes,
+        synced: Option<oneshot::Sender<()>>,
+    },
+
+    /// 写入之前的所有数据并 `fsync`，完成后通知。服务器关闭时使用。
+    Sync(oneshot::Sender<()>),
+}
+
+/// AOF 的统计数据。
+#[derive(Debug, Default)]
+struct AofCounters {
+    /// 已经交给写入任务、但尚未写入文件的字节数
+    pending_bytes: AtomicU64,
+
+    /// 文件的字节数
+    size: AtomicU64,
+
+    /// 调用 `fsync` 的次数
+    fsyncs: AtomicU64,
+
+    /// 最后一次 `fsync` 耗费的时间（微秒）
+    last_fsync_latency_us: AtomicU64,
+
+    /// 最后一次写入或者 `fsync` 是否出错
+    last_write_failed: AtomicBool,
+}
+
+/// AOF 的状态，由 `INFO persistence` 报告。
+#[derive(Debug, Clone)]
+pub(crate) struct AofStats {
+    /// `fsync` 策略
+    pub(crate) policy: AppendFsync,
+
+    /// 已经交给写入任务、但尚未写入文件的字节数
+    pub(crate) pending_bytes: u64,
+
+    /// 文件的字节数
+    pub(crate) size: u64,
+
+    /// 调用 `fsync` 的次数
+    pub(crate) fsyncs: u64,
+
+    /// 最后一次 `fsync` 耗费的时间（微秒）
+    pub(crate) last_fsync_latency_us: u64,
+
+    /// 最后一次写入或者 `fsync` 是否成功
+    pub(crate) last_write_ok: bool,
+}
+
+impl Aof {
+    /// 打开 `path` 处的 AOF 并启动写入任务，之后的命令追加到文件末尾。
+    pub(super) fn open(path: &Path, policy: AppendFsync) -> io::Result<Aof> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        let counters = Arc::new(AofCounters::default());
+        counters
+            .size
+            .store(file.metadata()?.len(), Ordering::Relaxed);
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let writer = Writer {
+            file: tokio::fs::File::from_std(file),
+            policy,
+            counters: counters.clone(),
+            dirty: false,
+            failed: false,
+        };
+        crate::task::spawn("mini-redis::aof", writer.run(rx));
+
+        Ok(Aof {
+            tx,
+            policy,
+            counters,
+        })
+    }
+
+    /// 将编码后的写命令 `data` 交给写入任务，不等待写入完成。在持有数据库的锁时调用。
+    pub(super) fn send(&self, data: Bytes) -> AofWrite {
+        let len = data.len() as u64;
+        let (synced, done) = match self.policy {
+            AppendFsync::Always => {
+                let (tx, rx) = oneshot::channel();
+                (Some(tx), Some(rx))
+            }
+            _ => (None, None),
+        };
+
+        self.counters
+            .pending_bytes
+            .fetch_add(len, Ordering::Relaxed);
+
+        let request = Request::Append { data, synced };
+
+        if self.tx.send(request).is_err() {
+            self.counters
+                .pending_bytes
+                .fetch_sub(len, Ordering::Relaxed);
+            return AofWrite(Err("ERR AOF writer is not running"));
+        }
+
+        AofWrite(Ok(done))
+    }
+
+    /// 最后一次写入或者 `fsync` 失败时返回发送给客户端的错误信息，此时应拒绝写命令。
+    pub(super) fn write_error(&self) -> Option<&'static str> {
+        if self.counters.last_write_failed.load(Ordering::Relaxed) {
+            Some(MISCONF)
+        } else {
+            None
+        }
+    }
+
+    /// 等待之前追加的所有数据写入磁盘。
+    pub(super) async fn sync(&self) {
+        let (tx, rx) = oneshot::channel();
+
+        if self.tx.send(Request::Sync(tx)).is_ok() {
+            let _ = rx.await;
+        }
+    }
+
+    /// 返回 AOF 的状态。
+    pub(super) fn stats(&self) -> AofStats {
+        AofStats {
+            policy: self.policy,
+            pending_bytes: self.counters.pending_bytes.load(Ordering::Relaxed),
+            size: self.counters.size.load(Ordering::Relaxed),
+            fsyncs: self.counters.fsyncs.load(Ordering::Relaxed),
+            last_fsync_latency_us: self.counters.last_fsync_latency_us.load(Ordering::Relaxed),
+            last_write_ok: !self.counters.last_write_failed.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// 交给写入任务的一次追加，由 `Db::batch_logged` 返回。
+#[derive(Debug)]
+pub(crate) struct AofWrite(Result<Option<oneshot::Receiver<()>>, &'static str>);
+
+impl AofWrite {
+    /// 不需要等待的追加，未开启 AOF 时使用。
+    pub(crate) fn done() -> AofWrite {
+        AofWrite(Ok(None))
+    }
+
+    /// `always` 策略下等待追加的命令写入磁盘，其他策略下立即返回。写入失败时返回发送给客户端的错误信息。
+    pub(crate) async fn wait(self) -> Result<(), &'static str> {
+        match self.0? {
+            Some(synced) => synced.await.map_err(|_| MISCONF),
+            None => Ok(()),
+        }
+    }
+}
+
+/// 写入任务的状态。
+struct Writer {
+    /// 以追加模式打开的 AOF
+    file: tokio::fs::File,
+
+    /// `fsync` 策略
+    policy: AppendFsync,
+
+    /// 与 `Aof` 共享的统计数据
+    counters: Arc<AofCounters>,
+
+    /// 上一次 `fsync` 之后是否写入过数据
+    dirty: bool,
+
+    /// 最后一次写入或者 `fsync` 是否失败。失败的写入可能在文件末尾留下不完整的命令，
+    /// 在 `recover` 截断文件之前不再追加数据。
+    failed: bool,
+}
+
+impl Writer {
+    /// 接收并写入请求，直到所有 `Aof` 句柄都被丢弃。
+    async fn run(mut self, mut rx: mpsc::UnboundedReceiver<Request>) {
+        let mut ticker = time::interval(FSYNC_INTERVAL);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                request = rx.recv() => match request {
+                    Some(request) => {
+                        // 合并已经到达的请求，一次写入。
+                        let mut batch = vec![request];
+                        while batch.len() < MAX_BATCH {
+                            match rx.try_recv() {
+                                Ok(request) => batch.push(request),
+                                Err(_) => break,
+                            }
+                        }
+
+                        self.write_batch(batch).await;
+                    }
+                    None => break,
+                },
+                _ = ticker.tick(), if self.failed || (self.policy == AppendFsync::EverySec && self.dirty) => {
+                    if self.failed {
+                        self.recover().await;
+                    } else {
+                        let _ = self.fsync().await;
+                    }
+                }
+            }
+        }
+
+        if self.failed {
+            self.recover().await;
+        } else if self.dirty && self.policy != AppendFsync::No {
+            let _ = self.fsync().await;
+        }
+    }
+
+    /// 写入一批请求，并按照策略调用 `fsync`。
+    async fn write_batch(&mut self, batch: Vec<Request>) {
+        let mut buf = BytesMut::new();
+        let mut synced = vec![];
+        let mut force_sync = false;
+
+        for request in batch {
+            match request {
+                Request::Append { data, synced: tx } => {
+                    buf.extend_from_slice(&data);
+                    synced.extend(tx);
+                }
+                Request::Sync(tx) => {
+                    synced.push(tx);
+                    force_sync = true;
+                }
+            }
+        }
+
+        let len = buf.len() as u64;
+
+        // 之前的写入失败时先截断文件，否则新的命令会追加在不完整的命令之后。仍然失败时丢弃这一批数据。
+        let mut ok = self.recover().await;
+
+        if !ok {
+            self.counters
+                .pending_bytes
+                .fetch_sub(len, Ordering::Relaxed);
+        } else if !buf.is_empty() {
+            let res = async {
+                self.file.write_all(&buf).await?;
+                self.file.flush().await
+            }
+            .await;
+
+            self.counters
+                .pending_bytes
+                .fetch_sub(len, Ordering::Relaxed);
+
+            match res {
+                Ok(()) => {
+                    self.counters.size.fetch_add(len, Ordering::Relaxed);
+                    self.dirty = true;
+                }
+                Err(err) => {
+                    error!(cause = %err, "failed to write AOF");
+                    ok = false;
+                }
+            }
+        }
+
+        if ok && self.dirty && (self.policy == AppendFsync::Always || force_sync) {
+            ok = self.fsync().await.is_ok();
+        }
+
+        self.failed = !ok;
+        self.counters
+            .last_write_failed
+            .store(!ok, Ordering::Relaxed);
+
+        // 写入失败时丢弃通知，等待的连接以错误响应。
+        if ok {
+            for tx in synced {
+                let _ = tx.send(());
+            }
+        }
+    }
+
+    /// 从失败的写入中恢复：将文件截断到最后一次成功写入的末尾，丢弃失败的写入可能留下的不完整的命令，
+    /// 然后将之前写入的数据 `fsync`。没有失败过或者恢复成功时返回 `true`。
+    async fn recover(&mut self) -> bool {
+        if !self.failed {
+            return true;
+        }
+
+        let size = self.counters.size.load(Ordering::Relaxed);
+
+        if let Err(err) = self.file.set_len(size).await {
+            error!(cause = %err, "failed to truncate AOF");
+            return false;
+        }
+
+        if self.dirty && self.policy != AppendFsync::No && self.fsync().await.is_err() {
+            return false;
+        }
+
+        info!("AOF is writable again");
+        self.failed = false;
+        self.counters
+            .last_write_failed
+            .store(false, Ordering::Relaxed);
+        true
+    }
+
+    /// 调用 `fsync`，记录耗费的时间。
+    async fn fsync(&mut self) -> io::Result<()> {
+        let start = Instant::now();
+        let res = self.file.sync_data().await;
+
+        match &res {
+            Ok(()) => {
+                self.dirty = false;
+                self.counters.fsyncs.fetch_add(1, Ordering::Relaxed);
+                self.counters
+                    .last_fsync_latency_us
+                    .store(start.elapsed().as_micros() as u64, Ordering::Relaxed);
+            }
+            Err(err) => error!(cause = %err, "failed to fsync AOF"),
+        }
+
+        res
+    }
+}
+
+/// 按顺序重新执行 `path` 处的 AOF 中的命令，返回执行的命令数量。文件不存在时不执行任何命令。
+///
+/// 文件末尾不完整的命令（写入时进程退出）被丢弃，文件被截断到最后一个完整的命令，之后的命令从那里继续追加。
+pub(super) fn load(db: &Db, path: &Path) -> crate::Result<u64> {
+    let data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(err) => return Err(err.into()),
+    };
+
+    let total = data.len();
+    let mut buf = BytesMut::from(&data[..]);
+    let limits = Limits::default();
+    let mut loaded = 0;
+
+    loop {
+        let offset = total - buf.len();
+
+        let frame = match Frame::decode(&mut buf, &limits) {
+            Ok(Some(frame)) => frame,
+            Ok(None) if buf.is_empty() => break,
+            Ok(None) => {
+                warn!(
+                    path = %path.display(),
+                    offset,
+                    "AOF ends with an incomplete command, truncating"
+                );
+                File::options()
+                    .write(true)
+                    .open(path)?
+                    .set_len(offset as u64)?;
+                break;
+            }
+            Err(err) => return Err(format!("bad command at byte {}: {}", offset, err).into()),
+        };
+
+        let cmd = Command::from_frame(frame.clone())?;
+
+        if !cmd.is_propagated() {
+            return Err(format!(
+                "command '{}' at byte {} cannot be replayed",
+                cmd.get_name(),
+                offset
+            )
+            .into());
+        }
+
+        // 执行时以错误响应的命令（例如 `WRONGTYPE`）同样被记录，加载时以同样的方式出错，不影响结果。
+        db.batch(|batch| {
+            let _ = cmd.execute_propagated(batch, frame);
+        });
+
+        loaded += 1;
+    }
+
+    info!(path = %path.display(), commands = loaded, "AOF loaded");
+
+    Ok(loaded)
+}
@@ -0,0 +1,165 @@
+//! 复制积压缓冲区。
+//!
+//! 主节点执行的写命令按照 RESP 格式编码后追加到积压缓冲区，与追加到 AOF 在同一次加锁下完成。
+//! 复制偏移量是开始记录以来追加的字节总数。副本记住自己处理到的偏移量，断开之后重新连接时通过
+//! `PSYNC replid offset` 请求从该偏移量继续；数据仍在缓冲区中时只需要发送缺少的部分，否则重新发送完整的快照。
+//!
+//! 缓冲区在第一个副本连接时才开始记录，之后只保留最近的 `capacity` 字节。
+
+use bytes::{Bytes, BytesMut};
+use std::collections::hash_map::RandomState;
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::hash::{BuildHasher, Hasher};
+
+/// 积压缓冲区的默认容量（1MB），与 Redis 的 `repl-backlog-size` 相同。
+pub(crate) const DEFAULT_BACKLOG_SIZE: usize = 1024 * 1024;
+
+/// 复制积压缓冲区，保存在 `State` 中。
+#[derive(Debug)]
+pub(super) struct Backlog {
+    /// 复制 ID，40 个十六进制字符，每个 `Db` 实例各不相同。副本据此判断重新连接的是不是同一个主节点。
+    replid: String,
+
+    /// 复制偏移量，即开始记录以来追加的字节总数
+    offset: u64,
+
+    /// 是否已经开始记录。在此之前写命令不会被追加，偏移量保持不变。
+    active: bool,
+
+    /// 缓冲的数据，按追加的顺序排列。第一个块从偏移量 `start` 开始，最后一个块在 `offset` 结束。
+    chunks: VecDeque<Bytes>,
+
+    /// 缓冲的第一个字节的偏移量
+    start: u64,
+
+    /// 最多缓冲的字节数
+    capacity: usize,
+}
+
+/// 从积压缓冲区读取的结果。
+#[derive(Debug)]
+pub(crate) enum BacklogRead {
+    /// 从请求的偏移量开始缓冲的数据，以及读取之后的偏移量。没有新数据时为空。
+    Data(Bytes, u64),
+
+    /// 请求的偏移量已经被丢弃，或者超过了当前的偏移量，无法从该偏移量继续。
+    Unavailable,
+}
+
+impl Backlog {
+    /// 创建一个尚未开始记录的缓冲区。
+    pub(super) fn new(capacity: usize) -> Backlog {
+        Backlog {
+            replid: new_replid(),
+            offset: 0,
+            active: false,
+            chunks: VecDeque::new(),
+            start: 0,
+            capacity,
+        }
+    }
+
+    /// 返回复制 ID。
+    pub(super) fn replid(&self) -> &str {
+        &self.replid
+    }
+
+    /// 返回复制偏移量。
+    pub(super) fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// 是否已经开始记录。
+    pub(super) fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// 缓冲的第一个字节的偏移量。
+    pub(super) fn first_offset(&self) -> u64 {
+        self.start
+    }
+
+    /// 缓冲的字节数。
+    pub(super) fn len(&self) -> u64 {
+        self.offset - self.start
+    }
+
+    /// 最多缓冲的字节数。
+    pub(super) fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// 设置最多缓冲的字节数，超出的旧数据立即被丢弃。
+    pub(super) fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.trim();
+    }
+
+    /// 开始记录。已经开始时什么也不做。
+    pub(super) fn activate(&mut self) {
+        if !self.active {
+            self.active = true;
+            self.start = self.offset;
+        }
+    }
+
+    /// 追加编码后的写命令。尚未开始记录时什么也不做。
+    pub(super) fn append(&mut self, data: Bytes) {
+        if !self.active || data.is_empty() {
+            return;
+        }
+
+        self.offset += data.len() as u64;
+        self.chunks.push_back(data);
+        self.trim();
+    }
+
+    /// 读取从 `offset` 开始缓冲的数据。
+    pub(super) fn read(&self, offset: u64) -> BacklogRead {
+        if !self.active || offset < self.start || offset > self.offset {
+            return BacklogRead::Unavailable;
+        }
+
+        let mut pos = self.start;
+        let mut data = BytesMut::new();
+
+        for chunk in &self.chunks {
+            let end = pos + chunk.len() as u64;
+
+            if end > offset {
+                let skip = offset.saturating_sub(pos) as usize;
+                data.extend_from_slice(&chunk[skip..]);
+            }
+
+            pos = end;
+        }
+
+        BacklogRead::Data(data.freeze(), self.offset)
+    }
+
+    /// 丢弃超出容量的旧数据。每次丢弃一整个块，保留的数据可能略少于容量。
+    fn trim(&mut self) {
+        while self.len() > self.capacity as u64 {
+            match self.chunks.pop_front() {
+                Some(chunk) => self.start += chunk.len() as u64,
+                None => break,
+            }
+        }
+    }
+}
+
+/// 生成一个随机的复制 ID。
+fn new_replid() -> String {
+    let seed = RandomState::new();
+    let mut replid = String::with_capacity(40);
+
+    for i in 0..3u64 {
+        let mut hasher = seed.build_hasher();
+        hasher.write_u64(i);
+        let _ = write!(replid, "{:016x}", hasher.finish());
+    }
+
+    replid.truncate(40);
+    replid
+}
@@ -0,0 +1,87 @@
+//! 阻塞命令按键等待的通知。
+//!
+//! 与 Redis 的 `blocking_keys` 一样，每个阻塞的命令（`BLPOP`、`XREAD`）只在它等待的键下登记。
+//! 键被写入时只唤醒登记在该键下的命令，而不是所有阻塞的命令。
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+/// 正在等待的阻塞命令，以键为索引，保存在 `Shared` 中。
+#[derive(Debug, Default)]
+pub(super) struct BlockedKeys {
+    /// 每个键下登记的等待者。每个等待者持有一个自己的 `Notify`，可能登记在多个键下。
+    waiters: Mutex<HashMap<String, Vec<Arc<Notify>>>>,
+}
+
+/// 登记在一组键下的等待者，由 `Db::watch_keys` 创建。被丢弃时从所有键下移除。
+#[derive(Debug)]
+pub(crate) struct KeyWaiter<'a> {
+    blocked: &'a BlockedKeys,
+
+    /// 登记的键。
+    keys: Vec<String>,
+
+    /// 任意一个键被写入时收到通知。
+    notify: Arc<Notify>,
+}
+
+impl BlockedKeys {
+    /// 在 `keys` 下登记一个新的等待者。
+    pub(super) fn watch<'a>(&'a self, keys: impl IntoIterator<Item = &'a str>) -> KeyWaiter<'a> {
+        let notify = Arc::new(Notify::new());
+        let keys: Vec<String> = keys.into_iter().map(str::to_string).collect();
+
+        let mut waiters = self.waiters.lock().unwrap();
+        for key in &keys {
+            waiters.entry(key.clone()).or_default().push(notify.clone());
+        }
+
+        KeyWaiter {
+            blocked: self,
+            keys,
+            notify,
+        }
+    }
+
+    /// 唤醒登记在 `key` 下的所有等待者。
+    pub(super) fn wake(&self, key: &str) {
+        let waiters = self.waiters.lock().unwrap();
+
+        if let Some(waiters) = waiters.get(key) {
+            for notify in waiters {
+                // `notify_one` 在等待者尚未开始等待时保存一个许可，因此登记之后、等待之前的写入不会被错过。
+                notify.notify_one();
+            }
+        }
+    }
+}
+
+impl KeyWaiter<'_> {
+    /// 等待任意一个登记的键被写入。
+    ///
+    /// 登记之后发生的写入都会让此方法立即返回，因此调用者可以先登记，再检查键，最后等待。
+    /// 返回之后键不一定满足调用者的条件（例如推入的元素已经被其他客户端弹出），调用者需要重新检查。
+    pub(crate) async fn wait(&self) {
+        self.notify.notified().await;
+    }
+}
+
+impl Drop for KeyWaiter<'_> {
+    fn drop(&mut self) {
+        let mut waiters = self.blocked.waiters.lock().unwrap();
+
+        for key in &self.keys {
+            if let Entry::Occupied(mut entry) = waiters.entry(key.clone()) {
+                entry
+                    .get_mut()
+                    .retain(|notify| !Arc::ptr_eq(notify, &self.notify));
+
+                if entry.get().is_empty() {
+                    entry.remove();
+                }
+            }
+        }
+    }
+}
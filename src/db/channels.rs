@@ -0,0 +1,139 @@
+//! 发布/订阅频道。
+//!
+//! Redis 使用一个**独立**的键空间来分别处理键值和发布/订阅。这里的频道表也与键值数据分开加锁：
+//! 频繁的 `PUBLISH` 和订阅不会与 `GET`/`SET` 争用 `State` 的锁，反之亦然。
+//!
+//! 需要同时持有两个锁时（`EVALBATCH` 中的 `PUBLISH`），总是先获取 `State` 的锁，再获取频道表的锁。
+
+use bytes::Bytes;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::SystemTime;
+use tokio::sync::broadcast;
+
+/// 发布到频道上的一条消息。
+#[derive(Debug, Clone)]
+pub(crate) struct Published {
+    /// 消息的内容。
+    pub(crate) payload: Bytes,
+
+    /// 发布消息的连接的 ID。不是由客户端连接发布的消息（例如嵌入服务器的应用程序调用 `Db::publish`）为 `None`。
+    pub(crate) publisher: Option<u64>,
+
+    /// 消息被发布的时间。同一次发布的多条消息时间相同。
+    pub(crate) timestamp: SystemTime,
+}
+
+/// 所有活动的发布/订阅频道，保存在 `Shared` 中。
+#[derive(Debug)]
+pub(super) struct Channels {
+    /// 每个频道的广播发送器，以频道名称为键。
+    senders: Mutex<HashMap<String, broadcast::Sender<Published>>>,
+
+    /// 每个频道的广播缓冲区容量（消息数）。
+    capacity: usize,
+}
+
+impl Channels {
+    /// 创建一个空的频道表，新建的频道能够缓冲 `capacity` 条消息。
+    pub(super) fn new(capacity: usize) -> Channels {
+        Channels {
+            senders: Mutex::new(HashMap::new()),
+            capacity,
+        }
+    }
+
+    /// 返回频道 `key` 的 `Receiver`，频道不存在时创建它。参见 `Db::subscribe`。
+    pub(super) fn subscribe(&self, key: String) -> broadcast::Receiver<Published> {
+        let mut senders = self.senders.lock().unwrap();
+
+        // 如果请求的频道没有条目，则创建一个新的广播频道并将其与键关联。
+        // 如果已经存在，则返回一个关联的接收器。
+        match senders.entry(key) {
+            Entry::Occupied(e) => e.get().subscribe(),
+            Entry::Vacant(e) => {
+                // 目前没有广播频道，因此创建一个。
+                //
+                // 创建的频道容量为 `capacity` 条消息（由 `server::Config` 配置）。
+                // 消息会存储在频道中，直到**所有**订阅者都已查看。
+                // 这意味着缓慢的订阅者可能导致消息被无限期保留。
+                //
+                // 当频道容量达到上限时，发布操作会导致旧消息被丢弃，落后的订阅者会收到 `Lagged` 错误。
+                // 这可以防止缓慢的消费者阻塞整个系统。
+                let (tx, rx) = broadcast::channel(self.capacity);
+                e.insert(tx);
+                rx
+            }
+        }
+    }
+
+    /// 频道已经没有订阅者时，移除它的发送器。参见 `Db::release_channel`。
+    pub(super) fn release(&self, key: &str) {
+        let mut senders = self.senders.lock().unwrap();
+
+        if senders.get(key).is_some_and(|tx| tx.receiver_count() == 0) {
+            senders.remove(key);
+        }
+    }
+
+    /// 返回至少有一个订阅者的频道，同时移除所有已经没有订阅者的频道。
+    pub(super) fn names(&self) -> Vec<String> {
+        let mut senders = self.senders.lock().unwrap();
+        senders.retain(|_, tx| tx.receiver_count() > 0);
+        senders.keys().cloned().collect()
+    }
+
+    /// 返回至少有一个订阅者的频道的数量，同时移除所有已经没有订阅者的频道。
+    pub(super) fn len(&self) -> usize {
+        let mut senders = self.senders.lock().unwrap();
+        senders.retain(|_, tx| tx.receiver_count() > 0);
+        senders.len()
+    }
+
+    /// 返回频道的订阅者数量。
+    pub(super) fn subscriber_count(&self, key: &str) -> usize {
+        self.senders
+            .lock()
+            .unwrap()
+            .get(key)
+            .map_or(0, broadcast::Sender::receiver_count)
+    }
+
+    /// 在一次加锁中将 `values` 按顺序发布到频道，`publisher` 是发布消息的连接的 ID。返回正在监听该频道的订阅者数量。
+    ///
+    /// 频道没有订阅者时直接返回 `0`，不会发送消息。所有订阅者都已离开的频道在此时被移除。
+    pub(super) fn publish(
+        &self,
+        key: &str,
+        values: impl IntoIterator<Item = Bytes>,
+        publisher: Option<u64>,
+    ) -> usize {
+        let mut senders = self.senders.lock().unwrap();
+
+        // 如果频道键没有条目，则表示没有订阅者。在这种情况下，返回 `0`。
+        let tx = match senders.get(key) {
+            Some(tx) => tx,
+            None => return 0,
+        };
+
+        // 所有订阅者都已离开时，移除频道的发送器，避免频道表无限增长。
+        // 新的接收器只能在持有锁时通过 `subscribe` 创建，因此检查之后不会有订阅者加入。
+        if tx.receiver_count() == 0 {
+            senders.remove(key);
+            return 0;
+        }
+
+        // 在广播频道成功发送消息时，返回订阅者的数量。
+        // 检查之后最后的订阅者仍然可能离开，此时发送失败，返回 `0`。
+        let timestamp = SystemTime::now();
+        values.into_iter().fold(0, |_, payload| {
+            let msg = Published {
+                payload,
+                publisher,
+                timestamp,
+            };
+            tx.send(msg).unwrap_or(0)
+        })
+    }
+}
@@ -0,0 +1,147 @@
+//! 延迟监控。
+//!
+//! 耗时达到阈值的操作被记录到对应名称的事件中，当前有两个事件：`command`（一个命令从开始执行到写入响应的时间）
+//! 和 `expire-cycle`（后台任务一次清除过期键的时间），与 Redis 的事件名称相同。
+//!
+//! 每个事件保留最近 `MAX_SAMPLES` 个采样，同一秒内的多次记录只保留最大值。阈值为 0 时不记录任何事件。
+
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryFrom;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// 每个事件保留的采样数量，与 Redis 相同。
+const MAX_SAMPLES: usize = 160;
+
+/// 命令执行时间的事件名称。
+pub(crate) const COMMAND_EVENT: &str = "command";
+
+/// 后台任务清除过期键的耗时的事件名称。
+pub(super) const EXPIRE_CYCLE_EVENT: &str = "expire-cycle";
+
+/// 事件的一个采样。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct LatencySample {
+    /// 记录的时间（Unix 时间戳，秒）
+    pub(crate) time: u64,
+
+    /// 延迟（毫秒）
+    pub(crate) latency_ms: u64,
+}
+
+/// 一个事件的概况，由 `INFO latency` 报告。
+#[derive(Debug, Clone)]
+pub(crate) struct LatencyEvent {
+    /// 事件名称
+    pub(crate) name: &'static str,
+
+    /// 最近一次记录的采样
+    pub(crate) latest: LatencySample,
+
+    /// 开始记录（或者上次重置）以来的最大延迟（毫秒）
+    pub(crate) max_ms: u64,
+}
+
+/// 一个事件的采样，按时间顺序排列。
+#[derive(Debug, Default)]
+struct Series {
+    samples: VecDeque<LatencySample>,
+    max_ms: u64,
+}
+
+/// 所有事件的采样，保存在 `Shared` 中。
+#[derive(Debug, Default)]
+pub(super) struct LatencyMonitor {
+    /// 记录事件的阈值（毫秒），`0` 表示不记录
+    threshold_ms: AtomicU64,
+
+    /// 按名称保存的事件。只在记录、查询和重置时短暂加锁，与数据库的锁无关。
+    events: Mutex<HashMap<&'static str, Series>>,
+}
+
+impl LatencyMonitor {
+    /// 返回记录事件的阈值。
+    pub(super) fn threshold(&self) -> Duration {
+        Duration::from_millis(self.threshold_ms.load(Ordering::Relaxed))
+    }
+
+    /// 设置记录事件的阈值，精确到毫秒。`Duration::ZERO` 表示不记录。
+    pub(super) fn set_threshold(&self, threshold: Duration) {
+        let threshold_ms = u64::try_from(threshold.as_millis()).unwrap_or(u64::MAX);
+        self.threshold_ms.store(threshold_ms, Ordering::Relaxed);
+    }
+
+    /// 延迟达到阈值时记录到事件 `event` 中。
+    pub(super) fn record(&self, event: &'static str, latency: Duration) {
+        let threshold_ms = self.threshold_ms.load(Ordering::Relaxed);
+        let latency_ms = u64::try_from(latency.as_millis()).unwrap_or(u64::MAX);
+
+        if threshold_ms == 0 || latency_ms < threshold_ms {
+            return;
+        }
+
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since| since.as_secs())
+            .unwrap_or(0);
+
+        let mut events = self.events.lock().unwrap();
+        let series = events.entry(event).or_default();
+        series.max_ms = series.max_ms.max(latency_ms);
+
+        match series.samples.back_mut() {
+            Some(last) if last.time == time => last.latency_ms = last.latency_ms.max(latency_ms),
+            _ => {
+                if series.samples.len() == MAX_SAMPLES {
+                    series.samples.pop_front();
+                }
+                series.samples.push_back(LatencySample { time, latency_ms });
+            }
+        }
+    }
+
+    /// 返回事件 `event` 的所有采样，事件不存在时返回空列表。
+    pub(super) fn history(&self, event: &str) -> Vec<LatencySample> {
+        match self.events.lock().unwrap().get(event) {
+            Some(series) => series.samples.iter().copied().collect(),
+            None => vec![],
+        }
+    }
+
+    /// 返回所有事件的概况，按名称排序。
+    pub(super) fn events(&self) -> Vec<LatencyEvent> {
+        let mut events: Vec<_> = self
+            .events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|(name, series)| {
+                series.samples.back().map(|latest| LatencyEvent {
+                    name,
+                    latest: *latest,
+                    max_ms: series.max_ms,
+                })
+            })
+            .collect();
+
+        events.sort_by_key(|event| event.name);
+        events
+    }
+
+    /// 删除 `events` 中列出的事件，`events` 为空时删除所有事件。返回被删除的事件数量。
+    pub(super) fn reset(&self, events: &[String]) -> usize {
+        let mut all = self.events.lock().unwrap();
+
+        if events.is_empty() {
+            let count = all.len();
+            all.clear();
+            return count;
+        }
+
+        events
+            .iter()
+            .filter(|event| all.remove(&event[..]).is_some())
+            .count()
+    }
+}
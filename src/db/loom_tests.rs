@@ -0,0 +1,60 @@
+//! 使用 loom 检查 `Db` 的后台任务与写入、关闭之间的交错执行。
+//!
+//! 运行方式：
+//!
+//! ```text
+//! RUSTFLAGS="--cfg mini_redis_loom" cargo test --lib --release loom
+//! ```
+
+use super::DbDropGuard;
+use crate::clock::ManualClock;
+
+use bytes::Bytes;
+use loom::thread;
+use std::sync::Arc;
+use tokio::time::Duration;
+
+/// Dropping the guard always stops the purge task, even when a concurrent
+/// `set` is notifying it at the same time.
+#[test]
+fn loom_shutdown_stops_purge_task() {
+    loom::model(|| {
+        let guard = DbDropGuard::new_with_observer(1, Arc::new(ManualClock::new()), None);
+        let db = guard.db();
+
+        let writer = thread::spawn(move || {
+            db.set(
+                "a".into(),
+                Bytes::from("1"),
+                Some(Duration::from_secs(1)),
+                false,
+            );
+        });
+
+        drop(guard);
+        writer.join().unwrap();
+    });
+}
+
+/// A purge racing with an overwrite must never remove the value that
+/// replaced the expired one.
+#[test]
+fn loom_purge_keeps_overwritten_key() {
+    loom::model(|| {
+        let guard = DbDropGuard::new_with_observer(1, Arc::new(ManualClock::new()), None);
+        let db = guard.db();
+
+        let writer = {
+            let db = db.clone();
+            thread::spawn(move || {
+                db.set("a".into(), Bytes::from("1"), Some(Duration::ZERO), false);
+                db.set("a".into(), Bytes::from("2"), None, false);
+            })
+        };
+
+        writer.join().unwrap();
+        assert_eq!(Some(Bytes::from("2")), db.get("a").unwrap());
+
+        drop(guard);
+    });
+}
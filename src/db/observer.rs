@@ -0,0 +1,131 @@
+//! 键空间变化的观察者。
+//!
+//! `DbObserver` 在键被写入、修改、删除或者过期之后得到通知。事件在持有 `State` 的锁时按照修改的顺序记录，
+//! 释放锁之后才交给观察者，观察者的代码因此不会阻塞其他连接，也可以再次访问 `Db`。
+
+use bytes::Bytes;
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// 键空间变化的观察者，通过 `server::Config::observer` 安装。
+///
+/// 持久化、复制或者键空间通知等功能可以建立在它之上。所有方法都有什么也不做的默认实现，只需要实现关心的事件。
+///
+/// 方法在释放数据库的锁之后调用，参数都是拥有所有权的数据。同一时刻最多只有一个方法在执行，
+/// 事件按照修改发生的顺序交付；修改键的调用可能在它的事件交付之前就已经返回。
+/// 方法中可以读写 `Db`，由此产生的事件在当前方法返回之后交付。方法不应 panic，也不应长时间阻塞，
+/// 否则之后的事件会一直等待。
+///
+/// 每次修改键都会被报告：字符串的写入（`SET`、`INCR`、`SETRANGE`、`RESTORE` 等）报告为 `on_set`；
+/// 列表、集合、哈希和流的创建或修改（包括哈希字段的过期时间变化和过期字段的清除）报告为 `on_modify`；
+/// 任意类型的键被删除报告为 `on_delete`，键过期报告为 `on_expire`。从快照加载的键和副本完整同步时收到的键
+/// 同样按照类型报告为 `on_set` 或 `on_modify`，被替换的原有键报告为 `on_delete`。
+pub trait DbObserver: Send + Sync {
+    /// 字符串 `value` 被写入 `key`。`ttl` 是键剩余的生存时间，`None` 表示键不会过期。
+    fn on_set(&self, key: String, value: Bytes, ttl: Option<Duration>) {
+        let _ = (key, value, ttl);
+    }
+
+    /// `key` 中存储的列表、集合、哈希或者流被创建或修改。需要修改之后的值时，可以在方法中从 `Db` 读取。
+    fn on_modify(&self, key: String) {
+        let _ = key;
+    }
+
+    /// `key` 被删除。
+    fn on_delete(&self, key: String) {
+        let _ = key;
+    }
+
+    /// `key` 已过期，被后台任务清除或者在被修改之前移除。
+    fn on_expire(&self, key: String) {
+        let _ = key;
+    }
+}
+
+impl fmt::Debug for dyn DbObserver {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("DbObserver")
+    }
+}
+
+/// 在持有锁时记录的一次变化。
+#[derive(Debug)]
+pub(super) enum KeyEvent {
+    Set {
+        key: String,
+        value: Bytes,
+        ttl: Option<Duration>,
+    },
+    Modify(String),
+    Delete(String),
+    Expire(String),
+}
+
+/// 安装的观察者和等待交付的事件。
+#[derive(Debug)]
+pub(super) struct Observer {
+    observer: Arc<dyn DbObserver>,
+    queue: Mutex<Queue>,
+}
+
+#[derive(Debug, Default)]
+struct Queue {
+    events: VecDeque<KeyEvent>,
+
+    /// 是否有线程正在交付事件。交付事件的线程一直交付到队列为空，其他线程只把事件加入队列。
+    dispatching: bool,
+}
+
+impl Observer {
+    pub(super) fn new(observer: Arc<dyn DbObserver>) -> Observer {
+        Observer {
+            observer,
+            queue: Mutex::new(Queue::default()),
+        }
+    }
+
+    /// 记录一次变化。在持有 `State` 的锁时调用，队列中的顺序因此与修改的顺序一致。
+    pub(super) fn record(&self, event: KeyEvent) {
+        self.queue.lock().unwrap().events.push_back(event);
+    }
+
+    /// 交付队列中的事件。在释放 `State` 的锁之后调用。
+    ///
+    /// 已经有线程在交付时直接返回，由它交付新加入的事件。观察者在方法中修改 `Db` 时也是如此，
+    /// 因此方法不会被重入。
+    pub(super) fn dispatch(&self) {
+        {
+            let mut queue = self.queue.lock().unwrap();
+
+            if queue.dispatching || queue.events.is_empty() {
+                return;
+            }
+
+            queue.dispatching = true;
+        }
+
+        loop {
+            let events = {
+                let mut queue = self.queue.lock().unwrap();
+
+                if queue.events.is_empty() {
+                    queue.dispatching = false;
+                    return;
+                }
+
+                std::mem::take(&mut queue.events)
+            };
+
+            for event in events {
+                match event {
+                    KeyEvent::Set { key, value, ttl } => self.observer.on_set(key, value, ttl),
+                    KeyEvent::Modify(key) => self.observer.on_modify(key),
+                    KeyEvent::Delete(key) => self.observer.on_delete(key),
+                    KeyEvent::Expire(key) => self.observer.on_expire(key),
+                }
+            }
+        }
+    }
+}
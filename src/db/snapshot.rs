@@ -0,0 +1,864 @@
+//! `BGSAVE` 写入的数据库快照。
+//!
+//! 生成快照分为两步：先在持有锁的情况下复制所有未过期的条目（值是 `Bytes`，复制只增加引用计数，
+//! 列表、集合等容器本身需要复制，但不复制元素的数据），然后在 `spawn_blocking` 的线程上不持有锁地编码并写入文件，
+//! 写入期间其他连接照常读写数据库。快照先写入临时文件，完成后重命名为目标文件，因此目标文件总是一个完整的快照。
+//!
+//! 设置了 `server::Config::snapshot_path` 的服务器启动时从快照文件加载数据。`check` 不启动服务器，
+//! 只检查一个快照文件是否完整，`mini-redis-cli --check-dump` 通过它实现。
+//!
+//! # 格式
+//!
+//! 所有整数都以小端序编码。文件的结构为：
+//!
+//! ```text
+//! magic:"MREDSNAP"  version:u32  count:u64  entry...  crc32:u32
+//! ```
+//!
+//! `crc32` 是 `count` 及之后所有条目的 CRC-32 校验和（与 zlib 相同的多项式）。加载时拒绝比 `VERSION` 更新的版本，
+//! 以免把新版本写入的文件错误地解析为旧的格式；旧的版本仍然可以加载。每个条目的结构为：
+//!
+//! ```text
+//! key:bytes  expires_at:u64  type:u8  value
+//! ```
+//!
+//! `bytes` 是 `u32` 长度之后跟随的数据。`expires_at` 是键过期时的 Unix 时间戳（毫秒），`0` 表示没有过期时间。
+//! `value` 的内容取决于 `type`：
+//!
+//! * `0` 字符串：`bytes`
+//! * `1` 列表：`u32` 元素数量，之后是每个元素的 `bytes`
+//! * `2` 集合：与列表相同
+//! * `3` 哈希：`u32` 字段数量，之后是每个字段的 `field:bytes value:bytes expires_at:u64`
+//! * `4` 流：最后添加的 ID（`ms:u64 seq:u64`），`u32` 条目数量，之后是每个条目的 `ms:u64 seq:u64`、
+//!   `u32` 字段数量和每个字段的 `field:bytes value:bytes`
+//!
+//! `DUMP` 使用同样的编码序列化单个键的值，不包含键名和键的过期时间：
+//!
+//! ```text
+//! type:u8  value  version:u32  crc32:u32
+//! ```
+//!
+//! `crc32` 是之前所有字节的校验和。`RESTORE` 与加载快照一样拒绝更新的版本和校验和不符的数据。
+
+use super::{Entry, Field, State, Value};
+use crate::stream::{Stream, StreamEntry, StreamId};
+
+use bytes::Bytes;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::convert::TryFrom;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Instant as StdInstant, SystemTime, UNIX_EPOCH};
+use tokio::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// 默认的快照文件路径。
+pub(crate) const DEFAULT_SNAPSHOT_PATH: &str = "dump.snapshot";
+
+/// 快照文件开头的魔数。
+const MAGIC: &[u8; 8] = b"MREDSNAP";
+
+/// 当前写入的格式版本。
+pub const VERSION: u32 = 1;
+
+/// 魔数和版本号的长度。
+const HEADER_LEN: usize = MAGIC.len() + 4;
+
+/// 值的类型标记。
+const TYPE_STRING: u8 = 0;
+const TYPE_LIST: u8 = 1;
+const TYPE_SET: u8 = 2;
+const TYPE_HASH: u8 = 3;
+const TYPE_STREAM: u8 = 4;
+
+/// 加载或检查快照文件失败的原因。
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum SnapshotError {
+    /// 读取文件失败。
+    Io(io::Error),
+
+    /// 文件不是以快照的魔数开头，不是快照文件。
+    NotASnapshot,
+
+    /// 文件由更新版本的 mini-redis 写入，当前版本无法解析。
+    UnsupportedVersion {
+        /// 文件的格式版本
+        version: u32,
+    },
+
+    /// 文件在 `offset` 处意外结束，通常是写入被中断或者文件被截断。
+    Truncated {
+        /// 文件的字节数
+        offset: u64,
+    },
+
+    /// 文件在 `offset` 处的内容无法解析。
+    Corrupt {
+        /// 出错的位置（从文件开头计算的字节数）
+        offset: u64,
+
+        /// 出错的原因
+        reason: &'static str,
+    },
+
+    /// 文件的结构完整，但内容与校验和不符。
+    ChecksumMismatch {
+        /// 文件末尾记录的校验和
+        expected: u32,
+
+        /// 根据文件内容计算的校验和
+        actual: u32,
+    },
+}
+
+/// `check` 返回的快照文件的概况。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotSummary {
+    /// 文件的格式版本
+    pub version: u32,
+
+    /// 文件中键的数量，包括写入之后已经过期的键
+    pub keys: u64,
+
+    /// 文件的字节数
+    pub size: u64,
+}
+
+/// 快照的配置和状态，保存在 `Shared` 中。
+#[derive(Debug)]
+pub(super) struct Snapshots {
+    /// 快照文件的路径
+    path: Mutex<PathBuf>,
+
+    /// 是否有正在进行的 `BGSAVE`
+    in_progress: AtomicBool,
+
+    /// 正在进行的 `BGSAVE` 需要写入的键的数量
+    keys_total: AtomicU64,
+
+    /// 正在进行的 `BGSAVE` 已经写入的键的数量
+    keys_saved: AtomicU64,
+
+    /// 成功完成的 `BGSAVE` 的次数
+    saves: AtomicU64,
+
+    /// 最后一次成功完成快照的 Unix 时间戳（秒），`0` 表示还没有快照
+    last_save_time: AtomicU64,
+
+    /// 最后一次 `BGSAVE` 是否成功。还没有执行过 `BGSAVE` 时为 `true`，与 Redis 相同。
+    last_status_ok: AtomicBool,
+
+    /// 最后一次 `BGSAVE` 耗费的时间（毫秒）
+    last_duration_ms: AtomicU64,
+}
+
+/// 快照的状态，由 `INFO persistence` 报告。
+#[derive(Debug, Clone)]
+pub(crate) struct SnapshotStats {
+    /// 是否有正在进行的 `BGSAVE`
+    pub(crate) in_progress: bool,
+
+    /// 正在进行的 `BGSAVE` 需要写入的键的数量
+    pub(crate) keys_total: u64,
+
+    /// 正在进行的 `BGSAVE` 已经写入的键的数量
+    pub(crate) keys_saved: u64,
+
+    /// 成功完成的 `BGSAVE` 的次数
+    pub(crate) saves: u64,
+
+    /// 最后一次成功完成快照的 Unix 时间戳（秒），`0` 表示还没有快照
+    pub(crate) last_save_time: u64,
+
+    /// 最后一次 `BGSAVE` 是否成功
+    pub(crate) last_status_ok: bool,
+
+    /// 最后一次 `BGSAVE` 耗费的时间（毫秒）
+    pub(crate) last_duration_ms: u64,
+}
+
+/// 在持有锁时复制的数据库内容，之后不持有锁地写入文件。
+pub(super) struct Snapshot {
+    /// 未过期的键、值和过期时刻
+    entries: Vec<SnapshotEntry>,
+
+    /// 复制的时刻，用于换算过期时间
+    time: Timestamps,
+}
+
+/// 快照中的一个条目：键、值和过期时刻。
+type SnapshotEntry = (String, Value, Option<Instant>);
+
+/// 同一时刻的数据库时钟和 Unix 时间戳，用于在 `Instant` 和文件中的 Unix 时间戳之间换算过期时间。
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Timestamps {
+    /// 数据库时钟的时刻
+    now: Instant,
+
+    /// Unix 时间戳（毫秒）
+    unix_now_ms: u64,
+}
+
+impl Default for Snapshots {
+    fn default() -> Snapshots {
+        Snapshots {
+            path: Mutex::new(PathBuf::from(DEFAULT_SNAPSHOT_PATH)),
+            in_progress: AtomicBool::new(false),
+            keys_total: AtomicU64::new(0),
+            keys_saved: AtomicU64::new(0),
+            saves: AtomicU64::new(0),
+            last_save_time: AtomicU64::new(0),
+            last_status_ok: AtomicBool::new(true),
+            last_duration_ms: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Snapshots {
+    /// 设置快照文件的路径。
+    pub(super) fn set_path(&self, path: PathBuf) {
+        *self.path.lock().unwrap() = path;
+    }
+
+    /// 标记开始一次 `BGSAVE`。已经有正在进行的 `BGSAVE` 时返回 `false`。
+    pub(super) fn begin(&self) -> bool {
+        !self.in_progress.swap(true, Ordering::AcqRel)
+    }
+
+    /// 编码 `snapshot` 并写入快照文件，记录结果。在 `spawn_blocking` 的线程上调用。
+    pub(super) fn save(&self, snapshot: Snapshot) {
+        let path = self.path.lock().unwrap().clone();
+        let start = StdInstant::now();
+
+        self.keys_total
+            .store(snapshot.entries.len() as u64, Ordering::Relaxed);
+        self.keys_saved.store(0, Ordering::Relaxed);
+
+        let result = snapshot.write_to(&path, &self.keys_saved);
+        let elapsed = start.elapsed();
+
+        match &result {
+            Ok(()) => {
+                info!(path = %path.display(), ?elapsed, "snapshot saved");
+                self.saves.fetch_add(1, Ordering::Relaxed);
+                self.last_save_time
+                    .store(snapshot.time.unix_now_ms / 1000, Ordering::Relaxed);
+            }
+            Err(err) => warn!(path = %path.display(), cause = %err, "failed to save snapshot"),
+        }
+
+        self.last_status_ok.store(result.is_ok(), Ordering::Relaxed);
+        self.last_duration_ms
+            .store(elapsed.as_millis() as u64, Ordering::Relaxed);
+        self.in_progress.store(false, Ordering::Release);
+    }
+
+    /// 返回快照的状态。
+    pub(super) fn stats(&self) -> SnapshotStats {
+        SnapshotStats {
+            in_progress: self.in_progress.load(Ordering::Acquire),
+            keys_total: self.keys_total.load(Ordering::Relaxed),
+            keys_saved: self.keys_saved.load(Ordering::Relaxed),
+            saves: self.saves.load(Ordering::Relaxed),
+            last_save_time: self.last_save_time.load(Ordering::Relaxed),
+            last_status_ok: self.last_status_ok.load(Ordering::Relaxed),
+            last_duration_ms: self.last_duration_ms.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Snapshot {
+    /// 复制 `state` 中所有未过期的条目。调用者持有锁。
+    pub(super) fn copy(state: &State) -> Snapshot {
+        let entries = state
+            .live_entries()
+            .map(|(key, entry)| (key.clone(), entry.data.clone(), entry.expires_at))
+            .collect();
+
+        Snapshot {
+            entries,
+            time: Timestamps::now(state.clock.now()),
+        }
+    }
+
+    /// 将快照写入 `path`。每写入一个键，`progress` 加一。
+    ///
+    /// 先写入同一目录下的临时文件，同步到磁盘之后再重命名，写入失败时原有的快照文件保持不变。
+    fn write_to(&self, path: &Path, progress: &AtomicU64) -> io::Result<()> {
+        let mut tmp = OsString::from(path.as_os_str());
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+
+        let result = (|| {
+            let mut dst = BufWriter::new(File::create(&tmp)?);
+            self.encode(&mut dst, progress)?;
+
+            let file = dst.into_inner().map_err(|err| err.into_error())?;
+            file.sync_all()
+        })();
+
+        match result {
+            Ok(()) => fs::rename(&tmp, path),
+            Err(err) => {
+                let _ = fs::remove_file(&tmp);
+                Err(err)
+            }
+        }
+    }
+
+    /// 按照模块文档描述的格式将快照编码到内存中。副本完整同步时，主节点将它发送给副本。
+    pub(super) fn to_bytes(&self) -> Vec<u8> {
+        let mut dst = Vec::new();
+        self.encode(&mut dst, &AtomicU64::new(0))
+            .expect("writing to a `Vec` cannot fail");
+        dst
+    }
+
+    /// 按照模块文档描述的格式编码快照。
+    fn encode(&self, dst: &mut impl Write, progress: &AtomicU64) -> io::Result<()> {
+        dst.write_all(MAGIC)?;
+        dst.write_all(&VERSION.to_le_bytes())?;
+
+        let mut dst = Checksummed {
+            inner: dst,
+            hasher: crc32fast::Hasher::new(),
+        };
+
+        write_u64(&mut dst, self.entries.len() as u64)?;
+
+        for (key, value, expires_at) in &self.entries {
+            self.encode_entry(&mut dst, key, value, *expires_at)?;
+            progress.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let checksum = dst.hasher.finalize();
+        dst.inner.write_all(&checksum.to_le_bytes())
+    }
+
+    /// 编码一个条目。
+    fn encode_entry(
+        &self,
+        dst: &mut impl Write,
+        key: &str,
+        value: &Value,
+        expires_at: Option<Instant>,
+    ) -> io::Result<()> {
+        write_bytes(dst, key.as_bytes())?;
+        write_u64(dst, self.time.to_unix_ms(expires_at))?;
+        encode_value(dst, value, self.time)
+    }
+}
+
+/// 编码一个值：类型标记和之后的内容。哈希字段的过期时间根据 `time` 换算为 Unix 时间戳。
+fn encode_value(dst: &mut impl Write, value: &Value, time: Timestamps) -> io::Result<()> {
+    match value {
+        Value::String(data) => {
+            dst.write_all(&[TYPE_STRING])?;
+            write_bytes(dst, data)?;
+        }
+        Value::List(list) => {
+            dst.write_all(&[TYPE_LIST])?;
+            write_len(dst, list.len())?;
+
+            for element in list {
+                write_bytes(dst, element)?;
+            }
+        }
+        Value::Set(set) => {
+            dst.write_all(&[TYPE_SET])?;
+            write_len(dst, set.len())?;
+
+            for member in set {
+                write_bytes(dst, member)?;
+            }
+        }
+        Value::Hash(hash) => {
+            // 跳过已经过期、但后台任务尚未清除的字段。
+            let fields: Vec<_> = hash
+                .iter()
+                .filter(|(_, field)| field.expires_at.is_none_or(|when| when > time.now))
+                .collect();
+
+            dst.write_all(&[TYPE_HASH])?;
+            write_len(dst, fields.len())?;
+
+            for (name, field) in fields {
+                write_bytes(dst, name)?;
+                write_bytes(dst, &field.value)?;
+                write_u64(dst, time.to_unix_ms(field.expires_at))?;
+            }
+        }
+        Value::Stream(stream) => {
+            let entries = stream.range(StreamId::MIN, StreamId::MAX, None);
+
+            dst.write_all(&[TYPE_STREAM])?;
+            write_id(dst, stream.last_id())?;
+            write_len(dst, entries.len())?;
+
+            for entry in entries {
+                write_id(dst, entry.id)?;
+                write_len(dst, entry.fields.len())?;
+
+                for (name, value) in &entry.fields {
+                    write_bytes(dst, name)?;
+                    write_bytes(dst, value)?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+impl Timestamps {
+    /// 以 `now` 作为当前的数据库时钟。
+    pub(super) fn now(now: Instant) -> Timestamps {
+        let unix_now_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis() as u64);
+
+        Timestamps { now, unix_now_ms }
+    }
+
+    /// 将数据库时钟的时刻换算为 Unix 时间戳（毫秒），`None` 换算为 `0`。
+    pub(super) fn to_unix_ms(self, when: Option<Instant>) -> u64 {
+        match when {
+            Some(when) => {
+                let remaining = when.saturating_duration_since(self.now).as_millis() as u64;
+                (self.unix_now_ms + remaining).max(1)
+            }
+            None => 0,
+        }
+    }
+
+    /// 将文件中的 Unix 时间戳换算为数据库时钟的时刻，`0` 换算为 `None`。已经过去的时间戳换算为 `now`。
+    pub(super) fn to_instant(self, unix_ms: u64) -> Option<Instant> {
+        match unix_ms {
+            0 => None,
+            unix_ms => {
+                let remaining = unix_ms.saturating_sub(self.unix_now_ms);
+                Some(self.now + Duration::from_millis(remaining))
+            }
+        }
+    }
+}
+
+/// 检查 `path` 处的快照文件是否完整，返回文件的概况。
+///
+/// 与服务器启动时加载快照执行相同的检查：魔数、格式版本、每个条目的结构以及校验和，但不创建数据库。
+///
+/// ```no_run
+/// use mini_redis::snapshot;
+///
+/// match snapshot::check("dump.snapshot") {
+///     Ok(summary) => println!("{} keys", summary.keys),
+///     Err(err) => eprintln!("bad snapshot: {}", err),
+/// }
+/// ```
+pub fn check(path: impl AsRef<Path>) -> Result<SnapshotSummary, SnapshotError> {
+    let data = fs::read(path)?;
+    let time = Timestamps::now(Instant::now());
+    let (version, entries) = decode(&data, time)?;
+
+    Ok(SnapshotSummary {
+        version,
+        keys: entries.len() as u64,
+        size: data.len() as u64,
+    })
+}
+
+/// 从 `path` 处的快照文件加载数据到 `state`，返回加载的键的数量。文件不存在时不加载任何数据。
+///
+/// 写入快照之后已经过期的键和哈希字段被跳过。已有的同名键被替换。
+pub(super) fn load(state: &mut State, path: &Path) -> Result<u64, SnapshotError> {
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(err) => return Err(err.into()),
+    };
+
+    load_data(state, &data)
+}
+
+/// 从快照的内容 `data` 加载数据到 `state`，返回加载的键的数量。参见 `load`。
+pub(super) fn load_data(state: &mut State, data: &[u8]) -> Result<u64, SnapshotError> {
+    let time = Timestamps::now(state.clock.now());
+    let (_, entries) = decode(data, time)?;
+    let mut loaded = 0;
+
+    for (key, mut value, expires_at) in entries {
+        if expires_at.is_some_and(|when| when <= time.now) {
+            continue;
+        }
+
+        if let Value::Hash(hash) = &mut value {
+            hash.retain(|_, field| field.expires_at.is_none_or(|when| when > time.now));
+
+            if hash.is_empty() {
+                continue;
+            }
+        }
+
+        state.del(&key);
+
+        if let Value::Hash(hash) = &value {
+            for (name, field) in hash {
+                if let Some(when) = field.expires_at {
+                    state
+                        .field_expirations
+                        .insert((when, key.clone(), name.clone()));
+                }
+            }
+        }
+
+        if let Some(when) = expires_at {
+            state.expirations.insert((when, key.clone()));
+        }
+
+        state
+            .entries
+            .insert(key.clone(), Entry::new(value, expires_at, time.now));
+        state.record_write(&key);
+        state.invalidate(&key);
+        loaded += 1;
+    }
+
+    Ok(loaded)
+}
+
+/// 按照模块文档描述的 `DUMP` 格式序列化 `value`。`now` 是数据库时钟的当前时刻，用于换算哈希字段的过期时间。
+pub(super) fn dump(value: &Value, now: Instant) -> Bytes {
+    let mut dst = Checksummed {
+        inner: Vec::new(),
+        hasher: crc32fast::Hasher::new(),
+    };
+
+    encode_value(&mut dst, value, Timestamps::now(now))
+        .and_then(|()| dst.write_all(&VERSION.to_le_bytes()))
+        .expect("writing to a `Vec` cannot fail");
+
+    let checksum = dst.hasher.finalize();
+    dst.inner.extend_from_slice(&checksum.to_le_bytes());
+    dst.inner.into()
+}
+
+/// 解析 `dump` 生成的数据，返回其中的值。已经过期的哈希字段被丢弃，所有字段都已过期的哈希返回空的哈希。
+pub(super) fn restore(data: &[u8], now: Instant) -> Result<Value, SnapshotError> {
+    let time = Timestamps::now(now);
+
+    // 末尾是版本号和校验和。
+    if data.len() < 8 {
+        return Err(SnapshotError::Truncated {
+            offset: data.len() as u64,
+        });
+    }
+
+    let version_start = data.len() - 8;
+    let payload_end = data.len() - 4;
+
+    let mut trailer = Decoder {
+        data,
+        pos: version_start,
+        time,
+    };
+    let version = trailer.u32()?;
+    let expected = trailer.u32()?;
+
+    if version == 0 || version > VERSION {
+        return Err(SnapshotError::UnsupportedVersion { version });
+    }
+
+    let actual = crc32fast::hash(&data[..payload_end]);
+    if actual != expected {
+        return Err(SnapshotError::ChecksumMismatch { expected, actual });
+    }
+
+    let mut src = Decoder {
+        data: &data[..version_start],
+        pos: 0,
+        time,
+    };
+
+    let mut value = src.value()?;
+    if src.pos != src.data.len() {
+        return Err(src.corrupt("unexpected data after the value"));
+    }
+
+    if let Value::Hash(hash) = &mut value {
+        hash.retain(|_, field| field.expires_at.is_none_or(|when| when > time.now));
+    }
+
+    Ok(value)
+}
+
+/// 解析快照文件的内容，返回格式版本和所有条目。
+fn decode(data: &[u8], time: Timestamps) -> Result<(u32, Vec<SnapshotEntry>), SnapshotError> {
+    if data.len() < MAGIC.len() || &data[..MAGIC.len()] != MAGIC {
+        return Err(SnapshotError::NotASnapshot);
+    }
+
+    let mut src = Decoder {
+        data,
+        pos: MAGIC.len(),
+        time,
+    };
+
+    let version = src.u32()?;
+    if version == 0 || version > VERSION {
+        return Err(SnapshotError::UnsupportedVersion { version });
+    }
+
+    let count = src.u64()?;
+    let mut entries = Vec::new();
+
+    for _ in 0..count {
+        entries.push(src.entry()?);
+    }
+
+    // 所有条目之后只剩下校验和。
+    let payload_end = src.pos;
+    let expected = src.u32()?;
+    if src.pos != data.len() {
+        return Err(src.corrupt("unexpected data after the checksum"));
+    }
+
+    let actual = crc32fast::hash(&data[HEADER_LEN..payload_end]);
+    if actual != expected {
+        return Err(SnapshotError::ChecksumMismatch { expected, actual });
+    }
+
+    Ok((version, entries))
+}
+
+/// 快照文件的解析器。
+struct Decoder<'a> {
+    /// 整个文件的内容
+    data: &'a [u8],
+
+    /// 下一个要读取的字节的位置
+    pos: usize,
+
+    /// 用于将过期时间换算为数据库时钟的时刻
+    time: Timestamps,
+}
+
+impl Decoder<'_> {
+    /// 读取 `len` 个字节。剩余的数据不足时说明文件被截断。
+    fn take(&mut self, len: usize) -> Result<&[u8], SnapshotError> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.data.len())
+            .ok_or(SnapshotError::Truncated {
+                offset: self.data.len() as u64,
+            })?;
+
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, SnapshotError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, SnapshotError> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn u64(&mut self) -> Result<u64, SnapshotError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    fn len(&mut self) -> Result<usize, SnapshotError> {
+        Ok(self.u32()? as usize)
+    }
+
+    fn bytes(&mut self) -> Result<Bytes, SnapshotError> {
+        let len = self.len()?;
+        Ok(Bytes::copy_from_slice(self.take(len)?))
+    }
+
+    fn id(&mut self) -> Result<StreamId, SnapshotError> {
+        Ok(StreamId::new(self.u64()?, self.u64()?))
+    }
+
+    fn expires_at(&mut self) -> Result<Option<Instant>, SnapshotError> {
+        Ok(self.time.to_instant(self.u64()?))
+    }
+
+    fn corrupt(&self, reason: &'static str) -> SnapshotError {
+        SnapshotError::Corrupt {
+            offset: self.pos as u64,
+            reason,
+        }
+    }
+
+    /// 读取一个条目。
+    fn entry(&mut self) -> Result<SnapshotEntry, SnapshotError> {
+        let key = match String::from_utf8(self.bytes()?.to_vec()) {
+            Ok(key) => key,
+            Err(_) => return Err(self.corrupt("key is not valid UTF-8")),
+        };
+        let expires_at = self.expires_at()?;
+        let value = self.value()?;
+
+        Ok((key, value, expires_at))
+    }
+
+    /// 读取一个值：类型标记和之后的内容。
+    fn value(&mut self) -> Result<Value, SnapshotError> {
+        let value = match self.u8()? {
+            TYPE_STRING => Value::String(self.bytes()?),
+            TYPE_LIST => {
+                let len = self.len()?;
+                let mut list = VecDeque::new();
+
+                for _ in 0..len {
+                    list.push_back(self.bytes()?);
+                }
+
+                Value::List(list)
+            }
+            TYPE_SET => {
+                let len = self.len()?;
+                let mut set = BTreeSet::new();
+
+                for _ in 0..len {
+                    set.insert(self.bytes()?);
+                }
+
+                Value::Set(set)
+            }
+            TYPE_HASH => {
+                let len = self.len()?;
+                let mut hash = HashMap::new();
+
+                for _ in 0..len {
+                    let name = self.bytes()?;
+                    let value = self.bytes()?;
+                    let expires_at = self.expires_at()?;
+                    hash.insert(name, Field { value, expires_at });
+                }
+
+                Value::Hash(hash)
+            }
+            TYPE_STREAM => {
+                let last_id = self.id()?;
+                let len = self.len()?;
+                let mut entries = Vec::new();
+
+                for _ in 0..len {
+                    let id = self.id()?;
+                    let count = self.len()?;
+                    let mut fields = Vec::new();
+
+                    for _ in 0..count {
+                        fields.push((self.bytes()?, self.bytes()?));
+                    }
+
+                    entries.push(StreamEntry { id, fields });
+                }
+
+                Value::Stream(Stream::restore(last_id, entries))
+            }
+            _ => {
+                // 指向类型标记本身，而不是它之后的字节。
+                self.pos -= 1;
+                return Err(self.corrupt("unknown value type"));
+            }
+        };
+
+        Ok(value)
+    }
+}
+
+/// 在写入的同时计算写入内容的校验和。
+struct Checksummed<W> {
+    inner: W,
+    hasher: crc32fast::Hasher,
+}
+
+impl<W: Write> Write for Checksummed<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn write_u64(dst: &mut impl Write, value: u64) -> io::Result<()> {
+    dst.write_all(&value.to_le_bytes())
+}
+
+fn write_len(dst: &mut impl Write, len: usize) -> io::Result<()> {
+    let len = u32::try_from(len)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "value too large for snapshot"))?;
+    dst.write_all(&len.to_le_bytes())
+}
+
+fn write_bytes(dst: &mut impl Write, data: &[u8]) -> io::Result<()> {
+    write_len(dst, data.len())?;
+    dst.write_all(data)
+}
+
+fn write_id(dst: &mut impl Write, id: StreamId) -> io::Result<()> {
+    write_u64(dst, id.ms)?;
+    write_u64(dst, id.seq)
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnapshotError::Io(err) => write!(fmt, "failed to read snapshot: {}", err),
+            SnapshotError::NotASnapshot => "not a mini-redis snapshot file".fmt(fmt),
+            SnapshotError::UnsupportedVersion { version } => write!(
+                fmt,
+                "snapshot format version {} is not supported (this build reads versions 1 to {})",
+                version, VERSION
+            ),
+            SnapshotError::Truncated { offset } => write!(
+                fmt,
+                "snapshot is truncated: unexpected end of file after {} bytes",
+                offset
+            ),
+            SnapshotError::Corrupt { offset, reason } => {
+                write!(fmt, "snapshot is corrupt at byte {}: {}", offset, reason)
+            }
+            SnapshotError::ChecksumMismatch { expected, actual } => write!(
+                fmt,
+                "snapshot checksum mismatch: expected {:08x}, computed {:08x}",
+                expected, actual
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SnapshotError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SnapshotError {
+    fn from(err: io::Error) -> SnapshotError {
+        SnapshotError::Io(err)
+    }
+}
@@ -1,16 +1,21 @@
 //! 提供一个表示 Redis 协议帧的类型以及用于从字节数组解析帧的工具。
 
-use bytes::{Buf, Bytes};
-use std::convert::TryInto;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use bytestring::ByteString;
+use std::convert::{TryFrom, TryInto};
 use std::fmt;
 use std::io::Cursor;
 use std::num::TryFromIntError;
+use std::ops::Range;
 use std::string::FromUtf8Error;
 
 /// Redis 协议中的一个帧。
+///
+/// `Simple` 和 `Bulk` 帧的内容以引用计数的字节保存。通过 `Frame::parse_bytes` 解析时，
+/// 它们直接引用接收缓冲区中的数据，而不会发生复制。
 #[derive(Clone, Debug)]
 pub enum Frame {
-    Simple(String),
+    Simple(ByteString),
     Error(String),
     Integer(u64),
     Bulk(Bytes),
@@ -18,7 +23,217 @@ pub enum Frame {
     Array(Vec<Frame>),
 }
 
+/// 错误帧的类型，由错误信息开头的大写错误码表示。
+///
+/// 客户端库根据错误码区分错误（例如 `WRONGTYPE` 和 `ERR`），因此服务器发送的每个错误帧都以其中一个错误码开头。
+/// 通过 `Frame::typed_error` 或 `Frame::error_syntax` 之类的方法构造错误帧，可以保证错误码与 Redis 一致。
+///
+/// # 示例
+///
+/// ```
+/// use mini_redis::frame::ErrorKind;
+/// use mini_redis::Frame;
+///
+/// let frame = Frame::error_wrongtype();
+/// assert_eq!(Some(ErrorKind::WrongType), frame.error_kind());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// 一般错误，例如语法错误或参数错误。
+    Err,
+
+    /// 对保存了其他类型值的键执行了操作。
+    WrongType,
+
+    /// 连接需要先通过 `AUTH` 认证。
+    NoAuth,
+
+    /// 用户名或密码错误。
+    WrongPass,
+
+    /// 用户没有执行该命令或访问该键的权限。
+    NoPerm,
+
+    /// 目标键已经存在。
+    BusyKey,
+
+    /// 服务器繁忙，例如超出了速率限制。
+    Busy,
+
+    /// 事务由于之前的错误被放弃。
+    ExecAbort,
+
+    /// 没有与 SHA1 摘要匹配的脚本。
+    NoScript,
+
+    /// 只读的服务器拒绝了写入命令。
+    ReadOnly,
+
+    /// 键所在的槽位由集群中的另一个节点负责。
+    Moved,
+
+    /// 键所在的槽位正在迁移，键需要在目标节点上访问。
+    Ask,
+
+    /// 命令的键不属于同一个槽位。
+    CrossSlot,
+
+    /// 集群中没有节点负责键所在的槽位。
+    ClusterDown,
+}
+
+impl ErrorKind {
+    /// 所有的错误类型，用于根据错误码查找。
+    const ALL: [ErrorKind; 14] = [
+        ErrorKind::Err,
+        ErrorKind::WrongType,
+        ErrorKind::NoAuth,
+        ErrorKind::WrongPass,
+        ErrorKind::NoPerm,
+        ErrorKind::BusyKey,
+        ErrorKind::Busy,
+        ErrorKind::ExecAbort,
+        ErrorKind::NoScript,
+        ErrorKind::ReadOnly,
+        ErrorKind::Moved,
+        ErrorKind::Ask,
+        ErrorKind::CrossSlot,
+        ErrorKind::ClusterDown,
+    ];
+
+    /// 返回错误信息开头的错误码。
+    pub fn code(self) -> &'static str {
+        match self {
+            ErrorKind::Err => "ERR",
+            ErrorKind::WrongType => "WRONGTYPE",
+            ErrorKind::NoAuth => "NOAUTH",
+            ErrorKind::WrongPass => "WRONGPASS",
+            ErrorKind::NoPerm => "NOPERM",
+            ErrorKind::BusyKey => "BUSYKEY",
+            ErrorKind::Busy => "BUSY",
+            ErrorKind::ExecAbort => "EXECABORT",
+            ErrorKind::NoScript => "NOSCRIPT",
+            ErrorKind::ReadOnly => "READONLY",
+            ErrorKind::Moved => "MOVED",
+            ErrorKind::Ask => "ASK",
+            ErrorKind::CrossSlot => "CROSSSLOT",
+            ErrorKind::ClusterDown => "CLUSTERDOWN",
+        }
+    }
+
+    /// 根据错误信息开头的错误码确定错误类型。错误码无法识别时返回 `None`。
+    pub fn of(msg: &str) -> Option<ErrorKind> {
+        let code = msg.split(' ').next().unwrap_or(msg);
+        ErrorKind::ALL
+            .iter()
+            .copied()
+            .find(|kind| kind.code() == code)
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str(self.code())
+    }
+}
+
+/// 解析帧时允许的最大尺寸。
+///
+/// 这些限制在 `Frame::check_with_limits` 中检查，在读取到帧的数据之前就会拒绝过大的帧，
+/// 防止恶意的客户端仅凭一个帧头（例如 `$999999999999\r\n`）就让服务器分配大量内存。
+///
+/// 默认值与 Redis 保持一致：批量字符串最大 512MB，数组最多 1048576 个元素，inline 命令最长 64KB。
+/// 此外数组最多嵌套 32 层，解析嵌套的数组需要递归，限制深度可以防止过深的嵌套耗尽栈空间。
+///
+/// # 示例
+///
+/// ```
+/// use mini_redis::frame::Limits;
+///
+/// let limits = Limits::new().max_bulk_len(1024 * 1024).max_array_len(1024);
+/// # drop(limits);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    /// 批量字符串的最大长度（字节）。
+    max_bulk_len: usize,
+
+    /// 数组的最大元素数量。
+    max_array_len: u64,
+
+    /// inline 命令的最大长度（字节）。
+    max_inline_len: usize,
+
+    /// 数组的最大嵌套层数。
+    max_depth: usize,
+}
+
+impl Limits {
+    /// 不做任何限制，用于解析已经通过检查的帧。
+    const UNLIMITED: Limits = Limits {
+        max_bulk_len: usize::MAX,
+        max_array_len: u64::MAX,
+        max_inline_len: usize::MAX,
+        max_depth: usize::MAX,
+    };
+
+    /// 返回默认的限制。
+    pub fn new() -> Limits {
+        Limits {
+            max_bulk_len: 512 * 1024 * 1024,
+            max_array_len: 1024 * 1024,
+            max_inline_len: 64 * 1024,
+            max_depth: 32,
+        }
+    }
+
+    /// 设置批量字符串的最大长度（字节）。
+    pub fn max_bulk_len(mut self, len: usize) -> Limits {
+        self.max_bulk_len = len;
+        self
+    }
+
+    /// 设置数组的最大元素数量。
+    pub fn max_array_len(mut self, len: u64) -> Limits {
+        self.max_array_len = len;
+        self
+    }
+
+    /// 设置 inline 命令的最大长度（字节）。
+    pub fn max_inline_len(mut self, len: usize) -> Limits {
+        self.max_inline_len = len;
+        self
+    }
+
+    /// 设置数组的最大嵌套层数。
+    pub fn max_depth(mut self, depth: usize) -> Limits {
+        self.max_depth = depth;
+        self
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits::new()
+    }
+}
+
+/// 扫描出的帧，尚未从读缓冲区中分离出来。
+///
+/// `Simple`、`Error` 和 `Bulk` 的内容以它们在缓冲区中的范围表示（`Simple` 和 `Error` 还记录了帧的起始偏移量，
+/// 用于报告错误），在确认收到完整的帧之后，才通过 `into_frame` 转换为 `Frame`。
+enum Raw {
+    Simple(u64, Range<usize>),
+    Error(u64, Range<usize>),
+    Integer(u64),
+    Bulk(Range<usize>),
+    Null,
+    Array(Vec<Raw>),
+}
+
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
     /// 没有足够的数据来解析消息
     Incomplete,
@@ -27,18 +242,94 @@ pub enum Error {
     Other(crate::Error),
 }
 
+/// `Frame::decode` 中，帧的数据不到读缓冲区容量的 `1 / SHARE_RATIO` 时复制数据，而不是共享缓冲区的内存。
+const SHARE_RATIO: usize = 8;
+
 impl Frame {
     /// 返回一个空数组
-    pub(crate) fn array() -> Frame {
+    ///
+    /// 可以配合 `push_bulk` 和 `push_int` 构建命令帧：
+    ///
+    /// ```
+    /// use mini_redis::Frame;
+    ///
+    /// let mut frame = Frame::array();
+    /// frame.push_bulk("GET".into());
+    /// frame.push_bulk("hello".into());
+    ///
+    /// assert_eq!(2, frame.as_array().unwrap().len());
+    /// ```
+    pub fn array() -> Frame {
         Frame::Array(vec![])
     }
 
+    /// 返回一个 "bulk" 帧。
+    pub fn bulk(data: impl Into<Bytes>) -> Frame {
+        Frame::Bulk(data.into())
+    }
+
+    /// 返回一个 "simple" 帧。
+    pub fn simple(string: impl Into<ByteString>) -> Frame {
+        Frame::Simple(string.into())
+    }
+
+    /// 返回一个 "error" 帧。
+    ///
+    /// `msg` 以可识别的错误码开头时（参见 `ErrorKind`）原样使用，否则加上 `ERR` 前缀，
+    /// 保证客户端库能够对错误进行分类。
+    ///
+    /// ```
+    /// use mini_redis::Frame;
+    ///
+    /// let frame = Frame::error("no such key");
+    /// assert!(matches!(frame, Frame::Error(msg) if msg == "ERR no such key"));
+    ///
+    /// let frame = Frame::error("NOAUTH Authentication required.");
+    /// assert!(matches!(frame, Frame::Error(msg) if msg == "NOAUTH Authentication required."));
+    /// ```
+    pub fn error(msg: impl Into<String>) -> Frame {
+        let msg = msg.into();
+
+        match ErrorKind::of(&msg) {
+            Some(_) => Frame::Error(msg),
+            None => Frame::typed_error(ErrorKind::Err, msg),
+        }
+    }
+
+    /// 返回一个 `kind` 类型的 "error" 帧，`msg` 是错误码之后的信息。
+    pub fn typed_error(kind: ErrorKind, msg: impl fmt::Display) -> Frame {
+        Frame::Error(format!("{} {}", kind.code(), msg))
+    }
+
+    /// 返回 Redis 对无法识别的选项等语法错误使用的错误帧。
+    pub fn error_syntax() -> Frame {
+        Frame::typed_error(ErrorKind::Err, "syntax error")
+    }
+
+    /// 返回对保存了其他类型值的键执行操作时的错误帧。
+    pub fn error_wrongtype() -> Frame {
+        Frame::typed_error(
+            ErrorKind::WrongType,
+            "Operation against a key holding the wrong kind of value",
+        )
+    }
+
+    /// 返回连接尚未认证时的错误帧。
+    pub fn error_noauth() -> Frame {
+        Frame::typed_error(ErrorKind::NoAuth, "Authentication required.")
+    }
+
+    /// 返回目标键已经存在时的错误帧。
+    pub fn error_busykey() -> Frame {
+        Frame::typed_error(ErrorKind::BusyKey, "Target key name already exists.")
+    }
+
     /// 将一个 "bulk" 帧推入数组。`self` 必须是一个数组帧。
     ///
     /// # Panic
     ///
     /// 当 `self` 不是数组时会触发panic
-    pub(crate) fn push_bulk(&mut self, bytes: Bytes) {
+    pub fn push_bulk(&mut self, bytes: Bytes) {
         match self {
             Frame::Array(vec) => {
                 vec.push(Frame::Bulk(bytes));
@@ -52,7 +343,7 @@ impl Frame {
     /// # Panic
     ///
     /// 当 `self` 不是数组时会触发panic
-    pub(crate) fn push_int(&mut self, value: u64) {
+    pub fn push_int(&mut self, value: u64) {
         match self {
             Frame::Array(vec) => {
                 vec.push(Frame::Integer(value));
@@ -62,107 +353,238 @@ impl Frame {
     }
 
     /// 检查是否可以从 `src` 解码出完整的消息
+    ///
+    /// 如果数据不是有效的帧，返回的错误会指出出错的字节偏移量（相对于帧的起始位置）和帧类型；
+    /// 对于嵌套在数组中的元素，还会指出元素在数组中的位置。
+    ///
+    /// 除了 RESP 编码的帧之外，还接受 Redis 的 inline 命令格式（例如 `GET foo\r\n`），
+    /// 以便可以直接使用 netcat 或 telnet 与服务器交互。inline 命令以 `\n` 结尾（前面可以有 `\r`），
+    /// 被解析为由空白分隔的参数组成的 `Bulk` 帧数组。命令之间的空行会被忽略。
+    ///
+    /// 使用默认的 `Limits` 限制帧的大小。
     pub fn check(src: &mut Cursor<&[u8]>) -> Result<(), Error> {
-        match get_u8(src)? {
-            b'+' => {
-                get_line(src)?;
-                Ok(())
-            }
-            b'-' => {
-                get_line(src)?;
-                Ok(())
-            }
-            b':' => {
-                let _ = get_decimal(src)?;
-                Ok(())
-            }
-            b'$' => {
-                if b'-' == peek_u8(src)? {
-                    // Skip '-1\r\n'
-                    skip(src, 4)
-                } else {
-                    // 读取批量字符串
-                    let len: usize = get_decimal(src)?.try_into()?;
-
-                    // 跳过该数量的字节 + 2 (\r\n)。
-                    skip(src, len + 2)
-                }
-            }
-            b'*' => {
-                let len = get_decimal(src)?;
+        Frame::check_with_limits(src, &Limits::default())
+    }
 
-                for _ in 0..len {
-                    Frame::check(src)?;
-                }
+    /// 与 `check` 相同，但使用 `limits` 限制帧的大小。
+    ///
+    /// 超过限制的帧会立即返回协议错误，而不会等待接收其余的数据。
+    pub fn check_with_limits(src: &mut Cursor<&[u8]>, limits: &Limits) -> Result<(), Error> {
+        skip_blank_lines(src);
 
-                Ok(())
-            }
-            actual => Err(format!("protocol error; invalid frame type byte `{}`", actual).into()),
+        if is_inline(peek_u8(src)?) {
+            let start = src.position();
+
+            return match get_inline_line(src) {
+                Ok(line) if line.len() <= limits.max_inline_len => Ok(()),
+                // 尚未收到行尾，但已经收到的数据还没有超过限制。
+                Err(Error::Incomplete) if src.remaining() <= limits.max_inline_len => {
+                    Err(Error::Incomplete)
+                }
+                Ok(_) | Err(Error::Incomplete) => Err(too_large(
+                    start,
+                    "inline command length",
+                    limits.max_inline_len as u64,
+                )),
+                Err(err) => Err(err),
+            };
         }
+
+        check_value(src, limits, 0)
     }
     
     /// 此消息已通过 `check` 验证。
+    ///
+    /// `Simple` 和 `Bulk` 帧的内容会从 `src` 中复制出来。如果数据已经保存在 `Bytes` 中，
+    /// 应使用 `parse_bytes` 以避免复制。
     pub fn parse(src: &mut Cursor<&[u8]>) -> Result<Frame, Error> {
-        match get_u8(src)? {
-            b'+' => {
-                // 读取行并转换为 `Vec<u8>`
-                let line = get_line(src)?.to_vec();
+        let raw = scan_command(src, &Limits::UNLIMITED)?;
+        raw.into_frame(src.get_ref(), None)
+    }
 
-                // 将该行转换为字符串
-                let string = String::from_utf8(line)?;
+    /// 从 `src` 解析一个已通过 `check` 验证的完整帧。
+    ///
+    /// 与 `parse` 不同，返回帧中的 `Simple` 和 `Bulk` 内容是 `src` 的切片，不会复制数据。
+    /// 需要注意的是，只要其中任何一个切片仍然存活，`src` 的整块内存就不会被释放。
+    pub fn parse_bytes(src: &Bytes) -> Result<Frame, Error> {
+        let raw = scan_command(&mut Cursor::new(&src[..]), &Limits::UNLIMITED)?;
+        raw.into_frame(src, Some(src))
+    }
 
-                Ok(Frame::Simple(string))
-            }
-            b'-' => {
-                // 读取行并转换为 `Vec<u8>`
-                let line = get_line(src)?.to_vec();
+    /// 从读缓冲区 `src` 中解码一个帧，同时使用 `limits` 限制帧的大小。
+    ///
+    /// 与先调用 `check_with_limits` 再调用 `parse_bytes` 的结果相同，但只扫描一次数据：
+    /// 验证的同时记录每个字符串在缓冲区中的位置，确认收到完整的帧后，将帧的数据从 `src` 中分离出来，
+    /// 返回的帧中的内容都是这块数据的切片，不会复制。数据不足时返回 `Ok(None)`，`src` 保持不变。
+    ///
+    /// 切片会让整个读缓冲区的内存一直无法释放，而存储命令会把这些切片长期保存在数据库中。
+    /// 因此帧的数据远小于缓冲区时（不到缓冲区容量的 `1 / SHARE_RATIO`），会把帧的数据复制出来，
+    /// 只有占据缓冲区大部分空间的大帧才与缓冲区共享内存。
+    ///
+    /// # 示例
+    ///
+    /// ```
+    /// use bytes::BytesMut;
+    /// use mini_redis::frame::Limits;
+    /// use mini_redis::Frame;
+    ///
+    /// let mut buf = BytesMut::from(&b"*1\r\n$4\r\nPI"[..]);
+    /// assert!(Frame::decode(&mut buf, &Limits::default()).unwrap().is_none());
+    ///
+    /// buf.extend_from_slice(b"NG\r\n");
+    /// let frame = Frame::decode(&mut buf, &Limits::default()).unwrap().unwrap();
+    ///
+    /// assert_eq!("PING", frame.to_string());
+    /// assert!(buf.is_empty());
+    /// ```
+    pub fn decode(src: &mut BytesMut, limits: &Limits) -> Result<Option<Frame>, Error> {
+        let mut buf = Cursor::new(&src[..]);
 
-                // 将该行转换为字符串
-                let string = String::from_utf8(line)?;
+        let raw = match scan_command(&mut buf, limits) {
+            Ok(raw) => raw,
+            Err(Error::Incomplete) => return Ok(None),
+            Err(err) => return Err(err),
+        };
 
-                Ok(Frame::Error(string))
-            }
-            b':' => {
-                let len = get_decimal(src)?;
-                Ok(Frame::Integer(len))
-            }
-            b'$' => {
-                if b'-' == peek_u8(src)? {
-                    let line = get_line(src)?;
+        // `split_to` 不会复制数据：帧中的切片与 `src` 共享同一块内存。小帧则复制出来，
+        // 避免几个字节的值让整个读缓冲区无法释放。
+        let len = buf.position() as usize;
+        let data = if len.saturating_mul(SHARE_RATIO) < src.capacity() {
+            let data = Bytes::copy_from_slice(&src[..len]);
+            src.advance(len);
+            data
+        } else {
+            src.split_to(len).freeze()
+        };
 
-                    if line != b"-1" {
-                        return Err("protocol error; invalid frame format".into());
-                    }
+        raw.into_frame(&data, Some(&data)).map(Some)
+    }
+
+    /// 以原始字节的形式返回 `Bulk` 或 `Simple` 帧的内容。
+    ///
+    /// `Bulk` 帧的内容是任意字节，可能不是有效的 UTF-8，因此需要保留二进制内容时应使用此方法，
+    /// 而不是通过 `to_string` 转换。返回的 `Bytes` 是浅拷贝。
+    ///
+    /// 其他帧类型返回 `None`。
+    pub fn as_bytes(&self) -> Option<Bytes> {
+        match self {
+            Frame::Bulk(data) => Some(data.clone()),
+            Frame::Simple(s) => Some(s.as_bytes().clone()),
+            _ => None,
+        }
+    }
 
-                    Ok(Frame::Null)
-                } else {
-                    // 读取批量字符串
-                    let len = get_decimal(src)?.try_into()?;
-                    let n = len + 2;
+    /// 如果是 `Integer` 帧，返回其值。
+    pub fn as_int(&self) -> Option<u64> {
+        match self {
+            Frame::Integer(n) => Some(*n),
+            _ => None,
+        }
+    }
 
-                    if src.remaining() < n {
-                        return Err(Error::Incomplete);
-                    }
+    /// 如果是 `Bulk` 帧，返回其内容。
+    ///
+    /// 与 `as_bytes` 不同，`Simple` 帧返回 `None`。
+    pub fn as_bulk(&self) -> Option<&Bytes> {
+        match self {
+            Frame::Bulk(data) => Some(data),
+            _ => None,
+        }
+    }
 
-                    let data = Bytes::copy_from_slice(&src.chunk()[..len]);
+    /// 如果是 `Array` 帧，返回其元素。
+    pub fn as_array(&self) -> Option<&[Frame]> {
+        match self {
+            Frame::Array(parts) => Some(parts),
+            _ => None,
+        }
+    }
 
-                    // 跳过该数量的字节 + 2 (\r\n)。
-                    skip(src, n)?;
+    /// 如果是 `Error` 帧，返回其错误类型。错误码无法识别时返回 `None`。
+    pub fn error_kind(&self) -> Option<ErrorKind> {
+        match self {
+            Frame::Error(msg) => ErrorKind::of(msg),
+            _ => None,
+        }
+    }
 
-                    Ok(Frame::Bulk(data))
-                }
+    /// 将 `Array` 帧转换为其元素组成的 `Vec`。
+    ///
+    /// 如果不是 `Array` 帧，则返回“意外帧”错误。
+    pub fn try_into_vec(self) -> crate::Result<Vec<Frame>> {
+        match self {
+            Frame::Array(parts) => Ok(parts),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// 将帧转换为 JSON 值。
+    ///
+    /// `Simple` 和 UTF-8 的 `Bulk` 帧转换为字符串，`Integer` 转换为数字，`Null` 转换为 `null`，
+    /// `Array` 转换为数组，`Error` 转换为 `{"error": "..."}` 对象。
+    /// 不是有效 UTF-8 的 `Bulk` 帧转换为由字节值组成的数组，不会丢失数据。
+    ///
+    /// ```
+    /// use mini_redis::Frame;
+    ///
+    /// let mut frame = Frame::array();
+    /// frame.push_bulk("hello".into());
+    /// frame.push_int(3);
+    ///
+    /// assert_eq!(r#"["hello",3]"#, frame.to_json().to_string());
+    /// ```
+    pub fn to_json(&self) -> serde_json::Value {
+        use serde_json::Value;
+
+        match self {
+            Frame::Simple(response) => Value::from(&response[..]),
+            Frame::Error(msg) => serde_json::json!({ "error": msg }),
+            Frame::Integer(num) => Value::from(*num),
+            Frame::Bulk(data) => match std::str::from_utf8(data) {
+                Ok(string) => Value::from(string),
+                Err(_) => Value::from(data.to_vec()),
+            },
+            Frame::Null => Value::Null,
+            Frame::Array(parts) => parts.iter().map(Frame::to_json).collect(),
+        }
+    }
+
+    /// 将帧按照 RESP 格式编码并追加到 `dst`。
+    ///
+    /// 嵌套的数组会被递归地编码。
+    pub(crate) fn encode(&self, dst: &mut BytesMut) {
+        match self {
+            Frame::Simple(val) => {
+                dst.put_u8(b'+');
+                dst.put_slice(val.as_bytes());
+                dst.put_slice(b"\r\n");
             }
-            b'*' => {
-                let len = get_decimal(src)?.try_into()?;
-                let mut out = Vec::with_capacity(len);
+            Frame::Error(val) => {
+                dst.put_u8(b'-');
+                dst.put_slice(val.as_bytes());
+                dst.put_slice(b"\r\n");
+            }
+            Frame::Integer(val) => {
+                dst.put_u8(b':');
+                put_decimal(dst, *val);
+            }
+            Frame::Null => {
+                dst.put_slice(b"$-1\r\n");
+            }
+            Frame::Bulk(val) => {
+                dst.put_u8(b'$');
+                put_decimal(dst, val.len() as u64);
+                dst.put_slice(val);
+                dst.put_slice(b"\r\n");
+            }
+            Frame::Array(parts) => {
+                dst.put_u8(b'*');
+                put_decimal(dst, parts.len() as u64);
 
-                for _ in 0..len {
-                    out.push(Frame::parse(src)?);
+                for part in parts {
+                    part.encode(dst);
                 }
-
-                Ok(Frame::Array(out))
             }
-            _ => unimplemented!(),
         }
     }
     
@@ -211,6 +633,279 @@ impl fmt::Display for Frame {
     }
 }
 
+/// 检查 `src` 中是否有一个完整的 RESP 编码的帧。`depth` 是帧所在的数组的嵌套层数，顶层的帧为 0。
+fn check_value(src: &mut Cursor<&[u8]>, limits: &Limits, depth: usize) -> Result<(), Error> {
+    let start = src.position();
+
+    match get_u8(src)? {
+        b'+' => {
+            get_line(src)?;
+            Ok(())
+        }
+        b'-' => {
+            get_line(src)?;
+            Ok(())
+        }
+        b':' => {
+            get_decimal(src).map_err(|e| e.at(start, "integer"))?;
+            Ok(())
+        }
+        b'$' => {
+            if b'-' == peek_u8(src)? {
+                if get_line(src)? != b"-1" {
+                    return Err(invalid(start, "null bulk string"));
+                }
+
+                Ok(())
+            } else {
+                // 读取批量字符串
+                let len: usize = get_decimal(src)
+                    .and_then(|len| Ok(len.try_into()?))
+                    .map_err(|e| e.at(start, "bulk string length"))?;
+
+                if len > limits.max_bulk_len {
+                    return Err(too_large(
+                        start,
+                        "bulk string length",
+                        limits.max_bulk_len as u64,
+                    ));
+                }
+
+                // 跳过该数量的字节，然后确认其后紧跟 `\r\n`。
+                skip(src, len)?;
+
+                if get_u8(src)? != b'\r' || get_u8(src)? != b'\n' {
+                    return Err(invalid(start, "bulk string terminator"));
+                }
+
+                Ok(())
+            }
+        }
+        b'*' => {
+            if depth >= limits.max_depth {
+                return Err(too_large(start, "nesting depth", limits.max_depth as u64));
+            }
+
+            let len = get_decimal(src).map_err(|e| e.at(start, "array length"))?;
+
+            if len > limits.max_array_len {
+                return Err(too_large(start, "array length", limits.max_array_len));
+            }
+
+            for i in 0..len {
+                check_value(src, limits, depth + 1).map_err(|e| e.in_array(i, len))?;
+            }
+
+            Ok(())
+        }
+        actual => Err(format!(
+            "protocol error; invalid frame type byte `{}` at offset {}",
+            actual, start
+        )
+        .into()),
+    }
+}
+
+/// 在一次扫描中同时验证并解码一个顶层帧，它可以是 RESP 编码的帧，也可以是 inline 命令。
+///
+/// 与 `check_with_limits` 一样检查 `limits` 并报告错误的位置。
+fn scan_command(src: &mut Cursor<&[u8]>, limits: &Limits) -> Result<Raw, Error> {
+    skip_blank_lines(src);
+
+    if !is_inline(peek_u8(src)?) {
+        return scan_value(src, limits, 0);
+    }
+
+    let line_start = src.position() as usize;
+
+    let line = match get_inline_line(src) {
+        Ok(line) if line.len() <= limits.max_inline_len => line,
+        // 尚未收到行尾，但已经收到的数据还没有超过限制。
+        Err(Error::Incomplete) if src.remaining() <= limits.max_inline_len => {
+            return Err(Error::Incomplete);
+        }
+        Ok(_) | Err(Error::Incomplete) => {
+            return Err(too_large(
+                line_start as u64,
+                "inline command length",
+                limits.max_inline_len as u64,
+            ));
+        }
+        Err(err) => return Err(err),
+    };
+
+    // 按空白将 inline 命令拆分为参数，每个参数作为一个 `Bulk` 帧。
+    let mut parts = vec![];
+    let mut offset = 0;
+
+    for arg in line.split(|b| *b == b' ' || *b == b'\t') {
+        if !arg.is_empty() {
+            let begin = line_start + offset;
+            parts.push(Raw::Bulk(begin..begin + arg.len()));
+        }
+
+        // 跳过参数以及其后的分隔符
+        offset += arg.len() + 1;
+    }
+
+    Ok(Raw::Array(parts))
+}
+
+/// 在一次扫描中同时验证并解码一个 RESP 编码的帧。`depth` 与 `check_value` 中的含义相同。
+fn scan_value(src: &mut Cursor<&[u8]>, limits: &Limits, depth: usize) -> Result<Raw, Error> {
+    let start = src.position();
+
+    match get_u8(src)? {
+        b'+' => {
+            let pos = src.position() as usize;
+            let len = get_line(src)?.len();
+            Ok(Raw::Simple(start, pos..pos + len))
+        }
+        b'-' => {
+            let pos = src.position() as usize;
+            let len = get_line(src)?.len();
+            Ok(Raw::Error(start, pos..pos + len))
+        }
+        b':' => {
+            let value = get_decimal(src).map_err(|e| e.at(start, "integer"))?;
+            Ok(Raw::Integer(value))
+        }
+        b'$' => {
+            if b'-' == peek_u8(src)? {
+                if get_line(src)? != b"-1" {
+                    return Err(invalid(start, "null bulk string"));
+                }
+
+                Ok(Raw::Null)
+            } else {
+                // 读取批量字符串
+                let len: usize = get_decimal(src)
+                    .and_then(|len| Ok(len.try_into()?))
+                    .map_err(|e| e.at(start, "bulk string length"))?;
+
+                if len > limits.max_bulk_len {
+                    return Err(too_large(
+                        start,
+                        "bulk string length",
+                        limits.max_bulk_len as u64,
+                    ));
+                }
+
+                let pos = src.position() as usize;
+
+                // 跳过该数量的字节，然后确认其后紧跟 `\r\n`。
+                skip(src, len)?;
+
+                if get_u8(src)? != b'\r' || get_u8(src)? != b'\n' {
+                    return Err(invalid(start, "bulk string terminator"));
+                }
+
+                Ok(Raw::Bulk(pos..pos + len))
+            }
+        }
+        b'*' => {
+            if depth >= limits.max_depth {
+                return Err(too_large(start, "nesting depth", limits.max_depth as u64));
+            }
+
+            let len = get_decimal(src).map_err(|e| e.at(start, "array length"))?;
+
+            if len > limits.max_array_len {
+                return Err(too_large(start, "array length", limits.max_array_len));
+            }
+
+            // 数组的元素可能还没有全部收到。每个元素至少占 3 个字节，不要按照声明的长度预先分配过多的内存。
+            let capacity = len.min(src.remaining() as u64 / 3);
+            let mut out = Vec::with_capacity(capacity.try_into()?);
+
+            for i in 0..len {
+                out.push(scan_value(src, limits, depth + 1).map_err(|e| e.in_array(i, len))?);
+            }
+
+            Ok(Raw::Array(out))
+        }
+        actual => Err(format!(
+            "protocol error; invalid frame type byte `{}` at offset {}",
+            actual, start
+        )
+        .into()),
+    }
+}
+
+impl Raw {
+    /// 将扫描出的帧转换为 `Frame`。`src` 是扫描时的数据。
+    ///
+    /// 如果提供了 `backing`，它必须是 `src` 所引用的数据，`Simple` 和 `Bulk` 帧的内容会作为它的切片返回；
+    /// 否则内容会被复制。
+    fn into_frame(self, src: &[u8], backing: Option<&Bytes>) -> Result<Frame, Error> {
+        match self {
+            Raw::Simple(start, range) => {
+                let data = slice(src, backing, range);
+                let string =
+                    ByteString::try_from(data).map_err(|_| invalid(start, "simple string"))?;
+                Ok(Frame::Simple(string))
+            }
+            Raw::Error(start, range) => {
+                let string =
+                    String::from_utf8(src[range].to_vec()).map_err(|_| invalid(start, "error"))?;
+                Ok(Frame::Error(string))
+            }
+            Raw::Integer(value) => Ok(Frame::Integer(value)),
+            Raw::Bulk(range) => Ok(Frame::Bulk(slice(src, backing, range))),
+            Raw::Null => Ok(Frame::Null),
+            Raw::Array(parts) => parts
+                .into_iter()
+                .map(|part| part.into_frame(src, backing))
+                .collect::<Result<_, _>>()
+                .map(Frame::Array),
+        }
+    }
+}
+
+/// 返回 `src` 中 `range` 范围内的数据。有 `backing` 时返回其切片，否则复制数据。
+fn slice(src: &[u8], backing: Option<&Bytes>, range: Range<usize>) -> Bytes {
+    match backing {
+        Some(bytes) => bytes.slice(range),
+        None => Bytes::copy_from_slice(&src[range]),
+    }
+}
+
+/// 第一个字节不是 RESP 类型标记的数据被视为 inline 命令。
+fn is_inline(first: u8) -> bool {
+    !matches!(first, b'+' | b'-' | b':' | b'$' | b'*')
+}
+
+/// 跳过 inline 命令之间的空行。
+fn skip_blank_lines(src: &mut Cursor<&[u8]>) {
+    while src.has_remaining() && matches!(src.chunk()[0], b'\r' | b'\n') {
+        src.advance(1);
+    }
+}
+
+/// 读取一个以 `\n` 结尾的 inline 命令行，返回的行不包括行尾的 `\r\n` 或 `\n`。
+fn get_inline_line<'a>(src: &mut Cursor<&'a [u8]>) -> Result<&'a [u8], Error> {
+    let start = src.position() as usize;
+    let data = *src.get_ref();
+
+    match data[start..].iter().position(|b| *b == b'\n') {
+        Some(len) => {
+            src.set_position((start + len + 1) as u64);
+
+            let line = &data[start..start + len];
+            Ok(line.strip_suffix(b"\r").unwrap_or(line))
+        }
+        None => Err(Error::Incomplete),
+    }
+}
+
+/// 将一个以 `\r\n` 结尾的十进制数写入 `dst`。
+fn put_decimal(dst: &mut BytesMut, val: u64) {
+    use std::fmt::Write;
+
+    // 写入 `BytesMut` 不会失败
+    let _ = write!(dst, "{}\r\n", val);
+}
+
 fn peek_u8(src: &mut Cursor<&[u8]>) -> Result<u8, Error> {
     if !src.has_remaining() {
         return Err(Error::Incomplete);
@@ -265,6 +960,49 @@ fn get_line<'a>(src: &mut Cursor<&'a [u8]>) -> Result<&'a [u8], Error> {
     Err(Error::Incomplete)
 }
 
+impl Error {
+    /// 为即将关闭的连接生成发送给对等方的最后一个错误帧。
+    ///
+    /// 格式与 Redis 的诊断信息保持一致：`-ERR Protocol error: ...`。
+    pub(crate) fn to_frame(&self) -> Frame {
+        let msg = self.to_string();
+        let reason = msg.strip_prefix("protocol error; ").unwrap_or(&msg);
+        Frame::typed_error(ErrorKind::Err, format_args!("Protocol error: {}", reason))
+    }
+
+    /// 为 `start` 偏移量处的 `kind` 帧的格式错误附加上下文。
+    fn at(self, start: u64, kind: &str) -> Error {
+        match self {
+            Error::Incomplete => Error::Incomplete,
+            Error::Other(_) => invalid(start, kind),
+        }
+    }
+
+    /// 为数组中第 `index` 个元素（共 `len` 个）的错误附加上下文。
+    ///
+    /// `Incomplete` 不是真正的错误，原样返回。
+    fn in_array(self, index: u64, len: u64) -> Error {
+        match self {
+            Error::Incomplete => Error::Incomplete,
+            Error::Other(err) => format!("{} (array element {} of {})", err, index, len).into(),
+        }
+    }
+}
+
+/// 构造一个指出出错偏移量和帧类型的协议错误。
+fn invalid(start: u64, kind: &str) -> Error {
+    format!("protocol error; invalid {} at offset {}", kind, start).into()
+}
+
+/// 构造一个指出帧超过大小限制的协议错误。
+fn too_large(start: u64, kind: &str, limit: u64) -> Error {
+    format!(
+        "protocol error; {} exceeds the limit of {} at offset {}",
+        kind, limit, start
+    )
+    .into()
+}
+
 impl From<String> for Error {
     fn from(src: String) -> Error {
         Error::Other(src.into())
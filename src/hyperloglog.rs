@@ -0,0 +1,137 @@
+//! 一个精简的 HyperLogLog 实现，用于 `PFADD` 和 `PFCOUNT` 命令。
+//!
+//! HyperLogLog 是一种概率数据结构：它用固定大小的内存估计一个集合中不同元素的数量（基数），
+//! 代价是结果只是近似值。这里使用 2^12 个寄存器，每个寄存器占一个字节，标准误差约为 1.6%。
+//!
+//! 与 Redis 相同，HyperLogLog 被编码为普通的字符串值存储在 `Db` 中，因此 `GET`、`DEL`、
+//! 过期时间等功能都可以直接作用于它。编码由一个魔数头和紧随其后的寄存器组成。
+
+use bytes::{BufMut, Bytes, BytesMut};
+
+/// 用于选择寄存器的哈希位数。
+const PRECISION: u32 = 12;
+
+/// 寄存器的数量。
+const REGISTERS: usize = 1 << PRECISION;
+
+/// 编码后的值的开头，用于识别 HyperLogLog 值。
+const MAGIC: &[u8] = b"HYLL";
+
+/// 不是有效的 HyperLogLog 值时返回的错误。
+pub(crate) const WRONGTYPE: &str = "WRONGTYPE Key is not a valid HyperLogLog string value.";
+
+#[derive(Debug, Clone)]
+pub(crate) struct HyperLogLog {
+    /// 每个寄存器保存落入该寄存器的哈希值中，剩余位的前导零个数加一的最大值。
+    registers: Box<[u8]>,
+}
+
+impl HyperLogLog {
+    /// 创建一个空的 HyperLogLog。
+    pub(crate) fn new() -> HyperLogLog {
+        HyperLogLog {
+            registers: vec![0; REGISTERS].into_boxed_slice(),
+        }
+    }
+
+    /// 从编码后的字符串值中解码 HyperLogLog。如果 `data` 不是有效的编码，则返回 `None`。
+    pub(crate) fn decode(data: &[u8]) -> Option<HyperLogLog> {
+        if data.len() != MAGIC.len() + REGISTERS || !data.starts_with(MAGIC) {
+            return None;
+        }
+
+        let registers = &data[MAGIC.len()..];
+
+        // 寄存器的值不可能超过剩余的哈希位数加一。
+        if registers.iter().any(|&r| r > max_rank()) {
+            return None;
+        }
+
+        Some(HyperLogLog {
+            registers: registers.into(),
+        })
+    }
+
+    /// 将 HyperLogLog 编码为字符串值。
+    pub(crate) fn encode(&self) -> Bytes {
+        let mut data = BytesMut::with_capacity(MAGIC.len() + REGISTERS);
+        data.put_slice(MAGIC);
+        data.put_slice(&self.registers);
+        data.freeze()
+    }
+
+    /// 添加一个元素。如果某个寄存器因此改变，则返回 `true`。
+    pub(crate) fn add(&mut self, element: &[u8]) -> bool {
+        let hash = hash(element);
+
+        // 高位选择寄存器，剩余的位用于计算前导零。末尾补一个 `1`，保证秩不超过 `max_rank()`。
+        let index = (hash >> (64 - PRECISION)) as usize;
+        let rest = (hash << PRECISION) | (1 << (PRECISION - 1));
+        let rank = rest.leading_zeros() as u8 + 1;
+
+        if rank > self.registers[index] {
+            self.registers[index] = rank;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// 将 `other` 合并到 `self` 中。合并后的结果估计两个集合的并集的基数。
+    pub(crate) fn merge(&mut self, other: &HyperLogLog) {
+        for (r, &o) in self.registers.iter_mut().zip(other.registers.iter()) {
+            *r = (*r).max(o);
+        }
+    }
+
+    /// 返回估计的基数。
+    pub(crate) fn count(&self) -> u64 {
+        let m = REGISTERS as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+
+        let sum: f64 = self
+            .registers
+            .iter()
+            .map(|&r| 1.0 / (1u64 << r) as f64)
+            .sum();
+
+        let estimate = alpha * m * m / sum;
+
+        // 基数较小时，原始估计的偏差较大，改用线性计数。
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+
+        let estimate = if estimate <= 2.5 * m && zeros > 0 {
+            m * (m / zeros as f64).ln()
+        } else {
+            estimate
+        };
+
+        estimate.round() as u64
+    }
+}
+
+/// 寄存器可能保存的最大值。
+fn max_rank() -> u8 {
+    (64 - PRECISION + 1) as u8
+}
+
+/// 计算元素的 64 位哈希值。
+///
+/// 使用 FNV-1a，再用 MurmurHash3 的 `fmix64` 打散各个位。这里不使用 `std` 的 `DefaultHasher`，
+/// 因为其算法不保证在不同的 Rust 版本之间保持不变，而编码后的值需要在不同的进程之间保持一致。
+fn hash(data: &[u8]) -> u64 {
+    let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+
+    for &byte in data {
+        h ^= byte as u64;
+        h = h.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    h ^= h >> 33;
+
+    h
+}
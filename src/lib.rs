@@ -14,32 +14,64 @@
 //!
 //! * `cmd`：对支持的 Redis 命令的实现。
 //!
+//! * `acl`：最小化的访问控制列表，限制每个用户可以执行的命令和访问的键。
+//!
+//! * `cluster`：集群风格的哈希槽计算，以及服务器端的槽检查钩子。
+//!
+//! * `sentinel`：监控主节点、在主节点下线时将副本提升为主节点的故障转移协调者。
+//!
+//! * `stream`：流数据类型的条目和 ID，由服务器存储、由客户端返回。
+//!
 //! * `frame`：表示一个 Redis 协议帧。帧作为“命令”和字节表示之间的中间表示。
 
+pub mod acl;
+
 pub mod clients;
 pub use clients::{BlockingClient, BufferedClient, Client};
 
+pub mod clock;
+
+pub mod cluster;
+
 pub mod cmd;
 pub use cmd::Command;
 
+pub mod codec;
+
 mod connection;
-pub use connection::Connection;
+pub use connection::{BufferPool, BufferPoolStats, BufferStats, Connection};
 
 pub mod frame;
 pub use frame::Frame;
 
 mod db;
-use db::Db;
+pub use db::snapshot;
 use db::DbDropGuard;
+pub use db::{Db, DbObserver};
+
+mod hyperloglog;
 
 mod parse;
 use parse::{Parse, ParseError};
 
+mod replication;
+
+pub mod sentinel;
+
 pub mod server;
 
+pub mod stream;
+
 mod shutdown;
 use shutdown::Shutdown;
 
+mod socket;
+use socket::SocketOptions;
+
+mod sync;
+
+mod task;
+
 /// Redis 服务器监听的默认端口。
 ///
 /// 如果没有指定端口，则使用此端口。
@@ -1,7 +1,9 @@
 use crate::Frame;
 
 use bytes::Bytes;
-use std::{fmt, str, vec};
+use bytestring::ByteString;
+use std::convert::TryFrom;
+use std::{fmt, vec};
 
 /// 用于解析命令的工具
 ///
@@ -14,7 +16,7 @@ pub(crate) struct Parse {
 
 /// 解析帧时遇到的错误。
 ///
-/// 仅在运行时处理 `EndOfStream` 错误。所有其他错误都会导致连接终止。
+/// 仅在运行时处理 `EndOfStream` 错误。所有其他错误都使服务器以错误帧响应该命令。
 #[derive(Debug)]
 pub(crate) enum ParseError {
     /// 由于帧被完全消耗，尝试提取值失败。
@@ -44,17 +46,33 @@ impl Parse {
         self.parts.next().ok_or(ParseError::EndOfStream)
     }
 
+    /// 返回剩余的条目数量。
+    pub(crate) fn remaining(&self) -> usize {
+        self.parts.len()
+    }
+
     /// 将下一个条目返回为字符串。
     ///
     /// 如果下一个条目不能表示为字符串，则返回错误。
+    ///
+    /// 返回的 `String` 拥有自己的内存。如果只需要读取字符串，应使用 `next_str` 以避免分配。
     pub(crate) fn next_string(&mut self) -> Result<String, ParseError> {
+        self.next_str().map(String::from)
+    }
+
+    /// 将下一个条目返回为字符串视图。
+    ///
+    /// 返回的 `ByteString` 与帧共享内存：它只是接收到的数据的一个视图，不会复制或分配。
+    ///
+    /// 如果下一个条目不能表示为字符串，则返回错误。
+    pub(crate) fn next_str(&mut self) -> Result<ByteString, ParseError> {
         match self.next()? {
             // `Simple` 和 `Bulk` 表示都可以是字符串。字符串被解析为 UTF-8。
             // 虽然错误被存储为字符串，但它们被视为不同的类型。
             Frame::Simple(s) => Ok(s),
-            Frame::Bulk(data) => str::from_utf8(&data[..])
-                .map(|s| s.to_string())
-                .map_err(|_| "protocol error; invalid string".into()),
+            Frame::Bulk(data) => {
+                ByteString::try_from(data).map_err(|_| "protocol error; invalid string".into())
+            }
             frame => Err(format!(
                 "protocol error; expected simple frame or bulk frame, got {:?}",
                 frame
@@ -69,7 +87,7 @@ impl Parse {
         match self.next()? {
             // `Simple` 和 `Bulk` 表示都可以是原始字节。
             // 虽然错误存储为字符串并且可以表示为原始字节，但它们被视为不同的类型。
-            Frame::Simple(s) => Ok(Bytes::from(s.into_bytes())),
+            Frame::Simple(s) => Ok(s.into_bytes()),
             Frame::Bulk(data) => Ok(data),
             frame => Err(format!(
                 "protocol error; expected simple frame or bulk frame, got {:?}",
@@ -99,6 +117,22 @@ impl Parse {
         }
     }
 
+    /// 返回下一个条目作为有符号整数。
+    ///
+    /// 与 `next_int` 相同，但允许负数。整数帧是无符号的，因此负数只能以 `Simple` 或 `Bulk` 帧的形式出现。
+    pub(crate) fn next_signed_int(&mut self) -> Result<i64, ParseError> {
+        use atoi::atoi;
+
+        const MSG: &str = "protocol error; invalid number";
+
+        match self.next()? {
+            Frame::Integer(v) => i64::try_from(v).map_err(|_| MSG.into()),
+            Frame::Simple(data) => atoi::<i64>(data.as_bytes()).ok_or_else(|| MSG.into()),
+            Frame::Bulk(data) => atoi::<i64>(&data).ok_or_else(|| MSG.into()),
+            frame => Err(format!("protocol error; expected int frame but got {:?}", frame).into()),
+        }
+    }
+
     /// 确保数组中没有更多条目。
     pub(crate) fn finish(&mut self) -> Result<(), ParseError> {
         if self.parts.next().is_none() {
@@ -0,0 +1,217 @@
+//! 副本一侧的复制。
+//!
+//! `REPLICAOF host port` 使服务器成为副本：后台任务连接到主节点并发送 `PSYNC`，完整同步时以主节点发送的快照
+//! 替换所有数据，之后执行主节点发送的写命令，并记住处理到的复制偏移量。副本在执行一批写命令之后、以及没有写命令时
+//! 每秒一次以 `REPLCONF ACK offset` 向主节点确认处理到的偏移量，主节点据此记录每个副本的进度，`WAIT` 据此统计确认了写入的副本。连接断开之后任务等待一段时间重新连接，
+//! 以 `PSYNC replid offset` 请求从该偏移量继续，数据仍在主节点的积压缓冲区中时不需要再次传输快照。
+//!
+//! 主节点一侧参见 `cmd::PSync` 和 `db::backlog`。
+
+use crate::cmd::{replconf_ack, PSync};
+use crate::{Command, Connection, Db, Frame};
+
+use bytes::BytesMut;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpStream;
+use tokio::task::JoinHandle;
+use tokio::time::{self, Duration};
+use tracing::{info, warn};
+
+/// 与主节点的连接断开之后，重新连接之前等待的时间。
+const RECONNECT_DELAY: Duration = Duration::from_millis(500);
+
+/// 没有新的写命令时，副本向主节点确认偏移量的间隔。
+const ACK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 副本与主节点之间的连接。被丢弃时复制任务被取消。
+#[derive(Debug)]
+pub(crate) struct Link {
+    /// 主节点的主机名
+    host: String,
+
+    /// 主节点的端口
+    port: u16,
+
+    /// 复制任务更新的状态
+    shared: Arc<LinkShared>,
+
+    /// 复制任务的句柄
+    task: JoinHandle<()>,
+}
+
+/// 复制任务与 `Link` 共享的状态。
+#[derive(Debug, Default)]
+struct LinkShared {
+    /// 是否已经完成同步、正在接收写命令
+    up: AtomicBool,
+
+    /// 已经处理到的主节点的复制 ID 和偏移量。`None` 表示尚未完成过同步。
+    position: Mutex<Option<(String, u64)>>,
+}
+
+/// 与主节点的连接的状态，由 `INFO replication` 报告。
+#[derive(Debug, Clone)]
+pub(crate) struct LinkStatus {
+    /// 主节点的主机名
+    pub(crate) host: String,
+
+    /// 主节点的端口
+    pub(crate) port: u16,
+
+    /// 是否已经完成同步、正在接收写命令
+    pub(crate) up: bool,
+
+    /// 已经处理到的主节点的复制偏移量
+    pub(crate) offset: u64,
+}
+
+impl Link {
+    /// 开始从 `host:port` 上的主节点复制数据。
+    pub(crate) fn start(db: Db, host: String, port: u16) -> Link {
+        let shared = Arc::new(LinkShared::default());
+
+        let task = crate::task::spawn(
+            "mini-redis::replication",
+            run(db, host.clone(), port, shared.clone()),
+        );
+
+        Link {
+            host,
+            port,
+            shared,
+            task,
+        }
+    }
+
+    /// 返回连接的状态。
+    pub(crate) fn status(&self) -> LinkStatus {
+        let offset = match &*self.shared.position.lock().unwrap() {
+            Some((_, offset)) => *offset,
+            None => 0,
+        };
+
+        LinkStatus {
+            host: self.host.clone(),
+            port: self.port,
+            up: self.shared.up.load(Ordering::Relaxed),
+            offset,
+        }
+    }
+}
+
+impl Drop for Link {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// 复制任务：连接到主节点并接收写命令，连接断开之后重新连接。
+async fn run(db: Db, host: String, port: u16, shared: Arc<LinkShared>) {
+    loop {
+        match sync(&db, &host, port, &shared).await {
+            Ok(()) => info!(%host, port, "primary closed the replication connection"),
+            Err(err) => warn!(%host, port, cause = %err, "replication connection failed"),
+        }
+
+        shared.up.store(false, Ordering::Relaxed);
+        time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+/// 与主节点同步一次，直到连接断开。
+async fn sync(db: &Db, host: &str, port: u16, shared: &LinkShared) -> crate::Result<()> {
+    let socket = TcpStream::connect((host, port)).await?;
+    let mut connection = Connection::new(socket);
+
+    let position = shared.position.lock().unwrap().clone();
+    connection
+        .write_frame(&PSync::new(position).into_frame())
+        .await?;
+
+    let reply = match connection.read_frame().await? {
+        Some(Frame::Simple(reply)) => reply,
+        Some(Frame::Error(err)) => return Err(err.to_string().into()),
+        Some(frame) => return Err(format!("unexpected PSYNC reply: {:?}", frame).into()),
+        None => return Err("connection closed by the primary".into()),
+    };
+
+    let mut parts = reply.split(' ');
+
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some("FULLRESYNC"), Some(replid), Some(offset)) => {
+            let offset = offset.parse::<u64>()?;
+
+            let snapshot = match connection.read_frame().await? {
+                Some(Frame::Bulk(data)) => data,
+                _ => return Err("expected a snapshot after FULLRESYNC".into()),
+            };
+
+            let keys = db.replace_with_snapshot(&snapshot)?;
+            info!(
+                replid,
+                offset, keys, "full resynchronization with the primary"
+            );
+
+            *shared.position.lock().unwrap() = Some((replid.to_string(), offset));
+        }
+        (Some("CONTINUE"), Some(_), None) => {
+            info!("continuing replication from the primary's backlog");
+        }
+        _ => return Err(format!("unexpected PSYNC reply: {}", reply).into()),
+    }
+
+    shared.up.store(true, Ordering::Relaxed);
+
+    // 第一次立即完成，同步之后马上确认一次偏移量。
+    let mut ack_interval = time::interval(ACK_INTERVAL);
+
+    loop {
+        tokio::select! {
+            res = connection.read_frame() => {
+                let frame = match res? {
+                    Some(frame) => frame,
+                    None => return Ok(()),
+                };
+                apply(db, shared, frame).await?;
+
+                // 先执行已经到达的其他写命令，一次确认覆盖整批写命令。
+                while let Some(frame) = connection.try_read_frame()? {
+                    apply(db, shared, frame).await?;
+                }
+            }
+            _ = ack_interval.tick() => {}
+        }
+
+        let offset = match &*shared.position.lock().unwrap() {
+            Some((_, offset)) => *offset,
+            None => 0,
+        };
+        connection.write_frame(&replconf_ack(offset)).await?;
+    }
+}
+
+/// 执行主节点发送的一个写命令，并将处理到的偏移量向后移动。
+async fn apply(db: &Db, shared: &LinkShared, frame: Frame) -> crate::Result<()> {
+    // 偏移量按编码之后的字节数计算，与主节点的积压缓冲区一致。
+    let mut encoded = BytesMut::new();
+    frame.encode(&mut encoded);
+
+    let cmd = Command::from_frame(frame.clone())?;
+
+    if !cmd.is_propagated() {
+        return Err(format!("command '{}' cannot be replicated", cmd.get_name()).into());
+    }
+
+    // 主节点只发送执行成功的写命令的效果。副本的效果同样被记录，级联的副本和副本自己的 AOF 与主节点一致。
+    let ((), write) = db.batch_logged(|batch| {
+        let _ = cmd.execute_propagated(batch, frame);
+    });
+    let _ = write.wait().await;
+
+    if let Some((_, offset)) = &mut *shared.position.lock().unwrap() {
+        *offset += encoded.len() as u64;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,333 @@
+//! 一个极简的故障转移协调者，类似于 Redis Sentinel 的最小子集。
+//!
+//! 协调者定期向主节点发送 `PING`。主节点在 `down_after` 内一直没有响应时，协调者询问每个副本的复制偏移量，
+//! 对复制得最多的副本执行 `REPLICAOF NO ONE` 使其成为新的主节点，再让其他副本改为复制新的主节点。
+//! 原来的主节点恢复之后，协调者让它成为新主节点的副本。
+//!
+//! 每次故障转移之后，协调者在新的主节点和所有副本上的 `channel` 频道（默认为 `+switch-master`）发布
+//! `<原主节点地址> <新主节点地址>`，订阅了该频道的客户端据此切换到新的主节点。
+//!
+//! 与 Redis Sentinel 不同，这里只有一个协调者，不需要多数派的同意：协调者与主节点之间的网络故障同样会触发故障转移。
+
+use crate::clients::Client;
+
+use bytes::Bytes;
+use std::future::Future;
+use tokio::time::{self, Duration, Instant, MissedTickBehavior};
+use tracing::{error, info, warn};
+
+/// 默认的 `PING` 间隔。
+const DEFAULT_PING_INTERVAL: Duration = Duration::from_millis(500);
+
+/// 主节点默认在多长时间内没有响应之后被认为已经下线。
+const DEFAULT_DOWN_AFTER: Duration = Duration::from_secs(5);
+
+/// 默认的通知频道，与 Redis Sentinel 的事件名称相同。
+pub const DEFAULT_CHANNEL: &str = "+switch-master";
+
+/// 故障转移协调者的配置。
+///
+/// # 示例
+///
+/// ```no_run
+/// use mini_redis::sentinel;
+/// use std::time::Duration;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let config = sentinel::Config::new("127.0.0.1:6379")
+///         .replica("127.0.0.1:6380")
+///         .replica("127.0.0.1:6381")
+///         .down_after(Duration::from_secs(3));
+///
+///     sentinel::run(config, tokio::signal::ctrl_c()).await.unwrap();
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// 主节点的地址
+    primary: String,
+
+    /// 副本的地址
+    replicas: Vec<String>,
+
+    /// `PING` 的间隔，也是每次请求的超时时间
+    ping_interval: Duration,
+
+    /// 主节点在多长时间内没有响应之后被认为已经下线
+    down_after: Duration,
+
+    /// 发布故障转移通知的频道
+    channel: String,
+}
+
+impl Config {
+    /// 创建监控 `primary`（`HOST:PORT` 格式）的配置。
+    pub fn new(primary: impl Into<String>) -> Config {
+        Config {
+            primary: primary.into(),
+            replicas: vec![],
+            ping_interval: DEFAULT_PING_INTERVAL,
+            down_after: DEFAULT_DOWN_AFTER,
+            channel: DEFAULT_CHANNEL.to_string(),
+        }
+    }
+
+    /// 添加一个副本（`HOST:PORT` 格式）。副本应当已经在复制主节点，参见 `server::Config::replica_of`。
+    pub fn replica(mut self, addr: impl Into<String>) -> Config {
+        self.replicas.push(addr.into());
+        self
+    }
+
+    /// 设置 `PING` 的间隔，默认为 500 毫秒。每次请求的超时时间与间隔相同。
+    pub fn ping_interval(mut self, interval: Duration) -> Config {
+        self.ping_interval = interval;
+        self
+    }
+
+    /// 设置主节点在多长时间内没有响应之后被认为已经下线，默认为 5 秒。
+    pub fn down_after(mut self, duration: Duration) -> Config {
+        self.down_after = duration;
+        self
+    }
+
+    /// 设置发布故障转移通知的频道，默认为 `+switch-master`。
+    pub fn channel(mut self, channel: impl Into<String>) -> Config {
+        self.channel = channel.into();
+        self
+    }
+}
+
+/// 协调者的状态。
+struct Sentinel {
+    config: Config,
+
+    /// 到主节点的连接。`PING` 失败之后被丢弃，下一次 `PING` 时重新连接。
+    client: Option<Client>,
+
+    /// 主节点最后一次响应 `PING` 的时间
+    last_reply: Instant,
+
+    /// 被替换的主节点。它们恢复之后被改为复制新的主节点。
+    demoted: Vec<String>,
+}
+
+/// 运行协调者，直到 `shutdown` 完成。
+///
+/// 地址的格式不正确时返回 `Err`。连接或者命令失败只会被记录，协调者继续运行。
+pub async fn run(config: Config, shutdown: impl Future) -> crate::Result<()> {
+    for addr in config.replicas.iter().chain(Some(&config.primary)) {
+        split_addr(addr)?;
+    }
+
+    let mut interval = time::interval(config.ping_interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    let mut sentinel = Sentinel {
+        config,
+        client: None,
+        last_reply: Instant::now(),
+        demoted: vec![],
+    };
+
+    info!(primary = %sentinel.config.primary, "monitoring primary");
+
+    tokio::pin!(shutdown);
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => sentinel.tick().await,
+            _ = &mut shutdown => return Ok(()),
+        }
+    }
+}
+
+impl Sentinel {
+    /// 检查一次主节点，必要时进行故障转移。
+    async fn tick(&mut self) {
+        if self.ping_primary().await {
+            self.last_reply = Instant::now();
+        } else if self.last_reply.elapsed() >= self.config.down_after {
+            self.failover().await;
+        }
+
+        self.reconfigure_demoted().await;
+    }
+
+    /// 向主节点发送 `PING`，返回是否收到响应。
+    async fn ping_primary(&mut self) -> bool {
+        let timeout = self.config.ping_interval;
+        let primary = &self.config.primary;
+        let client = &mut self.client;
+
+        let res = with_timeout(timeout, async {
+            if client.is_none() {
+                *client = Some(Client::connect(primary).await?);
+            }
+
+            client.as_mut().unwrap().ping(None).await
+        })
+        .await;
+
+        if let Err(err) = res {
+            warn!(%primary, cause = %err, "primary did not reply to PING");
+            self.client = None;
+            return false;
+        }
+
+        true
+    }
+
+    /// 将复制得最多的副本提升为主节点，让其他副本复制它，并发布通知。
+    async fn failover(&mut self) {
+        let old = self.config.primary.clone();
+        warn!(primary = %old, "primary is down, starting failover");
+
+        let mut best: Option<(usize, u64)> = None;
+        for (i, replica) in self.config.replicas.iter().enumerate() {
+            match self.repl_offset(replica).await {
+                Ok(offset) if best.is_none_or(|(_, best)| offset > best) => {
+                    best = Some((i, offset))
+                }
+                Ok(_) => {}
+                Err(err) => warn!(%replica, cause = %err, "replica is unreachable"),
+            }
+        }
+
+        let chosen = match best {
+            Some((i, _)) => i,
+            None => {
+                error!("no replica can be promoted, failover postponed");
+                return;
+            }
+        };
+
+        let new = self.config.replicas[chosen].clone();
+        if let Err(err) = self
+            .request(
+                &new,
+                |mut client| async move { client.replicaof_no_one().await },
+            )
+            .await
+        {
+            error!(replica = %new, cause = %err, "failed to promote replica");
+            return;
+        }
+
+        info!(old = %old, new = %new, "replica promoted to primary");
+
+        self.config.replicas.remove(chosen);
+        self.config.primary = new.clone();
+        self.client = None;
+        self.last_reply = Instant::now();
+        self.demoted.push(old.clone());
+
+        let (host, port) = split_addr(&new).expect("addresses are checked at startup");
+        for replica in &self.config.replicas {
+            let host = host.to_string();
+            let res = self
+                .request(replica, |mut client| async move {
+                    client.replicaof(&host, port).await
+                })
+                .await;
+
+            if let Err(err) = res {
+                warn!(%replica, cause = %err, "failed to reconfigure replica");
+            }
+        }
+
+        let message = Bytes::from(format!("{} {}", old, new));
+        for node in Some(&new).into_iter().chain(&self.config.replicas) {
+            let channel = self.config.channel.clone();
+            let message = message.clone();
+            let res = self
+                .request(node, |mut client| async move {
+                    client.publish(&channel, message).await
+                })
+                .await;
+
+            if let Err(err) = res {
+                warn!(%node, cause = %err, "failed to publish failover notification");
+            }
+        }
+    }
+
+    /// 让恢复了的原主节点复制当前的主节点。
+    async fn reconfigure_demoted(&mut self) {
+        if self.demoted.is_empty() {
+            return;
+        }
+
+        let (host, port) =
+            split_addr(&self.config.primary).expect("addresses are checked at startup");
+        let mut remaining = vec![];
+
+        for node in std::mem::take(&mut self.demoted) {
+            let host = host.to_string();
+            let res = self
+                .request(&node, |mut client| async move {
+                    client.replicaof(&host, port).await
+                })
+                .await;
+
+            match res {
+                Ok(()) => {
+                    info!(%node, primary = %self.config.primary, "former primary is now a replica");
+                    self.config.replicas.push(node);
+                }
+                Err(_) => remaining.push(node),
+            }
+        }
+
+        self.demoted = remaining;
+    }
+
+    /// 返回副本已经处理到的主节点的复制偏移量。
+    async fn repl_offset(&self, replica: &str) -> crate::Result<u64> {
+        let info = self
+            .request(replica, |mut client| async move {
+                client.info(Some("replication")).await
+            })
+            .await?;
+
+        let offset = info
+            .lines()
+            .find_map(|line| line.strip_prefix("slave_repl_offset:"))
+            .ok_or("not a replica")?;
+
+        Ok(offset.parse()?)
+    }
+
+    /// 连接到 `addr` 并执行 `f`，整个过程的超时时间为 `PING` 的间隔。
+    async fn request<T, F, Fut>(&self, addr: &str, f: F) -> crate::Result<T>
+    where
+        F: FnOnce(Client) -> Fut,
+        Fut: Future<Output = crate::Result<T>>,
+    {
+        with_timeout(self.config.ping_interval, async {
+            let client = Client::connect(addr).await?;
+            f(client).await
+        })
+        .await
+    }
+}
+
+/// 在 `timeout` 内完成 `future`，超时时返回错误。
+async fn with_timeout<T>(
+    timeout: Duration,
+    future: impl Future<Output = crate::Result<T>>,
+) -> crate::Result<T> {
+    match time::timeout(timeout, future).await {
+        Ok(res) => res,
+        Err(_) => Err("timed out".into()),
+    }
+}
+
+/// 将 `HOST:PORT` 格式的地址拆分为主机名和端口。
+fn split_addr(addr: &str) -> crate::Result<(&str, u16)> {
+    let (host, port) = addr
+        .rsplit_once(':')
+        .ok_or_else(|| format!("expected `HOST:PORT`, got `{}`", addr))?;
+
+    Ok((host, port.parse()?))
+}
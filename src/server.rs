@@ -3,14 +3,35 @@
 //! 提供一个异步 `run` 函数，监听传入的连接，
 //! 每个连接生成一个任务。
 
-use crate::{Command, Connection, Db, DbDropGuard, Shutdown};
+use crate::acl::{self, Acl, User};
+use crate::clock::{Clock, SystemClock};
+use crate::cluster::{self, SlotCheck};
+use crate::cmd::{self, CommandHandler, Registry, Subscriptions, Tracking, Unknown};
+#[cfg(feature = "scripting")]
+use crate::db::DEFAULT_SCRIPT_INSTRUCTION_LIMIT;
+use crate::db::{DbObserver, COMMAND_EVENT, DEFAULT_BACKLOG_SIZE, DEFAULT_SNAPSHOT_PATH};
+use crate::frame::{self, ErrorKind};
+use crate::replication::Link;
+use crate::{
+    task, BufferPool, Command, Connection, Db, DbDropGuard, Frame, Shutdown, SocketOptions,
+};
 
-use std::future::Future;
+use bytestring::ByteString;
+use std::fmt;
+use std::future::{self, Future};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
-use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::{broadcast, mpsc, Semaphore};
-use tokio::time::{self, Duration};
-use tracing::{debug, error, info, instrument};
+use std::task::Poll;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio::sync::{broadcast, mpsc, oneshot, Notify};
+use tokio::task::JoinHandle;
+use tokio::time::{self, Duration, Instant};
+use tracing::{debug, error, field, info, info_span, instrument, warn, Instrument};
+
+pub use crate::db::AppendFsync;
 
 /// 服务器侦听器状态。在 `run` 调用中创建。它包括一个执行 TCP 监听和初始化每个连接状态的 `run` 方法。
 #[derive(Debug)]
@@ -22,15 +43,58 @@ struct Listener {
     /// 这里包含了一个 `Arc` 的包装器。内部的 `Db` 可以被检索并传入每个连接状态 (`Handler`)。
     db_holder: DbDropGuard,
 
-    /// 由 `run` 调用者提供的 TCP 侦听器。
-    listener: TcpListener,
+    /// 由 `run` 调用者提供的 TCP 侦听器。服务器同时接受所有侦听器上的连接。
+    listeners: Vec<TcpListener>,
+
+    /// 下一次 `accept` 首先轮询的侦听器。轮流从不同的侦听器开始，使繁忙的侦听器不会让其他侦听器饿死。
+    next_listener: usize,
+
+    /// 应用于每个连接的帧大小限制。
+    frame_limits: frame::Limits,
+
+    /// 应用于每个连接的输出缓冲区上限。
+    output_limit: Option<usize>,
+
+    /// 应用于每个连接的读缓冲区上限。
+    read_buffer_limit: Option<usize>,
+
+    /// 集群模式下的槽检查钩子，传递给每个连接。
+    slot_check: Option<Arc<dyn SlotCheck>>,
+
+    /// 用户表，传递给每个连接。
+    acl: Arc<Acl>,
+
+    /// 是否拒绝所有写命令，传递给每个连接。
+    read_only: bool,
+
+    /// 自定义命令，传递给每个连接。
+    commands: Arc<Registry>,
+
+    /// 命令执行前后调用的钩子，传递给每个连接。
+    command_hooks: Arc<[Arc<dyn CommandHook>]>,
+
+    /// 每个连接的命令速率限制。
+    rate_limit: Option<RateLimit>,
+
+    /// 接受连接失败后重试的最长等待时间。
+    max_accept_backoff: Duration,
+
+    /// 应用于每个接受的套接字的 TCP 选项。
+    socket_options: SocketOptions,
+
+    /// 新连接必须在多长时间内发送第一个完整的帧。
+    handshake_timeout: Option<Duration>,
 
     /// 限制最大连接数量。
     ///
-    /// 使用 `Semaphore` 来限制最大连接数量。在尝试接受新连接之前，
-    ///
-    /// 当处理程序完成处理一个连接时，许可证会被返回到信号量。
-    limit_connections: Arc<Semaphore>,
+    /// 每个连接持有一个 `ConnectionPermit`，当处理程序完成处理一个连接时，许可证被丢弃，名额被归还。
+    limit_connections: Arc<ConnectionLimit>,
+
+    /// 连接数量达到上限时，是否接受新连接并以错误响应后关闭，而不是等待名额。
+    reject_excess_connections: bool,
+
+    /// 在连接之间复用读写缓冲区。
+    buffer_pool: BufferPool,
 
     /// 向所有活动连接广播关闭信号。
     ///
@@ -66,6 +130,9 @@ struct Handler {
     /// the byte level protocol parsing details encapsulated in `Connection`.
     connection: Connection,
 
+    /// 对端地址，记录在每个命令的 span 中并传递给 `command_hooks`。
+    peer: SocketAddr,
+
     /// 监听关闭通知。
     ///
     /// 这是对于 `Listener` 中的发送器配对的 `broadcast::Receiver` 的包装。
@@ -74,16 +141,969 @@ struct Handler {
     /// 此时连接才会终止。
     shutdown: Shutdown,
 
+    /// 集群模式下的槽检查钩子。访问不属于本节点的键的命令不会被执行，而是以 `MOVED` 错误响应。
+    slot_check: Option<Arc<dyn SlotCheck>>,
+
+    /// 用户表，`AUTH` 在其中查找用户。
+    acl: Arc<Acl>,
+
+    /// 是否以 `READONLY` 错误拒绝所有写命令。
+    read_only: bool,
+
+    /// 自定义命令。内置命令中没有的命令名称在这里查找。
+    commands: Arc<Registry>,
+
+    /// 命令执行前后调用的钩子。
+    command_hooks: Arc<[Arc<dyn CommandHook>]>,
+
+    /// 连接当前的用户。命令执行之前检查该用户的权限。`None` 表示连接尚未认证。
+    user: Option<Arc<User>>,
+
+    /// 命令速率限制的令牌桶。`None` 表示不限制速率。
+    rate_limit: Option<TokenBucket>,
+
+    /// 连接的状态，决定如何处理接收到的命令。
+    state: State,
+
+    /// `CLIENT TRACKING` 的状态。开启时，在等待命令的同时推送被跟踪的键的失效通知。
+    tracking: Tracking,
+
+    /// 转发的消息是否附带发布者的连接 ID 和发布时间，由 `CLIENT PUBSUB-METADATA` 设置。
+    pubsub_metadata: bool,
+
+    /// 上一个命令是否为 `ASKING`。只对紧随其后的一个命令有效，参见 `cluster::check_command`。
+    asking: bool,
+
+    /// 服务器的连接数量上限，`CONFIG SET maxclients` 修改它。
+    connection_limit: Arc<ConnectionLimit>,
+
+    /// 服务器的缓冲池，`INFO` 报告它的统计信息。
+    buffer_pool: BufferPool,
+
+    /// 收到第一个完整的帧的期限，超过时关闭连接。收到第一个帧之后为 `None`。
+    handshake_deadline: Option<Instant>,
+
+    /// 上次让出执行权之后处理的命令数量，参见 `COMMANDS_PER_YIELD`。
+    commands_since_yield: u32,
+
     /// 不直接使用。相反，当 `Handler` 被丢弃时...？
     _shutdown_complete: mpsc::Sender<()>,
 }
 
-/// Redis 服务器可接受的最大并发连接数。
+/// 连接的状态。
+///
+/// 新连接处于 `Normal` 状态。`MULTI` 进入 `Multi` 状态，`EXEC` 或 `DISCARD` 离开；
+/// `SUBSCRIBE` 进入 `Subscribed` 状态，取消所有订阅后离开。`RESET` 在任何状态下都回到 `Normal` 状态。
+#[derive(Debug)]
+enum State {
+    /// 正常地执行命令。
+    Normal,
+
+    /// 命令及其帧被排队，直到 `EXEC` 原子地执行它们。帧用于记录写命令的效果，参见 `Command::execute_propagated`。
+    /// `aborted` 表示排队时出现了错误，`EXEC` 将放弃事务。
+    Multi {
+        queued: Vec<(Command, Frame)>,
+        aborted: bool,
+    },
+
+    /// 订阅了至少一个频道。在等待命令的同时，转发已订阅频道上的消息。
+    Subscribed(Subscriptions),
+}
+
+impl State {
+    /// 等待已订阅的频道上的下一条消息。不处于订阅状态时永远不会完成。
+    async fn next_message(&mut self) -> Frame {
+        if let State::Subscribed(subscriptions) = self {
+            if let Some(frame) = subscriptions.next_message().await {
+                return frame;
+            }
+        }
+
+        std::future::pending().await
+    }
+}
+
+/// 连接执行命令之前需要检查的权限：用户的 ACL、只读模式以及集群模式下的槽。
+///
+/// 客户端发送的命令和脚本通过 `redis.call` 执行的命令使用同样的检查。
+struct Permissions<'a> {
+    /// 连接的用户，`None` 表示尚未认证。
+    user: Option<&'a User>,
+
+    /// 服务器是否以只读模式运行。
+    read_only: bool,
+
+    /// 集群模式下的槽检查。
+    slot_check: Option<&'a dyn SlotCheck>,
+
+    /// 上一个命令是否为 `ASKING`。
+    asking: bool,
+
+    db: &'a Db,
+}
+
+impl Permissions<'_> {
+    /// 检查能否执行命令，不允许执行时返回发送给客户端的错误帧。
+    fn check(&self, cmd: &Command) -> Option<Frame> {
+        let denied = match self.user {
+            Some(user) => acl::check_command(user, cmd),
+            None => Some(Frame::error_noauth()),
+        };
+
+        if denied.is_some() {
+            return denied;
+        }
+
+        if (self.read_only || self.db.is_replica()) && cmd.is_write() {
+            return Some(Frame::typed_error(
+                ErrorKind::ReadOnly,
+                "You can't write against a read only replica.",
+            ));
+        }
+
+        // 与 Redis 一样，写入 AOF 失败之后拒绝写命令，以免客户端以为写入已经持久化。
+        if cmd.is_write() {
+            if let Some(msg) = self.db.aof_write_error() {
+                return Some(Frame::error(msg));
+            }
+        }
+
+        // 在集群模式下，访问不属于本节点的键的命令被重定向到负责的节点。
+        self.slot_check
+            .and_then(|check| cluster::check_command(cmd, check, self.asking, self.db))
+    }
+}
+
+/// Redis 服务器默认可接受的最大并发连接数。
 ///
 /// 当达到此限制时，服务器将停止接受连接，直到有活动连接终止。
-/// 是一样的)。
-/// well).
-const MAX_CONNECTIONS: usize = 250;
+const DEFAULT_MAX_CONNECTIONS: usize = 250;
+
+/// 每个发布/订阅频道默认能够缓冲的消息数量。
+const DEFAULT_CHANNEL_CAPACITY: usize = 1024;
+
+/// 缓冲池默认最多保留的空闲缓冲区数量。
+const DEFAULT_BUFFER_POOL_SIZE: usize = 128;
+
+/// 每个连接积压的发布/订阅消息默认的上限，与 Redis 对发布/订阅客户端的 `client-output-buffer-limit` 的硬限制相同。
+const DEFAULT_OUTPUT_LIMIT: usize = 32 * 1024 * 1024;
+
+/// 每个连接的读缓冲区中不完整的帧默认的上限，与 Redis 的 `client-query-buffer-limit` 相同。
+const DEFAULT_READ_BUFFER_LIMIT: usize = 1024 * 1024 * 1024;
+
+/// 接受连接失败后重试的默认最长等待时间。
+const DEFAULT_MAX_ACCEPT_BACKOFF: Duration = Duration::from_secs(64);
+
+/// 连接连续处理多少个命令之后让出一次执行权。
+///
+/// 帧已经在读缓冲区中时，读取命令不需要等待套接字，连接任务可能一直不让出执行权。流水线发送大量命令的连接因此
+/// 会占用工作线程，使同一线程上的其他连接得不到处理。
+const COMMANDS_PER_YIELD: u32 = 32;
+
+/// 连接空闲多久之后开始发送 keepalive 探测的默认值，与 Redis 的 `tcp-keepalive` 相同。
+const DEFAULT_TCP_KEEPALIVE: Duration = Duration::from_secs(300);
+
+/// 服务器配置。
+///
+/// 通过 `run_with_config` 使用自定义配置运行服务器，`run` 则使用默认配置。
+///
+/// # 示例
+///
+/// ```
+/// use mini_redis::server::Config;
+///
+/// let config = Config::new().channel_capacity(128);
+/// # drop(config);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// 每个发布/订阅频道的广播缓冲区容量。
+    channel_capacity: usize,
+
+    /// 客户端发送的帧的大小限制。
+    frame_limits: frame::Limits,
+
+    /// 每个连接尚未发送的数据的上限。
+    output_limit: Option<usize>,
+
+    /// 每个连接的读缓冲区中不完整的帧的上限。
+    read_buffer_limit: Option<usize>,
+
+    /// 集群模式下的槽检查钩子。
+    slot_check: Option<Arc<dyn SlotCheck>>,
+
+    /// 用户表。
+    acl: Arc<Acl>,
+
+    /// 是否拒绝所有写命令。
+    read_only: bool,
+
+    /// 自定义命令。
+    commands: Registry,
+
+    /// 每个连接的命令速率限制。
+    rate_limit: Option<RateLimit>,
+
+    /// 数据库使用的时间源。
+    clock: Arc<dyn Clock>,
+
+    /// 键空间变化的观察者。
+    observer: Option<Arc<dyn DbObserver>>,
+
+    /// 接受连接失败后重试的最长等待时间。
+    max_accept_backoff: Duration,
+
+    /// 最大并发连接数量。
+    max_connections: usize,
+
+    /// 连接数量达到上限时是否拒绝新连接。
+    reject_excess_connections: bool,
+
+    /// 缓冲池最多保留的空闲缓冲区数量。
+    buffer_pool_size: usize,
+
+    /// TTL 随机延长的最大百分比。
+    ttl_jitter: u8,
+
+    /// 脚本最多执行的 Lua 指令数量。
+    #[cfg(feature = "scripting")]
+    script_instruction_limit: Option<u64>,
+
+    /// 快照文件的路径。设置时服务器启动时从中加载数据。
+    snapshot_path: Option<PathBuf>,
+
+    /// AOF 的路径。设置时开启 AOF，服务器启动时重新执行其中的命令。
+    append_only: Option<PathBuf>,
+
+    /// AOF 调用 `fsync` 的策略。
+    append_fsync: AppendFsync,
+
+    /// 复制积压缓冲区最多缓冲的字节数。
+    repl_backlog_size: usize,
+
+    /// 启动时复制的主节点。
+    replica_of: Option<(String, u16)>,
+
+    /// 延迟监控记录事件的阈值。
+    latency_monitor_threshold: Duration,
+
+    /// 命令执行前后调用的钩子，按注册的顺序调用。
+    command_hooks: Vec<Arc<dyn CommandHook>>,
+
+    /// 应用于每个接受的套接字的 TCP 选项。
+    socket_options: SocketOptions,
+
+    /// 新连接必须在多长时间内发送第一个完整的帧。
+    handshake_timeout: Option<Duration>,
+}
+
+impl Config {
+    /// 返回默认配置。
+    pub fn new() -> Config {
+        Config {
+            channel_capacity: DEFAULT_CHANNEL_CAPACITY,
+            frame_limits: frame::Limits::default(),
+            output_limit: Some(DEFAULT_OUTPUT_LIMIT),
+            read_buffer_limit: Some(DEFAULT_READ_BUFFER_LIMIT),
+            slot_check: None,
+            acl: Arc::new(Acl::unrestricted()),
+            read_only: false,
+            commands: Registry::default(),
+            rate_limit: None,
+            clock: Arc::new(SystemClock),
+            observer: None,
+            max_accept_backoff: DEFAULT_MAX_ACCEPT_BACKOFF,
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            reject_excess_connections: false,
+            buffer_pool_size: DEFAULT_BUFFER_POOL_SIZE,
+            ttl_jitter: 0,
+            #[cfg(feature = "scripting")]
+            script_instruction_limit: Some(DEFAULT_SCRIPT_INSTRUCTION_LIMIT),
+            snapshot_path: None,
+            append_only: None,
+            append_fsync: AppendFsync::default(),
+            repl_backlog_size: DEFAULT_BACKLOG_SIZE,
+            replica_of: None,
+            latency_monitor_threshold: Duration::ZERO,
+            command_hooks: vec![],
+            socket_options: SocketOptions {
+                nodelay: true,
+                keepalive: Some(DEFAULT_TCP_KEEPALIVE),
+            },
+            handshake_timeout: None,
+        }
+    }
+
+    /// 设置每个发布/订阅频道能够缓冲的消息数量，默认为 `1024`。
+    ///
+    /// 当某个订阅者落后超过该数量的消息时，最旧的消息会被丢弃，
+    /// 服务器会向该订阅者发送一个 `lagged` 帧，指出丢失的消息数量。
+    ///
+    /// # Panic
+    ///
+    /// 当 `capacity` 为 0 时会触发panic。
+    pub fn channel_capacity(mut self, capacity: usize) -> Config {
+        assert!(capacity > 0, "channel capacity must be greater than zero");
+        self.channel_capacity = capacity;
+        self
+    }
+
+    /// 设置客户端发送的帧的大小限制，默认为 `frame::Limits::default()`。
+    ///
+    /// 超过限制的帧会被视为协议错误，连接会在发送错误帧后被关闭。
+    pub fn frame_limits(mut self, limits: frame::Limits) -> Config {
+        self.frame_limits = limits;
+        self
+    }
+
+    /// 设置每个连接尚未发送给客户端的数据的上限（字节），默认为 32MB。`None` 表示不限制。
+    ///
+    /// 发布/订阅消息不等待写入完成就继续处理下一条消息，跟不上的订阅者的消息会在服务器上积压。
+    /// 积压的数据超过上限时，连接被关闭并记录错误，类似 Redis 的 `client-output-buffer-limit`。
+    /// 普通命令的响应等待写入完成，不受此限制。
+    ///
+    /// 不限制时，停止读取的订阅者会让服务器的内存无限制地增长，只应在能够信任所有订阅者时使用。
+    pub fn output_limit(mut self, bytes: Option<usize>) -> Config {
+        self.output_limit = bytes;
+        self
+    }
+
+    /// 设置每个连接的读缓冲区中不完整的帧的上限（字节），默认为 1GB。`None` 表示不限制。
+    ///
+    /// 客户端发送的一个帧在缓冲了这么多数据之后仍不完整时，服务器发送协议错误并关闭连接，
+    /// 一个连接不会因为发送一个巨大的帧而占用无限制的内存。上限应当大于 `frame_limits` 允许的最大的帧。
+    pub fn read_buffer_limit(mut self, bytes: Option<usize>) -> Config {
+        self.read_buffer_limit = bytes;
+        self
+    }
+
+    /// 注册一个槽检查钩子，使服务器以集群节点的方式运行。
+    ///
+    /// 每个命令执行之前，服务器计算它访问的键的哈希槽（见 `cluster::key_slot`），并调用 `check`：
+    /// 槽由其他节点负责时，命令不会被执行，而是以 `MOVED slot host:port` 错误响应；
+    /// 槽未分配时以 `CLUSTERDOWN` 错误响应；键不属于同一个槽时，以 `CROSSSLOT` 错误响应。
+    /// 不访问键的命令不受影响。
+    ///
+    /// # 示例
+    ///
+    /// ```
+    /// use mini_redis::cluster::SlotMap;
+    /// use mini_redis::server::Config;
+    ///
+    /// // 负责前一半的槽，其余的槽由另一个节点负责。
+    /// let config = Config::new().slot_check(
+    ///     SlotMap::new()
+    ///         .local(0..=8191)
+    ///         .node(8192..=16383, "127.0.0.1:6380"),
+    /// );
+    /// ```
+    pub fn slot_check(mut self, check: impl SlotCheck + 'static) -> Config {
+        self.slot_check = Some(Arc::new(check));
+        self
+    }
+
+    /// 设置服务器的用户表，默认只有一个拥有所有权限的 `default` 用户。
+    ///
+    /// 新连接以 `default` 用户的身份开始，通过 `AUTH` 切换用户。每个命令执行之前，服务器检查当前用户
+    /// 能否执行该命令、能否访问命令中的键，不允许时以 `NOPERM` 错误响应。用户表中没有 `default`
+    /// 用户时，连接在认证之前执行的命令都以 `NOAUTH` 错误响应。详见 `acl` 模块。
+    pub fn acl(mut self, acl: Acl) -> Config {
+        self.acl = Arc::new(acl);
+        self
+    }
+
+    /// 设置服务器是否以只读模式运行，默认为 `false`。
+    ///
+    /// 只读模式下，所有可能修改数据库的命令（包括脚本和包含写命令的 `EVALBATCH`）都以
+    /// `READONLY` 错误响应，读命令和发布/订阅不受影响。适用于作为副本运行或者用于演示的实例。
+    pub fn read_only(mut self, read_only: bool) -> Config {
+        self.read_only = read_only;
+        self
+    }
+
+    /// 注册一个自定义命令，名称不区分大小写。同名的自定义命令会被替换。
+    ///
+    /// 服务器收到不属于内置命令的命令时，调用对应的 `handler`，并将其返回的帧发送给客户端。
+    /// 与内置命令一样，自定义命令受 `acl` 和 `rate_limit` 的限制；它不访问键，因此不受 `slot_check` 的限制。
+    ///
+    /// # Panic
+    ///
+    /// 当 `name` 与内置命令同名时会触发panic。
+    ///
+    /// # 示例
+    ///
+    /// ```
+    /// use mini_redis::server::Config;
+    /// use mini_redis::Frame;
+    ///
+    /// // `ECHO message` 原样返回消息。
+    /// let config = Config::new().register_command("echo", |args: Vec<bytes::Bytes>| match &args[..] {
+    ///     [message] => Frame::Bulk(message.clone()),
+    ///     _ => Frame::error("wrong number of arguments for 'echo' command"),
+    /// });
+    /// ```
+    pub fn register_command(
+        mut self,
+        name: &str,
+        handler: impl CommandHandler + 'static,
+    ) -> Config {
+        assert!(
+            cmd::lookup(name).is_none(),
+            "'{}' is a built-in command",
+            name
+        );
+        self.commands.insert(name, Arc::new(handler));
+        self
+    }
+
+    /// 限制每个连接执行命令的速率，默认不限制。
+    ///
+    /// 这样可以防止单个繁忙的客户端长时间占用共享的数据库锁。详见 `RateLimit`。
+    pub fn rate_limit(mut self, limit: RateLimit) -> Config {
+        self.rate_limit = Some(limit);
+        self
+    }
+
+    /// 设置数据库计算键的过期时间所使用的时间源，默认为 `SystemClock`。
+    ///
+    /// 测试和模拟可以使用 `ManualClock` 确定性地控制键何时过期，而不必等待实际的时间流逝。
+    ///
+    /// # 示例
+    ///
+    /// ```
+    /// use mini_redis::clock::ManualClock;
+    /// use mini_redis::server::Config;
+    /// use std::time::Duration;
+    ///
+    /// let clock = ManualClock::new();
+    /// let _config = Config::new().clock(clock.clone());
+    ///
+    /// // 启动服务器之后，推进时间即可使设置了 TTL 的键过期。
+    /// clock.advance(Duration::from_secs(60));
+    /// ```
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Config {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// 安装键空间变化的观察者，默认没有观察者。
+    ///
+    /// 键被写入、修改、删除或者过期之后调用 `observer` 的方法，可以用来实现持久化、复制或者变更通知，参见 `DbObserver`。
+    /// 启动时重新执行 AOF 中的命令和从快照加载数据同样会产生通知。
+    ///
+    /// # 示例
+    ///
+    /// ```
+    /// use bytes::Bytes;
+    /// use mini_redis::server::Config;
+    /// use mini_redis::DbObserver;
+    /// use std::time::Duration;
+    ///
+    /// struct Log;
+    ///
+    /// impl DbObserver for Log {
+    ///     fn on_set(&self, key: String, value: Bytes, _ttl: Option<Duration>) {
+    ///         println!("SET {} {:?}", key, value);
+    ///     }
+    /// }
+    ///
+    /// let _config = Config::new().observer(Log);
+    /// ```
+    pub fn observer(mut self, observer: impl DbObserver + 'static) -> Config {
+        self.observer = Some(Arc::new(observer));
+        self
+    }
+
+    /// 设置接受连接失败后重试的最长等待时间，默认为 64 秒。
+    ///
+    /// 接受连接失败时（例如进程打开的文件描述符达到上限），服务器记录一条警告，等待 1 秒后重试，
+    /// 之后每次失败等待时间加倍。需要的等待时间超过 `max` 时，服务器放弃并关闭。
+    /// 成功接受连接之后，等待时间重新从 1 秒开始。
+    pub fn max_accept_backoff(mut self, max: Duration) -> Config {
+        self.max_accept_backoff = max;
+        self
+    }
+
+    /// 设置最大并发连接数量，默认为 `250`。运行时可以通过 `CONFIG SET maxclients` 修改。
+    ///
+    /// # Panic
+    ///
+    /// 当 `max` 为 0 时会触发panic。
+    pub fn max_connections(mut self, max: usize) -> Config {
+        assert!(max > 0, "max connections must be greater than zero");
+        self.max_connections = max;
+        self
+    }
+
+    /// 设置连接数量达到上限时是否拒绝新连接，默认为 `false`。
+    ///
+    /// 默认情况下，连接数量达到上限后服务器暂停接受连接，新连接在操作系统的队列中等待，直到有连接关闭。
+    /// 设置为 `true` 时，服务器仍然接受新连接，但发送 `-ERR max number of clients reached` 之后立即关闭它，
+    /// 与 Redis 的行为相同，客户端可以据此立即得知失败的原因。
+    pub fn reject_excess_connections(mut self, reject: bool) -> Config {
+        self.reject_excess_connections = reject;
+        self
+    }
+
+    /// 设置在连接之间复用的空闲缓冲区的最大数量，默认为 `128`，`0` 表示不复用缓冲区。
+    ///
+    /// 每个连接使用一个读缓冲区和一个写缓冲区，连接关闭时它们被归还到缓冲池，供之后的连接使用，
+    /// 连接频繁建立和关闭时可以减少内存分配。缓冲池的统计信息可以通过 `INFO buffers` 查看。
+    pub fn buffer_pool_size(mut self, size: usize) -> Config {
+        self.buffer_pool_size = size;
+        self
+    }
+
+    /// 设置过期时长随机延长的最大百分比，默认为 `0`，即不延长。当前的值可以通过 `INFO stats` 查看。
+    ///
+    /// 大量键以相同的 TTL 写入时（例如批量导入的缓存），它们会在同一时刻过期，后台任务需要连续清除所有这些键。
+    /// 设置该百分比之后，每个键的过期时长被随机延长 `0` 到 `percent`% 之间的时间，过期被分散到一段时间内。
+    /// 键不会早于请求的时间过期，但 `TTL` 可能返回比请求的时长更大的值。
+    ///
+    /// # Panic
+    ///
+    /// 当 `percent` 大于 100 时会触发panic。
+    pub fn ttl_jitter(mut self, percent: u8) -> Config {
+        assert!(percent <= 100, "TTL jitter must be at most 100 percent");
+        self.ttl_jitter = percent;
+        self
+    }
+
+    /// 设置一个脚本最多执行的 Lua 指令数量，默认为一亿条。`None` 表示不限制。
+    ///
+    /// 脚本在持有数据库的锁时执行，执行期间其他连接的命令和清除过期键的后台任务都在等待。
+    /// 超出上限的脚本被中止，客户端收到 `BUSY` 错误；脚本在中止之前已经执行的写入不会被撤销。
+    #[cfg(feature = "scripting")]
+    pub fn script_instruction_limit(mut self, limit: Option<u64>) -> Config {
+        self.script_instruction_limit = limit;
+        self
+    }
+
+    /// 设置快照文件的路径。服务器启动时从该文件加载数据，`BGSAVE` 写入该文件。
+    ///
+    /// 文件不存在时以空数据库启动。文件损坏、被截断或者由更新版本的 mini-redis 写入时服务器不会启动，
+    /// 可以使用 `mini-redis-cli --check-dump` 检查文件。未设置时不加载数据，`BGSAVE` 写入当前目录下的 `dump.snapshot`。
+    ///
+    /// 快照先写入同一目录下名称加上 `.tmp` 后缀的临时文件，写入完成之后再替换原有的文件。
+    /// 快照的进度和最后一次快照的结果可以通过 `INFO persistence` 查看。
+    pub fn snapshot_path(mut self, path: impl Into<PathBuf>) -> Config {
+        self.snapshot_path = Some(path.into());
+        self
+    }
+
+    /// 开启 AOF，写命令在执行时被追加到 `path`。服务器启动时按顺序重新执行文件中的命令，
+    /// 此时不再加载 `snapshot_path` 设置的快照文件，与 Redis 相同。
+    ///
+    /// 记录的是写命令的效果：`BLPOP` 被记录为 `LPOP`，脚本被记录为它执行的写命令，
+    /// 相对的过期时间（例如 `SET key value EX 10`）被记录为绝对的时间戳，参见 `Command::execute_propagated`。
+    pub fn append_only(mut self, path: impl Into<PathBuf>) -> Config {
+        self.append_only = Some(path.into());
+        self
+    }
+
+    /// 设置 AOF 调用 `fsync` 的策略，默认为 `AppendFsync::EverySec`。
+    ///
+    /// 写入任务把同时到达的命令合并为一次写入。`Always` 策略下写命令在写入磁盘之后才响应，
+    /// 写入失败时以 `MISCONF` 错误响应。无论哪种策略，写入失败之后的写命令都以 `MISCONF` 错误被拒绝，
+    /// 直到写入任务每秒一次的重试成功为止。最后一次 `fsync` 的耗时和尚未写入的字节数
+    /// 可以通过 `INFO persistence` 查看。
+    pub fn append_fsync(mut self, policy: AppendFsync) -> Config {
+        self.append_fsync = policy;
+        self
+    }
+
+    /// 设置复制积压缓冲区最多缓冲的字节数，默认为 1MB。
+    ///
+    /// 第一个副本连接之后，写命令被追加到积压缓冲区。断开的副本重新连接时，如果它处理到的位置之后的写命令
+    /// 仍在缓冲区中，只需要发送这些命令，否则需要重新发送完整的快照。副本断开的时间越长、写入越频繁，
+    /// 需要的缓冲区越大。部分同步和完整同步的次数可以通过 `INFO stats` 查看。
+    pub fn repl_backlog_size(mut self, bytes: usize) -> Config {
+        self.repl_backlog_size = bytes;
+        self
+    }
+
+    /// 启动时成为 `host:port` 上的主节点的副本，等同于启动之后执行 `REPLICAOF host port`。
+    ///
+    /// 副本在后台同步数据，拒绝客户端的写命令。之后可以通过 `REPLICAOF NO ONE` 重新成为主节点。
+    pub fn replica_of(mut self, host: impl Into<String>, port: u16) -> Config {
+        self.replica_of = Some((host.into(), port));
+        self
+    }
+
+    /// 设置延迟监控的阈值，精确到毫秒，默认为 `Duration::ZERO`，即不记录。
+    ///
+    /// 执行时间达到阈值的命令被记录到 `command` 事件中，后台任务一次清除过期键的耗时达到阈值时被记录到
+    /// `expire-cycle` 事件中。`BLPOP`、`WAIT` 等等待其他连接或者副本的命令不被记录。
+    /// 记录的采样可以通过 `LATENCY HISTORY event` 查看、`LATENCY RESET` 清除，各事件的概况可以通过 `INFO latency` 查看。
+    pub fn latency_monitor_threshold(mut self, threshold: Duration) -> Config {
+        self.latency_monitor_threshold = threshold;
+        self
+    }
+
+    /// 设置是否在接受的连接上设置 `TCP_NODELAY`，默认开启。
+    ///
+    /// 关闭时操作系统会合并小的写入，流水线请求的吞吐量可能略有提高，但每个响应的延迟会增加。
+    pub fn tcp_nodelay(mut self, nodelay: bool) -> Config {
+        self.socket_options.nodelay = nodelay;
+        self
+    }
+
+    /// 设置接受的连接空闲多久之后开始发送 TCP keepalive 探测，精确到秒，默认为 300 秒，与 Redis 的 `tcp-keepalive` 相同。
+    ///
+    /// 对端已经消失（例如断电或者网络中断）而没有关闭的连接会被操作系统发现并关闭，归还占用的连接名额。
+    /// `Duration::ZERO` 表示不开启 keepalive。
+    pub fn tcp_keepalive(mut self, time: Duration) -> Config {
+        self.socket_options.keepalive = Some(time).filter(|time| !time.is_zero());
+        self
+    }
+
+    /// 设置新连接发送第一个完整的帧的期限，默认不限制。
+    ///
+    /// 期限内没有收到完整的帧（例如端口扫描或者建立之后一直空闲的连接）时，连接被关闭，占用的连接名额被归还，
+    /// 这样的连接不会耗尽 `max_connections`。收到第一个帧之后，连接可以任意长时间空闲。
+    pub fn handshake_timeout(mut self, timeout: Duration) -> Config {
+        self.handshake_timeout = Some(timeout);
+        self
+    }
+
+    /// 注册一个在命令执行前后调用的钩子，参见 `CommandHook`。可以调用多次，钩子按注册的顺序调用。
+    pub fn on_command(mut self, hook: impl CommandHook + 'static) -> Config {
+        self.command_hooks.push(Arc::new(hook));
+        self
+    }
+
+    /// 按照配置创建服务器使用的数据库。开启了 AOF 时重新执行其中的命令，否则设置了快照文件时从中加载数据。
+    fn new_db(&self) -> crate::Result<DbDropGuard> {
+        let db_holder = DbDropGuard::new_with_observer(
+            self.channel_capacity,
+            self.clock.clone(),
+            self.observer.clone(),
+        );
+        let db = db_holder.db();
+        db.set_ttl_jitter(self.ttl_jitter);
+        #[cfg(feature = "scripting")]
+        db.set_script_instruction_limit(self.script_instruction_limit);
+        db.set_latency_threshold(self.latency_monitor_threshold);
+
+        let snapshot_path = self
+            .snapshot_path
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_SNAPSHOT_PATH));
+        db.set_snapshot_path(snapshot_path);
+
+        if let Some(path) = &self.append_only {
+            db.open_aof(path, self.append_fsync)
+                .map_err(|err| format!("failed to load AOF {}: {}", path.display(), err))?;
+        } else if let Some(path) = &self.snapshot_path {
+            let loaded = db
+                .load_snapshot(path)
+                .map_err(|err| format!("failed to load snapshot {}: {}", path.display(), err))?;
+            info!(path = %path.display(), keys = loaded, "snapshot loaded");
+        }
+
+        db.set_backlog_size(self.repl_backlog_size);
+
+        if let Some((host, port)) = &self.replica_of {
+            db.set_replica_link(Some(Link::start(db.clone(), host.clone(), *port)));
+        }
+
+        Ok(db_holder)
+    }
+}
+
+/// 服务器的并发连接数量上限。
+///
+/// 与 `Semaphore` 不同，上限可以在运行时通过 `CONFIG SET maxclients` 调高或者调低。
+#[derive(Debug)]
+pub(crate) struct ConnectionLimit {
+    /// 允许的最大连接数量。
+    max: AtomicUsize,
+
+    /// 当前持有许可证的连接数量。
+    active: AtomicUsize,
+
+    /// 连接关闭或者上限被修改时通知监听器。只有监听器等待此通知。
+    changed: Notify,
+}
+
+/// 一个连接名额。被丢弃时名额被归还。
+#[derive(Debug)]
+struct ConnectionPermit(Arc<ConnectionLimit>);
+
+impl ConnectionLimit {
+    fn new(max: usize) -> ConnectionLimit {
+        ConnectionLimit {
+            max: AtomicUsize::new(max),
+            active: AtomicUsize::new(0),
+            changed: Notify::new(),
+        }
+    }
+
+    /// 返回允许的最大连接数量。
+    pub(crate) fn max(&self) -> usize {
+        self.max.load(Ordering::SeqCst)
+    }
+
+    /// 返回当前的连接数量。
+    pub(crate) fn active(&self) -> usize {
+        self.active.load(Ordering::SeqCst)
+    }
+
+    /// 修改允许的最大连接数量。已经建立的连接不受影响。
+    pub(crate) fn set_max(&self, max: usize) {
+        self.max.store(max, Ordering::SeqCst);
+        self.changed.notify_one();
+    }
+
+    /// 连接数量低于上限时获取一个名额，否则返回 `None`。
+    fn try_acquire(self: &Arc<Self>) -> Option<ConnectionPermit> {
+        self.active
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |active| {
+                (active < self.max()).then_some(active + 1)
+            })
+            .ok()
+            .map(|_| ConnectionPermit(self.clone()))
+    }
+
+    /// 等待直到获取一个名额。
+    async fn acquire(self: &Arc<Self>) -> ConnectionPermit {
+        loop {
+            if let Some(permit) = self.try_acquire() {
+                return permit;
+            }
+
+            // `notify_one` 在没有等待者时保存通知，因此在检查之后、等待之前归还的名额不会被错过。
+            self.changed.notified().await;
+        }
+    }
+
+    /// 等待直到连接数量低于上限，但不获取名额。
+    async fn ready(&self) {
+        while self.active() >= self.max() {
+            self.changed.notified().await;
+        }
+    }
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        self.0.active.fetch_sub(1, Ordering::SeqCst);
+        self.0.changed.notify_one();
+    }
+}
+
+/// 告知对等方连接数量已经达到上限，然后关闭连接。
+async fn reject_connection(mut socket: TcpStream) {
+    let response = Frame::typed_error(ErrorKind::Err, "max number of clients reached");
+    debug!(?response);
+
+    let mut buf = bytes::BytesMut::new();
+    response.encode(&mut buf);
+
+    // 连接无论如何都会被关闭，写入失败无关紧要。
+    let _ = socket.write_all(&buf).await;
+}
+
+/// 每个连接的命令速率限制，使用令牌桶算法。
+///
+/// 令牌以每秒 `per_second` 个的速度补充，最多积攒 `burst` 个，新连接的令牌桶是满的。每个命令消耗一个令牌；
+/// 没有令牌时，命令被延迟到补充出新的令牌为止，或者在 `reject` 模式下以 `BUSY` 错误拒绝。
+///
+/// # 示例
+///
+/// ```
+/// use mini_redis::server::{Config, RateLimit};
+///
+/// // 每个连接每秒最多执行 100 个命令，允许 20 个命令的突发，超出时拒绝。
+/// let config = Config::new().rate_limit(RateLimit::new(100, 20).reject());
+/// # drop(config);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    /// 每秒补充的令牌数量
+    per_second: u32,
+
+    /// 最多积攒的令牌数量
+    burst: u32,
+
+    /// 超出限制时是否拒绝命令，而不是延迟命令
+    reject: bool,
+}
+
+impl RateLimit {
+    /// 创建一个每秒补充 `per_second` 个令牌、最多积攒 `burst` 个令牌的速率限制。
+    ///
+    /// # Panic
+    ///
+    /// 当 `per_second` 或 `burst` 为 0 时会触发panic。
+    pub fn new(per_second: u32, burst: u32) -> RateLimit {
+        assert!(per_second > 0, "rate must be greater than zero");
+        assert!(burst > 0, "burst must be greater than zero");
+
+        RateLimit {
+            per_second,
+            burst,
+            reject: false,
+        }
+    }
+
+    /// 超出限制时以 `BUSY` 错误拒绝命令，而不是延迟命令。
+    pub fn reject(mut self) -> RateLimit {
+        self.reject = true;
+        self
+    }
+}
+
+/// 服务器执行命令前后调用的钩子，通过 `Config::on_command` 注册。
+///
+/// 每个解析成功的命令在执行之前依次经过所有钩子的 `before`，任意一个钩子返回 `Some(response)` 时命令不被执行，
+/// 客户端收到 `response`；在事务中这与其他被拒绝的命令一样使 `EXEC` 放弃整个事务。其余的命令在写入响应之后
+/// 依次经过所有钩子的 `after`。钩子可以用来记录审计日志、收集自定义的指标或者拒绝某些命令，不需要修改本 crate。
+///
+/// 钩子在连接任务中同步调用，应当尽快返回。两个方法都有默认的实现，只需实现关心的方法。
+/// `Fn(&Command, SocketAddr)` 闭包也实现了该 trait，它在命令执行之前被调用，不拒绝任何命令。
+///
+/// # 示例
+///
+/// ```
+/// use mini_redis::server::{CommandHook, Config};
+/// use mini_redis::{Command, Frame};
+/// use std::net::SocketAddr;
+///
+/// /// 拒绝 `KEYS`，它需要遍历整个键空间。
+/// struct DenyKeys;
+///
+/// impl CommandHook for DenyKeys {
+///     fn before(&self, cmd: &Command, _peer: SocketAddr) -> Option<Frame> {
+///         (cmd.get_name() == "keys").then(|| Frame::error("KEYS is disabled"))
+///     }
+/// }
+///
+/// let config = Config::new()
+///     .on_command(|cmd: &Command, peer: SocketAddr| println!("{} {}", peer, cmd.get_name()))
+///     .on_command(DenyKeys);
+/// # drop(config);
+/// ```
+pub trait CommandHook: Send + Sync {
+    /// 在命令执行之前调用。返回 `Some(response)` 时命令不被执行，以 `response` 响应。
+    ///
+    /// 认证、权限和只读模式的检查在此之后进行，因此没有权限的命令也会经过此方法。
+    fn before(&self, cmd: &Command, peer: SocketAddr) -> Option<Frame> {
+        let _ = (cmd, peer);
+        None
+    }
+
+    /// 在命令执行、写入响应之后调用。`name` 是命令名称，`elapsed` 是执行命令和写入响应的时间。
+    fn after(&self, name: &str, peer: SocketAddr, elapsed: Duration, outcome: Outcome) {
+        let _ = (name, peer, elapsed, outcome);
+    }
+}
+
+impl<F> CommandHook for F
+where
+    F: Fn(&Command, SocketAddr) + Send + Sync,
+{
+    fn before(&self, cmd: &Command, peer: SocketAddr) -> Option<Frame> {
+        self(cmd, peer);
+        None
+    }
+}
+
+impl fmt::Debug for dyn CommandHook {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("CommandHook")
+    }
+}
+
+/// 一个命令的执行结果，传递给 `CommandHook::after`。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Outcome {
+    /// 命令以非错误的响应完成。
+    Ok,
+
+    /// 命令以错误帧响应，连接保持打开。
+    Error,
+
+    /// 连接出错，例如写入响应失败。连接随后被关闭。
+    Failed,
+}
+
+impl Outcome {
+    /// 返回结果的名称，记录在每个命令的 span 中。
+    fn as_str(self) -> &'static str {
+        match self {
+            Outcome::Ok => "ok",
+            Outcome::Error => "error",
+            Outcome::Failed => "failed",
+        }
+    }
+}
+
+/// 一个连接的令牌桶。
+#[derive(Debug)]
+struct TokenBucket {
+    /// 速率限制的配置
+    limit: RateLimit,
+
+    /// 当前的令牌数量，可能不是整数
+    tokens: f64,
+
+    /// 上次补充令牌的时间
+    updated: Instant,
+}
+
+impl TokenBucket {
+    /// 创建一个满的令牌桶。
+    fn new(limit: RateLimit) -> TokenBucket {
+        TokenBucket {
+            limit,
+            tokens: limit.burst as f64,
+            updated: Instant::now(),
+        }
+    }
+
+    /// 取出一个令牌。令牌不足时不取出令牌，返回 `false`。
+    fn try_acquire(&mut self) -> bool {
+        self.refill();
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// 预先取出一个令牌，返回补充出该令牌需要等待的时间。令牌充足时返回零。
+    ///
+    /// 令牌不足时令牌数量变为负数，之后的命令需要等待更长的时间。
+    fn acquire(&mut self) -> Duration {
+        self.refill();
+        self.tokens -= 1.0;
+
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.limit.per_second as f64)
+        }
+    }
+
+    /// 按照经过的时间补充令牌。
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let refilled = (now - self.updated).as_secs_f64() * self.limit.per_second as f64;
+
+        self.tokens = (self.tokens + refilled).min(self.limit.burst as f64);
+        self.updated = now;
+    }
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config::new()
+    }
+}
 
 /// 运行 mini-redis 服务器。
 ///
@@ -91,9 +1111,50 @@ const MAX_CONNECTIONS: usize = 250;
 /// 生成一个任务来处理该连接。服务器一直运行到
 /// `shutdown` future 完成，此时服务器将优雅地关闭。
 ///
-/// 可以将 `tokio::signal::ctrl_c()` 用作 `shutdown` 参数。
-/// 这将监听 SIGINT 信号。
-pub async fn run(listener: TcpListener, shutdown: impl Future) {
+/// 可以将 `tokio::signal::ctrl_c()` 用作 `shutdown` 参数。
+/// 这将监听 SIGINT 信号。
+pub async fn run(listener: TcpListener, shutdown: impl Future) {
+    run_with_config(listener, Config::default(), shutdown).await
+}
+
+/// 使用给定的 `config` 运行 mini-redis 服务器。
+///
+/// 除了配置之外，与 `run` 相同。
+pub async fn run_with_config(listener: TcpListener, config: Config, shutdown: impl Future) {
+    run_with_listeners(vec![listener], config, shutdown).await
+}
+
+/// 使用给定的 `config` 运行 mini-redis 服务器，同时接受多个侦听器上的连接。
+///
+/// 例如，可以同时侦听 IPv4 和 IPv6 地址。所有侦听器上的连接共享同一个数据库和连接数量上限。
+///
+/// # Panic
+///
+/// 当 `listeners` 为空时会触发panic。
+pub async fn run_with_listeners(
+    listeners: Vec<TcpListener>,
+    config: Config,
+    shutdown: impl Future,
+) {
+    let db_holder = match config.new_db() {
+        Ok(db_holder) => db_holder,
+        Err(err) => {
+            error!(cause = %err, "failed to start server");
+            return;
+        }
+    };
+    serve(listeners, config, db_holder, shutdown).await
+}
+
+/// 使用 `db_holder` 中的数据库运行服务器。参见 `run_with_listeners`。
+async fn serve(
+    listeners: Vec<TcpListener>,
+    config: Config,
+    db_holder: DbDropGuard,
+    shutdown: impl Future,
+) {
+    assert!(!listeners.is_empty(), "at least one listener is required");
+
     // 当提供的 `shutdown` future 完成时，我们必须向所有活动连接发送关闭消息。
     // 我们使用广播通道来实现这一目的。下面的调用忽略了广播对的接收器，当需要接收器时，
     // 使用发送器上的 subscribe() 方法来创建一个。
@@ -102,9 +1163,24 @@ pub async fn run(listener: TcpListener, shutdown: impl Future) {
 
     // 初始化监听器状态
     let mut server = Listener {
-        listener,
-        db_holder: DbDropGuard::new(),
-        limit_connections: Arc::new(Semaphore::new(MAX_CONNECTIONS)),
+        listeners,
+        next_listener: 0,
+        frame_limits: config.frame_limits,
+        output_limit: config.output_limit,
+        read_buffer_limit: config.read_buffer_limit,
+        slot_check: config.slot_check,
+        acl: config.acl,
+        read_only: config.read_only,
+        commands: Arc::new(config.commands),
+        command_hooks: config.command_hooks.into(),
+        rate_limit: config.rate_limit,
+        max_accept_backoff: config.max_accept_backoff,
+        socket_options: config.socket_options,
+        handshake_timeout: config.handshake_timeout,
+        db_holder,
+        limit_connections: Arc::new(ConnectionLimit::new(config.max_connections)),
+        reject_excess_connections: config.reject_excess_connections,
+        buffer_pool: BufferPool::new(config.buffer_pool_size),
         notify_shutdown,
         shutdown_complete_tx,
     };
@@ -145,6 +1221,7 @@ pub async fn run(listener: TcpListener, shutdown: impl Future) {
     let Listener {
         shutdown_complete_tx,
         notify_shutdown,
+        db_holder,
         ..
     } = server;
 
@@ -158,6 +1235,224 @@ pub async fn run(listener: TcpListener, shutdown: impl Future) {
     // 唯一剩下的 `Sender` 实例在连接处理器任务中持有。
     // 当这些任务完成时，`mpsc` 通道将关闭，`recv()` 将返回 `None`。
     let _ = shutdown_complete_rx.recv().await;
+
+    // 所有连接都已经完成，等待它们追加到 AOF 的命令写入磁盘。
+    db_holder.db().sync_aof().await;
+}
+
+/// 在 `127.0.0.1` 上由操作系统分配的临时端口启动一个使用默认配置的服务器，返回它侦听的地址。
+///
+/// 服务器在后台任务中运行，直到当前的 Tokio 运行时关闭。需要关闭服务器时使用 `spawn`。主要用于测试：
+/// 每个测试启动自己的服务器，不需要挑选空闲的端口，也不会与其他测试共享数据库。
+///
+/// # 示例
+///
+/// ```
+/// use mini_redis::{clients::Client, server};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let addr = server::spawn_ephemeral().await.unwrap();
+///
+///     let mut client = Client::connect(addr).await.unwrap();
+///     client.set("foo", "bar".into()).await.unwrap();
+/// }
+/// ```
+pub async fn spawn_ephemeral() -> crate::Result<SocketAddr> {
+    let handle = spawn("127.0.0.1:0", Config::new()).await?;
+    Ok(handle.addr())
+}
+
+/// 绑定 `addr` 并在后台任务中使用给定的 `config` 运行服务器，返回用于获取地址和关闭服务器的 `Handle`。
+///
+/// 端口为 `0` 时由操作系统分配，实际的地址可以通过 `Handle::addr` 获取。
+///
+/// # 示例
+///
+/// ```
+/// use mini_redis::{clients::Client, server};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let handle = server::spawn("127.0.0.1:0", server::Config::new()).await.unwrap();
+///
+///     let mut client = Client::connect(handle.addr()).await.unwrap();
+///     client.set("foo", "bar".into()).await.unwrap();
+///
+///     // 等待连接处理完毕之后返回。
+///     handle.shutdown().await;
+/// }
+/// ```
+pub async fn spawn(addr: impl ToSocketAddrs, config: Config) -> crate::Result<Handle> {
+    let listener = TcpListener::bind(addr).await?;
+    spawn_with_listener(listener, config)
+}
+
+/// 在后台任务中使用给定的 `config` 运行服务器，接受 `listener` 上的连接。参见 `spawn`。
+pub fn spawn_with_listener(listener: TcpListener, config: Config) -> crate::Result<Handle> {
+    spawn_with_listeners(vec![listener], config)
+}
+
+/// 在后台任务中运行服务器，接受所有 `listeners` 上的连接。`Handle::addr` 返回第一个侦听器的地址。
+fn spawn_with_listeners(listeners: Vec<TcpListener>, config: Config) -> crate::Result<Handle> {
+    let addr = match listeners.first() {
+        Some(listener) => listener.local_addr()?,
+        None => return Err("at least one listener is required".into()),
+    };
+    let (shutdown_tx, shutdown_rx) = oneshot::channel();
+    let db_holder = config.new_db()?;
+    let db = db_holder.db();
+
+    // `Handle` 被丢弃时 `shutdown_tx` 也被丢弃，此时服务器继续运行，而不是关闭。
+    let shutdown = async {
+        if shutdown_rx.await.is_err() {
+            future::pending::<()>().await;
+        }
+    };
+
+    let task = task::spawn(
+        "mini-redis::server",
+        serve(listeners, config, db_holder, shutdown),
+    );
+
+    Ok(Handle {
+        addr,
+        db,
+        shutdown: shutdown_tx,
+        task,
+    })
+}
+
+/// 组合配置和侦听器并启动服务器的构建器。
+///
+/// `run_with_listeners` 和 `spawn_with_listener` 都可以通过它完成。之后新增的启动选项会加在这里，
+/// 而不是再增加一个 `run_with_*` 函数。
+///
+/// # 示例
+///
+/// ```
+/// use mini_redis::{clients::Client, server};
+/// use tokio::net::TcpListener;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+///
+///     let handle = server::Builder::new()
+///         .config(server::Config::new().channel_capacity(128))
+///         .listener(listener)
+///         .spawn()
+///         .unwrap();
+///
+///     let mut client = Client::connect(handle.addr()).await.unwrap();
+///     client.set("foo", "bar".into()).await.unwrap();
+///
+///     handle.shutdown().await;
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct Builder {
+    /// 服务器配置。
+    config: Config,
+
+    /// 接受连接的侦听器。
+    listeners: Vec<TcpListener>,
+}
+
+impl Builder {
+    /// 返回一个使用默认配置、没有侦听器的构建器。
+    pub fn new() -> Builder {
+        Builder::default()
+    }
+
+    /// 设置服务器配置，替换之前设置的配置。
+    pub fn config(mut self, config: Config) -> Builder {
+        self.config = config;
+        self
+    }
+
+    /// 添加一个侦听器。所有侦听器上的连接共享同一个数据库和连接数量上限。
+    pub fn listener(mut self, listener: TcpListener) -> Builder {
+        self.listeners.push(listener);
+        self
+    }
+
+    /// 运行服务器直到 `shutdown` 完成。参见 `run_with_listeners`。
+    ///
+    /// # Panic
+    ///
+    /// 没有添加任何侦听器时会触发panic。
+    pub async fn run(self, shutdown: impl Future) {
+        run_with_listeners(self.listeners, self.config, shutdown).await
+    }
+
+    /// 在后台任务中运行服务器，返回服务器的 `Handle`。`Handle::addr` 返回第一个侦听器的地址。
+    ///
+    /// 没有添加任何侦听器时返回错误。
+    pub fn spawn(self) -> crate::Result<Handle> {
+        spawn_with_listeners(self.listeners, self.config)
+    }
+}
+
+/// 由 `spawn` 启动的服务器的句柄。
+///
+/// 与 `tokio::task::JoinHandle` 一样，丢弃句柄不会关闭服务器，服务器继续运行直到 Tokio 运行时关闭。
+#[derive(Debug)]
+pub struct Handle {
+    /// 服务器侦听的地址。
+    addr: SocketAddr,
+
+    /// 服务器使用的数据库。
+    db: Db,
+
+    /// 发送关闭信号。
+    shutdown: oneshot::Sender<()>,
+
+    /// 运行服务器的后台任务。
+    task: JoinHandle<()>,
+}
+
+impl Handle {
+    /// 返回服务器侦听的地址。
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// 返回服务器使用的数据库的句柄。
+    ///
+    /// 通过它读写的键与网络客户端看到的相同，例如应用程序可以在服务器运行期间预先填充或者检查数据。
+    /// 服务器关闭之后句柄仍然可以使用，但是过期的键不再被后台任务清除，只在访问时被当作不存在。
+    ///
+    /// # 示例
+    ///
+    /// ```
+    /// use mini_redis::{clients::Client, server};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let handle = server::spawn("127.0.0.1:0", server::Config::new()).await.unwrap();
+    ///     handle.db().set("foo".to_string(), "bar".into(), None, false);
+    ///
+    ///     let mut client = Client::connect(handle.addr()).await.unwrap();
+    ///     assert_eq!(Some("bar".into()), client.get("foo").await.unwrap());
+    /// }
+    /// ```
+    pub fn db(&self) -> Db {
+        self.db.clone()
+    }
+
+    /// 通知服务器关闭，并等待所有活动连接处理完毕。
+    pub async fn shutdown(self) {
+        let _ = self.shutdown.send(());
+        let _ = self.task.await;
+    }
+
+    /// 等待服务器退出，不通知它关闭。
+    ///
+    /// 服务器只会在接受连接反复失败时自行退出，因此该方法通常用于在服务器运行期间保持调用者不返回。
+    pub async fn join(self) {
+        let _ = self.task.await;
+    }
 }
 
 impl Listener {
@@ -172,81 +1467,177 @@ impl Listener {
     ///
     /// 无法检测到短暂错误何时自行解决。一种处理这种情况的策略是实现退避策略，我们在这里实现这种策略。
     async fn run(&mut self) -> crate::Result<()> {
-        info!("accepting inbound connections");
+        for listener in &self.listeners {
+            match listener.local_addr() {
+                Ok(addr) => info!(%addr, "accepting inbound connections"),
+                Err(err) => warn!(cause = %err, "accepting inbound connections on unknown address"),
+            }
+        }
 
         loop {
-            // 等待许可证可用
-            //
-            // `acquire_owned` 返回一个与信号量绑定的许可证。
-            // 当许可证值被丢弃时，它会自动返回到信号量。
-            //
-            // 当信号量已关闭时，`acquire_owned()` 会返回 `Err`。
-            // 我们从不关闭信号量，所以 `unwrap()` 是安全的。
-            let permit = self
-                .limit_connections
-                .clone()
-                .acquire_owned()
-                .await
-                .unwrap();
+            // 等待名额可用。拒绝多余连接时不等待，而是先接受连接，再尝试获取许可证。
+            if !self.reject_excess_connections {
+                self.limit_connections.ready().await;
+            }
 
             // 接受一个新的套接字。这将尝试执行错误处理。
             // `accept` 方法在内部尝试恢复错误，因此此处的错误是不可恢复的。
             let socket = self.accept().await?;
 
+            // 获取许可证。许可证被丢弃时，名额会自动归还。只有监听器获取许可证，因此等待之后名额通常仍然可用，
+            // 除非上限在接受连接期间被调低。
+            let permit = match self.limit_connections.try_acquire() {
+                Some(permit) => permit,
+                None if self.reject_excess_connections => {
+                    // 在单独的任务中发送错误，慢速的对等方不会阻塞监听器。
+                    task::spawn("mini-redis::reject", reject_connection(socket));
+                    continue;
+                }
+                None => self.limit_connections.acquire().await,
+            };
+
+            // 对端地址记录在连接任务的 span 中，日志和 tokio-console 据此区分各个连接。
+            // 无法获取对端地址说明对等方已经断开了连接。
+            let peer = match socket.peer_addr() {
+                Ok(peer) => peer,
+                Err(err) => {
+                    debug!(cause = %err, "peer disconnected before the connection was handled");
+                    continue;
+                }
+            };
+
+            // 设置套接字选项失败不影响连接的使用，只是缺少对应的优化。
+            if let Err(err) = self.socket_options.apply(&socket) {
+                warn!(cause = %err, %peer, "failed to set socket options");
+            }
+
+            let mut connection = Connection::new_with_pool(socket, &self.buffer_pool);
+            connection.set_limits(self.frame_limits);
+            connection.set_output_limit(self.output_limit);
+            connection.set_read_buffer_limit(self.read_buffer_limit);
+
             // 创建每个连接所需的处理状态。
             let mut handler = Handler {
-                // 获取一个共享数据库的句柄。
-                db: self.db_holder.db(),
+                // 获取一个共享数据库的句柄，并为连接分配 ID。
+                db: self.db_holder.db().for_new_client(),
 
                 // 初始化连接状态。这将分配读/写缓冲区以执行 redis 协议帧解析。
-                connection: Connection::new(socket),
+                connection,
+
+                peer,
 
                 // 接收关闭通知。
                 shutdown: Shutdown::new(self.notify_shutdown.subscribe()),
 
+                slot_check: self.slot_check.clone(),
+
+                // 新连接以 `default` 用户的身份开始。
+                user: self.acl.default_user(),
+                acl: self.acl.clone(),
+
+                read_only: self.read_only,
+
+                commands: self.commands.clone(),
+
+                command_hooks: self.command_hooks.clone(),
+
+                rate_limit: self.rate_limit.map(TokenBucket::new),
+
+                state: State::Normal,
+
+                tracking: Tracking::default(),
+
+                pubsub_metadata: false,
+
+                asking: false,
+
+                connection_limit: self.limit_connections.clone(),
+
+                buffer_pool: self.buffer_pool.clone(),
+
+                handshake_deadline: self
+                    .handshake_timeout
+                    .map(|timeout| Instant::now() + timeout),
+
+                commands_since_yield: 0,
+
                 // 一旦所有克隆被丢弃后通知接收方。
                 _shutdown_complete: self.shutdown_complete_tx.clone(),
             };
 
             // 生成一个新任务来处理连接。Tokio 任务类似于异步绿线程，并发执行。
-            tokio::spawn(async move {
-                // 处理连接。如果遇到错误，记录错误。
-                if let Err(err) = handler.run().await {
-                    error!(cause = ?err, "connection error");
+            let span = info_span!("connection", %peer);
+            let name = format!("mini-redis::connection {}", peer);
+
+            task::spawn(
+                &name,
+                async move {
+                    // 处理连接。如果遇到错误，记录错误。
+                    if let Err(err) = handler.run().await {
+                        error!(cause = ?err, "connection error");
+                    }
+                    // 将许可证移入任务，并在完成后将其丢弃。这会将许可证返回到信号量。
+                    drop(permit);
                 }
-                // 将许可证移入任务，并在完成后将其丢弃。这会将许可证返回到信号量。
-                drop(permit);
-            });
+                .instrument(span),
+            );
         }
     }
     /// 接受一个传入的连接。
     ///
     /// 通过退避重试来处理错误。使用指数退避策略。在第一次失败后，任务等待1秒。
     /// 第二次失败后，任务等待2秒。每次后续失败都会使等待时间加倍。
-    /// 如果在等待64秒后第6次尝试接受失败，则此函数将返回一个错误。
+    /// 需要的等待时间超过 `max_accept_backoff` 时（默认即等待64秒之后再次失败时），此函数返回一个错误。
+    ///
+    /// 等待时间只在失败之后产生，并且每次调用都从1秒开始，因此成功接受连接之后退避被重置。
     async fn accept(&mut self) -> crate::Result<TcpStream> {
-        let mut backoff = 1;
+        let mut backoff = Duration::from_secs(1);
 
         // 尝试接受几次
         loop {
             // 执行接受操作。如果成功接受了一个套接字，则返回它。否则，保存错误。
-            match self.listener.accept().await {
+            match self.poll_listeners().await {
                 Ok((socket, _)) => return Ok(socket),
                 Err(err) => {
-                    if backoff > 64 {
+                    if backoff > self.max_accept_backoff {
                         // 接受操作失败太多次。返回错误。
                         return Err(err.into());
                     }
+
+                    warn!(cause = %err, ?backoff, "failed to accept connection, retrying");
                 }
             }
 
             // 暂停执行直到退避期结束。
-            time::sleep(Duration::from_secs(backoff)).await;
+            time::sleep(backoff).await;
 
             // 将退避时间加倍
             backoff *= 2;
         }
     }
+
+    /// 等待任意一个侦听器上的连接。
+    async fn poll_listeners(&mut self) -> std::io::Result<(TcpStream, SocketAddr)> {
+        let listeners = &self.listeners;
+        let start = self.next_listener;
+
+        let (index, res) = future::poll_fn(|cx| {
+            for i in 0..listeners.len() {
+                let index = (start + i) % listeners.len();
+
+                if let Poll::Ready(res) = listeners[index].poll_accept(cx) {
+                    return Poll::Ready((index, res));
+                }
+            }
+
+            Poll::Pending
+        })
+        .await;
+
+        self.next_listener = (index + 1) % self.listeners.len();
+
+        res
+    }
 }
 impl Handler {
     /// 处理单个连接。
@@ -263,12 +1654,44 @@ impl Handler {
         while !self.shutdown.is_shutdown() {
             // 在读取请求帧的同时也监听关闭信号。
             let maybe_frame = tokio::select! {
-                res = self.connection.read_frame() => res?,
+                res = self.connection.read_frame() => match res {
+                    Ok(maybe_frame) => maybe_frame,
+                    Err(err) => {
+                        // 如果对等方发送了格式错误的帧，连接将被关闭。
+                        // 在关闭之前，与 Redis 一样发送最后一个 `-ERR Protocol error: ...` 帧，
+                        // 以便客户端了解连接被关闭的原因。写入失败不影响结果，连接无论如何都会关闭。
+                        if let Some(err) = err.downcast_ref::<frame::Error>() {
+                            let _ = self.connection.write_frame(&err.to_frame()).await;
+                        }
+
+                        return Err(err);
+                    }
+                },
+                // 在订阅状态下，转发已订阅频道上的消息。
+                //
+                // 消息不等待写入完成，慢速的订阅者不会阻塞这个循环。未写完的数据在等待下一个命令时继续发送，
+                // 积压超过上限时连接被关闭。
+                frame = self.state.next_message() => {
+                    self.connection.queue_frame(&frame)?;
+                    continue;
+                }
+                // 开启了键跟踪时，推送失效通知。与订阅消息一样不等待写入完成。
+                frame = self.tracking.next_invalidation() => {
+                    self.connection.queue_frame(&frame)?;
+                    continue;
+                }
                 _ = self.shutdown.recv() => {
                     // 如果收到关闭信号，从 `run` 返回。
                     // 这将导致任务终止。
                     return Ok(());
                 }
+                // 期限内没有收到第一个完整的帧，关闭连接，归还连接名额。
+                _ = time::sleep_until(self.handshake_deadline.unwrap_or_else(Instant::now)),
+                    if self.handshake_deadline.is_some() =>
+                {
+                    debug!("no complete frame received before the handshake timeout");
+                    return Ok(());
+                }
             };
 
             // 如果 `read_frame()` 返回 `None`，则表示对等方关闭了套接字。
@@ -277,9 +1700,52 @@ impl Handler {
                 Some(frame) => frame,
                 None => return Ok(()),
             };
+            self.handshake_deadline = None;
+
+            // 定期让出执行权，使流水线发送大量命令的连接不会饿死同一线程上的其他连接。
+            self.commands_since_yield += 1;
+            if self.commands_since_yield == COMMANDS_PER_YIELD {
+                self.commands_since_yield = 0;
+                tokio::task::yield_now().await;
+            }
+
+            // 超出速率限制时，延迟或者拒绝命令。
+            if let Some(bucket) = &mut self.rate_limit {
+                if bucket.limit.reject {
+                    if !bucket.try_acquire() {
+                        let busy = Frame::typed_error(
+                            ErrorKind::Busy,
+                            "rate limit exceeded, try again later",
+                        );
+                        debug!(?busy);
+                        self.connection.write_frame(&busy).await?;
+                        continue;
+                    }
+                } else {
+                    let wait = bucket.acquire();
+
+                    if !wait.is_zero() {
+                        tokio::select! {
+                            _ = time::sleep(wait) => {}
+                            _ = self.shutdown.recv() => return Ok(()),
+                        }
+                    }
+                }
+            }
+
+            // 保留原始的帧，开启了 AOF 或者有副本时，写命令执行时原样追加到 AOF 和复制积压缓冲区。
+            // 是否需要追加在执行时才能确定：副本可能在命令被解析之后才开始同步。
+            let logged = frame.clone();
 
-            // 将 redis 帧转换为命令结构体。如果帧不是有效的 redis 命令或不支持的命令，则返回错误。
-            let cmd = Command::from_frame(frame)?;
+            // 将 redis 帧转换为命令结构体。帧已经被完整地读取，因此命令的参数不正确时（例如 `SET` 不支持的选项）
+            // 与 Redis 一样以错误响应，连接保持打开，之后的请求不受影响。
+            let cmd = match Command::from_frame_with(frame, &self.commands) {
+                Ok(cmd) => cmd,
+                Err(err) => {
+                    self.reject(&err).await?;
+                    continue;
+                }
+            };
 
             // 记录 `cmd` 对象。这里的语法是由 `tracing` crate 提供的简写。
             // 它可以被认为类似于：
@@ -291,13 +1757,410 @@ impl Handler {
             // `tracing` 提供结构化日志记录，因此信息以键值对的形式“记录”。
             debug!(?cmd);
 
+            // 钩子拒绝的命令与无法解析的命令一样以错误响应，不会被执行。
+            let denied = self
+                .command_hooks
+                .iter()
+                .find_map(|hook| hook.before(&cmd, self.peer));
+
+            if let Some(response) = denied {
+                self.deny(response).await?;
+                continue;
+            }
+
+            let name = cmd.get_name().to_string();
+            let quit = matches!(cmd, Command::Quit(_));
+            let asking = matches!(cmd, Command::Asking(_));
+
+            // 每个命令在独立的 span 中执行，span 记录对端地址、命令名称、第一个键以及执行结果，
+            // 启用 `otel` 功能时可以在追踪系统中查看单个命令。
+            let span = info_span!(
+                "command",
+                peer = %self.peer,
+                command = name.as_str(),
+                key = field::Empty,
+                outcome = field::Empty,
+            );
+
+            if let Some(key) = cmd.keys().first() {
+                span.record("key", String::from_utf8_lossy(key).as_ref());
+            }
+
+            // 等待其他连接或者副本的命令（例如 `BLPOP`）不计入延迟监控。
+            let monitored = !cmd.may_wait();
+            let start = Instant::now();
+            let error_replies = self.connection.error_replies();
+
+            let res = self.dispatch(cmd, logged).instrument(span.clone()).await;
+
+            // `failed` 表示连接出错，`error` 表示命令以错误帧响应。
+            let outcome = match res {
+                Err(_) => Outcome::Failed,
+                Ok(()) if self.connection.error_replies() > error_replies => Outcome::Error,
+                Ok(()) => Outcome::Ok,
+            };
+            span.record("outcome", outcome.as_str());
+
+            let elapsed = start.elapsed();
+            if monitored {
+                self.db.record_latency(COMMAND_EVENT, elapsed);
+            }
+
+            for hook in self.command_hooks.iter() {
+                hook.after(&name, self.peer, elapsed, outcome);
+            }
+
+            // 以 `histogram.` 为前缀的字段会被 `tracing-opentelemetry` 的指标层记录为直方图。
+            span.in_scope(|| {
+                debug!(
+                    histogram.command_latency_us = elapsed.as_micros() as u64,
+                    "command completed"
+                )
+            });
+
+            res?;
+
+            // `ASKING` 只对紧随其后的一个命令有效。
+            if !asking {
+                self.asking = false;
+            }
+
+            // `QUIT` 的响应已经写入，关闭连接。
+            if quit {
+                return Ok(());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 根据连接的状态应用命令。`logged` 是命令的原始帧。
+    async fn dispatch(&mut self, cmd: Command, logged: Frame) -> crate::Result<()> {
+        // `RESET` 不受权限限制，在任何状态下都将连接恢复到初始状态。
+        if let Command::Reset(cmd) = cmd {
+            self.state = State::Normal;
+            self.tracking.disable();
+            self.pubsub_metadata = false;
+            self.user = self.acl.default_user();
+            return cmd.apply(&mut self.connection).await;
+        }
+
+        // `QUIT` 同样不受权限限制，连接处理程序在写入响应之后关闭连接。
+        if let Command::Quit(cmd) = cmd {
+            return cmd.apply(&mut self.connection).await;
+        }
+
+        match self.state {
+            State::Normal => self.apply(cmd, logged).await,
+            State::Multi { .. } => self.queue(cmd, logged).await,
+            State::Subscribed(_) => self.apply_subscribed(cmd),
+        }
+    }
+
+    /// 在正常状态下应用命令。
+    async fn apply(&mut self, cmd: Command, logged: Frame) -> crate::Result<()> {
+        // `AUTH` 修改连接的用户，由处理程序直接应用，不受权限限制。
+        let cmd = match cmd {
+            Command::Auth(cmd) => {
+                return cmd
+                    .apply(&self.acl, &mut self.user, &mut self.connection)
+                    .await;
+            }
+            cmd => cmd,
+        };
+
+        if let Some(denied) = self.check(&cmd) {
+            debug!(?denied);
+            self.connection.write_frame(&denied).await?;
+            return Ok(());
+        }
+
+        match cmd {
+            Command::Acl(cmd) => {
+                cmd.apply(&self.acl, self.user.as_deref(), &mut self.connection)
+                    .await
+            }
+            Command::Asking(cmd) => {
+                self.asking = true;
+                cmd.apply(&mut self.connection).await
+            }
+            // 脚本通过 `redis.call` 执行的命令与客户端发送的命令一样检查权限。
+            #[cfg(feature = "scripting")]
+            Command::Eval(cmd) => {
+                let (permissions, dst) = self.script_context();
+                cmd.apply(permissions.db, dst, &|cmd| permissions.check(cmd))
+                    .await
+            }
+            #[cfg(feature = "scripting")]
+            Command::EvalSha(cmd) => {
+                let (permissions, dst) = self.script_context();
+                cmd.apply(permissions.db, dst, &|cmd| permissions.check(cmd))
+                    .await
+            }
+            Command::Client(cmd) => {
+                cmd.apply(
+                    &mut self.tracking,
+                    &mut self.pubsub_metadata,
+                    &self.db,
+                    &mut self.connection,
+                )
+                .await
+            }
+            Command::Config(cmd) => {
+                cmd.apply(&self.connection_limit, &mut self.connection)
+                    .await
+            }
+            Command::Info(cmd) => {
+                cmd.apply(
+                    &self.db,
+                    &self.connection_limit,
+                    &self.buffer_pool,
+                    &mut self.connection,
+                )
+                .await
+            }
+            // 连接之后只用于向副本发送写命令，直到任意一方关闭连接。
+            Command::PSync(cmd) => {
+                cmd.apply(&self.db, &mut self.connection, &mut self.shutdown)
+                    .await
+            }
+            Command::ReplicaOf(cmd) => cmd.apply(&self.db, &mut self.connection).await,
+            Command::Multi(cmd) => {
+                self.state = State::Multi {
+                    queued: vec![],
+                    aborted: false,
+                };
+                cmd.apply(&mut self.connection).await
+            }
+            Command::Exec(_) => {
+                self.write_error(Frame::typed_error(ErrorKind::Err, "EXEC without MULTI"))
+                    .await
+            }
+            Command::Discard(_) => {
+                self.write_error(Frame::typed_error(ErrorKind::Err, "DISCARD without MULTI"))
+                    .await
+            }
+            Command::Subscribe(cmd) => {
+                let mut subscriptions = Subscriptions::new(&self.db, self.pubsub_metadata);
+                subscriptions.subscribe(cmd, &mut self.connection)?;
+                self.state = State::Subscribed(subscriptions);
+                Ok(())
+            }
+            Command::Unsubscribe(cmd) => Subscriptions::new(&self.db, self.pubsub_metadata)
+                .unsubscribe(cmd, &mut self.connection),
+            // 写命令与追加它的效果到 AOF 和复制积压缓冲区在同一次加锁下完成。
+            cmd if cmd.is_propagated() => {
+                let (response, write) = self.db.batch_logged(|batch| {
+                    cmd.execute_propagated(batch, logged)
+                        .unwrap_or_else(|err| Frame::error(err.to_string()))
+                });
+
+                // `always` 策略下等待命令写入磁盘之后再响应。
+                let response = match write.wait().await {
+                    Ok(()) => response,
+                    Err(msg) => Frame::error(msg),
+                };
+
+                debug!(?response);
+                self.connection.write_frame(&response).await?;
+                Ok(())
+            }
             // 执行应用命令所需的工作。这可能会导致数据库状态的变化。
             //
             // 连接被传递到 apply 函数中，这允许命令直接将响应帧写入连接。
-            // 在发布/订阅的情况下，可能会有多个帧发送回对等方。
-            cmd.apply(&self.db, &mut self.connection, &mut self.shutdown)
-                .await?;
+            cmd => {
+                // 开启了键跟踪时，记住只读命令读取的键。键在命令执行之后才被跟踪，
+                // 但是执行期间被修改的键仍然会失效，因为失效通知在接收时才与被跟踪的键比较。
+                let tracked: Vec<String> = if self.tracking.is_enabled() && !cmd.is_write() {
+                    cmd.keys()
+                        .into_iter()
+                        .map(|key| String::from_utf8_lossy(key).into_owned())
+                        .collect()
+                } else {
+                    vec![]
+                };
+
+                cmd.apply(&self.db, &mut self.connection, &mut self.shutdown)
+                    .await?;
+
+                self.tracking.track(tracked);
+                Ok(())
+            }
+        }
+    }
+
+    /// 在事务状态下排队命令，直到 `EXEC` 或 `DISCARD`。
+    ///
+    /// 与 Redis 一样，排队时被拒绝的命令（未知命令、没有权限的命令、不能在事务中执行的命令）
+    /// 会使 `EXEC` 放弃整个事务。
+    async fn queue(&mut self, cmd: Command, logged: Frame) -> crate::Result<()> {
+        let error = match cmd {
+            Command::Exec(cmd) => {
+                return match std::mem::replace(&mut self.state, State::Normal) {
+                    State::Multi { aborted: true, .. } => {
+                        self.write_error(Frame::typed_error(
+                            ErrorKind::ExecAbort,
+                            "Transaction discarded because of previous errors.",
+                        ))
+                        .await
+                    }
+                    State::Multi { queued, .. } => {
+                        cmd.apply(queued, &self.db, &mut self.connection).await
+                    }
+                    _ => unreachable!(),
+                };
+            }
+            Command::Discard(cmd) => {
+                self.state = State::Normal;
+                return cmd.apply(&mut self.connection).await;
+            }
+            Command::Multi(_) => {
+                // 嵌套的 `MULTI` 不影响事务。
+                return self
+                    .write_error(Frame::typed_error(
+                        ErrorKind::Err,
+                        "MULTI calls can not be nested",
+                    ))
+                    .await;
+            }
+            Command::Unknown(cmd) => cmd.to_error(),
+            cmd => match self.check(&cmd) {
+                Some(denied) => denied,
+                None if cmd.is_atomic() => {
+                    if let State::Multi { queued, .. } = &mut self.state {
+                        queued.push((cmd, logged));
+                    }
+
+                    let response = Frame::Simple(ByteString::from_static("QUEUED"));
+                    debug!(?response);
+                    self.connection.write_frame(&response).await?;
+                    return Ok(());
+                }
+                None => Frame::typed_error(
+                    ErrorKind::Err,
+                    format_args!(
+                        "command '{}' is not allowed inside a transaction",
+                        cmd.get_name()
+                    ),
+                ),
+            },
+        };
+
+        if let State::Multi { aborted, .. } = &mut self.state {
+            *aborted = true;
+        }
+
+        debug!(?error);
+        self.connection.write_frame(&error).await?;
+
+        Ok(())
+    }
+
+    /// 在订阅状态下应用命令。在此状态下只允许订阅、取消订阅和 `PING` 命令。
+    ///
+    /// 订阅者的连接上可能积压着大量尚未发送的消息，因此所有响应都通过 `queue_frame` 写入而不等待，
+    /// 连接处理程序在慢速的对等方停止读取时仍然能够响应关闭信号。积压的数据受 `Config::output_limit`
+    /// 限制（默认 32MB），停止读取的订阅者超过上限时连接被关闭，而不是无限制地占用内存。
+    fn apply_subscribed(&mut self, cmd: Command) -> crate::Result<()> {
+        if !matches!(
+            cmd,
+            Command::Subscribe(_) | Command::Unsubscribe(_) | Command::Ping(_)
+        ) {
+            let response = Unknown::new(cmd.get_name()).to_error();
+            debug!(?response);
+            self.connection.queue_frame(&response)?;
+            return Ok(());
+        }
+
+        if let Some(denied) = self.check(&cmd) {
+            debug!(?denied);
+            self.connection.queue_frame(&denied)?;
+            return Ok(());
+        }
+
+        let subscriptions = match &mut self.state {
+            State::Subscribed(subscriptions) => subscriptions,
+            _ => unreachable!(),
+        };
+
+        match cmd {
+            Command::Subscribe(cmd) => subscriptions.subscribe(cmd, &mut self.connection),
+            Command::Unsubscribe(cmd) => {
+                subscriptions.unsubscribe(cmd, &mut self.connection)?;
+
+                // 与 Redis 一样，订阅数量降为零时，连接离开订阅状态，可以再次执行普通命令。
+                if subscriptions.is_empty() {
+                    self.state = State::Normal;
+                }
+
+                Ok(())
+            }
+            Command::Ping(cmd) => cmd.apply_subscribed(&mut self.connection),
+            _ => unreachable!(),
+        }
+    }
+
+    /// 检查当前用户能否执行命令、只读模式下或者副本上命令是否会写入，以及在集群模式下命令访问的键是否由本节点负责。
+    ///
+    /// 不允许执行时返回发送给客户端的错误帧。
+    fn check(&self, cmd: &Command) -> Option<Frame> {
+        self.permissions().check(cmd)
+    }
+
+    /// 返回连接当前的权限。
+    fn permissions(&self) -> Permissions<'_> {
+        Permissions {
+            user: self.user.as_deref(),
+            read_only: self.read_only,
+            slot_check: self.slot_check.as_deref(),
+            asking: self.asking,
+            db: &self.db,
         }
+    }
+
+    /// 与 `permissions` 相同，同时返回连接本身。脚本需要检查通过 `redis.call` 执行的每个命令，并将响应写入连接。
+    #[cfg(feature = "scripting")]
+    fn script_context(&mut self) -> (Permissions<'_>, &mut Connection) {
+        let permissions = Permissions {
+            user: self.user.as_deref(),
+            read_only: self.read_only,
+            slot_check: self.slot_check.as_deref(),
+            asking: self.asking,
+            db: &self.db,
+        };
+
+        (permissions, &mut self.connection)
+    }
+
+    /// 以错误响应无法解析的命令。与 Redis 一样，在事务中这会使 `EXEC` 放弃整个事务。
+    async fn reject(&mut self, err: &crate::Error) -> crate::Result<()> {
+        self.deny(Frame::error(err.to_string())).await
+    }
+
+    /// 以 `response` 响应一个不被执行的命令。在事务中这会使 `EXEC` 放弃整个事务。
+    async fn deny(&mut self, response: Frame) -> crate::Result<()> {
+        debug!(?response);
+
+        match &mut self.state {
+            State::Multi { aborted, .. } => *aborted = true,
+            // 订阅状态下不等待写入完成，参见 `apply_subscribed`。
+            State::Subscribed(_) => {
+                self.connection.queue_frame(&response)?;
+                return Ok(());
+            }
+            State::Normal => {}
+        }
+
+        self.connection.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// 向客户端写入一个错误帧。
+    async fn write_error(&mut self, response: Frame) -> crate::Result<()> {
+        debug!(?response);
+
+        self.connection.write_frame(&response).await?;
 
         Ok(())
     }
@@ -0,0 +1,48 @@
+//! TCP 套接字选项，服务器接受的连接和客户端建立的连接共用。
+
+use socket2::{SockRef, TcpKeepalive};
+use std::io;
+use std::time::Duration;
+use tokio::net::TcpStream;
+
+/// keepalive 时间的下限。操作系统以秒为单位设置 keepalive，不足一秒的时间会被拒绝。
+const MIN_KEEPALIVE: Duration = Duration::from_secs(1);
+
+/// 应用于每个连接的 TCP 套接字选项。
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SocketOptions {
+    /// 是否设置 `TCP_NODELAY`，即关闭 Nagle 算法。请求和响应都很小，默认开启以避免延迟。
+    pub(crate) nodelay: bool,
+
+    /// 连接空闲多久之后开始发送 keepalive 探测。`None` 表示不开启 keepalive。
+    pub(crate) keepalive: Option<Duration>,
+}
+
+impl SocketOptions {
+    /// 将选项应用到 `socket`。
+    pub(crate) fn apply(&self, socket: &TcpStream) -> io::Result<()> {
+        socket.set_nodelay(self.nodelay)?;
+
+        if let Some(time) = self.keepalive {
+            let time = time.max(MIN_KEEPALIVE);
+            let keepalive = TcpKeepalive::new().with_time(time);
+
+            // 与 Redis 一样，Linux 上每隔三分之一的空闲时间发送一次探测。
+            #[cfg(target_os = "linux")]
+            let keepalive = keepalive.with_interval((time / 3).max(MIN_KEEPALIVE));
+
+            SockRef::from(socket).set_tcp_keepalive(&keepalive)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for SocketOptions {
+    fn default() -> SocketOptions {
+        SocketOptions {
+            nodelay: true,
+            keepalive: None,
+        }
+    }
+}
@@ -0,0 +1,236 @@
+//! 流（stream）数据类型，用于 `XADD`、`XRANGE` 和 `XREAD` 命令。
+//!
+//! 流是一个只能追加的条目序列，每个条目由一个单调递增的 ID 和一组字段/值对组成。
+//! 服务器在 `Db` 中存储 `Stream`，客户端以 `StreamEntry` 的形式接收条目。
+
+use crate::Frame;
+
+use bytes::Bytes;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 流条目的 ID，格式为 `<毫秒时间戳>-<序列号>`。
+///
+/// ID 按照先时间戳、后序列号的顺序比较。同一毫秒内添加的条目使用递增的序列号区分。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StreamId {
+    /// 毫秒时间戳
+    pub ms: u64,
+
+    /// 同一毫秒内的序列号
+    pub seq: u64,
+}
+
+/// 流中的一个条目。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamEntry {
+    /// 条目的 ID
+    pub id: StreamId,
+
+    /// 条目的字段/值对，顺序与添加时相同
+    pub fields: Vec<(Bytes, Bytes)>,
+}
+
+impl StreamId {
+    /// 最小的 ID，即 `0-0`。
+    pub const MIN: StreamId = StreamId { ms: 0, seq: 0 };
+
+    /// 最大的 ID。
+    pub const MAX: StreamId = StreamId {
+        ms: u64::MAX,
+        seq: u64::MAX,
+    };
+
+    /// 创建一个新的 `StreamId`。
+    pub fn new(ms: u64, seq: u64) -> StreamId {
+        StreamId { ms, seq }
+    }
+
+    /// 解析 ID，省略序列号时使用 `seq`。
+    ///
+    /// `XRANGE` 中省略序列号的起始 ID 表示该毫秒内的第一个条目，结束 ID 表示最后一个条目。
+    pub(crate) fn parse_with_default_seq(s: &str, seq: u64) -> crate::Result<StreamId> {
+        let invalid = || "ERR Invalid stream ID specified as stream command argument";
+
+        let (ms, seq) = match s.split_once('-') {
+            Some((ms, seq)) => (ms, seq.parse().map_err(|_| invalid())?),
+            None => (s, seq),
+        };
+
+        let ms = ms.parse().map_err(|_| invalid())?;
+
+        Ok(StreamId { ms, seq })
+    }
+
+    /// 返回比 `self` 大的最小 ID。如果 `self` 已经是最大的 ID，则返回 `None`。
+    fn next(self) -> Option<StreamId> {
+        match self.seq.checked_add(1) {
+            Some(seq) => Some(StreamId { ms: self.ms, seq }),
+            None => self.ms.checked_add(1).map(|ms| StreamId { ms, seq: 0 }),
+        }
+    }
+}
+
+impl fmt::Display for StreamId {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}-{}", self.ms, self.seq)
+    }
+}
+
+impl FromStr for StreamId {
+    type Err = crate::Error;
+
+    /// 解析 `<ms>-<seq>` 或 `<ms>` 格式的 ID，省略序列号时视为 `0`。
+    fn from_str(s: &str) -> crate::Result<StreamId> {
+        StreamId::parse_with_default_seq(s, 0)
+    }
+}
+
+impl StreamEntry {
+    /// 将条目编码为 `[id, [field, value, ...]]` 形式的数组帧。
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut fields = Frame::array();
+
+        for (field, value) in self.fields {
+            fields.push_bulk(field);
+            fields.push_bulk(value);
+        }
+
+        Frame::Array(vec![Frame::Bulk(Bytes::from(self.id.to_string())), fields])
+    }
+
+    /// 从 `into_frame` 生成的数组帧中解码条目。
+    pub(crate) fn from_frame(frame: Frame) -> crate::Result<StreamEntry> {
+        let mut parts = frame.try_into_vec()?.into_iter();
+
+        let (id, fields) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(Frame::Bulk(id)), Some(Frame::Array(fields)), None) => (id, fields),
+            _ => return Err("protocol error; invalid stream entry".into()),
+        };
+
+        let id = std::str::from_utf8(&id)?.parse()?;
+
+        if fields.len() % 2 != 0 {
+            return Err("protocol error; invalid stream entry".into());
+        }
+
+        let mut pairs = Vec::with_capacity(fields.len() / 2);
+        let mut fields = fields.into_iter();
+
+        while let (Some(field), Some(value)) = (fields.next(), fields.next()) {
+            match (field, value) {
+                (Frame::Bulk(field), Frame::Bulk(value)) => pairs.push((field, value)),
+                (field, _) => return Err(field.to_error()),
+            }
+        }
+
+        Ok(StreamEntry { id, fields: pairs })
+    }
+}
+
+/// 存储在 `Db` 中的流。
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Stream {
+    /// 按 ID 排序的条目
+    entries: BTreeMap<StreamId, Vec<(Bytes, Bytes)>>,
+
+    /// 最后添加的条目的 ID。条目被删除后仍然保留，保证新的 ID 总是递增。
+    last_id: StreamId,
+}
+
+impl Stream {
+    /// 流中条目的数量。
+    #[cfg(feature = "debug")]
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// 从快照中恢复一个流。`entries` 按 ID 排序，`last_id` 不小于其中最大的 ID。
+    pub(crate) fn restore(last_id: StreamId, entries: Vec<StreamEntry>) -> Stream {
+        Stream {
+            entries: entries
+                .into_iter()
+                .map(|entry| (entry.id, entry.fields))
+                .collect(),
+            last_id,
+        }
+    }
+
+    /// 最后添加的条目的 ID。空的流返回 `0-0`。
+    pub(crate) fn last_id(&self) -> StreamId {
+        self.last_id
+    }
+
+    /// 添加一个条目，返回其 ID。
+    ///
+    /// `id` 为 `None` 时根据当前时间自动生成 ID。指定的 ID 必须大于流中最后一个条目的 ID。
+    pub(crate) fn add(
+        &mut self,
+        id: Option<StreamId>,
+        fields: Vec<(Bytes, Bytes)>,
+    ) -> Result<StreamId, &'static str> {
+        let id = match id {
+            Some(StreamId::MIN) => {
+                return Err("ERR The ID specified in XADD must be greater than 0-0");
+            }
+            Some(id) if id <= self.last_id => {
+                return Err(
+                    "ERR The ID specified in XADD is equal or smaller than the target stream top item",
+                );
+            }
+            Some(id) => id,
+            None => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |elapsed| elapsed.as_millis() as u64);
+
+                // 时钟回拨或者同一毫秒内添加多个条目时，沿用最后的时间戳并递增序列号。
+                if now > self.last_id.ms {
+                    StreamId::new(now, 0)
+                } else {
+                    self.last_id
+                        .next()
+                        .ok_or("ERR The stream has exhausted the last possible ID, unable to add more items")?
+                }
+            }
+        };
+
+        self.entries.insert(id, fields);
+        self.last_id = id;
+
+        Ok(id)
+    }
+
+    /// 返回 ID 在 `start` 和 `end`（包含两端）之间的条目，最多返回 `count` 个。
+    pub(crate) fn range(
+        &self,
+        start: StreamId,
+        end: StreamId,
+        count: Option<u64>,
+    ) -> Vec<StreamEntry> {
+        if start > end {
+            return vec![];
+        }
+
+        let count = count.map_or(usize::MAX, |count| count as usize);
+
+        self.entries
+            .range(start..=end)
+            .take(count)
+            .map(|(&id, fields)| StreamEntry {
+                id,
+                fields: fields.clone(),
+            })
+            .collect()
+    }
+
+    /// 返回 ID 大于 `after` 的条目，最多返回 `count` 个。
+    pub(crate) fn read_after(&self, after: StreamId, count: Option<u64>) -> Vec<StreamEntry> {
+        match after.next() {
+            Some(start) => self.range(start, StreamId::MAX, count),
+            None => vec![],
+        }
+    }
+}
@@ -0,0 +1,63 @@
+//! `Db` 的后台任务使用的同步原语。
+//!
+//! 普通构建直接使用标准库和 Tokio 的实现。`cfg(mini_redis_loom)` 构建换成 loom 的实现，使 `Db::set`、
+//! 后台任务清除过期键以及 `shutdown_purge_task` 之间的交错执行能够在 loom 下被穷举检查，
+//! 详见 `src/db/loom_tests.rs`。
+//!
+//! 这里没有使用常见的 `cfg(loom)`：`RUSTFLAGS` 中的配置对所有依赖生效，而 Tokio 在 `cfg(loom)` 下会禁用
+//! `net` 等模块，导致服务器和客户端无法编译。
+
+#[cfg(not(mini_redis_loom))]
+pub(crate) use std::sync::{Arc, Mutex, MutexGuard};
+#[cfg(not(mini_redis_loom))]
+pub(crate) use tokio::sync::Notify;
+
+#[cfg(mini_redis_loom)]
+pub(crate) use self::loom_notify::Notify;
+#[cfg(mini_redis_loom)]
+pub(crate) use loom::sync::{Arc, Mutex, MutexGuard};
+
+#[cfg(mini_redis_loom)]
+mod loom_notify {
+    use loom::sync::{Condvar, Mutex};
+    use std::fmt;
+
+    /// 与 `tokio::sync::Notify::notify_one` 语义相同的通知：没有等待者时保存一个许可，
+    /// 下一次 `wait` 立即返回，因此通知不会因为发生在等待之前而丢失。
+    pub(crate) struct Notify {
+        permit: Mutex<bool>,
+        condvar: Condvar,
+    }
+
+    impl Notify {
+        pub(crate) fn new() -> Notify {
+            Notify {
+                permit: Mutex::new(false),
+                condvar: Condvar::new(),
+            }
+        }
+
+        /// 唤醒等待者，没有等待者时保存许可。
+        pub(crate) fn notify_one(&self) {
+            *self.permit.lock().unwrap() = true;
+            self.condvar.notify_one();
+        }
+
+        /// 阻塞当前线程，直到获得许可。
+        pub(crate) fn wait(&self) {
+            let mut permit = self.permit.lock().unwrap();
+
+            while !*permit {
+                permit = self.condvar.wait(permit).unwrap();
+            }
+
+            *permit = false;
+        }
+    }
+
+    impl fmt::Debug for Notify {
+        fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+            fmt.write_str("Notify")
+        }
+    }
+}
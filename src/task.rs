@@ -0,0 +1,30 @@
+//! 生成具名的 Tokio 任务。
+//!
+//! 启用 `console` 特性并以 `RUSTFLAGS="--cfg tokio_unstable"` 编译时，任务通过 `tokio::task::Builder`
+//! 以给定的名称生成，tokio-console 据此显示每个任务的身份；否则名称被忽略，等同于 `tokio::spawn`。
+
+use std::future::Future;
+use tokio::task::JoinHandle;
+
+/// 以 `name` 为名称生成一个任务。
+#[cfg(all(feature = "console", tokio_unstable))]
+pub(crate) fn spawn<F>(name: &str, future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    tokio::task::Builder::new()
+        .name(name)
+        .spawn(future)
+        .expect("failed to spawn task")
+}
+
+/// 以 `name` 为名称生成一个任务。
+#[cfg(not(all(feature = "console", tokio_unstable)))]
+pub(crate) fn spawn<F>(_name: &str, future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    tokio::spawn(future)
+}
@@ -0,0 +1,91 @@
+use mini_redis::{
+    acl::{Acl, User},
+    clients::Client,
+    server::{self, Config},
+};
+use std::net::SocketAddr;
+use tokio::net::TcpListener;
+
+/// Without an ACL every connection runs as the unrestricted `default` user.
+#[tokio::test]
+async fn default_user_is_unrestricted() {
+    let addr = start_server(Config::new()).await;
+
+    let mut client = Client::connect(addr).await.unwrap();
+    assert_eq!("default", client.acl_whoami().await.unwrap());
+    assert_eq!(
+        vec!["user default on nopass ~* +@all".to_string()],
+        client.acl_list().await.unwrap()
+    );
+
+    client.set("foo", "bar".into()).await.unwrap();
+}
+
+/// Users may only run their allowed commands against keys matching their
+/// patterns, and must authenticate when there is no `default` user.
+#[tokio::test]
+async fn users_are_restricted() {
+    let acl = Acl::new()
+        .user(
+            User::new("app")
+                .password("secret")
+                .command("get")
+                .command("set")
+                .key_pattern("app:*"),
+        )
+        .user(
+            User::new("admin")
+                .password("root")
+                .all_commands()
+                .all_keys(),
+        );
+
+    let addr = start_server(Config::new().acl(acl)).await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    let err = client.get("app:1").await.unwrap_err();
+    assert_eq!("NOAUTH Authentication required.", err.to_string());
+
+    let err = client.auth("app", "wrong").await.unwrap_err();
+    assert!(err.to_string().starts_with("WRONGPASS"));
+
+    client.auth("app", "secret").await.unwrap();
+    client.set("app:1", "1".into()).await.unwrap();
+    assert_eq!(b"1", &client.get("app:1").await.unwrap().unwrap()[..]);
+
+    let err = client.get("other").await.unwrap_err();
+    assert_eq!(
+        "NOPERM this user has no permissions to access one of the keys used as arguments",
+        err.to_string()
+    );
+
+    let err = client.incr("app:1").await.unwrap_err();
+    assert_eq!(
+        "NOPERM this user has no permissions to run the 'incr' command",
+        err.to_string()
+    );
+
+    client.auth("admin", "root").await.unwrap();
+    assert_eq!("admin", client.acl_whoami().await.unwrap());
+    assert_eq!(2, client.incr("app:1").await.unwrap());
+    assert_eq!(
+        vec![
+            "user admin on ~* +@all".to_string(),
+            "user app on ~app:* +get +set".to_string(),
+        ],
+        client.acl_list().await.unwrap()
+    );
+}
+
+async fn start_server(config: Config) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(server::run_with_config(
+        listener,
+        config,
+        std::future::pending::<()>(),
+    ));
+
+    addr
+}
@@ -0,0 +1,99 @@
+use mini_redis::{clients::BlockingClient, server};
+use std::net::SocketAddr;
+use std::thread;
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+/// The blocking client exposes the same commands as the async one.
+#[test]
+fn blocking_commands() {
+    let addr = start_server();
+    let mut client = BlockingClient::connect(addr).unwrap();
+
+    assert_eq!(b"PONG", &client.ping(None).unwrap()[..]);
+    assert_eq!(1, client.incr("counter").unwrap());
+
+    client.set("hello", "world".into()).unwrap();
+    let keys = vec!["counter".to_string(), "hello".to_string()];
+    assert_eq!(2, client.exists(&keys).unwrap());
+    assert_eq!(2, client.del(&keys).unwrap());
+    assert_eq!(None, client.get("hello").unwrap());
+}
+
+/// `next_message_timeout` returns `None` when the channel is quiet and the
+/// message once one is published.
+#[test]
+fn next_message_timeout() {
+    let addr = start_server();
+
+    let client = BlockingClient::connect(addr).unwrap();
+    let mut subscriber = client.subscribe(vec!["hello".into()]).unwrap();
+
+    let message = subscriber
+        .next_message_timeout(Duration::from_millis(50))
+        .unwrap();
+    assert!(message.is_none());
+
+    let mut publisher = BlockingClient::connect(addr).unwrap();
+    publisher.publish("hello", "world".into()).unwrap();
+
+    let message = subscriber
+        .next_message_timeout(Duration::from_secs(5))
+        .unwrap()
+        .unwrap();
+    assert_eq!("hello", &message.channel);
+    assert_eq!(b"world", &message.content[..]);
+}
+
+/// `try_iter` yields the messages that have already arrived and stops
+/// instead of waiting for more.
+#[test]
+fn try_iter_yields_available_messages() {
+    let addr = start_server();
+
+    let client = BlockingClient::connect(addr).unwrap();
+    let mut subscriber = client.subscribe(vec!["hello".into()]).unwrap();
+
+    assert_eq!(0, subscriber.try_iter().count());
+
+    let mut publisher = BlockingClient::connect(addr).unwrap();
+    for i in 0..3 {
+        publisher.publish("hello", i.to_string().into()).unwrap();
+    }
+
+    // Wait for the first message, then give the rest time to arrive.
+    let first = subscriber
+        .next_message_timeout(Duration::from_secs(5))
+        .unwrap()
+        .unwrap();
+    assert_eq!(b"0", &first.content[..]);
+    thread::sleep(Duration::from_millis(100));
+
+    let rest: Vec<_> = subscriber
+        .try_iter()
+        .map(|message| message.unwrap().content)
+        .collect();
+    assert_eq!(vec!["1", "2"], rest);
+
+    assert_eq!(0, subscriber.try_iter().count());
+}
+
+fn start_server() -> SocketAddr {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    listener.set_nonblocking(true).unwrap();
+
+    thread::spawn(move || {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        rt.block_on(async move {
+            let listener = TcpListener::from_std(listener).unwrap();
+            server::run(listener, std::future::pending::<()>()).await
+        });
+    });
+
+    addr
+}
@@ -3,6 +3,7 @@ use mini_redis::{
     server,
 };
 use std::net::SocketAddr;
+use std::time::Duration;
 use tokio::net::TcpListener;
 use tokio::task::JoinHandle;
 
@@ -23,6 +24,67 @@ async fn pool_key_value_get_set() {
     assert_eq!(b"world", &value[..])
 }
 
+/// The buffered handle supports the same commands as `Client`, and clones of
+/// it share the connection.
+#[tokio::test]
+async fn pool_all_commands() {
+    let (addr, _) = start_server().await;
+
+    let client = Client::connect(addr).await.unwrap();
+    let mut client = BufferedClient::buffer(client);
+    let mut other = client.clone();
+
+    assert_eq!(b"PONG", &client.ping(None).await.unwrap()[..]);
+
+    assert_eq!(1, client.incr("counter").await.unwrap());
+    assert_eq!(2, other.incr("counter").await.unwrap());
+
+    client
+        .set_expires("temp", "value".into(), Duration::from_secs(60))
+        .await
+        .unwrap();
+    let keys = vec![
+        "counter".to_string(),
+        "temp".to_string(),
+        "none".to_string(),
+    ];
+    assert_eq!(2, client.exists(&keys).await.unwrap());
+    assert_eq!(2, other.del(&keys).await.unwrap());
+    assert_eq!(None, client.get("temp").await.unwrap());
+
+    assert_eq!(0, client.publish("channel", "hello".into()).await.unwrap());
+}
+
+/// `close` lets requests that are already queued complete, rejects new ones
+/// and waits for the connection task to exit.
+#[tokio::test]
+async fn pool_close_drains_requests() {
+    let (addr, _) = start_server().await;
+
+    let client = Client::connect(addr).await.unwrap();
+    let client = BufferedClient::builder().queue_depth(4).build(client);
+
+    let mut pending = vec![];
+    for _ in 0..4 {
+        let mut client = client.clone();
+        pending.push(tokio::spawn(async move { client.incr("counter").await }));
+    }
+    tokio::task::yield_now().await;
+
+    let mut other = client.clone();
+    client.close().await.unwrap();
+
+    let mut values = vec![];
+    for handle in pending {
+        values.push(handle.await.unwrap().unwrap());
+    }
+    values.sort_unstable();
+    assert_eq!(vec![1, 2, 3, 4], values);
+
+    assert!(other.ping(None).await.is_err());
+    other.close().await.unwrap();
+}
+
 async fn start_server() -> (SocketAddr, JoinHandle<()>) {
     let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
     let addr = listener.local_addr().unwrap();
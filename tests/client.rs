@@ -1,6 +1,17 @@
-use mini_redis::{clients::Client, server};
+use bytes::Bytes;
+use mini_redis::{
+    clients::{CachingClient, Client, Middleware, PubSubEvent, ServerError, SharedSubscriber},
+    frame::ErrorKind,
+    server,
+    snapshot::{self, SnapshotError},
+    stream::StreamId,
+    Frame,
+};
 use std::net::SocketAddr;
-use tokio::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
 use tokio::task::JoinHandle;
 
 /// A PING PONG test without message provided.
@@ -39,6 +50,43 @@ async fn key_value_get_set() {
     assert_eq!(b"world", &value[..])
 }
 
+/// DEL, EXISTS and INCR behave like their Redis counterparts
+#[tokio::test]
+async fn del_exists_incr() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    assert_eq!(1, client.incr("counter").await.unwrap());
+    assert_eq!(2, client.incr("counter").await.unwrap());
+    assert_eq!(b"2", &client.get("counter").await.unwrap().unwrap()[..]);
+
+    client.set("hello", "world".into()).await.unwrap();
+    assert!(client.incr("hello").await.is_err());
+
+    let keys = vec![
+        "counter".to_string(),
+        "hello".to_string(),
+        "hello".to_string(),
+    ];
+    assert_eq!(3, client.exists(&keys).await.unwrap());
+    assert_eq!(2, client.del(&keys).await.unwrap());
+    assert_eq!(0, client.exists(&keys).await.unwrap());
+}
+
+/// `quit` returns once the server has answered, after every earlier command
+/// has been applied.
+#[tokio::test]
+async fn quit_after_writes() {
+    let (addr, _) = start_server().await;
+
+    let mut client = Client::connect(addr).await.unwrap();
+    client.set("hello", "world".into()).await.unwrap();
+    client.quit().await.unwrap();
+
+    let mut client = Client::connect(addr).await.unwrap();
+    assert_eq!(b"world", &client.get("hello").await.unwrap().unwrap()[..]);
+}
+
 /// similar to the "hello world" style test, But this time
 /// a single channel subscription will be tested instead
 #[tokio::test]
@@ -58,6 +106,47 @@ async fn receive_message_subscribed_channel() {
     assert_eq!(b"world", &message.content[..])
 }
 
+/// MPUBLISH delivers every message, in order, and reports the subscriber
+/// count once for the whole batch.
+#[tokio::test]
+async fn mpublish_delivers_in_order() {
+    let (addr, _) = start_server().await;
+
+    let client = Client::connect(addr).await.unwrap();
+    let mut subscriber = client.subscribe(vec!["hello".into()]).await.unwrap();
+
+    let mut client = Client::connect(addr).await.unwrap();
+    let messages = vec!["one".into(), "two".into(), "three".into()];
+    assert_eq!(1, client.mpublish("hello", messages).await.unwrap());
+    assert_eq!(0, client.mpublish("other", vec!["x".into()]).await.unwrap());
+
+    for expected in ["one", "two", "three"] {
+        let message = subscriber.next_message().await.unwrap().unwrap();
+        assert_eq!(expected.as_bytes(), &message.content[..]);
+    }
+}
+
+/// test that non UTF-8 payloads reach the subscriber unchanged
+#[tokio::test]
+async fn receive_binary_message_subscribed_channel() {
+    let (addr, _) = start_server().await;
+
+    let client = Client::connect(addr).await.unwrap();
+    let mut subscriber = client.subscribe(vec!["hello".into()]).await.unwrap();
+
+    tokio::spawn(async move {
+        let mut client = Client::connect(addr).await.unwrap();
+        client
+            .publish("hello", Bytes::from_static(b"\xff\x00\xfe\x80"))
+            .await
+            .unwrap()
+    });
+
+    let message = subscriber.next_message().await.unwrap().unwrap();
+    assert_eq!("hello", &message.channel);
+    assert_eq!(b"\xff\x00\xfe\x80", &message.content[..])
+}
+
 /// test that a client gets messages from multiple subscribed channels
 #[tokio::test]
 async fn receive_message_multiple_subscribed_channels() {
@@ -88,6 +177,112 @@ async fn receive_message_multiple_subscribed_channels() {
     assert_eq!(b"howdy?", &message2.content[..])
 }
 
+/// test that messages are surfaced as typed events through `next_event`
+#[tokio::test]
+async fn receive_event_subscribed_channel() {
+    let (addr, _) = start_server().await;
+
+    let client = Client::connect(addr).await.unwrap();
+    let mut subscriber = client.subscribe(vec!["hello".into()]).await.unwrap();
+
+    tokio::spawn(async move {
+        let mut client = Client::connect(addr).await.unwrap();
+        client.publish("hello", "world".into()).await.unwrap()
+    });
+
+    match subscriber.next_event().await.unwrap().unwrap() {
+        PubSubEvent::Message { channel, content } => {
+            assert_eq!("hello", &channel);
+            assert_eq!(b"world", &content[..]);
+        }
+        event => panic!("unexpected event {:?}", event),
+    }
+}
+
+/// test that a subscriber that opted in to metadata learns which connection
+/// published each message and when, while other subscribers do not
+#[tokio::test]
+async fn receive_message_with_publisher_metadata() {
+    let (addr, _) = start_server().await;
+
+    let mut client = Client::connect(addr).await.unwrap();
+    client.client_pubsub_metadata(true).await.unwrap();
+    let mut traced = client.subscribe(vec!["hello".into()]).await.unwrap();
+
+    let client = Client::connect(addr).await.unwrap();
+    let mut plain = client.subscribe(vec!["hello".into()]).await.unwrap();
+
+    let mut publisher = Client::connect(addr).await.unwrap();
+    let publisher_id = publisher.client_id().await.unwrap();
+    let before = SystemTime::now() - Duration::from_millis(1);
+    assert_eq!(2, publisher.publish("hello", "world".into()).await.unwrap());
+
+    let message = traced.next_message().await.unwrap().unwrap();
+    assert_eq!(b"world", &message.content[..]);
+    let metadata = message.metadata.unwrap();
+    assert_eq!(Some(publisher_id), metadata.publisher);
+    assert!(metadata.published_at >= before);
+    assert!(metadata.published_at <= SystemTime::now());
+
+    let message = plain.next_message().await.unwrap().unwrap();
+    assert_eq!(b"world", &message.content[..]);
+    assert!(message.metadata.is_none());
+}
+
+/// test that a subscribed client can still PING the server
+#[tokio::test]
+async fn ping_while_subscribed() {
+    let (addr, _) = start_server().await;
+
+    let client = Client::connect(addr).await.unwrap();
+    let mut subscriber = client.subscribe(vec!["hello".into()]).await.unwrap();
+
+    let pong = subscriber.ping(None).await.unwrap();
+    assert_eq!(b"PONG", &pong[..]);
+
+    let pong = subscriber.ping(Some("howdy".into())).await.unwrap();
+    assert_eq!(b"howdy", &pong[..]);
+}
+
+/// test that a subscriber which falls behind the channel capacity is told how
+/// many messages were dropped
+#[tokio::test]
+async fn lagged_subscriber_is_notified() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let config = server::Config::new().channel_capacity(2);
+    tokio::spawn(async move {
+        server::run_with_config(listener, config, tokio::signal::ctrl_c()).await
+    });
+
+    let client = Client::connect(addr).await.unwrap();
+    let mut subscriber = client.subscribe(vec!["hello".into()]).await.unwrap();
+
+    // Publish four messages atomically so the subscriber cannot keep up.
+    // EVALBATCH 4 3 PUBLISH hello a 3 PUBLISH hello b ...
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let mut request = b"*18\r\n$9\r\nEVALBATCH\r\n:4\r\n".to_vec();
+    for msg in ["a", "b", "c", "d"] {
+        request.extend_from_slice(b":3\r\n$7\r\nPUBLISH\r\n$5\r\nhello\r\n$1\r\n");
+        request.extend_from_slice(msg.as_bytes());
+        request.extend_from_slice(b"\r\n");
+    }
+    stream.write_all(&request).await.unwrap();
+
+    match subscriber.next_event().await.unwrap().unwrap() {
+        PubSubEvent::Lagged { channel, skipped } => {
+            assert_eq!("hello", &channel);
+            assert_eq!(2, skipped);
+        }
+        event => panic!("unexpected event {:?}", event),
+    }
+
+    for expected in ["c", "d"] {
+        let message = subscriber.next_message().await.unwrap().unwrap();
+        assert_eq!(expected.as_bytes(), &message.content[..]);
+    }
+}
+
 /// test that a client accurately removes its own subscribed channel list
 /// when unsubscribing to all subscribed channels by submitting an empty vec
 #[tokio::test]
@@ -104,11 +299,1398 @@ async fn unsubscribes_from_channels() {
     assert_eq!(subscriber.get_subscribed().len(), 0);
 }
 
-async fn start_server() -> (SocketAddr, JoinHandle<()>) {
+/// test that subscribing to the same channel twice is tracked once, so
+/// unsubscribing from it still succeeds
+#[tokio::test]
+async fn duplicate_subscribe_is_tracked_once() {
+    let (addr, _) = start_server().await;
+
+    let client = Client::connect(addr).await.unwrap();
+    let mut subscriber = client
+        .subscribe(vec!["hello".into(), "hello".into()])
+        .await
+        .unwrap();
+    subscriber.subscribe(&["hello".into()]).await.unwrap();
+    assert_eq!(subscriber.get_subscribed(), ["hello".to_string()]);
+
+    subscriber.unsubscribe(&["hello".into()]).await.unwrap();
+    assert!(subscriber.get_subscribed().is_empty());
+}
+
+/// After unsubscribing from every channel, the connection leaves pub/sub mode
+/// and can be reused for regular commands.
+#[tokio::test]
+async fn subscriber_into_client() {
+    let (addr, _) = start_server().await;
+
+    let client = Client::connect(addr).await.unwrap();
+    let subscriber = client
+        .subscribe(vec!["hello".into(), "world".into()])
+        .await
+        .unwrap();
+
+    let mut client = subscriber.into_client().await.unwrap();
+    client.set("foo", "bar".into()).await.unwrap();
+    let value = client.get("foo").await.unwrap().unwrap();
+    assert_eq!(b"bar", &value[..]);
+
+    // The connection can enter pub/sub mode again.
+    let mut subscriber = client.subscribe(vec!["hello".into()]).await.unwrap();
+    subscriber.unsubscribe_all().await.unwrap();
+    assert!(subscriber.get_subscribed().is_empty());
+}
+
+/// Middleware observes every request and response and may rewrite frames.
+#[tokio::test]
+async fn middleware_intercepts_frames() {
+    struct Prefix(Arc<Mutex<Vec<String>>>);
+
+    impl Middleware for Prefix {
+        fn before_send(&self, request: &mut Frame) {
+            if let Frame::Array(parts) = request {
+                if let Some(Frame::Bulk(key)) = parts.get_mut(1) {
+                    *key = Bytes::from([&b"app:"[..], &key[..]].concat());
+                }
+            }
+        }
+
+        fn after_receive(&self, request: &Frame, response: &mut Frame, _: Duration) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("{} -> {}", request, response));
+        }
+    }
+
+    let (addr, _) = start_server().await;
+    let log = Arc::new(Mutex::new(vec![]));
+
+    let mut client = Client::connect(addr)
+        .await
+        .unwrap()
+        .with_middleware(Prefix(log.clone()));
+    client.set("foo", "bar".into()).await.unwrap();
+    assert_eq!(b"bar", &client.get("foo").await.unwrap().unwrap()[..]);
+
+    let mut plain = Client::connect(addr).await.unwrap();
+    assert_eq!(b"bar", &plain.get("app:foo").await.unwrap().unwrap()[..]);
+    assert_eq!(None, plain.get("foo").await.unwrap());
+
+    let log = log.lock().unwrap();
+    assert_eq!(2, log.len());
+    assert!(log[0].starts_with("set app:foo bar"), "{}", log[0]);
+    assert!(log[1].starts_with("get app:foo"), "{}", log[1]);
+}
+
+/// Without replication no replica acknowledges writes: `WAIT 0` returns
+/// immediately, otherwise the command waits for the timeout.
+#[tokio::test]
+async fn wait_without_replicas() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    client.set("hello", "world".into()).await.unwrap();
+    assert_eq!(0, client.wait(0, None).await.unwrap());
+
+    let start = std::time::Instant::now();
+    let timeout = Duration::from_millis(50);
+    assert_eq!(0, client.wait(1, Some(timeout)).await.unwrap());
+    assert!(start.elapsed() >= timeout);
+}
+
+/// SETRANGE pads with zero bytes and GETRANGE supports negative offsets.
+#[tokio::test]
+async fn setrange_getrange() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    client.set("greeting", "Hello World".into()).await.unwrap();
+    assert_eq!(
+        11,
+        client
+            .setrange("greeting", 6, "Redis".into())
+            .await
+            .unwrap()
+    );
+    assert_eq!(
+        b"Hello Redis",
+        &client.get("greeting").await.unwrap().unwrap()[..]
+    );
+
+    assert_eq!(
+        b"Redis",
+        &client.getrange("greeting", -5, -1).await.unwrap()[..]
+    );
+    assert_eq!(
+        b"Hello",
+        &client.getrange("greeting", 0, 4).await.unwrap()[..]
+    );
+    assert_eq!(
+        b"Hello Redis",
+        &client.getrange("greeting", 0, 100).await.unwrap()[..]
+    );
+    assert!(client.getrange("greeting", 5, 2).await.unwrap().is_empty());
+    assert!(client.getrange("missing", 0, -1).await.unwrap().is_empty());
+
+    // Writing past the end of a missing key pads it with zero bytes.
+    assert_eq!(5, client.setrange("blob", 2, "abc".into()).await.unwrap());
+    assert_eq!(b"\0\0abc", &client.get("blob").await.unwrap().unwrap()[..]);
+
+    // An empty value does not create the key.
+    assert_eq!(0, client.setrange("empty", 10, Bytes::new()).await.unwrap());
+    assert_eq!(None, client.get("empty").await.unwrap());
+
+    assert!(client
+        .setrange("huge", 512 * 1024 * 1024, "x".into())
+        .await
+        .is_err());
+}
+
+/// `raw_command` sends arbitrary arguments and returns the reply frame as-is,
+/// including error replies.
+#[tokio::test]
+async fn raw_command_returns_reply_frame() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    let reply = client
+        .raw_command(["set", "hello", "world"].map(Bytes::from))
+        .await
+        .unwrap();
+    assert!(matches!(reply, Frame::Simple(ref s) if s == "OK"));
+
+    let reply = client
+        .raw_command(vec![Bytes::from("get"), Bytes::from("hello")])
+        .await
+        .unwrap();
+    assert!(matches!(reply, Frame::Bulk(ref data) if data == "world"));
+
+    let reply = client
+        .raw_command([Bytes::from("incr"), Bytes::from("hello")])
+        .await
+        .unwrap();
+    assert!(matches!(reply, Frame::Error(_)));
+
+    // The connection stays usable after an error reply.
+    assert_eq!(b"world", &client.get("hello").await.unwrap().unwrap()[..]);
+}
+
+/// A pipeline returns one reply per command, in order, even when the batch is
+/// larger than the socket buffers.
+#[tokio::test]
+async fn pipeline_returns_replies_in_order() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    let value = Bytes::from(vec![b'x'; 1024]);
+    let commands = (0..2000).map(|i| {
+        vec![
+            Bytes::from("set"),
+            Bytes::from(format!("key:{}", i)),
+            value.clone(),
+        ]
+    });
+    let replies = client.pipeline(commands).await.unwrap();
+    assert_eq!(2000, replies.len());
+    assert!(replies.iter().all(|reply| *reply == "OK"));
+
+    let replies = client
+        .pipeline(vec![
+            vec![Bytes::from("incr"), Bytes::from("counter")],
+            vec![Bytes::from("incr"), Bytes::from("key:0")],
+            vec![Bytes::from("incr"), Bytes::from("counter")],
+        ])
+        .await
+        .unwrap();
+    assert!(matches!(replies[0], Frame::Integer(1)));
+    assert!(matches!(replies[1], Frame::Error(_)));
+    assert!(matches!(replies[2], Frame::Integer(2)));
+
+    assert!(client.pipeline(vec![]).await.unwrap().is_empty());
+}
+
+/// KEYS returns the matching keys and DBSIZE counts every key, whatever its
+/// type; expired keys are not included.
+#[tokio::test]
+async fn keys_and_dbsize() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    assert_eq!(0, client.dbsize().await.unwrap());
+    assert!(client.keys("*").await.unwrap().is_empty());
+
+    client.set("user:1", "a".into()).await.unwrap();
+    client.set("user:2", "b".into()).await.unwrap();
+    client.set("session", "c".into()).await.unwrap();
+    client.rpush("queue", &["job".into()]).await.unwrap();
+    client
+        .set_expires("user:3", "d".into(), Duration::from_millis(10))
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    assert_eq!(4, client.dbsize().await.unwrap());
+
+    let mut keys = client.keys("user:*").await.unwrap();
+    keys.sort();
+    assert_eq!(vec!["user:1", "user:2"], keys);
+
+    assert_eq!(vec!["queue"], client.keys("qu?ue").await.unwrap());
+    assert_eq!(4, client.keys("*").await.unwrap().len());
+}
+
+/// A burst of keys expiring together is purged over several capped cycles,
+/// and INFO reports how many keys were expired.
+#[tokio::test]
+async fn expiration_burst_is_purged_in_cycles() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    let commands = (0..1000).map(|i| {
+        ["set", &format!("key:{}", i), "value", "px", "10"]
+            .iter()
+            .map(|arg| Bytes::from(arg.to_string()))
+            .collect()
+    });
+    client.pipeline(commands).await.unwrap();
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(0, client.dbsize().await.unwrap());
+
+    let info = client.info(Some("stats")).await.unwrap();
+    let field = |name: &str| -> u64 {
+        info.lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+            .unwrap()
+            .parse()
+            .unwrap()
+    };
+
+    assert_eq!(1000, field("expired_keys"));
+    assert!(field("expire_cycles") >= 4);
+}
+
+/// MSET sets every key and discards existing expirations.
+#[tokio::test]
+async fn mset_sets_all_keys() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    client
+        .set_expires("a", "old".into(), Duration::from_millis(50))
+        .await
+        .unwrap();
+    client
+        .mset(&[("a", "1".into()), ("b", "2".into())])
+        .await
+        .unwrap();
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(Some("1".into()), client.get("a").await.unwrap());
+    assert_eq!(Some("2".into()), client.get("b").await.unwrap());
+}
+
+/// A channel whose subscribers have all left is removed on the next publish,
+/// and subscribing to it again creates a fresh channel.
+#[tokio::test]
+async fn publish_after_last_unsubscribe() {
+    let (addr, _) = start_server().await;
+    let mut publisher = Client::connect(addr).await.unwrap();
+
+    let client = Client::connect(addr).await.unwrap();
+    let mut subscriber = client.subscribe(vec!["hello".into()]).await.unwrap();
+    assert_eq!(1, publisher.publish("hello", "a".into()).await.unwrap());
+    subscriber.next_message().await.unwrap().unwrap();
+
+    subscriber.unsubscribe_all().await.unwrap();
+    assert_eq!(0, publisher.publish("hello", "b".into()).await.unwrap());
+    assert_eq!(0, publisher.publish("hello", "c".into()).await.unwrap());
+
+    subscriber.subscribe(&["hello".into()]).await.unwrap();
+    assert_eq!(1, publisher.publish("hello", "d".into()).await.unwrap());
+    assert_eq!(
+        "d",
+        subscriber.next_message().await.unwrap().unwrap().content
+    );
+}
+
+/// Local consumers of a shared subscriber share one server subscription per
+/// channel, which is dropped once the last consumer of the channel leaves.
+#[tokio::test]
+async fn shared_subscriber_fans_out() {
+    let (addr, _) = start_server().await;
+    let mut publisher = Client::connect(addr).await.unwrap();
+
+    let shared = SharedSubscriber::new(Client::connect(addr).await.unwrap());
+    let mut first = shared.subscribe("hello").await.unwrap();
+    let mut second = shared.subscribe("hello").await.unwrap();
+    let mut other = shared.subscribe("world").await.unwrap();
+
+    // All consumers share one connection, so the server sees one subscriber
+    assert_eq!(1, publisher.publish("hello", "a".into()).await.unwrap());
+    assert_eq!(1, publisher.publish("world", "b".into()).await.unwrap());
+
+    assert_eq!(b"a", &first.next_message().await.unwrap().content[..]);
+    assert_eq!(b"a", &second.next_message().await.unwrap().content[..]);
+    assert_eq!(b"b", &other.next_message().await.unwrap().content[..]);
+
+    // The channel stays subscribed while one of its consumers remains
+    drop(first);
+    assert_eq!(1, publisher.publish("hello", "c".into()).await.unwrap());
+    assert_eq!(b"c", &second.next_message().await.unwrap().content[..]);
+
+    // Dropping the last consumer unsubscribes the connection from the channel
+    drop(second);
+    tokio::time::timeout(Duration::from_secs(1), async {
+        while publisher.pubsub_numsub(&["hello".into()]).await.unwrap()[0].1 != 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(1, publisher.publish("world", "d".into()).await.unwrap());
+    assert_eq!(b"d", &other.next_message().await.unwrap().content[..]);
+}
+
+/// PUBSUB and INFO only report channels that still have subscribers, and a
+/// channel is released when its last subscriber disconnects.
+#[tokio::test]
+async fn pubsub_channels_are_released() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    let first = Client::connect(addr).await.unwrap();
+    let mut first = first
+        .subscribe(vec!["news.tech".into(), "news.sport".into()])
+        .await
+        .unwrap();
+    let second = Client::connect(addr).await.unwrap();
+    let second = second.subscribe(vec!["news.tech".into()]).await.unwrap();
+
+    let mut channels = client.pubsub_channels(None).await.unwrap();
+    channels.sort();
+    assert_eq!(vec!["news.sport", "news.tech"], channels);
+    assert_eq!(
+        vec![("news.tech".to_string(), 2), ("other".to_string(), 0)],
+        client
+            .pubsub_numsub(&["news.tech".into(), "other".into()])
+            .await
+            .unwrap()
+    );
+
+    first.unsubscribe(&["news.sport".into()]).await.unwrap();
+    assert_eq!(
+        vec!["news.tech"],
+        client.pubsub_channels(Some("news.*")).await.unwrap()
+    );
+
+    drop(first);
+    drop(second);
+
+    // The server notices the disconnects asynchronously.
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !client
+            .info(Some("stats"))
+            .await
+            .unwrap()
+            .contains("pubsub_channels:0\r\n")
+        {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+    assert!(client.pubsub_channels(None).await.unwrap().is_empty());
+}
+
+/// With TTL jitter enabled, keys set with the same TTL expire over a window
+/// instead of all at once.
+#[tokio::test]
+async fn ttl_jitter_spreads_expirations() {
     let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
     let addr = listener.local_addr().unwrap();
+    let config = server::Config::new().ttl_jitter(100);
+    tokio::spawn(async move {
+        server::run_with_config(listener, config, tokio::signal::ctrl_c()).await
+    });
 
-    let handle = tokio::spawn(async move { server::run(listener, tokio::signal::ctrl_c()).await });
+    let mut client = Client::connect(addr).await.unwrap();
 
-    (addr, handle)
+    let commands = (0..200).map(|i| {
+        ["set", &format!("key:{}", i), "value", "px", "100"]
+            .iter()
+            .map(|arg| Bytes::from(arg.to_string()))
+            .collect()
+    });
+    client.pipeline(commands).await.unwrap();
+
+    // Without jitter every key would be gone by now.
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert!(client.dbsize().await.unwrap() > 0);
+
+    // No key lives longer than twice its TTL.
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert_eq!(0, client.dbsize().await.unwrap());
+
+    let info = client.info(Some("stats")).await.unwrap();
+    assert!(info.contains("ttl_jitter_percent:100\r\n"));
+}
+
+/// When an address list is given, the client skips addresses it cannot
+/// connect to and uses the first one that accepts the connection.
+#[tokio::test]
+async fn connect_tries_every_address() {
+    let (addr, _) = start_server().await;
+
+    // Reserve a port, then close it so connecting to it is refused.
+    let closed = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let closed_addr = closed.local_addr().unwrap();
+    drop(closed);
+
+    let mut client = Client::builder()
+        .connect_timeout(Duration::from_secs(1))
+        .connect(&[closed_addr, addr][..])
+        .await
+        .unwrap();
+    assert_eq!(b"PONG", &client.ping(None).await.unwrap()[..]);
+
+    assert!(Client::builder()
+        .connect_timeout(Duration::from_secs(1))
+        .connect(closed_addr)
+        .await
+        .is_err());
+}
+
+/// Socket options are applied on both ends without affecting the connection.
+#[tokio::test]
+async fn socket_options() {
+    let config = server::Config::new()
+        .tcp_nodelay(false)
+        .tcp_keepalive(Duration::from_secs(1));
+    let handle = server::spawn("127.0.0.1:0", config).await.unwrap();
+
+    let mut client = Client::builder()
+        .nodelay(false)
+        .tcp_keepalive(Duration::from_secs(1))
+        .connect(handle.addr())
+        .await
+        .unwrap();
+    assert_eq!(b"PONG", &client.ping(None).await.unwrap()[..]);
+
+    drop(client);
+    handle.shutdown().await;
+}
+
+async fn start_server() -> (SocketAddr, JoinHandle<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let handle = tokio::spawn(async move { server::run(listener, tokio::signal::ctrl_c()).await });
+
+    (addr, handle)
+}
+
+/// Polls `INFO persistence` until the running `BGSAVE` finishes and returns the
+/// final report.
+async fn wait_for_bgsave(client: &mut Client) -> String {
+    loop {
+        let info = client.info(Some("persistence")).await.unwrap();
+        if info.contains("rdb_bgsave_in_progress:0\r\n") {
+            return info;
+        }
+
+        tokio::time::sleep(Duration::from_millis(1)).await;
+    }
+}
+
+#[tokio::test]
+async fn setbit_getbit_bitcount() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    // Setting a bit past the end zero-pads the string; offset 0 is the MSB.
+    assert_eq!(0, client.setbit("bits", 9, true).await.unwrap());
+    assert_eq!(b"\x00\x40", &client.get("bits").await.unwrap().unwrap()[..]);
+    assert_eq!(1, client.getbit("bits", 9).await.unwrap());
+    assert_eq!(0, client.getbit("bits", 8).await.unwrap());
+    assert_eq!(0, client.getbit("bits", 1000).await.unwrap());
+    assert_eq!(1, client.setbit("bits", 9, false).await.unwrap());
+    assert_eq!(0, client.getbit("missing", 0).await.unwrap());
+
+    client.set("key", "foobar foobar".into()).await.unwrap();
+    assert_eq!(53, client.bitcount("key", None).await.unwrap());
+    assert_eq!(4, client.bitcount("key", Some((0, 0))).await.unwrap());
+    assert_eq!(6, client.bitcount("key", Some((1, 1))).await.unwrap());
+    assert_eq!(4, client.bitcount("key", Some((-1, -1))).await.unwrap());
+    assert_eq!(0, client.bitcount("key", Some((5, 2))).await.unwrap());
+    assert_eq!(0, client.bitcount("missing", None).await.unwrap());
+
+    assert!(client.setbit("bits", 1 << 32, true).await.is_err());
+}
+
+#[tokio::test]
+async fn pfadd_pfcount() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    let elements: Vec<Bytes> = (0..1000).map(|i| Bytes::from(format!("a{}", i))).collect();
+    assert!(client.pfadd("hll1", &elements).await.unwrap());
+    // Adding the same elements again does not change any register.
+    assert!(!client.pfadd("hll1", &elements).await.unwrap());
+
+    let count = client.pfcount(&["hll1".into()]).await.unwrap();
+    assert!((950..=1050).contains(&count), "count = {}", count);
+
+    // The union of two overlapping sets.
+    let elements: Vec<Bytes> = (500..1500)
+        .map(|i| Bytes::from(format!("a{}", i)))
+        .collect();
+    client.pfadd("hll2", &elements).await.unwrap();
+    let count = client
+        .pfcount(&["hll1".into(), "hll2".into(), "missing".into()])
+        .await
+        .unwrap();
+    assert!((1425..=1575).contains(&count), "count = {}", count);
+
+    // Creating an empty HyperLogLog.
+    assert!(client.pfadd("empty", &[]).await.unwrap());
+    assert_eq!(0, client.pfcount(&["empty".into()]).await.unwrap());
+
+    client.set("plain", "value".into()).await.unwrap();
+    assert!(client.pfadd("plain", &["a".into()]).await.is_err());
+    assert!(client.pfcount(&["plain".into()]).await.is_err());
+}
+
+#[tokio::test]
+async fn xadd_xrange() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    let fields = [(Bytes::from("temp"), Bytes::from("20"))];
+    let first = client.xadd("sensor", None, &fields).await.unwrap();
+    let second = client.xadd("sensor", None, &fields).await.unwrap();
+    assert!(second > first);
+
+    let explicit = StreamId::new(second.ms + 1, 5);
+    assert_eq!(
+        explicit,
+        client
+            .xadd("sensor", Some(explicit), &fields)
+            .await
+            .unwrap()
+    );
+    // IDs must be strictly increasing.
+    assert!(client.xadd("sensor", Some(first), &fields).await.is_err());
+
+    let entries = client.xrange("sensor", None, None, None).await.unwrap();
+    let ids: Vec<_> = entries.iter().map(|entry| entry.id).collect();
+    assert_eq!(vec![first, second, explicit], ids);
+    assert_eq!(&fields[..], &entries[0].fields[..]);
+
+    let entries = client
+        .xrange("sensor", Some(second), None, Some(1))
+        .await
+        .unwrap();
+    assert_eq!(1, entries.len());
+    assert_eq!(second, entries[0].id);
+
+    assert!(client
+        .xrange("missing", None, None, None)
+        .await
+        .unwrap()
+        .is_empty());
+
+    // Streams and strings share the keyspace.
+    assert!(client.get("sensor").await.is_err());
+    client.set("plain", "value".into()).await.unwrap();
+    assert!(client.xadd("plain", None, &fields).await.is_err());
+}
+
+#[tokio::test]
+async fn xread_block() {
+    let (addr, _) = start_server().await;
+    let mut reader = Client::connect(addr).await.unwrap();
+    let mut writer = Client::connect(addr).await.unwrap();
+
+    let fields = [(Bytes::from("n"), Bytes::from("1"))];
+    let first = writer.xadd("events", None, &fields).await.unwrap();
+
+    // Non-blocking read of everything after 0-0.
+    let streams = [("events".to_string(), Some(StreamId::MIN))];
+    let read = reader.xread(&streams, None, None).await.unwrap();
+    assert_eq!(1, read.len());
+    assert_eq!("events", read[0].0);
+    assert_eq!(first, read[0].1[0].id);
+
+    // Nothing newer than `$`; times out with an empty result.
+    let streams = [("events".to_string(), None)];
+    let read = reader
+        .xread(&streams, None, Some(Duration::from_millis(50)))
+        .await
+        .unwrap();
+    assert!(read.is_empty());
+
+    // A blocked reader is woken by XADD on another connection.
+    let blocked = tokio::spawn(async move {
+        reader
+            .xread(&streams, None, Some(Duration::ZERO))
+            .await
+            .unwrap()
+    });
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let second = writer.xadd("events", None, &fields).await.unwrap();
+
+    let read = blocked.await.unwrap();
+    assert_eq!(1, read[0].1.len());
+    assert_eq!(second, read[0].1[0].id);
+}
+
+#[tokio::test]
+async fn list_push_pop() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    assert_eq!(
+        2,
+        client
+            .rpush("list", &["b".into(), "c".into()])
+            .await
+            .unwrap()
+    );
+    assert_eq!(3, client.lpush("list", &["a".into()]).await.unwrap());
+    assert_eq!(3, client.llen("list").await.unwrap());
+
+    assert_eq!(Some(Bytes::from("a")), client.lpop("list").await.unwrap());
+    assert_eq!(Some(Bytes::from("b")), client.lpop("list").await.unwrap());
+    assert_eq!(Some(Bytes::from("c")), client.lpop("list").await.unwrap());
+
+    // The key is removed once the list is empty.
+    assert_eq!(None, client.lpop("list").await.unwrap());
+    assert_eq!(0, client.exists(&["list".into()]).await.unwrap());
+
+    client.set("plain", "value".into()).await.unwrap();
+    assert!(client.lpush("plain", &["a".into()]).await.is_err());
+}
+
+/// Jobs are handed out in FIFO order and a blocked worker is woken by a new job.
+#[tokio::test]
+async fn job_queue() {
+    let (addr, _) = start_server().await;
+    let mut producer = Client::connect(addr).await.unwrap();
+    let mut worker = Client::connect(addr).await.unwrap();
+
+    producer.enqueue("jobs", "first".into()).await.unwrap();
+    assert_eq!(2, producer.enqueue("jobs", "second".into()).await.unwrap());
+
+    let timeout = Some(Duration::from_millis(50));
+    assert_eq!(
+        Some(Bytes::from("first")),
+        worker.dequeue("jobs", timeout).await.unwrap()
+    );
+    assert_eq!(
+        Some(Bytes::from("second")),
+        worker.dequeue("jobs", timeout).await.unwrap()
+    );
+    assert_eq!(None, worker.dequeue("jobs", timeout).await.unwrap());
+
+    let blocked = tokio::spawn(async move { worker.dequeue("jobs", None).await.unwrap() });
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    producer.enqueue("jobs", "third".into()).await.unwrap();
+
+    assert_eq!(Some(Bytes::from("third")), blocked.await.unwrap());
+    assert_eq!(0, producer.llen("jobs").await.unwrap());
+}
+
+/// Repeated GETs are served locally until another client writes the key or it expires.
+#[tokio::test]
+async fn caching_client_invalidation() {
+    struct Count(Arc<Mutex<usize>>);
+
+    impl Middleware for Count {
+        fn before_send(&self, _: &mut Frame) {
+            *self.0.lock().unwrap() += 1;
+        }
+    }
+
+    let (addr, _) = start_server().await;
+    let mut writer = Client::connect(addr).await.unwrap();
+
+    let sent = Arc::new(Mutex::new(0));
+    let client = Client::connect(addr)
+        .await
+        .unwrap()
+        .with_middleware(Count(sent.clone()));
+    let mut client = CachingClient::new(client).await.unwrap();
+    let requests = || *sent.lock().unwrap();
+
+    writer.set("config", "1".into()).await.unwrap();
+
+    let before = requests();
+    assert_eq!(Some(Bytes::from("1")), client.get("config").await.unwrap());
+    assert_eq!(Some(Bytes::from("1")), client.get("config").await.unwrap());
+    assert_eq!(None, client.get("missing").await.unwrap());
+    assert_eq!(None, client.get("missing").await.unwrap());
+    assert_eq!(before + 2, requests());
+
+    // Writes from another connection are pushed as invalidations.
+    writer.set("config", "2".into()).await.unwrap();
+    writer.set("missing", "now".into()).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    assert_eq!(Some(Bytes::from("2")), client.get("config").await.unwrap());
+    assert_eq!(
+        Some(Bytes::from("now")),
+        client.get("missing").await.unwrap()
+    );
+    assert_eq!(before + 4, requests());
+
+    // So is expiration.
+    writer
+        .set_expires("session", "s".into(), Duration::from_millis(50))
+        .await
+        .unwrap();
+    assert_eq!(Some(Bytes::from("s")), client.get("session").await.unwrap());
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert_eq!(None, client.get("session").await.unwrap());
+
+    // The connection keeps working as a plain client once tracking is off.
+    let mut client = client.into_client().await.unwrap();
+    assert_eq!(Some(Bytes::from("2")), client.get("config").await.unwrap());
+}
+
+#[tokio::test]
+async fn set_keep_ttl() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    client
+        .set_expires("kept", "a".into(), Duration::from_millis(200))
+        .await
+        .unwrap();
+    client.set_keep_ttl("kept", "b".into()).await.unwrap();
+
+    client
+        .set_expires("dropped", "a".into(), Duration::from_millis(200))
+        .await
+        .unwrap();
+    client.set("dropped", "b".into()).await.unwrap();
+
+    // KEEPTTL on a missing key creates it without an expiration.
+    client.set_keep_ttl("fresh", "c".into()).await.unwrap();
+
+    assert_eq!(b"b", &client.get("kept").await.unwrap().unwrap()[..]);
+
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    assert_eq!(None, client.get("kept").await.unwrap());
+    assert_eq!(b"b", &client.get("dropped").await.unwrap().unwrap()[..]);
+    assert_eq!(b"c", &client.get("fresh").await.unwrap().unwrap()[..]);
+}
+
+/// A key that has expired is missing even if it has not been purged yet, so
+/// KEEPTTL must not resurrect its old expiration.
+#[tokio::test]
+async fn keep_ttl_on_expired_key() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    for _ in 0..10 {
+        client
+            .set_expires("stale", "a".into(), Duration::from_millis(1))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(2)).await;
+
+        assert_eq!(None, client.get("stale").await.unwrap());
+        client.set_keep_ttl("stale", "b".into()).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+
+        assert_eq!(b"b", &client.get("stale").await.unwrap().unwrap()[..]);
+        client.del(&["stale".into()]).await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn compare_and_set() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    // A missing key never matches.
+    assert!(!client
+        .compare_and_set("lock", "free".into(), "taken".into())
+        .await
+        .unwrap());
+    assert_eq!(None, client.get("lock").await.unwrap());
+
+    client.set("lock", "free".into()).await.unwrap();
+    assert!(client
+        .compare_and_set("lock", "free".into(), "taken".into())
+        .await
+        .unwrap());
+    assert!(!client
+        .compare_and_set("lock", "free".into(), "again".into())
+        .await
+        .unwrap());
+    assert_eq!(b"taken", &client.get("lock").await.unwrap().unwrap()[..]);
+}
+
+/// Commands exceeding the per-connection rate limit are rejected with `BUSY`
+/// once the burst is used up, while other connections are not affected.
+#[tokio::test]
+async fn rate_limit_rejects() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let config = server::Config::new().rate_limit(server::RateLimit::new(1, 2).reject());
+    tokio::spawn(async move {
+        server::run_with_config(listener, config, tokio::signal::ctrl_c()).await
+    });
+
+    let mut client = Client::connect(addr).await.unwrap();
+    client.ping(None).await.unwrap();
+    client.ping(None).await.unwrap();
+
+    let err = client.ping(None).await.unwrap_err();
+    let err = err.downcast_ref::<ServerError>().unwrap();
+    assert_eq!(Some(ErrorKind::Busy), err.kind());
+    assert!(err.is_retryable());
+
+    let mut other = Client::connect(addr).await.unwrap();
+    other.ping(None).await.unwrap();
+}
+
+/// Set algebra commands combine the sets stored at several keys, treating
+/// missing keys as empty sets.
+#[tokio::test]
+async fn set_algebra() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    let members = |names: &[&str]| -> Vec<Bytes> {
+        names
+            .iter()
+            .map(|name| Bytes::from(name.to_string()))
+            .collect()
+    };
+    let keys =
+        |names: &[&str]| -> Vec<String> { names.iter().map(|name| name.to_string()).collect() };
+
+    assert_eq!(
+        3,
+        client.sadd("a", &members(&["x", "y", "z"])).await.unwrap()
+    );
+    assert_eq!(1, client.sadd("a", &members(&["x", "w"])).await.unwrap());
+    client.sadd("b", &members(&["y", "z", "v"])).await.unwrap();
+
+    assert_eq!(
+        members(&["w", "x", "y", "z"]),
+        client.smembers("a").await.unwrap()
+    );
+    assert_eq!(
+        members(&["y", "z"]),
+        client.sinter(&keys(&["a", "b"])).await.unwrap()
+    );
+    assert_eq!(
+        members(&["v", "w", "x", "y", "z"]),
+        client.sunion(&keys(&["a", "b", "missing"])).await.unwrap()
+    );
+    assert_eq!(
+        members(&["w", "x"]),
+        client.sdiff(&keys(&["a", "b"])).await.unwrap()
+    );
+    assert!(client
+        .sinter(&keys(&["a", "missing"]))
+        .await
+        .unwrap()
+        .is_empty());
+
+    // STORE variants replace the destination, whatever it held before.
+    client.set("dest", "string".into()).await.unwrap();
+    assert_eq!(
+        2,
+        client
+            .sinterstore("dest", &keys(&["a", "b"]))
+            .await
+            .unwrap()
+    );
+    assert_eq!(members(&["y", "z"]), client.smembers("dest").await.unwrap());
+    assert_eq!(
+        5,
+        client
+            .sunionstore("dest", &keys(&["a", "b"]))
+            .await
+            .unwrap()
+    );
+    assert_eq!(
+        2,
+        client.sdiffstore("dest", &keys(&["a", "b"])).await.unwrap()
+    );
+    assert_eq!(members(&["w", "x"]), client.smembers("dest").await.unwrap());
+
+    // An empty result deletes the destination.
+    assert_eq!(
+        0,
+        client.sdiffstore("dest", &keys(&["a", "a"])).await.unwrap()
+    );
+    assert_eq!(0, client.exists(&keys(&["dest"])).await.unwrap());
+
+    client.set("str", "value".into()).await.unwrap();
+    let err = client.sunion(&keys(&["a", "str"])).await.unwrap_err();
+    let err = err.downcast_ref::<ServerError>().unwrap();
+    assert_eq!(Some(ErrorKind::WrongType), err.kind());
+}
+
+/// Error replies are returned as `ServerError`s carrying the error kind.
+#[tokio::test]
+async fn server_errors_are_typed() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    client.rpush("list", &[Bytes::from("a")]).await.unwrap();
+
+    let err = client.get("list").await.unwrap_err();
+    let err = err.downcast_ref::<ServerError>().unwrap();
+    assert_eq!(Some(ErrorKind::WrongType), err.kind());
+    assert_eq!("WRONGTYPE", err.code());
+    assert_eq!(
+        "Operation against a key holding the wrong kind of value",
+        err.message()
+    );
+    assert!(!err.is_retryable());
+
+    // The full message is kept when the error is displayed.
+    assert_eq!(
+        "WRONGTYPE Operation against a key holding the wrong kind of value",
+        err.to_string()
+    );
+}
+
+/// Without `reject`, commands exceeding the rate limit are delayed instead.
+#[tokio::test]
+async fn rate_limit_delays() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let config = server::Config::new().rate_limit(server::RateLimit::new(20, 1));
+    tokio::spawn(async move {
+        server::run_with_config(listener, config, tokio::signal::ctrl_c()).await
+    });
+
+    let mut client = Client::connect(addr).await.unwrap();
+
+    let start = std::time::Instant::now();
+    for _ in 0..5 {
+        client.ping(None).await.unwrap();
+    }
+
+    // The first command uses the burst, the other four wait 50ms each.
+    assert!(start.elapsed() >= Duration::from_millis(190));
+}
+
+/// The output limit only applies to messages queued for subscribers: a reply
+/// larger than the limit is still sent, since the server waits for it to be
+/// written.
+#[tokio::test]
+async fn output_limit_does_not_apply_to_replies() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let config = server::Config::new().output_limit(Some(1024));
+    tokio::spawn(async move {
+        server::run_with_config(listener, config, tokio::signal::ctrl_c()).await
+    });
+
+    let mut client = Client::connect(addr).await.unwrap();
+    let value = Bytes::from(vec![b'x'; 1024 * 1024]);
+    client.set("big", value.clone()).await.unwrap();
+    assert_eq!(Some(value), client.get("big").await.unwrap());
+}
+
+/// A subscriber that stops reading is disconnected once its pending output
+/// exceeds the configured limit, instead of buffering without bound.
+#[tokio::test]
+async fn output_limit_disconnects_slow_subscriber() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let config = server::Config::new().output_limit(Some(64 * 1024));
+    tokio::spawn(async move {
+        server::run_with_config(listener, config, tokio::signal::ctrl_c()).await
+    });
+
+    // Subscribe over a raw socket that is never read from.
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(b"*2\r\n$9\r\nSUBSCRIBE\r\n$5\r\nhello\r\n")
+        .await
+        .unwrap();
+
+    let mut client = Client::connect(addr).await.unwrap();
+    while client.publish("hello", "".into()).await.unwrap() == 0 {
+        tokio::task::yield_now().await;
+    }
+
+    // Fill the socket buffers, then the server side output buffer.
+    let message = Bytes::from(vec![b'x'; 1024 * 1024]);
+    let mut subscribers = 1;
+    for _ in 0..256 {
+        subscribers = client.publish("hello", message.clone()).await.unwrap();
+        if subscribers == 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(1)).await;
+    }
+
+    assert_eq!(0, subscribers);
+    drop(stream);
+}
+
+/// Buffers of closed connections are returned to the pool and reused by new
+/// connections, as reported by `INFO`.
+#[tokio::test]
+async fn info_reports_buffer_reuse() {
+    let (addr, _) = start_server().await;
+
+    let mut first = Client::connect(addr).await.unwrap();
+    first.ping(None).await.unwrap();
+    drop(first);
+
+    let mut second = Client::connect(addr).await.unwrap();
+
+    // Wait for the server to notice the first connection was closed.
+    while !second
+        .info(Some("clients"))
+        .await
+        .unwrap()
+        .contains("connected_clients:1\r\n")
+    {
+        tokio::time::sleep(Duration::from_millis(1)).await;
+    }
+
+    // Whether the second or the third connection picked up the buffers of the
+    // first one, exactly one pair of buffers has been reused.
+    let mut third = Client::connect(addr).await.unwrap();
+    let info = third.info(None).await.unwrap();
+
+    assert!(info.contains("connected_clients:2\r\n"), "{}", info);
+    assert!(info.contains("buffer_pool_allocated:4\r\n"), "{}", info);
+    assert!(info.contains("buffer_pool_reused:2\r\n"), "{}", info);
+}
+
+/// Hash fields hold counters that `HINCRBY` and `HINCRBYFLOAT` update in
+/// place, starting from zero for missing fields.
+#[tokio::test]
+async fn hash_counters() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    assert_eq!(
+        2,
+        client
+            .hset("h", &[("name", "mini".into()), ("hits", "10".into())])
+            .await
+            .unwrap()
+    );
+    assert_eq!(
+        0,
+        client.hset("h", &[("name", "redis".into())]).await.unwrap()
+    );
+    assert_eq!(
+        Some("redis".into()),
+        client.hget("h", "name").await.unwrap()
+    );
+    assert_eq!(None, client.hget("h", "missing").await.unwrap());
+
+    assert_eq!(15, client.hincrby("h", "hits", 5).await.unwrap());
+    assert_eq!(3, client.hincrby("h", "new", 3).await.unwrap());
+    assert_eq!(2.5, client.hincrbyfloat("h", "ratio", 2.5).await.unwrap());
+    assert_eq!(17.5, client.hincrbyfloat("h", "hits", 2.5).await.unwrap());
+
+    let err = client.hincrby("h", "name", 1).await.unwrap_err();
+    assert_eq!(
+        "ERR hash value is not an integer",
+        err.downcast_ref::<ServerError>().unwrap().to_string()
+    );
+
+    client.set("str", "value".into()).await.unwrap();
+    let err = client.hincrby("str", "field", 1).await.unwrap_err();
+    assert_eq!(
+        Some(ErrorKind::WrongType),
+        err.downcast_ref::<ServerError>().unwrap().kind()
+    );
+}
+
+/// `BGSAVE` writes the snapshot in the background and reports its progress
+/// and result through `INFO persistence`.
+#[tokio::test]
+async fn bgsave_writes_snapshot() {
+    let path = std::env::temp_dir().join(format!("mini-redis-bgsave-{}", std::process::id()));
+    let handle = server::spawn("127.0.0.1:0", server::Config::new().snapshot_path(&path))
+        .await
+        .unwrap();
+    let mut client = Client::connect(handle.addr()).await.unwrap();
+
+    let info = client.info(Some("persistence")).await.unwrap();
+    assert!(info.contains("rdb_saves:0\r\n"), "{}", info);
+    assert!(info.contains("rdb_last_bgsave_status:ok\r\n"), "{}", info);
+
+    client.set("string", "value".into()).await.unwrap();
+    client
+        .rpush("list", &["a".into(), "b".into()])
+        .await
+        .unwrap();
+    client
+        .hset("hash", &[("field", "value".into())])
+        .await
+        .unwrap();
+
+    client.bgsave().await.unwrap();
+
+    let info = wait_for_bgsave(&mut client).await;
+    assert!(info.contains("rdb_saves:1\r\n"), "{}", info);
+    assert!(info.contains("rdb_bgsave_keys_saved:3\r\n"), "{}", info);
+    assert!(info.contains("rdb_last_bgsave_status:ok\r\n"), "{}", info);
+    assert!(std::fs::metadata(&path).unwrap().len() > 0);
+
+    std::fs::remove_file(&path).unwrap();
+    handle.shutdown().await;
+}
+
+/// A server configured with a snapshot path loads the snapshot written by an
+/// earlier `BGSAVE` at startup.
+#[tokio::test]
+async fn snapshot_is_loaded_at_startup() {
+    let path = std::env::temp_dir().join(format!("mini-redis-load-{}", std::process::id()));
+    let config = || server::Config::new().snapshot_path(&path);
+
+    let handle = server::spawn("127.0.0.1:0", config()).await.unwrap();
+    let mut client = Client::connect(handle.addr()).await.unwrap();
+
+    client.set("string", "value".into()).await.unwrap();
+    client
+        .set_expires("expiring", "value".into(), Duration::from_secs(3600))
+        .await
+        .unwrap();
+    client.sadd("set", &["a".into(), "b".into()]).await.unwrap();
+    client
+        .hset("hash", &[("field", "value".into())])
+        .await
+        .unwrap();
+    client.bgsave().await.unwrap();
+    wait_for_bgsave(&mut client).await;
+    handle.shutdown().await;
+
+    let summary = snapshot::check(&path).unwrap();
+    assert_eq!(summary.version, snapshot::VERSION);
+    assert_eq!(summary.keys, 4);
+
+    let handle = server::spawn("127.0.0.1:0", config()).await.unwrap();
+    let mut client = Client::connect(handle.addr()).await.unwrap();
+
+    assert_eq!(client.get("string").await.unwrap().unwrap(), "value");
+    assert_eq!(client.get("expiring").await.unwrap().unwrap(), "value");
+    assert_eq!(client.smembers("set").await.unwrap(), vec!["a", "b"]);
+    assert_eq!(
+        client.hget("hash", "field").await.unwrap().unwrap(),
+        "value"
+    );
+
+    std::fs::remove_file(&path).unwrap();
+    handle.shutdown().await;
+}
+
+/// `snapshot::check` and server startup reject damaged snapshot files with an
+/// error describing what is wrong.
+#[tokio::test]
+async fn damaged_snapshots_are_rejected() {
+    let path = std::env::temp_dir().join(format!("mini-redis-damaged-{}", std::process::id()));
+
+    let handle = server::spawn("127.0.0.1:0", server::Config::new().snapshot_path(&path))
+        .await
+        .unwrap();
+    let mut client = Client::connect(handle.addr()).await.unwrap();
+    client.set("foo", "bar".into()).await.unwrap();
+    client.bgsave().await.unwrap();
+    wait_for_bgsave(&mut client).await;
+    handle.shutdown().await;
+
+    let valid = std::fs::read(&path).unwrap();
+
+    let check = |data: &[u8]| {
+        std::fs::write(&path, data).unwrap();
+        snapshot::check(&path).unwrap_err()
+    };
+
+    let err = check(b"not a snapshot");
+    assert!(matches!(err, SnapshotError::NotASnapshot), "{}", err);
+
+    let mut newer = valid.clone();
+    newer[8..12].copy_from_slice(&(snapshot::VERSION + 1).to_le_bytes());
+    let err = check(&newer);
+    assert!(
+        matches!(err, SnapshotError::UnsupportedVersion { version } if version == snapshot::VERSION + 1),
+        "{}",
+        err
+    );
+
+    let err = check(&valid[..valid.len() - 6]);
+    assert!(matches!(err, SnapshotError::Truncated { .. }), "{}", err);
+    assert!(err.to_string().contains("truncated"), "{}", err);
+
+    // Flip a bit in the value "bar": the structure is intact but the checksum is not.
+    let mut flipped = valid.clone();
+    let pos = valid.windows(3).position(|w| w == b"bar").unwrap();
+    flipped[pos] ^= 1;
+    let err = check(&flipped);
+    assert!(
+        matches!(err, SnapshotError::ChecksumMismatch { .. }),
+        "{}",
+        err
+    );
+
+    // The server refuses to start from a damaged snapshot.
+    let err = server::spawn("127.0.0.1:0", server::Config::new().snapshot_path(&path))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("checksum mismatch"), "{}", err);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+/// Reads are spread over the replicas round-robin, writes go to the primary,
+/// and `read_from_primary` sends reads to the primary while it is held.
+#[tokio::test]
+async fn reads_are_routed_to_replicas() {
+    let mut servers = vec![];
+    for name in ["primary", "replica-1", "replica-2"].iter() {
+        let handle = server::spawn("127.0.0.1:0", server::Config::new())
+            .await
+            .unwrap();
+        handle
+            .db()
+            .set("name".to_string(), Bytes::from(*name), None, false);
+        servers.push(handle);
+    }
+
+    let mut client = Client::connect_with_replicas(
+        servers[0].addr(),
+        vec![servers[1].addr(), servers[2].addr()],
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(Some("replica-1".into()), client.get("name").await.unwrap());
+    assert_eq!(Some("replica-2".into()), client.get("name").await.unwrap());
+    assert_eq!(Some("replica-1".into()), client.get("name").await.unwrap());
+
+    client.set("written", "yes".into()).await.unwrap();
+    assert!(servers[0].db().get("written").unwrap().is_some());
+    assert!(servers[1].db().get("written").unwrap().is_none());
+
+    {
+        let mut primary = client.read_from_primary();
+        assert_eq!(Some("primary".into()), primary.get("name").await.unwrap());
+        assert_eq!(Some("yes".into()), primary.get("written").await.unwrap());
+    }
+
+    assert_eq!(Some("replica-2".into()), client.get("name").await.unwrap());
+}
+
+/// DUMP serializes a value of any type that RESTORE recreates, refusing to
+/// overwrite an existing key unless asked to and rejecting damaged payloads.
+#[tokio::test]
+async fn dump_and_restore() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    assert_eq!(None, client.dump("missing").await.unwrap());
+
+    client.set("greeting", "hello".into()).await.unwrap();
+    client
+        .rpush("list", &["a".into(), "b".into()])
+        .await
+        .unwrap();
+    client
+        .hset("hash", &[("field", "value".into())])
+        .await
+        .unwrap();
+
+    let greeting = client.dump("greeting").await.unwrap().unwrap();
+    let list = client.dump("list").await.unwrap().unwrap();
+    let hash = client.dump("hash").await.unwrap().unwrap();
+
+    client
+        .restore("greeting-copy", None, greeting.clone(), false)
+        .await
+        .unwrap();
+    client
+        .restore("list-copy", None, list, false)
+        .await
+        .unwrap();
+    client
+        .restore("hash-copy", Some(Duration::from_secs(60)), hash, false)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        Some("hello".into()),
+        client.get("greeting-copy").await.unwrap()
+    );
+    assert_eq!(Some("a".into()), client.lpop("list-copy").await.unwrap());
+    assert_eq!(Some("b".into()), client.lpop("list-copy").await.unwrap());
+    assert_eq!(
+        Some("value".into()),
+        client.hget("hash-copy", "field").await.unwrap()
+    );
+
+    // The target already exists
+    let err = client
+        .restore("list", None, greeting.clone(), false)
+        .await
+        .unwrap_err();
+    let err = err.downcast_ref::<ServerError>().unwrap();
+    assert_eq!(Some(ErrorKind::BusyKey), err.kind());
+
+    client
+        .restore("list", None, greeting.clone(), true)
+        .await
+        .unwrap();
+    assert_eq!(Some("hello".into()), client.get("list").await.unwrap());
+
+    // A flipped bit fails the checksum
+    let mut damaged = greeting.to_vec();
+    damaged[2] ^= 1;
+    let err = client
+        .restore("damaged", None, damaged.into(), false)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("checksum"), "{}", err);
+    assert_eq!(None, client.get("damaged").await.unwrap());
+}
+
+/// `migrate` moves a key to another server and removes it from the source.
+#[tokio::test]
+async fn migrate_moves_key() {
+    let (source_addr, _) = start_server().await;
+    let (target_addr, _) = start_server().await;
+    let mut source = Client::connect(source_addr).await.unwrap();
+    let mut target = Client::connect(target_addr).await.unwrap();
+
+    source
+        .sadd("members", &["x".into(), "y".into()])
+        .await
+        .unwrap();
+    target.set("members", "stale".into()).await.unwrap();
+
+    assert!(source.migrate("members", &mut target).await.unwrap());
+    assert!(!source.migrate("missing", &mut target).await.unwrap());
+
+    assert_eq!(0, source.exists(&["members".into()]).await.unwrap());
+    assert_eq!(
+        vec![Bytes::from("x"), Bytes::from("y")],
+        target.smembers("members").await.unwrap()
+    );
 }
@@ -0,0 +1,179 @@
+use mini_redis::{
+    clients::{Client, ClusterClient},
+    cluster::{key_slot, SlotMap, SlotOwner},
+    server::{self, Config},
+};
+use std::net::SocketAddr;
+use tokio::net::TcpListener;
+
+/// Slots are computed with CRC16 like Redis Cluster, honouring hash tags.
+#[test]
+fn key_slots() {
+    assert_eq!(12182, key_slot(b"foo"));
+    assert_eq!(5061, key_slot(b"bar"));
+    assert_eq!(key_slot(b"user1000"), key_slot(b"{user1000}.following"));
+    // An empty hash tag is ignored.
+    assert_eq!(key_slot(b"{}foo"), key_slot(b"{}foo"));
+    assert_ne!(key_slot(b"{}foo"), key_slot(b"foo"));
+}
+
+/// A node replies `MOVED` for keys it does not own and `CROSSSLOT` for
+/// requests spanning several slots.
+#[tokio::test]
+async fn node_redirects_foreign_keys() {
+    let (a, b) = start_cluster().await;
+
+    let mut client = Client::connect(a).await.unwrap();
+    client.set("bar", "1".into()).await.unwrap();
+
+    let err = client.get("foo").await.unwrap_err();
+    assert_eq!(format!("MOVED 12182 {}", b), err.to_string());
+
+    let keys = vec!["foo".to_string(), "bar".to_string()];
+    let err = client.del(&keys).await.unwrap_err();
+    assert!(err.to_string().starts_with("CROSSSLOT"));
+
+    // Commands without keys are not affected.
+    client.ping(None).await.unwrap();
+}
+
+/// The cluster client follows redirections, even when its initial slot map
+/// does not match the servers.
+#[tokio::test]
+async fn cluster_client_routes_keys() {
+    let (a, b) = start_cluster().await;
+
+    // Deliberately list the nodes in the wrong order.
+    let mut client = ClusterClient::new(vec![b, a]);
+
+    client.set("foo", "1".into()).await.unwrap();
+    client.set("bar", "2".into()).await.unwrap();
+    assert_eq!(b.to_string(), client.node_for("foo"));
+    assert_eq!(a.to_string(), client.node_for("bar"));
+
+    assert_eq!(b"1", &client.get("foo").await.unwrap().unwrap()[..]);
+    assert_eq!(2, client.incr("foo").await.unwrap());
+
+    // Each key is stored on the node owning its slot.
+    let mut node_b = Client::connect(b).await.unwrap();
+    assert_eq!(b"2", &node_b.get("foo").await.unwrap().unwrap()[..]);
+
+    let keys = vec!["foo".to_string(), "bar".to_string(), "baz".to_string()];
+    assert_eq!(2, client.exists(&keys).await.unwrap());
+    assert_eq!(2, client.del(&keys).await.unwrap());
+    assert_eq!(None, client.get("bar").await.unwrap());
+}
+
+/// While a slot is migrating, the source node answers `ASK` for keys it no
+/// longer holds and the target only serves them right after `ASKING`. The
+/// cluster client follows the `ASK` without updating its slot map.
+#[tokio::test]
+async fn cluster_client_follows_ask() {
+    let slot = key_slot(b"foo");
+
+    let listener_a = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let listener_b = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let a = listener_a.local_addr().unwrap();
+    let b = listener_b.local_addr().unwrap();
+
+    let config_a = Config::new().slot_check(move |s: u16| {
+        if s == slot {
+            SlotOwner::Migrating(b.to_string())
+        } else {
+            SlotOwner::Local
+        }
+    });
+    let config_b = Config::new().slot_check(move |s: u16| {
+        if s == slot {
+            SlotOwner::Importing(a.to_string())
+        } else {
+            SlotOwner::Node(a.to_string())
+        }
+    });
+
+    tokio::spawn(server::run_with_config(
+        listener_a,
+        config_a,
+        std::future::pending::<()>(),
+    ));
+    tokio::spawn(server::run_with_config(
+        listener_b,
+        config_b,
+        std::future::pending::<()>(),
+    ));
+
+    let mut client = ClusterClient::new(vec![a]);
+    client.set("foo", "1".into()).await.unwrap();
+    assert_eq!(b"1", &client.get("foo").await.unwrap().unwrap()[..]);
+    assert_eq!(a.to_string(), client.node_for("foo"));
+
+    // The key was written to the importing node.
+    let mut node_a = Client::connect(a).await.unwrap();
+    let err = node_a.get("foo").await.unwrap_err();
+    assert_eq!(format!("ASK {} {}", slot, b), err.to_string());
+
+    // Without `ASKING` the importing node redirects to the current owner, and
+    // `ASKING` only applies to the next command.
+    let mut node_b = Client::connect(b).await.unwrap();
+    let err = node_b.get("foo").await.unwrap_err();
+    assert_eq!(format!("MOVED {} {}", slot, a), err.to_string());
+
+    node_b.asking().await.unwrap();
+    assert_eq!(b"1", &node_b.get("foo").await.unwrap().unwrap()[..]);
+    assert!(node_b.get("foo").await.is_err());
+}
+
+/// Slots that no node serves are reported as `CLUSTERDOWN`.
+#[tokio::test]
+async fn unassigned_slot() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let config = Config::new().slot_check(SlotMap::new().local(0..=8191));
+    tokio::spawn(server::run_with_config(
+        listener,
+        config,
+        std::future::pending::<()>(),
+    ));
+
+    let mut client = Client::connect(addr).await.unwrap();
+    client.set("bar", "1".into()).await.unwrap();
+
+    let err = client.get("foo").await.unwrap_err();
+    assert!(err.to_string().starts_with("CLUSTERDOWN"));
+}
+
+/// Starts two nodes: the first owns the slots below 8192, the second owns the
+/// rest.
+async fn start_cluster() -> (SocketAddr, SocketAddr) {
+    let listener_a = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let listener_b = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let a = listener_a.local_addr().unwrap();
+    let b = listener_b.local_addr().unwrap();
+
+    let config_a = Config::new().slot_check(
+        SlotMap::new()
+            .local(0..=8191)
+            .node(8192..=16383, b.to_string()),
+    );
+    let config_b = Config::new().slot_check(move |slot: u16| {
+        if slot < 8192 {
+            SlotOwner::Node(a.to_string())
+        } else {
+            SlotOwner::Local
+        }
+    });
+
+    tokio::spawn(server::run_with_config(
+        listener_a,
+        config_a,
+        std::future::pending::<()>(),
+    ));
+    tokio::spawn(server::run_with_config(
+        listener_b,
+        config_b,
+        std::future::pending::<()>(),
+    ));
+
+    (a, b)
+}
@@ -0,0 +1,56 @@
+use bytes::BytesMut;
+use mini_redis::codec::RespCodec;
+use mini_redis::frame::Limits;
+use mini_redis::{server, Frame};
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio_stream::StreamExt;
+use tokio_util::codec::{Decoder, Encoder, FramedRead};
+
+/// A frame is only returned once all of its bytes have been buffered, and
+/// the decoded bytes are removed from the buffer.
+#[test]
+fn decode_partial_frame() {
+    let mut codec = RespCodec::new();
+    let mut buf = BytesMut::from(&b"*2\r\n$3\r\nGET\r\n$5\r\nhel"[..]);
+
+    assert!(codec.decode(&mut buf).unwrap().is_none());
+
+    buf.extend_from_slice(b"lo\r\n+OK\r\n");
+
+    let frame = codec.decode(&mut buf).unwrap().unwrap();
+    assert_eq!("GET hello", frame.to_string());
+    assert_eq!(b"+OK\r\n", &buf[..]);
+}
+
+/// Frames exceeding the configured limits are rejected before the payload
+/// has been received.
+#[test]
+fn decode_rejects_oversized_frame() {
+    let mut codec = RespCodec::with_limits(Limits::new().max_bulk_len(4));
+    let mut buf = BytesMut::from(&b"$5\r\n"[..]);
+
+    assert!(codec.decode(&mut buf).is_err());
+}
+
+/// The codec can talk to the server over a `FramedRead` transport.
+#[tokio::test]
+async fn framed_round_trip() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move { server::run(listener, tokio::signal::ctrl_c()).await });
+
+    let (read, mut write) = TcpStream::connect(addr).await.unwrap().into_split();
+    let mut codec = RespCodec::new();
+    let mut buf = BytesMut::new();
+
+    let mut ping = Frame::array();
+    ping.push_bulk("PING".into());
+    codec.encode(ping, &mut buf).unwrap();
+    write.write_all(&buf).await.unwrap();
+
+    let mut frames = FramedRead::new(read, codec);
+    let pong = frames.next().await.unwrap().unwrap();
+    assert_eq!("PONG", pong.to_string());
+}
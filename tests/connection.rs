@@ -0,0 +1,126 @@
+use mini_redis::{Connection, Frame};
+
+use bytes::Bytes;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+
+/// The read buffer grows when frames regularly exceed its capacity and shrinks
+/// back once the connection only handles small frames again.
+#[tokio::test]
+async fn read_buffer_adapts_to_frame_size() {
+    let (mut tx, mut rx) = connection_pair().await;
+
+    let large = Frame::Bulk(Bytes::from(vec![b'x'; 64 * 1024]));
+    let small = Frame::Simple("OK".into());
+
+    tokio::spawn(async move {
+        for _ in 0..8 {
+            tx.write_frame(&large).await.unwrap();
+        }
+        for _ in 0..1024 {
+            tx.write_frame(&small).await.unwrap();
+        }
+    });
+
+    for _ in 0..8 {
+        rx.read_frame().await.unwrap().unwrap();
+    }
+
+    let stats = rx.buffer_stats();
+    assert!(stats.grows > 0);
+    assert!(stats.read_capacity > 4 * 1024);
+
+    for _ in 0..1024 {
+        rx.read_frame().await.unwrap().unwrap();
+    }
+
+    let stats = rx.buffer_stats();
+    assert!(stats.shrinks > 0);
+    assert_eq!(stats.read_capacity, 4 * 1024);
+}
+
+/// A connection created with a custom capacity releases the memory used by a
+/// very large frame once the frame has been handled.
+#[tokio::test]
+async fn oversized_read_buffer_is_released() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (client, server) = tokio::join!(TcpStream::connect(addr), listener.accept());
+
+    let mut tx = Connection::new(client.unwrap());
+    let mut rx = Connection::new_with_capacity(server.unwrap().0, 1024);
+    assert_eq!(1024, rx.buffer_stats().read_capacity);
+
+    let huge = Frame::Bulk(Bytes::from(vec![b'x'; 2 * 1024 * 1024]));
+    tokio::spawn(async move { tx.write_frame(&huge).await.unwrap() });
+
+    let frame = rx.read_frame().await.unwrap().unwrap();
+    assert_eq!(2 * 1024 * 1024, frame.as_bulk().unwrap().len());
+    assert_eq!(1, rx.buffer_stats().resets);
+}
+
+/// Nested arrays are encoded and can be read back by the peer.
+#[tokio::test]
+async fn write_nested_array() {
+    let (mut tx, mut rx) = connection_pair().await;
+
+    let mut inner = Frame::array();
+    inner.push_bulk(Bytes::from_static(b"world"));
+    inner.push_int(2);
+
+    let frame = Frame::Array(vec![Frame::bulk("hello"), inner, Frame::Null]);
+    tx.write_frame(&frame).await.unwrap();
+
+    let parts = rx
+        .read_frame()
+        .await
+        .unwrap()
+        .unwrap()
+        .try_into_vec()
+        .unwrap();
+    assert_eq!(3, parts.len());
+    assert!(parts[0] == "hello");
+
+    let inner = parts[1].as_array().unwrap();
+    assert!(inner[0] == "world");
+    assert_eq!(Some(2), inner[1].as_int());
+    assert!(matches!(parts[2], Frame::Null));
+}
+
+/// Cancelling `write_frame` while the peer is not reading keeps the unsent
+/// bytes in the write buffer, and the next write sends them before its own
+/// frame, so the peer still receives both frames intact.
+#[tokio::test]
+async fn cancelled_write_frame_keeps_unsent_bytes() {
+    let (mut tx, mut rx) = connection_pair().await;
+
+    let huge = Frame::Bulk(Bytes::from(vec![b'x'; 32 * 1024 * 1024]));
+    let res = tokio::time::timeout(Duration::from_millis(50), tx.write_frame(&huge)).await;
+    assert!(res.is_err());
+    assert!(tx.pending_bytes() > 0);
+
+    let reader = tokio::spawn(async move {
+        let first = rx.read_frame().await.unwrap().unwrap();
+        let second = rx.read_frame().await.unwrap().unwrap();
+        (first, second)
+    });
+
+    tx.write_frame(&Frame::bulk("after")).await.unwrap();
+    assert_eq!(0, tx.pending_bytes());
+
+    let (first, second) = reader.await.unwrap();
+    assert_eq!(32 * 1024 * 1024, first.as_bulk().unwrap().len());
+    assert!(second == "after");
+}
+
+async fn connection_pair() -> (Connection, Connection) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let (client, server) = tokio::join!(TcpStream::connect(addr), listener.accept());
+
+    (
+        Connection::new(client.unwrap()),
+        Connection::new(server.unwrap().0),
+    )
+}
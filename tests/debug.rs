@@ -0,0 +1,94 @@
+#![cfg(feature = "debug")]
+
+use mini_redis::{clients::Client, server};
+
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// `DEBUG OBJECT` reports the type, size and remaining TTL of a key.
+#[tokio::test]
+async fn debug_object() {
+    let addr = start_server().await;
+
+    let mut client = Client::connect(addr).await.unwrap();
+    client.set("hello", "world".into()).await.unwrap();
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(b"*3\r\n$5\r\nDEBUG\r\n$6\r\nOBJECT\r\n$5\r\nhello\r\n")
+        .await
+        .unwrap();
+
+    let mut response = [0; 29];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(b"+type:string size:5 pttl:-1\r\n", &response);
+}
+
+/// `DEBUG SLEEP` delays its own connection without blocking others.
+#[tokio::test]
+async fn debug_sleep() {
+    let addr = start_server().await;
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let start = Instant::now();
+    stream
+        .write_all(b"*3\r\n$5\r\nDEBUG\r\n$5\r\nSLEEP\r\n$3\r\n0.2\r\n")
+        .await
+        .unwrap();
+
+    // Other connections are served while the first one sleeps.
+    let mut client = Client::connect(addr).await.unwrap();
+    client.ping(None).await.unwrap();
+    assert!(start.elapsed() < Duration::from_millis(200));
+
+    let mut response = [0; 5];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(b"+OK\r\n", &response);
+    assert!(start.elapsed() >= Duration::from_millis(200));
+}
+
+/// Commands slower than the threshold are recorded in the `command` latency
+/// event, blocking commands are not.
+#[tokio::test]
+async fn latency_monitor_records_slow_commands() {
+    let config = server::Config::new().latency_monitor_threshold(Duration::from_millis(20));
+    let handle = server::spawn("127.0.0.1:0", config).await.unwrap();
+
+    let mut client = Client::connect(handle.addr()).await.unwrap();
+    client.ping(None).await.unwrap();
+    client
+        .blpop(&["queue".to_string()], Some(Duration::from_millis(100)))
+        .await
+        .unwrap();
+    assert!(client.latency_history("command").await.unwrap().is_empty());
+
+    let mut stream = TcpStream::connect(handle.addr()).await.unwrap();
+    stream
+        .write_all(b"*3\r\n$5\r\nDEBUG\r\n$5\r\nSLEEP\r\n$3\r\n0.1\r\n")
+        .await
+        .unwrap();
+    let mut response = [0; 5];
+    stream.read_exact(&mut response).await.unwrap();
+
+    let history = client.latency_history("command").await.unwrap();
+    assert_eq!(1, history.len());
+    assert!(history[0].1 >= Duration::from_millis(100));
+
+    let info = client.info(Some("latency")).await.unwrap();
+    assert!(info.contains("latency_monitor_threshold:20\r\n"));
+    assert!(info.contains("\r\ncommand:time="));
+
+    assert_eq!(1, client.latency_reset(&[]).await.unwrap());
+    assert!(client.latency_history("command").await.unwrap().is_empty());
+}
+
+async fn start_server() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move { server::run(listener, tokio::signal::ctrl_c()).await });
+
+    addr
+}
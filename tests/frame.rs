@@ -0,0 +1,209 @@
+use bytes::Bytes;
+use mini_redis::frame::ErrorKind;
+use mini_redis::Frame;
+
+/// `Frame::parse_bytes` returns string and bulk contents that point into the
+/// source buffer instead of copying them.
+#[test]
+fn parse_bytes_does_not_copy() {
+    let src = Bytes::from_static(b"*2\r\n+OK\r\n$5\r\nhello\r\n");
+    let range = src.as_ptr_range();
+
+    let parts = match Frame::parse_bytes(&src).unwrap() {
+        Frame::Array(parts) => parts,
+        frame => panic!("unexpected frame {:?}", frame),
+    };
+
+    match &parts[..] {
+        [Frame::Simple(simple), Frame::Bulk(bulk)] => {
+            assert_eq!("OK", &simple[..]);
+            assert_eq!(b"hello", &bulk[..]);
+
+            assert!(range.contains(&simple.as_ptr()));
+            assert!(range.contains(&bulk.as_ptr()));
+        }
+        parts => panic!("unexpected frames {:?}", parts),
+    }
+}
+
+/// The constructors and typed accessors agree with the underlying variants.
+#[test]
+fn builders_and_accessors() {
+    let mut frame = Frame::array();
+    frame.push_bulk(Bytes::from_static(b"hello"));
+    frame.push_int(3);
+
+    let parts = frame.as_array().unwrap();
+    assert_eq!(Some(&Bytes::from_static(b"hello")), parts[0].as_bulk());
+    assert_eq!(Some(3), parts[1].as_int());
+    assert_eq!(None, parts[1].as_bulk());
+
+    assert_eq!(2, frame.try_into_vec().unwrap().len());
+    assert!(Frame::simple("OK").try_into_vec().is_err());
+
+    assert!(Frame::simple("OK") == "OK");
+    assert!(Frame::bulk("world") == "world");
+    assert_eq!(None, Frame::simple("OK").as_bulk());
+    assert!(matches!(Frame::error("ERR oops"), Frame::Error(msg) if msg == "ERR oops"));
+}
+
+/// Every frame type maps to the corresponding JSON value; bulk strings that
+/// are not UTF-8 become arrays of bytes.
+#[test]
+fn to_json() {
+    let frame = Frame::Array(vec![
+        Frame::simple("OK"),
+        Frame::error("ERR oops"),
+        Frame::Integer(42),
+        Frame::bulk("hello"),
+        Frame::bulk(Bytes::from_static(b"\xff\x00")),
+        Frame::Null,
+        Frame::Array(vec![]),
+    ]);
+
+    assert_eq!(
+        r#"["OK",{"error":"ERR oops"},42,"hello",[255,0],null,[]]"#,
+        frame.to_json().to_string()
+    );
+}
+
+/// Frames over the configured limits are rejected before they are complete.
+#[test]
+fn check_with_limits() {
+    use mini_redis::frame::Limits;
+    use std::io::Cursor;
+
+    let limits = Limits::new().max_array_len(2).max_inline_len(8);
+
+    let mut src = Cursor::new(&b"*3\r\n"[..]);
+    assert!(Frame::check_with_limits(&mut src, &limits).is_err());
+
+    let mut src = Cursor::new(&b"*2\r\n"[..]);
+    assert!(matches!(
+        Frame::check_with_limits(&mut src, &limits),
+        Err(mini_redis::frame::Error::Incomplete)
+    ));
+
+    let mut src = Cursor::new(&b"SET key value"[..]);
+    assert!(Frame::check_with_limits(&mut src, &limits).is_err());
+}
+
+/// Deeply nested arrays are rejected with a protocol error instead of
+/// exhausting the stack.
+#[test]
+fn check_rejects_deep_nesting() {
+    use mini_redis::frame::Limits;
+    use std::io::Cursor;
+
+    let limits = Limits::new().max_depth(2);
+
+    let mut src = Cursor::new(&b"*1\r\n*1\r\n:1\r\n"[..]);
+    assert!(Frame::check_with_limits(&mut src, &limits).is_ok());
+
+    let mut src = Cursor::new(&b"*1\r\n*1\r\n*1\r\n:1\r\n"[..]);
+    let err = Frame::check_with_limits(&mut src, &limits).unwrap_err();
+    assert!(err
+        .to_string()
+        .starts_with("protocol error; nesting depth exceeds the limit of 2 at offset 8"));
+
+    let nested = b"*1\r\n".repeat(1024 * 1024);
+    let mut src = Cursor::new(&nested[..]);
+    assert!(Frame::check(&mut src).is_err());
+}
+
+/// The single pass `Frame::decode` validates the frame while decoding it and
+/// leaves the buffer untouched until a whole frame has been received.
+#[test]
+fn decode_single_pass() {
+    use bytes::BytesMut;
+    use mini_redis::frame::Limits;
+
+    let limits = Limits::new().max_array_len(2);
+
+    let mut src = BytesMut::from(&b"*2\r\n+OK\r\n$5\r\nhel"[..]);
+    assert!(Frame::decode(&mut src, &limits).unwrap().is_none());
+    assert_eq!(16, src.len());
+
+    src.extend_from_slice(b"lo\r\n:1\r\n");
+    let frame = Frame::decode(&mut src, &limits).unwrap().unwrap();
+    assert_eq!("OK hello", frame.to_string());
+    assert_eq!(b":1\r\n", &src[..]);
+
+    let mut src = BytesMut::from(&b"*3\r\n"[..]);
+    assert!(Frame::decode(&mut src, &limits).is_err());
+
+    let mut src = BytesMut::from(&b"$5\r\nhelloXX"[..]);
+    let err = Frame::decode(&mut src, &limits).unwrap_err();
+    assert_eq!(
+        "protocol error; invalid bulk string terminator at offset 0",
+        err.to_string()
+    );
+
+    // Nesting deeper than the limit fails before recursing any further.
+    let mut src = BytesMut::from(&b"*1\r\n".repeat(1024 * 1024)[..]);
+    let err = Frame::decode(&mut src, &Limits::default()).unwrap_err();
+    assert!(err
+        .to_string()
+        .starts_with("protocol error; nesting depth exceeds the limit of 32 at offset 128"));
+}
+
+/// A small frame decoded out of a large read buffer is copied, so storing its
+/// contents does not keep the whole buffer alive. A frame that fills most of
+/// the buffer still shares its memory.
+#[test]
+fn decode_copies_small_frames() {
+    use bytes::BytesMut;
+    use mini_redis::frame::Limits;
+
+    let mut src = BytesMut::with_capacity(64 * 1024);
+    src.extend_from_slice(b"$5\r\nhello\r\n");
+    let buffer = src.as_ptr_range();
+
+    let frame = Frame::decode(&mut src, &Limits::default())
+        .unwrap()
+        .unwrap();
+    let data = frame.as_bytes().unwrap();
+    assert_eq!(&b"hello"[..], &data[..]);
+    assert!(!buffer.contains(&data.as_ptr()));
+
+    let value = vec![b'x'; 60 * 1024];
+    let mut src = BytesMut::with_capacity(64 * 1024);
+    src.extend_from_slice(format!("${}\r\n", value.len()).as_bytes());
+    src.extend_from_slice(&value);
+    src.extend_from_slice(b"\r\n");
+    let buffer = src.as_ptr_range();
+
+    let frame = Frame::decode(&mut src, &Limits::default())
+        .unwrap()
+        .unwrap();
+    let data = frame.as_bytes().unwrap();
+    assert_eq!(value.len(), data.len());
+    assert!(buffer.contains(&data.as_ptr()));
+}
+
+/// Error frames always start with a Redis error code, and the code can be
+/// read back as an `ErrorKind`.
+#[test]
+fn error_kinds() {
+    let frame = Frame::error("no such key");
+    assert_eq!("error: ERR no such key", frame.to_string());
+    assert_eq!(Some(ErrorKind::Err), frame.error_kind());
+
+    let frame = Frame::error("WRONGTYPE Operation against a key holding the wrong kind of value");
+    assert_eq!(Some(ErrorKind::WrongType), frame.error_kind());
+    assert_eq!(Frame::error_wrongtype().to_string(), frame.to_string());
+
+    assert_eq!(Some(ErrorKind::Err), Frame::error_syntax().error_kind());
+    assert_eq!(Some(ErrorKind::NoAuth), Frame::error_noauth().error_kind());
+    assert_eq!(
+        Some(ErrorKind::BusyKey),
+        Frame::error_busykey().error_kind()
+    );
+    assert_eq!(
+        "error: MOVED 3999 127.0.0.1:6381",
+        Frame::typed_error(ErrorKind::Moved, "3999 127.0.0.1:6381").to_string()
+    );
+
+    assert_eq!(None, Frame::Error("Oops".to_string()).error_kind());
+    assert_eq!(None, Frame::Null.error_kind());
+}
@@ -0,0 +1,314 @@
+//! Byte-level checks of the RESP replies, compared against what a real Redis
+//! server sends for the same requests. Every test talks to its own server on
+//! an ephemeral port through a raw TCP socket, so any change to the encoding of
+//! a reply shows up here even if the bundled client would still accept it.
+
+use mini_redis::server;
+
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+/// A raw connection to a server started with `server::spawn_ephemeral`.
+struct RawConnection {
+    stream: TcpStream,
+}
+
+impl RawConnection {
+    async fn connect() -> RawConnection {
+        let addr = server::spawn_ephemeral().await.unwrap();
+        let stream = TcpStream::connect(addr).await.unwrap();
+        RawConnection { stream }
+    }
+
+    /// Opens another connection to the same server.
+    async fn connect_another(&self) -> RawConnection {
+        let addr = self.stream.peer_addr().unwrap();
+        let stream = TcpStream::connect(addr).await.unwrap();
+        RawConnection { stream }
+    }
+
+    /// Sends `args` as an array of bulk strings, the way `redis-cli` does.
+    async fn send(&mut self, args: &[&str]) {
+        let mut request = format!("*{}\r\n", args.len()).into_bytes();
+        for arg in args {
+            request.extend_from_slice(format!("${}\r\n{}\r\n", arg.len(), arg).as_bytes());
+        }
+
+        self.stream.write_all(&request).await.unwrap();
+    }
+
+    /// Reads exactly as many bytes as `expected` holds and compares them.
+    async fn expect(&mut self, expected: &str) {
+        let mut actual = vec![0; expected.len()];
+        timeout(Duration::from_secs(5), self.stream.read_exact(&mut actual))
+            .await
+            .unwrap_or_else(|_| panic!("timed out waiting for {:?}", expected))
+            .unwrap();
+
+        assert_eq!(expected, String::from_utf8_lossy(&actual));
+    }
+
+    /// Sends `args` and checks the reply.
+    async fn assert_reply(&mut self, args: &[&str], expected: &str) {
+        self.send(args).await;
+        self.expect(expected).await;
+    }
+}
+
+#[tokio::test]
+async fn ping() {
+    let mut conn = RawConnection::connect().await;
+
+    conn.assert_reply(&["PING"], "+PONG\r\n").await;
+    conn.assert_reply(&["PING", "hello"], "$5\r\nhello\r\n")
+        .await;
+    conn.assert_reply(&["ping"], "+PONG\r\n").await;
+}
+
+#[tokio::test]
+async fn strings() {
+    let mut conn = RawConnection::connect().await;
+
+    conn.assert_reply(&["GET", "foo"], "$-1\r\n").await;
+    conn.assert_reply(&["SET", "foo", "bar"], "+OK\r\n").await;
+    conn.assert_reply(&["GET", "foo"], "$3\r\nbar\r\n").await;
+    conn.assert_reply(&["SET", "empty", ""], "+OK\r\n").await;
+    conn.assert_reply(&["GET", "empty"], "$0\r\n\r\n").await;
+    conn.assert_reply(&["SET", "foo", "baz", "EX", "100"], "+OK\r\n")
+        .await;
+    conn.assert_reply(&["SET", "foo", "qux", "KEEPTTL"], "+OK\r\n")
+        .await;
+    conn.assert_reply(&["SETRANGE", "foo", "1", "UU"], ":3\r\n")
+        .await;
+    conn.assert_reply(&["GETRANGE", "foo", "0", "-2"], "$2\r\nqU\r\n")
+        .await;
+    conn.assert_reply(&["GETRANGE", "missing", "0", "-1"], "$0\r\n\r\n")
+        .await;
+}
+
+#[tokio::test]
+async fn counters_and_bits() {
+    let mut conn = RawConnection::connect().await;
+
+    conn.assert_reply(&["INCR", "counter"], ":1\r\n").await;
+    conn.assert_reply(&["INCR", "counter"], ":2\r\n").await;
+    conn.assert_reply(&["SETBIT", "bits", "7", "1"], ":0\r\n")
+        .await;
+    conn.assert_reply(&["SETBIT", "bits", "7", "0"], ":1\r\n")
+        .await;
+    conn.assert_reply(&["GETBIT", "bits", "100"], ":0\r\n")
+        .await;
+    conn.assert_reply(&["SET", "bits", "foobar"], "+OK\r\n")
+        .await;
+    conn.assert_reply(&["BITCOUNT", "bits"], ":26\r\n").await;
+    conn.assert_reply(&["BITCOUNT", "bits", "1", "1"], ":6\r\n")
+        .await;
+}
+
+#[tokio::test]
+async fn keyspace() {
+    let mut conn = RawConnection::connect().await;
+
+    conn.assert_reply(&["DBSIZE"], ":0\r\n").await;
+    conn.assert_reply(&["SET", "a", "1"], "+OK\r\n").await;
+    conn.assert_reply(&["EXISTS", "a", "a", "b"], ":2\r\n")
+        .await;
+    conn.assert_reply(&["KEYS", "a*"], "*1\r\n$1\r\na\r\n")
+        .await;
+    conn.assert_reply(&["KEYS", "nothing*"], "*0\r\n").await;
+    conn.assert_reply(&["DBSIZE"], ":1\r\n").await;
+    conn.assert_reply(&["MSET", "b", "2", "c", "3"], "+OK\r\n")
+        .await;
+    conn.assert_reply(&["DEL", "a", "b", "c", "d"], ":3\r\n")
+        .await;
+    conn.assert_reply(&["DEL", "a"], ":0\r\n").await;
+}
+
+#[tokio::test]
+async fn lists() {
+    let mut conn = RawConnection::connect().await;
+
+    conn.assert_reply(&["RPUSH", "list", "a", "b"], ":2\r\n")
+        .await;
+    conn.assert_reply(&["LPUSH", "list", "z"], ":3\r\n").await;
+    conn.assert_reply(&["LLEN", "list"], ":3\r\n").await;
+    conn.assert_reply(&["LPOP", "list"], "$1\r\nz\r\n").await;
+    conn.assert_reply(&["LLEN", "missing"], ":0\r\n").await;
+    conn.assert_reply(&["LPOP", "missing"], "$-1\r\n").await;
+    conn.assert_reply(&["BLPOP", "list", "0"], "*2\r\n$4\r\nlist\r\n$1\r\na\r\n")
+        .await;
+    // Redis replies with a null array (`*-1`) here. `Frame` has no null array,
+    // so mini-redis sends a null bulk string instead, which clients accept as
+    // "no value" all the same.
+    conn.assert_reply(&["BLPOP", "missing", "0.01"], "$-1\r\n")
+        .await;
+}
+
+#[tokio::test]
+async fn hashes() {
+    let mut conn = RawConnection::connect().await;
+
+    conn.assert_reply(&["HSET", "h", "f", "1", "g", "x"], ":2\r\n")
+        .await;
+    conn.assert_reply(&["HGET", "h", "f"], "$1\r\n1\r\n").await;
+    conn.assert_reply(&["HGET", "h", "missing"], "$-1\r\n")
+        .await;
+    conn.assert_reply(&["HINCRBY", "h", "f", "5"], ":6\r\n")
+        .await;
+    conn.assert_reply(&["HINCRBYFLOAT", "h", "f", "0.5"], "$3\r\n6.5\r\n")
+        .await;
+    conn.assert_reply(
+        &["HINCRBY", "h", "g", "1"],
+        "-ERR hash value is not an integer\r\n",
+    )
+    .await;
+    conn.assert_reply(&["HEXPIRE", "h", "0", "FIELDS", "1", "g"], "*1\r\n:2\r\n")
+        .await;
+    conn.assert_reply(&["HGET", "h", "g"], "$-1\r\n").await;
+}
+
+#[tokio::test]
+async fn sets() {
+    let mut conn = RawConnection::connect().await;
+
+    conn.assert_reply(&["SADD", "a", "x", "y", "x"], ":2\r\n")
+        .await;
+    conn.assert_reply(&["SADD", "b", "y"], ":1\r\n").await;
+    conn.assert_reply(&["SINTER", "a", "b"], "*1\r\n$1\r\ny\r\n")
+        .await;
+    conn.assert_reply(&["SDIFF", "a", "b"], "*1\r\n$1\r\nx\r\n")
+        .await;
+    conn.assert_reply(&["SUNIONSTORE", "c", "a", "b"], ":2\r\n")
+        .await;
+    conn.assert_reply(&["SMEMBERS", "c"], "*2\r\n$1\r\nx\r\n$1\r\ny\r\n")
+        .await;
+    conn.assert_reply(&["SMEMBERS", "missing"], "*0\r\n").await;
+}
+
+#[tokio::test]
+async fn streams() {
+    let mut conn = RawConnection::connect().await;
+
+    conn.assert_reply(&["XADD", "s", "1-1", "f", "v"], "$3\r\n1-1\r\n")
+        .await;
+    conn.assert_reply(
+        &["XRANGE", "s", "-", "+"],
+        "*1\r\n*2\r\n$3\r\n1-1\r\n*2\r\n$1\r\nf\r\n$1\r\nv\r\n",
+    )
+    .await;
+    conn.assert_reply(&["XRANGE", "missing", "-", "+"], "*0\r\n")
+        .await;
+}
+
+#[tokio::test]
+async fn hyperloglog() {
+    let mut conn = RawConnection::connect().await;
+
+    conn.assert_reply(&["PFADD", "hll", "a", "b", "c"], ":1\r\n")
+        .await;
+    conn.assert_reply(&["PFADD", "hll", "a"], ":0\r\n").await;
+    conn.assert_reply(&["PFCOUNT", "hll"], ":3\r\n").await;
+}
+
+#[tokio::test]
+async fn transactions() {
+    let mut conn = RawConnection::connect().await;
+
+    conn.assert_reply(&["MULTI"], "+OK\r\n").await;
+    conn.assert_reply(&["SET", "foo", "bar"], "+QUEUED\r\n")
+        .await;
+    conn.assert_reply(&["INCR", "counter"], "+QUEUED\r\n").await;
+    conn.assert_reply(&["EXEC"], "*2\r\n+OK\r\n:1\r\n").await;
+
+    conn.assert_reply(&["MULTI"], "+OK\r\n").await;
+    conn.assert_reply(&["SET", "foo", "baz"], "+QUEUED\r\n")
+        .await;
+    conn.assert_reply(&["DISCARD"], "+OK\r\n").await;
+    conn.assert_reply(&["GET", "foo"], "$3\r\nbar\r\n").await;
+
+    conn.assert_reply(&["EXEC"], "-ERR EXEC without MULTI\r\n")
+        .await;
+}
+
+#[tokio::test]
+async fn pub_sub() {
+    let mut subscriber = RawConnection::connect().await;
+    let mut publisher = subscriber.connect_another().await;
+
+    publisher
+        .assert_reply(&["PUBLISH", "news", "hi"], ":0\r\n")
+        .await;
+
+    subscriber
+        .assert_reply(
+            &["SUBSCRIBE", "news", "other"],
+            "*3\r\n$9\r\nsubscribe\r\n$4\r\nnews\r\n:1\r\n\
+             *3\r\n$9\r\nsubscribe\r\n$5\r\nother\r\n:2\r\n",
+        )
+        .await;
+
+    publisher
+        .assert_reply(&["PUBSUB", "NUMSUB", "news"], "*2\r\n$4\r\nnews\r\n:1\r\n")
+        .await;
+    publisher
+        .assert_reply(&["PUBLISH", "news", "hi"], ":1\r\n")
+        .await;
+    subscriber
+        .expect("*3\r\n$7\r\nmessage\r\n$4\r\nnews\r\n$2\r\nhi\r\n")
+        .await;
+
+    subscriber
+        .assert_reply(&["PING"], "*2\r\n$4\r\npong\r\n$0\r\n\r\n")
+        .await;
+    subscriber
+        .assert_reply(
+            &["UNSUBSCRIBE"],
+            "*3\r\n$11\r\nunsubscribe\r\n$4\r\nnews\r\n:1\r\n\
+             *3\r\n$11\r\nunsubscribe\r\n$5\r\nother\r\n:0\r\n",
+        )
+        .await;
+    subscriber
+        .assert_reply(
+            &["UNSUBSCRIBE"],
+            "*3\r\n$11\r\nunsubscribe\r\n$-1\r\n:0\r\n",
+        )
+        .await;
+}
+
+#[tokio::test]
+async fn errors() {
+    let mut conn = RawConnection::connect().await;
+
+    conn.assert_reply(&["FOO"], "-ERR unknown command 'foo'\r\n")
+        .await;
+    conn.assert_reply(&["RPUSH", "list", "a"], ":1\r\n").await;
+    conn.assert_reply(
+        &["GET", "list"],
+        "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n",
+    )
+    .await;
+    conn.assert_reply(&["SET", "foo", "bar", "NX", "XX"], "-ERR syntax error\r\n")
+        .await;
+
+    // The connection stays usable after an error reply.
+    conn.assert_reply(&["PING"], "+PONG\r\n").await;
+}
+
+#[tokio::test]
+async fn server_commands() {
+    let mut conn = RawConnection::connect().await;
+
+    conn.assert_reply(
+        &["CONFIG", "GET", "maxclients"],
+        "*2\r\n$10\r\nmaxclients\r\n$3\r\n250\r\n",
+    )
+    .await;
+    conn.assert_reply(&["CONFIG", "SET", "maxclients", "100"], "+OK\r\n")
+        .await;
+    conn.assert_reply(&["WAIT", "0", "0"], ":0\r\n").await;
+    conn.assert_reply(&["ACL", "WHOAMI"], "$7\r\ndefault\r\n")
+        .await;
+    conn.assert_reply(&["RESET"], "+RESET\r\n").await;
+}
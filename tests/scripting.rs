@@ -0,0 +1,234 @@
+#![cfg(feature = "scripting")]
+
+use mini_redis::acl::{Acl, User};
+use mini_redis::clients::Client;
+use mini_redis::server;
+
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// A script can read and write keys through `redis.call`, and is cached so it
+/// can be executed again through `EVALSHA`.
+#[tokio::test]
+async fn eval_and_evalsha() {
+    let addr = start_server().await;
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    // EVAL "redis.call('SET', KEYS[1], ARGV[1]) return redis.call('GET', KEYS[1])" 1 hello world
+    let script = "redis.call('SET', KEYS[1], ARGV[1]) return redis.call('GET', KEYS[1])";
+    let request = format!(
+        "*5\r\n$4\r\nEVAL\r\n${}\r\n{}\r\n:1\r\n$5\r\nhello\r\n$5\r\nworld\r\n",
+        script.len(),
+        script
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    let mut response = [0; 11];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(b"$5\r\nworld\r\n", &response);
+
+    // SCRIPT LOAD "return ARGV[1]"
+    stream
+        .write_all(b"*3\r\n$6\r\nSCRIPT\r\n$4\r\nLOAD\r\n$14\r\nreturn ARGV[1]\r\n")
+        .await
+        .unwrap();
+
+    let mut response = [0; 47];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(&b"$40\r\n"[..], &response[..5]);
+    let sha = std::str::from_utf8(&response[5..45]).unwrap().to_string();
+
+    // EVALSHA <sha> 0 howdy
+    let request = format!(
+        "*4\r\n$7\r\nEVALSHA\r\n$40\r\n{}\r\n:0\r\n$5\r\nhowdy\r\n",
+        sha
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    let mut response = [0; 11];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(b"$5\r\nhowdy\r\n", &response);
+
+    // Unknown scripts are reported with NOSCRIPT
+    stream
+        .write_all(b"*3\r\n$7\r\nEVALSHA\r\n$3\r\nabc\r\n:0\r\n")
+        .await
+        .unwrap();
+
+    let mut response = [0; 48];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(
+        &b"-NOSCRIPT No matching script. Please use EVAL.\r\n"[..],
+        &response[..]
+    );
+}
+
+/// A script that never finishes is aborted once it exceeds the configured
+/// instruction budget, and the server keeps serving other connections.
+#[tokio::test]
+async fn runaway_script_is_aborted() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let config = server::Config::new().script_instruction_limit(Some(1_000_000));
+    tokio::spawn(async move {
+        server::run_with_config(listener, config, tokio::signal::ctrl_c()).await
+    });
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    // EVAL "while true do end" 0
+    stream
+        .write_all(b"*3\r\n$4\r\nEVAL\r\n$17\r\nwhile true do end\r\n:0\r\n")
+        .await
+        .unwrap();
+
+    let mut response = [0; 5];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(b"-BUSY", &response);
+
+    let mut other = TcpStream::connect(addr).await.unwrap();
+    other.write_all(b"*1\r\n$4\r\nPING\r\n").await.unwrap();
+
+    let mut response = [0; 7];
+    other.read_exact(&mut response).await.unwrap();
+    assert_eq!(b"+PONG\r\n", &response);
+}
+
+/// Commands run through `redis.call` are subject to the same ACL checks as
+/// commands sent by the client: a user restricted to `app:*` keys cannot
+/// read other keys from a script.
+#[tokio::test]
+async fn redis_call_checks_acl() {
+    let acl = Acl::new().user(
+        User::new("app")
+            .password("pass")
+            .command("eval")
+            .command("get")
+            .key_pattern("app:*"),
+    );
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let config = server::Config::new().acl(acl);
+    tokio::spawn(async move {
+        server::run_with_config(listener, config, tokio::signal::ctrl_c()).await
+    });
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    // AUTH app pass
+    stream
+        .write_all(b"*3\r\n$4\r\nAUTH\r\n$3\r\napp\r\n$4\r\npass\r\n")
+        .await
+        .unwrap();
+
+    let mut response = [0; 5];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(b"+OK\r\n", &response);
+
+    // EVAL "return redis.call('get', 'secret')" 0
+    let script = "return redis.call('get', 'secret')";
+    let request = format!(
+        "*3\r\n$4\r\nEVAL\r\n${}\r\n{}\r\n:0\r\n",
+        script.len(),
+        script
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    let response = read_line(&mut stream).await;
+    assert!(response.starts_with("-ERR"), "{}", response);
+    assert!(
+        response.contains("NOPERM this user has no permissions to access one of the keys"),
+        "{}",
+        response
+    );
+
+    // EVAL "return redis.call('set', 'app:1', 'x')" 0
+    let script = "return redis.call('set', 'app:1', 'x')";
+    let request = format!(
+        "*3\r\n$4\r\nEVAL\r\n${}\r\n{}\r\n:0\r\n",
+        script.len(),
+        script
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    let response = read_line(&mut stream).await;
+    assert!(
+        response.contains("NOPERM this user has no permissions to run the 'set' command"),
+        "{}",
+        response
+    );
+}
+
+/// The writes a script makes through `redis.call` are appended to the AOF and
+/// replayed on restart. The relative expiry time the script sets is recorded
+/// as an absolute one, so the key does not live again after the restart.
+#[tokio::test]
+async fn eval_writes_are_appended_to_aof() {
+    let path = std::env::temp_dir().join(format!("mini-redis-aof-eval-{}", std::process::id()));
+    let config = || {
+        server::Config::new()
+            .append_only(&path)
+            .append_fsync(server::AppendFsync::Always)
+    };
+
+    let handle = server::spawn("127.0.0.1:0", config()).await.unwrap();
+    let mut stream = TcpStream::connect(handle.addr()).await.unwrap();
+
+    // EVAL "..." 3 temp hello counter world
+    let script = "redis.call('SET', KEYS[1], ARGV[1], 'PX', 300) \
+                  redis.call('SET', KEYS[2], ARGV[1]) \
+                  return redis.call('INCR', KEYS[3])";
+    let request = format!(
+        "*7\r\n$4\r\nEVAL\r\n${}\r\n{}\r\n:3\r\n$4\r\ntemp\r\n$5\r\nhello\r\n$7\r\ncounter\r\n$5\r\nworld\r\n",
+        script.len(),
+        script
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+    assert_eq!(":1\r\n", read_line(&mut stream).await);
+
+    drop(stream);
+    handle.shutdown().await;
+
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let handle = server::spawn("127.0.0.1:0", config()).await.unwrap();
+    let mut client = Client::connect(handle.addr()).await.unwrap();
+
+    assert_eq!(None, client.get("temp").await.unwrap());
+    assert_eq!(
+        Some(&b"world"[..]),
+        client.get("hello").await.unwrap().as_deref()
+    );
+    assert_eq!(
+        Some(&b"1"[..]),
+        client.get("counter").await.unwrap().as_deref()
+    );
+
+    drop(client);
+    handle.shutdown().await;
+    std::fs::remove_file(&path).unwrap();
+}
+
+/// Reads a single line of the response, including the trailing `\r\n`.
+async fn read_line(stream: &mut TcpStream) -> String {
+    let mut line = vec![];
+
+    while !line.ends_with(b"\r\n") {
+        line.push(stream.read_u8().await.unwrap());
+    }
+
+    String::from_utf8(line).unwrap()
+}
+
+async fn start_server() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move { server::run(listener, tokio::signal::ctrl_c()).await });
+
+    addr
+}
@@ -0,0 +1,79 @@
+use mini_redis::{clients::Client, sentinel, server};
+use tokio::sync::oneshot;
+use tokio::time::{self, Duration};
+
+/// When the primary stops answering PING, the sentinel promotes a replica,
+/// points the other replica at it and announces the switch over pub/sub.
+#[tokio::test]
+async fn replica_is_promoted_when_primary_goes_down() {
+    let primary = server::spawn("127.0.0.1:0", server::Config::new())
+        .await
+        .unwrap();
+    let primary_addr = primary.addr();
+
+    let mut replicas = vec![];
+    for _ in 0..2 {
+        let config = server::Config::new().replica_of("127.0.0.1", primary_addr.port());
+        replicas.push(server::spawn("127.0.0.1:0", config).await.unwrap());
+    }
+    let replica_addrs: Vec<_> = replicas.iter().map(|replica| replica.addr()).collect();
+
+    let mut client = Client::connect(primary_addr).await.unwrap();
+    client.set("hello", "world".into()).await.unwrap();
+    for addr in &replica_addrs {
+        eventually(addr, "hello", "world").await;
+    }
+
+    let mut subscriber = Client::connect(replica_addrs[1])
+        .await
+        .unwrap()
+        .subscribe(vec![sentinel::DEFAULT_CHANNEL.to_string()])
+        .await
+        .unwrap();
+
+    let config = sentinel::Config::new(primary_addr.to_string())
+        .replica(replica_addrs[0].to_string())
+        .replica(replica_addrs[1].to_string())
+        .ping_interval(Duration::from_millis(50))
+        .down_after(Duration::from_millis(200));
+    let (stop, stopped) = oneshot::channel::<()>();
+    let sentinel = tokio::spawn(sentinel::run(config, stopped));
+
+    drop(client);
+    primary.shutdown().await;
+
+    let message = time::timeout(Duration::from_secs(5), subscriber.next_message())
+        .await
+        .expect("no failover notification")
+        .unwrap()
+        .unwrap();
+    let message = String::from_utf8(message.content.to_vec()).unwrap();
+    let (old, new) = message.split_once(' ').unwrap();
+    assert_eq!(primary_addr.to_string(), old);
+
+    // The promoted replica accepts writes and the other one replicates them.
+    let new: std::net::SocketAddr = new.parse().unwrap();
+    let other = *replica_addrs.iter().find(|addr| **addr != new).unwrap();
+    let mut client = Client::connect(new).await.unwrap();
+    client.set("after", "failover".into()).await.unwrap();
+    eventually(&other, "hello", "world").await;
+    eventually(&other, "after", "failover").await;
+
+    stop.send(()).unwrap();
+    sentinel.await.unwrap().unwrap();
+}
+
+/// Waits until `key` has `value` on the server at `addr`.
+async fn eventually(addr: &std::net::SocketAddr, key: &str, value: &str) {
+    let mut client = Client::connect(addr).await.unwrap();
+
+    for _ in 0..500 {
+        if client.get(key).await.unwrap().as_deref() == Some(value.as_bytes()) {
+            return;
+        }
+
+        time::sleep(Duration::from_millis(10)).await;
+    }
+
+    panic!("`{}` never became `{}` on {}", key, value, addr);
+}
@@ -1,6 +1,10 @@
-use mini_redis::server;
+use bytes::Bytes;
+use mini_redis::clients::Client;
+use mini_redis::clock::ManualClock;
+use mini_redis::{server, Command, Connection, DbObserver, Frame};
 
 use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
 use tokio::time::{self, Duration};
@@ -331,26 +335,1161 @@ async fn manage_subscription() {
     );
 }
 
+/// Subscribing to a channel twice is confirmed without changing the
+/// subscription count, and messages are still delivered only once.
+#[tokio::test]
+async fn duplicate_subscribe() {
+    let addr = start_server().await;
+
+    let mut publisher = TcpStream::connect(addr).await.unwrap();
+
+    let mut sub = TcpStream::connect(addr).await.unwrap();
+    sub.write_all(b"*4\r\n$9\r\nSUBSCRIBE\r\n$5\r\nhello\r\n$5\r\nhello\r\n$3\r\nfoo\r\n")
+        .await
+        .unwrap();
+
+    let mut response = [0; 100];
+    sub.read_exact(&mut response).await.unwrap();
+    assert_eq!(
+        &b"*3\r\n$9\r\nsubscribe\r\n$5\r\nhello\r\n:1\r\n\
+           *3\r\n$9\r\nsubscribe\r\n$5\r\nhello\r\n:1\r\n\
+           *3\r\n$9\r\nsubscribe\r\n$3\r\nfoo\r\n:2\r\n"[..],
+        &response[..]
+    );
+
+    // Subscribing again in a later command does not change the count either
+    sub.write_all(b"*2\r\n$9\r\nSUBSCRIBE\r\n$3\r\nfoo\r\n")
+        .await
+        .unwrap();
+
+    let mut response = [0; 32];
+    sub.read_exact(&mut response).await.unwrap();
+    assert_eq!(
+        &b"*3\r\n$9\r\nsubscribe\r\n$3\r\nfoo\r\n:2\r\n"[..],
+        &response[..]
+    );
+
+    publisher
+        .write_all(b"*3\r\n$7\r\nPUBLISH\r\n$5\r\nhello\r\n$5\r\nworld\r\n")
+        .await
+        .unwrap();
+    let mut response = [0; 4];
+    publisher.read_exact(&mut response).await.unwrap();
+    assert_eq!(b":1\r\n", &response);
+
+    let mut response = [0; 39];
+    sub.read_exact(&mut response).await.unwrap();
+    assert_eq!(
+        &b"*3\r\n$7\r\nmessage\r\n$5\r\nhello\r\n$5\r\nworld\r\n"[..],
+        &response[..]
+    );
+
+    // The message is delivered only once
+    let mut response = [0; 1];
+    time::timeout(Duration::from_millis(100), sub.read(&mut response))
+        .await
+        .unwrap_err();
+}
+
+/// Commands sent through EVALBATCH are executed in order and their results are
+/// returned as a single array.
+#[tokio::test]
+async fn eval_batch() {
+    let addr = start_server().await;
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    // EVALBATCH 3 3 SET hello world 2 GET hello 1 PING
+    stream
+        .write_all(
+            b"*11\r\n$9\r\nEVALBATCH\r\n:3\r\n\
+              :3\r\n$3\r\nSET\r\n$5\r\nhello\r\n$5\r\nworld\r\n\
+              :2\r\n$3\r\nGET\r\n$5\r\nhello\r\n\
+              :1\r\n$4\r\nPING\r\n",
+        )
+        .await
+        .unwrap();
+
+    let expected = b"*3\r\n+OK\r\n$5\r\nworld\r\n+PONG\r\n";
+    let mut response = [0; 27];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(&expected[..], &response[..]);
+}
+
+/// Commands can be sent in the inline format, as netcat or telnet would,
+/// mixed with blank lines and regular RESP frames.
+#[tokio::test]
+async fn inline_commands() {
+    let addr = start_server().await;
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    stream
+        .write_all(b"SET hello  world\r\n\r\nGET hello\n*1\r\n$4\r\nPING\r\n")
+        .await
+        .unwrap();
+
+    let expected = b"+OK\r\n$5\r\nworld\r\n+PONG\r\n";
+    let mut response = [0; 23];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(&expected[..], &response[..]);
+}
+
+/// `COMMAND DOCS` describes the requested commands and ignores unknown ones.
+#[tokio::test]
+async fn command_docs() {
+    let addr = start_server().await;
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    stream
+        .write_all(b"COMMAND DOCS get nosuchcommand\r\n")
+        .await
+        .unwrap();
+
+    let expected = b"*2\r\n$3\r\nget\r\n*4\r\n$7\r\nsummary\r\n$34\r\nReturns the string value of a key.\r\n$5\r\ngroup\r\n$6\r\nstring\r\n";
+    let mut response = [0; 94];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(&expected[..], &response[..]);
+}
+
+/// `COMMAND` lists every command with its arity, flags and key positions.
+/// Negative numbers are sent as simple strings since integer frames are
+/// unsigned.
+#[tokio::test]
+async fn command_info() {
+    let addr = start_server().await;
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut connection = Connection::new(stream);
+
+    let mut request = Frame::array();
+    request.push_bulk(Bytes::from("command"));
+    connection.write_frame(&request).await.unwrap();
+
+    let commands = match connection.read_frame().await.unwrap() {
+        Some(Frame::Array(commands)) => commands,
+        frame => panic!("unexpected frame {:?}", frame),
+    };
+
+    let set = commands
+        .into_iter()
+        .filter_map(|info| info.try_into_vec().ok())
+        .find(|info| matches!(&info[0], Frame::Bulk(name) if name == "set"))
+        .unwrap();
+
+    assert!(matches!(&set[1], Frame::Simple(arity) if arity == "-3"));
+    assert!(matches!(&set[2], Frame::Array(flags) if flags.len() == 2));
+    assert!(matches!(
+        set[3..],
+        [Frame::Integer(1), Frame::Integer(1), Frame::Integer(1)]
+    ));
+}
+
+/// A malformed frame results in a final protocol error frame that identifies
+/// the offending element, after which the connection is closed.
+#[tokio::test]
+async fn protocol_error_closes_connection() {
+    let addr = start_server().await;
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    // The second array element has an invalid bulk string length.
+    stream
+        .write_all(b"*2\r\n$3\r\nGET\r\n$x\r\nhello\r\n")
+        .await
+        .unwrap();
+
+    let mut response = vec![];
+    stream.read_to_end(&mut response).await.unwrap();
+    assert_eq!(
+        &b"-ERR Protocol error: invalid bulk string length at offset 13 \
+           (array element 1 of 2)\r\n"[..],
+        &response[..]
+    );
+}
+
+/// An oversized bulk string header is rejected as soon as it is received,
+/// without waiting for (or allocating) the announced payload.
+#[tokio::test]
+async fn oversized_bulk_string_is_rejected() {
+    let addr = start_server().await;
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    stream
+        .write_all(b"*2\r\n$3\r\nGET\r\n$999999999999\r\n")
+        .await
+        .unwrap();
+
+    let mut response = vec![];
+    stream.read_to_end(&mut response).await.unwrap();
+    assert_eq!(
+        &b"-ERR Protocol error: bulk string length exceeds the limit of 536870912 \
+           at offset 13 (array element 1 of 2)\r\n"[..],
+        &response[..]
+    );
+}
+
+/// A frame that is still incomplete once the read buffer limit is reached is
+/// rejected, while pipelined frames that add up to more than the limit are not.
+#[tokio::test]
+async fn read_buffer_limit_rejects_huge_frames() {
+    let config = server::Config::new().read_buffer_limit(Some(1024));
+    let handle = server::spawn("127.0.0.1:0", config).await.unwrap();
+
+    let mut stream = TcpStream::connect(handle.addr()).await.unwrap();
+    let pings = b"*1\r\n$4\r\nPING\r\n".repeat(100);
+    stream.write_all(&pings).await.unwrap();
+
+    let mut response = vec![0; b"+PONG\r\n".len() * 100];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(b"+PONG\r\n".repeat(100), response);
+
+    // Exactly 1024 bytes of a frame announcing a much longer bulk string
+    let mut request = b"*2\r\n$4\r\nECHO\r\n$100000\r\n".to_vec();
+    request.resize(1024, b'a');
+    stream.write_all(&request).await.unwrap();
+
+    let mut response = vec![];
+    stream.read_to_end(&mut response).await.unwrap();
+    assert_eq!(
+        &b"-ERR Protocol error: incomplete frame exceeds the read buffer limit of 1024 bytes\r\n"[..],
+        &response[..]
+    );
+}
+
+/// Deeply nested arrays result in a protocol error for the offending client
+/// instead of overflowing the stack and taking down the server.
+#[tokio::test]
+async fn deeply_nested_arrays_are_rejected() {
+    let addr = start_server().await;
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    // The server closes the connection without reading the rest of the
+    // request, so both sides may see the connection reset.
+    let _ = stream.write_all(&b"*1\r\n".repeat(300 * 1024)).await;
+
+    let mut response = vec![];
+    let _ = stream.read_to_end(&mut response).await;
+    assert!(response
+        .starts_with(b"-ERR Protocol error: nesting depth exceeds the limit of 32 at offset 128"));
+
+    // Other clients are still served.
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(b"*1\r\n$4\r\nPING\r\n").await.unwrap();
+
+    let mut response = [0; 7];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(b"+PONG\r\n", &response);
+}
+
+/// PING is answered while subscribed, and RESET leaves the subscribed state so
+/// regular commands work again.
+#[tokio::test]
+async fn ping_and_reset_while_subscribed() {
+    let addr = start_server().await;
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(b"*2\r\n$9\r\nSUBSCRIBE\r\n$5\r\nhello\r\n")
+        .await
+        .unwrap();
+
+    let mut response = [0; 34];
+    stream.read_exact(&mut response).await.unwrap();
+
+    stream.write_all(b"*1\r\n$4\r\nPING\r\n").await.unwrap();
+
+    let mut response = [0; 20];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(&b"*2\r\n$4\r\npong\r\n$0\r\n\r\n"[..], &response[..]);
+
+    stream.write_all(b"*1\r\n$5\r\nRESET\r\n").await.unwrap();
+
+    let mut response = [0; 8];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(b"+RESET\r\n", &response);
+
+    stream
+        .write_all(b"*2\r\n$3\r\nGET\r\n$5\r\nhello\r\n")
+        .await
+        .unwrap();
+
+    let mut response = [0; 5];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(b"$-1\r\n", &response);
+}
+
+/// QUIT is answered with OK even while subscribed, and the server then closes
+/// the connection.
+#[tokio::test]
+async fn quit_closes_connection() {
+    let addr = start_server().await;
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(b"*2\r\n$9\r\nSUBSCRIBE\r\n$5\r\nhello\r\n")
+        .await
+        .unwrap();
+
+    let mut response = [0; 34];
+    stream.read_exact(&mut response).await.unwrap();
+
+    stream.write_all(b"*1\r\n$4\r\nQUIT\r\n").await.unwrap();
+
+    let mut response = vec![];
+    stream.read_to_end(&mut response).await.unwrap();
+    assert_eq!(b"+OK\r\n", &response[..]);
+}
+
+/// Commands between MULTI and EXEC are queued and then executed together.
+#[tokio::test]
+async fn multi_exec() {
+    let addr = start_server().await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    assert_reply(&mut stream, b"MULTI\r\n", b"+OK\r\n").await;
+    assert_reply(&mut stream, b"SET hello world\r\n", b"+QUEUED\r\n").await;
+    assert_reply(&mut stream, b"GET hello\r\n", b"+QUEUED\r\n").await;
+    assert_reply(&mut stream, b"EXEC\r\n", b"*2\r\n+OK\r\n$5\r\nworld\r\n").await;
+
+    assert_reply(&mut stream, b"EXEC\r\n", b"-ERR EXEC without MULTI\r\n").await;
+}
+
+/// A command rejected while queueing makes EXEC discard the transaction.
+#[tokio::test]
+async fn multi_exec_abort() {
+    let addr = start_server().await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    assert_reply(&mut stream, b"MULTI\r\n", b"+OK\r\n").await;
+    assert_reply(&mut stream, b"SET hello world\r\n", b"+QUEUED\r\n").await;
+    assert_reply(
+        &mut stream,
+        b"SUBSCRIBE hello\r\n",
+        b"-ERR command 'subscribe' is not allowed inside a transaction\r\n",
+    )
+    .await;
+    assert_reply(
+        &mut stream,
+        b"EXEC\r\n",
+        b"-EXECABORT Transaction discarded because of previous errors.\r\n",
+    )
+    .await;
+
+    assert_reply(&mut stream, b"GET hello\r\n", b"$-1\r\n").await;
+}
+
+/// A malformed command is answered with an error and the connection stays
+/// open. Inside a transaction it makes EXEC discard the transaction.
+#[tokio::test]
+async fn malformed_command_is_rejected() {
+    let addr = start_server().await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    assert_reply(
+        &mut stream,
+        b"SET hello world FOO\r\n",
+        b"-ERR syntax error\r\n",
+    )
+    .await;
+    assert_reply(&mut stream, b"PING\r\n", b"+PONG\r\n").await;
+
+    assert_reply(&mut stream, b"MULTI\r\n", b"+OK\r\n").await;
+    assert_reply(&mut stream, b"SET hello world\r\n", b"+QUEUED\r\n").await;
+    assert_reply(
+        &mut stream,
+        b"SET other value EX soon\r\n",
+        b"-ERR protocol error; invalid number\r\n",
+    )
+    .await;
+    assert_reply(
+        &mut stream,
+        b"EXEC\r\n",
+        b"-EXECABORT Transaction discarded because of previous errors.\r\n",
+    )
+    .await;
+
+    assert_reply(&mut stream, b"GET hello\r\n", b"$-1\r\n").await;
+}
+
+/// A command with the wrong number of arguments is answered with Redis'
+/// arity error, checked against the command table before parsing.
+#[tokio::test]
+async fn wrong_number_of_arguments() {
+    let addr = start_server().await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    assert_reply(
+        &mut stream,
+        b"GET\r\n",
+        b"-ERR wrong number of arguments for 'get' command\r\n",
+    )
+    .await;
+    assert_reply(
+        &mut stream,
+        b"GET hello world\r\n",
+        b"-ERR wrong number of arguments for 'get' command\r\n",
+    )
+    .await;
+    assert_reply(
+        &mut stream,
+        b"SET hello\r\n",
+        b"-ERR wrong number of arguments for 'set' command\r\n",
+    )
+    .await;
+
+    // Enough arguments for the table, but a subcommand is missing its own.
+    assert_reply(
+        &mut stream,
+        b"LATENCY HISTORY\r\n",
+        b"-ERR wrong number of arguments for 'latency' command\r\n",
+    )
+    .await;
+
+    assert_reply(&mut stream, b"PING\r\n", b"+PONG\r\n").await;
+}
+
+/// RESET discards a pending transaction.
+#[tokio::test]
+async fn reset_discards_multi() {
+    let addr = start_server().await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    assert_reply(&mut stream, b"MULTI\r\n", b"+OK\r\n").await;
+    assert_reply(&mut stream, b"SET hello world\r\n", b"+QUEUED\r\n").await;
+    assert_reply(&mut stream, b"RESET\r\n", b"+RESET\r\n").await;
+    assert_reply(&mut stream, b"EXEC\r\n", b"-ERR EXEC without MULTI\r\n").await;
+    assert_reply(&mut stream, b"GET hello\r\n", b"$-1\r\n").await;
+}
+
+/// A read-only server rejects writes, including writes queued in a
+/// transaction, but still serves reads.
+#[tokio::test]
+async fn read_only() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let config = server::Config::new().read_only(true);
+
+    tokio::spawn(async move {
+        server::run_with_config(listener, config, tokio::signal::ctrl_c()).await
+    });
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let readonly = b"-READONLY You can't write against a read only replica.\r\n";
+
+    assert_reply(&mut stream, b"SET hello world\r\n", readonly).await;
+    assert_reply(&mut stream, b"EVALBATCH 1 3 SET hello world\r\n", readonly).await;
+    assert_reply(&mut stream, b"GET hello\r\n", b"$-1\r\n").await;
+    assert_reply(&mut stream, b"PUBLISH chan msg\r\n", b":0\r\n").await;
+
+    assert_reply(&mut stream, b"MULTI\r\n", b"+OK\r\n").await;
+    assert_reply(&mut stream, b"INCR counter\r\n", readonly).await;
+}
+
 // In this case we test that server Responds with an Error message if a client
 // sends an unknown command
 #[tokio::test]
-async fn send_error_unknown_command() {
-    let addr = start_server().await;
+async fn send_error_unknown_command() {
+    let addr = start_server().await;
+
+    // Establish a connection to the server
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    // Get a key, data is missing
+    stream
+        .write_all(b"*2\r\n$3\r\nFOO\r\n$5\r\nhello\r\n")
+        .await
+        .unwrap();
+
+    let mut response = [0; 28];
+
+    stream.read_exact(&mut response).await.unwrap();
+
+    assert_eq!(b"-ERR unknown command \'foo\'\r\n", &response);
+}
+
+// Commands registered with `Config::register_command` are dispatched to their
+// handler, matching the name case-insensitively
+#[tokio::test]
+async fn custom_command() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let config =
+        server::Config::new().register_command("ECHO", |args: Vec<Bytes>| match &args[..] {
+            [message] => Frame::Bulk(message.clone()),
+            _ => Frame::Error("ERR wrong number of arguments".to_string()),
+        });
+
+    tokio::spawn(async move {
+        server::run_with_config(listener, config, tokio::signal::ctrl_c()).await
+    });
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    stream
+        .write_all(b"*2\r\n$4\r\necho\r\n$5\r\nhello\r\n")
+        .await
+        .unwrap();
+
+    let mut response = [0; 11];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(b"$5\r\nhello\r\n", &response);
+
+    stream.write_all(b"*1\r\n$4\r\nECHO\r\n").await.unwrap();
+
+    let mut response = [0; 32];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(b"-ERR wrong number of arguments\r\n", &response);
+}
+
+/// Hooks registered with `Config::on_command` see every command with the
+/// peer's address, can reject commands, and are told how executed commands
+/// completed.
+#[tokio::test]
+async fn command_hooks() {
+    struct DenyKeys(Arc<Mutex<Vec<(String, server::Outcome)>>>);
+
+    impl server::CommandHook for DenyKeys {
+        fn before(&self, cmd: &Command, _peer: SocketAddr) -> Option<Frame> {
+            (cmd.get_name() == "keys").then(|| Frame::error("KEYS is disabled"))
+        }
+
+        fn after(
+            &self,
+            name: &str,
+            _peer: SocketAddr,
+            _elapsed: Duration,
+            outcome: server::Outcome,
+        ) {
+            self.0.lock().unwrap().push((name.to_string(), outcome));
+        }
+    }
+
+    let seen = Arc::new(Mutex::new(vec![]));
+    let completed = Arc::new(Mutex::new(vec![]));
+
+    let config = server::Config::new()
+        .on_command({
+            let seen = seen.clone();
+            move |cmd: &Command, peer: SocketAddr| {
+                seen.lock()
+                    .unwrap()
+                    .push((cmd.get_name().to_string(), peer))
+            }
+        })
+        .on_command(DenyKeys(completed.clone()));
+    let handle = server::spawn("127.0.0.1:0", config).await.unwrap();
+
+    let mut client = Client::connect(handle.addr()).await.unwrap();
+    client.set("hello", "world".into()).await.unwrap();
+    client.incr("hello").await.unwrap_err();
+
+    let err = client.keys("*").await.unwrap_err();
+    assert_eq!("ERR KEYS is disabled", err.to_string());
+
+    let seen = seen.lock().unwrap().clone();
+    let names: Vec<_> = seen.iter().map(|(name, _)| &name[..]).collect();
+    assert_eq!(vec!["set", "incr", "keys"], names);
+    assert!(seen.iter().all(|(_, peer)| peer.ip().is_loopback()));
+
+    // Rejected commands are not executed, so `after` only sees the others.
+    assert_eq!(
+        vec![
+            ("set".to_string(), server::Outcome::Ok),
+            ("incr".to_string(), server::Outcome::Error),
+        ],
+        *completed.lock().unwrap()
+    );
+}
+
+/// When rejecting excess connections, a client over the limit receives an
+/// error and is disconnected. Raising the limit with `CONFIG SET` admits new
+/// connections.
+#[tokio::test]
+async fn max_connections_reject() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let config = server::Config::new()
+        .max_connections(1)
+        .reject_excess_connections(true);
+
+    tokio::spawn(async move {
+        server::run_with_config(listener, config, tokio::signal::ctrl_c()).await
+    });
+
+    let mut first = TcpStream::connect(addr).await.unwrap();
+    assert_reply(&mut first, b"PING\r\n", b"+PONG\r\n").await;
+
+    let mut second = TcpStream::connect(addr).await.unwrap();
+    let mut response = vec![];
+    second.read_to_end(&mut response).await.unwrap();
+    assert_eq!(
+        &b"-ERR max number of clients reached\r\n"[..],
+        &response[..]
+    );
+
+    assert_reply(
+        &mut first,
+        b"CONFIG GET maxclients\r\n",
+        b"*2\r\n$10\r\nmaxclients\r\n$1\r\n1\r\n",
+    )
+    .await;
+    assert_reply(&mut first, b"CONFIG SET maxclients 2\r\n", b"+OK\r\n").await;
+
+    let mut third = TcpStream::connect(addr).await.unwrap();
+    assert_reply(&mut third, b"PING\r\n", b"+PONG\r\n").await;
+}
+
+/// A connection that sends nothing within the handshake timeout is closed and
+/// its slot goes to the next connection. Once a frame has arrived, the
+/// connection may stay idle.
+#[tokio::test]
+async fn handshake_timeout_releases_slot() {
+    let config = server::Config::new()
+        .max_connections(1)
+        .handshake_timeout(Duration::from_millis(200));
+    let handle = server::spawn("127.0.0.1:0", config).await.unwrap();
+
+    let mut idle = TcpStream::connect(handle.addr()).await.unwrap();
+
+    // Waits for the slot held by `idle`.
+    let mut waiting = TcpStream::connect(handle.addr()).await.unwrap();
+    assert_reply(&mut waiting, b"PING\r\n", b"+PONG\r\n").await;
+
+    let mut response = vec![];
+    idle.read_to_end(&mut response).await.unwrap();
+    assert!(response.is_empty());
+
+    time::sleep(Duration::from_millis(300)).await;
+    assert_reply(&mut waiting, b"PING\r\n", b"+PONG\r\n").await;
+}
+
+/// With a manual clock, keys expire exactly when the clock is advanced past
+/// their TTL, without waiting for real time to pass.
+#[tokio::test]
+async fn manual_clock_expires_keys() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let clock = ManualClock::new();
+    let config = server::Config::new().clock(clock.clone());
+
+    tokio::spawn(async move {
+        server::run_with_config(listener, config, tokio::signal::ctrl_c()).await
+    });
 
-    // Establish a connection to the server
     let mut stream = TcpStream::connect(addr).await.unwrap();
 
-    // Get a key, data is missing
+    assert_reply(&mut stream, b"SET hello world PX 1000\r\n", b"+OK\r\n").await;
+
+    clock.advance(Duration::from_millis(999));
+    assert_reply(&mut stream, b"GET hello\r\n", b"$5\r\nworld\r\n").await;
+
+    clock.advance(Duration::from_millis(1));
+    assert_reply(&mut stream, b"GET hello\r\n", b"$-1\r\n").await;
+}
+
+/// `SETEX` and `PSETEX` behave like `SET` with `EX` / `PX`, and reject a
+/// non-positive expiration.
+#[tokio::test]
+async fn setex_psetex() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let clock = ManualClock::new();
+    let config = server::Config::new().clock(clock.clone());
+
+    tokio::spawn(async move {
+        server::run_with_config(listener, config, tokio::signal::ctrl_c()).await
+    });
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    assert_reply(&mut stream, b"SETEX hello 2 world\r\n", b"+OK\r\n").await;
+    assert_reply(&mut stream, b"PSETEX foo 1500 bar\r\n", b"+OK\r\n").await;
+
+    clock.advance(Duration::from_millis(1500));
+    assert_reply(&mut stream, b"GET foo\r\n", b"$-1\r\n").await;
+    assert_reply(&mut stream, b"GET hello\r\n", b"$5\r\nworld\r\n").await;
+
+    clock.advance(Duration::from_millis(500));
+    assert_reply(&mut stream, b"GET hello\r\n", b"$-1\r\n").await;
+
+    assert_reply(
+        &mut stream,
+        b"SETEX hello 0 world\r\n",
+        b"-ERR invalid expire time in 'setex' command\r\n",
+    )
+    .await;
+    assert_reply(
+        &mut stream,
+        b"PSETEX hello -1 world\r\n",
+        b"-ERR invalid expire time in 'psetex' command\r\n",
+    )
+    .await;
+}
+
+/// Reads reset the idle time of a key and bump its access frequency, which
+/// decays by one for every idle minute. `OBJECT` itself does not count as a
+/// read.
+#[tokio::test]
+async fn manual_clock_tracks_key_access() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let clock = ManualClock::new();
+    let config = server::Config::new().clock(clock.clone());
+
+    tokio::spawn(async move {
+        server::run_with_config(listener, config, tokio::signal::ctrl_c()).await
+    });
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    assert_reply(&mut stream, b"SET hello world\r\n", b"+OK\r\n").await;
+    assert_reply(&mut stream, b"OBJECT FREQ hello\r\n", b":5\r\n").await;
+
+    clock.advance(Duration::from_secs(30));
+    assert_reply(&mut stream, b"OBJECT IDLETIME hello\r\n", b":30\r\n").await;
+    assert_reply(&mut stream, b"GET hello\r\n", b"$5\r\nworld\r\n").await;
+    assert_reply(&mut stream, b"GET hello\r\n", b"$5\r\nworld\r\n").await;
+    assert_reply(&mut stream, b"OBJECT IDLETIME hello\r\n", b":0\r\n").await;
+    assert_reply(&mut stream, b"OBJECT FREQ hello\r\n", b":7\r\n").await;
+
+    clock.advance(Duration::from_secs(180));
+    assert_reply(&mut stream, b"OBJECT FREQ hello\r\n", b":4\r\n").await;
+    assert_reply(&mut stream, b"OBJECT FREQ missing\r\n", b"$-1\r\n").await;
+}
+
+/// `HEXPIRE` expires single hash fields, leaving the rest of the hash in
+/// place. A field that is set again loses its expiration.
+#[tokio::test]
+async fn manual_clock_expires_hash_fields() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let clock = ManualClock::new();
+    let config = server::Config::new().clock(clock.clone());
+
+    tokio::spawn(async move {
+        server::run_with_config(listener, config, tokio::signal::ctrl_c()).await
+    });
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    assert_reply(&mut stream, b"HSET h a 1 b 2 c 3\r\n", b":3\r\n").await;
+    assert_reply(
+        &mut stream,
+        b"HEXPIRE h 10 FIELDS 3 a b missing\r\n",
+        b"*3\r\n:1\r\n:1\r\n$-1\r\n",
+    )
+    .await;
+    assert_reply(&mut stream, b"HSET h b 20\r\n", b":0\r\n").await;
+
+    clock.advance(Duration::from_secs(10));
+    assert_reply(&mut stream, b"HGET h a\r\n", b"$-1\r\n").await;
+    assert_reply(&mut stream, b"HGET h b\r\n", b"$2\r\n20\r\n").await;
+    assert_reply(&mut stream, b"HGET h c\r\n", b"$1\r\n3\r\n").await;
+
+    // Expiring the last field removes the key.
+    assert_reply(
+        &mut stream,
+        b"HEXPIRE h 1 FIELDS 2 b c\r\n",
+        b"*2\r\n:1\r\n:1\r\n",
+    )
+    .await;
+    clock.advance(Duration::from_secs(1));
+    assert_reply(&mut stream, b"EXISTS h\r\n", b":0\r\n").await;
+}
+
+/// `Handle::shutdown` closes open connections and the listener, and returns
+/// once the server has stopped.
+#[tokio::test]
+async fn spawned_server_shuts_down() {
+    let handle = server::spawn("127.0.0.1:0", server::Config::new())
+        .await
+        .unwrap();
+    let addr = handle.addr();
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
     stream
-        .write_all(b"*2\r\n$3\r\nFOO\r\n$5\r\nhello\r\n")
+        .write_all(b"*3\r\n$3\r\nSET\r\n$5\r\nhello\r\n$5\r\nworld\r\n")
         .await
         .unwrap();
+    let mut response = [0; 5];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(b"+OK\r\n", &response);
 
-    let mut response = [0; 28];
+    handle.shutdown().await;
+
+    // The open connection was closed by the server...
+    assert_eq!(0, stream.read(&mut response).await.unwrap());
+
+    // ...and no new connections are accepted.
+    assert!(TcpStream::connect(addr).await.is_err());
+}
+
+/// A subscriber that stops reading while messages pile up still gets its
+/// commands handled, and does not hold up server shutdown.
+#[tokio::test]
+async fn stalled_subscriber_does_not_block_shutdown() {
+    let handle = server::spawn("127.0.0.1:0", server::Config::new())
+        .await
+        .unwrap();
+
+    let mut subscriber = TcpStream::connect(handle.addr()).await.unwrap();
+    subscriber
+        .write_all(b"*2\r\n$9\r\nSUBSCRIBE\r\n$5\r\nhello\r\n")
+        .await
+        .unwrap();
+    let mut response = [0; 34];
+    subscriber.read_exact(&mut response).await.unwrap();
+
+    // Publish far more than the socket buffers hold, without ever reading
+    // on the subscriber side.
+    let message = Bytes::from(vec![b'x'; 64 * 1024]);
+    for _ in 0..256 {
+        handle.db().publish("hello", message.clone());
+    }
+    time::sleep(Duration::from_millis(100)).await;
+
+    // The acknowledgement is queued behind the backlog instead of waiting
+    // for the subscriber to catch up.
+    subscriber
+        .write_all(b"*2\r\n$9\r\nSUBSCRIBE\r\n$5\r\nworld\r\n")
+        .await
+        .unwrap();
+    time::sleep(Duration::from_millis(100)).await;
+
+    time::timeout(Duration::from_secs(5), handle.shutdown())
+        .await
+        .expect("shutdown was blocked by a stalled subscriber");
+}
+
+/// With the default configuration, a subscriber that stops reading is
+/// disconnected once its queued messages exceed the default output limit,
+/// instead of growing the server's memory without bound.
+#[tokio::test]
+async fn stalled_subscriber_is_disconnected_by_default() {
+    let handle = server::spawn("127.0.0.1:0", server::Config::new())
+        .await
+        .unwrap();
+
+    let mut subscriber = TcpStream::connect(handle.addr()).await.unwrap();
+    subscriber
+        .write_all(b"*2\r\n$9\r\nSUBSCRIBE\r\n$5\r\nhello\r\n")
+        .await
+        .unwrap();
+    let mut response = [0; 34];
+    subscriber.read_exact(&mut response).await.unwrap();
+
+    // Never read on the subscriber side. 256MB is far more than the socket
+    // buffers and the default limit of 32MB hold together.
+    let message = Bytes::from(vec![b'x'; 1024 * 1024]);
+    let mut subscribers = 1;
+    for _ in 0..256 {
+        subscribers = handle.db().publish("hello", message.clone());
+        if subscribers == 0 {
+            break;
+        }
+        time::sleep(Duration::from_millis(1)).await;
+    }
+
+    assert_eq!(0, subscribers);
+    drop(subscriber);
+}
+
+/// The `Db` handed out by `Handle::db` is the one network clients read and
+/// write.
+#[tokio::test]
+async fn spawned_server_exposes_db() {
+    let handle = server::spawn("127.0.0.1:0", server::Config::new())
+        .await
+        .unwrap();
+    let db = handle.db();
+    db.set("hello".to_string(), "world".into(), None, false);
+
+    let mut stream = TcpStream::connect(handle.addr()).await.unwrap();
+    stream
+        .write_all(b"*2\r\n$3\r\nGET\r\n$5\r\nhello\r\n")
+        .await
+        .unwrap();
+    let mut response = [0; 11];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(b"$5\r\nworld\r\n", &response);
 
+    stream
+        .write_all(b"*3\r\n$3\r\nSET\r\n$3\r\nfoo\r\n$3\r\nbar\r\n")
+        .await
+        .unwrap();
+    let mut response = [0; 5];
     stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(b"+OK\r\n", &response);
 
-    assert_eq!(b"-ERR unknown command \'foo\'\r\n", &response);
+    assert_eq!(Some("bar".into()), db.get("foo").unwrap());
+    assert_eq!(2, db.len());
+}
+
+/// Keys bulk-loaded with `Db::set_many` are visible to clients and expire
+/// as requested.
+#[tokio::test]
+async fn set_many_bulk_loads_keys() {
+    let handle = server::spawn("127.0.0.1:0", server::Config::new())
+        .await
+        .unwrap();
+    let db = handle.db();
+
+    db.set_many((0..1000).map(|i| {
+        let expire = if i % 2 == 0 {
+            Some(Duration::from_millis(20))
+        } else {
+            None
+        };
+        (format!("key:{}", i), Bytes::from(i.to_string()), expire)
+    }));
+    assert_eq!(1000, db.len());
+
+    time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(500, db.len());
+    assert_eq!(Some("1".into()), db.get("key:1").unwrap());
+}
+
+/// `Db::watch_key` pushes every change to a key, whether it comes from a
+/// client command, the embedding application or expiration.
+#[tokio::test]
+async fn watch_key_reports_changes() {
+    let handle = server::spawn("127.0.0.1:0", server::Config::new())
+        .await
+        .unwrap();
+    let db = handle.db();
+
+    let mut watcher = db.watch_key("hello");
+    assert_eq!(None, *watcher.borrow_and_update());
+
+    let mut stream = TcpStream::connect(handle.addr()).await.unwrap();
+    assert_reply(&mut stream, b"SET hello world\r\n", b"+OK\r\n").await;
+    watcher.changed().await.unwrap();
+    assert_eq!(Some("world".into()), *watcher.borrow_and_update());
+
+    // Writing the same value again is not a change
+    assert_reply(&mut stream, b"SET hello world\r\n", b"+OK\r\n").await;
+    assert!(!watcher.has_changed().unwrap());
+
+    assert_reply(&mut stream, b"DEL hello\r\n", b":1\r\n").await;
+    watcher.changed().await.unwrap();
+    assert_eq!(None, *watcher.borrow_and_update());
+
+    db.set(
+        "hello".to_string(),
+        "again".into(),
+        Some(Duration::from_millis(20)),
+        false,
+    );
+    watcher.changed().await.unwrap();
+    assert_eq!(Some("again".into()), *watcher.borrow_and_update());
+
+    // The background task notifies the watcher when the key expires
+    time::timeout(Duration::from_secs(1), watcher.changed())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(None, *watcher.borrow_and_update());
+}
+
+#[derive(Debug, PartialEq)]
+enum KeyEvent {
+    Set(String, Bytes, bool),
+    Modify(String),
+    Delete(String),
+    Expire(String),
+}
+
+/// Records every keyspace change reported to it.
+#[derive(Clone, Default)]
+struct RecordingObserver {
+    events: Arc<Mutex<Vec<KeyEvent>>>,
+}
+
+impl DbObserver for RecordingObserver {
+    fn on_set(&self, key: String, value: Bytes, ttl: Option<Duration>) {
+        let event = KeyEvent::Set(key, value, ttl.is_some());
+        self.events.lock().unwrap().push(event);
+    }
+
+    fn on_modify(&self, key: String) {
+        self.events.lock().unwrap().push(KeyEvent::Modify(key));
+    }
+
+    fn on_delete(&self, key: String) {
+        self.events.lock().unwrap().push(KeyEvent::Delete(key));
+    }
+
+    fn on_expire(&self, key: String) {
+        self.events.lock().unwrap().push(KeyEvent::Expire(key));
+    }
+}
+
+/// An installed `DbObserver` is told about sets, modifications of other value
+/// types, deletes and expirations in the order they happen.
+#[tokio::test]
+async fn observer_reports_key_changes() {
+    let observer = RecordingObserver::default();
+    let config = server::Config::new().observer(observer.clone());
+    let handle = server::spawn("127.0.0.1:0", config).await.unwrap();
+    let mut stream = TcpStream::connect(handle.addr()).await.unwrap();
+
+    assert_reply(&mut stream, b"SET hello world\r\n", b"+OK\r\n").await;
+    assert_reply(&mut stream, b"DEL hello\r\n", b":1\r\n").await;
+
+    // Deleting a missing key is not a change
+    assert_reply(&mut stream, b"DEL hello\r\n", b":0\r\n").await;
+
+    assert_reply(&mut stream, b"LPUSH list a\r\n", b":1\r\n").await;
+    assert_reply(&mut stream, b"HSET hash field value\r\n", b":1\r\n").await;
+    assert_reply(&mut stream, b"SADD set member\r\n", b":1\r\n").await;
+
+    // Adding an existing member does not change the set
+    assert_reply(&mut stream, b"SADD set member\r\n", b":0\r\n").await;
+    assert_reply(&mut stream, b"SET brief 1 PX 20\r\n", b"+OK\r\n").await;
+
+    // The background task removes the key once it expires
+    time::timeout(Duration::from_secs(1), async {
+        while observer.events.lock().unwrap().len() < 7 {
+            time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(
+        *observer.events.lock().unwrap(),
+        vec![
+            KeyEvent::Set("hello".into(), "world".into(), false),
+            KeyEvent::Delete("hello".into()),
+            KeyEvent::Modify("list".into()),
+            KeyEvent::Modify("hash".into()),
+            KeyEvent::Modify("set".into()),
+            KeyEvent::Set("brief".into(), "1".into(), true),
+            KeyEvent::Expire("brief".into()),
+        ]
+    );
+}
+
+/// INFO keyspace counts keys expired by the background task separately from
+/// keys found expired by a write, and buckets the TTLs that were set.
+#[tokio::test]
+async fn info_keyspace_reports_expirations() {
+    let clock = ManualClock::new();
+    let config = server::Config::new().clock(clock.clone());
+    let handle = server::spawn("127.0.0.1:0", config).await.unwrap();
+    let mut client = Client::connect(handle.addr()).await.unwrap();
+
+    client
+        .set_expires("short", "1".into(), Duration::from_millis(100))
+        .await
+        .unwrap();
+    client
+        .set_expires("minute", "1".into(), Duration::from_secs(30))
+        .await
+        .unwrap();
+    client
+        .set_expires("days", "1".into(), Duration::from_secs(200_000))
+        .await
+        .unwrap();
+    client.set("plain", "1".into()).await.unwrap();
+
+    let info = client.info(Some("keyspace")).await.unwrap();
+    assert!(info.starts_with("# Keyspace\r\n"), "{}", info);
+    assert_eq!("keys=4,expires=3", info_field(&info, "db0"));
+    assert_eq!("1", info_field(&info, "ttl_lt_1s"));
+    assert_eq!("1", info_field(&info, "ttl_lt_1m"));
+    assert_eq!("0", info_field(&info, "ttl_lt_1h"));
+    assert_eq!("0", info_field(&info, "ttl_lt_1d"));
+    assert_eq!("1", info_field(&info, "ttl_ge_1d"));
+
+    clock.advance(Duration::from_secs(31));
+
+    // Pushing onto the expired string removes it before creating the list
+    client.rpush("minute", &["x".into()]).await.unwrap();
+
+    // The background task wakes up on its own schedule to purge `short`
+    let info = time::timeout(Duration::from_secs(1), async {
+        loop {
+            let info = client.info(Some("keyspace")).await.unwrap();
+            if info_field(&info, "expired_keys_active") == "1" {
+                return info;
+            }
+            time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+
+    assert_eq!("1", info_field(&info, "expired_keys_lazy"));
+    assert_eq!("keys=3,expires=1", info_field(&info, "db0"));
+}
+
+/// Returns the value of `name` in an INFO response.
+fn info_field<'a>(info: &'a str, name: &str) -> &'a str {
+    info.lines()
+        .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+        .unwrap()
+}
+
+/// Connections accepted on any of several listeners share the same database.
+#[tokio::test]
+async fn multiple_listeners_share_database() {
+    let first = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let second = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let first_addr = first.local_addr().unwrap();
+    let second_addr = second.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        let config = server::Config::new();
+        server::run_with_listeners(vec![first, second], config, tokio::signal::ctrl_c()).await
+    });
+
+    let mut stream = TcpStream::connect(first_addr).await.unwrap();
+    assert_reply(&mut stream, b"SET hello world\r\n", b"+OK\r\n").await;
+
+    let mut stream = TcpStream::connect(second_addr).await.unwrap();
+    assert_reply(&mut stream, b"GET hello\r\n", b"$5\r\nworld\r\n").await;
+}
+
+/// `server::Builder` spawns a server on all of its listeners, and clients
+/// built with `Client::builder()` reach it on either address.
+#[tokio::test]
+async fn builder_spawns_on_all_listeners() {
+    let first = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let second = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let second_addr = second.local_addr().unwrap();
+
+    let handle = server::Builder::new()
+        .config(server::Config::new())
+        .listener(first)
+        .listener(second)
+        .spawn()
+        .unwrap();
+
+    let mut client = Client::builder().connect(handle.addr()).await.unwrap();
+    client.set("hello", "world".into()).await.unwrap();
+
+    let mut client = Client::builder().connect(second_addr).await.unwrap();
+    assert_eq!(Some("world".into()), client.get("hello").await.unwrap());
+
+    handle.shutdown().await;
+
+    assert!(server::Builder::new().spawn().is_err());
+}
+
+#[test]
+#[should_panic(expected = "built-in command")]
+fn custom_command_cannot_replace_builtin() {
+    let _ = server::Config::new().register_command("get", |_: Vec<Bytes>| Frame::Null);
 }
 
 // In this case we test that server Responds with an Error message if a client
@@ -397,6 +1536,325 @@ async fn send_error_get_set_after_subscribe() {
     assert_eq!(b"-ERR unknown command \'get\'\r\n", &response);
 }
 
+/// With AOF enabled, write commands (including transactions) are appended to
+/// the file and replayed when the server starts again. A command cut off at
+/// the end of the file is dropped and the file is truncated.
+#[tokio::test]
+async fn append_only_file_is_replayed() {
+    let path = std::env::temp_dir().join(format!("mini-redis-aof-{}", std::process::id()));
+    let config = |policy| {
+        server::Config::new()
+            .append_only(&path)
+            .append_fsync(policy)
+    };
+
+    let handle = server::spawn("127.0.0.1:0", config(server::AppendFsync::Always))
+        .await
+        .unwrap();
+    let mut stream = TcpStream::connect(handle.addr()).await.unwrap();
+
+    assert_reply(&mut stream, b"SET hello world\r\n", b"+OK\r\n").await;
+    assert_reply(&mut stream, b"INCR counter\r\n", b":1\r\n").await;
+    assert_reply(&mut stream, b"GET hello\r\n", b"$5\r\nworld\r\n").await;
+    assert_reply(&mut stream, b"MULTI\r\n", b"+OK\r\n").await;
+    assert_reply(&mut stream, b"INCR counter\r\n", b"+QUEUED\r\n").await;
+    assert_reply(&mut stream, b"RPUSH list a b\r\n", b"+QUEUED\r\n").await;
+    assert_reply(&mut stream, b"EXEC\r\n", b"*2\r\n:2\r\n:2\r\n").await;
+
+    let mut client = Client::connect(handle.addr()).await.unwrap();
+    let info = client.info(Some("persistence")).await.unwrap();
+    assert!(info.contains("aof_enabled:1\r\n"), "{}", info);
+    assert!(info.contains("aof_fsync:always\r\n"), "{}", info);
+    assert!(info.contains("aof_pending_bytes:0\r\n"), "{}", info);
+    assert!(!info.contains("aof_fsyncs:0\r\n"), "{}", info);
+    assert!(info.contains("aof_last_write_status:ok\r\n"), "{}", info);
+
+    drop(client);
+    drop(stream);
+    handle.shutdown().await;
+
+    // Half a command at the end of the file, as if the server crashed while
+    // writing it.
+    let complete = std::fs::metadata(&path).unwrap().len();
+    let mut file = std::fs::OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .unwrap();
+    std::io::Write::write_all(&mut file, b"*3\r\n$3\r\nSET\r\n$5\r\nhel").unwrap();
+    drop(file);
+
+    let handle = server::spawn("127.0.0.1:0", config(server::AppendFsync::EverySec))
+        .await
+        .unwrap();
+    let mut stream = TcpStream::connect(handle.addr()).await.unwrap();
+
+    assert_reply(&mut stream, b"GET hello\r\n", b"$5\r\nworld\r\n").await;
+    assert_reply(&mut stream, b"GET counter\r\n", b"$1\r\n2\r\n").await;
+    assert_reply(&mut stream, b"LLEN list\r\n", b":2\r\n").await;
+    assert_eq!(complete, std::fs::metadata(&path).unwrap().len());
+
+    drop(stream);
+    handle.shutdown().await;
+    std::fs::remove_file(&path).unwrap();
+}
+
+/// The AOF records what commands did rather than the commands themselves:
+/// `BLPOP` is replayed as the `LPOP` it performed, `XADD *` keeps its
+/// generated ID, and relative expiry times are stored as absolute ones so
+/// keys and hash fields expire at their original time after a restart.
+#[tokio::test]
+async fn append_only_file_records_effects() {
+    let path = std::env::temp_dir().join(format!("mini-redis-aof-effects-{}", std::process::id()));
+    let config = || {
+        server::Config::new()
+            .append_only(&path)
+            .append_fsync(server::AppendFsync::Always)
+    };
+
+    let handle = server::spawn("127.0.0.1:0", config()).await.unwrap();
+    let mut client = Client::connect(handle.addr()).await.unwrap();
+
+    client
+        .rpush("list", &["a".into(), "b".into()])
+        .await
+        .unwrap();
+    let popped = client.blpop(&["list".to_string()], None).await.unwrap();
+    assert_eq!(Some(("list".to_string(), Bytes::from("a"))), popped);
+
+    let id = client
+        .xadd("stream", None, &[("field".into(), "value".into())])
+        .await
+        .unwrap();
+
+    client
+        .set_expires("temp", "value".into(), Duration::from_millis(500))
+        .await
+        .unwrap();
+    client
+        .hset("hash", &[("f", "1".into()), ("g", "2".into())])
+        .await
+        .unwrap();
+    client
+        .hexpire("hash", Duration::from_secs(1), &["f"])
+        .await
+        .unwrap();
+
+    drop(client);
+    handle.shutdown().await;
+
+    // Longer than both expiry times, which must not start again from the
+    // time the file is loaded.
+    time::sleep(Duration::from_millis(1200)).await;
+
+    let handle = server::spawn("127.0.0.1:0", config()).await.unwrap();
+    let mut client = Client::connect(handle.addr()).await.unwrap();
+
+    assert_eq!(Some(Bytes::from("b")), client.lpop("list").await.unwrap());
+    assert_eq!(None, client.lpop("list").await.unwrap());
+
+    let entries = client.xrange("stream", None, None, None).await.unwrap();
+    assert_eq!(
+        vec![id],
+        entries.iter().map(|entry| entry.id).collect::<Vec<_>>()
+    );
+
+    assert_eq!(None, client.get("temp").await.unwrap());
+    assert_eq!(None, client.hget("hash", "f").await.unwrap());
+    assert_eq!(
+        Some(Bytes::from("2")),
+        client.hget("hash", "g").await.unwrap()
+    );
+
+    drop(client);
+    handle.shutdown().await;
+    std::fs::remove_file(&path).unwrap();
+}
+
+/// A replica copies the primary's data, rejects writes, and after losing the
+/// connection resumes from the primary's backlog instead of taking a new
+/// snapshot.
+#[tokio::test]
+async fn replica_resumes_from_backlog() {
+    let primary = server::spawn("127.0.0.1:0", server::Config::new())
+        .await
+        .unwrap();
+    let mut client = Client::connect(primary.addr()).await.unwrap();
+    client.set("before", "1".into()).await.unwrap();
+
+    // The replica reaches the primary through a proxy so the test can cut the
+    // replication connection.
+    let (proxy_addr, cut) = proxy(primary.addr()).await;
+    let replica = server::spawn(
+        "127.0.0.1:0",
+        server::Config::new().replica_of("127.0.0.1", proxy_addr.port()),
+    )
+    .await
+    .unwrap();
+    let mut replica_client = Client::connect(replica.addr()).await.unwrap();
+
+    client.set("during", "2".into()).await.unwrap();
+    eventually(&mut replica_client, "before", "1").await;
+    eventually(&mut replica_client, "during", "2").await;
+
+    let err = replica_client.set("foo", "bar".into()).await.unwrap_err();
+    assert!(err.to_string().starts_with("READONLY"), "{}", err);
+
+    let info = replica_client.info(Some("replication")).await.unwrap();
+    assert!(info.contains("role:slave\r\n"), "{}", info);
+    assert!(info.contains("master_link_status:up\r\n"), "{}", info);
+
+    // Writes made while the replica is disconnected are sent from the backlog.
+    cut.send(()).await.unwrap();
+    client.set("after", "3".into()).await.unwrap();
+    eventually(&mut replica_client, "after", "3").await;
+
+    let info = client.info(None).await.unwrap();
+    assert!(info.contains("sync_full:1\r\n"), "{}", info);
+    assert!(info.contains("sync_partial_ok:1\r\n"), "{}", info);
+    assert!(info.contains("repl_backlog_active:1\r\n"), "{}", info);
+
+    // Once promoted, the replica accepts writes and keeps the copied data.
+    replica_client.replicaof_no_one().await.unwrap();
+    replica_client.set("foo", "bar".into()).await.unwrap();
+    eventually(&mut replica_client, "before", "1").await;
+
+    let info = replica_client.info(Some("replication")).await.unwrap();
+    assert!(info.contains("role:master\r\n"), "{}", info);
+}
+
+/// The primary sends replicas what its commands did: a `BLPOP` arrives as the
+/// `LPOP` it performed and `XADD *` with the generated ID, and the replication
+/// connection stays up while the replica acknowledges its offset.
+#[tokio::test]
+async fn replica_receives_effects() {
+    let primary = server::spawn("127.0.0.1:0", server::Config::new())
+        .await
+        .unwrap();
+    let replica = server::spawn(
+        "127.0.0.1:0",
+        server::Config::new().replica_of("127.0.0.1", primary.addr().port()),
+    )
+    .await
+    .unwrap();
+    let mut client = Client::connect(primary.addr()).await.unwrap();
+    let mut replica_client = Client::connect(replica.addr()).await.unwrap();
+
+    client.set("synced", "1".into()).await.unwrap();
+    eventually(&mut replica_client, "synced", "1").await;
+
+    client
+        .rpush("list", &["a".into(), "b".into()])
+        .await
+        .unwrap();
+    client.blpop(&["list".to_string()], None).await.unwrap();
+    let id = client
+        .xadd("stream", None, &[("field".into(), "value".into())])
+        .await
+        .unwrap();
+    client.set("done", "1".into()).await.unwrap();
+    eventually(&mut replica_client, "done", "1").await;
+
+    assert_eq!(1, replica_client.llen("list").await.unwrap());
+    let entries = replica_client
+        .xrange("stream", None, None, None)
+        .await
+        .unwrap();
+    assert_eq!(
+        vec![id],
+        entries.iter().map(|entry| entry.id).collect::<Vec<_>>()
+    );
+
+    // Outlive a periodic acknowledgement.
+    time::sleep(Duration::from_millis(1100)).await;
+    let info = replica_client.info(Some("replication")).await.unwrap();
+    assert!(info.contains("master_link_status:up\r\n"), "{}", info);
+    let info = client.info(Some("stats")).await.unwrap();
+    assert!(info.contains("sync_full:1\r\n"), "{}", info);
+}
+
+/// `WAIT` returns once enough replicas have acknowledged the writes made
+/// before it, and reports the replicas that did when it times out.
+#[tokio::test]
+async fn wait_counts_acknowledging_replicas() {
+    let primary = server::spawn("127.0.0.1:0", server::Config::new())
+        .await
+        .unwrap();
+    let _replica = server::spawn(
+        "127.0.0.1:0",
+        server::Config::new().replica_of("127.0.0.1", primary.addr().port()),
+    )
+    .await
+    .unwrap();
+    let mut client = Client::connect(primary.addr()).await.unwrap();
+
+    client.set("hello", "world".into()).await.unwrap();
+    let acked = client.wait(1, Some(Duration::from_secs(5))).await.unwrap();
+    assert_eq!(1, acked);
+
+    client.set("hello", "again".into()).await.unwrap();
+    let acked = client
+        .wait(2, Some(Duration::from_millis(200)))
+        .await
+        .unwrap();
+    assert_eq!(1, acked);
+}
+
+/// Forwards connections to `target`. Sending on the returned channel closes
+/// the connections forwarded so far; new connections are still accepted.
+async fn proxy(target: SocketAddr) -> (SocketAddr, tokio::sync::mpsc::Sender<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (cut_tx, mut cut_rx) = tokio::sync::mpsc::channel(1);
+
+    tokio::spawn(async move {
+        let mut forwarded = vec![];
+
+        loop {
+            tokio::select! {
+                res = listener.accept() => {
+                    let (mut inbound, _) = res.unwrap();
+                    forwarded.push(tokio::spawn(async move {
+                        let mut outbound = TcpStream::connect(target).await.unwrap();
+                        let _ = tokio::io::copy_bidirectional(&mut inbound, &mut outbound).await;
+                    }));
+                }
+                Some(()) = cut_rx.recv() => {
+                    for task in forwarded.drain(..) {
+                        task.abort();
+                    }
+                }
+            }
+        }
+    });
+
+    (addr, cut_tx)
+}
+
+/// Waits until `key` has `value` on the server `client` is connected to.
+async fn eventually(client: &mut Client, key: &str, value: &str) {
+    for _ in 0..500 {
+        if client.get(key).await.unwrap().as_deref() == Some(value.as_bytes()) {
+            return;
+        }
+
+        time::sleep(Duration::from_millis(10)).await;
+    }
+
+    panic!("`{}` never became `{}`", key, value);
+}
+
+/// Sends `request` and asserts that the server replies with exactly `expected`.
+async fn assert_reply(stream: &mut TcpStream, request: &[u8], expected: &[u8]) {
+    stream.write_all(request).await.unwrap();
+
+    let mut response = vec![0; expected.len()];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(expected),
+        String::from_utf8_lossy(&response)
+    );
+}
+
 async fn start_server() -> SocketAddr {
     let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
     let addr = listener.local_addr().unwrap();